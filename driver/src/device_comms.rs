@@ -1,4 +1,10 @@
-use core::{ffi::c_void, mem, ptr::null_mut, slice::from_raw_parts, sync::atomic::Ordering};
+use core::{
+    ffi::c_void,
+    mem,
+    ptr::null_mut,
+    slice::from_raw_parts,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use crate::{
     DRIVER_MESSAGES, DRIVER_MESSAGES_CACHE,
@@ -7,10 +13,10 @@ use crate::{
 };
 use alloc::{format, string::String};
 use shared_no_std::{
-    constants::SanctumVersion,
+    constants::{SanctumVersion, VERSION_DRIVER},
     driver_ipc::{HandleObtained, ImageLoadQueues, ProcessStarted, ProcessTerminated},
     ghost_hunting::{DLLMessage, Syscall},
-    ioctl::{DriverMessages, SancIoctlPing},
+    ioctl::{DriverMessages, DriverQueueStats, SancIoctlPing},
 };
 use wdk::println;
 use wdk_mutex::{
@@ -23,6 +29,11 @@ use wdk_sys::{
     ntddk::{KeGetCurrentIrql, RtlCopyMemoryNonTemporal},
 };
 
+/// The number of events queued for usermode since the driver started, for usermode to tell whether it is keeping up
+static EVENTS_QUEUED: AtomicU64 = AtomicU64::new(0);
+/// The number of events dropped rather than queued for usermode since the driver started
+static EVENTS_DROPPED: AtomicU64 = AtomicU64::new(0);
+
 /// DriverMessagesWithMutex object which contains a spinlock to allow for mutable access to the queue.
 /// This object should be used to safely manage access to the inner DriverMessages which contains
 /// the actual data. The DriverMessagesWithMutex contains metadata + the DriverMessages.
@@ -56,6 +67,7 @@ impl DriverMessagesWithMutex {
         let irql = unsafe { KeGetCurrentIrql() };
         if irql > APC_LEVEL as u8 {
             println!("[sanctum] [-] IRQL is above APC_LEVEL: {}", irql);
+            EVENTS_DROPPED.fetch_add(1, Ordering::Relaxed);
             return;
         }
 
@@ -64,6 +76,7 @@ impl DriverMessagesWithMutex {
             lock.is_empty = false;
             lock.messages.push(data);
         }
+        EVENTS_QUEUED.fetch_add(1, Ordering::Relaxed);
     }
 
     /// Adds serialised data to the message queue.
@@ -74,6 +87,7 @@ impl DriverMessagesWithMutex {
         let irql = unsafe { KeGetCurrentIrql() };
         if irql > APC_LEVEL as u8 {
             println!("[sanctum] [-] IRQL is above APC_LEVEL: {}", irql);
+            EVENTS_DROPPED.fetch_add(1, Ordering::Relaxed);
             return;
         }
 
@@ -82,6 +96,7 @@ impl DriverMessagesWithMutex {
             lock.is_empty = false;
             lock.process_creations.push(data);
         }
+        EVENTS_QUEUED.fetch_add(1, Ordering::Relaxed);
     }
 
     /// Adds a terminated process to the queue.
@@ -92,6 +107,7 @@ impl DriverMessagesWithMutex {
         let irql = unsafe { KeGetCurrentIrql() };
         if irql > APC_LEVEL as u8 {
             println!("[sanctum] [-] IRQL is above APC_LEVEL: {}", irql);
+            EVENTS_DROPPED.fetch_add(1, Ordering::Relaxed);
             return;
         }
 
@@ -100,6 +116,7 @@ impl DriverMessagesWithMutex {
             lock.is_empty = false;
             lock.process_terminations.push(data);
         }
+        EVENTS_QUEUED.fetch_add(1, Ordering::Relaxed);
     }
 
     /// Add new granted handle information to the messages object
@@ -107,6 +124,7 @@ impl DriverMessagesWithMutex {
         let irql = unsafe { KeGetCurrentIrql() };
         if irql > APC_LEVEL as u8 {
             println!("[sanctum] [-] IRQL is above APC_LEVEL: {}", irql);
+            EVENTS_DROPPED.fetch_add(1, Ordering::Relaxed);
             return;
         }
 
@@ -115,6 +133,7 @@ impl DriverMessagesWithMutex {
             lock.is_empty = false;
             lock.handles.push(data);
         }
+        EVENTS_QUEUED.fetch_add(1, Ordering::Relaxed);
     }
 
     /// Extract all data out of the queue if there is data.
@@ -148,6 +167,16 @@ impl DriverMessagesWithMutex {
         }
     }
 
+    /// The number of events in the queue
+    fn len(&mut self) -> u64 {
+        let lock = self.data.lock().unwrap();
+
+        (lock.messages.len()
+            + lock.process_creations.len()
+            + lock.process_terminations.len()
+            + lock.handles.len()) as u64
+    }
+
     fn add_existing_queue(&mut self, q: &mut DriverMessages) -> usize {
         let mut lock = self.data.lock().unwrap();
 
@@ -426,6 +455,48 @@ pub fn ioctl_handler_send_kernel_msgs_to_userland(pirp: PIRP) -> Result<(), Driv
     Ok(())
 }
 
+/// Sends the state of the queue of events for usermode back to userland, so that usermode can tell whether it is
+/// keeping up with the events the driver raises. The depth of the queue includes events drained into the cache
+/// which usermode has yet to collect.
+pub fn ioctl_handler_get_queue_stats(pirp: PIRP) -> Result<(), DriverError> {
+    unsafe {
+        if (*pirp).AssociatedIrp.SystemBuffer.is_null() {
+            println!("[sanctum] [-] SystemBuffer is a null pointer in ioctl_handler_get_queue_stats.");
+            return Err(DriverError::NullPtr);
+        }
+    }
+
+    let mut queue_depth = 0;
+    for queue in [&DRIVER_MESSAGES, &DRIVER_MESSAGES_CACHE] {
+        let ptr = queue.load(Ordering::SeqCst);
+        if ptr.is_null() {
+            println!("[sanctum] [-] Driver messages is null");
+            return Err(DriverError::DriverMessagePtrNull);
+        }
+
+        queue_depth += unsafe { &mut *ptr }.len();
+    }
+
+    let stats = DriverQueueStats {
+        events_queued: EVENTS_QUEUED.load(Ordering::Relaxed),
+        events_dropped: EVENTS_DROPPED.load(Ordering::Relaxed),
+        queue_depth,
+    };
+
+    unsafe { (*pirp).IoStatus.Information = mem::size_of::<DriverQueueStats>() as u64 };
+
+    // copy the memory into the buffer
+    unsafe {
+        RtlCopyMemoryNonTemporal(
+            (*pirp).AssociatedIrp.SystemBuffer,
+            &stats as *const _ as *const _,
+            mem::size_of::<DriverQueueStats>() as u64,
+        )
+    };
+
+    Ok(())
+}
+
 pub fn ioctl_handler_ping_return_struct(
     p_stack_location: *mut _IO_STACK_LOCATION,
     pirp: PIRP,
@@ -622,6 +693,17 @@ pub fn ioctl_check_driver_compatibility(
     Ok(())
 }
 
+/// Sends the version of the driver back to usermode as a string, for the engine to report alongside its own version.
+pub fn ioctl_handler_get_version(
+    p_stack_location: *mut _IO_STACK_LOCATION,
+    pirp: PIRP,
+) -> Result<(), NTSTATUS> {
+    let ioctl_buffer = IoctlBuffer::new(p_stack_location, pirp);
+    ioctl_buffer.send_str(&format!("{}", VERSION_DRIVER))?;
+
+    Ok(())
+}
+
 // todo docs
 pub fn ioctl_dll_hook_syscall(
     p_stack_location: *mut _IO_STACK_LOCATION,
@@ -35,7 +35,8 @@ use core::{
 use device_comms::{
     DriverMessagesWithMutex, ioctl_check_driver_compatibility, ioctl_dll_hook_syscall,
     ioctl_get_image_load_len, ioctl_handler_get_image_loads, ioctl_handler_get_kernel_msg_len,
-    ioctl_handler_ping, ioctl_handler_ping_return_struct,
+    ioctl_handler_get_queue_stats, ioctl_handler_get_version, ioctl_handler_ping,
+    ioctl_handler_ping_return_struct,
     ioctl_handler_send_kernel_msgs_to_userland,
 };
 use ffi::IoGetCurrentIrpStackLocation;
@@ -44,7 +45,8 @@ use shared_no_std::{
     ioctl::{
         SANC_IOCTL_CHECK_COMPATIBILITY, SANC_IOCTL_DLL_SYSCALL, SANC_IOCTL_DRIVER_GET_IMAGE_LOADS,
         SANC_IOCTL_DRIVER_GET_IMAGE_LOADS_LEN, SANC_IOCTL_DRIVER_GET_MESSAGE_LEN,
-        SANC_IOCTL_DRIVER_GET_MESSAGES, SANC_IOCTL_PING, SANC_IOCTL_PING_WITH_STRUCT,
+        SANC_IOCTL_DRIVER_GET_MESSAGES, SANC_IOCTL_DRIVER_GET_QUEUE_STATS,
+        SANC_IOCTL_DRIVER_GET_VERSION, SANC_IOCTL_PING, SANC_IOCTL_PING_WITH_STRUCT,
         SANC_IOCTL_SEND_BASE_ADDRS,
     },
 };
@@ -470,6 +472,13 @@ unsafe extern "C" fn handle_ioctl(_device: *mut DEVICE_OBJECT, pirp: PIRP) -> NT
                 STATUS_SUCCESS
             }
         }
+        SANC_IOCTL_DRIVER_GET_QUEUE_STATS => {
+            if let Err(_) = ioctl_handler_get_queue_stats(pirp) {
+                STATUS_UNSUCCESSFUL
+            } else {
+                STATUS_SUCCESS
+            }
+        }
         SANC_IOCTL_DRIVER_GET_IMAGE_LOADS_LEN => {
             if let Err(_) = ioctl_get_image_load_len(pirp) {
                 STATUS_UNSUCCESSFUL
@@ -495,6 +504,14 @@ unsafe extern "C" fn handle_ioctl(_device: *mut DEVICE_OBJECT, pirp: PIRP) -> NT
             set_monitored_dll_fn_ptrs(p_stack_location, pirp);
             STATUS_SUCCESS
         }
+        SANC_IOCTL_DRIVER_GET_VERSION => {
+            if let Err(e) = ioctl_handler_get_version(p_stack_location, pirp) {
+                println!("[sanctum] [-] Error: {e}");
+                e
+            } else {
+                STATUS_SUCCESS
+            }
+        }
 
         _ => {
             println!(
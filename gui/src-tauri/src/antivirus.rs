@@ -1,33 +1,80 @@
 //! Antivirus.rs contains all functions associated with the antivirus UI in Tauri.
 //! This module will handle state, requests, async, and events.
+//!
+//! # Behaviour by scanner state
+//!
+//! The engine runs one scan at a time, so the commands behave as follows depending on whether a scan is running:
+//!
+//! - Starting a scan (the `scanner_start_*` commands, `scanner_scan_autoruns`, `scanner_scan_browser_extensions`,
+//!   `scanner_scan_drivers`, `scanner_scan_scheduled_tasks`, `scanner_scan_registry` and
+//!   `scanner_rescan_detections`) is refused with an error whilst Scanning. In any other state the scan starts,
+//!   replacing the last scan's results.
+//! - `scanner_stop_scan` cancels the running scan whilst Scanning, returning its ID once the engine has accepted the
+//!   cancellation. In any other state it returns an error, as there is no scan to stop. `scans_cancel` is the same,
+//!   but only cancels the scan with the given ID. `scanner_cancel_by_target` cancels the scans of the given path,
+//!   reporting how many there were, which is none rather than an error when nothing is scanning it.
+//! - `scanner_reset` is refused whilst Scanning. In any other state it clears the last scan's results, returning the
+//!   scanner to Inactive.
+//! - `scanner_check_page_state`, `scanner_get_scan_stats`, `scanner_current_event` and `scans_list` report on the
+//!   running scan whilst Scanning, and on the outcome of the last scan otherwise. They only fail when the engine
+//!   cannot be reached, and `scanner_check_page_state` then reports `Uninitialized`.
+//! - Estimates, detection details, triage, comparisons, exports, quarantining the detections of an earlier scan
+//!   (`quarantine_scan_detections`), inspecting the memory of a process (`scanner_inspect_process`), classifying a
+//!   file (`scanner_classify`), explaining why a file was not detected (`scanner_explain`) and listing the loaded
+//!   drivers (`drivers_list`) do not depend on the scanner state.
 
 use serde_json::{to_value, Value};
-use shared_std::file_scanner::{FileScannerState, ScanningLiveInfo};
-use std::path::PathBuf;
+use shared_no_std::ipc::CommandResponse;
+use shared_std::diagnostics::ProtectionScore;
+use shared_std::events::EngineEvent;
+use shared_std::file_scanner::{
+    AutorunScanResult, BrowserExtensionScanResult, CancelledByTarget, Classification, DetectionAction, DetectionDetails,
+    DetectionThresholds, DriverInfo, DriverScanResult, Explanation, FileScannerState, ProcessMemoryReport, RegistryRoot,
+    RegistryScanRequest, RegistryScanResult, RescanResult, ResultsPage, ResultsPageRequest, ScanEstimate, ScanOptions,
+    ScanRequest, ScanStarted, ScanStatus, ScanTarget, ScanTargetRequest, ScanningLiveInfo, ScheduledTaskScanResult,
+    CANCEL_REASON_TIME_LIMIT,
+};
+use shared_std::history::{
+    DetectionAnnotation, DetectionAnnotationRequest, ExportFormat, ProtectionStats, RecentDetection, ScanDiff,
+    ScanDiffRequest, ScanExportReport, ScanExportRequest, StatsPeriod, ThreatTimeline, TriageStatus,
+};
+use shared_std::quarantine::BatchQuarantineResult;
+use std::{
+    path::PathBuf,
+    time::{Duration, UNIX_EPOCH},
+};
 use tauri::Emitter;
 
 use crate::ipc::IpcClient;
 
+/// The state reported to the UI when the engine is not there to ask
+const UNINITIALIZED_STATE: &str = "Uninitialized";
+
+/// Gets the name of the scanner's state for the UI. If the engine cannot be reached, such as while it is still
+/// starting, `Uninitialized` is returned rather than guessing at a state.
 #[tauri::command]
 pub async fn scanner_check_page_state() -> Result<String, ()> {
     match IpcClient::send_ipc::<FileScannerState, Option<Value>>("scanner_check_page_state", None)
         .await
     {
         Ok(response) => {
-            return Ok(format!("{:?}", response));
+            return Ok(response.name().to_string());
         }
         Err(e) => {
             eprintln!("[-] Error with IPC: {e}");
-            return Ok("Inactive".to_string()); // todo proper error handling
+            return Ok(UNINITIALIZED_STATE.to_string());
         }
     };
 }
 
 /// Reports the scan statistics back to the UI
 #[tauri::command]
-pub async fn scanner_get_scan_stats() -> Result<String, ()> {
-    match IpcClient::send_ipc::<ScanningLiveInfo, Option<Value>>("scanner_get_scan_stats", None)
-        .await
+pub async fn scanner_get_scan_stats() -> Result<String, String> {
+    match IpcClient::send_ipc_binary::<ScanningLiveInfo, Option<Value>>(
+        "scanner_get_scan_stats",
+        None,
+    )
+    .await
     {
         Ok(response) => {
             let response = serde_json::to_string(&response).unwrap();
@@ -35,136 +82,748 @@ pub async fn scanner_get_scan_stats() -> Result<String, ()> {
         }
         Err(e) => {
             eprintln!("[-] Error with IPC: {e}");
-            return Ok("Inactive".to_string()); // todo proper error handling
+            return Err(format!("IPC error: {e}"));
         }
     };
 }
 
+/// Stops the running scan, whichever kind of scan it is, keeping the results found so far
+///
+/// # Returns
+///
+/// The ID of the scan which was stopped, or an error if no scan was running.
 #[tauri::command]
-pub async fn scanner_stop_scan() -> Result<(), ()> {
-    match IpcClient::send_ipc::<(), Option<Value>>("scanner_cancel_scan", None).await {
-        Ok(_) => (),
-        Err(e) => {
+pub async fn scanner_stop_scan() -> Result<u64, String> {
+    IpcClient::send_ipc::<Result<u64, String>, Option<Value>>("scanner_cancel_scan", None)
+        .await
+        .map_err(|e| {
             eprintln!("[-] Error with IPC for stop scan: {e}");
+            format!("IPC error: {e}")
+        })?
+}
+
+/// Lists the scans in progress with their progress as JSON, for the jobs panel
+#[tauri::command]
+pub async fn scans_list() -> Result<String, String> {
+    let scans = IpcClient::send_ipc::<Vec<ScanStatus>, Option<Value>>("scans_list", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&scans).unwrap())
+}
+
+/// Cancels the scan with the ID, failing if that scan is no longer running
+#[tauri::command]
+pub async fn scans_cancel(scan_id: u64) -> Result<(), String> {
+    IpcClient::send_ipc::<Result<(), String>, _>("scans_cancel", Some(to_value(scan_id).unwrap()))
+        .await
+        .map_err(|e| format!("IPC error: {e}"))?
+}
+
+/// Cancels every running scan of the path, for when the ID of the scan is not known, such as after the page was
+/// reloaded. Returns how many scans were cancelled, and their IDs, as JSON.
+#[tauri::command]
+pub async fn scanner_cancel_by_target(path: String) -> Result<String, String> {
+    let cancelled = IpcClient::send_ipc::<CancelledByTarget, _>(
+        "scanner_cancel_by_target",
+        Some(to_value(PathBuf::from(path)).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&cancelled).unwrap())
+}
+
+/// Clears the last scan result from the engine, returning it to an inactive state. The engine will refuse
+/// this whilst a scan is in progress.
+#[tauri::command]
+pub async fn scanner_reset() -> Result<String, String> {
+    match IpcClient::send_ipc::<CommandResponse, Option<Value>>("scanner_reset", None).await {
+        Ok(response) => Ok(serde_json::to_string(&response).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for scanner reset: {e}");
+            Err(format!("IPC error: {e}"))
         }
+    }
+}
+
+#[tauri::command]
+pub async fn scanner_start_folder_scan(
+    file_path: String,
+    max_duration_secs: Option<u64>,
+    action: Option<DetectionAction>,
+    thresholds: Option<DetectionThresholds>,
+    executables_only: Option<bool>,
+    include_remote_volumes: Option<bool>,
+    modified_since_secs: Option<u64>,
+    app_handle: tauri::AppHandle,
+) -> Result<u64, String> {
+    // the engine is told whether the path is a file or a folder, rather than it being guessed from the path
+    let path = PathBuf::from(file_path);
+    let target = match path.is_file() {
+        true => ScanTarget::File(path),
+        false => ScanTarget::Folder(vec![path]),
     };
 
-    Ok(())
+    let request = ScanTargetRequest {
+        target,
+        options: scan_options(
+            max_duration_secs,
+            action,
+            thresholds,
+            executables_only,
+            include_remote_volumes,
+            modified_since_secs,
+        ),
+    };
+    start_scan(request, app_handle).await
 }
 
+/// Starts a scan of the file or folder which first estimates how many files it will scan, emitting
+/// `folder_scan_estimating` as soon as the engine has accepted it and `folder_scan_started` with the estimate once
+/// scanning begins. The scan can be cancelled by its ID throughout, including whilst it is estimating.
 #[tauri::command]
-pub async fn scanner_start_folder_scan(
+pub async fn scanner_start_scan_with_estimate(
     file_path: String,
+    max_duration_secs: Option<u64>,
+    action: Option<DetectionAction>,
+    thresholds: Option<DetectionThresholds>,
+    executables_only: Option<bool>,
+    include_remote_volumes: Option<bool>,
+    modified_since_secs: Option<u64>,
     app_handle: tauri::AppHandle,
-) -> Result<String, ()> {
-    let path = to_value(vec![PathBuf::from(file_path)]).unwrap();
+) -> Result<u64, String> {
+    let request = ScanRequest {
+        targets: vec![PathBuf::from(file_path)],
+        options: scan_options(
+            max_duration_secs,
+            action,
+            thresholds,
+            executables_only,
+            include_remote_volumes,
+            modified_since_secs,
+        ),
+    };
 
+    let started = IpcClient::send_ipc::<Result<ScanStarted, String>, _>(
+        "scanner_start_scan_with_estimate",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    app_handle.emit("folder_scan_estimating", &started).unwrap();
+
+    let scan_id = started.scan_id;
     tokio::spawn(async move {
-        // The result is wrapped inside of an enum from the filescanner module, so we need to first match on that
-        // as DirectoryResult (since we are scanning a dir). The result should never be anything else for this scan
-        // so if it is something has gone wrong with the internal wiring.
+        watch_estimate(scan_id, &app_handle).await;
+        watch_scan(scan_id, app_handle).await;
+    });
 
-        match IpcClient::send_ipc::<FileScannerState, _>("scanner_start_folder_scan", Some(path))
+    Ok(scan_id)
+}
+
+/// Starts a full scan of the volume for the drive letter, skipping the folders reserved by the system at its root
+#[tauri::command]
+pub async fn scanner_start_volume_scan(
+    drive_letter: String,
+    max_duration_secs: Option<u64>,
+    action: Option<DetectionAction>,
+    thresholds: Option<DetectionThresholds>,
+    executables_only: Option<bool>,
+    include_remote_volumes: Option<bool>,
+    modified_since_secs: Option<u64>,
+    app_handle: tauri::AppHandle,
+) -> Result<u64, String> {
+    let request = ScanTargetRequest {
+        target: ScanTarget::Volume(drive_letter),
+        options: scan_options(
+            max_duration_secs,
+            action,
+            thresholds,
+            executables_only,
+            include_remote_volumes,
+            modified_since_secs,
+        ),
+    };
+    start_scan(request, app_handle).await
+}
+
+/// Starts a scan of the contents of a disk image (VHD, VHDX or ISO), which the engine mounts read only for the
+/// length of the scan
+#[tauri::command]
+pub async fn scanner_start_image_scan(
+    image_path: String,
+    max_duration_secs: Option<u64>,
+    action: Option<DetectionAction>,
+    thresholds: Option<DetectionThresholds>,
+    executables_only: Option<bool>,
+    include_remote_volumes: Option<bool>,
+    modified_since_secs: Option<u64>,
+    app_handle: tauri::AppHandle,
+) -> Result<u64, String> {
+    let request = ScanTargetRequest {
+        target: ScanTarget::Image(PathBuf::from(image_path)),
+        options: scan_options(
+            max_duration_secs,
+            action,
+            thresholds,
+            executables_only,
+            include_remote_volumes,
+            modified_since_secs,
+        ),
+    };
+    start_scan(request, app_handle).await
+}
+
+#[tauri::command]
+pub async fn scanner_start_quick_scan(
+    action: Option<DetectionAction>,
+    thresholds: Option<DetectionThresholds>,
+    executables_only: Option<bool>,
+    include_remote_volumes: Option<bool>,
+    modified_since_secs: Option<u64>,
+    app_handle: tauri::AppHandle,
+) -> Result<u64, String> {
+    let paths = IpcClient::send_ipc::<Vec<PathBuf>, Option<Value>>(
+        "settings_get_common_scan_areas",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
+
+    let request = ScanTargetRequest {
+        target: ScanTarget::Folder(paths),
+        options: scan_options(
+            None,
+            action,
+            thresholds,
+            executables_only,
+            include_remote_volumes,
+            modified_since_secs,
+        ),
+    };
+    start_scan(request, app_handle).await
+}
+
+/// Scans the binaries started from the autorun locations, such as the Run keys and scheduled tasks. Unlike the other
+/// scans, this waits for the scan to complete as only a few hundred binaries are scanned, returning the results as
+/// JSON.
+#[tauri::command]
+pub async fn scanner_scan_autoruns() -> Result<String, String> {
+    let result = IpcClient::send_ipc::<Result<AutorunScanResult, String>, Option<Value>>(
+        "scanner_scan_autoruns",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// Scans the extensions installed into each user's browsers, flagging those which request dangerous permissions. As
+/// with the autoruns, this waits for the scan to complete, returning the results as JSON.
+#[tauri::command]
+pub async fn scanner_scan_browser_extensions() -> Result<String, String> {
+    let result = IpcClient::send_ipc::<Result<BrowserExtensionScanResult, String>, Option<Value>>(
+        "scanner_scan_browser_extensions",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// Scans the files of the kernel drivers loaded on the machine, flagging those which are unsigned
+/// or whose signature is not trusted. As with the autoruns, this waits for the scan to complete,
+/// returning the results as JSON.
+#[tauri::command]
+pub async fn scanner_scan_drivers() -> Result<String, String> {
+    let result = IpcClient::send_ipc::<Result<DriverScanResult, String>, Option<Value>>(
+        "scanner_scan_drivers",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// Lists the kernel drivers loaded on the machine with the signature of each, without scanning
+/// them, returning the list as JSON
+#[tauri::command]
+pub async fn drivers_list() -> Result<String, String> {
+    let drivers =
+        IpcClient::send_ipc::<Result<Vec<DriverInfo>, String>, Option<Value>>("drivers_list", None)
             .await
-        {
-            Ok(response) => match response {
-                FileScannerState::Finished => {
-                    let scan_result = IpcClient::send_ipc::<ScanningLiveInfo, Option<Value>>(
-                        "scanner_get_scan_stats",
-                        None,
-                    )
-                    .await
-                    .unwrap();
+            .map_err(|e| format!("IPC error: {e}"))??;
 
-                    if scan_result.scan_results.is_empty() {
-                        app_handle
-                            .emit("folder_scan_no_results", "No malicious files found.")
-                            .unwrap();
-                    } else {
-                        app_handle
-                            .emit("folder_scan_malware_found", &scan_result)
-                            .unwrap();
-                    }
-                }
-                FileScannerState::FinishedWithError(v) => {
-                    app_handle.emit("folder_scan_error", &v).unwrap();
-                }
-                FileScannerState::Scanning => app_handle
-                    .emit(
-                        "folder_scan_error",
-                        "A scan is already in progress.".to_string(),
-                    )
-                    .unwrap(),
-                _ => (),
-            },
-            Err(e) => {
-                eprintln!("[-] Error with IPC: {e}");
-            }
-        };
-    });
+    Ok(serde_json::to_string(&drivers).unwrap())
+}
+
+/// Scans the files run by the tasks registered with the Task Scheduler, flagging tasks which look
+/// suspicious, such as one running an encoded PowerShell command or hidden from the Task
+/// Scheduler. As with the autoruns, this waits for the scan to complete, returning the results as
+/// JSON.
+#[tauri::command]
+pub async fn scanner_scan_scheduled_tasks() -> Result<String, String> {
+    let result = IpcClient::send_ipc::<Result<ScheduledTaskScanResult, String>, Option<Value>>(
+        "scanner_scan_scheduled_tasks",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
 
-    // // todo some kind of feedback like 1/1 file scanned; but then same for the mass scanner, be good to show x files scanned, and time taken so far. Then completed time and
-    // // total files after.
+/// Scans a subtree of the registry for files its values reference and for values whose contents
+/// match a registry signature. As with the autoruns, this waits for the scan to complete,
+/// returning the results as JSON.
+#[tauri::command]
+pub async fn scanner_scan_registry(root_key: RegistryRoot, subkey: String) -> Result<String, String> {
+    let request = RegistryScanRequest { root_key, subkey };
+
+    let result = IpcClient::send_ipc::<Result<RegistryScanResult, String>, _>(
+        "scanner_scan_registry",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// Inspects the memory of a running process for signs of injected code, returning what was found as JSON. Unlike a
+/// scan of the process, this looks at how its memory is laid out rather than matching its executable on disk.
+#[tauri::command]
+pub async fn scanner_inspect_process(pid: u32) -> Result<String, String> {
+    let report = IpcClient::send_ipc::<Result<ProcessMemoryReport, String>, _>(
+        "scanner_inspect_process",
+        Some(to_value(pid).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Runs the detection engines over a single file, returning their verdict as JSON. Unlike a scan, nothing is recorded
+/// in the history or done to the file.
+#[tauri::command]
+pub async fn scanner_classify(path: String) -> Result<String, String> {
+    let classification = IpcClient::send_ipc::<Result<Classification, String>, _>(
+        "scanner_classify",
+        Some(to_value(PathBuf::from(path)).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&classification).unwrap())
+}
+
+/// Explains why each detection engine did or did not detect a single file, returning the explanation as JSON. As with
+/// classifying, nothing is recorded in the history or done to the file.
+#[tauri::command]
+pub async fn scanner_explain(path: String) -> Result<String, String> {
+    let explanation = IpcClient::send_ipc::<Result<Explanation, String>, _>(
+        "scanner_explain",
+        Some(to_value(PathBuf::from(path)).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&explanation).unwrap())
+}
+
+/// Gets the event which started the running scan as JSON, or null if no scan is running, so that the page can show a
+/// scan which was started before it was loaded
+#[tauri::command]
+pub async fn scanner_current_event() -> Result<String, String> {
+    let event = IpcClient::send_ipc::<Option<EngineEvent>, Option<Value>>(
+        "scanner_current_event",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
 
-    // todo this shouldn't show in every case..?
-    Ok(format!("Scan in progress..."))
+    Ok(serde_json::to_string(&event).unwrap())
 }
 
+/// Rescans the files detected by an earlier scan with the current signatures and exclusions, such as after a false
+/// positive was excluded. This waits for the rescan to complete, returning which files are still detected as JSON.
 #[tauri::command]
-pub async fn scanner_start_quick_scan(app_handle: tauri::AppHandle) -> Result<String, ()> {
+pub async fn scanner_rescan_detections(scan_id: u64) -> Result<String, String> {
+    let result = IpcClient::send_ipc::<Result<RescanResult, String>, _>(
+        "scanner_rescan_detections",
+        Some(to_value(scan_id).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// Quarantines every detection of an earlier scan, other than files matching an exclusion and detections triaged as
+/// benign. This waits for every file to be quarantined, returning what became of each detection as JSON, so that
+/// files which are in use or denied can be shown.
+#[tauri::command]
+pub async fn quarantine_scan_detections(scan_id: u64) -> Result<String, String> {
+    let result = IpcClient::send_ipc::<Result<BatchQuarantineResult, String>, _>(
+        "quarantine_scan_detections",
+        Some(to_value(scan_id).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// Estimates how many files a scan of the path would cover, and their total size, without scanning them. This waits
+/// for the estimate to finish, or be cancelled with `scanner_cancel_estimate`, returning it as JSON.
+#[tauri::command]
+pub async fn scanner_estimate(file_path: String) -> Result<String, String> {
+    let targets = vec![PathBuf::from(file_path)];
+    let estimate = IpcClient::send_ipc::<Result<ScanEstimate, String>, _>(
+        "scanner_estimate",
+        Some(to_value(targets).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&estimate).unwrap())
+}
+
+#[tauri::command]
+pub async fn scanner_cancel_estimate() -> Result<(), String> {
+    IpcClient::send_ipc::<String, Option<Value>>("scanner_cancel_estimate", None)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            eprintln!("[-] Error with IPC cancelling scan estimate: {e}");
+            format!("IPC error: {e}")
+        })
+}
+
+/// Gets a page of the detections of a scan, in the order they were found, so that a scan with
+/// thousands of detections can be shown a page at a time. Returns the page as JSON.
+#[tauri::command]
+pub async fn scanner_results_page(scan_id: u64, offset: usize, limit: usize) -> Result<String, String> {
+    let request = ResultsPageRequest { scan_id, offset, limit };
+
+    let page = IpcClient::send_ipc::<Result<ResultsPage, String>, _>(
+        "scanner_results_page",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&page).unwrap())
+}
+
+/// Gets the bytes which matched the detection of the file in the most recent scan, as JSON containing a hex dump of
+/// each matched region
+#[tauri::command]
+pub async fn scanner_detection_details(file_path: String) -> Result<String, String> {
+    let details = IpcClient::send_ipc::<Result<DetectionDetails, String>, _>(
+        "scanner_detection_details",
+        Some(to_value(PathBuf::from(file_path)).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&details).unwrap())
+}
+
+/// Sets the triage status and note of a detection, identified by its scan and its index in the scan's detections,
+/// returning the stored annotation as JSON
+#[tauri::command]
+pub async fn detection_annotate(
+    scan_id: u64,
+    detection_id: usize,
+    status: TriageStatus,
+    note: String,
+) -> Result<String, String> {
+    let request = DetectionAnnotationRequest {
+        scan_id,
+        detection_id,
+        status,
+        note,
+    };
+
+    let annotation = IpcClient::send_ipc::<Result<DetectionAnnotation, String>, _>(
+        "detection_annotate",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&annotation).unwrap())
+}
+
+/// Gets the most recent detections from the scan history with their triage, newest first, as JSON. Where a status is
+/// given, only detections with that status are returned.
+#[tauri::command]
+pub async fn detection_recent(status: Option<TriageStatus>) -> Result<String, String> {
+    let detections = IpcClient::send_ipc::<Vec<RecentDetection>, _>(
+        "detection_recent",
+        Some(to_value(status).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&detections).unwrap())
+}
+
+/// Gets the totals of the scans over the period from the scan history, with the totals of each day of the period for
+/// charting, as JSON. Without a period, the last month is given.
+#[tauri::command]
+pub async fn protection_stats(period: Option<StatsPeriod>) -> Result<String, String> {
+    let stats = IpcClient::send_ipc::<ProtectionStats, _>(
+        "protection_stats",
+        Some(to_value(period).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&stats).unwrap())
+}
+
+/// Gets the protection score, a number from 0 to 100 summarising how well the machine is protected,
+/// with how each factor counted towards it and what would improve it, as JSON
+#[tauri::command]
+pub async fn protection_score() -> Result<String, String> {
+    let score = IpcClient::send_ipc::<ProtectionScore, Option<Value>>("protection_score", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&score).unwrap())
+}
+
+/// Gets when the threat with the hash was first and last seen across the scan history, the scans which saw it, and
+/// which of the files it was found in are still on disk, as JSON
+#[tauri::command]
+pub async fn threat_timeline(hash: String) -> Result<String, String> {
+    let timeline = IpcClient::send_ipc::<Result<ThreatTimeline, String>, _>(
+        "threat_timeline",
+        Some(to_value(hash).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&timeline).unwrap())
+}
+
+/// Compares the detections of two scans from the scan history, from scan A to the later scan B, returning which are
+/// new, resolved, persistent and not rescanned as JSON
+#[tauri::command]
+pub async fn scanner_diff(
+    scan_id_a: u64,
+    scan_id_b: u64,
+    match_hash: Option<bool>,
+) -> Result<String, String> {
+    let request = ScanDiffRequest {
+        scan_id_a,
+        scan_id_b,
+        match_hash: match_hash.unwrap_or(false),
+    };
+
+    let diff = IpcClient::send_ipc::<Result<ScanDiff, String>, _>(
+        "scanner_diff",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&diff).unwrap())
+}
+
+/// Exports a scan from the scan history to the file, compressed where a compressed format is chosen, returning a
+/// summary of the export as JSON
+#[tauri::command]
+pub async fn scanner_export_results(
+    scan_id: u64,
+    format: ExportFormat,
+    path: String,
+) -> Result<String, String> {
+    let request = ScanExportRequest {
+        scan_id,
+        format,
+        path: PathBuf::from(path),
+    };
+
+    let report = IpcClient::send_ipc::<Result<ScanExportReport, String>, _>(
+        "scanner_export_results",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Builds the options for a scan from those passed by the UI, where a time limit is given in seconds. Detections are
+/// only reported unless the UI asks for an action, and the thresholds in the settings are used unless overridden.
+/// Every file is scanned unless the UI asks for executables only, or for the files changed since a
+/// time, given in seconds since the Unix epoch.
+fn scan_options(
+    max_duration_secs: Option<u64>,
+    action: Option<DetectionAction>,
+    thresholds: Option<DetectionThresholds>,
+    executables_only: Option<bool>,
+    include_remote_volumes: Option<bool>,
+    modified_since_secs: Option<u64>,
+) -> ScanOptions {
+    ScanOptions {
+        max_duration: max_duration_secs.map(Duration::from_secs),
+        action: action.unwrap_or_default(),
+        thresholds,
+        executables_only: executables_only.unwrap_or(false),
+        include_remote_volumes: include_remote_volumes.unwrap_or(false),
+        modified_since: modified_since_secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+    }
+}
+
+/// Asks the engine to start a scan of the target, emitting `folder_scan_started` as soon as the engine has accepted
+/// it. A background task then watches the scan, emitting one of `folder_scan_no_results`, `folder_scan_malware_found`,
+/// `scan_timed_out` or `folder_scan_error` once the scan has ended.
+///
+/// # Returns
+///
+/// The ID of the scan, or an error if the engine refused to start the scan.
+async fn start_scan(request: ScanTargetRequest, app_handle: tauri::AppHandle) -> Result<u64, String> {
+    let started = IpcClient::send_ipc::<Result<ScanStarted, String>, _>(
+        "scanner_start_scan",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    app_handle.emit("folder_scan_started", &started).unwrap();
+
+    let scan_id = started.scan_id;
     tokio::spawn(async move {
-        let paths = IpcClient::send_ipc::<Vec<PathBuf>, Option<Value>>(
-            "settings_get_common_scan_areas",
+        watch_scan(scan_id, app_handle).await;
+    });
+
+    Ok(scan_id)
+}
+
+/// Polls the engine until the scan with the given ID has finished estimating, then emits `folder_scan_started` with
+/// the estimate. Nothing is emitted for a scan which ended whilst estimating, as watching the scan reports its outcome.
+async fn watch_estimate(scan_id: u64, app_handle: &tauri::AppHandle) {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let event = match IpcClient::send_ipc::<Option<EngineEvent>, Option<Value>>(
+            "scanner_current_event",
             None,
         )
         .await
-        .unwrap();
+        {
+            Ok(event) => event,
+            // losing the engine is reported when watching the scan
+            Err(_) => return,
+        };
 
-        // The result is wrapped inside of an enum from the filescanner module, so we need to first match on that
-        // as DirectoryResult (since we are scanning a dir). The result should never be anything else for this scan
-        // so if it is something has gone wrong with the internal wiring.
-        match IpcClient::send_ipc::<FileScannerState, _>("scanner_start_folder_scan", Some(paths))
-            .await
+        match event {
+            Some(EngineEvent::ScanStarted { started }) if started.scan_id == scan_id => {
+                if started.estimated_file_count.is_some() {
+                    app_handle.emit("folder_scan_started", &started).unwrap();
+                    return;
+                }
+            }
+            _ => return,
+        }
+    }
+}
+
+/// Polls the engine until the scan with the given ID has ended, then emits the outcome to the UI.
+async fn watch_scan(scan_id: u64, app_handle: tauri::AppHandle) {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let state = match IpcClient::send_ipc::<FileScannerState, Option<Value>>(
+            "scanner_check_page_state",
+            None,
+        )
+        .await
         {
-            Ok(response) => match response {
-                FileScannerState::Finished => {
-                    let scan_result = IpcClient::send_ipc::<ScanningLiveInfo, Option<Value>>(
-                        "scanner_get_scan_stats",
-                        None,
-                    )
-                    .await
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[-] Error with IPC whilst watching scan: {e}");
+                app_handle
+                    .emit("folder_scan_error", format!("Lost contact with the engine: {e}"))
                     .unwrap();
+                return;
+            }
+        };
 
-                    if scan_result.scan_results.is_empty() {
-                        app_handle
-                            .emit("folder_scan_no_results", "No malicious files found.")
-                            .unwrap();
-                    } else {
+        match state {
+            FileScannerState::Scanning => continue,
+            // the scan was reset by the user, the UI has already updated itself for this
+            FileScannerState::Inactive => return,
+            FileScannerState::Cancelled => {
+                // a scan which reached its time limit is reported with its partial results; whereas for a cancel by
+                // the user, the UI has already updated itself
+                if let Ok(scan_result) = IpcClient::send_ipc::<ScanningLiveInfo, Option<Value>>(
+                    "scanner_get_scan_stats",
+                    None,
+                )
+                .await
+                {
+                    if scan_result.scan_id == Some(scan_id)
+                        && scan_result.cancel_reason.as_deref() == Some(CANCEL_REASON_TIME_LIMIT)
+                    {
+                        app_handle.emit("scan_timed_out", &scan_result).unwrap();
+                    }
+                }
+                return;
+            }
+            FileScannerState::FinishedWithError(v) => {
+                app_handle.emit("folder_scan_error", &v).unwrap();
+                return;
+            }
+            FileScannerState::Finished => {
+                let scan_result = match IpcClient::send_ipc::<ScanningLiveInfo, Option<Value>>(
+                    "scanner_get_scan_stats",
+                    None,
+                )
+                .await
+                {
+                    Ok(r) => r,
+                    Err(e) => {
+                        eprintln!("[-] Error with IPC: {e}");
                         app_handle
-                            .emit("folder_scan_malware_found", &scan_result)
+                            .emit("folder_scan_error", format!("Could not get scan results: {e}"))
                             .unwrap();
+                        return;
                     }
+                };
+
+                // a newer scan has since been started, which will be reported by its own watcher
+                if scan_result.scan_id != Some(scan_id) {
+                    return;
                 }
-                FileScannerState::FinishedWithError(v) => {
-                    app_handle.emit("folder_scan_error", &v).unwrap();
-                }
-                FileScannerState::Scanning => app_handle
-                    .emit(
-                        "folder_scan_error",
-                        "A scan is already in progress.".to_string(),
-                    )
-                    .unwrap(),
-                _ => (),
-            },
-            Err(e) => {
-                eprintln!("[-] Error with IPC: {e}");
-            }
-        };
-    });
 
-    // // todo some kind of feedback like 1/1 file scanned; but then same for the mass scanner, be good to show x files scanned, and time taken so far. Then completed time and
-    // // total files after.
+                // files which changed during the scan could not be verified, so are reported alongside any malware
+                if scan_result.detection_count == 0 && scan_result.changed_during_scan.is_empty() {
+                    app_handle
+                        .emit("folder_scan_no_results", "No malicious files found.")
+                        .unwrap();
+                } else {
+                    app_handle
+                        .emit("folder_scan_malware_found", &scan_result)
+                        .unwrap();
+                }
 
-    // todo this shouldn't show in every case..
-    Ok("Scan in progress...".to_string())
+                return;
+            }
+        }
+    }
 }
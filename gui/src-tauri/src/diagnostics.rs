@@ -0,0 +1,280 @@
+//! Diagnostics for troubleshooting the install, such as the engine's self-test.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde_json::{to_value, Value};
+use shared_std::{
+    connections::ConnectionInfo,
+    diagnostics::{
+        DiagnosticsExportReport, DiagnosticsExportRequest, LogRotation, ProtectionScore, ResourceUsage,
+        SelfTestReport, WarmupReport,
+    },
+    file_scanner::{
+        BenchmarkReport, BenchmarkRequest, DetectionAction, MatchedIOC, SimulatedDetectionRequest,
+        SizeDistribution, StopAllSummary,
+    },
+    history::{ProtectionStats, StatsPeriod},
+    maintenance::MaintenanceReport,
+    quarantine::{
+        QuarantineEntry, QuarantineExtractRequest, QuarantineExtraction, QuarantineReport,
+        QuarantineRestoration, QuarantineRestoreRequest, RestoreConflictPolicy,
+    },
+    version::VersionInfo,
+};
+
+use crate::ipc::{decode_batched, IpcClient};
+
+/// What the home page shows as it loads, each part of which may fail on its own
+#[derive(Serialize)]
+struct HomePageState {
+    version_info: Result<VersionInfo, String>,
+    protection_stats: Result<ProtectionStats, String>,
+    protection_score: Result<ProtectionScore, String>,
+    resource_usage: Result<ResourceUsage, String>,
+}
+
+/// Gets everything the home page shows as it loads in a single round trip with the engine, as
+/// JSON. Each part is given as `Ok` or `Err` on its own, so one which fails still lets the page
+/// show the others.
+#[tauri::command]
+pub async fn home_page_state(period: Option<StatsPeriod>) -> Result<String, String> {
+    let mut responses = IpcClient::send_batch(vec![
+        ("version_info", None),
+        ("protection_stats", Some(to_value(period).unwrap())),
+        ("protection_score", None),
+        ("resource_usage", None),
+    ])
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?
+    .into_iter();
+    let mut next = || responses.next().unwrap_or(Value::Null);
+
+    let state = HomePageState {
+        version_info: decode_batched(next()),
+        protection_stats: decode_batched(next()),
+        protection_score: decode_batched(next()),
+        resource_usage: decode_batched::<Result<ResourceUsage, String>>(next()).and_then(|r| r),
+    };
+
+    Ok(serde_json::to_string(&state).unwrap())
+}
+
+/// Asks the engine to run its self-test, confirming end to end that detection is working
+#[tauri::command]
+pub async fn self_test() -> Result<String, String> {
+    match IpcClient::send_ipc::<SelfTestReport, Option<Value>>("self_test", None).await {
+        Ok(report) => Ok(serde_json::to_string(&report).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for self test: {e}");
+            Err(format!("Could not run the self-test, the engine may not be running. {e}"))
+        }
+    }
+}
+
+/// Asks the engine to ready itself for the first scan, returning once it has, so the GUI can show that it is still
+/// initialising until then
+#[tauri::command]
+pub async fn warmup() -> Result<String, String> {
+    match IpcClient::send_ipc::<WarmupReport, Option<Value>>("warmup", None).await {
+        Ok(report) => Ok(serde_json::to_string(&report).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for warmup: {e}");
+            Err(format!("Could not initialise the engine, it may not be running. {e}"))
+        }
+    }
+}
+
+/// Asks the engine for its own use of the machine's resources, which takes a moment as its CPU use is sampled
+#[tauri::command]
+pub async fn resource_usage() -> Result<String, String> {
+    let usage =
+        IpcClient::send_ipc::<Result<ResourceUsage, String>, Option<Value>>("resource_usage", None)
+            .await
+            .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&usage).unwrap())
+}
+
+/// Asks the engine to write a diagnostics bundle to attach to a bug report. File paths and the user name are redacted
+/// from the bundle unless `full_detail` is set.
+#[tauri::command]
+pub async fn export_diagnostics(dest: String, full_detail: bool) -> Result<String, String> {
+    let request = DiagnosticsExportRequest {
+        dest: PathBuf::from(dest),
+        full_detail,
+    };
+
+    let report = IpcClient::send_ipc::<Result<DiagnosticsExportReport, String>, _>(
+        "export_diagnostics",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Asks the engine to rotate its log, so that the log collected for a support case ends at this moment. Returns the
+/// file the log was closed in and the file it continues in as JSON.
+#[tauri::command]
+pub async fn logs_rotate() -> Result<String, String> {
+    let rotation = IpcClient::send_ipc::<Result<LogRotation, String>, Option<Value>>("logs_rotate", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&rotation).unwrap())
+}
+
+/// Asks the engine to prune old scan history and orphaned quarantine entries now, rather than waiting for its periodic
+/// maintenance
+#[tauri::command]
+pub async fn maintenance_run() -> Result<String, String> {
+    match IpcClient::send_ipc::<MaintenanceReport, Option<Value>>("maintenance_run", None).await {
+        Ok(report) => Ok(serde_json::to_string(&report).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for maintenance: {e}");
+            Err(format!("Could not run maintenance, the engine may not be running. {e}"))
+        }
+    }
+}
+
+/// Asks the engine to check the quarantine manifest against the quarantined files, removing entries whose file is
+/// missing where `repair` is set
+#[tauri::command]
+pub async fn quarantine_verify(repair: bool) -> Result<String, String> {
+    let report = IpcClient::send_ipc::<Result<QuarantineReport, String>, _>(
+        "quarantine_verify",
+        Some(to_value(repair).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Gets the latest quarantine entry for a file by the path it was quarantined from, as JSON, which is
+/// null where the file has never been quarantined from there
+#[tauri::command]
+pub async fn quarantine_status(path: PathBuf) -> Result<String, String> {
+    let entry = IpcClient::send_ipc::<Option<QuarantineEntry>, _>(
+        "quarantine_status",
+        Some(to_value(path).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&entry).unwrap())
+}
+
+/// Extracts a defanged copy of a quarantined file for analysis, leaving it in quarantine. The engine
+/// only serves this to a GUI running as an administrator.
+#[tauri::command]
+pub async fn quarantine_extract(entry_id: String, dest: PathBuf) -> Result<String, String> {
+    let request = QuarantineExtractRequest { entry_id, dest };
+    let extraction = IpcClient::send_ipc::<Result<QuarantineExtraction, String>, _>(
+        "quarantine_extract",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&extraction).unwrap())
+}
+
+/// Restores a quarantined file to where it was quarantined from. Where another file is now there,
+/// the policy decides what is done, and by default nothing is restored. The engine only serves this
+/// to a GUI running as an administrator.
+#[tauri::command]
+pub async fn quarantine_restore(
+    entry_id: String,
+    conflict_policy: Option<RestoreConflictPolicy>,
+) -> Result<String, String> {
+    let request = QuarantineRestoreRequest {
+        entry_id,
+        conflict_policy: conflict_policy.unwrap_or_default(),
+    };
+    let restoration = IpcClient::send_ipc::<Result<QuarantineRestoration, String>, _>(
+        "quarantine_restore",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&restoration).unwrap())
+}
+
+/// Lists the clients connected to the engine and the event subscriptions they hold, for diagnosing
+/// a client which is not receiving events
+#[tauri::command]
+pub async fn connections_list() -> Result<String, String> {
+    match IpcClient::send_ipc::<Vec<ConnectionInfo>, Option<Value>>("connections_list", None).await {
+        Ok(connections) => Ok(serde_json::to_string(&connections).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for connections list: {e}");
+            Err(format!("Could not list the connections to the engine. {e}"))
+        }
+    }
+}
+
+/// Asks the engine to inject a made up detection of the file, to test that notifications, the
+/// quarantine, the SIEM and the app all see it. Only an engine built for testing will do this.
+#[tauri::command]
+pub async fn simulate_detection(
+    path: String,
+    name: String,
+    severity: u8,
+    action: Option<DetectionAction>,
+) -> Result<String, String> {
+    let request = SimulatedDetectionRequest {
+        path: PathBuf::from(path),
+        name,
+        severity,
+        action: action.unwrap_or_default(),
+    };
+
+    let ioc = IpcClient::send_ipc::<Result<MatchedIOC, String>, _>(
+        "simulate_detection",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&ioc).unwrap())
+}
+
+/// Asks the engine to measure how fast it scans, over the number of files generated in memory of the average size in
+/// bytes, spread as the distribution says. Only an engine built for testing will do this.
+#[tauri::command]
+pub async fn benchmark_scan(
+    file_count: u64,
+    avg_size: u64,
+    distribution: Option<SizeDistribution>,
+) -> Result<String, String> {
+    let request = BenchmarkRequest {
+        file_count,
+        avg_size,
+        distribution: distribution.unwrap_or_default(),
+    };
+
+    let report = IpcClient::send_ipc::<Result<BenchmarkReport, String>, _>(
+        "benchmark_scan",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Asks the engine to stop everything it is running, such as during incident response or before shutting it down
+#[tauri::command]
+pub async fn stop_all() -> Result<String, String> {
+    match IpcClient::send_ipc::<StopAllSummary, Option<Value>>("stop_all", None).await {
+        Ok(summary) => Ok(serde_json::to_string(&summary).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for stop all: {e}");
+            Err(format!("Could not stop the engine's activity, the engine may not be running. {e}"))
+        }
+    }
+}
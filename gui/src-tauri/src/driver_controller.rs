@@ -1,6 +1,9 @@
 use crate::ipc::IpcClient;
 use serde_json::{to_value, Value};
-use shared_std::driver_manager::DriverState;
+use shared_no_std::ipc::CommandResponse;
+use shared_std::driver_manager::{
+    DriverAvailability, DriverState, DriverStats, RealtimeStatus, STATUS_DRIVER_UNAVAILABLE,
+};
 
 #[derive(serde::Serialize, serde::Deserialize)]
 enum Response {
@@ -97,11 +100,75 @@ pub async fn driver_check_state() -> Result<String, ()> {
     Ok(state_string)
 }
 
+/// Gets whether the engine can reach the driver, as JSON. Whilst it cannot, the engine is running degraded and the
+/// features which rely on the driver should be disabled, with the reason shown to the user.
+#[tauri::command]
+pub async fn driver_availability() -> Result<String, ()> {
+    let availability = match IpcClient::send_ipc::<DriverAvailability, Option<Value>>(
+        "driver_availability",
+        None,
+    )
+    .await
+    {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("[-] Error with IPC for driver availability: {e}");
+            DriverAvailability::Unavailable {
+                reason: "An error occurred talking to the engine.".to_string(),
+            }
+        }
+    };
+
+    Ok(serde_json::to_string(&availability).unwrap())
+}
+
+/// Gets how well the engine is keeping up with the events the driver raises, and how many have been dropped, as JSON
+#[tauri::command]
+pub async fn driver_stats() -> Result<String, String> {
+    let stats = IpcClient::send_ipc::<DriverStats, Option<Value>>("driver_stats", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&stats).unwrap())
+}
+
+/// Gets the policy real-time protection applies, and whether the driver it relies on is available, as JSON
+#[tauri::command]
+pub async fn realtime_status() -> Result<String, String> {
+    let status = IpcClient::send_ipc::<RealtimeStatus, Option<Value>>("realtime_status", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&status).unwrap())
+}
+
+/// Snoozes real-time protection for the number of seconds, after which the engine resumes it by itself
+#[tauri::command]
+pub async fn realtime_snooze(duration_secs: u64) -> Result<(), String> {
+    IpcClient::send_ipc::<Result<(), String>, _>(
+        "realtime_snooze",
+        Some(to_value(duration_secs).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?
+}
+
+/// Resumes real-time protection before its snooze has run out, returning whether it was snoozed
+#[tauri::command]
+pub async fn realtime_resume() -> Result<bool, String> {
+    IpcClient::send_ipc::<bool, Option<Value>>("realtime_resume", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))
+}
+
 #[tauri::command]
 pub async fn ioctl_ping_driver() -> Result<String, ()> {
     let response =
-        match IpcClient::send_ipc::<String, Option<Value>>("ioctl_ping_driver", None).await {
-            Ok(s) => s,
+        match IpcClient::send_ipc::<Value, Option<Value>>("ioctl_ping_driver", None).await {
+            Ok(v) => match driver_unavailable(&v) {
+                Some(reason) => format!("The driver is unavailable. {reason}"),
+                None => serde_json::from_value::<String>(v).unwrap_or_default(),
+            },
             Err(e) => {
                 eprintln!("[-] Error with IPC for get driver state: {e}");
                 "An error occurred when communicating  with the driver.".to_string()
@@ -111,6 +178,14 @@ pub async fn ioctl_ping_driver() -> Result<String, ()> {
     Ok(response)
 }
 
+/// Gets the reason the driver is unavailable, where the engine refused a command as it needs the driver
+fn driver_unavailable(response: &Value) -> Option<String> {
+    serde_json::from_value::<CommandResponse>(response.clone())
+        .ok()
+        .filter(|r| r.status == STATUS_DRIVER_UNAVAILABLE)
+        .map(|r| r.message)
+}
+
 /// Poll the usermode engine for any new messages from the kernel which need to be processed by the GUI; this is only
 /// for the driver controller page
 #[tauri::command]
@@ -0,0 +1,62 @@
+//! Forwards the events raised by the engine to the frontend.
+
+use shared_std::events::{EngineEvent, EventCategory};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_winrt_notification::{Duration, Toast};
+use tokio_stream::StreamExt;
+
+use crate::ipc::IpcClient;
+
+/// The name of the frontend event carrying each `EngineEvent`
+const ENGINE_EVENT: &str = "engine_event";
+
+/// Subscribes to the engine's events and emits each to the frontend as an `engine_event`, for as long as the app runs.
+/// The subscription reconnects by itself, so this continues through restarts of the engine.
+///
+/// `Notify` events are also shown as a toast, as the user may not have the app open. Every category is subscribed to,
+/// as each page of the frontend listens for the events it shows.
+pub async fn forward_engine_events(app_handle: AppHandle) {
+    let mut events = IpcClient::subscribe_events(EventCategory::ALL);
+
+    while let Some(event) = events.next().await {
+        if let EngineEvent::Notify {
+            title,
+            message,
+            scan_id,
+        } = &event
+        {
+            show_toast(&app_handle, title, message, *scan_id);
+        }
+
+        if let Err(e) = app_handle.emit(ENGINE_EVENT, &event) {
+            eprintln!("[-] Could not forward engine event to the frontend: {e}");
+        }
+    }
+}
+
+/// Shows a notification as a toast. Where the notification is about a scan, clicking the toast
+/// opens the scan results.
+fn show_toast(app_handle: &AppHandle, title: &str, message: &str, scan_id: Option<u64>) {
+    let app_handle = app_handle.clone();
+
+    let result = Toast::new(Toast::POWERSHELL_APP_ID)
+        .title(title)
+        .text1(message)
+        .duration(Duration::Short)
+        .on_activated(move |_| {
+            if scan_id.is_none() {
+                return Ok(());
+            }
+            if let Some(window) = app_handle.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+                let _ = window.eval("window.location.href = 'antivirus.html'");
+            }
+            Ok(())
+        })
+        .show();
+
+    if let Err(e) = result {
+        eprintln!("[-] Could not show notification: {e}");
+    }
+}
@@ -1,14 +1,54 @@
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 use serde::{de::DeserializeOwned, Serialize};
-use serde_json::{to_value, to_vec};
-use shared_no_std::{constants::PIPE_NAME, ipc::CommandRequest};
-use shared_std::{constants::PIPE_FOR_GUI, security::create_security_attributes};
+use serde_json::{to_value, to_vec, Value};
+use shared_no_std::{
+    constants::{IPC_EVENT_HEARTBEAT_SECS, IPC_STREAM_MAX_CHUNK_LEN, PIPE_NAME},
+    ipc::{
+        CodecKind, CommandRequest, CommandResponse, ConnectionRole, IpcHandshake,
+        STATUS_PERMISSION_DENIED,
+    },
+};
+use shared_std::{
+    codec::{decode_tagged, Codec, Json, Postcard},
+    constants::PIPE_FOR_GUI,
+    events::{
+        EngineEvent, EngineEventEnvelope, EventCategory, EventSubscribeArgs,
+        EventSubscriptionUpdate,
+    },
+    file_scanner::{BytesScanResult, EngineBusy, STATUS_ENGINE_BUSY},
+    security::create_security_attributes,
+};
 use tauri_winrt_notification::{Duration, Sound, Toast};
 use tokio::{
-    io::{self, AsyncReadExt, AsyncWriteExt},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::windows::named_pipe::{ClientOptions, NamedPipeClient, ServerOptions},
+    sync::{mpsc, watch, OnceCell},
 };
+#[cfg(feature = "remote-management")]
+use std::sync::Arc;
+#[cfg(feature = "remote-management")]
+use tokio::net::TcpStream;
+#[cfg(feature = "remote-management")]
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig},
+    TlsConnector,
+};
+use tokio_stream::Stream;
+
+/// The capabilities advertised by the engine, fetched once on first use
+static HANDSHAKE: OnceCell<IpcHandshake> = OnceCell::const_new();
+
+/// The number of events buffered for a subscriber which is slow to take them
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+/// The delay before the first attempt to reconnect an event subscription, doubling on each failed attempt
+const EVENT_RECONNECT_MIN: std::time::Duration = std::time::Duration::from_millis(250);
+/// The longest delay between attempts to reconnect an event subscription
+const EVENT_RECONNECT_MAX: std::time::Duration = std::time::Duration::from_secs(10);
 
 pub struct IpcClient {
     client: NamedPipeClient,
@@ -33,7 +73,7 @@ impl IpcClient {
     ///
     /// ```
     /// // where IPC is of type IpcClient as implemented in the GUI.
-    /// IpcClient::send_ipc::<(), Option<Value>>("scanner_cancel_scan", None).await
+    /// IpcClient::send_ipc::<Result<u64, String>, Option<Value>>("scanner_cancel_scan", None).await
     /// ```
     ///
     /// ## Data of type A to send:
@@ -54,32 +94,498 @@ impl IpcClient {
     where
         T: DeserializeOwned + Debug,
         A: Serialize,
+    {
+        Self::send_ipc_with_codec::<Json, T, A>(command, args).await
+    }
+
+    /// Sends an IPC request, asking for the response with a compact binary codec where the engine advertised
+    /// support for it on this command in its handshake. For use on hot paths such as polling the live scan stats.
+    ///
+    /// If the engine did not advertise support (or the handshake could not be made), this falls back to JSON.
+    pub async fn send_ipc_binary<T, A>(command: &str, args: Option<A>) -> io::Result<T>
+    where
+        T: DeserializeOwned + Debug,
+        A: Serialize,
+    {
+        if Self::binary_supported(command).await {
+            Self::send_ipc_with_codec::<Postcard, T, A>(command, args).await
+        } else {
+            Self::send_ipc_with_codec::<Json, T, A>(command, args).await
+        }
+    }
+
+    /// Whether the engine advertised in its handshake that it can answer the command with the binary codec
+    async fn binary_supported(command: &str) -> bool {
+        match Self::handshake().await {
+            Some(h) => {
+                h.codecs.contains(&Postcard::KIND)
+                    && h.binary_commands.iter().any(|c| c == command)
+            }
+            None => false,
+        }
+    }
+
+    /// The capabilities the engine advertised in its handshake, so the client can configure itself from them rather
+    /// than assuming what the engine supports. The handshake is made once and kept for the life of the GUI.
+    ///
+    /// # Returns
+    ///
+    /// None if the handshake could not be made, in which case it is tried again on the next call.
+    pub async fn handshake() -> Option<&'static IpcHandshake> {
+        HANDSHAKE
+            .get_or_try_init(|| async {
+                Self::send_ipc::<IpcHandshake, Option<Value>>("ipc_handshake", None).await
+            })
+            .await
+            .ok()
+    }
+
+    /// Sends an IPC request asking for the response to be encoded with codec `C`. The request itself is always
+    /// JSON; the response is decoded according to the codec the engine reports it used.
+    pub async fn send_ipc_with_codec<C, T, A>(command: &str, args: Option<A>) -> io::Result<T>
+    where
+        C: Codec,
+        T: DeserializeOwned + Debug,
+        A: Serialize,
     {
         let mut client = ClientOptions::new().open(PIPE_NAME)?;
 
-        // where there are args, serialise, otherwise, set to none
-        let args = args.map(|a| to_value(a).unwrap());
+        client.write_all(&encode_request::<C, A>(command, args)?).await?;
+
+        let response = read_response(&mut client).await?;
+        let received_data = response.as_slice();
+
+        // an on-demand request the engine is too busy to serve is answered with `EngineBusy`, so is reported as the
+        // engine being busy rather than as a response which could not be decoded
+        if let Some(busy) = engine_busy(received_data) {
+            return Err(io::Error::new(io::ErrorKind::ResourceBusy, busy.message));
+        }
+
+        // likewise a command the engine would not serve to this client, such as one needing elevation
+        if let Some(denied) = permission_denied(received_data) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, denied.message));
+        }
+
+        // Deserialize the received data into the response type
+        let response_message: T = decode_tagged(received_data)?;
+
+        Ok(response_message)
+    }
+
+    /// Scans content read from `reader` without it touching the disk, for example content piped in on stdin. The
+    /// content is streamed to the engine in chunks and hashed as it arrives, so neither side holds it all in memory.
+    ///
+    /// # Args
+    ///
+    /// - `reader`: The source of the content to scan
+    /// - `name`: A name for the content which the engine will report in place of a file path
+    ///
+    /// # Returns
+    ///
+    /// The verdict from the engine, or an error if the IPC failed or the engine rejected the content (for example,
+    /// where it exceeds the maximum size the engine will accept).
+    pub async fn scan_bytes_stream<R>(mut reader: R, name: &str) -> io::Result<BytesScanResult>
+    where
+        R: AsyncRead + Unpin,
+    {
+        // an engine which cannot be asked is left to refuse the stream itself
+        if Self::handshake().await.is_some_and(|h| !h.supports_streaming) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "The engine does not support streamed scans.",
+            ));
+        }
+
+        let mut client = ClientOptions::new().open(PIPE_NAME)?;
 
         let message = CommandRequest {
-            command: command.to_string(),
-            args,
+            command: "scanner_scan_bytes".to_string(),
+            args: Some(to_value(name)?),
+            response_codec: CodecKind::Json,
+            role: ConnectionRole::Controller,
         };
+        client.write_all(&to_vec(&message)?).await?;
 
-        let message_data = to_vec(&message)?;
-        client.write_all(&message_data).await?;
-
-        // read the response
+        // wait for the engine to be ready to receive the content; the connection stays open, so this is read as the
+        // one message the engine sends before it is sent the content
         let mut buffer = vec![0u8; 1024];
         let bytes_read = client.read(&mut buffer).await?;
-        let received_data = &buffer[..bytes_read];
+        let ready: CommandResponse = serde_json::from_slice(&buffer[..bytes_read])?;
+        if ready.status != "ready" {
+            return Err(io::Error::other(ready.message));
+        }
 
-        // Deserialize the received JSON data into a Message struct
-        let response_message: T = serde_json::from_slice(received_data)?;
+        // stream the content as length prefixed chunks, terminated by a zero length chunk
+        let mut chunk = vec![0u8; IPC_STREAM_MAX_CHUNK_LEN as usize];
+        loop {
+            let count = reader.read(&mut chunk).await?;
+            if count == 0 {
+                break;
+            }
 
-        Ok(response_message)
+            client.write_u32_le(count as u32).await?;
+            client.write_all(&chunk[..count]).await?;
+        }
+        client.write_u32_le(0).await?;
+
+        // the engine responds with either the verdict or an error
+        let response: Value = decode_tagged(&read_response(&mut client).await?)?;
+
+        match serde_json::from_value::<BytesScanResult>(response.clone()) {
+            Ok(result) => Ok(result),
+            Err(_) => {
+                let error: CommandResponse = serde_json::from_value(response)?;
+                Err(io::Error::other(error.message))
+            }
+        }
+    }
+}
+
+impl IpcClient {
+    /// Sends several commands to the engine in one round trip with its `batch` command, such as for
+    /// the state a page shows as it loads. The engine answers each command as it would on its own,
+    /// so one which fails or is refused does not stop the others; see [`decode_batched`].
+    ///
+    /// # Returns
+    ///
+    /// The response to each command, in the order they were given, or an error where the IPC failed
+    /// or the engine refused the batch itself.
+    pub async fn send_batch(commands: Vec<(&str, Option<Value>)>) -> io::Result<Vec<Value>> {
+        let requests: Vec<CommandRequest> = commands
+            .into_iter()
+            .map(|(command, args)| CommandRequest {
+                command: command.to_string(),
+                args,
+                response_codec: CodecKind::Json,
+                role: ConnectionRole::Controller,
+            })
+            .collect();
+
+        Self::send_ipc::<Vec<Value>, _>("batch", Some(requests)).await
+    }
+}
+
+#[cfg(feature = "remote-management")]
+impl IpcClient {
+    /// Connects to the remote management listener of the engine at `addr`, given as `host:port`, for managing an
+    /// engine on another machine. The host is the name the engine's certificate must be issued to.
+    ///
+    /// The TLS config must trust the engine's certificate, and hold a client certificate issued by the CA the engine
+    /// trusts, as the engine refuses any client without one. The handshake is made on connecting, so a client which
+    /// is refused is known straight away.
+    ///
+    /// # Returns
+    ///
+    /// A client which sends requests as [`IpcClient::send_ipc`] does over the pipe, with the same commands.
+    #[allow(dead_code)] // for remote consoles, the GUI itself only manages the engine on its own machine
+    pub async fn connect_tcp(
+        addr: &str,
+        tls_config: Arc<ClientConfig>,
+    ) -> io::Result<TcpIpcClient> {
+        let host = match addr.rsplit_once(':') {
+            Some((host, _)) => host.trim_start_matches('[').trim_end_matches(']'),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("The engine address '{addr}' must be given as host:port."),
+                ))
+            }
+        };
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let mut client = TcpIpcClient {
+            addr: addr.to_string(),
+            server_name,
+            connector: TlsConnector::from(tls_config),
+            handshake: None,
+        };
+        let handshake = client
+            .send_ipc::<IpcHandshake, Option<Value>>("ipc_handshake", None)
+            .await?;
+        client.handshake = Some(handshake);
+
+        Ok(client)
+    }
+}
+
+/// A client of the remote management listener of an engine, made with [`IpcClient::connect_tcp`]. As on the pipe, the
+/// engine serves a single request on each connection, so each request is made on a connection of its own.
+#[cfg(feature = "remote-management")]
+#[allow(dead_code)]
+pub struct TcpIpcClient {
+    addr: String,
+    server_name: ServerName<'static>,
+    connector: TlsConnector,
+    handshake: Option<IpcHandshake>,
+}
+
+#[cfg(feature = "remote-management")]
+#[allow(dead_code)]
+impl TcpIpcClient {
+    /// Sends an IPC request to the engine, as [`IpcClient::send_ipc`] does over the pipe
+    pub async fn send_ipc<T, A>(&self, command: &str, args: Option<A>) -> io::Result<T>
+    where
+        T: DeserializeOwned + Debug,
+        A: Serialize,
+    {
+        let stream = TcpStream::connect(&self.addr).await?;
+        let mut client = self.connector.connect(self.server_name.clone(), stream).await?;
+
+        client.write_all(&encode_request::<Json, A>(command, args)?).await?;
+        client.flush().await?;
+
+        // the engine closes the session once the whole response has been sent
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await?;
+
+        decode_tagged(&response)
+    }
+
+    /// The capabilities the engine advertised when the client connected
+    pub fn handshake(&self) -> Option<&IpcHandshake> {
+        self.handshake.as_ref()
+    }
+}
+
+/// Reads the whole of a response from the engine, which closes the pipe once it has been sent, however many reads it
+/// takes to arrive, as a response such as that to a batch can be far longer than one read gives
+async fn read_response(client: &mut NamedPipeClient) -> io::Result<Vec<u8>> {
+    let mut response = Vec::new();
+    client.read_to_end(&mut response).await?;
+
+    Ok(response)
+}
+
+/// Serialises a request as a controller, asking for the response to be encoded with codec `C`
+fn encode_request<C, A>(command: &str, args: Option<A>) -> io::Result<Vec<u8>>
+where
+    C: Codec,
+    A: Serialize,
+{
+    // where there are args, serialise, otherwise, set to none
+    let args = args.map(|a| to_value(a).unwrap());
+
+    let message = CommandRequest {
+        command: command.to_string(),
+        args,
+        response_codec: C::KIND,
+        role: ConnectionRole::Controller,
+    };
+
+    Ok(to_vec(&message)?)
+}
+
+/// A subscription to the events raised by the engine, yielded as a [`Stream`] of [`EngineEvent`]s.
+///
+/// Events are delivered over a dedicated pipe connection, separate from the command request / response path. If the
+/// connection drops, such as when the engine restarts, the subscription reconnects with a backoff and resumes from the
+/// last event it received, so events retained by the engine in the meantime are not missed.
+///
+/// Only events in the subscription's categories are sent by the engine, which can be changed with
+/// [`EventSubscription::update`] without reconnecting.
+///
+/// The subscription runs until it is dropped.
+pub struct EventSubscription {
+    rx: mpsc::Receiver<EngineEvent>,
+    categories: watch::Sender<EventCategory>,
+}
+
+impl EventSubscription {
+    /// Changes the categories of event the engine sends, from its next event on. Where the subscription is
+    /// reconnecting, it resubscribes with these categories.
+    pub fn update(&self, categories: EventCategory) {
+        self.categories.send_replace(categories);
     }
 }
 
+impl Stream for EventSubscription {
+    type Item = EngineEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl IpcClient {
+    /// Subscribes to the events in the categories raised by the engine. See [`EventSubscription`].
+    pub fn subscribe_events(categories: EventCategory) -> EventSubscription {
+        let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (categories, mut wanted) = watch::channel(categories);
+
+        tokio::spawn(async move {
+            let mut last_seq: Option<u64> = None;
+            let mut backoff = EVENT_RECONNECT_MIN;
+
+            loop {
+                let mut subscribed = false;
+
+                tokio::select! {
+                    result = Self::receive_events(&tx, &mut wanted, &mut last_seq, &mut subscribed) => {
+                        if let Err(e) = result {
+                            eprintln!("[-] Event subscription to the engine lost: {e}");
+                        }
+                    }
+                    // the subscription was dropped
+                    _ = tx.closed() => return,
+                }
+
+                if tx.is_closed() {
+                    return;
+                }
+
+                // a connection which was accepted resets the backoff, so a restart of the engine is picked up quickly
+                if subscribed {
+                    backoff = EVENT_RECONNECT_MIN;
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(EVENT_RECONNECT_MAX);
+            }
+        });
+
+        EventSubscription { rx, categories }
+    }
+
+    /// Opens an event subscription with the engine and forwards events from it until the connection drops.
+    ///
+    /// # Args
+    ///
+    /// - `categories`: The categories of event wanted, a change to which is sent to the engine as a
+    ///   `subscribe_update`
+    /// - `last_seq`: The sequence number of the last event received, updated as events arrive
+    /// - `subscribed`: Set once the engine has accepted the subscription
+    async fn receive_events(
+        tx: &mpsc::Sender<EngineEvent>,
+        categories: &mut watch::Receiver<EventCategory>,
+        last_seq: &mut Option<u64>,
+        subscribed: &mut bool,
+    ) -> io::Result<()> {
+        // events are sent with the binary codec where the engine supports it, as they are sent throughout a scan
+        let response_codec = match Self::binary_supported("events_subscribe").await {
+            true => Postcard::KIND,
+            false => Json::KIND,
+        };
+        let mut client = ClientOptions::new().open(PIPE_NAME)?;
+
+        let message = CommandRequest {
+            command: "events_subscribe".to_string(),
+            args: Some(to_value(EventSubscribeArgs {
+                after_seq: *last_seq,
+                categories: *categories.borrow_and_update(),
+            })?),
+            response_codec,
+            role: ConnectionRole::Controller,
+        };
+        client.write_all(&to_vec(&message)?).await?;
+
+        let ready: CommandResponse = serde_json::from_slice(&read_event_frame(&mut client).await?)?;
+        if ready.status != "ready" {
+            return Err(io::Error::other(ready.message));
+        }
+        *subscribed = true;
+
+        let (mut reader, mut writer) = io::split(client);
+
+        tokio::select! {
+            result = Self::forward_events(&mut reader, tx, last_seq) => result,
+            result = Self::send_subscription_updates(&mut writer, categories) => result,
+        }
+    }
+
+    /// Forwards the events read from the subscription until the connection drops, or the subscription is dropped
+    async fn forward_events(
+        reader: &mut (impl AsyncRead + Unpin),
+        tx: &mpsc::Sender<EngineEvent>,
+        last_seq: &mut Option<u64>,
+    ) -> io::Result<()> {
+        loop {
+            let frame = read_event_frame(reader).await?;
+
+            // a zero length frame is a heartbeat
+            if frame.is_empty() {
+                continue;
+            }
+
+            let envelope: EngineEventEnvelope = decode_tagged(&frame)?;
+            *last_seq = Some(envelope.seq);
+
+            if tx.send(envelope.event).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends a `subscribe_update` to the engine each time the categories of event wanted change, framed as the
+    /// engine's frames are
+    async fn send_subscription_updates(
+        writer: &mut (impl AsyncWrite + Unpin),
+        categories: &mut watch::Receiver<EventCategory>,
+    ) -> io::Result<()> {
+        // the sender is held by the subscription, so the categories only stop changing once it is dropped
+        while categories.changed().await.is_ok() {
+            let message = CommandRequest {
+                command: "subscribe_update".to_string(),
+                args: Some(to_value(EventSubscriptionUpdate {
+                    categories: *categories.borrow_and_update(),
+                })?),
+                response_codec: CodecKind::Json,
+                role: ConnectionRole::Controller,
+            };
+            let frame = to_vec(&message)?;
+
+            writer.write_u32_le(frame.len() as u32).await?;
+            writer.write_all(&frame).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a length prefixed frame from an event subscription. The engine sends a heartbeat when idle, so where nothing
+/// arrives for several heartbeat intervals the connection is treated as dead.
+async fn read_event_frame(client: &mut (impl AsyncRead + Unpin)) -> io::Result<Vec<u8>> {
+    let timeout = std::time::Duration::from_secs(IPC_EVENT_HEARTBEAT_SECS * 3);
+
+    tokio::time::timeout(timeout, async {
+        let len = client.read_u32_le().await?;
+        if len > IPC_STREAM_MAX_CHUNK_LEN {
+            return Err(io::Error::other(format!(
+                "Event frame of {len} bytes exceeds the maximum of {IPC_STREAM_MAX_CHUNK_LEN}."
+            )));
+        }
+
+        let mut frame = vec![0u8; len as usize];
+        client.read_exact(&mut frame).await?;
+        Ok(frame)
+    })
+    .await
+    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "No heartbeat received from the engine"))?
+}
+
+/// Decodes the response to one command of a batch into the type the command returns, or the
+/// engine's message where it refused the command, such as where it was busy or not permitted
+pub fn decode_batched<T: DeserializeOwned>(response: Value) -> Result<T, String> {
+    serde_json::from_value::<T>(response.clone()).map_err(|e| {
+        serde_json::from_value::<CommandResponse>(response)
+            .map(|refused| refused.message)
+            .unwrap_or_else(|_| format!("Could not decode the response. {e}"))
+    })
+}
+
+/// Gets the response as `EngineBusy`, where the engine refused the request as it is busy
+fn engine_busy(response: &[u8]) -> Option<EngineBusy> {
+    serde_json::from_slice::<EngineBusy>(response)
+        .ok()
+        .filter(|busy| busy.status == STATUS_ENGINE_BUSY)
+}
+
+/// Gets the response as a `CommandResponse`, where the engine refused the request as not permitted for the client
+fn permission_denied(response: &[u8]) -> Option<CommandResponse> {
+    serde_json::from_slice::<CommandResponse>(response)
+        .ok()
+        .filter(|response| response.status == STATUS_PERMISSION_DENIED)
+}
+
 /// An IPC server for inbound notifications from the EDR where we aren't sending outbound polls.
 pub async fn global_inbound_ipc() {
     return;
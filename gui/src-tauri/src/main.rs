@@ -4,44 +4,137 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 #[allow(non_snake_case)]
 mod antivirus;
+mod diagnostics;
 mod driver_controller;
+mod events;
 mod ipc;
 mod processes;
 mod settings;
+mod version;
 
 use antivirus::{
-    scanner_check_page_state, scanner_get_scan_stats, scanner_start_folder_scan,
-    scanner_start_quick_scan, scanner_stop_scan,
+    detection_annotate, detection_recent, protection_score, protection_stats, quarantine_scan_detections,
+    threat_timeline,
+    scanner_cancel_estimate, scanner_check_page_state,
+    scanner_current_event, scanner_detection_details, scanner_diff, scanner_estimate,
+    scanner_export_results, scanner_get_scan_stats, scanner_rescan_detections, scanner_reset,
+    scanner_results_page,
+    scanner_classify, scanner_explain, scanner_inspect_process, scanner_scan_autoruns, scanner_scan_browser_extensions,
+    scanner_scan_drivers, drivers_list, scanner_scan_scheduled_tasks, scanner_scan_registry,
+    scanner_start_folder_scan,
+    scanner_start_image_scan, scanner_start_quick_scan, scanner_start_scan_with_estimate,
+    scanner_start_volume_scan, scanner_stop_scan, scans_cancel, scanner_cancel_by_target,
+    scans_list,
+};
+use diagnostics::{
+    benchmark_scan, connections_list, export_diagnostics, home_page_state, logs_rotate,
+    maintenance_run, quarantine_extract, quarantine_restore, quarantine_status, quarantine_verify,
+    resource_usage, self_test, simulate_detection, stop_all, warmup,
 };
 use driver_controller::{
-    driver_check_state, driver_get_kernel_debug_messages, driver_install_driver,
-    driver_start_driver, driver_stop_driver, driver_uninstall_driver, ioctl_ping_driver,
+    driver_availability, driver_check_state, driver_get_kernel_debug_messages,
+    driver_install_driver, driver_start_driver, driver_stats, driver_stop_driver,
+    driver_uninstall_driver, ioctl_ping_driver, realtime_resume, realtime_snooze, realtime_status,
 };
+use events::forward_engine_events;
 use ipc::global_inbound_ipc;
 use processes::process_query_pid;
-use settings::{settings_load_page_state, settings_update_settings};
+use settings::{
+    config_effective, config_reload, exclusions_preview, exclusions_test, settings_load_page_state,
+    settings_update_settings, verdict_policy,
+};
+use version::{
+    hash_file, signatures_export, signatures_import, signatures_reload,
+    signatures_set_database_enabled, signatures_validate, version_info,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tauri::async_runtime::spawn({ global_inbound_ipc() });
 
     Ok(tauri::Builder::default()
+        .setup(|app| {
+            tauri::async_runtime::spawn(forward_engine_events(app.handle().clone()));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             scanner_start_folder_scan,
+            scanner_start_scan_with_estimate,
             scanner_check_page_state,
+            scanner_current_event,
             scanner_stop_scan,
+            scans_list,
+            scans_cancel,
+            scanner_cancel_by_target,
             scanner_get_scan_stats,
+            scanner_results_page,
             scanner_start_quick_scan,
+            scanner_start_volume_scan,
+            scanner_start_image_scan,
+            scanner_scan_autoruns,
+            scanner_scan_browser_extensions,
+            scanner_scan_drivers,
+            drivers_list,
+            scanner_scan_scheduled_tasks,
+            scanner_scan_registry,
+            scanner_inspect_process,
+            scanner_classify,
+            scanner_explain,
+            scanner_rescan_detections,
+            quarantine_scan_detections,
+            scanner_estimate,
+            scanner_cancel_estimate,
+            scanner_detection_details,
+            detection_annotate,
+            detection_recent,
+            protection_stats,
+            protection_score,
+            threat_timeline,
+            scanner_diff,
+            scanner_export_results,
+            scanner_reset,
             settings_load_page_state,
             settings_update_settings,
+            exclusions_preview,
+            exclusions_test,
+            config_effective,
+            config_reload,
+            verdict_policy,
             driver_install_driver,
             driver_uninstall_driver,
             driver_start_driver,
             driver_stop_driver,
             driver_check_state,
+            driver_availability,
+            driver_stats,
+            realtime_status,
+            realtime_snooze,
+            realtime_resume,
             ioctl_ping_driver,
             driver_get_kernel_debug_messages,
             process_query_pid,
+            version_info,
+            signatures_reload,
+            signatures_import,
+            signatures_export,
+            signatures_validate,
+            signatures_set_database_enabled,
+            hash_file,
+            self_test,
+            warmup,
+            resource_usage,
+            home_page_state,
+            maintenance_run,
+            stop_all,
+            connections_list,
+            simulate_detection,
+            benchmark_scan,
+            quarantine_verify,
+            quarantine_status,
+            quarantine_extract,
+            quarantine_restore,
+            export_diagnostics,
+            logs_rotate,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application"))
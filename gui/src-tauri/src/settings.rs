@@ -1,5 +1,11 @@
 use serde_json::Value;
-use shared_std::settings::SanctumSettings;
+use shared_std::{
+    file_scanner::VerdictPolicy,
+    settings::{
+        ConfigReloadReport, EffectiveConfig, ExclusionMatch, ExclusionPreview, ExclusionPreviewRequest,
+        ExclusionRule, SanctumSettings,
+    },
+};
 
 use crate::ipc::IpcClient;
 
@@ -20,7 +26,8 @@ pub async fn settings_load_page_state() -> Result<String, ()> {
 pub async fn settings_update_settings(settings: String) -> Result<String, ()> {
     let settings: SanctumSettings = serde_json::from_str(&settings).unwrap();
 
-    match IpcClient::send_ipc::<String, _>("settings_update_settings", Some(settings)).await {
+    // the engine responds with an empty string on success, or a CommandResponse describing why the settings were rejected
+    match IpcClient::send_ipc::<Value, _>("settings_update_settings", Some(settings)).await {
         Ok(response) => return Ok(serde_json::to_string(&response).unwrap()),
         Err(e) => {
             eprintln!("[-] Error with IPC: {e}");
@@ -28,3 +35,68 @@ pub async fn settings_update_settings(settings: String) -> Result<String, ()> {
         }
     };
 }
+
+/// Tests whether the path would be excluded from scans by the saved exclusions, and by which rule
+#[tauri::command]
+pub async fn exclusions_test(path: String) -> Result<String, String> {
+    match IpcClient::send_ipc::<ExclusionMatch, _>("exclusions_test", Some(path)).await {
+        Ok(response) => Ok(serde_json::to_string(&response).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for exclusion test: {e}");
+            Err(format!("Could not test the path against the exclusions: {e}"))
+        }
+    }
+}
+
+/// Previews what adding the exclusion would have changed about an earlier scan: which of its
+/// detections it would have excluded, and how many files it would have kept out of the scan
+#[tauri::command]
+pub async fn exclusions_preview(rule: ExclusionRule, scan_id: u64) -> Result<String, String> {
+    let preview = IpcClient::send_ipc::<Result<ExclusionPreview, String>, _>(
+        "exclusions_preview",
+        Some(ExclusionPreviewRequest { rule, scan_id }),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&preview).unwrap())
+}
+
+/// Gets the settings the engine is running with, and whether each was read from the settings file
+/// or took its default
+#[tauri::command]
+pub async fn config_effective() -> Result<String, String> {
+    let config = IpcClient::send_ipc::<Result<EffectiveConfig, String>, Option<Value>>(
+        "config_effective",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&config).unwrap())
+}
+
+/// Has the engine read the settings file again after it was changed outside of the GUI, returning
+/// which changes were applied and which take effect once the engine restarts. An invalid file is
+/// refused, leaving the settings in use as they were.
+#[tauri::command]
+pub async fn config_reload() -> Result<String, String> {
+    let report = IpcClient::send_ipc::<Result<ConfigReloadReport, String>, Option<Value>>(
+        "config_reload",
+        None,
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Gets the order the engine resolves the verdicts of the detection engines in, where more than one detects a file
+#[tauri::command]
+pub async fn verdict_policy() -> Result<String, String> {
+    let policy = IpcClient::send_ipc::<VerdictPolicy, Option<Value>>("verdict_policy", None)
+        .await
+        .map_err(|e| format!("IPC error: {e}"))?;
+
+    Ok(serde_json::to_string(&policy).unwrap())
+}
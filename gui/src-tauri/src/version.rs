@@ -0,0 +1,151 @@
+//! Reports the versions of the running Sanctum components, for display to the user and inclusion in bug reports.
+
+use std::path::PathBuf;
+
+use serde_json::{to_value, Value};
+use shared_no_std::ipc::CommandResponse;
+use shared_std::version::{
+    HashAlgorithm, HashRequest, HashResult, SignatureCounts, SignatureDatabaseToggle,
+    SignatureExportReport, SignatureExportRequest, SignatureImportFormat, SignatureImportReport,
+    SignatureImportRequest, SignatureValidationReport, SignatureValidationRequest, VersionInfo,
+};
+
+use crate::ipc::IpcClient;
+
+#[tauri::command]
+pub async fn version_info() -> Result<String, String> {
+    match IpcClient::send_ipc::<VersionInfo, Option<Value>>("version_info", None).await {
+        Ok(info) => Ok(serde_json::to_string(&info).unwrap()),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for version info: {e}");
+            Err(format!("Could not get version information from the engine: {e}"))
+        }
+    }
+}
+
+/// Asks the engine to reload its signatures from disk, such as after adding a file to the signatures folder
+#[tauri::command]
+pub async fn signatures_reload() -> Result<String, String> {
+    match IpcClient::send_ipc::<Result<SignatureCounts, String>, Option<Value>>(
+        "signatures_reload",
+        None,
+    )
+    .await
+    {
+        Ok(Ok(counts)) => Ok(serde_json::to_string(&counts).unwrap()),
+        Ok(Err(e)) => Err(e),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for signature reload: {e}");
+            Err(format!("Could not reload signatures: {e}"))
+        }
+    }
+}
+
+/// Asks the engine to import signatures from a file in a third party format, such as a hash list or ClamAV hash
+/// signatures, returning how many were imported and skipped as JSON
+#[tauri::command]
+pub async fn signatures_import(format: SignatureImportFormat, path: String) -> Result<String, String> {
+    let request = SignatureImportRequest {
+        format,
+        path: PathBuf::from(path),
+    };
+
+    match IpcClient::send_ipc::<Result<SignatureImportReport, String>, _>(
+        "signatures_import",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    {
+        Ok(Ok(report)) => Ok(serde_json::to_string(&report).unwrap()),
+        Ok(Err(e)) => Err(e),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for signature import: {e}");
+            Err(format!("Could not import signatures: {e}"))
+        }
+    }
+}
+
+/// Asks the engine to export the signatures it matches against to a single file, which can be
+/// imported on another machine, returning what was exported as JSON
+#[tauri::command]
+pub async fn signatures_export(dest: String) -> Result<String, String> {
+    let request = SignatureExportRequest {
+        dest: PathBuf::from(dest),
+    };
+
+    match IpcClient::send_ipc::<Result<SignatureExportReport, String>, _>(
+        "signatures_export",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    {
+        Ok(Ok(report)) => Ok(serde_json::to_string(&report).unwrap()),
+        Ok(Err(e)) => Err(e),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for signature export: {e}");
+            Err(format!("Could not export signatures: {e}"))
+        }
+    }
+}
+
+/// Asks the engine to check a signature file without loading it, returning every problem found in
+/// it, with the line each is on, as JSON
+#[tauri::command]
+pub async fn signatures_validate(path: String) -> Result<String, String> {
+    let request = SignatureValidationRequest {
+        path: PathBuf::from(path),
+    };
+
+    match IpcClient::send_ipc::<Result<SignatureValidationReport, String>, _>(
+        "signatures_validate",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    {
+        Ok(Ok(report)) => Ok(serde_json::to_string(&report).unwrap()),
+        Ok(Err(e)) => Err(e),
+        Err(e) => {
+            eprintln!("[-] Error with IPC for signature validation: {e}");
+            Err(format!("Could not validate signatures: {e}"))
+        }
+    }
+}
+
+/// Enables or disables matching against the named signature database, which applies from the next file scanned
+#[tauri::command]
+pub async fn signatures_set_database_enabled(
+    name: String,
+    enabled: bool,
+) -> Result<String, String> {
+    let toggle = SignatureDatabaseToggle { name, enabled };
+
+    let response = IpcClient::send_ipc::<CommandResponse, _>(
+        "signatures_set_database_enabled",
+        Some(to_value(toggle).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))?;
+
+    match response.status.as_str() {
+        "success" => Ok(response.message),
+        _ => Err(response.message),
+    }
+}
+
+/// Hashes the file with each of the algorithms in the format used by signature files, returning the hashes as JSON
+#[tauri::command]
+pub async fn hash_file(path: String, algorithms: Vec<HashAlgorithm>) -> Result<String, String> {
+    let request = HashRequest {
+        path: PathBuf::from(path),
+        algorithms,
+    };
+
+    let hashes = IpcClient::send_ipc::<Result<Vec<HashResult>, String>, _>(
+        "hash_file",
+        Some(to_value(request).unwrap()),
+    )
+    .await
+    .map_err(|e| format!("IPC error: {e}"))??;
+
+    Ok(serde_json::to_string(&hashes).unwrap())
+}
@@ -10,6 +10,8 @@ pub static DRIVER_UM_NAME: &str = "\\\\.\\SanctumEDR"; // \\.\ sets device names
 pub static SYS_INSTALL_RELATIVE_LOC: &str = "sanctum.sys";
 pub static SVC_NAME: &str = "Sanctum";
 pub static PIPE_NAME: &str = r"\\.\pipe\sanctum_um_engine_pipe";
+/// Clients of this pipe are served as observers, which may only watch the engine
+pub static PIPE_NAME_OBSERVER: &str = r"\\.\pipe\sanctum_um_engine_observer_pipe";
 pub static PIPE_NAME_FOR_DRIVER: &str = r"\??\pipe\sanctum_um_engine_pipe";
 
 //
@@ -51,7 +53,40 @@ impl Display for SanctumVersion<'_> {
 //
 pub static SANC_SYS_FILE_LOCATION: &str = "Sanctum\\sanctum.sys";
 pub static IOC_LIST_LOCATION: &str = "Sanctum\\ioc_list.txt";
+pub static IOC_SECTION_LIST_LOCATION: &str = "Sanctum\\ioc_section_list.txt";
+pub static IOC_FUZZY_LIST_LOCATION: &str = "Sanctum\\ioc_fuzzy_list.txt";
+/// A folder of `.sig` signature files, merged into the signatures from the IOC lists
+pub static IOC_DIRECTORY_LOCATION: &str = "Sanctum\\signatures";
+pub static QUARANTINE_LOCATION: &str = "Sanctum\\quarantine";
+/// The manifest of quarantined files, within the quarantine folder
+pub static QUARANTINE_MANIFEST_NAME: &str = "manifest.json";
+pub static SCAN_HISTORY_LOCATION: &str = "Sanctum\\scan_history.json";
+/// When each threat in the scan history was seen, kept apart from the history so it outlives the scans being pruned
+pub static THREAT_TIMELINE_LOCATION: &str = "Sanctum\\threat_timeline.json";
+/// The results of the running scan, written as it runs so that they survive the engine crashing
+pub static SCAN_JOURNAL_LOCATION: &str = "Sanctum\\scan_journal.jsonl";
+/// The record of actions which could put the machine at risk, such as a quarantined sample being extracted
+pub static AUDIT_LOG_LOCATION: &str = "Sanctum\\audit_log.jsonl";
 pub static IOC_URL: &str =
     "https://raw.githubusercontent.com/0xflux/Sanctum/refs/heads/main/ioc_list.txt";
 pub static LOG_PATH: &str = r"logs\sanctum.log";
 pub static SANCTUM_DLL_RELATIVE_PATH: &str = "Sanctum\\sanctum.dll";
+
+//
+// IPC streaming constants
+//
+/// The largest single chunk a client may send when streaming content to the engine
+pub const IPC_STREAM_MAX_CHUNK_LEN: u32 = 1024 * 1024; // 1 mb
+/// The largest total content a client may stream to the engine for a single scan
+pub const IPC_STREAM_MAX_TOTAL_LEN: u64 = 512 * 1024 * 1024; // 512 mb
+/// How often the engine sends a heartbeat on an idle event subscription, so either side notices a dead connection
+pub const IPC_EVENT_HEARTBEAT_SECS: u64 = 15;
+/// The most connections the engine serves at once, including event subscriptions. Further clients wait until one
+/// disconnects.
+pub const IPC_MAX_CONCURRENT_CONNECTIONS: u32 = 16;
+/// The largest request the engine reads from a client. A request is read in a single read, so a longer request is cut
+/// short and fails to deserialise.
+pub const IPC_MAX_REQUEST_LEN: u32 = 1024;
+/// The most commands a client may send in a single `batch` request. The batch must also fit in
+/// [`IPC_MAX_REQUEST_LEN`].
+pub const IPC_MAX_BATCH_LEN: u32 = 16;
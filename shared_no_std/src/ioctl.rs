@@ -51,6 +51,12 @@ pub const SANC_IOCTL_DLL_SYSCALL: u32 =
 pub const SANC_IOCTL_SEND_BASE_ADDRS: u32 =
     CTL_CODE!(FILE_DEVICE_UNKNOWN, 0x808, METHOD_BUFFERED, FILE_ANY_ACCESS);
 
+pub const SANC_IOCTL_DRIVER_GET_VERSION: u32 =
+    CTL_CODE!(FILE_DEVICE_UNKNOWN, 0x809, METHOD_BUFFERED, FILE_ANY_ACCESS);
+
+pub const SANC_IOCTL_DRIVER_GET_QUEUE_STATS: u32 =
+    CTL_CODE!(FILE_DEVICE_UNKNOWN, 0x80A, METHOD_BUFFERED, FILE_ANY_ACCESS);
+
 // ****************** IOCTL MSG STRUCTS ******************
 
 #[repr(C)]
@@ -69,6 +75,19 @@ pub struct SancIoctlPing {
     pub capacity: usize,
 }
 
+/// The state of the driver's queue of events for usermode, copied back as is in response to
+/// `SANC_IOCTL_DRIVER_GET_QUEUE_STATS`. The counts are since the driver started.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DriverQueueStats {
+    /// Events queued for usermode
+    pub events_queued: u64,
+    /// Events which could not be queued, as they were raised above APC_LEVEL where the queue cannot be locked
+    pub events_dropped: u64,
+    /// Events queued which usermode has yet to collect
+    pub queue_depth: u64,
+}
+
 /// The capacity maximum for the u8 buffer for the ping protocol
 const SANC_IOCTL_PING_CAPACITY: usize = 256;
 
@@ -1,6 +1,6 @@
 extern crate alloc;
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 //
@@ -20,7 +20,7 @@ use serde_json::Value;
 ///
 /// ```
 /// // where IPC is of type IpcClient as implemented in the GUI.
-/// ipc.send_ipc::<(), Option<Value>>("scanner_cancel_scan", None).await
+/// ipc.send_ipc::<Result<u64, String>, Option<Value>>("scanner_cancel_scan", None).await
 /// ```
 ///
 /// ## Data of type A to send:
@@ -33,6 +33,94 @@ use serde_json::Value;
 pub struct CommandRequest {
     pub command: String,
     pub args: Option<Value>,
+    /// The codec the client would like the response encoded with. Requests themselves are always JSON so that
+    /// `Value` based args keep working and commands remain easy to debug.
+    #[serde(default)]
+    pub response_codec: CodecKind,
+    /// The role the client asks to be served with, which can only lessen the role of its connection
+    #[serde(default)]
+    pub role: ConnectionRole,
+}
+
+/// The role a client connects to the engine with. The engine binds the role to the connection: clients of
+/// [`PIPE_NAME_OBSERVER`](crate::constants::PIPE_NAME_OBSERVER) are always served as observers, whatever role their
+/// requests give, whereas a controller connection may ask for a request to be served as an observer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionRole {
+    /// May send any command, as the GUI does
+    #[default]
+    Controller,
+    /// May only subscribe to events and query the status of the engine, for dashboards and log shippers which must
+    /// not be able to change anything. Any other command is refused with [`STATUS_PERMISSION_DENIED`].
+    Observer,
+}
+
+/// The status of the `CommandResponse` to a command the client's role does not allow it to send, or which needs the
+/// client to be running as an administrator where it is not
+pub const STATUS_PERMISSION_DENIED: &str = "PermissionDenied";
+
+/// The codecs which may be used to encode an IPC response.
+///
+/// Where a client asks for anything other than `Json`, the server prefixes the response with the id of the codec
+/// it actually used, as not every command supports a binary encoding. A response to a `Json` request is sent
+/// untagged for compatibility.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodecKind {
+    #[default]
+    Json,
+    Postcard,
+}
+
+impl CodecKind {
+    pub const ID_JSON: u8 = 0x01;
+    pub const ID_POSTCARD: u8 = 0x02;
+
+    /// The tag byte which prefixes a response encoded with this codec
+    pub fn id(&self) -> u8 {
+        match self {
+            CodecKind::Json => Self::ID_JSON,
+            CodecKind::Postcard => Self::ID_POSTCARD,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            Self::ID_JSON => Some(CodecKind::Json),
+            Self::ID_POSTCARD => Some(CodecKind::Postcard),
+            _ => None,
+        }
+    }
+}
+
+/// The response to the `ipc_handshake` command, advertising the capabilities of the server so that a client
+/// can configure itself rather than hardcoding what the engine supports.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IpcHandshake {
+    /// Codecs the server is able to encode responses with
+    pub codecs: Vec<CodecKind>,
+    /// Commands whose responses can be encoded with a codec other than JSON
+    pub binary_commands: Vec<String>,
+    /// The role the server accepted the handshake with, which is the role of the connection unless the client asked
+    /// to be an observer
+    #[serde(default)]
+    pub role: ConnectionRole,
+    /// Commands a client connecting as an observer may send
+    #[serde(default)]
+    pub observer_commands: Vec<String>,
+    /// The most connections the server serves at once, for a client to size how many it opens. 0 where the server
+    /// did not advertise it.
+    #[serde(default)]
+    pub max_concurrent_connections: u32,
+    /// Whether the server accepts content streamed over the pipe (`scanner_scan_bytes`) and streams events
+    /// (`events_subscribe`)
+    #[serde(default)]
+    pub supports_streaming: bool,
+    /// The largest request, in bytes, the server reads. 0 where the server did not advertise it.
+    #[serde(default)]
+    pub max_request_len: u32,
+    /// The most commands the server serves in a single `batch` request. 0 where the server does not serve batches.
+    #[serde(default)]
+    pub max_batch_len: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -0,0 +1,158 @@
+//! Serialisation codecs for IPC responses between the usermode engine and its clients.
+//!
+//! JSON is used for commands as it keeps them easy to debug, however it is wasteful for high volume responses
+//! such as the live scan stats which are polled whilst a scan runs. Those commands may instead be answered with a
+//! compact binary codec if the client asks for one in its `CommandRequest`.
+
+use std::io;
+
+use serde::{Serialize, de::DeserializeOwned};
+use shared_no_std::ipc::CodecKind;
+
+/// A serialisation format which can be used for an IPC response
+pub trait Codec {
+    const KIND: CodecKind;
+
+    fn encode<T: Serialize>(value: &T) -> io::Result<Vec<u8>>;
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T>;
+}
+
+/// The default codec, which supports `serde_json::Value`
+pub struct Json;
+
+/// A compact binary codec. This does not support self describing types such as `serde_json::Value`, so both sides
+/// must agree on the concrete type being sent.
+pub struct Postcard;
+
+impl Codec for Json {
+    const KIND: CodecKind = CodecKind::Json;
+
+    fn encode<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+        serde_json::to_vec(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        serde_json::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Codec for Postcard {
+    const KIND: CodecKind = CodecKind::Postcard;
+
+    fn encode<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+        postcard::to_allocvec(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        postcard::from_bytes(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Encodes a value with the codec, prefixed by the codec's tag byte so the receiver knows how to decode it.
+pub fn encode_tagged<C: Codec, T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    let payload = C::encode(value)?;
+
+    let mut frame = Vec::with_capacity(payload.len() + 1);
+    frame.push(C::KIND.id());
+    frame.extend_from_slice(&payload);
+
+    Ok(frame)
+}
+
+/// Decodes a response which may either be tagged with a codec id, or be an untagged JSON response as sent for
+/// requests which asked for the JSON codec (or by a server which predates codec negotiation).
+pub fn decode_tagged<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    let Some((tag, payload)) = bytes.split_first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Empty IPC response.",
+        ));
+    };
+
+    match CodecKind::from_id(*tag) {
+        Some(CodecKind::Json) => Json::decode(payload),
+        Some(CodecKind::Postcard) => Postcard::decode(payload),
+        // no valid tag, so this is an untagged JSON response
+        None => Json::decode(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{hint::black_box, path::PathBuf, time::Instant};
+
+    use super::*;
+    use crate::events::{EngineEvent, EngineEventEnvelope};
+
+    /// The number of events encoded and decoded in each measurement
+    const EVENTS: u64 = 200_000;
+
+    /// The events sent most often to a subscriber whilst the engine is busy: telemetry from the driver, and the
+    /// progress of a running scan
+    fn event(seq: u64) -> EngineEventEnvelope {
+        let event = match seq % 2 {
+            0 => EngineEvent::Telemetry {
+                source: "driver".to_string(),
+                message: format!("Process {seq} started by 4242: C:\\Windows\\System32\\cmd.exe /c whoami"),
+            },
+            _ => EngineEvent::Progress {
+                scan_id: 1_760_440_000_000,
+                num_files_scanned: seq as u128 * 12,
+                bytes_scanned: seq * 4_096_000,
+                current_root: Some(PathBuf::from("C:\\Users\\Public\\Documents")),
+                current_file: None,
+            },
+        };
+
+        EngineEventEnvelope { seq, event }
+    }
+
+    #[test]
+    fn events_decode_from_each_codec() {
+        for seq in 0..2 {
+            let envelope = event(seq);
+
+            let postcard = encode_tagged::<Postcard, _>(&envelope).unwrap();
+            assert_eq!(decode_tagged::<EngineEventEnvelope>(&postcard).unwrap(), envelope);
+            let json = encode_tagged::<Json, _>(&envelope).unwrap();
+            assert_eq!(decode_tagged::<EngineEventEnvelope>(&json).unwrap(), envelope);
+            // as sent to a subscriber which asked for JSON
+            let untagged = serde_json::to_vec(&envelope).unwrap();
+            assert_eq!(decode_tagged::<EngineEventEnvelope>(&untagged).unwrap(), envelope);
+
+            assert!(postcard.len() < json.len());
+        }
+    }
+
+    /// Measures the size of each event and how many can be encoded and decoded a second with each codec. Run with
+    /// `cargo test --release -p shared_std -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn event_stream_throughput() {
+        fn measure<C: Codec>(events: &[EngineEventEnvelope]) {
+            let start = Instant::now();
+            let frames: Vec<Vec<u8>> = events.iter().map(|e| encode_tagged::<C, _>(black_box(e)).unwrap()).collect();
+            let encoding = start.elapsed();
+
+            let start = Instant::now();
+            for frame in &frames {
+                black_box(decode_tagged::<EngineEventEnvelope>(frame).unwrap());
+            }
+            let decoding = start.elapsed();
+
+            let bytes: usize = frames.iter().map(Vec::len).sum();
+            println!(
+                "{:?}: {:.1} bytes an event, {:.0} events encoded and {:.0} decoded a second, {:.1} MB a second",
+                C::KIND,
+                bytes as f64 / events.len() as f64,
+                events.len() as f64 / encoding.as_secs_f64(),
+                events.len() as f64 / decoding.as_secs_f64(),
+                bytes as f64 / encoding.as_secs_f64() / (1024.0 * 1024.0),
+            );
+        }
+
+        let events: Vec<EngineEventEnvelope> = (0..EVENTS).map(event).collect();
+        measure::<Json>(&events);
+        measure::<Postcard>(&events);
+    }
+}
@@ -0,0 +1,47 @@
+//! The clients connected to the engine's IPC server, reported by `connections_list` for diagnosing clients which are
+//! not getting what they expect, such as a GUI which is not receiving events.
+
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::EventCategory;
+
+/// How a client is connected to the engine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ConnectionKind {
+    /// The named pipe, used by the GUI and other local clients
+    Pipe,
+    /// Remote management over TLS, from the given address
+    Remote { peer: String },
+}
+
+/// A client connected to the engine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConnectionInfo {
+    /// The ID of the connection, which is the correlation ID its request is traced with
+    pub id: u64,
+    pub kind: ConnectionKind,
+    /// The command the client sent, or None where its request has yet to be read
+    pub command: Option<String>,
+    /// The event subscriptions the connection holds. A connection carries a single request, so this is empty unless
+    /// the request was `events_subscribe`.
+    pub subscriptions: Vec<EventSubscriptionInfo>,
+    pub connected_at: SystemTime,
+    /// Whether the client connected as an observer, which may only watch the engine
+    pub is_observer: bool,
+}
+
+/// An event subscription held by a connection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EventSubscriptionInfo {
+    /// The sequence number the subscriber asked to resume after, or None where it subscribed to new events only
+    pub after_seq: Option<u64>,
+    /// The sequence number of the last event sent to the subscriber, or passed over as not in its categories, which
+    /// falling behind the latest event means events are not reaching it
+    pub last_sent_seq: u64,
+    /// The categories of event the subscriber is sent, as it last asked
+    #[serde(default)]
+    pub categories: EventCategory,
+    pub subscribed_at: SystemTime,
+}
@@ -0,0 +1,132 @@
+//! Types for the engine's diagnostic self-test, the diagnostics bundle exported for bug reports, rotating the log, the
+//! engine's own resource usage, and the protection score.
+
+use std::{path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::driver_manager::DriverAvailability;
+
+/// The outcome of checking a single component during the self-test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComponentCheck {
+    pub passed: bool,
+    /// A human readable description of what was found, including why the check failed
+    pub detail: String,
+}
+
+/// A report of whether each component the detection pipeline relies upon is working
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelfTestReport {
+    /// The IOC list was loaded and contains signatures
+    pub signature_db: ComponentCheck,
+    /// The EICAR test pattern was detected when scanned
+    pub detection: ComponentCheck,
+    /// The driver responded to an IOCTL
+    pub driver: ComponentCheck,
+    /// A file could be written to, and removed from, the quarantine folder
+    pub quarantine: ComponentCheck,
+}
+
+/// The outcome of warming the engine up ahead of its first scan
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WarmupReport {
+    /// Whether the engine is ready to scan, the signatures being loaded and the test pattern detected. The driver is
+    /// not required, as scanning works without it.
+    pub ready: bool,
+    /// Whether the driver could be reached once probed
+    pub driver: DriverAvailability,
+    pub self_test: SelfTestReport,
+    /// How long the warmup took
+    pub elapsed_ms: u64,
+}
+
+/// The engine's own use of the machine's resources
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResourceUsage {
+    /// The share of the machine's CPU the engine used over a short sample, from 0 to 100 across all cores
+    pub cpu_percent: f64,
+    pub working_set_bytes: u64,
+    pub handle_count: u32,
+    pub thread_count: u32,
+    /// The scans in progress, as listed by `scans_list`
+    pub open_scans: usize,
+}
+
+/// Something about the machine which counts towards the protection score
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ProtectionFactorKind {
+    /// Real-time protection is not snoozed, and blocks or quarantines the files it detects
+    RealtimeProtection,
+    /// The driver is loaded, without which real-time protection cannot see files being opened
+    DriverLoaded,
+    /// The signatures were updated recently
+    SignaturesUpToDate,
+    /// A scan of a whole volume finished recently
+    RecentFullScan,
+    /// No detected file has been left in place, other than those triaged as benign
+    NoOutstandingDetections,
+}
+
+/// How one factor counted towards the protection score
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProtectionFactor {
+    pub kind: ProtectionFactorKind,
+    /// The most the factor can add to the score
+    pub weight: u32,
+    /// What the factor added to the score, from none up to its weight
+    pub points: u32,
+    /// A human readable description of what was found
+    pub detail: String,
+    /// What the user can do to earn the rest of the factor's weight, or None where it scored in full
+    pub improvement: Option<String>,
+}
+
+/// A single number summarising how well the machine is protected, as reported by `protection_score`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProtectionScore {
+    /// From 0 to 100, being the points of every factor added up
+    pub score: u32,
+    pub factors: Vec<ProtectionFactor>,
+}
+
+/// A request to export a diagnostics bundle, a zip archive the user can attach to a bug report
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosticsExportRequest {
+    /// The file to write the archive to, which is replaced if it exists
+    pub dest: PathBuf,
+    /// Leaves file paths and the user name in the bundle. Without this they are replaced, so the bundle does not
+    /// reveal the names of the user's files.
+    #[serde(default)]
+    pub full_detail: bool,
+}
+
+/// A summary of a diagnostics bundle which was written
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosticsExportReport {
+    pub file: PathBuf,
+    pub bytes_written: u64,
+    /// Whether file paths and the user name were redacted
+    pub redacted: bool,
+    /// The names of the files in the archive
+    pub contents: Vec<String>,
+}
+
+/// The outcome of rotating the engine's log with `logs_rotate`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogRotation {
+    /// The file the log was written to up to the rotation, which is no longer written to
+    pub rotated: PathBuf,
+    /// The fresh file the log is written to from the rotation
+    pub current: PathBuf,
+    pub rotated_at: SystemTime,
+}
+
+impl SelfTestReport {
+    pub fn all_passed(&self) -> bool {
+        self.signature_db.passed
+            && self.detection.passed
+            && self.driver.passed
+            && self.quarantine.passed
+    }
+}
@@ -1,5 +1,10 @@
 use shared_no_std::driver_ipc::ProcessStarted;
-use std::mem::take;
+use std::{
+    mem::take,
+    time::{Duration, SystemTime},
+};
+
+use crate::settings::RealtimePolicy;
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum DriverState {
@@ -9,6 +14,48 @@ pub enum DriverState {
     Stopped(String),
 }
 
+/// The status of the `CommandResponse` returned by commands which need the driver whilst it is unavailable
+pub const STATUS_DRIVER_UNAVAILABLE: &str = "DriverUnavailable";
+
+/// Whether the engine can communicate with the driver. Whilst it cannot, the engine runs degraded; on-demand scanning
+/// still works, but real-time protection, telemetry and process monitoring, which rely on the driver, do not.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum DriverAvailability {
+    Available,
+    /// The driver could not be reached, with a description of why for the user
+    Unavailable { reason: String },
+}
+
+/// The policy real-time protection applies, as reported by `realtime_status`. Real-time protection relies on the driver,
+/// so the policy only takes effect whilst the driver is available.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RealtimeStatus {
+    pub policy: RealtimePolicy,
+    pub driver: DriverAvailability,
+    /// The time left until real-time protection resumes where it has been snoozed, None where it is not
+    #[serde(default)]
+    pub snooze_remaining: Option<Duration>,
+}
+
+/// How well the engine is keeping up with the events the driver raises, as reported by `driver_stats`. An event which is
+/// dropped is telemetry which nothing can be detected from, so drops by the driver and by the engine are each counted.
+///
+/// The counts from the driver are since the driver started, and those of the engine since the engine started.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DriverStats {
+    /// Events the engine has collected from the driver
+    pub events_received: u64,
+    /// Events the driver dropped rather than queueing for the engine, None where the driver could not be queried
+    pub events_dropped: Option<u64>,
+    /// Events queued by the driver which the engine has yet to collect, None where the driver could not be queried
+    pub queue_depth: Option<u64>,
+    /// Events the engine dropped itself, such as syscalls reported by the injected DLL whilst the driver was
+    /// unavailable to pass them on to
+    pub usermode_dropped: u64,
+    /// When the engine last collected an event from the driver
+    pub last_event_time: Option<SystemTime>,
+}
+
 /// A structure to hold data from kernel debug messaging for use in usermode applications.
 /// Data can be enqueued and dequeued from a vector as required.
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
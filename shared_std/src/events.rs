@@ -0,0 +1,159 @@
+//! Events raised by the engine, delivered to subscribers over a dedicated event channel rather than in response to a
+//! command.
+
+use std::{path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_scanner::{FileProgress, MatchedIOC, ScanStarted};
+
+/// An event raised by the engine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum EngineEvent {
+    /// A scan which estimates its size first has begun its estimate. It is followed by a `ScanStarted` event with the
+    /// estimate once scanning begins, unless the scan is cancelled whilst still estimating.
+    ScanEstimating { scan_id: u64, targets: Vec<PathBuf> },
+    /// A scan of files, folders, a volume or a disk image was started
+    ScanStarted { started: ScanStarted },
+    /// Malware was detected during a scan
+    Detection {
+        scan_id: Option<u64>,
+        ioc: MatchedIOC,
+    },
+    /// The progress of a running scan, raised periodically whilst it runs
+    Progress {
+        scan_id: u64,
+        num_files_scanned: u128,
+        bytes_scanned: u64,
+        /// The root of the scan being walked, for a folder scan
+        #[serde(default)]
+        current_root: Option<PathBuf>,
+        /// How far the scan has got through the file being hashed, where it is large enough to take a while
+        #[serde(default)]
+        current_file: Option<FileProgress>,
+    },
+    /// A removable volume, such as a USB stick, was inserted and a scan of it was started. The scan is also announced
+    /// by a `ScanStarted` event.
+    RemovableMediaScanStarted {
+        drive: String,
+        serial: String,
+        started: ScanStarted,
+    },
+    /// A scan was cancelled as it reached its time limit; the results found before it was cancelled are kept
+    ScanTimedOut { scan_id: u64, max_duration: Duration },
+    /// Telemetry from a component of the EDR, such as messages from the driver
+    Telemetry { source: String, message: String },
+    /// An attempt to tamper with the EDR was detected
+    Tamper { description: String },
+    /// A notification intended to be shown to the user. Where it is about a scan, such as a detection, opening the
+    /// notification shows the results of the scan.
+    Notify {
+        title: String,
+        message: String,
+        #[serde(default)]
+        scan_id: Option<u64>,
+    },
+    /// Real-time protection was snoozed for the duration, after which it resumes by itself
+    RealtimeSnoozed { duration: Duration },
+    /// Real-time protection resumed after being snoozed, early where it was resumed before the snooze ran out
+    RealtimeResumed { early: bool },
+}
+
+impl EngineEvent {
+    /// The category of the event, which a subscriber filters the events it is sent by
+    pub fn category(&self) -> EventCategory {
+        match self {
+            EngineEvent::Detection { .. } => EventCategory::DETECTION,
+            EngineEvent::ScanEstimating { .. }
+            | EngineEvent::ScanStarted { .. }
+            | EngineEvent::Progress { .. }
+            | EngineEvent::RemovableMediaScanStarted { .. }
+            | EngineEvent::ScanTimedOut { .. } => EventCategory::PROGRESS,
+            EngineEvent::Telemetry { .. } => EventCategory::TELEMETRY,
+            EngineEvent::Tamper { .. } => EventCategory::TAMPER,
+            EngineEvent::Notify { .. } => EventCategory::NOTIFY,
+            EngineEvent::RealtimeSnoozed { .. } | EngineEvent::RealtimeResumed { .. } => EventCategory::SYSTEM,
+        }
+    }
+}
+
+/// A set of bit flags of the categories of event a subscriber is sent, so that a client which only shows some events
+/// is not sent the rest; by default a subscriber is sent every category.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(transparent)]
+pub struct EventCategory(pub u32);
+
+impl EventCategory {
+    /// `Detection` events
+    pub const DETECTION: EventCategory = EventCategory(1 << 0);
+    /// Scans starting, progressing and timing out
+    pub const PROGRESS: EventCategory = EventCategory(1 << 1);
+    /// `Telemetry` events, such as messages from the driver
+    pub const TELEMETRY: EventCategory = EventCategory(1 << 2);
+    /// `Tamper` events
+    pub const TAMPER: EventCategory = EventCategory(1 << 3);
+    /// `Notify` events, intended to be shown to the user
+    pub const NOTIFY: EventCategory = EventCategory(1 << 4);
+    /// Changes to the state of the engine itself, such as real-time protection being snoozed and resumed
+    pub const SYSTEM: EventCategory = EventCategory(1 << 5);
+
+    pub const ALL: EventCategory = EventCategory(
+        Self::DETECTION.0 | Self::PROGRESS.0 | Self::TELEMETRY.0 | Self::TAMPER.0 | Self::NOTIFY.0 | Self::SYSTEM.0,
+    );
+    pub const NONE: EventCategory = EventCategory(0);
+
+    pub fn contains(&self, other: EventCategory) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether any bits are set which do not correspond to a known category
+    pub fn has_unknown_bits(&self) -> bool {
+        self.0 & !Self::ALL.0 != 0
+    }
+}
+
+impl Default for EventCategory {
+    fn default() -> Self {
+        EventCategory::ALL
+    }
+}
+
+impl std::ops::BitOr for EventCategory {
+    type Output = EventCategory;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        EventCategory(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for EventCategory {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// An event along with its sequence number. Sequence numbers increase by one for each event raised, so a subscriber
+/// can resume from the last event it saw and can tell when it has missed events.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EngineEventEnvelope {
+    pub seq: u64,
+    pub event: EngineEvent,
+}
+
+/// The args of an `events_subscribe` request
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct EventSubscribeArgs {
+    /// The sequence number of the last event the subscriber received, so that events raised whilst it was
+    /// disconnected are replayed. None subscribes to new events only.
+    pub after_seq: Option<u64>,
+    /// The categories of event to be sent, which can be changed whilst subscribed with `subscribe_update`
+    #[serde(default)]
+    pub categories: EventCategory,
+}
+
+/// The args of a `subscribe_update` request, sent by a subscriber over its subscription to change the categories of
+/// event it is sent from then on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct EventSubscriptionUpdate {
+    pub categories: EventCategory,
+}
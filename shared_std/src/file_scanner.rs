@@ -1,22 +1,861 @@
-use std::{io, path::PathBuf, time::Duration};
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
 
 use serde::{Deserialize, Serialize};
 
+use crate::{history::DetectionAnnotation, settings::ExclusionMatch};
+
 /// The state of the scanner either Scanning or Inactive. If the scanner is scanning, then it contains
 /// further information about the live-time information such as how many files have been scanned and time taken so far.
-#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+///
+/// A newly constructed scanner is Inactive, so the state is always valid to query from the moment the engine starts.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize, Default)]
 pub enum FileScannerState {
     Scanning,
     Finished,
     FinishedWithError(String),
+    #[default]
     Inactive,
     Cancelled,
 }
 
+impl FileScannerState {
+    /// The name of the state, without any data it carries, for clients which only need to know which state the
+    /// scanner is in
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileScannerState::Scanning => "Scanning",
+            FileScannerState::Finished => "Finished",
+            FileScannerState::FinishedWithError(_) => "FinishedWithError",
+            FileScannerState::Inactive => "Inactive",
+            FileScannerState::Cancelled => "Cancelled",
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub enum ScanType {
     File,
     Folder,
+    /// A full walk of a volume from its root
+    Volume,
+    /// A walk of the volumes of a disk image, which is mounted read only for the length of the scan. The targets of
+    /// the scan are the roots of the mounted volumes.
+    Image { image: PathBuf },
+    /// A scan of the executable a running process was started from. The target of the scan is the executable.
+    Process { pid: u32 },
+    /// A scan of files found on the system rather than given as targets, such as the binaries started from the
+    /// autorun locations. The scan has no targets, and the kind says what was scanned.
+    System { kind: String },
+}
+
+/// What a scan is of, as given to `scanner_start_scan`. Each kind of target is walked differently, so the target says
+/// which is meant rather than it being guessed from a path.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum ScanTarget {
+    /// A single file
+    File(PathBuf),
+    /// One or more folders, each walked in full in turn, such as the common scan areas of a quick scan
+    Folder(Vec<PathBuf>),
+    /// A whole volume by its drive letter, such as `D`, `D:` or `D:\`, walked from its root skipping the folders
+    /// reserved by the system
+    Volume(String),
+    /// A disk image (VHD, VHDX or ISO), mounted read only for the length of the scan and each of its volumes walked
+    Image(PathBuf),
+    /// The executable a running process was started from, by the process ID. The memory of the process is not
+    /// scanned.
+    Process(u32),
+    /// Content streamed over the connection which never touches the disk, by the name it is reported under. The
+    /// content is streamed as it is for `scanner_scan_bytes`.
+    Bytes(String),
+}
+
+/// A request to start a scan of the target
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScanTargetRequest {
+    pub target: ScanTarget,
+    #[serde(default)]
+    pub options: ScanOptions,
+}
+
+/// Information about a scan which has just been started, sent as soon as the scan begins so the GUI can move into
+/// its scanning view and track the scan by its ID.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScanStarted {
+    pub scan_id: u64,
+    pub targets: Vec<PathBuf>,
+    pub mode: ScanType,
+    /// The number of files expected to be scanned, where a pre-pass over the targets has been made
+    pub estimated_file_count: Option<u64>,
+    /// Paths skipped by default for this scan, such as the folders reserved by the system at the root of a volume.
+    /// These are in addition to the user's exclusions.
+    pub skipped: Vec<PathBuf>,
+    /// What the scan was asked to scan, of which the targets are the paths walked
+    #[serde(default)]
+    pub target: Option<ScanTarget>,
+}
+
+/// A scan which is in progress, as listed by `scans_list`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScanStatus {
+    pub scan_id: u64,
+    pub mode: ScanType,
+    pub targets: Vec<PathBuf>,
+    #[serde(default)]
+    pub target: Option<ScanTarget>,
+    pub progress: ScanProgress,
+    pub started_at: Option<SystemTime>,
+    pub state: FileScannerState,
+}
+
+/// How far a scan in progress has got
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct ScanProgress {
+    pub num_files_scanned: u128,
+    pub bytes_scanned: u64,
+    /// The number of files expected to be scanned, where a pre-pass over the targets has been made
+    pub estimated_file_count: Option<u64>,
+    /// The root of the scan being walked, for a folder scan
+    #[serde(default)]
+    pub current_root: Option<PathBuf>,
+    /// How far the scan has got through each of its roots, for a folder scan
+    #[serde(default)]
+    pub roots: Vec<RootProgress>,
+    /// How far the scan has got through the file being hashed, where it is large enough to take a while
+    #[serde(default)]
+    pub current_file: Option<FileProgress>,
+}
+
+/// How far a scan has got through hashing a single large file, so a scan of one huge file is seen to be moving
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct FileProgress {
+    pub path: PathBuf,
+    pub bytes_hashed: u64,
+    /// The size of the file when the scan opened it
+    pub total_bytes: u64,
+}
+
+/// How far a folder scan has got through one of the folders it was started on
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RootProgress {
+    pub root: PathBuf,
+    pub state: RootState,
+    /// Files scanned beneath the root so far
+    pub num_files_scanned: u64,
+}
+
+/// Roots are walked one at a time, in the order they were given
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum RootState {
+    Pending,
+    Scanning,
+    Finished,
+}
+
+/// Options which apply to a single scan
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct ScanOptions {
+    /// The longest the scan may run for. Once reached, the scan is cancelled as a user cancel would, keeping the
+    /// results found so far. None lets the scan run to completion.
+    #[serde(default)]
+    pub max_duration: Option<Duration>,
+    /// What is done with each file detected as malicious
+    #[serde(default)]
+    pub action: DetectionAction,
+    /// Thresholds overriding those in the settings for this scan only. None uses the settings.
+    #[serde(default)]
+    pub thresholds: Option<DetectionThresholds>,
+    /// Scans only files whose content shows they can run, such as executables, scripts and installers, skipping
+    /// documents and media. Exclusions still apply to the files which are scanned.
+    #[serde(default)]
+    pub executables_only: bool,
+    /// Walks folders and files on network and remote volumes, such as a UNC path or a mapped network drive, where the
+    /// settings would otherwise skip them
+    #[serde(default)]
+    pub include_remote_volumes: bool,
+    /// Scans only files changed since this time, skipping the rest before they are read, such as for a daily scan of
+    /// what changed since the last. A file counts as changed by the later of when it was modified and when it was
+    /// created, so a file copied in with its old modified time is still scanned. Files from up to
+    /// [`MODIFIED_SINCE_GRACE`] before the time are scanned too, in case the clock of the machine which wrote them was
+    /// behind. Unlike the cache of verdicts, nothing is kept between scans.
+    #[serde(default)]
+    pub modified_since: Option<SystemTime>,
+}
+
+/// How long before a scan's `modified_since` a file may have changed and still be scanned, allowing for the clock of
+/// the machine which wrote it, such as a file server, being behind
+pub const MODIFIED_SINCE_GRACE: Duration = Duration::from_secs(5 * 60);
+
+/// Detection thresholds for a single scan, such as to make an investigative scan more sensitive without changing the
+/// settings. A threshold left as None is taken from the settings. Only the thresholds are overridden, so an engine
+/// which is turned off in the settings stays off.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub struct DetectionThresholds {
+    /// The total heuristic score at which a file is reported
+    #[serde(default)]
+    pub heuristic_score: Option<u32>,
+    /// The similarity (1 - 100) at which a file is reported as similar to a fuzzy hash signature
+    #[serde(default)]
+    pub fuzzy_similarity: Option<u32>,
+}
+
+/// What the scanner does with a file once it is detected as malicious
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum DetectionAction {
+    /// The detection is only reported, the file is left in place
+    #[default]
+    Report,
+    /// The file is moved into the quarantine store, from where it can be restored
+    Quarantine,
+    /// The file is deleted
+    Delete,
+}
+
+/// What was done with a file detected as malicious, so a detection can be seen to have actually been handled
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub enum ActionResult {
+    /// No action was taken, as the scan only reports detections
+    #[default]
+    None,
+    /// The file was moved into the quarantine store under the ID of its entry
+    Quarantined { id: String },
+    /// The file is in use so could not be deleted now, and will be deleted when Windows next starts
+    DeletePending,
+    Deleted,
+    /// The action could not be taken, and the file remains in place
+    ActionFailed(String),
+}
+
+/// A request to scan files and folders
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScanRequest {
+    pub targets: Vec<PathBuf>,
+    #[serde(default)]
+    pub options: ScanOptions,
+}
+
+/// A request to scan a whole volume
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct VolumeScanRequest {
+    /// The drive letter of the volume, such as `D`, `D:` or `D:\`
+    pub drive: String,
+    #[serde(default)]
+    pub options: ScanOptions,
+}
+
+/// A request to scan the contents of a disk image (VHD, VHDX or ISO)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ImageScanRequest {
+    pub image: PathBuf,
+    #[serde(default)]
+    pub options: ScanOptions,
+}
+
+/// The reason given when a scan is cancelled by the user
+pub const CANCEL_REASON_USER: &str = "cancelled by user";
+/// The reason given when a scan is cancelled because it reached its `max_duration`
+pub const CANCEL_REASON_TIME_LIMIT: &str = "time limit reached";
+/// The reason given when a scan is cancelled by a request to stop all activity
+pub const CANCEL_REASON_STOP_ALL: &str = "stopped by stop all";
+/// The reason given when a scan is cancelled so that an update can be applied
+pub const CANCEL_REASON_UPDATE: &str = "stopped for an update";
+
+/// A request to inject a made up detection of the file through the engine's detection pipeline, as if a scan had
+/// found it, for testing the alerting path end to end without real malware. Only an engine built with the `test_mode`
+/// feature serves these.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SimulatedDetectionRequest {
+    pub path: PathBuf,
+    /// The name the detection is reported under, as the name of a signature would be
+    pub name: String,
+    /// The CEF severity of the detection, from 0 - 10
+    pub severity: u8,
+    /// What is done with the file. Report, the default, leaves the file in place.
+    #[serde(default)]
+    pub action: DetectionAction,
+}
+
+/// The highest severity a simulated detection can be given, as severities follow CEF
+pub const MAX_SIMULATED_SEVERITY: u8 = 10;
+
+/// How the sizes of the synthetic files of a benchmark are spread about their average
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum SizeDistribution {
+    /// Every file is of the average size
+    #[default]
+    Fixed,
+    /// Sizes are spread evenly from empty to twice the average
+    Uniform,
+    /// Most files are small with a few far larger, as on a real disk
+    Exponential,
+}
+
+/// A request to measure how fast the engine scans, over synthetic files generated in memory so that no tree of files
+/// need be prepared. Only an engine built with the `test_mode` feature serves these.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BenchmarkRequest {
+    pub file_count: u64,
+    /// The average size of a file, in bytes
+    pub avg_size: u64,
+    #[serde(default)]
+    pub distribution: SizeDistribution,
+}
+
+/// The most files a benchmark may generate
+pub const MAX_BENCHMARK_FILES: u64 = 1_000_000;
+/// The most bytes a benchmark may generate across all of its files, so that it finishes in minutes
+pub const MAX_BENCHMARK_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+
+/// How fast the engine scanned the synthetic files of a benchmark. The time taken to generate the files is not
+/// counted.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BenchmarkReport {
+    pub file_count: u64,
+    pub bytes_scanned: u64,
+    pub elapsed: Duration,
+    pub files_per_sec: f64,
+    pub mb_per_sec: f64,
+    /// The time spent in each detection engine which ran over the files
+    pub engines: Vec<EngineTiming>,
+}
+
+/// The time a detection engine spent over the files of a benchmark
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct EngineTiming {
+    /// The name of the engine, as named in the verdict precedence
+    pub engine: String,
+    /// The time spent hashing the content
+    pub hashing: Duration,
+    /// The time spent matching the hash against the signatures, including finishing the hash
+    pub matching: Duration,
+}
+
+/// The most detections returned in one page by `scanner_results_page`
+pub const MAX_RESULTS_PAGE_LEN: usize = 500;
+
+/// The most files and folders a scan lists as inaccessible; beyond this they are only counted
+pub const MAX_INACCESSIBLE_LISTED: usize = 1000;
+
+/// The most files a scan lists as skipped as they were in use; beyond this they are only counted
+pub const MAX_IN_USE_LISTED: usize = 1000;
+
+/// A request for a page of the detections of a scan
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ResultsPageRequest {
+    pub scan_id: u64,
+    /// The index of the first detection of the page
+    pub offset: usize,
+    /// The most detections to return, capped at [`MAX_RESULTS_PAGE_LEN`]
+    pub limit: usize,
+}
+
+/// A page of the detections of a scan. Detections are in the order the scan found them; a running scan only adds
+/// to the end, so a page once read never shifts, and the next page starts at `offset` plus the length of `results`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ResultsPage {
+    pub scan_id: u64,
+    pub offset: usize,
+    /// The number of detections of the scan so far
+    pub total: usize,
+    pub results: Vec<MatchedIOC>,
+    /// Whether the scan has ended, so `total` is final. Otherwise more detections may follow.
+    pub complete: bool,
+}
+
+/// The status of the response to an on-demand request which the engine refused as it is busy
+pub const STATUS_ENGINE_BUSY: &str = "EngineBusy";
+
+/// The response to an on-demand request refused as the engine has no capacity for it, such as whilst a large scan is
+/// running, rather than leaving the request queued behind the work already running. It has the status and message of
+/// a `CommandResponse`, so a client which only understands those still sees why the request failed.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct EngineBusy {
+    pub status: String,
+    pub message: String,
+    /// How long the client should wait before sending the request again
+    pub retry_after_secs: u64,
+}
+
+/// An estimate of how much a scan of the targets would cover, made by walking them without reading any file
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct ScanEstimate {
+    /// Files which would be scanned, excluding those skipped by the exclusions
+    pub file_count: u64,
+    /// The total size of the files which would be scanned
+    pub total_bytes: u64,
+    /// Files and folders skipped by the exclusions. An excluded folder counts once, as it is not walked.
+    pub skipped_by_exclusions: u64,
+    /// Whether the estimate was cancelled before the walk finished, in which case the totals are what was found so far
+    pub cancelled: bool,
+}
+
+/// A summary of what was stopped by a request to stop all activity
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct StopAllSummary {
+    /// The IDs of the scans which were cancelled. Empty when nothing was running.
+    pub scans_cancelled: Vec<u64>,
+}
+
+/// The scans cancelled by `scanner_cancel_by_target`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct CancelledByTarget {
+    /// How many scans were cancelled, which is 0 where no running scan had the path as a target
+    pub cancelled: usize,
+    pub scan_ids: Vec<u64>,
+}
+
+/// How an autorun entry is started by Windows
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum AutorunMechanism {
+    /// A value under a Run or RunOnce registry key, run when the user logs on
+    RegistryRun { key: String, value: String },
+    /// A file or shortcut in a Startup folder, run when the user logs on
+    StartupFolder { file: PathBuf },
+    /// The action of a scheduled task
+    ScheduledTask { name: String },
+    /// A service or driver, started by the service control manager
+    Service { name: String },
+}
+
+/// An entry in one of the autorun locations, and the binary it starts
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct AutorunEntry {
+    pub mechanism: AutorunMechanism,
+    /// The command line or path as it is configured in the autorun location
+    pub command: String,
+    /// The binary the entry starts, or None if the command could not be resolved to a file on disk
+    pub image: Option<PathBuf>,
+}
+
+/// Malware found in a binary started from an autorun location, paired with the entry which starts it
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct AutorunDetection {
+    pub entry: AutorunEntry,
+    pub ioc: MatchedIOC,
+}
+
+/// The results of a scan of the autorun locations
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct AutorunScanResult {
+    pub scan_id: u64,
+    /// The number of autorun entries found
+    pub entries_found: usize,
+    /// The number of distinct binaries scanned; several entries may start the same binary, such as svchost.exe
+    pub binaries_scanned: usize,
+    pub detections: Vec<AutorunDetection>,
+    /// Entries whose binary kept changing whilst it was scanned, so could not be verified as clean
+    pub changed_during_scan: Vec<AutorunEntry>,
+    /// Entries whose command could not be resolved to a binary, so were not scanned. A binary which no longer
+    /// exists is common for software which was uninstalled, but may be worth reviewing.
+    pub unresolved: Vec<AutorunEntry>,
+    /// Whether the scan was cancelled before every entry was scanned
+    pub cancelled: bool,
+}
+
+/// A browser whose extensions are scanned
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Browser {
+    Chrome,
+    Edge,
+    Brave,
+    Firefox,
+}
+
+/// An extension installed into a browser profile
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BrowserExtension {
+    pub browser: Browser,
+    /// The Windows user whose profile the browser profile is in
+    pub user: String,
+    /// The browser profile, such as `Default` or `Profile 1`
+    pub profile: String,
+    pub extension_id: String,
+    /// The name from the manifest, which may be a placeholder such as `__MSG_appName__` for an extension translated
+    /// into several languages. None where the manifest could not be read.
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// The folder the extension is unpacked into, or its archive
+    pub path: PathBuf,
+    /// The dangerous permissions the manifest requests, such as access to every site or to the browser's cookies
+    pub dangerous_permissions: Vec<String>,
+}
+
+/// Malware found in a file of a browser extension, paired with the extension it belongs to
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BrowserExtensionDetection {
+    pub extension: BrowserExtension,
+    pub ioc: MatchedIOC,
+}
+
+/// The results of a scan of the browser extensions of each user
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BrowserExtensionScanResult {
+    pub scan_id: u64,
+    /// The number of extensions found, counting each version of an extension which is installed
+    pub extensions_found: usize,
+    pub files_scanned: usize,
+    pub detections: Vec<BrowserExtensionDetection>,
+    /// Extensions requesting dangerous permissions, which are worth reviewing whether or not any of their files were
+    /// detected
+    pub flagged: Vec<BrowserExtension>,
+    /// Files which kept changing whilst they were scanned, so could not be verified as clean
+    pub changed_during_scan: Vec<PathBuf>,
+    /// Whether the scan was cancelled before every extension was scanned
+    pub cancelled: bool,
+}
+
+/// A kernel driver loaded on the machine
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct DriverInfo {
+    /// The file name of the driver, such as `ntfs.sys`
+    pub name: String,
+    /// The file the driver was loaded from, or None where its path could not be resolved
+    pub path: Option<PathBuf>,
+    /// Whether the file is signed, embedded in the file or through a catalog, whether or not the signature is trusted
+    pub signed: bool,
+    /// The subject of the certificate the file was signed with, where it could be read
+    pub signer: Option<String>,
+    /// Whether the signature verifies and chains to a root the machine trusts. A driver which is not trusted is
+    /// flagged, as Windows only loads one where driver signature enforcement has been turned off or got around.
+    pub trusted: bool,
+    /// When the engine first saw the driver loaded, which is shortly after it was loaded. Windows does not keep when
+    /// each driver was loaded, so this is None for a driver which was already loaded when the engine started.
+    pub load_time: Option<SystemTime>,
+}
+
+/// A detection of the file of a loaded driver
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct DriverDetection {
+    pub driver: DriverInfo,
+    pub ioc: MatchedIOC,
+}
+
+/// The results of a scan of the files of the loaded drivers
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct DriverScanResult {
+    pub scan_id: u64,
+    /// The number of drivers loaded
+    pub drivers_found: usize,
+    pub files_scanned: usize,
+    pub detections: Vec<DriverDetection>,
+    /// Drivers which are unsigned or whose signature is not trusted, which are worth reviewing whether or not their
+    /// file was detected
+    pub flagged: Vec<DriverInfo>,
+    /// Drivers whose file kept changing whilst it was scanned, so could not be verified as clean
+    pub changed_during_scan: Vec<DriverInfo>,
+    /// Drivers whose file could not be found, so were not scanned. A loaded driver with no file on disk may have been
+    /// deleted to hide it.
+    pub unresolved: Vec<DriverInfo>,
+    /// Whether the scan was cancelled before every driver was scanned
+    pub cancelled: bool,
+}
+
+/// Why a scheduled task looks suspicious, whether or not any file it runs was detected
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum TaskIndicator {
+    /// An action runs PowerShell with an encoded command, which hides what it runs
+    EncodedPowerShell,
+    /// An action runs from, or is given a file in, a temporary folder, which software rarely runs from on a schedule
+    RunsFromTemp,
+    /// An action is given a URL, as a living off the land binary such as mshta or regsvr32 is to fetch its payload
+    RemotePayload,
+    /// The task is set to be hidden from the Task Scheduler
+    Hidden,
+    /// The security descriptor of the task has been removed from the registry, which hides it from the Task
+    /// Scheduler and from `schtasks` entirely
+    NoSecurityDescriptor,
+}
+
+/// A program a scheduled task starts
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TaskAction {
+    /// The program as it is configured in the task
+    pub command: String,
+    pub arguments: String,
+    /// The files the action runs: the binary it starts and any file given to it, such as the script given to
+    /// PowerShell. Empty where none could be resolved to a file on disk.
+    pub files: Vec<PathBuf>,
+}
+
+/// A task registered with the Task Scheduler
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScheduledTask {
+    /// The path of the task in the Task Scheduler, such as `\Microsoft\Windows\Defrag\ScheduledDefrag`
+    pub name: String,
+    /// What starts the task, by the kind of each of its triggers, such as `Logon` or `Calendar`. Empty for a task
+    /// which only runs when it is started by hand or by another program.
+    pub triggers: Vec<String>,
+    /// The account or group the task runs as, where it is given
+    pub run_as: Option<String>,
+    pub enabled: bool,
+    /// The programs the task starts. Actions which do not start a program, such as a COM handler, are not listed.
+    pub actions: Vec<TaskAction>,
+    /// Why the task looks suspicious, empty where nothing about it does
+    pub indicators: Vec<TaskIndicator>,
+}
+
+/// A detection of a file run by a scheduled task
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScheduledTaskDetection {
+    pub task: ScheduledTask,
+    pub ioc: MatchedIOC,
+}
+
+/// The results of a scan of the scheduled tasks
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScheduledTaskScanResult {
+    pub scan_id: u64,
+    /// The number of tasks registered
+    pub tasks_found: usize,
+    /// The number of distinct files scanned; many tasks run the same binary, such as rundll32.exe
+    pub files_scanned: usize,
+    pub detections: Vec<ScheduledTaskDetection>,
+    /// Tasks which look suspicious, which are worth reviewing whether or not any file they run was detected
+    pub flagged: Vec<ScheduledTask>,
+    /// Tasks a file of which kept changing whilst it was scanned, so could not be verified as clean
+    pub changed_during_scan: Vec<ScheduledTask>,
+    /// Tasks which start a program, none of whose files could be found, so were not scanned
+    pub unresolved: Vec<ScheduledTask>,
+    /// Whether the scan was cancelled before every task was scanned
+    pub cancelled: bool,
+}
+
+/// A root key of the registry a registry scan can start from. The engine runs as SYSTEM, so HKEY_CURRENT_USER would
+/// be the hive of SYSTEM; a user's hive is scanned beneath `Users`, by their SID.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum RegistryRoot {
+    LocalMachine,
+    Users,
+    ClassesRoot,
+    CurrentConfig,
+}
+
+/// The subtree of the registry to scan, for `scanner_scan_registry`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RegistryScanRequest {
+    pub root_key: RegistryRoot,
+    /// The key beneath the root to scan, such as `Software\Microsoft\Windows\CurrentVersion\Run`, or empty to
+    /// scan the whole root
+    pub subkey: String,
+}
+
+/// A registry value, by the key it is in and its name
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RegistryValue {
+    /// The full path of the key, such as `HKLM\Software\Microsoft\Windows\CurrentVersion\Run`
+    pub key: String,
+    /// The name of the value, which is empty for the default value of the key
+    pub value: String,
+    /// The data of the value as text, with binary data given in hex, truncated to
+    /// [`MAX_REGISTRY_DATA_LISTED`] characters
+    pub data: String,
+}
+
+/// The most characters of a registry value's data given in the results of a registry scan
+pub const MAX_REGISTRY_DATA_LISTED: usize = 512;
+
+/// Malware found in a file referenced from a registry value, paired with the value which references it
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RegistryDetection {
+    pub location: RegistryValue,
+    pub ioc: MatchedIOC,
+}
+
+/// A registry value whose contents matched a registry signature, such as an encoded payload stored in a Run key
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RegistryContentMatch {
+    pub location: RegistryValue,
+    /// The signature which matched, in lowercase, as signatures are matched regardless of case
+    pub signature: String,
+    /// The name of the signature database the signature is in
+    pub source: String,
+}
+
+/// The results of a scan of a subtree of the registry
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RegistryScanResult {
+    pub scan_id: u64,
+    /// The full path of the key the scan started from
+    pub root: String,
+    pub keys_scanned: u64,
+    pub values_scanned: u64,
+    /// The number of distinct files referenced from the values which were scanned
+    pub files_scanned: usize,
+    /// Detections of the files referenced from the values
+    pub detections: Vec<RegistryDetection>,
+    /// Values whose contents matched a registry signature. These are reported only, as there is no file to act on.
+    pub content_matches: Vec<RegistryContentMatch>,
+    /// Values referencing a file which kept changing whilst it was scanned, so could not be verified as clean
+    pub changed_during_scan: Vec<RegistryValue>,
+    /// The number of keys beneath the root which could not be opened, and so were not scanned
+    pub inaccessible_keys: u64,
+    /// Whether the scan was cancelled before the whole subtree was scanned
+    pub cancelled: bool,
+}
+
+/// What the memory of a running process was found to hold, from `scanner_inspect_process`. This is a behavioural
+/// heuristic on how the memory is laid out, rather than a match of its bytes against signatures.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ProcessMemoryReport {
+    pub pid: u32,
+    /// The executable the process was started from
+    pub image: PathBuf,
+    /// Whether the executable carries a valid embedded signature
+    pub signed: bool,
+    /// The JIT engine loaded in the process, such as clrjit.dll, where there is one. A JIT writes code into memory as
+    /// it runs, so writable and executable memory is not reported in a process with a JIT.
+    pub jit_module: Option<String>,
+    /// The number of regions of the process's memory inspected
+    pub regions_inspected: u64,
+    /// The number of modules loaded in the process, whose headers were compared against the files they were loaded from
+    pub modules_inspected: usize,
+    pub findings: Vec<InjectionSuspected>,
+}
+
+/// A region of a process's memory which suggests code was injected into the process
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct InjectionSuspected {
+    /// The address the region starts at in the process
+    pub address: u64,
+    /// The size of the region, in bytes
+    pub size: u64,
+    /// The memory protection of the region, as a PAGE_* constant
+    pub protection: u32,
+    pub indicator: InjectionIndicator,
+}
+
+/// Why a region of memory suggests injected code
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum InjectionIndicator {
+    /// Memory which is both writable and executable, and is not mapped from any file, as shellcode is commonly
+    /// written into
+    WritableExecutablePrivate,
+    /// Memory mapped from a file which is both writable and executable, in a process whose executable is signed, where
+    /// code from the signed file could have been overwritten
+    WritableExecutableInSigned,
+    /// A module whose headers in memory do not match the file it was loaded from, or whose memory is no longer mapped
+    /// from the file at all, as where the process was hollowed out and another executable put in its place
+    ImageMismatch { file: PathBuf, difference: String },
+}
+
+/// The results of rescanning the files detected by an earlier scan, such as after a signature update or a false
+/// positive being excluded
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RescanResult {
+    pub scan_id: u64,
+    /// The scan whose detections were rescanned
+    pub previous_scan_id: u64,
+    /// The number of distinct files rescanned
+    pub files_rescanned: usize,
+    /// Files which are still detected
+    pub still_detected: Vec<MatchedIOC>,
+    /// Files which no longer match, or are now excluded
+    pub cleared: Vec<PathBuf>,
+    /// Files which no longer exist, including those quarantined or deleted by the earlier scan
+    pub removed: Vec<PathBuf>,
+    /// Files which kept changing whilst they were rescanned, so could not be verified as clean
+    pub changed_during_scan: Vec<PathBuf>,
+    /// Whether the rescan was cancelled before every file was rescanned
+    pub cancelled: bool,
+}
+
+/// The verdict of the detection engines on a single file, given by `scanner_classify` without anything being recorded
+/// or acted on
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Classification {
+    pub file: PathBuf,
+    /// The MD5 hash of the file, None where the file hash engine is disabled
+    pub md5: Option<String>,
+    /// The SHA256 hash of the file, only computed where there are SHA256 signatures to match
+    pub sha256: Option<String>,
+    /// The detection which would be reported for the file, None where it is clean
+    pub detection: Option<MatchedIOC>,
+    /// The verdict each detection engine gave on the file, in the order they ran, clean or not
+    pub verdicts: Vec<EngineVerdict>,
+    /// The bytes of the file which matched, where the detection matched a region of it such as a PE section
+    pub regions: Vec<MatchedBytes>,
+}
+
+/// Why a file was or was not detected, engine by engine, as given by `scanner_explain` without anything being recorded
+/// or acted on. This is for closing detection gaps: where a file known to be malicious passed, it says what each
+/// engine would have needed for the file to be detected.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Explanation {
+    pub file: PathBuf,
+    /// The MD5 hash of the file, None where the file hash engine is disabled
+    pub md5: Option<String>,
+    /// The SHA256 hash of the file, only computed where there are SHA256 signatures to match
+    pub sha256: Option<String>,
+    /// The detection which would be reported for the file, None where it is clean
+    pub detection: Option<MatchedIOC>,
+    /// The exclusion which keeps scans from scanning the file, where one does. The engines are run on it regardless,
+    /// so the explanation says what a scan would find were it not excluded.
+    pub excluded_by: Option<ExclusionMatch>,
+    /// The triage of the file's most recent detection, where it was triaged as benign, which keeps the detection out of
+    /// what is outstanding and out of batch quarantines
+    pub triaged_benign: Option<DetectionAnnotation>,
+    /// The signature databases which are loaded but not matched against, so whose signatures no engine matched
+    pub disabled_databases: Vec<String>,
+    /// What each engine made of the file, in the order they ran
+    pub engines: Vec<EngineExplanation>,
+}
+
+/// What a single detection engine made of a file
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct EngineExplanation {
+    /// The name of the engine, as named in the verdict precedence
+    pub engine: String,
+    /// How the engine matched the file, where it detected the file
+    pub match_mode: Option<MatchMode>,
+    /// The signature database the engine matched the file in, where it detected the file
+    pub source: Option<String>,
+    /// Why the engine did not detect the file, where it did not
+    pub reason: Option<NotDetectedReason>,
+}
+
+/// Why a detection engine did not detect a file
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum NotDetectedReason {
+    /// The engine is turned off in the detection engines
+    EngineDisabled,
+    /// The engine is turned off in its own settings, as fuzzy hashing and the heuristics are opt in
+    DisabledInSettings,
+    /// No signature database which is matched against holds signatures of the kind the engine matches
+    NoSignatures,
+    /// The file is not a PE, so has no sections or imports to match
+    NotPe,
+    /// The hash of the whole file is in no signature database which is matched against. The SHA256 is given where
+    /// there are SHA256 signatures to match.
+    HashNotInDatabase { md5: String, sha256: Option<String> },
+    /// The hash of the file matched a signature in the database, but the file met none of the signature's conditions
+    ConditionsNotMet { hash: String, source: String },
+    /// None of the hashes of the sections of the PE is a section signature
+    SectionsNotInDatabase { sections: usize },
+    /// The PE imports nothing, or its import table is malformed, so it has no import hash
+    NoImports,
+    /// The import hash of the PE is not an import hash signature
+    ImportHashNotInDatabase { imphash: String },
+    /// The file is larger than the most bytes which are fuzzy hashed
+    TooLargeToFuzzyHash { len: u64, max: u64 },
+    /// No fuzzy signature was as similar to the file as the threshold. The similarity of the closest is given, where a
+    /// signature of a comparable block size was loaded to compare the file with.
+    BelowSimilarity { closest: Option<u32>, threshold: u32 },
+    /// The file has no VBA macros, as it is not an Office document or its document has none
+    NoMacros,
+    /// The document has macros, but none which both run by themselves and make suspicious calls
+    MacrosNotSuspicious { modules: usize },
+    /// The file is not a shortcut
+    NotShortcut,
+    /// The file is a shortcut whose target was not detected, or could not be resolved, and whose arguments do not look
+    /// like a payload
+    ShortcutNotSuspicious { target: Option<PathBuf> },
+    /// The score of the heuristic signals found in the file was below the threshold
+    BelowThreshold { score: u32, threshold: u32 },
+    /// The detector did not detect the file, and gives no reason why, as with a detector registered with the engine
+    NoMatch,
 }
 
 pub enum ScanResult {
@@ -27,14 +866,334 @@ pub enum ScanResult {
 /// Structure for containing results pertaining to an IOC match
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct MatchedIOC {
+    /// The hash which matched the IOC; for a section match this is the hash of the section, not the whole file
+    pub hash: String,
+    pub file: PathBuf,
+    pub match_mode: MatchMode,
+    /// What was done with the file once it was detected
+    #[serde(default)]
+    pub action_taken: ActionResult,
+    /// The name of the signature database or registered detector which matched, or None for a heuristic detection,
+    /// which matches no signature
+    #[serde(default)]
+    pub source: Option<String>,
+    /// The verdict each detection engine gave on the file, in the order they ran, from which the detection was
+    /// resolved. Empty for a detection not made by the detection engines, such as of scanned bytes.
+    #[serde(default)]
+    pub verdicts: Vec<EngineVerdict>,
+    /// The engine whose verdict was reported, being the engine of highest precedence to detect the file
+    #[serde(default)]
+    pub resolved_by: Option<String>,
+    /// The conditions of the signature which the file met, as written in the signature file, such as
+    /// `type=executable`. Empty where the signature which matched has no conditions.
+    #[serde(default)]
+    pub conditions: Vec<String>,
+}
+
+/// The verdict a single detection engine gave on a file
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct EngineVerdict {
+    /// The name of the engine, as named in the verdict precedence
+    pub engine: String,
+    /// How the engine matched the file, or None where it found the file clean or is disabled
+    pub match_mode: Option<MatchMode>,
+    /// The signature database the engine matched the file in, where it detected the file
+    pub source: Option<String>,
+}
+
+/// How the verdicts of the detection engines are resolved where more than one detects a file, as served by
+/// `verdict_policy`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct VerdictPolicy {
+    /// Every engine loaded, highest precedence first. The detection reported for a file is that of the first engine in
+    /// this order to detect it.
+    pub precedence: Vec<String>,
+    /// Engines named in the precedence in the settings which are not loaded, such as a detector which has not been
+    /// registered, so have no say
+    pub unknown: Vec<String>,
+}
+
+/// The bytes of a detected file which matched, captured when it was detected so they can be shown to an analyst even
+/// once the file has been quarantined or deleted
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct DetectionDetails {
+    pub file: PathBuf,
+    pub regions: Vec<MatchedBytes>,
+    /// The analyst's triage of the file's most recent detection, where it has been annotated
+    #[serde(default)]
+    pub annotation: Option<DetectionAnnotation>,
+}
+
+/// A region of a file which matched a detection. Captures are bounded, so a large region is truncated.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct MatchedBytes {
+    /// What matched, such as the name of a PE section
+    pub label: String,
+    /// The offset of the region in the file
+    pub offset: u64,
+    /// The number of bytes captured
+    pub len: u64,
+    /// Whether the region was longer than was captured
+    pub truncated: bool,
+    /// The captured bytes as a hex and ASCII dump, one line per 16 bytes
+    pub hexdump: String,
+}
+
+/// The verdict for a single file scanned from disk
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum FileVerdict {
+    Clean,
+    Malicious(MatchedIOC),
+    /// The file changed between being opened and being hashed, and again when it was rescanned, so the content which
+    /// was hashed may not be what is now on disk. This may be a file which is legitimately being written to, or an
+    /// attempt to swap a clean file for a malicious one after it was checked, so it cannot be reported as clean.
+    ChangedDuringScan(PathBuf),
+}
+
+/// The verdict for content scanned directly from bytes, such as content piped to the engine by an integrator
+/// which never touches the disk.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BytesScanResult {
+    /// The hash of the whole content
     pub hash: String,
+    /// The number of bytes scanned
+    pub len: u64,
+    /// The matched IOC, if the content was malicious
+    pub ioc: Option<MatchedIOC>,
+}
+
+/// Which part of the file matched against the IOC list
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum MatchMode {
+    /// The hash of the whole file matched a known bad hash
+    FileHash,
+    /// The hash of a single PE section matched a known bad section hash. This catches files which have had bytes
+    /// appended or padded to change the whole file hash whilst the payload itself is unchanged.
+    SectionHash { section: String },
+    /// The import hash (imphash) of a PE matched the imphash of known malware, which is in the hash of the detection.
+    /// This catches variants of a family which were recompiled with small changes, but import the same functions.
+    ImportHash,
+    /// The fuzzy hash of the file was similar to a known bad fuzzy hash, catching slightly modified variants of known
+    /// malware. The similarity is from 0 - 100.
+    FuzzyHash { similarity: u32 },
+    /// The signals the heuristic engine found in the file added up to at least the threshold, where no engine of
+    /// higher precedence detected it. The signals are included so the score can be understood.
+    Heuristic { score: u32, signals: Vec<HeuristicSignal> },
+    /// A detector registered with the engine, rather than one of the built in engines, detected the file for the
+    /// reason it gave
+    Custom { reason: String },
+    /// The file is an Office document with VBA macros, which run by themselves as the document is opened and make
+    /// calls malware uses, such as to run a command or download a file. The module is the macro module the suspicious
+    /// calls were found in, and the subroutines which run by themselves may be in any module of the document.
+    Macro {
+        module: String,
+        auto_exec: Vec<String>,
+        suspicious_calls: Vec<String>,
+    },
+    /// The file is a shortcut which points to a detected file, or whose arguments look like a payload, such as an
+    /// encoded PowerShell command. The target is the file the shortcut points to, where it could be resolved, and the
+    /// detection of the target is given where it was detected itself.
+    Shortcut {
+        target: Option<PathBuf>,
+        arguments: String,
+        indicators: Vec<ShortcutIndicator>,
+        target_detection: Option<Box<MatchedIOC>>,
+    },
+    /// A made up detection injected with `simulate_detection` for testing, which matched nothing in the file
+    Simulated { name: String, severity: u8 },
+}
+
+/// Why a shortcut looks suspicious, whether or not the file it points to was detected
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum ShortcutIndicator {
+    /// The shortcut runs PowerShell with an encoded command, which hides what it runs
+    EncodedPowerShell,
+    /// The shortcut starts a binary which ships with Windows and runs or fetches code given to it, such as mshta or
+    /// certutil, with arguments, or names one in its arguments. The binary is given by its file name.
+    LivingOffTheLand { binary: String },
+    /// The shortcut is given a URL, as a living off the land binary is to fetch its payload
+    RemotePayload,
+    /// The arguments are padded with whitespace, which pushes the command past what the shortcut's properties show
+    PaddedArguments,
+}
+
+/// A single reason the heuristic engine considered a file suspicious, and what it added to the file's score
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct HeuristicSignal {
+    pub score: u32,
+    pub reason: HeuristicReason,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum HeuristicReason {
+    /// The file is a PE beneath a folder executables are not normally run from, such as the temp folder. The root
+    /// is given as it is configured in the settings.
+    SuspiciousLocation { root: String },
+}
+
+/// A set of bit flags of the detection engines which run during a scan. The engines can be toggled at runtime to
+/// isolate behaviour or reduce load; by default all engines run.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(transparent)]
+pub struct EngineFlags(pub u32);
+
+impl EngineFlags {
+    /// Matches the hash of the whole file against the IOC list
+    pub const FILE_HASH: EngineFlags = EngineFlags(1 << 0);
+    /// Matches the hashes of individual PE sections against the section IOC list, and the import hash of a PE against
+    /// the import hash signatures
+    pub const SECTION_HASH: EngineFlags = EngineFlags(1 << 1);
+    /// Matches the fuzzy hash of the file against the fuzzy IOC list. Fuzzy hashing must additionally be enabled in
+    /// its own settings, as it is opt in for performance.
+    pub const FUZZY_HASH: EngineFlags = EngineFlags(1 << 2);
+    /// Scores the file on suspicious signals, such as where it is located. The heuristic engine must additionally be
+    /// enabled in its own settings, as it is opt in to avoid false positives.
+    pub const HEURISTIC: EngineFlags = EngineFlags(1 << 3);
+    /// Extracts the VBA macros from Office documents, detecting those which run by themselves and make suspicious
+    /// calls
+    pub const MACRO: EngineFlags = EngineFlags(1 << 4);
+    /// Parses shortcuts for the file they point to and the arguments they run it with, scanning the target and
+    /// detecting shortcuts to a detected file or with arguments which look like a payload
+    pub const SHORTCUT: EngineFlags = EngineFlags(1 << 5);
+
+    pub const ALL: EngineFlags = EngineFlags(
+        Self::FILE_HASH.0
+            | Self::SECTION_HASH.0
+            | Self::FUZZY_HASH.0
+            | Self::HEURISTIC.0
+            | Self::MACRO.0
+            | Self::SHORTCUT.0,
+    );
+    pub const NONE: EngineFlags = EngineFlags(0);
+
+    pub fn contains(&self, other: EngineFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether any bits are set which do not correspond to a known engine
+    pub fn has_unknown_bits(&self) -> bool {
+        self.0 & !Self::ALL.0 != 0
+    }
+}
+
+impl Default for EngineFlags {
+    fn default() -> Self {
+        EngineFlags::ALL
+    }
+}
+
+impl std::ops::BitOr for EngineFlags {
+    type Output = EngineFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        EngineFlags(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for EngineFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A file which the worker scanning it made no progress on for the stall timeout, such as where a read of the file
+/// never returned. The scan marks the file as failed and carries on with a new worker.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct StalledFile {
     pub file: PathBuf,
+    /// How long the worker had made no progress for when it was replaced
+    pub stalled_for: Duration,
+}
+
+/// The files of one extension which a scan scanned, for seeing what a scan spends its time on
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ExtensionStats {
+    /// The extension in lowercase, without the dot, or empty for files with no extension
+    pub extension: String,
+    pub files: u64,
+    pub bytes: u64,
 }
 
 /// Live time information about the current scan
+///
+/// The `started_at`, `finished_at`, `duration_ms` and `throughput_mb_s` fields are only populated once the scan
+/// has finished (or been cancelled); whilst in progress, `time_taken` gives the running time.
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct ScanningLiveInfo {
     pub num_files_scanned: u128,
     pub time_taken: Duration,
+    /// The detections of the scan. `scanner_get_scan_stats` leaves these out, reporting only how many there are in
+    /// `detection_count`, so that polling a scan with thousands of detections stays cheap; they are paged through
+    /// with `scanner_results_page`.
     pub scan_results: Vec<MatchedIOC>,
+    /// The number of detections of the scan, which is filled in even where `scan_results` is left out
+    #[serde(default)]
+    pub detection_count: usize,
+    /// Files which kept changing whilst they were scanned, so could not be verified as clean
+    pub changed_during_scan: Vec<PathBuf>,
+    /// Files the scan gave up on as they stalled the worker scanning them, so could not be verified as clean
+    pub stalled_files: Vec<StalledFile>,
+    /// How far the scan has got through each of its roots, for a folder scan
+    pub roots: Vec<RootProgress>,
+    /// Files skipped as they cannot run, where the scan is of executables only
+    pub skipped_not_executable: u64,
+    /// Files skipped as they matched an exclusion. Files beneath an excluded folder are not counted, as the folder is
+    /// never walked.
+    pub skipped_excluded: u64,
+    /// Folders and files skipped as they are on a network or remote volume, where the settings skip remote volumes.
+    /// A skipped folder counts once, as it is not walked.
+    pub skipped_remote: u64,
+    /// Files skipped as they had not changed since the scan's `modified_since`
+    #[serde(default)]
+    pub skipped_unchanged: u64,
+    /// Files which were only scanned as the engine enabled its backup privilege, as it was otherwise denied them
+    #[serde(default)]
+    pub scanned_with_backup_privilege: u64,
+    /// Files and folders the engine was denied even with its backup privilege, so which were not scanned, up to
+    /// [`MAX_INACCESSIBLE_LISTED`] of them
+    #[serde(default)]
+    pub inaccessible: Vec<PathBuf>,
+    /// The number of files and folders which were inaccessible, including those beyond the most listed
+    #[serde(default)]
+    pub inaccessible_count: u64,
+    /// Files another process held open when the scan reached them, which were scanned when retried at the end of the
+    /// scan
+    #[serde(default)]
+    pub scanned_on_retry: u64,
+    /// Files another process still held open after every retry, so which were not scanned, up to
+    /// [`MAX_IN_USE_LISTED`] of them
+    #[serde(default)]
+    pub skipped_in_use: Vec<PathBuf>,
+    /// The number of files skipped as they were in use, including those beyond the most listed
+    #[serde(default)]
+    pub skipped_in_use_count: u64,
+    /// Whether a large file is being held back right now, as the system is low on physical memory
+    pub memory_throttled: bool,
+    /// How many large files were held back as the system was low on physical memory, and for how long in total
+    pub memory_pauses: u64,
+    pub memory_paused_ms: u64,
+    /// The number and size of the files scanned of each extension, most common first once the scan has finished
+    pub extensions: Vec<ExtensionStats>,
+    /// Why the scan was cancelled, where it was
+    pub cancel_reason: Option<String>,
+    /// The ID of the scan these stats relate to
+    pub scan_id: Option<u64>,
+    /// What the scan was asked to scan
+    #[serde(default)]
+    pub target: Option<ScanTarget>,
+    /// The detection engines which were active during the scan. If the engines were changed mid scan, this includes
+    /// every engine which was active at any point.
+    pub engines: EngineFlags,
+    /// Total bytes read across all scanned files
+    pub bytes_scanned: u64,
+    /// The thresholds the scan ran with, each taken from the settings where the scan did not override it
+    pub thresholds: Option<DetectionThresholds>,
+    /// Wall clock time the scan started, for display purposes only
+    pub started_at: Option<SystemTime>,
+    /// Wall clock time the scan finished, for display purposes only
+    pub finished_at: Option<SystemTime>,
+    /// Duration of the scan measured on a monotonic clock
+    pub duration_ms: Option<u128>,
+    /// Average throughput in MB/s over the whole scan
+    pub throughput_mb_s: Option<f64>,
 }
@@ -0,0 +1,282 @@
+//! Types for the history of past scans, kept by the engine so results remain available after the scanner is reset.
+
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_scanner::{ActionResult, DetectionThresholds, ExtensionStats, MatchedIOC, ScanTarget, StalledFile};
+
+/// The outcome of a scan which never ended, as the engine stopped whilst it ran, recovered from its journal once the
+/// engine started again
+pub const OUTCOME_INTERRUPTED: &str = "Interrupted";
+
+/// A record of a scan which has ended
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanRecord {
+    pub scan_id: u64,
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
+    /// The name of the state the scan ended in, such as `Finished` or `Cancelled`, or [`OUTCOME_INTERRUPTED`] for a
+    /// scan recovered from its journal
+    pub outcome: String,
+    /// Why the scan was cancelled, where it was
+    #[serde(default)]
+    pub cancel_reason: Option<String>,
+    pub num_files_scanned: u128,
+    pub bytes_scanned: u64,
+    pub detections: Vec<MatchedIOC>,
+    pub changed_during_scan: Vec<PathBuf>,
+    /// Files the scan gave up on as they stalled the worker scanning them
+    #[serde(default)]
+    pub stalled_files: Vec<StalledFile>,
+    /// Files skipped as they cannot run, where the scan was of executables only
+    #[serde(default)]
+    pub skipped_not_executable: u64,
+    /// Files skipped as they matched an exclusion
+    #[serde(default)]
+    pub skipped_excluded: u64,
+    /// Folders and files skipped as they are on a network or remote volume
+    #[serde(default)]
+    pub skipped_remote: u64,
+    /// Files skipped as they had not changed since the time the scan was of files changed since
+    #[serde(default)]
+    pub skipped_unchanged: u64,
+    /// Files which were only scanned as the engine enabled its backup privilege
+    #[serde(default)]
+    pub scanned_with_backup_privilege: u64,
+    /// Files and folders the engine was denied even with its backup privilege, up to the most listed
+    #[serde(default)]
+    pub inaccessible: Vec<PathBuf>,
+    #[serde(default)]
+    pub inaccessible_count: u64,
+    /// Files which were in use when the scan reached them, but were scanned when retried
+    #[serde(default)]
+    pub scanned_on_retry: u64,
+    /// Files still in use after every retry, up to the most listed
+    #[serde(default)]
+    pub skipped_in_use: Vec<PathBuf>,
+    #[serde(default)]
+    pub skipped_in_use_count: u64,
+    /// What the scan was asked to scan. None for a scan recorded by an older engine, or recovered from its journal.
+    #[serde(default)]
+    pub target: Option<ScanTarget>,
+    /// The number and size of the files scanned of each extension, most common first
+    #[serde(default)]
+    pub extensions: Vec<ExtensionStats>,
+    /// The thresholds the scan ran with, or None for scans recorded before thresholds were kept
+    #[serde(default)]
+    pub thresholds: Option<DetectionThresholds>,
+    /// The triage of the detections, keyed by the index of the detection in `detections`, which is its detection ID
+    #[serde(default)]
+    pub annotations: HashMap<usize, DetectionAnnotation>,
+    /// The folders the scan walked. Empty for a scan of a single file, and for scans recorded before they were kept.
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+}
+
+/// The format a scan is exported in. The JSON formats are the scan's record, with the compressed formats suited to
+/// scans with many results. The SIEM formats hold one line per detection, for importing into a SIEM.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    /// JSON compressed with gzip
+    JsonGz,
+    /// JSON compressed with zstd
+    JsonZst,
+    /// ArcSight Common Event Format
+    Cef,
+    /// IBM QRadar Log Event Extended Format
+    Leef,
+}
+
+/// A request to export a scan from the history to a file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanExportRequest {
+    pub scan_id: u64,
+    pub format: ExportFormat,
+    /// The file to write the export to, which is replaced if it exists
+    pub path: PathBuf,
+}
+
+/// A summary of an export which was written
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanExportReport {
+    pub file: PathBuf,
+    pub format: ExportFormat,
+    /// The size of the file written, after any compression
+    pub bytes_written: u64,
+    pub detections: usize,
+}
+
+/// A request to compare two scans from the history, from scan A to the later scan B
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanDiffRequest {
+    pub scan_id_a: u64,
+    pub scan_id_b: u64,
+    /// Whether a file detected by both scans with a different hash is taken as a new detection, as the file has been
+    /// replaced, rather than the same one. Files are otherwise matched by path alone.
+    #[serde(default)]
+    pub match_hash: bool,
+}
+
+/// What changed in the detections from scan A to scan B. Files are matched by path, case insensitively as paths on
+/// Windows are.
+///
+/// The history keeps the detections of a scan but not every file it scanned, so files which were added or removed
+/// without being detected cannot be compared.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanDiff {
+    pub scan_id_a: u64,
+    pub scan_id_b: u64,
+    /// Detections in B of files A did not detect
+    pub new_detections: Vec<MatchedIOC>,
+    /// Detections in A of files B scanned again and did not detect
+    pub resolved_detections: Vec<MatchedIOC>,
+    /// Detections in B of files A also detected
+    pub persistent_detections: Vec<MatchedIOC>,
+    /// Detections in A of files B did not scan, as they are beneath none of its folders or it could not verify them,
+    /// so which may or may not still be malicious
+    pub not_rescanned: Vec<MatchedIOC>,
+    /// The change in the number of files scanned from A to B
+    pub files_scanned_change: i128,
+}
+
+/// Where a detection is in an analyst's triage
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum TriageStatus {
+    /// Not yet looked at, which is every detection until it is annotated
+    #[default]
+    New,
+    Investigating,
+    Confirmed,
+    Benign,
+}
+
+/// An analyst's triage of a detection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetectionAnnotation {
+    pub status: TriageStatus,
+    pub note: String,
+    /// When the detection was last annotated
+    pub updated_at: SystemTime,
+}
+
+/// A request to annotate a detection, identified by its scan and its index in the scan's detections
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetectionAnnotationRequest {
+    pub scan_id: u64,
+    pub detection_id: usize,
+    pub status: TriageStatus,
+    pub note: String,
+}
+
+/// A detection from the scan history, with its triage
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecentDetection {
+    pub scan_id: u64,
+    pub detection_id: usize,
+    pub finished_at: Option<SystemTime>,
+    pub ioc: MatchedIOC,
+    pub annotation: Option<DetectionAnnotation>,
+}
+
+impl RecentDetection {
+    /// The triage status of the detection, which is `New` until it is annotated
+    pub fn status(&self) -> TriageStatus {
+        self.annotation
+            .as_ref()
+            .map(|a| a.status)
+            .unwrap_or_default()
+    }
+}
+
+/// The period `protection_stats` aggregates the scan history over, each ending with today
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum StatsPeriod {
+    /// The last 7 days
+    Week,
+    /// The last 30 days
+    #[default]
+    Month,
+    /// The last 365 days
+    Year,
+    /// Every scan in the history, from the day of the first
+    Lifetime,
+}
+
+impl StatsPeriod {
+    /// The number of days in the period, or None for the lifetime of the history
+    pub fn days(&self) -> Option<u64> {
+        match self {
+            StatsPeriod::Week => Some(7),
+            StatsPeriod::Month => Some(30),
+            StatsPeriod::Year => Some(365),
+            StatsPeriod::Lifetime => None,
+        }
+    }
+}
+
+/// What the engine did over a period, from the scan history, for showing the user that they are being protected
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProtectionStats {
+    pub period: StatsPeriod,
+    /// The scans which ended in the period
+    pub scans: u64,
+    pub files_scanned: u128,
+    pub bytes_scanned: u64,
+    pub detections: u64,
+    /// Detections which were quarantined or deleted, or are to be deleted when Windows next starts
+    pub threats_blocked: u64,
+    /// When the most recent scan in the history ended, whether or not it was in the period
+    pub last_scan: Option<SystemTime>,
+    /// The totals of each day of the period, oldest first, including days without a scan so they can be charted
+    /// as they are. Days run from midnight UTC.
+    pub days: Vec<DailyProtectionStats>,
+}
+
+/// The totals of the scans which ended on a single day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DailyProtectionStats {
+    /// Midnight UTC at the start of the day
+    pub day: SystemTime,
+    pub scans: u64,
+    pub files_scanned: u128,
+    pub bytes_scanned: u64,
+    pub detections: u64,
+    pub threats_blocked: u64,
+}
+
+/// The most recent sightings of a threat kept in its timeline; the first sighting is kept apart, so is never dropped
+pub const MAX_THREAT_SIGHTINGS: usize = 100;
+
+/// A detection of a threat by a scan
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThreatSighting {
+    pub scan_id: u64,
+    /// When the scan which detected the threat ended, or None where the scan was recorded without a time
+    pub seen_at: Option<SystemTime>,
+    pub file: PathBuf,
+    pub action_taken: ActionResult,
+}
+
+/// When a threat was seen on the machine across scans, from `threat_timeline`. A threat is identified by the hash it
+/// was detected by, which for a match on a PE section or the imports of a PE is the hash of those rather than of the
+/// whole file.
+///
+/// The timeline is kept apart from the scan history, so a threat's first sighting is remembered after the scan which
+/// made it has been pruned from the history.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThreatTimeline {
+    pub hash: String,
+    pub first_seen: ThreatSighting,
+    pub last_seen: ThreatSighting,
+    /// The number of scans which detected the threat
+    pub scans: u64,
+    /// The most recent sightings, up to [`MAX_THREAT_SIGHTINGS`], oldest first
+    pub sightings: Vec<ThreatSighting>,
+    /// The files the threat was detected in which are still on disk, and were not quarantined or deleted when they
+    /// were last detected. A file may have been cleaned since, so should be rescanned to confirm it is still malicious.
+    #[serde(default)]
+    pub present: Vec<PathBuf>,
+}
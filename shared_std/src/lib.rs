@@ -1,6 +1,14 @@
+pub mod codec;
+pub mod connections;
 pub mod constants;
+pub mod diagnostics;
 pub mod driver_manager;
+pub mod events;
 pub mod file_scanner;
+pub mod history;
+pub mod maintenance;
 pub mod processes;
+pub mod quarantine;
 pub mod security;
 pub mod settings;
+pub mod version;
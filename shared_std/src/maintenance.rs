@@ -0,0 +1,23 @@
+//! Types for the engine's maintenance task, which keeps the history and quarantine stores from growing unbounded.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::quarantine::QuarantineEntry;
+
+/// A report of what was cleaned up by a maintenance run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct MaintenanceReport {
+    /// The number of scan history records removed for being older than the retention period
+    pub history_records_pruned: usize,
+    /// The size of the history file before and after it was compacted
+    pub history_bytes_before: u64,
+    pub history_bytes_after: u64,
+    /// Files in the quarantine folder with no manifest entry, which were deleted
+    pub orphaned_quarantine_files: Vec<PathBuf>,
+    /// Manifest entries whose quarantined file was missing, which were removed
+    pub orphaned_quarantine_entries: Vec<QuarantineEntry>,
+    /// Errors from any step which could not be completed; the other steps are still run
+    pub errors: Vec<String>,
+}
@@ -0,0 +1,158 @@
+//! Types for the quarantine store, where files detected as malicious are held so they can no longer run.
+
+use std::{path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+/// An entry in the quarantine manifest, describing a file held in quarantine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineEntry {
+    /// Identifies the entry, and is the name of the quarantined file within the quarantine folder
+    pub id: String,
+    /// Where the file was before it was quarantined
+    pub original_path: PathBuf,
+    /// The hash of the file which was detected
+    pub hash: String,
+    pub quarantined_at: SystemTime,
+    /// The size of the file held in quarantine, used to verify it has not changed. Entries made before this was
+    /// recorded only have the existence of their file verified.
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// The SHA256 of the file as it was quarantined, before it was obfuscated. Unlike `hash`, which may be the hash of a
+    /// PE section, this is always of the whole file.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Whether the file is held obfuscated, so that it can neither be run nor found by another scanner whilst in
+    /// quarantine. Files quarantined before files were obfuscated are held as they were.
+    #[serde(default)]
+    pub obfuscated: bool,
+    /// Whether the entry was recorded before its file was moved into quarantine, and not yet committed. A pending entry
+    /// left by an engine which stopped part way through quarantining a file is completed or rolled back at startup.
+    #[serde(default)]
+    pub pending: bool,
+}
+
+/// A report of checking the quarantine manifest against the files held in the quarantine folder
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct QuarantineReport {
+    /// The number of manifest entries checked
+    pub entries_checked: usize,
+    /// Entries whose file is present and matches what was recorded
+    pub healthy: usize,
+    /// Entries recorded before sizes and hashes were, whose file is present but could not be verified further
+    pub unverified: usize,
+    /// Entries whose file is missing, which cannot be restored
+    pub dangling_entries: Vec<QuarantineEntry>,
+    /// Entries whose file differs in size or hash from when it was quarantined
+    pub mismatched: Vec<QuarantineMismatch>,
+    /// Files in the quarantine folder which have no manifest entry, so cannot be restored
+    pub orphaned_files: Vec<PathBuf>,
+    /// Whether the dangling entries were removed from the manifest
+    pub repaired: bool,
+}
+
+/// A quarantined file which no longer matches its manifest entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineMismatch {
+    pub entry: QuarantineEntry,
+    /// What differs, such as the size or the hash
+    pub problem: String,
+}
+
+/// The extension given to a sample extracted from quarantine, so that it is not opened by its type by accident
+pub const EXTRACTED_EXTENSION: &str = "quarantined";
+
+/// A request to extract a copy of a quarantined file for analysis, as served by `quarantine_extract`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineExtractRequest {
+    /// The ID of the quarantine entry
+    pub entry_id: String,
+    /// The folder to extract into, where the copy is named after the original file, or the path of the copy itself.
+    /// Either way the copy is given the [`EXTRACTED_EXTENSION`] extension.
+    pub dest: PathBuf,
+}
+
+/// A sample extracted from quarantine. The entry remains in quarantine, unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineExtraction {
+    pub entry: QuarantineEntry,
+    /// Where the copy was written
+    pub path: PathBuf,
+    /// The SHA256 of the copy, which matches that recorded for the entry where one was
+    pub sha256: String,
+}
+
+/// What is done when restoring a quarantined file whose original path now holds another file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum RestoreConflictPolicy {
+    /// The file at the original path is replaced by the restored file
+    Overwrite,
+    /// The restored file is put beside the original path under a new name, such as `setup (restored).exe`
+    RenameRestored,
+    /// The file at the original path is renamed out of the way, such as to `setup (existing).exe`, and the restored
+    /// file takes its place
+    RenameExisting,
+    /// Nothing is restored, so that no file is lost without being asked for
+    #[default]
+    Fail,
+}
+
+/// A request to restore a quarantined file to where it was quarantined from, as served by `quarantine_restore`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineRestoreRequest {
+    /// The ID of the quarantine entry
+    pub entry_id: String,
+    /// What is done where the original path now holds another file
+    #[serde(default)]
+    pub conflict_policy: RestoreConflictPolicy,
+}
+
+/// A file restored from quarantine. Its entry is no longer in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantineRestoration {
+    pub entry: QuarantineEntry,
+    /// Where the file was restored to, which is the original path unless the restored file was renamed
+    pub path: PathBuf,
+    /// The SHA256 of the restored file
+    pub sha256: String,
+    /// Whether the restored file was checked against the SHA256 recorded when it was quarantined. Entries made before
+    /// that was recorded cannot be.
+    pub verified: bool,
+    /// The policy applied where the original path held another file, or None where the path was free
+    pub conflict: Option<RestoreConflictPolicy>,
+    /// Where the file which was at the original path was renamed to, where it was moved out of the way
+    pub existing_renamed_to: Option<PathBuf>,
+}
+
+/// What became of one detection of a scan quarantined by `quarantine_scan_detections`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum BatchQuarantineOutcome {
+    /// The file was moved into quarantine under the ID of its entry, or already was
+    Quarantined { id: String },
+    /// The file matches an exclusion, so was left in place
+    SkippedExcluded,
+    /// The detection was triaged as benign, so the file was left in place
+    SkippedBenign,
+    /// The file could not be quarantined, such as as it is in use or access was denied, and remains in place
+    Failed(String),
+}
+
+/// A detection of the scan, and what became of it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchQuarantineItem {
+    /// The index of the detection in the scan's detections
+    pub detection_id: usize,
+    pub file: PathBuf,
+    pub outcome: BatchQuarantineOutcome,
+}
+
+/// The result of quarantining every detection of a scan. A file detected more than once is quarantined once, with
+/// each of its detections given the same outcome.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchQuarantineResult {
+    pub scan_id: u64,
+    pub quarantined: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub items: Vec<BatchQuarantineItem>,
+}
@@ -1,8 +1,533 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::file_scanner::{EngineFlags, MatchedIOC};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct SanctumSettings {
     pub common_scan_areas: Vec<PathBuf>,
+    #[serde(default)]
+    pub exclusions: ExclusionList,
+    #[serde(default)]
+    pub fuzzy_hashing: FuzzyHashSettings,
+    #[serde(default)]
+    pub detection_engines: EngineFlags,
+    #[serde(default)]
+    pub maintenance: MaintenanceSettings,
+    #[serde(default)]
+    pub heuristics: HeuristicSettings,
+    #[serde(default)]
+    pub removable_media: RemovableMediaSettings,
+    #[serde(default)]
+    pub siem: SiemSettings,
+    #[serde(default)]
+    pub realtime: RealtimePolicy,
+    /// The names of the signature databases which are loaded but not matched against
+    #[serde(default)]
+    pub disabled_signature_databases: Vec<String>,
+    #[serde(default)]
+    pub scan_watchdog: ScanWatchdogSettings,
+    #[serde(default)]
+    pub remote_management: RemoteManagementSettings,
+    #[serde(default)]
+    pub scan_journal: ScanJournalSettings,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    #[serde(default)]
+    pub on_demand: OnDemandSettings,
+    #[serde(default)]
+    pub remote_volumes: RemoteVolumeSettings,
+    #[serde(default)]
+    pub verdicts: VerdictSettings,
+    #[serde(default)]
+    pub memory_pressure: MemoryPressureSettings,
+    #[serde(default)]
+    pub injection: InjectionSettings,
+    #[serde(default)]
+    pub webhook: WebhookSettings,
+}
+
+/// The settings the engine is running with, each annotated with where its value came from, for working out why the
+/// engine behaves as it does where the stored settings alone do not explain it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectiveConfig {
+    /// The settings file the sources of the settings were read from
+    pub settings_file: PathBuf,
+    /// Each setting, keyed by its path in the settings file such as `siem.enabled`
+    pub settings: Vec<EffectiveSetting>,
+    /// Settings in the file which the engine does not know, such as a misspelt setting, so which are ignored
+    pub unknown: Vec<String>,
+    /// Why the settings fail validation, where they do. The scanner then runs on its defaults in place of its own
+    /// settings, which can only be the case where the settings file was edited by hand, as an invalid change to the
+    /// settings is refused.
+    pub validation_error: Option<String>,
+}
+
+/// A single setting, with the default it takes where it is not in the settings file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EffectiveSetting {
+    pub key: String,
+    pub value: Value,
+    pub default: Value,
+    pub source: SettingSource,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingSource {
+    /// The setting is not in the settings file, so takes its default. This is usually a setting added in a later
+    /// version than the one which wrote the file.
+    Default,
+    /// The setting was read from the settings file. Every setting is written when the settings are first created or
+    /// saved, so a setting from the file may still hold its default value.
+    SettingsFile,
+}
+
+/// The outcome of `config_reload`, which reads the settings file again after it was changed by something other than
+/// the engine, such as a tool editing it or a policy being pushed to the machine
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfigReloadReport {
+    /// The settings file which was read
+    pub settings_file: PathBuf,
+    /// The settings which changed and are now in use, keyed by their path in the settings file such as `siem.enabled`
+    pub applied: Vec<String>,
+    /// The settings which changed but are only read as the engine starts, so take effect once it is restarted
+    pub restart_required: Vec<String>,
+    /// Settings in the file which the engine does not know, such as a misspelt setting, so which are ignored
+    pub unknown: Vec<String>,
+}
+
+/// Settings for the watchdog which replaces the worker of a folder scan when it stalls on a file, so that a file which
+/// can never be read (such as one on a hung network share) cannot stop the scan from finishing
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct ScanWatchdogSettings {
+    /// How long the worker may go without making progress on a file before the file is given up on. Progress is
+    /// measured as the file is read, so a large file which is being read is never given up on.
+    pub stall_timeout_secs: u64,
+}
+
+impl Default for ScanWatchdogSettings {
+    fn default() -> Self {
+        ScanWatchdogSettings {
+            stall_timeout_secs: 120,
+        }
+    }
+}
+
+/// Settings for journalling scans to disk as they run, so that the results of a scan are recovered into the scan
+/// history where the engine crashes or is stopped before the scan ends. This is off by default, as each detection is
+/// synced to disk as it is found.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub struct ScanJournalSettings {
+    pub enabled: bool,
+}
+
+/// Settings for how many on-demand requests, such as hashing a file or scanning the autoruns, the engine serves at
+/// once. A running scan takes one of the slots. Requests beyond the limit are refused as busy, to be retried after
+/// `retry_after_secs`, rather than queueing behind the scan and appearing to hang. Slots are reserved for critical
+/// requests, such as the self-test, which are never refused.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct OnDemandSettings {
+    /// The most on-demand requests served at once, including the slots reserved for critical requests
+    pub max_concurrent: u32,
+    /// How many of the slots only critical requests may take
+    pub reserved_critical: u32,
+    pub retry_after_secs: u64,
+}
+
+impl Default for OnDemandSettings {
+    fn default() -> Self {
+        OnDemandSettings {
+            max_concurrent: 4,
+            reserved_critical: 1,
+            retry_after_secs: 5,
+        }
+    }
+}
+
+/// Settings for scanning network and other remote volumes, such as a file server mapped as a drive letter. Walking a
+/// remote volume can take many hours, so by default scans skip any folder or file on one, counting how many were
+/// skipped. A scan can walk them regardless with its `include_remote_volumes` option.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct RemoteVolumeSettings {
+    pub skip_remote_volumes: bool,
+}
+
+impl Default for RemoteVolumeSettings {
+    fn default() -> Self {
+        RemoteVolumeSettings {
+            skip_remote_volumes: true,
+        }
+    }
+}
+
+/// Settings for throttling scans whilst the system is low on physical memory, so that a scan does not push the system
+/// into paging. Whilst less memory than the minimum is available, files of at least the large file size wait before
+/// being scanned until memory recovers, for at most the longest pause, after which they are scanned regardless.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct MemoryPressureSettings {
+    pub enabled: bool,
+    pub min_available_mb: u64,
+    pub large_file_mb: u64,
+    pub max_pause_secs: u64,
+}
+
+impl Default for MemoryPressureSettings {
+    fn default() -> Self {
+        MemoryPressureSettings {
+            enabled: true,
+            min_available_mb: 512,
+            large_file_mb: 16,
+            max_pause_secs: 120,
+        }
+    }
+}
+
+/// Settings for inspecting the memory of a running process for injected code. A process which has loaded one of the
+/// JIT modules, matched by file name regardless of case, compiles code into memory as it runs, so its writable and
+/// executable memory is not reported.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct InjectionSettings {
+    pub jit_modules: Vec<String>,
+}
+
+impl Default for InjectionSettings {
+    fn default() -> Self {
+        InjectionSettings {
+            jit_modules: [
+                // .NET
+                "clrjit.dll",
+                "mscorjit.dll",
+                // Java
+                "jvm.dll",
+                // V8, as embedded by Chromium based browsers, Electron apps and Node
+                "chrome.dll",
+                "msedge.dll",
+                "libcef.dll",
+                "v8.dll",
+                "node.exe",
+                // Firefox
+                "xul.dll",
+                // the JavaScript engines of Windows Script Host and the legacy browsers
+                "jscript9.dll",
+                "chakra.dll",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+/// Settings for resolving the verdicts of the detection engines. Every engine gives its verdict on each file, and where
+/// more than one detects a file, the detection reported is that of the engine named first in the precedence. Engines
+/// not named, such as registered detectors, rank where [`OTHER_ENGINES`] is in the precedence, in the order they run,
+/// or after every engine named where it is not.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct VerdictSettings {
+    pub precedence: Vec<String>,
+}
+
+/// Stands in the verdict precedence for every engine it does not name
+pub const OTHER_ENGINES: &str = "*";
+
+impl Default for VerdictSettings {
+    fn default() -> Self {
+        VerdictSettings {
+            precedence: [
+                "file_hash",
+                "section_hash",
+                "import_hash",
+                "fuzzy_hash",
+                "macro",
+                "shortcut",
+                OTHER_ENGINES,
+                "heuristic",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+/// Settings for the notifications shown to the user. Each detection of a scan is notified individually up to the limit,
+/// after which the rest are summarised together, so that a scan finding many threats does not flood the user with
+/// notifications. Every detection is still shown in the scan results.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct NotificationSettings {
+    pub detection_toast_limit: u32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            detection_toast_limit: 5,
+        }
+    }
+}
+
+/// What real-time protection does when it detects malware in a file as it is opened, and when the scan of the file
+/// takes too long to give a verdict.
+///
+/// Blocking on a timeout fails closed, so no file which could not be scanned is let through, but a slow scan (such as
+/// of a large file on a network share) then holds up whatever opened the file, which can hang the system where that is
+/// a system process. The default is to fail open, allowing the operation on a timeout.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub struct RealtimePolicy {
+    pub action: RealtimeAction,
+    pub on_timeout: RealtimeTimeoutAction,
+}
+
+/// What is done with a file operation on a file detected as malware
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum RealtimeAction {
+    /// The operation is denied, so the file cannot be opened
+    #[default]
+    Block,
+    /// The operation is allowed and the detection is only logged and reported
+    LogOnly,
+    /// The operation is allowed, and the file is quarantined once it completes
+    Quarantine,
+}
+
+/// What is done with a file operation when the scan of the file times out
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum RealtimeTimeoutAction {
+    /// The operation is allowed, failing open
+    #[default]
+    Allow,
+    /// The operation is denied, failing closed
+    Block,
+}
+
+/// Settings for forwarding detections to a SIEM, such as Splunk or QRadar, over syslog. This is off by default.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct SiemSettings {
+    pub enabled: bool,
+    pub format: SiemFormat,
+    /// The syslog collector detections are sent to, as `host:port`
+    pub collector: Option<String>,
+    pub transport: SyslogTransport,
+}
+
+/// Settings for posting each detection to a webhook as JSON, for automation which would otherwise poll the engine.
+/// This is off by default.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    /// The http:// or https:// URL detections are posted to
+    pub url: Option<String>,
+    /// The key each request is signed with using HMAC-SHA256, which the receiver checks the signature against
+    pub secret: Option<String>,
+}
+
+/// Settings for managing the engine from a remote console over TCP. This is off by default, so the engine can only be
+/// managed over its local named pipe. The listener always requires TLS, and each client must present a certificate
+/// issued by the client CA. The listener is started with the engine, so changes apply once the engine is restarted.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct RemoteManagementSettings {
+    pub enabled: bool,
+    /// The address to listen on, as `ip:port`
+    pub listen_address: Option<String>,
+    /// The PEM certificate chain presented to clients
+    pub cert_file: Option<PathBuf>,
+    /// The PEM private key of the certificate
+    pub key_file: Option<PathBuf>,
+    /// The PEM certificates of the CA which issues client certificates
+    pub client_ca_file: Option<PathBuf>,
+    /// The SHA-256 fingerprints, in hex, of the client certificates which may change the engine. Any other client
+    /// with a certificate issued by the client CA is served as an observer.
+    #[serde(default)]
+    pub controller_certificates: Vec<String>,
+    /// The SHA-256 fingerprints, in hex, of the client certificates served as a client running as an administrator
+    /// is, which are also served as controllers. No remote client is otherwise served as an administrator.
+    #[serde(default)]
+    pub elevated_certificates: Vec<String>,
+}
+
+/// The format detections are sent to a SIEM in
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum SiemFormat {
+    /// ArcSight Common Event Format, as read by Splunk and most other SIEMs
+    #[default]
+    Cef,
+    /// IBM QRadar Log Event Extended Format
+    Leef,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum SyslogTransport {
+    #[default]
+    Udp,
+    /// Each message is terminated by a newline, as most collectors expect of syslog over TCP
+    Tcp,
+}
+
+/// Settings for scanning removable media, such as USB sticks, as soon as it is inserted. Each volume is scanned in
+/// full with the default scan options, so detections are reported but not acted on. This is off by default.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct RemovableMediaSettings {
+    pub scan_on_insert: bool,
+    /// Serial numbers of volumes which are never scanned on insertion, as Windows shows them, such as `1A2B-3C4D`.
+    /// Matched case insensitively.
+    pub excluded_serials: Vec<String>,
+}
+
+impl RemovableMediaSettings {
+    /// Whether a volume with the serial number is scanned when it is inserted
+    pub fn scans(&self, serial: &str) -> bool {
+        self.scan_on_insert
+            && !self
+                .excluded_serials
+                .iter()
+                .any(|excluded| excluded.trim().eq_ignore_ascii_case(serial))
+    }
+}
+
+/// Settings for the maintenance task, which periodically prunes the scan history and quarantine store
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MaintenanceSettings {
+    /// Scan history older than this many days is deleted. 0 keeps history forever.
+    pub history_retention_days: u32,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        MaintenanceSettings {
+            history_retention_days: 90,
+        }
+    }
+}
+
+/// Settings for matching files against fuzzy hash signatures, which detects near duplicates of known malware. This is
+/// off by default as every file must be read a second time to compute its fuzzy hash.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FuzzyHashSettings {
+    pub enabled: bool,
+    /// The similarity (1 - 100) at which a file is reported as similar to a signature
+    pub similarity_threshold: u32,
+}
+
+impl Default for FuzzyHashSettings {
+    fn default() -> Self {
+        FuzzyHashSettings {
+            enabled: false,
+            similarity_threshold: 75,
+        }
+    }
+}
+
+/// Settings for the heuristic engine, which scores files on signals which are suspicious but not malicious by
+/// themselves. A file is reported once the scores of its signals add up to the threshold. This is
+/// off by default, as heuristics will flag some legitimate software.
+///
+/// Heuristic detections are only ever reported; the scan's action, such as quarantine, is not taken on them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HeuristicSettings {
+    pub enabled: bool,
+    /// The total score at which a file is reported
+    pub score_threshold: u32,
+    /// Folders where a PE is more suspicious than elsewhere, such as the temp folder or a browser cache
+    pub suspicious_roots: Vec<SuspiciousRoot>,
+}
+
+impl Default for HeuristicSettings {
+    fn default() -> Self {
+        let roots = [
+            "%TEMP%",
+            "%SystemRoot%\\Temp",
+            "%LOCALAPPDATA%\\Temp",
+            "%LOCALAPPDATA%\\Microsoft\\Windows\\INetCache",
+            "%LOCALAPPDATA%\\Google\\Chrome\\User Data\\Default\\Cache",
+            "%LOCALAPPDATA%\\Microsoft\\Edge\\User Data\\Default\\Cache",
+            "%LOCALAPPDATA%\\Mozilla\\Firefox\\Profiles",
+            "%SystemDrive%\\$Recycle.Bin",
+        ];
+
+        HeuristicSettings {
+            enabled: false,
+            score_threshold: 50,
+            suspicious_roots: roots
+                .into_iter()
+                .map(|path| SuspiciousRoot {
+                    path: path.to_string(),
+                    score: 50,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A folder where a PE adds to the heuristic score of the file
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SuspiciousRoot {
+    /// The folder, which may contain environment variables such as `%TEMP%`. Everything beneath it is included.
+    pub path: String,
+    pub score: u32,
+}
+
+/// Paths which the file scanner will skip during a scan.
+///
+/// Exclusions take precedence over detections: an excluded path is never opened by the scanner, so it cannot produce
+/// a detection even if it would match an IOC. An excluded folder is not walked, so nothing beneath it is scanned.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ExclusionList {
+    pub rules: Vec<ExclusionRule>,
+}
+
+/// Whether a path would be excluded from scans, and by which rule
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ExclusionMatch {
+    pub excluded: bool,
+    /// The rule which excludes the path, or None if the path would be scanned
+    pub rule: Option<ExclusionRule>,
+    /// The path the rule matched. This is the tested path itself, or where a folder above it is excluded, that
+    /// folder, as an excluded folder is not walked so nothing beneath it is scanned.
+    pub matched_path: Option<PathBuf>,
+}
+
+/// A request to preview what adding an exclusion would have changed about an earlier scan
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ExclusionPreviewRequest {
+    /// The exclusion being considered, which is not added to the exclusions
+    pub rule: ExclusionRule,
+    /// The scan in the scan history to preview the exclusion against
+    pub scan_id: u64,
+}
+
+/// What an exclusion would have changed about an earlier scan, had it been in place
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ExclusionPreview {
+    pub scan_id: u64,
+    /// The number of the scan's detections
+    pub detections_total: usize,
+    /// The scan's detections which the exclusion would have excluded, so which would not have been found
+    pub detections_excluded: Vec<MatchedIOC>,
+    /// Whether the files were counted. The scan history does not keep the files each scan scanned, so they are
+    /// counted from what is now beneath the folders the scan walked; a scan recorded before its folders were kept,
+    /// or of something other than files and folders, cannot be counted.
+    pub files_counted: bool,
+    /// The files now beneath the folders the scan walked which the current exclusions let through
+    pub files_total: u64,
+    /// Of those, the files the exclusion would exclude
+    pub files_excluded: u64,
+    /// The total size of the files the exclusion would exclude
+    pub bytes_excluded: u64,
+    /// Whether counting the files was cancelled before the walk finished, in which case the counts are what was
+    /// found so far
+    pub cancelled: bool,
+}
+
+/// A single exclusion. All rules are matched case insensitively, as paths on Windows are.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ExclusionRule {
+    /// Excludes the path and everything beneath it
+    Prefix(PathBuf),
+    /// A glob matched against the full path, such as `**/cache/**/*.log`. Either path separator may be used.
+    Glob(String),
+    /// A regex matched against the full path. When anchored, the regex must match the whole path rather than
+    /// any part of it.
+    Regex { pattern: String, anchored: bool },
 }
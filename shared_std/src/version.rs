@@ -0,0 +1,245 @@
+//! Version information reported by the engine for support and triage.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// The versions of each component of a running Sanctum install
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VersionInfo {
+    pub engine_version: String,
+    /// The UTC date the engine was built, as YYYY-MM-DD
+    pub build_date: String,
+    /// Identifies the IOC list the engine has loaded, as the number of signatures and a fingerprint of their content
+    pub signature_version: String,
+    /// The version reported by the driver, or None if the driver is not running
+    pub driver_version: Option<String>,
+    /// The number of signatures loaded into each detection engine
+    pub signatures: SignatureCounts,
+    /// The outcome of loading each file in the signatures folder, including any which were skipped
+    pub signature_files: Vec<SignatureFileStats>,
+    /// Each signature database loaded, and whether it is matched against
+    #[serde(default)]
+    pub signature_databases: Vec<SignatureDatabaseInfo>,
+}
+
+/// The number of signatures loaded into each detection engine, all taken from the set in use at the same moment. A
+/// count of zero means the signatures for that engine failed to load (or none are configured).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct SignatureCounts {
+    /// Whole file hashes (MD5 and SHA256), excluding signatures built in to the engine
+    pub hash_signatures: usize,
+    /// Hashes of individual PE sections
+    pub section_signatures: usize,
+    /// Import hashes (imphashes) of PEs
+    #[serde(default)]
+    pub import_hash_signatures: usize,
+    /// Strings matched against the contents of registry values by a registry scan
+    #[serde(default)]
+    pub registry_signatures: usize,
+    /// YARA rules. YARA is not yet supported by the engine, so this is always 0.
+    pub yara_rules: usize,
+    pub fuzzy_signatures: usize,
+}
+
+/// The formats signatures can be imported from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SignatureImportFormat {
+    /// One MD5 or SHA256 hash per line, with blank lines and lines starting with `#` ignored
+    HashList,
+    /// ClamAV hash signatures (`.hdb` and `.hsb`), one `hash:size:name` per line. Only the hash is imported, as the
+    /// engine matches on the hash alone.
+    ClamAv,
+    /// A signature export written by `signatures_export`, such as from another machine. The export's checksum is
+    /// verified before anything is imported, and every kind of signature in it is imported.
+    SanctumExport,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureImportRequest {
+    pub format: SignatureImportFormat,
+    pub path: PathBuf,
+}
+
+/// The outcome of importing signatures from a third party format
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureImportReport {
+    /// The number of distinct signatures imported
+    pub imported: usize,
+    /// Lines which were not a valid signature in the format
+    pub skipped_malformed: usize,
+    /// Valid signatures of a kind the engine cannot match, such as SHA1 hashes
+    pub skipped_unsupported: usize,
+    /// The signature file the imported signatures were written to, in the signatures folder
+    pub file: PathBuf,
+    /// The number of signatures loaded into each detection engine once the import was loaded
+    pub signatures: SignatureCounts,
+}
+
+/// How serious a problem found in a signature file by `signatures_validate` is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SignatureIssueSeverity {
+    /// The engine would skip the whole file
+    Error,
+    /// The file would load, but not as its author likely meant
+    Warning,
+}
+
+/// The kind of problem found in a signature file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SignatureIssueKind {
+    /// A line which is not a valid signature, such as a hash of the wrong length
+    Malformed,
+    /// A signature given more than once, where each repeat has no effect
+    Duplicate,
+    /// A hash given both with and without conditions, which matches regardless of its conditions
+    Conflicting,
+    /// Conditions which are not valid, or which the file's format version does not allow
+    UnsupportedCondition,
+    /// A format version this engine cannot load, or a file the engine would not load from the signatures folder
+    UnsupportedFormat,
+    /// A YARA rule. YARA is not yet supported by the engine, so no rule is checked beyond being found.
+    Yara,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureIssue {
+    /// The line the problem is on, counting from 1, or None for a problem with the file as a whole
+    pub line: Option<usize>,
+    pub severity: SignatureIssueSeverity,
+    pub kind: SignatureIssueKind,
+    pub message: String,
+}
+
+/// A request to check a signature file without loading it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureValidationRequest {
+    pub path: PathBuf,
+}
+
+/// The outcome of checking a signature file with `signatures_validate`, which leaves the signatures in use untouched
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureValidationReport {
+    pub path: PathBuf,
+    /// Whether the engine would load the file, being whether no error was found. A file with only warnings loads.
+    pub valid: bool,
+    /// The signatures the file holds of each kind, not counting repeats or lines with errors
+    pub signatures: SignatureCounts,
+    /// Every problem found, those with the file as a whole first, then in the order of the lines they are on
+    pub issues: Vec<SignatureIssue>,
+}
+
+/// A request to export the signatures matched against to a single file, which can be imported on another machine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureExportRequest {
+    /// The file to write the export to, which is replaced if it exists
+    pub dest: PathBuf,
+}
+
+/// The outcome of exporting the signatures
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureExportReport {
+    pub dest: PathBuf,
+    /// The number of signatures of each kind exported
+    pub signatures: SignatureCounts,
+    /// The signature version of the set exported
+    pub signature_version: String,
+    /// The names of the databases exported, being those enabled when the export was made
+    pub databases: Vec<String>,
+    /// The SHA256 of the signatures in the export, as uppercase hex, which is verified when it is imported
+    pub checksum: String,
+}
+
+/// An algorithm a file can be hashed with by `hash_file`, for writing signatures
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha256,
+    /// The ssdeep compatible fuzzy hash matched by the fuzzy hashing engine
+    Fuzzy,
+}
+
+/// A request to hash a file with one or more algorithms
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HashRequest {
+    pub path: PathBuf,
+    pub algorithms: Vec<HashAlgorithm>,
+}
+
+/// The hash of a file, in the format it is written in a signature file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HashResult {
+    pub algo: HashAlgorithm,
+    /// The hash as uppercase hex, or for a fuzzy hash, in ssdeep's `blocksize:hash:hash` format
+    pub hex: String,
+    /// The size of the file hashed, in bytes
+    pub size: u64,
+}
+
+/// The outcome of loading a single file from the signatures folder
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureFileStats {
+    pub path: PathBuf,
+    pub hash_signatures: usize,
+    pub section_signatures: usize,
+    #[serde(default)]
+    pub import_hash_signatures: usize,
+    #[serde(default)]
+    pub registry_signatures: usize,
+    pub fuzzy_signatures: usize,
+    /// Why the file was skipped, where it could not be loaded. A skipped file contributes no signatures.
+    pub error: Option<String>,
+}
+
+/// A named signature database. The IOC lists in the Sanctum folder make up the built in database, and each file
+/// loaded from the signatures folder is a database of its own, named after the file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureDatabaseInfo {
+    pub name: String,
+    /// The signature file the database was loaded from, or None for the built in database
+    pub path: Option<PathBuf>,
+    /// Whether files are matched against the database; a disabled database stays loaded so it can be enabled again
+    /// without a reload
+    pub enabled: bool,
+    pub hash_signatures: usize,
+    pub section_signatures: usize,
+    #[serde(default)]
+    pub import_hash_signatures: usize,
+    #[serde(default)]
+    pub registry_signatures: usize,
+    pub fuzzy_signatures: usize,
+}
+
+/// Enables or disables matching against the named signature database
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureDatabaseToggle {
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// Whether the engine is ready for an updater to replace its signatures or binaries, in reply to `update_begin`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct UpdateReadiness {
+    /// Whether every scan has ended, so the update can be applied. Where this is false the update has still begun,
+    /// and `update_begin` can be called again to wait for longer.
+    pub ready: bool,
+    /// The IDs of the scans which were cancelled for the update
+    pub scans_cancelled: Vec<u64>,
+}
+
+/// A request to finish an update begun with `update_begin`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpdateCommitRequest {
+    /// Whether the engine's binaries were replaced, so the engine must restart to finish the update. Otherwise the
+    /// signatures are reloaded and scans resume.
+    pub restart_required: bool,
+}
+
+/// The outcome of committing an update
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpdateCommitted {
+    /// Whether the engine is waiting to be restarted, in which case scans stay refused until it is
+    pub restart_required: bool,
+    /// The signatures loaded once the update was committed, or None where the engine is waiting to be restarted
+    pub signatures: Option<SignatureCounts>,
+}
@@ -0,0 +1,36 @@
+//! Build script for the engine, used to embed build metadata into the binary.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // respect SOURCE_DATE_EPOCH so reproducible builds get a stable date
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    println!("cargo:rustc-env=SANCTUM_BUILD_DATE={year:04}-{month:02}-{day:02}");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+/// Converts a count of days since the unix epoch into a (year, month, day) in the proleptic Gregorian calendar,
+/// using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,47 @@
+//! The audit log, recording actions which could put the machine at risk, such as a sample being extracted from
+//! quarantine, so that they can be accounted for afterwards.
+//!
+//! Each action is appended to the log as a line of JSON. The engine never rewrites or prunes the log.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use serde::Serialize;
+use shared_no_std::constants::AUDIT_LOG_LOCATION;
+
+/// A single action recorded in the audit log
+#[derive(Serialize)]
+pub struct AuditRecord<'a> {
+    pub at: SystemTime,
+    /// The command which took the action, such as `quarantine_extract`
+    pub action: &'a str,
+    /// What the action was taken on, such as the file extracted and where it was extracted to
+    pub detail: String,
+    /// Why the action failed, or None where it succeeded
+    pub error: Option<String>,
+}
+
+/// Appends the record to the audit log.
+///
+/// # Returns
+///
+/// An error if the record could not be written, in which case the caller should not let the action stand, as it would
+/// go unrecorded.
+pub fn record(record: &AuditRecord) -> io::Result<()> {
+    let path = audit_log_path().ok_or_else(|| io::Error::other("Could not find the App Data folder."))?;
+
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)
+}
+
+fn audit_log_path() -> Option<PathBuf> {
+    std::env::var("APPDATA")
+        .ok()
+        .map(|app_data| PathBuf::from(app_data).join(AUDIT_LOG_LOCATION))
+}
@@ -1,10 +1,18 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
 
+use shared_std::{driver_manager::DriverStats, events::EngineEvent};
 use tokio::sync::{Mutex, mpsc};
 
 use crate::{
     core::process_monitor::inject_edr_dll,
     driver_manager::SanctumDriverManager,
+    events::EventBus,
     utils::log::{Log, LogLevel},
 };
 
@@ -22,10 +30,15 @@ use super::{ipc_etw_consumer::run_ipc_for_etw, ipc_injected_dll::run_ipc_for_inj
 /// takes place prior to the poll rate sleep time.
 /// - `driver_dbg_message_cache`: a temporary cache of messages which are returned from the kernel which the
 /// GUI can request.
+/// - `events_received`, `usermode_dropped` and `last_event_time`: how well the engine is keeping up with the
+/// events from the driver, reported by [`Core::driver_stats`].
 #[derive(Debug, Default)]
 pub struct Core {
     driver_poll_rate: u64,
     driver_dbg_message_cache: Mutex<Vec<String>>,
+    events_received: AtomicU64,
+    usermode_dropped: AtomicU64,
+    last_event_time: Mutex<Option<SystemTime>>,
     // process_monitor: RwLock<ProcessMonitor>,
 }
 
@@ -39,8 +52,13 @@ impl Core {
         core
     }
 
-    /// Starts the core of the usermode engine; kicking off the frequent polling of the driver, and conducts relevant decision making
-    pub async fn start_core(&self, driver_manager: Arc<Mutex<SanctumDriverManager>>) -> ! {
+    /// Starts the core of the usermode engine; kicking off the frequent polling of the driver, and conducts relevant decision making.
+    /// Messages from the driver are published to the event bus as telemetry.
+    pub async fn start_core(
+        &self,
+        driver_manager: Arc<Mutex<SanctumDriverManager>>,
+        events: Arc<EventBus>,
+    ) -> ! {
         let logger = Log::new();
 
         //
@@ -70,6 +88,16 @@ impl Core {
         // todo: we need to actually inspect what these params are doing and if they are malicious.
         //
         loop {
+            // whilst the driver is unavailable there is nothing to poll, and syscall events from the injected DLL
+            // cannot be passed on to it, so they are dropped rather than building up
+            if !driver_manager.lock().await.is_available() {
+                while rx.try_recv().is_ok() {
+                    self.usermode_dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                tokio::time::sleep(Duration::from_millis(self.driver_poll_rate)).await;
+                continue;
+            }
+
             // See if there is a message from the injected DLL
             if let Ok(rx) = rx.try_recv() {
                 let mut mtx = driver_manager.lock().await;
@@ -93,6 +121,22 @@ impl Core {
                 // first deal with process terminations to prevent trying to add to an old process id if there is a duplicate
                 let mut driver_messages = driver_response.unwrap();
 
+                let num_events = driver_messages.messages.len()
+                    + driver_messages.process_creations.len()
+                    + driver_messages.process_terminations.len()
+                    + driver_messages.handles.len();
+                if num_events > 0 {
+                    self.events_received.fetch_add(num_events as u64, Ordering::Relaxed);
+                    *self.last_event_time.lock().await = Some(SystemTime::now());
+                }
+
+                for message in &driver_messages.messages {
+                    events.publish(EngineEvent::Telemetry {
+                        source: "driver".to_string(),
+                        message: message.clone(),
+                    });
+                }
+
                 // cache messages
                 {
                     let mut message_cache = self.driver_dbg_message_cache.lock().await;
@@ -132,6 +176,26 @@ impl Core {
         Some(tmp)
     }
 
+    /// Gets how well the engine is keeping up with the events the driver raises, querying the driver for the state of
+    /// its queue where it is available
+    pub async fn driver_stats(&self, driver_manager: &Mutex<SanctumDriverManager>) -> DriverStats {
+        let queue = {
+            let mut lock = driver_manager.lock().await;
+            match lock.is_available() {
+                true => lock.ioctl_get_queue_stats(),
+                false => None,
+            }
+        };
+
+        DriverStats {
+            events_received: self.events_received.load(Ordering::Relaxed),
+            events_dropped: queue.map(|q| q.events_dropped),
+            queue_depth: queue.map(|q| q.queue_depth),
+            usermode_dropped: self.usermode_dropped.load(Ordering::Relaxed),
+            last_event_time: *self.last_event_time.lock().await,
+        }
+    }
+
     // Query a given process by its Pid, returning information about the process
     // pub async fn query_process_by_pid(&self, pid: u64) -> Option<Process> {
     //     self.process_monitor.read().await.query_process_by_pid(pid)
@@ -0,0 +1,176 @@
+//! Diagnostic self-test of the engine, confirming end to end that detection is working.
+//!
+//! The self-test runs the EICAR test pattern through the same streaming scan used by `scanner_scan_bytes`. The
+//! pattern is held XOR encoded so the engine binary itself is not flagged by other antivirus products, and is
+//! only decoded into memory for the duration of the scan; it is never written to disk.
+
+use std::{fs, path::PathBuf, sync::Arc, time::Instant};
+
+use shared_no_std::constants::QUARANTINE_LOCATION;
+use shared_std::{
+    diagnostics::{ComponentCheck, SelfTestReport, WarmupReport},
+    driver_manager::DriverState,
+    version::VersionInfo,
+};
+use tokio::sync::Mutex;
+
+use crate::{driver_manager::SanctumDriverManager, filescanner::FileScanner};
+
+const EICAR_KEY: u8 = 0x5A;
+/// The EICAR standard antivirus test file, XOR'd with EICAR_KEY
+const EICAR_ENCODED: [u8; 68] = [
+    0x02, 0x6F, 0x15, 0x7B, 0x0A, 0x7F, 0x1A, 0x1B, 0x0A, 0x01, 0x6E, 0x06, 0x0A, 0x00, 0x02, 0x6F,
+    0x6E, 0x72, 0x0A, 0x04, 0x73, 0x6D, 0x19, 0x19, 0x73, 0x6D, 0x27, 0x7E, 0x1F, 0x13, 0x19, 0x1B,
+    0x08, 0x77, 0x09, 0x0E, 0x1B, 0x14, 0x1E, 0x1B, 0x08, 0x1E, 0x77, 0x1B, 0x14, 0x0E, 0x13, 0x0C,
+    0x13, 0x08, 0x0F, 0x09, 0x77, 0x0E, 0x1F, 0x09, 0x0E, 0x77, 0x1C, 0x13, 0x16, 0x1F, 0x7B, 0x7E,
+    0x12, 0x71, 0x12, 0x70,
+];
+
+/// Runs each of the self-test checks, returning a report of the outcome for each component. A failing
+/// component does not stop the remaining checks from running.
+pub async fn run_self_test(
+    file_scanner: &Arc<FileScanner>,
+    driver_manager: &Arc<Mutex<SanctumDriverManager>>,
+) -> SelfTestReport {
+    SelfTestReport {
+        signature_db: check_signature_db(file_scanner),
+        detection: check_detection(file_scanner),
+        driver: check_driver(driver_manager).await,
+        quarantine: check_quarantine(),
+    }
+}
+
+/// Readies the engine for its first scan, so that it does not pay for anything still to be done. The driver is probed,
+/// opening the handle to it now rather than at the next periodic probe, then the self-test takes a scan through the
+/// detection pipeline. The signatures themselves are loaded before the engine serves any request, so are already in
+/// memory by the time this can be called.
+///
+/// Nothing is changed other than the driver handle being opened, so this may be called any number of times, including
+/// whilst a scan runs or the signatures are being reloaded. A reload swaps the new set in whole, so the self-test
+/// matches against whichever set is current.
+pub async fn warmup(
+    file_scanner: &Arc<FileScanner>,
+    driver_manager: &Arc<Mutex<SanctumDriverManager>>,
+) -> WarmupReport {
+    let started = Instant::now();
+
+    let driver = driver_manager.lock().await.probe_availability();
+    let self_test = run_self_test(file_scanner, driver_manager).await;
+
+    WarmupReport {
+        ready: self_test.signature_db.passed && self_test.detection.passed,
+        driver,
+        self_test,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Gets the versions of each component, only asking the driver for its version when it is running, as otherwise
+/// opening a handle to it would just fail
+pub async fn version_info(
+    file_scanner: &FileScanner,
+    driver_manager: &Arc<Mutex<SanctumDriverManager>>,
+) -> VersionInfo {
+    let driver_version = {
+        let mut lock = driver_manager.lock().await;
+        match lock.get_state() {
+            DriverState::Started(_) => lock.ioctl_get_driver_version(),
+            _ => None,
+        }
+    };
+
+    VersionInfo {
+        engine_version: env!("CARGO_PKG_VERSION").to_string(),
+        build_date: env!("SANCTUM_BUILD_DATE").to_string(),
+        signature_version: file_scanner.signature_version().to_string(),
+        driver_version,
+        signatures: file_scanner.signature_counts(),
+        signature_files: file_scanner.signature_files(),
+        signature_databases: file_scanner.signature_databases(),
+    }
+}
+
+fn check_signature_db(file_scanner: &FileScanner) -> ComponentCheck {
+    let count = file_scanner.signature_count();
+
+    ComponentCheck {
+        passed: count > 0,
+        detail: format!(
+            "{count} signatures loaded, version {}.",
+            file_scanner.signature_version()
+        ),
+    }
+}
+
+fn check_detection(file_scanner: &FileScanner) -> ComponentCheck {
+    let mut eicar = EICAR_ENCODED;
+    eicar.iter_mut().for_each(|b| *b ^= EICAR_KEY);
+
+    let mut scan = file_scanner.begin_bytes_scan();
+    scan.update(&eicar);
+    let result = file_scanner.finish_bytes_scan(scan, "EICAR self-test");
+
+    // don't leave the decoded pattern lying around in memory
+    eicar.fill(0);
+
+    match result.ioc {
+        Some(_) => ComponentCheck {
+            passed: true,
+            detail: "EICAR test pattern detected.".to_string(),
+        },
+        None => ComponentCheck {
+            passed: false,
+            detail: format!("EICAR test pattern was not detected, hash {}.", result.hash),
+        },
+    }
+}
+
+async fn check_driver(driver_manager: &Arc<Mutex<SanctumDriverManager>>) -> ComponentCheck {
+    let mut lock = driver_manager.lock().await;
+
+    match lock.get_state() {
+        DriverState::Started(_) => match lock.ioctl_get_driver_version() {
+            Some(version) => ComponentCheck {
+                passed: true,
+                detail: format!("Driver responded, version {version}."),
+            },
+            None => ComponentCheck {
+                passed: false,
+                detail: "Driver is started but did not respond to an IOCTL.".to_string(),
+            },
+        },
+        state => ComponentCheck {
+            passed: false,
+            detail: format!("Driver is not running, state: {state:?}."),
+        },
+    }
+}
+
+fn check_quarantine() -> ComponentCheck {
+    let app_data = match std::env::var("APPDATA") {
+        Ok(a) => a,
+        Err(e) => {
+            return ComponentCheck {
+                passed: false,
+                detail: format!("Could not find the App Data folder. {e}"),
+            };
+        }
+    };
+    let dir = PathBuf::from(app_data).join(QUARANTINE_LOCATION);
+    let probe = dir.join(".self_test");
+
+    let result = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&probe, b"Sanctum self-test"))
+        .and_then(|_| fs::remove_file(&probe));
+
+    match result {
+        Ok(_) => ComponentCheck {
+            passed: true,
+            detail: format!("Quarantine folder {} is writable.", dir.display()),
+        },
+        Err(e) => ComponentCheck {
+            passed: false,
+            detail: format!("Quarantine folder {} is not writable. {e}", dir.display()),
+        },
+    }
+}
@@ -0,0 +1,238 @@
+//! Exporting a diagnostics bundle: a zip archive holding what support needs to look into a problem, which the user
+//! can attach to a bug report.
+//!
+//! The bundle holds the versions of each component and the signatures loaded, the self-test results, the settings
+//! along with where each came from, summaries of the most recent scans and the end of the engine's log. Unless the user opts into full detail, file
+//! paths and the user name are replaced in everything written to the bundle, as they can reveal more about the user
+//! than a bug report needs. The detections of each scan are counted rather than listed, for the same reason. Secrets in
+//! the settings, such as the key webhooks are signed with, are withheld even in full detail.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    time::SystemTime,
+};
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use shared_std::{
+    diagnostics::{DiagnosticsExportReport, DiagnosticsExportRequest, SelfTestReport},
+    history::ScanRecord,
+    settings::{EffectiveConfig, SanctumSettings},
+    version::VersionInfo,
+};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::utils::{
+    env::get_logged_in_username,
+    log::{Log, LogLevel, get_log_file_path},
+};
+
+/// The number of recent scans summarised in the bundle
+pub const BUNDLE_RECENT_SCANS: usize = 10;
+
+/// The settings which are never written to the bundle, even in full detail, by their path in the settings file
+const SECRET_SETTINGS: [&str; 1] = ["webhook.secret"];
+
+/// What a secret setting which is set is replaced with in the bundle
+const WITHHELD: &str = "<withheld>";
+
+/// How much of the end of the log is included in the bundle
+const LOG_TAIL_BYTES: u64 = 1024 * 1024;
+
+/// What is written to the bundle, gathered by the caller from the running engine
+pub struct DiagnosticsBundle {
+    pub versions: VersionInfo,
+    pub self_test: SelfTestReport,
+    pub settings: SanctumSettings,
+    pub effective_config: Result<EffectiveConfig, String>,
+    pub recent_scans: Vec<ScanRecord>,
+}
+
+/// A scan from the history, without the files it detected or which changed during it
+#[derive(Serialize)]
+struct ScanSummary {
+    scan_id: u64,
+    started_at: Option<SystemTime>,
+    finished_at: Option<SystemTime>,
+    outcome: String,
+    cancel_reason: Option<String>,
+    num_files_scanned: u128,
+    bytes_scanned: u64,
+    detections: usize,
+    changed_during_scan: usize,
+}
+
+impl From<&ScanRecord> for ScanSummary {
+    fn from(record: &ScanRecord) -> Self {
+        ScanSummary {
+            scan_id: record.scan_id,
+            started_at: record.started_at,
+            finished_at: record.finished_at,
+            outcome: record.outcome.clone(),
+            cancel_reason: record.cancel_reason.clone(),
+            num_files_scanned: record.num_files_scanned,
+            bytes_scanned: record.bytes_scanned,
+            detections: record.detections.len(),
+            changed_during_scan: record.changed_during_scan.len(),
+        }
+    }
+}
+
+/// Writes the bundle to the requested file. A failed export removes the partly written file rather than leaving a
+/// truncated archive behind.
+pub fn export_diagnostics(
+    request: &DiagnosticsExportRequest,
+    bundle: &DiagnosticsBundle,
+) -> Result<DiagnosticsExportReport, String> {
+    let redactor = (!request.full_detail).then(Redactor::new);
+
+    let file = File::create(&request.dest)
+        .map_err(|e| format!("Could not create the diagnostics bundle {}. {e}", request.dest.display()))?;
+
+    let contents = match write_bundle(bundle, redactor.as_ref(), file) {
+        Ok(contents) => contents,
+        Err(e) => {
+            let _ = fs::remove_file(&request.dest);
+            return Err(format!(
+                "Could not write the diagnostics bundle to {}. {e}",
+                request.dest.display()
+            ));
+        }
+    };
+
+    let bytes_written = fs::metadata(&request.dest).map(|m| m.len()).unwrap_or(0);
+    Log::new().log(
+        LogLevel::Info,
+        &format!(
+            "Exported a diagnostics bundle to {}, {bytes_written} bytes{}.",
+            request.dest.display(),
+            if redactor.is_some() { ", redacted" } else { "" }
+        ),
+    );
+
+    Ok(DiagnosticsExportReport {
+        file: request.dest.clone(),
+        bytes_written,
+        redacted: redactor.is_some(),
+        contents,
+    })
+}
+
+/// Writes each part of the bundle into the archive, returning the names of the files written
+fn write_bundle(
+    bundle: &DiagnosticsBundle,
+    redactor: Option<&Redactor>,
+    file: File,
+) -> io::Result<Vec<String>> {
+    let scans: Vec<ScanSummary> = bundle.recent_scans.iter().map(ScanSummary::from).collect();
+
+    let mut settings = bundle.settings.clone();
+    settings.webhook.secret = settings.webhook.secret.map(|_| WITHHELD.to_string());
+    let mut effective_config = bundle.effective_config.clone();
+    if let Ok(config) = &mut effective_config {
+        for setting in config.settings.iter_mut().filter(|s| SECRET_SETTINGS.contains(&s.key.as_str())) {
+            if !setting.value.is_null() {
+                setting.value = Value::String(WITHHELD.to_string());
+            }
+        }
+    }
+
+    let parts = [
+        ("versions.json", to_json(&bundle.versions, redactor)?),
+        ("self_test.json", to_json(&bundle.self_test, redactor)?),
+        ("settings.json", to_json(&settings, redactor)?),
+        ("effective_config.json", to_json(&effective_config, redactor)?),
+        ("recent_scans.json", to_json(&scans, redactor)?),
+        ("engine.log", log_tail(redactor)?),
+    ];
+
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, data) in &parts {
+        zip.start_file(*name, options).map_err(io::Error::other)?;
+        zip.write_all(data.as_bytes())?;
+    }
+
+    zip.finish().map_err(io::Error::other)?;
+
+    Ok(parts.iter().map(|(name, _)| name.to_string()).collect())
+}
+
+/// Serialises the part of the bundle as pretty printed JSON, redacting each string in it
+fn to_json<T: Serialize>(value: &T, redactor: Option<&Redactor>) -> io::Result<String> {
+    let mut value = serde_json::to_value(value)?;
+    if let Some(redactor) = redactor {
+        redactor.redact_value(&mut value);
+    }
+
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// Reads the end of the engine's log, starting from the first whole line. A log which cannot be opened is noted in
+/// the bundle instead, so the rest of the bundle is still exported.
+fn log_tail(redactor: Option<&Redactor>) -> io::Result<String> {
+    let mut file = match File::open(get_log_file_path()) {
+        Ok(f) => f,
+        Err(e) => return Ok(format!("The engine log could not be opened. {e}\n")),
+    };
+
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(LOG_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    // a tail starting part way through the log begins with the end of a line, which is dropped
+    let text = match start {
+        0 => &text[..],
+        _ => text.split_once('\n').map_or("", |(_, rest)| rest),
+    };
+
+    Ok(match redactor {
+        Some(redactor) => text.lines().map(|line| redactor.redact(line) + "\n").collect(),
+        None => text.to_string(),
+    })
+}
+
+/// Replaces file paths and the user name in text written to the bundle
+struct Redactor {
+    paths: Regex,
+    username: Option<Regex>,
+}
+
+impl Redactor {
+    fn new() -> Self {
+        // drive letter paths, along with UNC and device paths such as \\?\Volume{...}\
+        let paths = Regex::new(r#"(?i)(?:\b[a-z]:|\\\\[^\\\s"]+)\\[^\s"'<>|]*"#).unwrap();
+
+        let username = get_logged_in_username()
+            .ok()
+            .filter(|name| !name.is_empty())
+            .map(|name| Regex::new(&format!("(?i){}", regex::escape(&name))).unwrap());
+
+        Redactor { paths, username }
+    }
+
+    fn redact(&self, text: &str) -> String {
+        let text = self.paths.replace_all(text, "<path>");
+
+        match &self.username {
+            Some(username) => username.replace_all(&text, "<user>").into_owned(),
+            None => text.into_owned(),
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => *s = self.redact(s),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(fields) => fields.values_mut().for_each(|field| self.redact_value(field)),
+            _ => (),
+        }
+    }
+}
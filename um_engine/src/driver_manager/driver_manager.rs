@@ -1,8 +1,8 @@
 //! The main setup and more general functions for the driver manager module for the usermode engine
 
 use shared_no_std::constants::{DRIVER_UM_NAME, SANC_SYS_FILE_LOCATION, SVC_NAME};
-use shared_std::driver_manager::DriverState;
-use std::{os::windows::ffi::OsStrExt, path::PathBuf};
+use shared_std::driver_manager::{DriverAvailability, DriverState};
+use std::{os::windows::ffi::OsStrExt, path::PathBuf, time::Duration};
 use windows::{
     Win32::{
         Foundation::{CloseHandle, GetLastError, HANDLE},
@@ -11,7 +11,17 @@ use windows::{
     core::PCWSTR,
 };
 
-use crate::{strings::ToUnicodeString, utils::log::Log};
+use crate::{
+    strings::ToUnicodeString,
+    utils::log::{Log, LogLevel},
+};
+
+/// How often the engine checks whether the driver has become available, or has gone away
+pub const DRIVER_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Why the driver is unavailable when it cannot be reached, without a more specific reason
+const DRIVER_UNREACHABLE: &str = "Could not connect to the Sanctum driver, it may not be installed or started. \
+    Real-time protection, telemetry and process monitoring are unavailable until it is.";
 
 /// The SanctumDriverManager holds key information to be shared between
 /// modules which relates to uniquely identifiable attributes such as its name
@@ -27,6 +37,8 @@ pub struct SanctumDriverManager {
     pub(super) svc_name: Vec<u16>,
     pub handle_via_path: DriverHandleRaii,
     pub state: DriverState,
+    /// Whether the driver could be reached when it was last probed
+    availability: DriverAvailability,
     pub log: Log,
 }
 
@@ -54,33 +66,44 @@ impl SanctumDriverManager {
 
         let svc_name = SVC_NAME.to_u16_vec();
 
-        // check the sys file exists
-        // todo this eventually should be in the actual install directory under Windows
-        let x = unsafe { GetFileAttributesW(PCWSTR::from_raw(sys_file_path.as_ptr())) };
-        if x == INVALID_FILE_ATTRIBUTES {
-            panic!(
-                "[-] Cannot find sanctum.sys. Err: {}. Ensure the driver file is at: {:?}",
-                unsafe { GetLastError().0 },
-                sys_file_path
-            );
-        }
-
         let mut instance = SanctumDriverManager {
             device_um_symbolic_link_name,
             svc_path: sys_file_path,
             svc_name,
             handle_via_path: DriverHandleRaii::default(), // sets to None
             state: DriverState::Uninstalled("".to_string()),
+            availability: DriverAvailability::Unavailable {
+                reason: DRIVER_UNREACHABLE.to_string(),
+            },
             log,
         };
 
-        // attempt an install of the driver
-        instance.install_driver();
+        // check the sys file exists; without it the driver cannot be installed, though it may already be running
+        // todo this eventually should be in the actual install directory under Windows
+        let x = unsafe { GetFileAttributesW(PCWSTR::from_raw(instance.svc_path.as_ptr())) };
+        if x == INVALID_FILE_ATTRIBUTES {
+            instance.availability = DriverAvailability::Unavailable {
+                reason: format!(
+                    "Cannot find sanctum.sys (error {}), so the driver cannot be installed. Real-time protection, \
+                    telemetry and process monitoring are unavailable.",
+                    unsafe { GetLastError().0 }
+                ),
+            };
+        } else {
+            // attempt an install of the driver
+            instance.install_driver();
+        }
 
         // attempt to initialise a handle to the driver, this may silently fail - and will do so in the case
         // where the driver is not yet installed (or has been uninstalled)
         if instance.init_handle_via_registry() {
             instance.state = DriverState::Started("".to_string());
+            instance.availability = DriverAvailability::Available;
+        } else if let DriverAvailability::Unavailable { reason } = &instance.availability {
+            // the engine carries on without the driver, so that on-demand scanning still works
+            instance
+                .log
+                .log(LogLevel::Warning, &format!("Starting without the driver. {reason}"));
         }
 
         instance
@@ -89,6 +112,56 @@ impl SanctumDriverManager {
     pub fn get_state(&self) -> DriverState {
         self.state.clone()
     }
+
+    /// Gets whether the driver could be reached when it was last probed
+    pub fn get_availability(&self) -> DriverAvailability {
+        self.availability.clone()
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.availability == DriverAvailability::Available
+    }
+
+    /// Checks whether the driver can be reached, such as after it was started or stopped, or periodically so that the
+    /// engine recovers without a restart once the driver is loaded. A handle which was opened before the driver went
+    /// away is closed, so that the next probe opens a fresh one.
+    ///
+    /// # Returns
+    ///
+    /// The availability of the driver following the probe.
+    pub fn probe_availability(&mut self) -> DriverAvailability {
+        if self.handle_via_path.handle.is_some() && self.ioctl_get_driver_version().is_none() {
+            self.handle_via_path = DriverHandleRaii::default();
+        }
+
+        let available = self.handle_via_path.handle.is_some() || self.init_handle_via_registry();
+
+        let availability = match (available, &self.availability) {
+            (true, _) => DriverAvailability::Available,
+            // keep the more specific reason from when it first became unavailable, such as the sys file missing
+            (false, DriverAvailability::Unavailable { reason }) => DriverAvailability::Unavailable {
+                reason: reason.clone(),
+            },
+            (false, DriverAvailability::Available) => DriverAvailability::Unavailable {
+                reason: DRIVER_UNREACHABLE.to_string(),
+            },
+        };
+
+        if availability != self.availability {
+            match &availability {
+                DriverAvailability::Available => {
+                    self.log.log(LogLevel::Info, "The driver is available, leaving degraded mode.")
+                }
+                DriverAvailability::Unavailable { reason } => self.log.log(
+                    LogLevel::Warning,
+                    &format!("The driver is unavailable, running degraded. {reason}"),
+                ),
+            }
+            self.availability = availability.clone();
+        }
+
+        availability
+    }
 }
 
 impl Default for SanctumDriverManager {
@@ -142,3 +215,24 @@ impl Drop for DriverHandleRaii {
 
 //     svc_path
 // }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A driver manager which has neither installed nor opened the driver, as the engine runs where the driver is
+    /// missing
+    pub(crate) fn without_driver() -> SanctumDriverManager {
+        SanctumDriverManager {
+            device_um_symbolic_link_name: DRIVER_UM_NAME.to_u16_vec(),
+            svc_path: Vec::new(),
+            svc_name: SVC_NAME.to_u16_vec(),
+            handle_via_path: DriverHandleRaii::default(),
+            state: DriverState::Uninstalled("".to_string()),
+            availability: DriverAvailability::Unavailable {
+                reason: DRIVER_UNREACHABLE.to_string(),
+            },
+            log: Log::new(),
+        }
+    }
+}
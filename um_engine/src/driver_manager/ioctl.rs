@@ -9,7 +9,7 @@ use shared_no_std::{
     driver_ipc::ImageLoadQueues,
     ghost_hunting::Syscall,
     ioctl::{
-        BaseAddressesOfMonitoredDlls, DriverMessages, SancIoctlPing, SANC_IOCTL_CHECK_COMPATIBILITY, SANC_IOCTL_DLL_SYSCALL, SANC_IOCTL_DRIVER_GET_IMAGE_LOADS, SANC_IOCTL_DRIVER_GET_IMAGE_LOADS_LEN, SANC_IOCTL_DRIVER_GET_MESSAGES, SANC_IOCTL_DRIVER_GET_MESSAGE_LEN, SANC_IOCTL_PING, SANC_IOCTL_PING_WITH_STRUCT, SANC_IOCTL_SEND_BASE_ADDRS
+        BaseAddressesOfMonitoredDlls, DriverMessages, DriverQueueStats, SancIoctlPing, SANC_IOCTL_CHECK_COMPATIBILITY, SANC_IOCTL_DLL_SYSCALL, SANC_IOCTL_DRIVER_GET_IMAGE_LOADS, SANC_IOCTL_DRIVER_GET_IMAGE_LOADS_LEN, SANC_IOCTL_DRIVER_GET_MESSAGES, SANC_IOCTL_DRIVER_GET_MESSAGE_LEN, SANC_IOCTL_DRIVER_GET_QUEUE_STATS, SANC_IOCTL_DRIVER_GET_VERSION, SANC_IOCTL_PING, SANC_IOCTL_PING_WITH_STRUCT, SANC_IOCTL_SEND_BASE_ADDRS
     },
 };
 use std::{ffi::c_void, slice::from_raw_parts};
@@ -205,6 +205,106 @@ impl SanctumDriverManager {
         }
     }
 
+    /// Queries the driver for its version string.
+    ///
+    /// # Returns
+    ///
+    /// The version reported by the driver, or None if there is no handle to the driver or the call failed.
+    pub fn ioctl_get_driver_version(&mut self) -> Option<String> {
+        if self.handle_via_path.handle.is_none() {
+            // try 1 more time
+            self.init_handle_via_registry();
+            self.handle_via_path.handle?;
+        }
+
+        const RESP_SIZE: u32 = 256;
+        let mut response: [u8; RESP_SIZE as usize] = [0; RESP_SIZE as usize];
+        let mut bytes_returned: u32 = 0;
+
+        let result = unsafe {
+            DeviceIoControl(
+                self.handle_via_path.handle.unwrap(),
+                SANC_IOCTL_DRIVER_GET_VERSION,
+                None,
+                0,
+                Some(response.as_mut_ptr() as *mut c_void),
+                RESP_SIZE,
+                Some(&mut bytes_returned),
+                None,
+            )
+        };
+
+        if let Err(e) = result {
+            self.log.log(
+                LogLevel::Error,
+                &format!("Error getting the driver version via IOCTL. {e}"),
+            );
+            return None;
+        }
+
+        let len = (bytes_returned as usize).min(response.len());
+        match str::from_utf8(&response[..len]) {
+            Ok(v) => Some(v.to_string()),
+            Err(_) => {
+                self.log.log(
+                    LogLevel::Error,
+                    &format!("Driver version was not valid UTF-8. Raw data: {:?}", &response[..len]),
+                );
+                None
+            }
+        }
+    }
+
+    /// Queries the driver for the state of its queue of events for usermode.
+    ///
+    /// # Returns
+    ///
+    /// The state of the queue, or None if there is no handle to the driver or the call failed.
+    pub fn ioctl_get_queue_stats(&mut self) -> Option<DriverQueueStats> {
+        if self.handle_via_path.handle.is_none() {
+            // try 1 more time
+            self.init_handle_via_registry();
+            self.handle_via_path.handle?;
+        }
+
+        let mut stats = DriverQueueStats::default();
+        let mut bytes_returned: u32 = 0;
+
+        let result = unsafe {
+            DeviceIoControl(
+                self.handle_via_path.handle.unwrap(),
+                SANC_IOCTL_DRIVER_GET_QUEUE_STATS,
+                None,
+                0,
+                Some(&mut stats as *mut _ as *mut c_void),
+                size_of::<DriverQueueStats>() as u32,
+                Some(&mut bytes_returned),
+                None,
+            )
+        };
+
+        if let Err(e) = result {
+            self.log.log(
+                LogLevel::Error,
+                &format!("Error getting the driver queue stats via IOCTL. {e}"),
+            );
+            return None;
+        }
+
+        if bytes_returned as usize != size_of::<DriverQueueStats>() {
+            self.log.log(
+                LogLevel::Error,
+                &format!(
+                    "Driver queue stats were {bytes_returned} bytes, expected {}.",
+                    size_of::<DriverQueueStats>()
+                ),
+            );
+            return None;
+        }
+
+        Some(stats)
+    }
+
     /// Makes a request to pull messages from the driver back to userland for parsing, these events include:
     ///
     /// - Debug messages
@@ -1,6 +1,11 @@
 use std::{ffi::CStr, sync::Arc};
 
-use shared_std::settings::SanctumSettings;
+use shared_std::{
+    driver_manager::DriverAvailability,
+    events::EngineEvent,
+    file_scanner::{FileScannerState, ScanOptions},
+    settings::SanctumSettings,
+};
 use tokio::sync::Mutex;
 use windows::Win32::{
     Foundation::GetLastError,
@@ -11,11 +16,19 @@ use windows::Win32::{
 
 use crate::{
     core::core::Core,
-    driver_manager::SanctumDriverManager,
-    filescanner::FileScanner,
+    driver_manager::{DRIVER_PROBE_INTERVAL, SanctumDriverManager},
+    events::EventBus,
+    filescanner::{FileScanner, detector::Detector},
     gui_communication::ipc::UmIpc,
+    history::ScanHistory,
+    maintenance::{MAINTENANCE_INTERVAL, run_maintenance},
+    quarantine::QuarantineStore,
+    realtime::RealtimeSnooze,
+    removable_media::{REMOVABLE_MEDIA_POLL_INTERVAL, RemovableMediaWatcher, RemovableVolume},
     settings::SanctumSettingsImpl,
+    siem::forward_detections,
     utils::log::{Log, LogLevel},
+    webhook::post_detections,
 };
 
 /// The Process ID of the Sanctum PPL service.
@@ -31,28 +44,67 @@ pub static mut PPL_SERVICE_PID: u32 = 0;
 /// - Process monitoring
 /// - File monitoring
 /// - Driver management
-pub struct Engine;
+#[derive(Default)]
+pub struct Engine {
+    // detectors to run alongside the built in engines, handed to the file scanner when the engine starts
+    detectors: Vec<Box<dyn Detector>>,
+}
 
 impl Engine {
+    /// Registers a detector to be run on every file scanned, after the signature engines and ahead of the
+    /// heuristics. Detectors are registered before the engine is started.
+    #[allow(dead_code)] // for integrators building the engine with detectors of their own
+    pub fn register_detector(&mut self, detector: Box<dyn Detector>) {
+        self.detectors.push(detector);
+    }
+
     /// Start the engine
-    pub async fn start() -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn start(self) -> Result<(), Box<dyn std::error::Error>> {
 
         //
         // Start by instantiating the elements we will be using in the engine.
         // Once created; clone them as Arcs to share across the threads
         //
 
+        // event bus, for streaming events from each component to subscribers such as the GUI
+        let events = Arc::new(EventBus::new());
+        let events_for_core = Arc::clone(&events);
+        let events_for_umipc = Arc::clone(&events);
+        let events_for_driver_probe = Arc::clone(&events);
+        let events_for_removable_media = Arc::clone(&events);
+        let events_for_siem = Arc::clone(&events);
+        let events_for_webhook = Arc::clone(&events);
+
+        // real-time protection snoozes, held in memory so that a restart always resumes protection
+        let realtime_snooze = Arc::new(RealtimeSnooze::new(Arc::clone(&events)));
+
         // core
         let core = Arc::new(Core::from(20));
         let core_umipc = Arc::clone(&core);
 
+        // stores on disk
+        let history = Arc::new(ScanHistory::load());
+        let quarantine = Arc::new(QuarantineStore::load());
+        let history_for_maintenance = Arc::clone(&history);
+        let quarantine_for_maintenance = Arc::clone(&quarantine);
+        let quarantine_for_umipc = Arc::clone(&quarantine);
+
         // file scanner
-        let scanner = FileScanner::new().await;
+        let scanner =
+            FileScanner::new(events, Arc::clone(&history), Arc::clone(&quarantine)).await;
         if let Err(e) = scanner {
             panic!("[-] Failed to initialise scanner: {e}.");
         }
         let file_scanner = Arc::new(scanner.unwrap());
+        for detector in self.detectors {
+            let name = detector.name().to_string();
+            match file_scanner.register_detector(detector) {
+                Ok(_) => Log::new().log(LogLevel::Info, &format!("Registered detector {name}.")),
+                Err(e) => Log::new().log(LogLevel::Warning, &format!("Could not register detector {name}. {e}")),
+            }
+        }
         let file_scanner_clone = Arc::clone(&file_scanner);
+        let file_scanner_for_removable_media = Arc::clone(&file_scanner);
 
         // driver manager
         // Happy the driver manager being wrapped in a mutex now; it isn't a high performance module and I
@@ -62,11 +114,25 @@ impl Engine {
         let driver_manager = Arc::new(Mutex::new(SanctumDriverManager::new()));
         let drv_mgr_for_umipc = Arc::clone(&driver_manager);
         let drv_mgr_for_core = Arc::clone(&driver_manager);
+        let drv_mgr_for_probe = Arc::clone(&driver_manager);
 
         // settings - happy to leave as mutex for now, may refactor later to move the mutex deeper into the
         // call flow
         let sanctum_settings = Arc::new(Mutex::new(SanctumSettings::load()));
         let settings_clone = Arc::clone(&sanctum_settings);
+        let settings_for_maintenance = Arc::clone(&sanctum_settings);
+        let settings_for_removable_media = Arc::clone(&sanctum_settings);
+        let settings_for_siem = Arc::clone(&sanctum_settings);
+        let settings_for_webhook = Arc::clone(&sanctum_settings);
+
+        // settings are validated when they are changed, so this should only fail if the config was edited by hand
+        if let Err(e) = file_scanner.apply_settings(&*sanctum_settings.lock().await) {
+            let logger = Log::new();
+            logger.log(
+                LogLevel::Warning,
+                &format!("Scanner settings in the settings file are invalid, using defaults. {e}"),
+            );
+        }
 
         //
         // Spawn the core of the engine which will constantly talk to the driver and process any IO
@@ -77,7 +143,158 @@ impl Engine {
         // other threads from the engine / usermode IPC loops.
         //
         let core_handle = tokio::spawn(async move {
-            core.start_core(drv_mgr_for_core).await;
+            core.start_core(drv_mgr_for_core, events_for_core).await;
+        });
+
+        //
+        // Periodically check whether the driver can be reached, so that the engine leaves degraded mode once the
+        // driver is loaded, and notices if it goes away. The user is notified whenever this changes.
+        //
+        tokio::spawn(async move {
+            let mut previous = drv_mgr_for_probe.lock().await.get_availability();
+            loop {
+                tokio::time::sleep(DRIVER_PROBE_INTERVAL).await;
+
+                let availability = drv_mgr_for_probe.lock().await.probe_availability();
+                if availability == previous {
+                    continue;
+                }
+
+                let (title, message) = match &availability {
+                    DriverAvailability::Available => (
+                        "Driver available",
+                        "The Sanctum driver is running, real-time protection is available.".to_string(),
+                    ),
+                    DriverAvailability::Unavailable { reason } => ("Driver unavailable", reason.clone()),
+                };
+                events_for_driver_probe.publish(EngineEvent::Notify {
+                    title: title.to_string(),
+                    message,
+                    scan_id: None,
+                });
+
+                previous = availability;
+            }
+        });
+
+        //
+        // Scan removable media as it is inserted, where enabled in the settings. A volume inserted whilst another scan
+        // is running waits for that scan to end, so long as it is still inserted by then. If the media is removed
+        // part way through its scan, the scan ends with an error as the target became unavailable.
+        //
+        tokio::spawn(async move {
+            let logger = Log::new();
+            let mut watcher = tokio::task::spawn_blocking(RemovableMediaWatcher::new).await.unwrap();
+            let mut waiting: Vec<RemovableVolume> = Vec::new();
+
+            loop {
+                tokio::time::sleep(REMOVABLE_MEDIA_POLL_INTERVAL).await;
+
+                let (returned, arrived) = tokio::task::spawn_blocking(move || {
+                    let arrived = watcher.poll();
+                    (watcher, arrived)
+                })
+                .await
+                .unwrap();
+                watcher = returned;
+
+                let settings = settings_for_removable_media.lock().await.removable_media.clone();
+                for volume in arrived {
+                    if settings.scans(&volume.serial) {
+                        waiting.push(volume);
+                    } else {
+                        logger.log(
+                            LogLevel::Info,
+                            &format!(
+                                "Removable volume {} ({}) was inserted, it is not scanned on insertion.",
+                                volume.drive, volume.serial
+                            ),
+                        );
+                    }
+                }
+
+                // re-checked each time round, as scanning on insertion may have been turned off whilst media waited
+                waiting.retain(|volume| settings.scans(&volume.serial) && volume.is_present());
+                // media inserted whilst an update is in progress waits for it to end
+                if waiting.is_empty()
+                    || file_scanner_for_removable_media.get_state() == FileScannerState::Scanning
+                    || file_scanner_for_removable_media.is_updating()
+                {
+                    continue;
+                }
+
+                let volume = waiting.remove(0);
+                let started = match file_scanner_for_removable_media.prepare_volume_scan(&volume.drive) {
+                    Ok(started) => started,
+                    Err(e) => {
+                        logger.log(
+                            LogLevel::Warning,
+                            &format!(
+                                "Could not scan removable volume {} ({}) on insertion. {e}",
+                                volume.drive, volume.serial
+                            ),
+                        );
+                        continue;
+                    }
+                };
+
+                logger.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Removable volume {} ({}) was inserted, started scan {}.",
+                        volume.drive, volume.serial, started.scan_id
+                    ),
+                );
+                events_for_removable_media.publish(EngineEvent::RemovableMediaScanStarted {
+                    drive: volume.drive,
+                    serial: volume.serial,
+                    started: started.clone(),
+                });
+
+                let scanner = Arc::clone(&file_scanner_for_removable_media);
+                tokio::task::spawn_blocking(move || scanner.run_scan(started.targets, ScanOptions::default()));
+            }
+        });
+
+        //
+        // Forward detections to a SIEM collector over syslog, where enabled in the settings
+        //
+        tokio::spawn(forward_detections(events_for_siem, settings_for_siem));
+
+        //
+        // Post detections to a webhook, where enabled in the settings
+        //
+        tokio::spawn(post_detections(events_for_webhook, settings_for_webhook));
+
+        //
+        // Periodically prune the scan history and quarantine so they don't grow unbounded. This runs once at startup
+        // to catch up on any time the engine was not running.
+        //
+        tokio::spawn(async move {
+            let logger = Log::new();
+            loop {
+                let retention = settings_for_maintenance.lock().await.maintenance.clone();
+                let report = run_maintenance(
+                    &history_for_maintenance,
+                    &quarantine_for_maintenance,
+                    &retention,
+                );
+
+                logger.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Maintenance pruned {} history records, {} orphaned quarantine files and {} orphaned quarantine entries.",
+                        report.history_records_pruned,
+                        report.orphaned_quarantine_files.len(),
+                        report.orphaned_quarantine_entries.len()
+                    ),
+                );
+                for e in &report.errors {
+                    logger.log(LogLevel::Warning, e);
+                }
+
+                tokio::time::sleep(MAINTENANCE_INTERVAL).await;
+            }
         });
 
         // blocks indefinitely unless some error gets thrown up
@@ -88,6 +305,10 @@ impl Engine {
                 core_umipc,
                 file_scanner_clone,
                 drv_mgr_for_umipc,
+                events_for_umipc,
+                history,
+                quarantine_for_umipc,
+                realtime_snooze,
             )
             .await;
 
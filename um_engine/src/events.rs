@@ -0,0 +1,77 @@
+//! The engine's event bus, from which events are streamed to subscribers such as the GUI.
+//!
+//! Recent events are held in a ring buffer so that a subscriber which disconnects (for example, when the GUI is
+//! restarted) can resume from the last event it saw without missing anything, provided it reconnects before the
+//! buffer wraps. Publishing never blocks on subscribers, so a slow or absent GUI cannot hold up a scan.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use shared_std::events::{EngineEvent, EngineEventEnvelope};
+use tokio::sync::watch;
+
+/// The number of recent events retained for subscribers which reconnect
+const EVENT_BUFFER_CAPACITY: usize = 1024;
+
+pub struct EventBus {
+    buffer: Mutex<VecDeque<EngineEventEnvelope>>,
+    /// The sequence number of the most recently published event, watched by subscribers to learn of new events
+    latest_seq: watch::Sender<u64>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus {
+            buffer: Mutex::new(VecDeque::with_capacity(EVENT_BUFFER_CAPACITY)),
+            latest_seq: watch::Sender::new(0),
+        }
+    }
+
+    /// Publishes an event to all subscribers, dropping the oldest retained event if the buffer is full
+    pub fn publish(&self, event: EngineEvent) {
+        let mut buffer = self.buffer.lock().unwrap();
+        let seq = *self.latest_seq.borrow() + 1;
+
+        if buffer.len() == EVENT_BUFFER_CAPACITY {
+            buffer.pop_front();
+        }
+        buffer.push_back(EngineEventEnvelope { seq, event });
+
+        // updated whilst holding the buffer lock so sequence numbers are handed out in order
+        self.latest_seq.send_replace(seq);
+    }
+
+    /// Gets the retained events published after the given sequence number.
+    ///
+    /// Where the sequence number is ahead of the latest event, the subscriber saw events from a previous run of the
+    /// engine, so every retained event is returned.
+    pub fn events_after(&self, after_seq: u64) -> Vec<EngineEventEnvelope> {
+        let buffer = self.buffer.lock().unwrap();
+        let after_seq = if after_seq > *self.latest_seq.borrow() {
+            0
+        } else {
+            after_seq
+        };
+
+        buffer
+            .iter()
+            .filter(|e| e.seq > after_seq)
+            .cloned()
+            .collect()
+    }
+
+    /// The sequence number of the most recently published event, or 0 if none have been published
+    pub fn latest_seq(&self) -> u64 {
+        *self.latest_seq.borrow()
+    }
+
+    /// Subscribes to changes in the latest sequence number, for waiting on new events
+    pub fn watch(&self) -> watch::Receiver<u64> {
+        self.latest_seq.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,164 @@
+//! Exporting the record of a scan from the scan history to a file, for analysis outside of Sanctum.
+//!
+//! The record is serialised straight into the file through the compressor, so the export is never held in memory as
+//! a whole document however many results the scan has.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use flate2::{Compression, write::GzEncoder};
+use shared_std::{
+    history::{ExportFormat, ScanExportReport, ScanExportRequest, ScanRecord},
+    settings::SiemFormat,
+};
+
+use crate::{
+    history::ScanHistory,
+    siem::format_detection,
+    utils::log::{Log, LogLevel},
+};
+
+/// Exports the scan in the requested format. A failed export removes the partly written file rather than leaving a
+/// truncated export behind.
+pub fn export_scan(history: &ScanHistory, request: &ScanExportRequest) -> Result<ScanExportReport, String> {
+    let record = history
+        .find(request.scan_id)
+        .ok_or_else(|| format!("Scan {} was not found in the scan history.", request.scan_id))?;
+
+    let file = File::create(&request.path)
+        .map_err(|e| format!("Could not create the export file {}. {e}", request.path.display()))?;
+
+    if let Err(e) = write_export(&record, request.format, file) {
+        let _ = fs::remove_file(&request.path);
+        return Err(format!(
+            "Could not export scan {} to {}. {e}",
+            request.scan_id,
+            request.path.display()
+        ));
+    }
+
+    let bytes_written = fs::metadata(&request.path).map(|m| m.len()).unwrap_or(0);
+    Log::new().log(
+        LogLevel::Info,
+        &format!(
+            "Exported scan {} to {} ({bytes_written} bytes).",
+            request.scan_id,
+            request.path.display()
+        ),
+    );
+
+    Ok(ScanExportReport {
+        file: request.path.clone(),
+        format: request.format,
+        bytes_written,
+        detections: record.detections.len(),
+    })
+}
+
+fn write_export(record: &ScanRecord, format: ExportFormat, file: File) -> io::Result<()> {
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        ExportFormat::Json => serde_json::to_writer_pretty(&mut writer, record)?,
+        ExportFormat::JsonGz => {
+            let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+            serde_json::to_writer(&mut encoder, record)?;
+            encoder.finish()?;
+        }
+        ExportFormat::JsonZst => {
+            let mut encoder = zstd::Encoder::new(&mut writer, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            serde_json::to_writer(&mut encoder, record)?;
+            encoder.finish()?;
+        }
+        ExportFormat::Cef => write_siem_lines(record, SiemFormat::Cef, &mut writer)?,
+        ExportFormat::Leef => write_siem_lines(record, SiemFormat::Leef, &mut writer)?,
+    }
+
+    // flushed explicitly, as an error flushing on drop would go unnoticed
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+/// Writes each detection of the scan as a line in the SIEM format. The time a detection was made is not recorded, so
+/// the time the scan finished is given for each.
+fn write_siem_lines(record: &ScanRecord, format: SiemFormat, writer: &mut impl Write) -> io::Result<()> {
+    let host = std::env::var("COMPUTERNAME").unwrap_or_else(|_| "-".to_string());
+
+    for ioc in &record.detections {
+        let line = format_detection(format, ioc, Some(record.scan_id), record.finished_at, &host);
+        writeln!(writer, "{line}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use flate2::read::GzDecoder;
+    use shared_std::file_scanner::{ActionResult, MatchMode, MatchedIOC};
+
+    use super::*;
+
+    fn record() -> ScanRecord {
+        let mut record: ScanRecord = serde_json::from_value(serde_json::json!({
+            "scan_id": 42,
+            "started_at": null,
+            "finished_at": null,
+            "outcome": "Finished",
+            "num_files_scanned": 1200,
+            "bytes_scanned": 1 << 30,
+            "detections": [],
+            "changed_during_scan": ["C:\\Users\\Public\\changing.log"],
+        }))
+        .unwrap();
+
+        record.started_at = Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123));
+        record.finished_at = Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_060_456));
+        record.detections = (0..3)
+            .map(|i| MatchedIOC {
+                hash: "44D88612FEA8A8F36DE82E1278ABB02F".to_string(),
+                file: PathBuf::from(format!("C:\\Users\\Public\\Downloads\\eicar-{i}.com")),
+                match_mode: MatchMode::FileHash,
+                action_taken: ActionResult::None,
+                source: Some("sanctum".to_string()),
+                verdicts: Vec::new(),
+                resolved_by: None,
+                conditions: Vec::new(),
+            })
+            .collect();
+
+        record
+    }
+
+    /// Exports the record in the format, reading the export back through the decompressor for the format
+    fn round_trip(format: ExportFormat, decompress: impl FnOnce(File) -> Box<dyn Read>) -> ScanRecord {
+        let path = std::env::temp_dir().join(format!("sanctum-export-{}-{format:?}", std::process::id()));
+        write_export(&record(), format, File::create(&path).unwrap()).unwrap();
+
+        let mut json = String::new();
+        decompress(File::open(&path).unwrap()).read_to_string(&mut json).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn compressed_exports_round_trip() {
+        let gz = round_trip(ExportFormat::JsonGz, |file| Box::new(GzDecoder::new(file)));
+        assert_eq!(gz, record());
+
+        let zst = round_trip(ExportFormat::JsonZst, |file| Box::new(zstd::Decoder::new(file).unwrap()));
+        assert_eq!(zst, record());
+
+        let json = round_trip(ExportFormat::Json, |file| Box::new(file));
+        assert_eq!(json, record());
+    }
+}
@@ -0,0 +1,220 @@
+//! Verifying the Authenticode signature of a file, and finding who signed it.
+//!
+//! A file is signed either by a signature embedded in the file, or through a catalog: a signed list of the hashes of
+//! files, which is how most of the files shipped with Windows, and most drivers, are signed. Revocation is not
+//! checked, so that verifying a file does not wait on the network.
+
+use std::{
+    ffi::c_void,
+    fs::File,
+    iter, mem,
+    os::windows::{ffi::OsStrExt, io::AsRawHandle},
+    path::Path,
+};
+
+use windows::{
+    Win32::{
+        Foundation::{HANDLE, HWND, TRUST_E_NOSIGNATURE, TRUST_E_PROVIDER_UNKNOWN, TRUST_E_SUBJECT_FORM_UNKNOWN},
+        Security::{
+            Cryptography::{
+                BCRYPT_SHA256_ALGORITHM, CERT_NAME_SIMPLE_DISPLAY_TYPE,
+                Catalog::{
+                    CATALOG_INFO, CryptCATAdminAcquireContext2, CryptCATAdminCalcHashFromFileHandle2,
+                    CryptCATAdminEnumCatalogFromHash, CryptCATAdminReleaseCatalogContext, CryptCATAdminReleaseContext,
+                    CryptCATCatalogInfoFromContext,
+                },
+                CertGetNameStringW,
+            },
+            WinTrust::{
+                DRIVER_ACTION_VERIFY, WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_CATALOG_INFO, WINTRUST_DATA,
+                WINTRUST_DATA_0, WINTRUST_FILE_INFO, WTD_CHOICE_CATALOG, WTD_CHOICE_FILE, WTD_REVOKE_NONE,
+                WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE, WTHelperGetProvSignerFromChain,
+                WTHelperProvDataFromStateData, WinVerifyTrust,
+            },
+        },
+    },
+    core::PCWSTR,
+};
+
+use super::filescanner::to_hex;
+
+/// The longest name of a signer read from its certificate
+const MAX_SIGNER_NAME_LEN: usize = 256;
+
+/// The Authenticode signature of a file
+#[derive(Debug, Default, Clone)]
+pub struct Signature {
+    /// Whether the file is signed, whether or not the signature is trusted
+    pub signed: bool,
+    /// Whether the signature verifies and chains to a root the machine trusts
+    pub trusted: bool,
+    /// The subject of the certificate the file was signed with, where it could be read
+    pub signer: Option<String>,
+}
+
+/// Verifies the signature of the file, whether embedded in the file or through a catalog
+pub fn verify(path: &Path) -> Signature {
+    let embedded = verify_embedded(path);
+    if embedded.signed {
+        return embedded;
+    }
+
+    verify_catalog(path).unwrap_or(embedded)
+}
+
+/// Verifies the signature embedded in the file. A file signed only through a catalog counts as unsigned.
+pub fn verify_embedded(path: &Path) -> Signature {
+    let path = wide(path);
+
+    let mut file = WINTRUST_FILE_INFO {
+        cbStruct: mem::size_of::<WINTRUST_FILE_INFO>() as u32,
+        pcwszFilePath: PCWSTR(path.as_ptr()),
+        ..Default::default()
+    };
+    let mut data = WINTRUST_DATA {
+        cbStruct: mem::size_of::<WINTRUST_DATA>() as u32,
+        dwUIChoice: WTD_UI_NONE,
+        fdwRevocationChecks: WTD_REVOKE_NONE,
+        dwUnionChoice: WTD_CHOICE_FILE,
+        Anonymous: WINTRUST_DATA_0 { pFile: &mut file },
+        dwStateAction: WTD_STATEACTION_VERIFY,
+        ..Default::default()
+    };
+
+    verify_trust(&mut data)
+}
+
+/// Verifies the file against the catalog which lists its hash, if any catalog on the machine does
+fn verify_catalog(path: &Path) -> Option<Signature> {
+    let file = File::open(path).ok()?;
+    let handle = HANDLE(file.as_raw_handle() as _);
+
+    let mut admin = Default::default();
+    unsafe {
+        CryptCATAdminAcquireContext2(
+            &mut admin,
+            Some(&DRIVER_ACTION_VERIFY),
+            BCRYPT_SHA256_ALGORITHM,
+            None,
+            None,
+        )
+    }
+    .ok()?;
+
+    let signature = catalog_signature(admin, path, handle);
+
+    let _ = unsafe { CryptCATAdminReleaseContext(admin, 0) };
+
+    signature
+}
+
+fn catalog_signature(admin: isize, path: &Path, handle: HANDLE) -> Option<Signature> {
+    // the first call gives the length of the hash
+    let mut len = 0;
+    let _ = unsafe { CryptCATAdminCalcHashFromFileHandle2(admin, handle, &mut len, None, None) };
+    if len == 0 {
+        return None;
+    }
+    let mut hash = vec![0u8; len as usize];
+    unsafe { CryptCATAdminCalcHashFromFileHandle2(admin, handle, &mut len, Some(hash.as_mut_ptr()), None) }.ok()?;
+
+    let catalog = unsafe { CryptCATAdminEnumCatalogFromHash(admin, &hash, None, None) };
+    if catalog == 0 {
+        return None;
+    }
+
+    let mut info = CATALOG_INFO {
+        cbStruct: mem::size_of::<CATALOG_INFO>() as u32,
+        ..Default::default()
+    };
+    let signature = unsafe { CryptCATCatalogInfoFromContext(catalog, &mut info, 0) }
+        .ok()
+        .map(|_| {
+            // the file is listed in the catalog by the hex of its hash
+            let member_tag: Vec<u16> = to_hex(&hash).encode_utf16().chain(iter::once(0)).collect();
+            let member_path = wide(path);
+
+            let mut catalog_info = WINTRUST_CATALOG_INFO {
+                cbStruct: mem::size_of::<WINTRUST_CATALOG_INFO>() as u32,
+                pcwszCatalogFilePath: PCWSTR(info.wszCatalogFile.as_ptr()),
+                pcwszMemberTag: PCWSTR(member_tag.as_ptr()),
+                pcwszMemberFilePath: PCWSTR(member_path.as_ptr()),
+                hMemberFile: handle,
+                pbCalculatedFileHash: hash.as_mut_ptr(),
+                cbCalculatedFileHash: len,
+                hCatAdmin: admin,
+                ..Default::default()
+            };
+            let mut data = WINTRUST_DATA {
+                cbStruct: mem::size_of::<WINTRUST_DATA>() as u32,
+                dwUIChoice: WTD_UI_NONE,
+                fdwRevocationChecks: WTD_REVOKE_NONE,
+                dwUnionChoice: WTD_CHOICE_CATALOG,
+                Anonymous: WINTRUST_DATA_0 {
+                    pCatalog: &mut catalog_info,
+                },
+                dwStateAction: WTD_STATEACTION_VERIFY,
+                ..Default::default()
+            };
+
+            // a file listed in a catalog is signed by the catalog's signature, whether or not that is trusted
+            Signature {
+                signed: true,
+                ..verify_trust(&mut data)
+            }
+        });
+
+    let _ = unsafe { CryptCATAdminReleaseCatalogContext(admin, catalog, 0) };
+
+    signature
+}
+
+/// Verifies the signature described by the data, releasing the state held by the verification whatever its outcome
+fn verify_trust(data: &mut WINTRUST_DATA) -> Signature {
+    let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+
+    let status = unsafe { WinVerifyTrust(HWND::default(), &mut action, data as *mut WINTRUST_DATA as *mut c_void) };
+
+    // where the file has no signature, or is of a type which cannot be signed, there is no signer to read
+    let unsigned = [
+        TRUST_E_NOSIGNATURE,
+        TRUST_E_SUBJECT_FORM_UNKNOWN,
+        TRUST_E_PROVIDER_UNKNOWN,
+    ]
+    .iter()
+    .any(|e| e.0 == status);
+    let signature = Signature {
+        signed: !unsigned,
+        trusted: status == 0,
+        signer: (!unsigned).then(|| signer(data.hWVTStateData)).flatten(),
+    };
+
+    data.dwStateAction = WTD_STATEACTION_CLOSE;
+    let _ = unsafe { WinVerifyTrust(HWND::default(), &mut action, data as *mut WINTRUST_DATA as *mut c_void) };
+
+    signature
+}
+
+/// The subject of the certificate of the first signer, read from the state of a verification
+fn signer(state: HANDLE) -> Option<String> {
+    let provider = unsafe { WTHelperProvDataFromStateData(state) };
+    if provider.is_null() {
+        return None;
+    }
+
+    let signer = unsafe { WTHelperGetProvSignerFromChain(provider, 0, false, 0) };
+    if signer.is_null() || unsafe { (*signer).csCertChain } == 0 {
+        return None;
+    }
+    let cert = unsafe { (*(*signer).pasCertChain).pCert };
+
+    let mut name = [0u16; MAX_SIGNER_NAME_LEN];
+    let len = unsafe { CertGetNameStringW(cert, CERT_NAME_SIMPLE_DISPLAY_TYPE, 0, None, Some(&mut name)) } as usize;
+
+    // the length includes the terminating nul, so is 1 where the certificate has no name
+    (len > 1).then(|| String::from_utf16_lossy(&name[..len - 1]))
+}
+
+fn wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(iter::once(0)).collect()
+}
@@ -0,0 +1,324 @@
+//! Enumerating the locations Windows starts programs from automatically, which malware abuses to persist across
+//! reboots.
+//!
+//! Each location is configured with a command line rather than a path, so each entry is resolved to the binary it
+//! starts in the same way Windows would: environment variables are expanded, the native `\??\` and `\SystemRoot\`
+//! prefixes used by services are rewritten, and an unquoted command containing spaces is resolved to the shortest
+//! prefix which exists on disk. Shortcuts in the Startup folders are resolved to their target.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use shared_std::file_scanner::{AutorunEntry, AutorunMechanism};
+use windows_registry::{Key, LOCAL_MACHINE, USERS};
+
+use super::shortcut::shortcut_target;
+
+/// The Run and RunOnce keys, relative to HKLM or a user's hive
+const RUN_KEYS: [&str; 4] = [
+    "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+];
+
+const SERVICES_KEY: &str = "SYSTEM\\CurrentControlSet\\Services";
+
+/// The Startup folder, relative to a user's profile
+const USER_STARTUP_FOLDER: &str = "AppData\\Roaming\\Microsoft\\Windows\\Start Menu\\Programs\\Startup";
+
+/// The Startup folder for all users, relative to ProgramData
+const COMMON_STARTUP_FOLDER: &str = "Microsoft\\Windows\\Start Menu\\Programs\\StartUp";
+
+/// The folder holding the definitions of scheduled tasks, relative to the Windows directory
+const TASKS_FOLDER: &str = "System32\\Tasks";
+
+/// Enumerates the entries in each of the autorun locations, resolving each to the binary it starts.
+///
+/// The engine runs as SYSTEM, so rather than reading HKCU (which would be the hive of SYSTEM), the Run keys of every
+/// loaded user hive are read, along with the Startup folder of every user profile.
+pub fn enumerate_autoruns() -> Vec<AutorunEntry> {
+    let mut entries = Vec::new();
+
+    enumerate_run_keys(&mut entries);
+    enumerate_startup_folders(&mut entries);
+    enumerate_scheduled_tasks(&mut entries);
+    enumerate_services(&mut entries);
+
+    entries
+}
+
+fn enumerate_run_keys(entries: &mut Vec<AutorunEntry>) {
+    enumerate_hive_run_keys(entries, "HKLM", LOCAL_MACHINE);
+
+    // the classes hive of each user is loaded alongside their main hive, and holds no Run keys
+    if let Ok(sids) = USERS.keys() {
+        for sid in sids.filter(|sid| !sid.ends_with("_Classes")) {
+            if let Ok(hive) = USERS.open(&sid) {
+                enumerate_hive_run_keys(entries, &format!("HKU\\{sid}"), &hive);
+            }
+        }
+    }
+}
+
+fn enumerate_hive_run_keys(entries: &mut Vec<AutorunEntry>, hive_name: &str, hive: &Key) {
+    for run_key in RUN_KEYS {
+        let Ok(key) = hive.open(run_key) else {
+            continue;
+        };
+
+        for (value, command) in string_values(&key) {
+            entries.push(AutorunEntry {
+                mechanism: AutorunMechanism::RegistryRun {
+                    key: format!("{hive_name}\\{run_key}"),
+                    value,
+                },
+                image: resolve_command(&command),
+                command,
+            });
+        }
+    }
+}
+
+fn enumerate_startup_folders(entries: &mut Vec<AutorunEntry>) {
+    let mut folders = Vec::new();
+
+    if let Ok(program_data) = env::var("ProgramData") {
+        folders.push(Path::new(&program_data).join(COMMON_STARTUP_FOLDER));
+    }
+
+    let system_drive = env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    if let Ok(profiles) = fs::read_dir(format!("{system_drive}\\Users")) {
+        folders.extend(profiles.filter_map(|p| p.ok()).map(|p| p.path().join(USER_STARTUP_FOLDER)));
+    }
+
+    for folder in folders {
+        let Ok(files) = fs::read_dir(&folder) else {
+            continue;
+        };
+
+        for file in files.filter_map(|f| f.ok()).map(|f| f.path()) {
+            if !file.is_file() || file.file_name().is_some_and(|n| n.eq_ignore_ascii_case("desktop.ini")) {
+                continue;
+            }
+
+            let is_shortcut = file.extension().is_some_and(|e| e.eq_ignore_ascii_case("lnk"));
+            let image = if is_shortcut {
+                shortcut_target(&file).filter(|target| target.is_file())
+            } else {
+                Some(file.clone())
+            };
+
+            entries.push(AutorunEntry {
+                command: file.to_string_lossy().to_string(),
+                mechanism: AutorunMechanism::StartupFolder { file },
+                image,
+            });
+        }
+    }
+}
+
+fn enumerate_scheduled_tasks(entries: &mut Vec<AutorunEntry>) {
+    for (name, xml) in scheduled_task_definitions() {
+        // a task may have several actions, each of which starts a program
+        for command in xml_elements(&xml, "Command") {
+            entries.push(AutorunEntry {
+                mechanism: AutorunMechanism::ScheduledTask { name: name.clone() },
+                image: resolve_command(&command),
+                command,
+            });
+        }
+    }
+}
+
+/// Reads the definition of every scheduled task, by the task's path in the task scheduler
+pub(super) fn scheduled_task_definitions() -> Vec<(String, String)> {
+    let Some(tasks_root) = windows_dir().map(|w| w.join(TASKS_FOLDER)) else {
+        return Vec::new();
+    };
+
+    let mut tasks = Vec::new();
+
+    // tasks are stored as XML files, in folders matching the folders of the task scheduler
+    let mut dirs = vec![tasks_root.clone()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for path in read_dir.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let Some(xml) = read_task_xml(&path) else {
+                continue;
+            };
+
+            let name = path
+                .strip_prefix(&tasks_root)
+                .map(|p| format!("\\{}", p.display()))
+                .unwrap_or_else(|_| path.display().to_string());
+
+            tasks.push((name, xml));
+        }
+    }
+
+    tasks
+}
+
+fn enumerate_services(entries: &mut Vec<AutorunEntry>) {
+    let Ok(services) = LOCAL_MACHINE.open(SERVICES_KEY) else {
+        return;
+    };
+    let Ok(names) = services.keys() else {
+        return;
+    };
+
+    for name in names {
+        // not every key under Services is a service with a binary, such as those only holding event log config
+        let Ok(command) = services.open(&name).and_then(|svc| svc.get_string("ImagePath")) else {
+            continue;
+        };
+
+        entries.push(AutorunEntry {
+            mechanism: AutorunMechanism::Service { name },
+            image: resolve_command(&command),
+            command,
+        });
+    }
+}
+
+/// Reads each string value of the key, as pairs of the value name and its data
+fn string_values(key: &Key) -> Vec<(String, String)> {
+    let Ok(values) = key.values() else {
+        return Vec::new();
+    };
+
+    values
+        .filter_map(|(name, _)| key.get_string(&name).ok().map(|data| (name, data)))
+        .collect()
+}
+
+/// Resolves a command line to the binary it starts, returning None if it does not resolve to a file on disk
+pub(super) fn resolve_command(command: &str) -> Option<PathBuf> {
+    let command = expand_env_vars(command.trim());
+
+    // a quoted path is taken as is
+    if let Some(quoted) = command.trim().strip_prefix('"') {
+        let path = quoted.split('"').next().unwrap_or_default();
+        return find_binary(&normalise_native_path(path));
+    }
+
+    let command = normalise_native_path(command.trim());
+
+    // otherwise, the path ends at the first space at which the prefix exists, as CreateProcess resolves it
+    let mut candidates: Vec<&str> = command.match_indices(' ').map(|(i, _)| &command[..i]).collect();
+    candidates.push(&command);
+
+    candidates.into_iter().find_map(find_binary)
+}
+
+/// Finds the binary for a path from a command line, where the `.exe` extension may have been left off, and a bare
+/// file name is looked up in System32.
+fn find_binary(path: &str) -> Option<PathBuf> {
+    if path.is_empty() {
+        return None;
+    }
+
+    let path = PathBuf::from(path);
+    let path = if path.parent().is_some_and(|p| p.as_os_str().is_empty()) {
+        windows_dir()?.join("System32").join(path)
+    } else {
+        path
+    };
+
+    if path.is_file() {
+        return Some(path);
+    }
+
+    let with_exe = path.with_extension("exe");
+    (path.extension().is_none() && with_exe.is_file()).then_some(with_exe)
+}
+
+/// Rewrites the native path forms used in service image paths, such as `\SystemRoot\System32\drivers\x.sys`,
+/// `\??\C:\x.sys` and `System32\drivers\x.sys`, as Win32 paths.
+pub(super) fn normalise_native_path(path: &str) -> String {
+    let path = path.strip_prefix("\\??\\").unwrap_or(path);
+    let windows = windows_dir().map(|w| w.to_string_lossy().to_string()).unwrap_or_default();
+
+    let lower = path.to_ascii_lowercase();
+    if lower.starts_with("\\systemroot\\") {
+        format!("{windows}{}", &path["\\SystemRoot".len()..])
+    } else if lower.starts_with("system32\\") || lower.starts_with("syswow64\\") {
+        format!("{windows}\\{path}")
+    } else {
+        path.to_string()
+    }
+}
+
+/// Expands each `%NAME%` in the string to the value of the environment variable, leaving any which are not set
+pub(super) fn expand_env_vars(s: &str) -> String {
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+
+        let name = &rest[start + 1..start + 1 + len];
+        expanded.push_str(&rest[..start]);
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[start..start + len + 2]),
+        }
+
+        rest = &rest[start + len + 2..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+fn windows_dir() -> Option<PathBuf> {
+    env::var("SystemRoot").or_else(|_| env::var("windir")).ok().map(PathBuf::from)
+}
+
+/// Reads the XML definition of a scheduled task, which is usually saved as UTF-16
+fn read_task_xml(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+
+    match data.as_slice() {
+        [0xFF, 0xFE, utf16 @ ..] => {
+            let chars: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&chars))
+        }
+        _ => String::from_utf8(data).ok(),
+    }
+}
+
+/// Gets the text of each element with the tag in the XML, unescaping the predefined entities
+pub(super) fn xml_elements(xml: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+
+    xml.split(&open)
+        .skip(1)
+        .filter_map(|s| s.split(&close).next())
+        .map(|text| {
+            text.trim()
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
@@ -0,0 +1,91 @@
+//! Synthetic files for measuring how fast the engine scans, generated in memory so that a benchmark needs no tree of
+//! files on disk and gives the same number each time it is run with the same request.
+//!
+//! The content of every file is drawn from one pool of pseudo random bytes, so memory use stays at the size of the
+//! pool however large the files are. Each file starts with its index, so that no two files hash the same.
+
+use shared_no_std::constants::{IPC_STREAM_MAX_CHUNK_LEN, IPC_STREAM_MAX_TOTAL_LEN};
+use shared_std::file_scanner::{BenchmarkRequest, MAX_BENCHMARK_BYTES, MAX_BENCHMARK_FILES, SizeDistribution};
+
+/// The seed the sizes and content are generated from, fixed so that each run generates the same files
+const SEED: u64 = 0x5A4E_C7D0_B3E1_9F21;
+
+/// Checks the request is within the limits of a benchmark
+pub fn validate(request: &BenchmarkRequest) -> Result<(), String> {
+    if request.file_count == 0 || request.file_count > MAX_BENCHMARK_FILES {
+        return Err(format!("A benchmark must be of between 1 and {MAX_BENCHMARK_FILES} files."));
+    }
+    if request.avg_size > IPC_STREAM_MAX_TOTAL_LEN {
+        return Err(format!("The average size can be at most {IPC_STREAM_MAX_TOTAL_LEN} bytes, as for scanned bytes."));
+    }
+    if request.file_count.saturating_mul(request.avg_size) > MAX_BENCHMARK_BYTES {
+        return Err(format!("A benchmark can generate at most {MAX_BENCHMARK_BYTES} bytes across its files."));
+    }
+
+    Ok(())
+}
+
+/// Generates the synthetic files of a benchmark one at a time, yielding each as the chunks it would be streamed in as
+pub struct SyntheticFiles {
+    request: BenchmarkRequest,
+    rng: XorShift,
+    pool: Vec<u8>,
+    generated: u64,
+}
+
+impl SyntheticFiles {
+    pub fn new(request: &BenchmarkRequest) -> Self {
+        let mut rng = XorShift(SEED);
+        let pool = (0..IPC_STREAM_MAX_CHUNK_LEN / 8).flat_map(|_| rng.next_u64().to_le_bytes()).collect();
+
+        SyntheticFiles {
+            request: request.clone(),
+            rng,
+            pool,
+            generated: 0,
+        }
+    }
+
+    /// Moves on to the next file, giving its size, or None once every file has been generated
+    pub fn next_file(&mut self) -> Option<u64> {
+        if self.generated == self.request.file_count {
+            return None;
+        }
+        self.generated += 1;
+
+        let avg = self.request.avg_size as f64;
+        let size = match self.request.distribution {
+            SizeDistribution::Fixed => self.request.avg_size,
+            SizeDistribution::Uniform => (self.rng.unit() * 2.0 * avg) as u64,
+            SizeDistribution::Exponential => (-(1.0 - self.rng.unit()).ln() * avg) as u64,
+        };
+
+        // the index goes at the start of the file, so the files differ
+        self.pool[..8].copy_from_slice(&self.generated.to_le_bytes());
+
+        Some(size.min(IPC_STREAM_MAX_TOTAL_LEN))
+    }
+
+    /// The content of the current file from the offset, up to a chunk of it
+    pub fn chunk(&self, offset: u64, size: u64) -> &[u8] {
+        let len = (size - offset).min(self.pool.len() as u64) as usize;
+        &self.pool[..len]
+    }
+}
+
+/// A xorshift generator, as the only randomness needed is reproducible rather than unpredictable
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number from 0 up to but excluding 1
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,185 @@
+//! A bloom filter sat in front of the exact IOC sets.
+//!
+//! The overwhelming majority of files scanned are not known bad, so the scanner can skip the exact lookup for almost
+//! every file by first asking the filter. A filter miss means the hash is definitely not in the set; a filter hit may
+//! be a false positive, so it is always confirmed against the exact set before being reported.
+//!
+//! Sized at a 1% false positive rate, the filter costs ~9.6 bits per signature: ~1.2 MB at 1M signatures and ~12 MB
+//! at 10M. A lookup is one SipHash of the 32 character hash string plus 7 bit probes, independent of the size of the
+//! set, whereas the BTreeSet descent grows with the log of the set and compares strings at each level.
+
+use std::{
+    collections::BTreeSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+/// The false positive rate the filter is sized for
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+/// The smallest filter built, so that an empty or tiny set still produces a usable filter
+const MIN_BITS: u64 = 64;
+
+/// A fixed size bloom filter over strings
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized to hold the expected number of items at the given false positive rate.
+    pub fn with_capacity(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+
+        let num_bits = ((-n * false_positive_rate.ln()) / (ln2 * ln2)).ceil() as u64;
+        let num_bits = num_bits.max(MIN_BITS);
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().max(1.0) as u32;
+
+        BloomFilter {
+            bits: vec![0u64; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        let (h1, h2) = Self::hash_pair(item);
+        for i in 0..self.num_hashes as u64 {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits;
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Checks whether the item may be in the filter.
+    ///
+    /// # Returns
+    ///
+    /// False if the item is definitely not in the filter, true if it might be.
+    pub fn might_contain(&self, item: &str) -> bool {
+        let (h1, h2) = Self::hash_pair(item);
+        (0..self.num_hashes as u64).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits;
+            self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    /// The memory used by the filter's bit array, in bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.bits.len() * size_of::<u64>()
+    }
+
+    /// Derives the two hashes used for double hashing (Kirsch-Mitzenmacher) from a single 64 bit hash. The second hash
+    /// is forced odd so that successive probes never collapse onto the same bit.
+    fn hash_pair(item: &str) -> (u64, u64) {
+        // DefaultHasher::new uses fixed keys, so the filter is deterministic for a given build
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        let h = hasher.finish();
+
+        (h, h.rotate_left(32) | 1)
+    }
+}
+
+/// A set of IOC hashes, checked against a bloom filter before the exact set.
+pub struct IocSet {
+    filter: BloomFilter,
+    exact: BTreeSet<String>,
+}
+
+impl IocSet {
+    pub fn new(exact: BTreeSet<String>) -> Self {
+        let mut filter = BloomFilter::with_capacity(exact.len(), FALSE_POSITIVE_RATE);
+        for ioc in &exact {
+            filter.insert(ioc);
+        }
+
+        IocSet { filter, exact }
+    }
+
+    /// Checks whether the hash is a known IOC. A false positive from the filter is caught by the exact set, so this
+    /// never reports a hash which is not in the set.
+    pub fn contains(&self, hash: &str) -> bool {
+        self.filter.might_contain(hash) && self.exact.contains(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.exact.len()
+    }
+
+    /// The hashes in the set, in order
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.exact.iter().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty()
+    }
+
+    /// The memory used by the bloom filter in front of the set, in bytes
+    pub fn filter_memory_bytes(&self) -> usize {
+        self.filter.memory_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{hint::black_box, time::Instant};
+
+    use super::*;
+
+    /// The number of hashes looked up in each measurement
+    const LOOKUPS: usize = 1_000_000;
+
+    /// MD5 shaped hashes, as the scanner formats them, which are distinct for each seed and index
+    fn hashes(seed: u64, count: usize) -> impl Iterator<Item = String> {
+        (0..count as u64).map(move |i| {
+            let mut hasher = DefaultHasher::new();
+            (seed, i).hash(&mut hasher);
+            format!("{:016X}{:016X}", hasher.finish(), i)
+        })
+    }
+
+    #[test]
+    fn false_positives_are_caught_by_the_exact_set() {
+        let set = IocSet::new(hashes(0, 10_000).collect());
+        assert!(hashes(0, 10_000).all(|hash| set.contains(&hash)));
+
+        // some of the hashes not in the set get past the filter, but none are reported as in the set
+        let unknown: Vec<String> = hashes(1, 100_000).collect();
+        let passed = unknown.iter().filter(|hash| set.filter.might_contain(hash)).count();
+        assert!(passed > 0 && passed < unknown.len() / 50, "{passed} of {} passed the filter", unknown.len());
+        assert!(unknown.iter().all(|hash| !set.contains(hash)));
+    }
+
+    /// Measures the memory of the filter and the time to look up a hash which is not in the set, as most hashes looked
+    /// up are not, against the exact set alone. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn filter_memory_and_lookup_latency() {
+        let unknown: Vec<String> = hashes(1, LOOKUPS).collect();
+
+        for signatures in [1_000_000, 10_000_000] {
+            let set = IocSet::new(hashes(0, signatures).collect());
+
+            let start = Instant::now();
+            let filtered = unknown.iter().filter(|hash| set.contains(black_box(hash))).count();
+            let with_filter = start.elapsed();
+
+            let start = Instant::now();
+            let exact = unknown.iter().filter(|hash| set.exact.contains(black_box(*hash))).count();
+            let without_filter = start.elapsed();
+            assert_eq!((filtered, exact), (0, 0));
+
+            println!(
+                "{signatures} signatures: filter of {:.1} MB, {:.0} ns a lookup with the filter, {:.0} ns without",
+                set.filter_memory_bytes() as f64 / (1024.0 * 1024.0),
+                with_filter.as_nanos() as f64 / LOOKUPS as f64,
+                without_filter.as_nanos() as f64 / LOOKUPS as f64,
+            );
+
+            // about 9.6 bits a signature at the false positive rate the filter is sized for
+            assert!(set.filter_memory_bytes() * 8 < signatures * 10);
+        }
+    }
+}
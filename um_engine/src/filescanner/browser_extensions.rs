@@ -0,0 +1,261 @@
+//! Enumerating the extensions installed into the browsers of each user, which malware installs itself as to read and
+//! change what the user does in the browser.
+//!
+//! The Chromium based browsers (Chrome, Edge and Brave) unpack each version of an extension into a folder of its own
+//! under the profile, whereas Firefox keeps each extension packed in an `.xpi` archive, so an extension is scanned as
+//! the files of its folder or as its archive. Its manifest is read either way, for the permissions it requests.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+use shared_std::file_scanner::{Browser, BrowserExtension};
+
+/// The user data folder of each Chromium based browser, relative to a user's profile
+const CHROMIUM_USER_DATA: [(Browser, &str); 3] = [
+    (Browser::Chrome, "AppData\\Local\\Google\\Chrome\\User Data"),
+    (Browser::Edge, "AppData\\Local\\Microsoft\\Edge\\User Data"),
+    (Browser::Brave, "AppData\\Local\\BraveSoftware\\Brave-Browser\\User Data"),
+];
+
+/// The folder holding the Firefox profiles, relative to a user's profile
+const FIREFOX_PROFILES: &str = "AppData\\Roaming\\Mozilla\\Firefox\\Profiles";
+
+/// The largest manifest read from an extension; real manifests are a few KB, so anything larger is not parsed
+const MAX_MANIFEST_LEN: u64 = 1024 * 1024;
+
+/// Permissions which give an extension control of the browser, or access to what the user does in it, beyond what
+/// most extensions need
+const DANGEROUS_PERMISSIONS: [&str; 14] = [
+    "browsingData",
+    "clipboardRead",
+    "contentSettings",
+    "cookies",
+    "debugger",
+    "desktopCapture",
+    "history",
+    "management",
+    "nativeMessaging",
+    "pageCapture",
+    "privacy",
+    "proxy",
+    "tabCapture",
+    "webRequestBlocking",
+];
+
+/// Enumerates the extensions installed into each browser profile of every user.
+///
+/// The engine runs as SYSTEM, so rather than looking in the profile of SYSTEM, the browser profiles beneath every user
+/// profile are enumerated.
+pub fn enumerate_browser_extensions() -> Vec<BrowserExtension> {
+    let mut extensions = Vec::new();
+
+    let system_drive = env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    for user_profile in subfolders(Path::new(&format!("{system_drive}\\Users"))) {
+        let user = folder_name(&user_profile);
+
+        for (browser, user_data) in CHROMIUM_USER_DATA {
+            enumerate_chromium_extensions(&mut extensions, browser, &user, &user_profile.join(user_data));
+        }
+        enumerate_firefox_extensions(&mut extensions, &user, &user_profile.join(FIREFOX_PROFILES));
+    }
+
+    extensions
+}
+
+fn enumerate_chromium_extensions(
+    extensions: &mut Vec<BrowserExtension>,
+    browser: Browser,
+    user: &str,
+    user_data: &Path,
+) {
+    // each browser profile, such as Default or Profile 1, is a folder of the user data with extensions of its own
+    for profile in subfolders(user_data) {
+        // the folder of an extension is named by its ID, alongside which the browser keeps a Temp folder for updates
+        let installed = subfolders(&profile.join("Extensions"))
+            .into_iter()
+            .filter(|extension| is_chromium_extension_id(&folder_name(extension)));
+
+        for extension in installed {
+            // each version is unpacked into a folder of its own, an update leaving the old one until the browser
+            // restarts, so each is scanned
+            for version in subfolders(&extension) {
+                let manifest = read_manifest_file(&version.join("manifest.json"));
+
+                extensions.push(browser_extension(
+                    browser,
+                    user,
+                    &folder_name(&profile),
+                    folder_name(&extension),
+                    version,
+                    manifest.as_ref(),
+                ));
+            }
+        }
+    }
+}
+
+fn enumerate_firefox_extensions(extensions: &mut Vec<BrowserExtension>, user: &str, profiles: &Path) {
+    for profile in subfolders(profiles) {
+        let Ok(entries) = fs::read_dir(profile.join("extensions")) else {
+            continue;
+        };
+
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            // extensions are installed as archives named by their ID, though one may also be unpacked into a folder
+            let (id, manifest) = if path.is_dir() {
+                (folder_name(&path), read_manifest_file(&path.join("manifest.json")))
+            } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xpi")) {
+                let id = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                (id, read_xpi_manifest(&path))
+            } else {
+                continue;
+            };
+
+            extensions.push(browser_extension(
+                Browser::Firefox,
+                user,
+                &folder_name(&profile),
+                id,
+                path,
+                manifest.as_ref(),
+            ));
+        }
+    }
+}
+
+fn browser_extension(
+    browser: Browser,
+    user: &str,
+    profile: &str,
+    extension_id: String,
+    path: PathBuf,
+    manifest: Option<&Value>,
+) -> BrowserExtension {
+    let manifest_str = |key: &str| manifest.and_then(|m| m.get(key)).and_then(Value::as_str).map(str::to_string);
+
+    BrowserExtension {
+        browser,
+        user: user.to_string(),
+        profile: profile.to_string(),
+        extension_id,
+        name: manifest_str("name"),
+        version: manifest_str("version"),
+        path,
+        dangerous_permissions: manifest.map(dangerous_permissions).unwrap_or_default(),
+    }
+}
+
+/// Gets the permissions the manifest requests which are dangerous, including access to every site, whether as a
+/// permission or as a content script injected into every page
+fn dangerous_permissions(manifest: &Value) -> Vec<String> {
+    let strings = |value: Option<&Value>| {
+        value
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    // manifest v2 mixes the sites an extension may access in with its permissions, v3 lists them separately
+    let mut found: Vec<String> = strings(manifest.get("permissions"))
+        .into_iter()
+        .chain(strings(manifest.get("host_permissions")))
+        .filter(|p| DANGEROUS_PERMISSIONS.contains(&p.as_str()) || matches_every_site(p))
+        .collect();
+
+    let content_scripts = manifest.get("content_scripts").and_then(Value::as_array).into_iter().flatten();
+    for pattern in content_scripts.flat_map(|script| strings(script.get("matches"))) {
+        if matches_every_site(&pattern) {
+            found.push(format!("content script on {pattern}"));
+        }
+    }
+
+    found.sort();
+    found.dedup();
+    found
+}
+
+/// Whether the name is a Chromium extension ID, which is 32 characters from a to p
+fn is_chromium_extension_id(name: &str) -> bool {
+    name.len() == 32 && name.bytes().all(|b| (b'a'..=b'p').contains(&b))
+}
+
+/// Whether a match pattern, such as `*://*/*`, matches every site
+fn matches_every_site(pattern: &str) -> bool {
+    if pattern == "<all_urls>" {
+        return true;
+    }
+
+    pattern
+        .split_once("://")
+        .is_some_and(|(_, rest)| rest.split('/').next() == Some("*"))
+}
+
+fn read_xpi_manifest(path: &Path) -> Option<Value> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let manifest = archive.by_name("manifest.json").ok()?;
+
+    read_manifest(&mut manifest.take(MAX_MANIFEST_LEN))
+}
+
+fn read_manifest_file(path: &Path) -> Option<Value> {
+    read_manifest(&mut File::open(path).ok()?.take(MAX_MANIFEST_LEN))
+}
+
+fn read_manifest(reader: &mut impl Read) -> Option<Value> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).ok()?;
+
+    // some manifests are saved with a byte order mark, which is not valid JSON
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&data);
+    serde_json::from_slice(data).ok()
+}
+
+/// Gets the files of an extension to scan, which are every file beneath its folder, or its archive
+pub fn extension_files(extension: &BrowserExtension) -> Vec<PathBuf> {
+    if extension.path.is_file() {
+        return vec![extension.path.clone()];
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = vec![extension.path.clone()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        // links are not followed, so a link out of the extension cannot take the scan elsewhere
+        for entry in entries.filter_map(|e| e.ok()) {
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => dirs.push(entry.path()),
+                Ok(t) if t.is_file() => files.push(entry.path()),
+                _ => (),
+            }
+        }
+    }
+
+    files
+}
+
+fn subfolders(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.path())
+        .collect()
+}
+
+fn folder_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
@@ -0,0 +1,148 @@
+//! Conditions on a signature, which narrow a hash signature down to the files it should detect so that a hash shared
+//! with something benign in another setting does not cause a false positive.
+//!
+//! Conditions follow the hash on a line of a `.sig` file, separated by whitespace, and are ANDed with the hash and
+//! with each other:
+//!
+//! ```text
+//! # sig_format: 2
+//! sha256:275A021BBFB6489E54D471899F7DB9D1663FC695EC2FE2A2C4538AABF651FD0F type=executable path=**/temp/**
+//! md5:44D88612FEA8A8F36DE82E1278ABB02F size=68-1024
+//! ```
+//!
+//! - `size=MIN-MAX` matches a file of between MIN and MAX bytes inclusive, either of which may be left out to leave
+//!   that end open; `size=N` matches a file of exactly N bytes
+//! - `type=KIND` matches a file of the kind detected from its content: `executable`, `script`, `installer`, `shortcut`
+//!   or `other`
+//! - `path=GLOB` matches the full path of the file, case insensitively and with `\` and `/` alike, as exclusion globs
+//!   are. A glob cannot contain whitespace, so a space in a path is matched with `?`.
+//!
+//! Conditions are only evaluated once the hash has matched, so they cost nothing for the files which do not.
+
+use std::{fmt, io, path::Path};
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use super::{
+    exclusions::normalise,
+    filetype::{FileKind, classify},
+};
+
+/// The conditions of a single signature, all of which a file must meet for the signature to match it
+pub struct SignatureConditions {
+    // inclusive bounds on the size of the file, in bytes
+    size: Option<(u64, u64)>,
+    kind: Option<FileKind>,
+    path: Option<GlobMatcher>,
+    // the conditions as written in the signature file, reported against a detection and written back on export
+    written: Vec<String>,
+}
+
+impl SignatureConditions {
+    /// Parses the conditions following the hash on a line of a signature file.
+    ///
+    /// # Returns
+    ///
+    /// The conditions, or an error naming the first which is not valid, or which is given more than once.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut conditions = SignatureConditions {
+            size: None,
+            kind: None,
+            path: None,
+            written: Vec::new(),
+        };
+
+        for condition in s.split_whitespace() {
+            let (name, value) = condition
+                .split_once('=')
+                .ok_or_else(|| format!("'{condition}' is not a condition, which is written as name=value."))?;
+
+            let duplicate = match name {
+                "size" => conditions.size.replace(parse_size(value)?).is_some(),
+                "type" => {
+                    let kind = FileKind::from_name(value).ok_or_else(|| format!("'{value}' is not a file type."))?;
+                    conditions.kind.replace(kind).is_some()
+                }
+                "path" => {
+                    let glob = GlobBuilder::new(&value.replace('\\', "/"))
+                        .case_insensitive(true)
+                        .literal_separator(true)
+                        .build()
+                        .map_err(|e| format!("Invalid path glob '{value}'. {e}"))?;
+                    conditions.path.replace(glob.compile_matcher()).is_some()
+                }
+                _ => return Err(format!("'{name}' is not a condition.")),
+            };
+
+            if duplicate {
+                return Err(format!("The {name} condition is given more than once."));
+            }
+            conditions.written.push(condition.to_string());
+        }
+
+        if conditions.written.is_empty() {
+            return Err("No conditions were given.".to_string());
+        }
+
+        Ok(conditions)
+    }
+
+    /// Checks whether the file meets every condition. The cheapest conditions are checked first, and the file is only
+    /// read to detect its type where the others are met.
+    ///
+    /// # Returns
+    ///
+    /// The conditions as written where the file meets them all, None where it does not, or an error where the file
+    /// could not be read.
+    pub fn evaluate(&self, path: &Path, len: u64) -> io::Result<Option<&[String]>> {
+        if let Some((min, max)) = self.size
+            && !(min..=max).contains(&len)
+        {
+            return Ok(None);
+        }
+
+        if let Some(glob) = &self.path
+            && !glob.is_match(normalise(&path.to_string_lossy()))
+        {
+            return Ok(None);
+        }
+
+        if let Some(kind) = self.kind
+            && classify(path)? != kind
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(&self.written))
+    }
+}
+
+impl fmt::Display for SignatureConditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.written.join(" "))
+    }
+}
+
+/// Parses a size condition of `MIN-MAX`, `MIN-`, `-MAX` or `N` bytes
+fn parse_size(value: &str) -> Result<(u64, u64), String> {
+    let invalid = || format!("'{value}' is not a size or range of sizes in bytes.");
+    let bound = |s: &str, open: u64| match s {
+        "" => Ok(open),
+        s => s.parse::<u64>().map_err(|_| invalid()),
+    };
+
+    let (min, max) = match value.split_once('-') {
+        Some((min, max)) if !(min.is_empty() && max.is_empty()) => (bound(min, 0)?, bound(max, u64::MAX)?),
+        Some(_) => return Err(invalid()),
+        None => {
+            let size = value.parse::<u64>().map_err(|_| invalid())?;
+            (size, size)
+        }
+    };
+
+    if min > max {
+        return Err(format!("The size range '{value}' is empty, as its minimum is above its maximum."));
+    }
+
+    Ok((min, max))
+}
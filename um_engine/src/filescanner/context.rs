@@ -0,0 +1,64 @@
+//! Capturing the bytes of a file which matched a detection, so an analyst triaging the detection can see what matched
+//! rather than only which signature did.
+//!
+//! The capture is bounded per detection, so a large match cannot bloat the detection or the IPC response carrying it.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use shared_std::file_scanner::MatchedBytes;
+
+/// The most bytes captured for a single detection
+pub const MAX_CONTEXT_BYTES: u64 = 256;
+
+/// The number of bytes shown on each line of a hex dump
+const BYTES_PER_LINE: usize = 16;
+
+/// Captures up to [`MAX_CONTEXT_BYTES`] of the match which starts at the offset and runs for len bytes, as a hex dump
+/// labelled with what matched
+pub fn capture<R: Read + Seek>(
+    reader: &mut R,
+    label: String,
+    offset: u64,
+    len: u64,
+) -> Result<MatchedBytes, io::Error> {
+    let captured_len = len.min(MAX_CONTEXT_BYTES);
+
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::with_capacity(captured_len as usize);
+    reader.take(captured_len).read_to_end(&mut bytes)?;
+
+    Ok(MatchedBytes {
+        label,
+        offset,
+        len: bytes.len() as u64,
+        truncated: len > bytes.len() as u64,
+        hexdump: hexdump(offset, &bytes),
+    })
+}
+
+/// Formats the bytes as a hex dump, with each line giving the offset in the file, the bytes as hex, and the bytes as
+/// ASCII with anything unprintable shown as a `.`:
+///
+/// ```text
+/// 00000400  4D 5A 90 00 03 00 00 00 04 00 00 00 FF FF 00 00  |MZ..............|
+/// ```
+fn hexdump(offset: u64, bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (i, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{b:02X}")).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+
+        dump.push_str(&format!(
+            "{:08X}  {:<width$}  |{ascii}|\n",
+            offset + (i * BYTES_PER_LINE) as u64,
+            hex.join(" "),
+            width = BYTES_PER_LINE * 3 - 1,
+        ));
+    }
+
+    dump
+}
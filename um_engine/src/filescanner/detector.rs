@@ -0,0 +1,447 @@
+//! Detectors are the stages of the scan pipeline which decide whether a file is malicious.
+//!
+//! The built in engines are detectors themselves, run in a fixed order; detectors registered with
+//! [`FileScanner::register_detector`] run after the signature engines and ahead of the heuristics. Every detector gives
+//! its verdict on each file, and where more than one detects it, the file is reported as detected by the detector of
+//! highest precedence in the verdict settings, with the verdicts of the others recorded alongside.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    sync::Arc,
+};
+
+use shared_std::file_scanner::{EngineFlags, MatchMode, MatchedBytes, NotDetectedReason};
+
+use super::{
+    FileScanner,
+    imports::import_hash,
+    macros::{analyse_macros, extract_macros},
+    pe::PeHeaders,
+    shortcut::{read_shortcut, shortcut_indicators},
+    signatures::SignatureSet,
+};
+
+/// A detector which may be run on each file of a scan, alongside the built in engines.
+///
+/// Detectors are run on the scan worker thread, so should return promptly; a detector which makes no progress on a
+/// file for the stall timeout has the file recorded as stalled, as the built in engines do.
+pub trait Detector: Send + Sync {
+    /// The name of the detector, which must be unique amongst those registered
+    fn name(&self) -> &str;
+
+    /// Inspects the file, returning the detection where it is malicious.
+    ///
+    /// # Returns
+    ///
+    /// - Ok(Some) containing the detection, which is reported where no detector of higher precedence detects the file
+    /// - Ok(None) if the detector did not detect the file, leaving it to the next detector
+    /// - Err if the file could not be read, which is reported as an error scanning the file
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>>;
+
+    /// Explains why the detector did not detect the file, for `scanner_explain`. This is only asked of a detector which
+    /// has just inspected the file and not detected it. A detector which does not implement this gives no reason.
+    fn explain(&self, _ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        Ok(NotDetectedReason::NoMatch)
+    }
+}
+
+/// The file being scanned, given to each detector in turn
+pub struct FileContext<'a> {
+    pub path: &'a Path,
+    /// The open file. Earlier detectors may have moved its position, so a detector should seek to wherever it reads
+    /// from.
+    pub file: &'a File,
+    /// The MD5 hash of the whole file, None where the file hash engine is disabled
+    pub md5: Option<&'a str>,
+    /// The SHA256 hash of the whole file, only computed where there are SHA256 signatures to match
+    pub sha256: Option<&'a str>,
+    // what the built in detectors match against, which is not exposed to registered detectors
+    pub(super) scanner: &'a FileScanner,
+    pub(super) signatures: &'a SignatureSet,
+    pub(super) disabled: &'a [String],
+    pub(super) engines: EngineFlags,
+    /// Whether the file is part of the running scan, rather than being classified on its own
+    pub(super) in_scan: bool,
+}
+
+/// What a detector found in a file, which is reported as a [`shared_std::file_scanner::MatchedIOC`]
+pub struct Detection {
+    /// The hash which matched, or which otherwise identifies what was found
+    pub hash: String,
+    pub match_mode: MatchMode,
+    /// The signature database or detector which detected the file
+    pub source: Option<String>,
+    /// The bytes of the file which matched, kept so they can be shown to an analyst
+    pub regions: Vec<MatchedBytes>,
+    /// The conditions of the signature which the file met, as written in the signature file; empty where the
+    /// signature has none
+    pub conditions: Vec<String>,
+}
+
+/// The built in detectors, in the order they are run
+pub(super) fn builtin_detectors() -> Vec<Arc<dyn Detector>> {
+    vec![
+        Arc::new(FileHashDetector),
+        Arc::new(SectionHashDetector),
+        Arc::new(ImportHashDetector),
+        Arc::new(FuzzyHashDetector),
+        Arc::new(MacroDetector),
+        Arc::new(ShortcutDetector),
+        Arc::new(HeuristicDetector),
+    ]
+}
+
+/// Matches the hashes of the whole file against each enabled signature database. Where the signature has conditions,
+/// the file must also meet one set of them, else the hash is looked for in the next database.
+struct FileHashDetector;
+
+impl Detector for FileHashDetector {
+    fn name(&self) -> &str {
+        "file_hash"
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        let hashes = [ctx.md5.map(|hash| (hash, false)), ctx.sha256.map(|hash| (hash, true))];
+        // the size is only read where a signature with conditions matches, which few files do
+        let mut len = None;
+
+        for (hash, sha256) in hashes.into_iter().flatten() {
+            for db in ctx.signatures.enabled(ctx.disabled) {
+                let contains = match sha256 {
+                    true => db.sha256_iocs.contains(hash),
+                    false => db.iocs.contains(hash),
+                };
+                if !contains {
+                    continue;
+                }
+
+                let conditions = match db.conditions(hash) {
+                    None => Vec::new(),
+                    Some(signatures) => {
+                        let file_len = match len {
+                            Some(file_len) => file_len,
+                            None => *len.insert(ctx.file.metadata()?.len()),
+                        };
+
+                        let met = signatures
+                            .iter()
+                            .find_map(|conditions| conditions.evaluate(ctx.path, file_len).transpose())
+                            .transpose()?;
+                        match met {
+                            Some(met) => met.to_vec(),
+                            None => continue,
+                        }
+                    }
+                };
+
+                return Ok(Some(Detection {
+                    hash: hash.to_string(),
+                    match_mode: MatchMode::FileHash,
+                    source: Some(db.name.clone()),
+                    regions: Vec::new(),
+                    conditions,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        let Some(md5) = ctx.md5 else {
+            return Ok(NotDetectedReason::EngineDisabled);
+        };
+
+        // a hash which is in a database was only passed over as the file met none of its signature's conditions
+        let hashes = [Some((md5, false)), ctx.sha256.map(|hash| (hash, true))];
+        for (hash, sha256) in hashes.into_iter().flatten() {
+            let db = ctx.signatures.enabled(ctx.disabled).find(|db| match sha256 {
+                true => db.sha256_iocs.contains(hash),
+                false => db.iocs.contains(hash),
+            });
+            if let Some(db) = db {
+                return Ok(NotDetectedReason::ConditionsNotMet {
+                    hash: hash.to_string(),
+                    source: db.name.clone(),
+                });
+            }
+        }
+
+        Ok(NotDetectedReason::HashNotInDatabase {
+            md5: md5.to_string(),
+            sha256: ctx.sha256.map(str::to_string),
+        })
+    }
+}
+
+/// Matches the hash of each section of a PE, for where the whole file did not match
+struct SectionHashDetector;
+
+impl Detector for SectionHashDetector {
+    fn name(&self) -> &str {
+        "section_hash"
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        if !ctx.engines.contains(EngineFlags::SECTION_HASH)
+            || ctx.signatures.enabled(ctx.disabled).all(|db| db.section_iocs.is_empty())
+        {
+            return Ok(None);
+        }
+
+        let matched = ctx.scanner.scan_sections_against_hashes(ctx.file, ctx.signatures, ctx.disabled, ctx.in_scan)?;
+
+        Ok(matched.map(|(hash, section, matched, source)| Detection {
+            hash,
+            match_mode: MatchMode::SectionHash { section },
+            source: Some(source),
+            regions: vec![matched],
+            conditions: Vec::new(),
+        }))
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        if !ctx.engines.contains(EngineFlags::SECTION_HASH) {
+            return Ok(NotDetectedReason::EngineDisabled);
+        }
+        if ctx.signatures.enabled(ctx.disabled).all(|db| db.section_iocs.is_empty()) {
+            return Ok(NotDetectedReason::NoSignatures);
+        }
+
+        Ok(match PeHeaders::parse(&mut BufReader::new(ctx.file))? {
+            Some(headers) => NotDetectedReason::SectionsNotInDatabase {
+                sections: headers.sections.len(),
+            },
+            None => NotDetectedReason::NotPe,
+        })
+    }
+}
+
+/// Matches the import hash of a PE, catching variants of a family which were recompiled but kept its imports. It runs
+/// as part of the section hash engine, as both match on the structure of a PE.
+struct ImportHashDetector;
+
+impl Detector for ImportHashDetector {
+    fn name(&self) -> &str {
+        "import_hash"
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        if !ctx.engines.contains(EngineFlags::SECTION_HASH)
+            || ctx.signatures.enabled(ctx.disabled).all(|db| db.import_iocs.is_empty())
+        {
+            return Ok(None);
+        }
+
+        let mut reader = BufReader::new(ctx.file);
+        let Some(headers) = PeHeaders::parse(&mut reader)? else {
+            return Ok(None);
+        };
+        let Some(imphash) = import_hash(&mut reader, &headers)? else {
+            return Ok(None);
+        };
+
+        let db = ctx.signatures.enabled(ctx.disabled).find(|db| db.import_iocs.contains(&imphash));
+
+        Ok(db.map(|db| Detection {
+            hash: imphash,
+            match_mode: MatchMode::ImportHash,
+            source: Some(db.name.clone()),
+            regions: Vec::new(),
+            conditions: Vec::new(),
+        }))
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        if !ctx.engines.contains(EngineFlags::SECTION_HASH) {
+            return Ok(NotDetectedReason::EngineDisabled);
+        }
+        if ctx.signatures.enabled(ctx.disabled).all(|db| db.import_iocs.is_empty()) {
+            return Ok(NotDetectedReason::NoSignatures);
+        }
+
+        let mut reader = BufReader::new(ctx.file);
+        let Some(headers) = PeHeaders::parse(&mut reader)? else {
+            return Ok(NotDetectedReason::NotPe);
+        };
+
+        Ok(match import_hash(&mut reader, &headers)? {
+            Some(imphash) => NotDetectedReason::ImportHashNotInDatabase { imphash },
+            None => NotDetectedReason::NoImports,
+        })
+    }
+}
+
+/// Checks whether the file is a near duplicate of known malware
+struct FuzzyHashDetector;
+
+impl Detector for FuzzyHashDetector {
+    fn name(&self) -> &str {
+        "fuzzy_hash"
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        if !ctx.engines.contains(EngineFlags::FUZZY_HASH) {
+            return Ok(None);
+        }
+
+        let matched =
+            ctx.scanner.scan_file_against_fuzzy_hashes(ctx.file, ctx.signatures, ctx.disabled, ctx.in_scan)?;
+
+        Ok(matched.map(|(signature, similarity, source)| Detection {
+            hash: signature,
+            match_mode: MatchMode::FuzzyHash { similarity },
+            source: Some(source),
+            regions: Vec::new(),
+            conditions: Vec::new(),
+        }))
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        if !ctx.engines.contains(EngineFlags::FUZZY_HASH) {
+            return Ok(NotDetectedReason::EngineDisabled);
+        }
+
+        ctx.scanner.explain_fuzzy_hash(ctx.file, ctx.signatures, ctx.disabled)
+    }
+}
+
+/// Extracts the VBA macros from Office documents, detecting those which run by themselves and make suspicious calls.
+/// The hash is that of the whole file, where it was computed.
+struct MacroDetector;
+
+impl Detector for MacroDetector {
+    fn name(&self) -> &str {
+        "macro"
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        if !ctx.engines.contains(EngineFlags::MACRO) {
+            return Ok(None);
+        }
+
+        let modules = extract_macros(ctx.file)?;
+
+        Ok(analyse_macros(&modules).map(|finding| Detection {
+            hash: ctx.md5.unwrap_or_default().to_string(),
+            match_mode: MatchMode::Macro {
+                module: finding.module,
+                auto_exec: finding.auto_exec,
+                suspicious_calls: finding.suspicious_calls,
+            },
+            source: None,
+            regions: Vec::new(),
+            conditions: Vec::new(),
+        }))
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        if !ctx.engines.contains(EngineFlags::MACRO) {
+            return Ok(NotDetectedReason::EngineDisabled);
+        }
+
+        Ok(match extract_macros(ctx.file)?.len() {
+            0 => NotDetectedReason::NoMacros,
+            modules => NotDetectedReason::MacrosNotSuspicious { modules },
+        })
+    }
+}
+
+/// Parses shortcuts for the file they point to, scanning it with the other detectors, and checks the arguments it is
+/// run with. A shortcut is detected where its target is detected, or where its arguments look like a payload. The hash
+/// is that of the target where it was detected, else that of the shortcut, where it was computed.
+struct ShortcutDetector;
+
+impl Detector for ShortcutDetector {
+    fn name(&self) -> &str {
+        "shortcut"
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        if !ctx.engines.contains(EngineFlags::SHORTCUT) {
+            return Ok(None);
+        }
+
+        let Some(shortcut) = read_shortcut(ctx.file, ctx.path)? else {
+            return Ok(None);
+        };
+
+        let indicators = shortcut_indicators(&shortcut);
+        let target_detection = shortcut
+            .target
+            .as_ref()
+            .and_then(|target| ctx.scanner.inspect_shortcut_target(target, ctx.in_scan));
+        if target_detection.is_none() && indicators.is_empty() {
+            return Ok(None);
+        }
+
+        // the database the target was matched in is that of the shortcut's detection too
+        let (hash, source) = match &target_detection {
+            Some(ioc) => (ioc.hash.clone(), ioc.source.clone()),
+            None => (ctx.md5.unwrap_or_default().to_string(), None),
+        };
+
+        Ok(Some(Detection {
+            hash,
+            match_mode: MatchMode::Shortcut {
+                target: shortcut.target,
+                arguments: shortcut.arguments,
+                indicators,
+                target_detection: target_detection.map(Box::new),
+            },
+            source,
+            regions: Vec::new(),
+            conditions: Vec::new(),
+        }))
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        if !ctx.engines.contains(EngineFlags::SHORTCUT) {
+            return Ok(NotDetectedReason::EngineDisabled);
+        }
+
+        Ok(match read_shortcut(ctx.file, ctx.path)? {
+            Some(shortcut) => NotDetectedReason::ShortcutNotSuspicious {
+                target: shortcut.target,
+            },
+            None => NotDetectedReason::NotShortcut,
+        })
+    }
+}
+
+/// Scores the file on the heuristics. The hash is that of the whole file, where it was computed.
+pub(super) struct HeuristicDetector;
+
+impl HeuristicDetector {
+    pub(super) const NAME: &str = "heuristic";
+}
+
+impl Detector for HeuristicDetector {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn inspect(&self, ctx: &FileContext) -> io::Result<Option<Detection>> {
+        if !ctx.engines.contains(EngineFlags::HEURISTIC) {
+            return Ok(None);
+        }
+
+        Ok(ctx.scanner.evaluate_heuristics(ctx.path, ctx.file, ctx.in_scan)?.map(|(score, signals)| Detection {
+            hash: ctx.md5.unwrap_or_default().to_string(),
+            match_mode: MatchMode::Heuristic { score, signals },
+            source: None,
+            regions: Vec::new(),
+            conditions: Vec::new(),
+        }))
+    }
+
+    fn explain(&self, ctx: &FileContext) -> io::Result<NotDetectedReason> {
+        if !ctx.engines.contains(EngineFlags::HEURISTIC) {
+            return Ok(NotDetectedReason::EngineDisabled);
+        }
+
+        ctx.scanner.explain_heuristics(ctx.path, ctx.file)
+    }
+}
@@ -0,0 +1,129 @@
+//! Enumerating the kernel drivers loaded on the machine, and verifying the signature of the file each was loaded
+//! from. A driver which is unsigned, or whose signature is not trusted, is a strong sign of a rootkit, as Windows only
+//! loads one where driver signature enforcement has been turned off or got around.
+//!
+//! Windows does not keep when each driver was loaded, so the drivers already loaded when the engine starts are noted,
+//! and any other driver is taken to have been loaded when the engine first sees it. A driver loaded since the engine
+//! started, such as one loaded by malware to hide itself, is therefore told apart from those loaded at boot.
+
+use std::{collections::HashMap, ffi::c_void, mem, path::PathBuf, ptr, sync::Mutex, time::SystemTime};
+
+use shared_std::file_scanner::DriverInfo;
+use windows::Win32::System::ProcessStatus::{EnumDeviceDrivers, GetDeviceDriverBaseNameW, GetDeviceDriverFileNameW};
+
+use super::{authenticode, autoruns::normalise_native_path};
+
+/// Room left for drivers loaded between measuring the list and reading it
+const EXTRA_DRIVER_SLOTS: usize = 32;
+/// The longest name or path of a driver read
+const MAX_DRIVER_PATH_LEN: usize = 1024;
+
+/// A driver loaded in the kernel, before its file has been verified
+struct LoadedDriver {
+    name: String,
+    path: Option<PathBuf>,
+}
+
+impl LoadedDriver {
+    /// The driver's key in the load times, its path in lowercase, or its name where it has no path
+    fn key(&self) -> String {
+        match &self.path {
+            Some(path) => path.to_string_lossy().to_lowercase(),
+            None => self.name.to_lowercase(),
+        }
+    }
+}
+
+/// When each driver was first seen loaded, by [`LoadedDriver::key`]
+pub struct DriverLoadTimes {
+    first_seen: Mutex<HashMap<String, Option<SystemTime>>>,
+}
+
+impl DriverLoadTimes {
+    /// Notes the drivers already loaded, which have no load time
+    pub fn new() -> Self {
+        let loaded = loaded_drivers().unwrap_or_default();
+
+        DriverLoadTimes {
+            first_seen: Mutex::new(loaded.iter().map(|driver| (driver.key(), None)).collect()),
+        }
+    }
+
+    /// When the driver was first seen, which is now for a driver not seen before
+    fn load_time(&self, driver: &LoadedDriver) -> Option<SystemTime> {
+        *self
+            .first_seen
+            .lock()
+            .unwrap()
+            .entry(driver.key())
+            .or_insert_with(|| Some(SystemTime::now()))
+    }
+}
+
+/// Lists the drivers loaded in the kernel, with the signature of the file each was loaded from. Each file is
+/// verified, which for a file signed through a catalog means looking up its catalog, so this takes a few seconds and
+/// should be run off of the async runtime.
+///
+/// # Returns
+///
+/// The loaded drivers, or an error where the drivers could not be listed, such as where the engine is not running
+/// with the privilege to see where they are loaded.
+pub fn enumerate_drivers(load_times: &DriverLoadTimes) -> Result<Vec<DriverInfo>, String> {
+    let drivers = loaded_drivers()?;
+
+    Ok(drivers
+        .into_iter()
+        .map(|driver| {
+            let signature = driver.path.as_deref().map(authenticode::verify).unwrap_or_default();
+
+            DriverInfo {
+                load_time: load_times.load_time(&driver),
+                name: driver.name,
+                path: driver.path,
+                signed: signature.signed,
+                signer: signature.signer,
+                trusted: signature.trusted,
+            }
+        })
+        .collect())
+}
+
+fn loaded_drivers() -> Result<Vec<LoadedDriver>, String> {
+    let slot = mem::size_of::<*mut c_void>();
+
+    let mut needed = 0;
+    unsafe { EnumDeviceDrivers(ptr::null_mut(), 0, &mut needed) }
+        .map_err(|e| format!("Could not list the loaded drivers. {e}"))?;
+
+    let mut bases: Vec<*mut c_void> = vec![ptr::null_mut(); needed as usize / slot + EXTRA_DRIVER_SLOTS];
+    unsafe { EnumDeviceDrivers(bases.as_mut_ptr(), (bases.len() * slot) as u32, &mut needed) }
+        .map_err(|e| format!("Could not list the loaded drivers. {e}"))?;
+    bases.truncate(needed as usize / slot);
+
+    // Windows gives every address as zero to a caller without the privilege to see where drivers are loaded, and
+    // looks drivers up by their address
+    if !bases.is_empty() && bases.iter().all(|base| base.is_null()) {
+        return Err("The engine does not have the privilege to see where the loaded drivers are.".to_string());
+    }
+
+    let mut buf = [0u16; MAX_DRIVER_PATH_LEN];
+    let read = |buf: &[u16], len: u32| String::from_utf16_lossy(&buf[..len as usize]);
+
+    Ok(bases
+        .into_iter()
+        .filter(|base| !base.is_null())
+        .filter_map(|base| {
+            let len = unsafe { GetDeviceDriverBaseNameW(base, &mut buf) };
+            if len == 0 {
+                return None;
+            }
+            let name = read(&buf, len);
+
+            // the path is in the native form the driver was loaded by, such as \SystemRoot\System32\drivers\x.sys
+            let len = unsafe { GetDeviceDriverFileNameW(base, &mut buf) };
+            let path = (len > 0).then(|| PathBuf::from(normalise_native_path(&read(&buf, len))));
+
+            Some(LoadedDriver { name, path })
+        })
+        .collect())
+}
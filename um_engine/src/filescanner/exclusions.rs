@@ -0,0 +1,175 @@
+//! Matching of paths against the user's exclusion list.
+//!
+//! The list is compiled once when it is set, so that every pattern is validated up front and the walk only pays for
+//! matching. Globs are compiled into a single `GlobSet` and regexes into a single `RegexSet`, so a path is checked
+//! against every pattern of a kind in one pass rather than one pattern at a time.
+
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexSet, RegexSetBuilder};
+use shared_std::settings::{ExclusionList, ExclusionRule};
+
+/// An [`ExclusionList`] which has been validated and compiled into matchers
+pub struct CompiledExclusions {
+    /// The rules as they were given, so the rule which excluded a path can be reported
+    rules: Vec<ExclusionRule>,
+    /// Normalised (lowercase, forward slash, no trailing slash) path prefixes, with the index of their rule
+    prefixes: Vec<(String, usize)>,
+    globs: GlobSet,
+    /// The index of the rule each glob in the set was compiled from
+    glob_rules: Vec<usize>,
+    regexes: RegexSet,
+    /// The index of the rule each regex in the set was compiled from
+    regex_rules: Vec<usize>,
+}
+
+impl Default for CompiledExclusions {
+    fn default() -> Self {
+        CompiledExclusions {
+            rules: Vec::new(),
+            prefixes: Vec::new(),
+            globs: GlobSet::empty(),
+            glob_rules: Vec::new(),
+            regexes: RegexSet::empty(),
+            regex_rules: Vec::new(),
+        }
+    }
+}
+
+impl CompiledExclusions {
+    /// Compiles the exclusion list.
+    ///
+    /// # Returns
+    ///
+    /// An error naming the first pattern which is not valid, with the reason it could not be compiled.
+    pub fn compile(list: &ExclusionList) -> Result<Self, String> {
+        let mut prefixes = Vec::new();
+        let mut globs = GlobSetBuilder::new();
+        let mut glob_rules = Vec::new();
+        let mut regexes = Vec::new();
+        let mut regex_rules = Vec::new();
+
+        for (i, rule) in list.rules.iter().enumerate() {
+            match rule {
+                ExclusionRule::Prefix(path) => {
+                    let prefix = normalise(&path.to_string_lossy());
+                    if prefix.is_empty() {
+                        return Err("Exclusion path prefix cannot be empty.".to_string());
+                    }
+                    prefixes.push((prefix, i));
+                }
+                ExclusionRule::Glob(pattern) => {
+                    let glob = GlobBuilder::new(&pattern.replace('\\', "/"))
+                        .case_insensitive(true)
+                        .literal_separator(true)
+                        .build()
+                        .map_err(|e| format!("Invalid exclusion glob '{pattern}'. {e}"))?;
+                    globs.add(glob);
+                    glob_rules.push(i);
+                }
+                ExclusionRule::Regex { pattern, anchored } => {
+                    let pattern = if *anchored {
+                        format!("^(?:{pattern})$")
+                    } else {
+                        pattern.clone()
+                    };
+                    regexes.push(pattern);
+                    regex_rules.push(i);
+                }
+            }
+        }
+
+        let globs = globs
+            .build()
+            .map_err(|e| format!("Could not build exclusion globs. {e}"))?;
+
+        // validate each regex individually first so the error names the offending pattern
+        for pattern in &regexes {
+            if let Err(e) = Regex::new(pattern) {
+                return Err(format!("Invalid exclusion regex '{pattern}'. {e}"));
+            }
+        }
+        let regexes = RegexSetBuilder::new(&regexes)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("Could not build exclusion regexes. {e}"))?;
+
+        Ok(CompiledExclusions {
+            rules: list.rules.clone(),
+            prefixes,
+            globs,
+            glob_rules,
+            regexes,
+            regex_rules,
+        })
+    }
+
+    /// Checks whether the path is excluded by any rule
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let raw = path.to_string_lossy();
+        let normalised = normalise(&raw);
+
+        let prefix_match = self
+            .prefixes
+            .iter()
+            .any(|(prefix, _)| prefix_matches(prefix, &normalised));
+
+        prefix_match || self.globs.is_match(&normalised) || self.regexes.is_match(&raw)
+    }
+
+    /// Finds the rule which excludes the path, matched in the same way as [`CompiledExclusions::is_excluded`]. Where
+    /// several rules match, the first in the list is returned.
+    pub fn matching_rule(&self, path: &Path) -> Option<&ExclusionRule> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let raw = path.to_string_lossy();
+        let normalised = normalise(&raw);
+
+        let prefixes = self
+            .prefixes
+            .iter()
+            .filter(|(prefix, _)| prefix_matches(prefix, &normalised))
+            .map(|(_, i)| *i);
+        let globs = self
+            .globs
+            .matches(&normalised)
+            .into_iter()
+            .map(|i| self.glob_rules[i]);
+        let regexes = self
+            .regexes
+            .matches(&raw)
+            .into_iter()
+            .map(|i| self.regex_rules[i]);
+
+        prefixes
+            .chain(globs)
+            .chain(regexes)
+            .min()
+            .map(|i| &self.rules[i])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty() && self.globs.is_empty() && self.regexes.is_empty()
+    }
+}
+
+/// Whether the normalised path is the prefix, or is beneath it
+pub(super) fn prefix_matches(prefix: &str, normalised: &str) -> bool {
+    normalised == prefix
+        || (normalised.starts_with(prefix) && normalised.as_bytes().get(prefix.len()) == Some(&b'/'))
+}
+
+/// Lowercases the path and converts it to forward slashes, with no trailing slash, so that prefixes and globs
+/// compare the way Windows resolves paths.
+pub(super) fn normalise(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_end_matches('/')
+        .to_lowercase()
+}
@@ -0,0 +1,4234 @@
+//! File scanner module
+//!
+//! This module provides functionality for scanning files and retrieving relevant
+//! information about a file that the EDR may want to use in decision making.
+
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use shared_std::{
+    events::EngineEvent,
+    file_scanner::{
+        ActionResult, AutorunDetection, AutorunScanResult, BenchmarkReport, BenchmarkRequest, BrowserExtensionDetection,
+        BrowserExtensionScanResult, BytesScanResult, CANCEL_REASON_TIME_LIMIT, CANCEL_REASON_UPDATE, CANCEL_REASON_USER,
+        Classification, DetectionAction, DetectionDetails, DetectionThresholds, DriverDetection, DriverInfo,
+        DriverScanResult, EngineBusy, EngineExplanation, EngineFlags, EngineTiming, EngineVerdict, Explanation,
+        ExtensionStats, FileProgress, FileScannerState, FileVerdict, HeuristicSignal, MAX_IN_USE_LISTED,
+        MAX_INACCESSIBLE_LISTED, MAX_RESULTS_PAGE_LEN, MAX_SIMULATED_SEVERITY, MODIFIED_SINCE_GRACE, MatchMode,
+        MatchedBytes, MatchedIOC, NotDetectedReason, ProcessMemoryReport, RegistryContentMatch, RegistryDetection,
+        RegistryScanRequest, RegistryScanResult, RescanResult, ResultsPage, ResultsPageRequest, RootProgress, RootState,
+        ScanEstimate, ScanOptions, ScanProgress, ScanStarted, ScanStatus, ScanTarget, ScanType, ScanningLiveInfo,
+        ScheduledTaskDetection, ScheduledTaskScanResult, SimulatedDetectionRequest, StalledFile, VerdictPolicy,
+    },
+    history::{ScanRecord, TriageStatus},
+    quarantine::{BatchQuarantineItem, BatchQuarantineOutcome, BatchQuarantineResult},
+    settings::{
+        ExclusionList, ExclusionMatch, ExclusionPreview, ExclusionPreviewRequest, FuzzyHashSettings, InjectionSettings,
+        OTHER_ENGINES, RemoteVolumeSettings, SanctumSettings, ScanWatchdogSettings, VerdictSettings,
+    },
+    version::{
+        HashAlgorithm, HashRequest, HashResult, SignatureCounts, SignatureDatabaseInfo, SignatureExportReport,
+        SignatureFileStats, SignatureImportFormat, SignatureImportReport, SignatureValidationReport, UpdateCommitted,
+        UpdateReadiness,
+    },
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    os::windows::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, RwLock,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::sync::watch;
+use windows::Win32::Foundation::{ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION};
+
+use crate::{
+    events::EventBus,
+    history::ScanHistory,
+    notifications::DetectionNotifier,
+    quarantine::QuarantineStore,
+    utils::log::{Log, LogLevel},
+};
+
+use super::{
+    autoruns::enumerate_autoruns,
+    benchmark::{self, SyntheticFiles},
+    browser_extensions::{enumerate_browser_extensions, extension_files},
+    context,
+    detector::{Detection, Detector, FileContext, HeuristicDetector, builtin_detectors},
+    drivers::{DriverLoadTimes, enumerate_drivers},
+    exclusions::{CompiledExclusions, normalise},
+    filetype::{FileKind, classify},
+    fuzzy::FuzzyHash,
+    heuristics::CompiledHeuristics,
+    image::MountedImage,
+    injection::inspect_process,
+    journal::{JournalCounters, ScanJournal, recover_interrupted_scan},
+    memory::MemoryThrottle,
+    pe::PeHeaders,
+    privileges::{open_for_read, read_dir},
+    process::process_image,
+    registry::RegistryWalk,
+    remediation::{describe_error, take_action},
+    scheduled_tasks::enumerate_scheduled_tasks,
+    scheduler::{OnDemandPermit, OnDemandScheduler, RequestPriority},
+    signatures::{SignatureSet, export_signatures, import_signatures, validate_signature_file},
+    update::UpdateWindow,
+    volume::{is_remote_volume, reserved_entries, resolve_volume_root},
+};
+
+/// How often the progress of a folder scan is published to event subscribers
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The size of the scratch buffer files are read into whilst hashing. Files larger than this are hashed in chunks.
+const HASH_CHUNK_SIZE: usize = 1024 * 1024; // 1 mb
+
+/// Files larger than this are not fuzzy hashed, as the whole file is read into memory to hash it
+const FUZZY_MAX_FILE_SIZE: u64 = 32 * 1024 * 1024; // 32 mb
+
+/// Files at least this large report how far through them a scan is whilst they are hashed, as hashing one takes long
+/// enough for a scan of it alone to look stuck
+const LARGE_FILE_PROGRESS_BYTES: u64 = 256 * 1024 * 1024; // 256 mb
+
+/// How often the scan watchdog checks the worker for progress
+const WATCHDOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// The shortest stall timeout allowed in the settings, so that a slow disk is not mistaken for a stalled worker
+const MIN_STALL_TIMEOUT_SECS: u64 = 10;
+
+/// How often a file held back whilst memory is low checks whether memory has recovered
+const MEMORY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long `update_begin` waits for a scan cancelled for the update to wind down
+const UPDATE_QUIESCE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often `update_begin` checks whether the cancelled scan has wound down
+const UPDATE_QUIESCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many times the files another process held open are retried at the end of a scan, before they are skipped
+const IN_USE_RETRY_ATTEMPTS: u32 = 3;
+/// How long to wait before the first retry of the files held open, doubling before each retry after
+const IN_USE_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// The most files held open which a scan keeps to retry; beyond this they are skipped straight away, so that a scan of
+/// a busy system does not build an unbounded list
+const MAX_IN_USE_DEFERRED: usize = 10_000;
+
+/// The FileScanner is the public interface into the module handling any static file scanning type capability.
+/// This struct is public for visibility from lib.rs the core of um_engine, but it not intended to be accessed from the
+/// Tauri application - for handling state (which tauri will need to interact with), see FileScannerState
+pub struct FileScanner {
+    // signatures: the signature sets matched against, replaced whole when the signatures are reloaded. Each file is
+    // matched against the set in use when its scan of that file began.
+    signatures: RwLock<Arc<SignatureSet>>,
+    // the names of the signature databases which are not matched against, from the settings. Replaced whole when the
+    // settings change, so that each file takes the names without copying them.
+    disabled_databases: RwLock<Arc<[String]>>,
+    fuzzy_settings: RwLock<FuzzyHashSettings>,
+    // the heuristics files which match no signature are scored on, compiled from the settings
+    heuristics: RwLock<CompiledHeuristics>,
+    // the detection engines which run, as the bits of EngineFlags; read per file so changes apply to a running scan
+    detection_engines: AtomicU32,
+    // the detectors each file is given to in turn: the built in engines, and any registered with register_detector.
+    // Replaced whole when a detector is registered, so each file takes the detectors without copying them, and a
+    // detector which stalls on a file cannot hold up one being registered.
+    detectors: RwLock<Arc<[Arc<dyn Detector>]>>,
+    // the order in which the verdicts of the detectors take precedence, from the settings. Replaced whole when the
+    // settings change, as the disabled databases are.
+    verdict_precedence: RwLock<Arc<[String]>>,
+    // the file names of the JIT engines a process may load, whose writable and executable memory is expected
+    jit_modules: RwLock<Vec<String>>,
+    // paths skipped during a scan, compiled from the exclusion list in the settings
+    exclusions: RwLock<CompiledExclusions>,
+    // paths skipped by default for the current scan only, such as the reserved folders at the root of a volume
+    scan_skips: RwLock<Vec<PathBuf>>,
+    // state - The state of the scanner so we can lock it whilst scanning
+    pub state: Arc<Mutex<FileScannerState>>,
+    // a copy of the state, published whenever it changes, so that reading the state never waits behind anything
+    // holding the state lock, such as a scan starting or ending
+    state_snapshot: watch::Sender<FileScannerState>,
+    // the scans in flight by their ID, each as it was announced when it started, for clients which connect part way
+    // through one. A scan which is not announced, such as a scan of the autoruns, is kept as a scan of the system.
+    scans: Mutex<BTreeMap<u64, ScanStarted>>,
+    pub scanning_info: Arc<Mutex<ScanningLiveInfo>>,
+    // bytes read during the current scan, kept outside of scanning_info so that the hot path doesn't need
+    // to take the lock for every file
+    bytes_scanned: AtomicU64,
+    // bumped as the file being scanned is read, for the watchdog to tell a slow file from a stalled worker
+    scan_heartbeat: AtomicU64,
+    // the large file being hashed by the scan and its size, where there is one, and how much of it has been hashed
+    large_file: Mutex<Option<(PathBuf, u64)>>,
+    large_file_bytes: AtomicU64,
+    // how long the worker of a scan may make no progress before the watchdog replaces it, from the settings
+    stall_timeout_secs: AtomicU64,
+    // monotonic start time of the current scan, used to calculate the duration so that a change to the system
+    // clock mid-scan cannot produce a negative or absurd duration
+    scan_timer: Mutex<Option<Instant>>,
+    // whether a scan is still running; this differs from the state being Scanning as a cancelled scan may still be
+    // winding down, during which a new scan must not be started
+    scan_in_flight: AtomicBool,
+    // whether a scan estimate is running, and whether it has been asked to stop; estimates are independent of scans
+    estimate_in_flight: AtomicBool,
+    estimate_cancelled: AtomicBool,
+    // the ID given to the most recent scan
+    last_scan_id: AtomicU64,
+    // detections and progress are published here for subscribers such as the GUI
+    events: Arc<EventBus>,
+    // each scan is recorded here once it ends
+    history: Arc<ScanHistory>,
+    // detected files are moved here when the scan's action is to quarantine
+    quarantine: Arc<QuarantineStore>,
+    // what is done with files detected by the current scan
+    scan_action: RwLock<DetectionAction>,
+    // thresholds overriding the settings for the current scan
+    scan_thresholds: RwLock<DetectionThresholds>,
+    // whether the current scan skips files which cannot run
+    executables_only: AtomicBool,
+    // whether scans skip folders and files on remote volumes, from the settings
+    skip_remote_volumes: AtomicBool,
+    // whether the current scan walks remote volumes regardless of the settings
+    include_remote_volumes: AtomicBool,
+    // files last changed before this are skipped by the current scan, being its modified_since less the grace window
+    unchanged_before: RwLock<Option<SystemTime>>,
+    // the bytes which matched each detection of the current scan, by file, where the engine which matched captures them
+    detection_details: Mutex<HashMap<PathBuf, DetectionDetails>>,
+    // the results of the current scan, written to disk as they are found where journalling is on in the settings
+    journal: ScanJournal,
+    // notifies the user of the detections of the current scan, coalescing them once there are many
+    notifier: DetectionNotifier,
+    // the slots on-demand requests are served in alongside a scan
+    on_demand: Arc<OnDemandScheduler>,
+    // holds back large files from a scan whilst the system is low on physical memory
+    memory: MemoryThrottle,
+    // open whilst an updater replaces the signatures or binaries, during which no scan is started
+    update: UpdateWindow,
+    // when each loaded driver was first seen, as Windows does not keep when drivers were loaded
+    driver_load_times: DriverLoadTimes,
+    log: Log,
+}
+
+/// An in progress scan of content received as a stream of bytes which never touches the disk. The content is hashed
+/// incrementally as it arrives, so memory use stays flat regardless of the size of the content.
+pub struct StreamingScan {
+    hasher: Md5,
+    len: u64,
+}
+
+impl StreamingScan {
+    /// Feeds the next chunk of content into the scan
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.len += data.len() as u64;
+    }
+
+    /// The number of bytes received so far
+    pub fn bytes_received(&self) -> u64 {
+        self.len
+    }
+}
+
+trait SLI {
+    fn new() -> Self;
+    fn reset(&mut self);
+}
+
+impl SLI for ScanningLiveInfo {
+    fn new() -> Self {
+        ScanningLiveInfo {
+            num_files_scanned: 0,
+            time_taken: Duration::new(0, 0),
+            scan_results: Vec::<MatchedIOC>::new(),
+            detection_count: 0,
+            changed_during_scan: Vec::new(),
+            stalled_files: Vec::new(),
+            roots: Vec::new(),
+            skipped_not_executable: 0,
+            skipped_excluded: 0,
+            skipped_remote: 0,
+            skipped_unchanged: 0,
+            scanned_with_backup_privilege: 0,
+            inaccessible: Vec::new(),
+            inaccessible_count: 0,
+            scanned_on_retry: 0,
+            skipped_in_use: Vec::new(),
+            skipped_in_use_count: 0,
+            memory_throttled: false,
+            memory_pauses: 0,
+            memory_paused_ms: 0,
+            extensions: Vec::new(),
+            cancel_reason: None,
+            scan_id: None,
+            target: None,
+            engines: EngineFlags::NONE,
+            bytes_scanned: 0,
+            thresholds: None,
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            throughput_mb_s: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = ScanningLiveInfo::new();
+    }
+}
+
+impl FileScanner {
+    /// Construct a new instance of the FileScanner, publishing its events to the event bus, recording its scans in
+    /// the history and quarantining detections into the quarantine store.
+    pub async fn new(
+        events: Arc<EventBus>,
+        history: Arc<ScanHistory>,
+        quarantine: Arc<QuarantineStore>,
+    ) -> Result<Self, std::io::Error> {
+        let signatures = SignatureSet::load().await?;
+
+        // a scan the engine stopped part way through is recovered before any new scan can replace its journal
+        recover_interrupted_scan(&history);
+
+        Ok(Self::with_signatures(events, history, quarantine, signatures))
+    }
+
+    /// Constructs the FileScanner matching against the signatures given, rather than those loaded from disk
+    fn with_signatures(
+        events: Arc<EventBus>,
+        history: Arc<ScanHistory>,
+        quarantine: Arc<QuarantineStore>,
+        signatures: SignatureSet,
+    ) -> Self {
+        let log = Log::new();
+        let notifier = DetectionNotifier::new(Arc::clone(&events));
+
+        FileScanner {
+            signatures: RwLock::new(Arc::new(signatures)),
+            disabled_databases: RwLock::new(Arc::from([])),
+            fuzzy_settings: RwLock::new(FuzzyHashSettings::default()),
+            heuristics: RwLock::new(CompiledHeuristics::default()),
+            detection_engines: AtomicU32::new(EngineFlags::default().0),
+            detectors: RwLock::new(builtin_detectors().into()),
+            verdict_precedence: RwLock::new(VerdictSettings::default().precedence.into()),
+            jit_modules: RwLock::new(InjectionSettings::default().jit_modules),
+            exclusions: RwLock::new(CompiledExclusions::default()),
+            scan_skips: RwLock::new(Vec::new()),
+            state: Arc::new(Mutex::new(FileScannerState::default())),
+            state_snapshot: watch::channel(FileScannerState::default()).0,
+            scans: Mutex::new(BTreeMap::new()),
+            scanning_info: Arc::new(Mutex::new(ScanningLiveInfo::new())),
+            bytes_scanned: AtomicU64::new(0),
+            scan_heartbeat: AtomicU64::new(0),
+            large_file: Mutex::new(None),
+            large_file_bytes: AtomicU64::new(0),
+            stall_timeout_secs: AtomicU64::new(ScanWatchdogSettings::default().stall_timeout_secs),
+            scan_timer: Mutex::new(None),
+            scan_in_flight: AtomicBool::new(false),
+            estimate_in_flight: AtomicBool::new(false),
+            estimate_cancelled: AtomicBool::new(false),
+            last_scan_id: AtomicU64::new(0),
+            events,
+            history,
+            quarantine,
+            scan_action: RwLock::new(DetectionAction::default()),
+            scan_thresholds: RwLock::new(DetectionThresholds::default()),
+            executables_only: AtomicBool::new(false),
+            skip_remote_volumes: AtomicBool::new(RemoteVolumeSettings::default().skip_remote_volumes),
+            include_remote_volumes: AtomicBool::new(false),
+            unchanged_before: RwLock::new(None),
+            detection_details: Mutex::new(HashMap::new()),
+            journal: ScanJournal::new(),
+            notifier,
+            on_demand: Arc::new(OnDemandScheduler::new()),
+            memory: MemoryThrottle::new(),
+            update: UpdateWindow::new(),
+            driver_load_times: DriverLoadTimes::new(),
+            log,
+        }
+    }
+
+    /// Moves the scanner into the Scanning state for a new scan, resetting the stats of any previous scan. The skips
+    /// are paths skipped by default for this scan only.
+    ///
+    /// The check for whether a scan is already running and the state change happen under the same lock, so two
+    /// requests racing to start a scan cannot both succeed.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new scan, or None if a scan is already in progress or an update is, giving the reason from
+    /// [`FileScanner::scan_refused`].
+    fn scan_started(&self, skips: Vec<PathBuf>) -> Option<u64> {
+        let mut lock = self.state.lock().unwrap();
+        if *lock == FileScannerState::Scanning || self.scan_in_flight.load(Ordering::SeqCst) || self.update.is_open() {
+            return None;
+        }
+
+        *self.scan_skips.write().unwrap() = skips;
+        *self.scan_action.write().unwrap() = DetectionAction::default();
+        *self.scan_thresholds.write().unwrap() = DetectionThresholds::default();
+        self.executables_only.store(false, Ordering::SeqCst);
+        self.include_remote_volumes.store(false, Ordering::SeqCst);
+        *self.unchanged_before.write().unwrap() = None;
+        self.detection_details.lock().unwrap().clear();
+
+        self.set_state(&mut lock, FileScannerState::Scanning);
+        self.scan_in_flight.store(true, Ordering::SeqCst);
+
+        // IDs are based on the time so they remain unique across restarts of the engine
+        let now_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let scan_id = now_ms.max(self.last_scan_id.load(Ordering::SeqCst) + 1);
+        self.last_scan_id.store(scan_id, Ordering::SeqCst);
+
+        // reset the stats
+        let started_at = Some(SystemTime::now());
+        {
+            let mut sli = self.scanning_info.lock().unwrap();
+            sli.reset();
+            sli.scan_id = Some(scan_id);
+            sli.engines = self.detection_engines();
+            sli.started_at = started_at;
+        }
+        self.journal.begin(scan_id, started_at);
+        self.notifier.begin(scan_id);
+        self.bytes_scanned.store(0, Ordering::Relaxed);
+        *self.scan_timer.lock().unwrap() = Some(Instant::now());
+
+        Some(scan_id)
+    }
+
+    /// Why a scan could not be started
+    pub fn scan_refused(&self) -> String {
+        match self.update.is_open() {
+            true => "The engine is being updated, so no scan can be started until the update ends.".to_string(),
+            false => "A scan is already in progress.".to_string(),
+        }
+    }
+
+    /// Starts a scan of files found on the system rather than given as targets, as [`FileScanner::scan_started`] does,
+    /// keeping it so that it is listed by [`FileScanner::scans_list`] although it is not announced.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new scan, or why it could not be started.
+    fn start_system_scan(&self, kind: &str) -> Result<u64, String> {
+        let scan_id = self
+            .scan_started(Vec::new())
+            .ok_or_else(|| self.scan_refused())?;
+
+        self.track_scan(ScanStarted {
+            scan_id,
+            targets: Vec::new(),
+            mode: ScanType::System {
+                kind: kind.to_string(),
+            },
+            estimated_file_count: None,
+            skipped: Vec::new(),
+            target: None,
+        });
+
+        Ok(scan_id)
+    }
+
+    /// Keeps the scan amongst the scans in flight, in place of what was kept of it before
+    fn track_scan(&self, started: ScanStarted) {
+        self.scanning_info.lock().unwrap().target = started.target.clone();
+        self.scans.lock().unwrap().insert(started.scan_id, started);
+    }
+
+    /// Publishes that the scan has started, keeping it so that a client which connects whilst the scan is running
+    /// can learn of it from [`FileScanner::current_event`].
+    fn announce_scan(&self, started: ScanStarted) -> ScanStarted {
+        self.track_scan(started.clone());
+        self.events.publish(EngineEvent::ScanStarted {
+            started: started.clone(),
+        });
+
+        started
+    }
+
+    /// Gets the event which started the running scan, so that a client which connected or reloaded part way through
+    /// a scan can show it as in progress straight away. The progress of the scan is not included, as it is read from
+    /// the live scan stats.
+    ///
+    /// # Returns
+    ///
+    /// The `ScanStarted` event of the running scan, or None if no scan is running. Scans of the autoruns and rescans
+    /// of detections are not announced, so also give None.
+    pub fn current_event(&self) -> Option<EngineEvent> {
+        if self.get_state() != FileScannerState::Scanning {
+            return None;
+        }
+
+        self.scans
+            .lock()
+            .unwrap()
+            .values()
+            .find(|started| !matches!(started.mode, ScanType::System { .. }))
+            .cloned()
+            .map(|started| EngineEvent::ScanStarted { started })
+    }
+
+    /// Lists the scans in progress with how far each has got, including the scans of files found on the system, such
+    /// as of the autoruns, which are not announced. Only one scan runs at a time, so this holds at most one scan, but
+    /// is a list so that clients need not change if scans are ever run alongside each other.
+    pub fn scans_list(&self) -> Vec<ScanStatus> {
+        let state = self.get_state();
+        if state != FileScannerState::Scanning {
+            return Vec::new();
+        }
+
+        let scans: Vec<ScanStarted> = self.scans.lock().unwrap().values().cloned().collect();
+        let (live_scan_id, num_files_scanned, started_at, roots) = {
+            let sli = self.scanning_info.lock().unwrap();
+            (sli.scan_id, sli.num_files_scanned, sli.started_at, sli.roots.clone())
+        };
+
+        scans
+            .into_iter()
+            .map(|started| {
+                // the live stats are of the scan running now, so a scan they are not of has no progress to give
+                let live = live_scan_id == Some(started.scan_id);
+                let progress = match live {
+                    true => ScanProgress {
+                        num_files_scanned,
+                        bytes_scanned: self.bytes_scanned.load(Ordering::Relaxed),
+                        estimated_file_count: started.estimated_file_count,
+                        current_root: current_root(&roots),
+                        roots: roots.clone(),
+                        current_file: self.file_progress(),
+                    },
+                    false => ScanProgress {
+                        estimated_file_count: started.estimated_file_count,
+                        ..ScanProgress::default()
+                    },
+                };
+
+                ScanStatus {
+                    scan_id: started.scan_id,
+                    mode: started.mode,
+                    targets: started.targets,
+                    target: started.target,
+                    progress,
+                    started_at: started_at.filter(|_| live),
+                    state: state.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Records the finish time, duration and average throughput of the scan which has just ended.
+    fn finalise_scan_stats(&self) {
+        let elapsed = match self.scan_timer.lock().unwrap().take() {
+            Some(timer) => timer.elapsed(),
+            None => return,
+        };
+        let bytes_scanned = self.bytes_scanned.load(Ordering::Relaxed);
+
+        let mut sli = self.scanning_info.lock().unwrap();
+        sli.finished_at = Some(SystemTime::now());
+        sli.duration_ms = Some(elapsed.as_millis());
+        sli.bytes_scanned = bytes_scanned;
+        sli.extensions.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.extension.cmp(&b.extension)));
+
+        let secs = elapsed.as_secs_f64();
+        sli.throughput_mb_s = if secs > 0.0 {
+            Some((bytes_scanned as f64 / 1_000_000.0) / secs)
+        } else {
+            None
+        };
+    }
+
+    /// Updates the internal state to the outcome of the scan which has just ended. The outcome remains the state of
+    /// the scanner until the next scan starts, or the scanner is reset.
+    fn end_scan(&self, outcome: FileScannerState) {
+        // the detections beyond the limit of individual notifications are summarised ahead of the scan finishing
+        self.notifier.end();
+
+        let (scan_id, detections) = {
+            let sli = self.scanning_info.lock().unwrap();
+            (sli.scan_id, sli.scan_results.len())
+        };
+        let message = match &outcome {
+            FileScannerState::Finished => Some(format!("Scan complete, {detections} detection(s).")),
+            FileScannerState::FinishedWithError(e) => {
+                Some(format!("Scan finished with an error. {e}"))
+            }
+            FileScannerState::Cancelled => Some("Scan cancelled.".to_string()),
+            FileScannerState::Scanning | FileScannerState::Inactive => None,
+        };
+
+        {
+            let mut lock = self.state.lock().unwrap();
+            self.set_state(&mut lock, outcome);
+            self.scan_in_flight.store(false, Ordering::SeqCst);
+        }
+        if let Some(scan_id) = scan_id {
+            self.scans.lock().unwrap().remove(&scan_id);
+        }
+
+        if let Some(message) = message {
+            self.events.publish(EngineEvent::Notify {
+                title: "Scan finished".to_string(),
+                message,
+                scan_id,
+            });
+        }
+    }
+
+    /// Records the scan which has just ended in the scan history
+    fn record_history(&self, outcome: &FileScannerState) {
+        let record = {
+            let sli = self.scanning_info.lock().unwrap();
+            let Some(scan_id) = sli.scan_id else {
+                self.journal.end();
+                return;
+            };
+
+            ScanRecord {
+                scan_id,
+                started_at: sli.started_at,
+                finished_at: sli.finished_at,
+                outcome: outcome.name().to_string(),
+                cancel_reason: sli.cancel_reason.clone(),
+                num_files_scanned: sli.num_files_scanned,
+                bytes_scanned: sli.bytes_scanned,
+                detections: sli.scan_results.clone(),
+                changed_during_scan: sli.changed_during_scan.clone(),
+                stalled_files: sli.stalled_files.clone(),
+                skipped_not_executable: sli.skipped_not_executable,
+                skipped_excluded: sli.skipped_excluded,
+                skipped_remote: sli.skipped_remote,
+                skipped_unchanged: sli.skipped_unchanged,
+                scanned_with_backup_privilege: sli.scanned_with_backup_privilege,
+                inaccessible: sli.inaccessible.clone(),
+                inaccessible_count: sli.inaccessible_count,
+                scanned_on_retry: sli.scanned_on_retry,
+                skipped_in_use: sli.skipped_in_use.clone(),
+                skipped_in_use_count: sli.skipped_in_use_count,
+                extensions: sli.extensions.clone(),
+                thresholds: sli.thresholds,
+                annotations: HashMap::new(),
+                roots: sli.roots.iter().map(|r| r.root.clone()).collect(),
+                target: sli.target.clone(),
+            }
+        };
+
+        // the journal is only removed once the scan is in the history, so a scan which could not be recorded is
+        // recovered from its journal when the engine next starts
+        match self.history.record(record) {
+            Ok(_) => self.journal.end(),
+            Err(e) => self
+                .log
+                .log(LogLevel::Error, &format!("Could not record scan in history. {e}")),
+        }
+    }
+
+    /// Publishes the progress of the running scan to subscribers
+    fn publish_progress(&self) {
+        let (scan_id, num_files_scanned, current_root) = {
+            let lock = self.scanning_info.lock().unwrap();
+            (lock.scan_id, lock.num_files_scanned, current_root(&lock.roots))
+        };
+
+        if let Some(scan_id) = scan_id {
+            self.events.publish(EngineEvent::Progress {
+                scan_id,
+                num_files_scanned,
+                bytes_scanned: self.bytes_scanned.load(Ordering::Relaxed),
+                current_root,
+                current_file: self.file_progress(),
+            });
+        }
+
+        self.journal.checkpoint(|| {
+            let sli = self.scanning_info.lock().unwrap();
+            JournalCounters {
+                num_files_scanned: sli.num_files_scanned,
+                bytes_scanned: self.bytes_scanned.load(Ordering::Relaxed),
+                skipped_excluded: sli.skipped_excluded,
+                skipped_not_executable: sli.skipped_not_executable,
+                skipped_remote: sli.skipped_remote,
+                skipped_unchanged: sli.skipped_unchanged,
+            }
+        });
+    }
+
+    /// How far the scan has got through hashing the file it is on, where the file is large enough to report it
+    fn file_progress(&self) -> Option<FileProgress> {
+        let large_file = self.large_file.lock().unwrap();
+
+        large_file.as_ref().map(|(path, total_bytes)| FileProgress {
+            path: path.clone(),
+            bytes_hashed: self.large_file_bytes.load(Ordering::Relaxed),
+            total_bytes: *total_bytes,
+        })
+    }
+
+    /// Records the verdict for a file against the running scan, taking the scan's action on any detection and
+    /// publishing it to subscribers
+    fn record_verdict(&self, verdict: FileVerdict) {
+        match verdict {
+            FileVerdict::Clean => (),
+            FileVerdict::Malicious(mut ioc) => {
+                // a heuristic detection is only a suspicion, as is a shortcut whose target was not detected, so is
+                // reported without acting on the file
+                let action = match ioc.match_mode {
+                    MatchMode::Heuristic { .. } | MatchMode::Shortcut { target_detection: None, .. } => {
+                        DetectionAction::Report
+                    }
+                    _ => *self.scan_action.read().unwrap(),
+                };
+                ioc.action_taken = take_action(action, &ioc, &self.quarantine);
+                if action != DetectionAction::Report {
+                    self.log.log(
+                        LogLevel::Info,
+                        &format!("Action on detection {}: {:?}", ioc.file.display(), ioc.action_taken),
+                    );
+                }
+
+                let scan_id = {
+                    let mut lock = self.scanning_info.lock().unwrap();
+                    lock.scan_results.push(ioc.clone());
+                    lock.scan_id
+                };
+
+                self.journal.detection(&ioc);
+                self.notifier.detection(&ioc);
+                self.events.publish(EngineEvent::Detection { scan_id, ioc });
+            }
+            FileVerdict::ChangedDuringScan(path) => {
+                self.journal.changed_during_scan(&path);
+                self.scanning_info.lock().unwrap().changed_during_scan.push(path);
+            }
+        }
+    }
+
+    /// Scan a file against the sets of known bad hashes, verifying that the file did not change whilst it was being
+    /// scanned.
+    ///
+    /// The file is fingerprinted (size and timestamps) when it is opened, and the path is fingerprinted again once it
+    /// has been scanned. If the two differ, the file was modified or replaced part way through, so what was hashed may
+    /// not be what is now on disk; the file is rescanned once, and if it changes again it is reported as such rather
+    /// than as clean.
+    ///
+    /// A file the engine is denied is opened with its backup privilege, and counted against the scan as only scanned
+    /// because of it.
+    ///
+    /// # Returns
+    ///
+    /// The function will return Ok if there were no IO errors, containing the verdict for the file. A detection is
+    /// always reported as malicious, even if the file has since changed, as the content which matched was on disk.
+    fn scan_file_against_hashes(
+        &self,
+        target: &PathBuf,
+        files_scanned: &Arc<Mutex<u32>>,
+        scratch: &mut [u8],
+    ) -> Result<FileVerdict, std::io::Error> {
+        let mut rescanned = false;
+        let mut with_privilege = false;
+
+        let verdict = loop {
+            self.scan_heartbeat.fetch_add(1, Ordering::Relaxed);
+            let (file, privileged) = open_for_read(target)?;
+            with_privilege |= privileged;
+            let opened = FileFingerprint::of(&file.metadata()?);
+
+            if let Some(ioc) = self.match_file_against_hashes(target, &file, scratch)? {
+                break FileVerdict::Malicious(ioc);
+            }
+
+            let unchanged = fs::metadata(target)
+                .map(|m| FileFingerprint::of(&m) == opened)
+                .unwrap_or(false);
+
+            if unchanged || self.is_cancelled() {
+                break FileVerdict::Clean;
+            }
+
+            if rescanned {
+                self.log.log(
+                    LogLevel::Warning,
+                    &format!("File changed during scan, and again when rescanned: {}", target.display()),
+                );
+                break FileVerdict::ChangedDuringScan(target.clone());
+            }
+
+            self.log.log(
+                LogLevel::Debug,
+                &format!("File changed during scan, rescanning: {}", target.display()),
+            );
+            rescanned = true;
+        };
+
+        if with_privilege {
+            self.scanning_info.lock().unwrap().scanned_with_backup_privilege += 1;
+        }
+
+        // increment the number of files scanned
+        {
+            let mut files_scanned = files_scanned.lock().unwrap();
+            *files_scanned += 1;
+        }
+
+        Ok(verdict)
+    }
+
+    /// Matches an open file against each of the detectors in turn, the detector of highest precedence in the verdict
+    /// policy to detect it deciding the match. The regions of the file which matched are kept for the detection
+    /// details.
+    ///
+    /// # Returns
+    ///
+    /// The function will return Ok if there were no IO errors, and the result of the Ok will be an Option of type
+    /// MatchedIOC. If the function returns None, then there was no hash match made for malware.
+    ///
+    /// If it returns the Some variant, the matched IOC will be returned for post-processing and decision making, containing
+    /// the matched hash, the file name / path as PathBuf, whether the whole file or a PE section matched, and the
+    /// verdicts of every detector it was resolved from.
+    fn match_file_against_hashes(
+        &self,
+        target: &Path,
+        file: &File,
+        scratch: &mut [u8],
+    ) -> Result<Option<MatchedIOC>, std::io::Error> {
+        let Some(FileInspection { detection: Some((ioc, regions)), .. }) =
+            self.inspect_file(target, file, scratch, true)?
+        else {
+            // No malware found
+            return Ok(None);
+        };
+
+        if !regions.is_empty() {
+            self.detection_details.lock().unwrap().insert(
+                target.to_path_buf(),
+                DetectionDetails {
+                    file: target.to_path_buf(),
+                    regions,
+                    annotation: None,
+                },
+            );
+        }
+
+        Ok(Some(ioc))
+    }
+
+    /// Runs each of the detectors over an open file, resolving their detections to the one reported.
+    ///
+    /// # Args
+    ///
+    /// - `in_scan`: Whether the file is part of the running scan, so counts towards its progress, is cancelled with it
+    ///   and is matched against its thresholds. A file inspected outside of a scan touches none of the scan's state.
+    ///
+    /// # Returns
+    ///
+    /// What was found, or None where the scan was cancelled part way through the file.
+    fn inspect_file(
+        &self,
+        target: &Path,
+        file: &File,
+        scratch: &mut [u8],
+        in_scan: bool,
+    ) -> Result<Option<FileInspection>, std::io::Error> {
+        //
+        // In order to not read the whole file into memory (would be bad if the file size is > the amount of RAM available)
+        // I've decided to loop over an array of 1024 bytes at at time until the end of the file, and use the hashing crate sha2
+        // to update the hash values, this should produce the hash without requiring the whole file read into memory.
+        //
+
+        let engines = self.detection_engines();
+        let signatures = self.signatures();
+        let disabled = self.disabled_databases();
+        let cancelled = || in_scan && self.is_cancelled();
+
+        //
+        // Most files are clean, so the hash is formatted on the stack and only turned into a String once a file is
+        // detected. The file is read directly into the scratch buffer, which is reused for every file in the scan.
+        //
+        let (hash, sha256_hash) = if engines.contains(EngineFlags::FILE_HASH) {
+            // SHA256 is only computed where there are SHA256 signatures to match, such as those imported from a feed
+            let mut hasher =
+                FileHasher::new(signatures.enabled(&disabled).any(|db| !db.sha256_iocs.is_empty()));
+            let mut reader = file;
+
+            let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let large_file = (in_scan && total_bytes >= LARGE_FILE_PROGRESS_BYTES)
+                .then(|| LargeFileProgress::start(self, target, total_bytes));
+
+            //
+            // ingest the file and update hash value per chunk
+            //
+            loop {
+                //
+                // This is a sensible place to check whether the user has cancelled the scan, anything before this is likely
+                // too short a time period to have the user stop the scan.
+                //
+                if cancelled() {
+                    return Ok(None);
+                }
+
+                let count = reader.read(scratch)?;
+                if count == 0 {
+                    break;
+                }
+                hasher.update(&scratch[..count]);
+                if in_scan {
+                    self.scan_heartbeat.fetch_add(1, Ordering::Relaxed);
+                }
+                if large_file.is_some() {
+                    self.large_file_bytes.fetch_add(count as u64, Ordering::Relaxed);
+                }
+            }
+            drop(large_file);
+
+            if in_scan {
+                self.bytes_scanned.fetch_add(hasher.len, Ordering::Relaxed);
+            }
+
+            let (md5, sha256) = hasher.finish();
+            (Some(md5), sha256)
+        } else {
+            (None, None)
+        };
+
+        let ctx = FileContext {
+            path: target,
+            file,
+            md5: hash.as_ref().map(|h| h.as_str()),
+            sha256: sha256_hash.as_ref().map(|h| h.as_str()),
+            scanner: self,
+            signatures: &signatures,
+            disabled: &disabled,
+            engines,
+            in_scan,
+        };
+
+        let detectors = self.detectors();
+        let precedence = self.verdict_precedence();
+
+        // every detector gives its verdict, so that the detection reported can be explained; only the detections are
+        // kept, as most files are clean
+        let mut detections: Vec<(usize, Detection)> = Vec::new();
+        for (index, detector) in detectors.iter().enumerate() {
+            if cancelled() {
+                return Ok(None);
+            }
+
+            if let Some(detection) = detector.inspect(&ctx)? {
+                detections.push((index, detection));
+            }
+        }
+
+        // the detection reported is that of the detector of highest precedence, the first to run where they rank alike
+        let resolved =
+            (0..detections.len()).min_by_key(|&i| verdict_rank(&precedence, detectors[detections[i].0].name()));
+
+        // a clean file in a scan is not given its verdicts, as most files are clean and they are not reported
+        if resolved.is_none() && in_scan {
+            return Ok(Some(FileInspection {
+                md5: hash,
+                sha256: sha256_hash,
+                verdicts: Vec::new(),
+                detection: None,
+            }));
+        }
+
+        let verdicts: Vec<EngineVerdict> = detectors
+            .iter()
+            .enumerate()
+            .map(|(index, detector)| {
+                let detection = detections.iter().find(|(i, _)| *i == index).map(|(_, detection)| detection);
+                EngineVerdict {
+                    engine: detector.name().to_string(),
+                    match_mode: detection.map(|d| d.match_mode.clone()),
+                    source: detection.and_then(|d| d.source.clone()),
+                }
+            })
+            .collect();
+
+        let detection = resolved.map(|resolved| {
+            let (index, detection) = detections.swap_remove(resolved);
+            let ioc = MatchedIOC {
+                hash: detection.hash,
+                file: target.to_path_buf(),
+                match_mode: detection.match_mode,
+                action_taken: ActionResult::None,
+                source: detection.source,
+                verdicts: verdicts.clone(),
+                resolved_by: Some(detectors[index].name().to_string()),
+                conditions: detection.conditions,
+            };
+            (ioc, detection.regions)
+        });
+
+        Ok(Some(FileInspection {
+            md5: hash,
+            sha256: sha256_hash,
+            verdicts,
+            detection,
+        }))
+    }
+
+    /// Computes the fuzzy hash of the file and compares it against the fuzzy hash signatures, if fuzzy hashing is
+    /// enabled in the settings. The similarity threshold is that of the scan, where the file is part of a scan which
+    /// overrides it.
+    ///
+    /// # Returns
+    ///
+    /// - Ok(Some) containing the most similar signature, its similarity, and the name of the database it is in, where
+    ///   it met the threshold
+    /// - Ok(None) if fuzzy hashing is disabled, the file is too large to fuzzy hash, or nothing was similar enough
+    pub(super) fn scan_file_against_fuzzy_hashes(
+        &self,
+        mut file: &File,
+        signatures: &SignatureSet,
+        disabled: &[String],
+        in_scan: bool,
+    ) -> Result<Option<(String, u32, String)>, std::io::Error> {
+        let settings = self.fuzzy_settings.read().unwrap().clone();
+        if !settings.enabled || signatures.enabled(disabled).all(|db| db.fuzzy_iocs.is_empty()) {
+            return Ok(None);
+        }
+
+        let len = file.metadata()?.file_size();
+        if len > FUZZY_MAX_FILE_SIZE {
+            return Ok(None);
+        }
+
+        let mut data = Vec::with_capacity(len as usize);
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut data)?;
+
+        let hash = FuzzyHash::hash(&data);
+        let overrides = match in_scan {
+            true => *self.scan_thresholds.read().unwrap(),
+            false => DetectionThresholds::default(),
+        };
+        let threshold = overrides.fuzzy_similarity.unwrap_or(settings.similarity_threshold);
+
+        // the most similar signature across the databases; on a tie, the first database matched in wins
+        let mut best: Option<(String, u32, String)> = None;
+        for db in signatures.enabled(disabled) {
+            if let Some((signature, similarity)) = db.fuzzy_iocs.best_match(&hash, threshold)
+                && best.as_ref().is_none_or(|(_, best_similarity, _)| similarity > *best_similarity)
+            {
+                best = Some((signature.to_string(), similarity, db.name.clone()));
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Explains why fuzzy hashing did not detect the file, giving the similarity of the closest signature against the
+    /// threshold in the settings
+    pub(super) fn explain_fuzzy_hash(
+        &self,
+        mut file: &File,
+        signatures: &SignatureSet,
+        disabled: &[String],
+    ) -> io::Result<NotDetectedReason> {
+        let settings = self.fuzzy_settings.read().unwrap().clone();
+        if !settings.enabled {
+            return Ok(NotDetectedReason::DisabledInSettings);
+        }
+        if signatures.enabled(disabled).all(|db| db.fuzzy_iocs.is_empty()) {
+            return Ok(NotDetectedReason::NoSignatures);
+        }
+
+        let len = file.metadata()?.file_size();
+        if len > FUZZY_MAX_FILE_SIZE {
+            return Ok(NotDetectedReason::TooLargeToFuzzyHash {
+                len,
+                max: FUZZY_MAX_FILE_SIZE,
+            });
+        }
+
+        let mut data = Vec::with_capacity(len as usize);
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut data)?;
+
+        let hash = FuzzyHash::hash(&data);
+        let closest = signatures
+            .enabled(disabled)
+            .filter_map(|db| db.fuzzy_iocs.best_match(&hash, 0).map(|(_, similarity)| similarity))
+            .max();
+
+        Ok(NotDetectedReason::BelowSimilarity {
+            closest,
+            threshold: settings.similarity_threshold,
+        })
+    }
+
+    /// Runs the detectors over the file a shortcut points to, so that the shortcut can be detected for what it runs.
+    /// The target is not recorded or acted on in its own right; the shortcut is reported with what was found in it.
+    /// Where the shortcut is part of a scan, so is the target, counting towards the scan and being cancelled with it.
+    ///
+    /// # Returns
+    ///
+    /// The detection of the target, or None where it is clean, excluded, could not be read, or is itself a shortcut,
+    /// which is not followed so that shortcuts pointing at each other cannot loop.
+    pub(super) fn inspect_shortcut_target(&self, target: &Path, in_scan: bool) -> Option<MatchedIOC> {
+        if self.is_excluded(target) || classify(target).ok()? == FileKind::Shortcut {
+            return None;
+        }
+
+        let inspection = open_for_read(target).and_then(|(file, _)| {
+            let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+            self.inspect_file(target, &file, &mut scratch, in_scan)
+        });
+
+        match inspection {
+            Ok(inspection) => inspection?.detection.map(|(ioc, _)| ioc),
+            Err(e) => {
+                self.log.log(
+                    LogLevel::Debug,
+                    &format!("Could not scan the target of a shortcut, {}. {e}", target.display()),
+                );
+                None
+            }
+        }
+    }
+
+    /// Hashes the raw data of each section of a PE and checks it against the known bad section hashes.
+    ///
+    /// # Returns
+    ///
+    /// - Ok(Some) containing the matched section hash, the name of the section, the start of the section's bytes, and
+    ///   the name of the database which matched
+    /// - Ok(None) if the file is not a PE, or no section matched
+    pub(super) fn scan_sections_against_hashes(
+        &self,
+        file: &File,
+        signatures: &SignatureSet,
+        disabled: &[String],
+        in_scan: bool,
+    ) -> Result<Option<(String, String, MatchedBytes, String)>, std::io::Error> {
+        let mut reader = BufReader::new(file);
+
+        let headers = match PeHeaders::parse(&mut reader)? {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+
+        for section in headers.sections {
+            if in_scan {
+                if self.is_cancelled() {
+                    return Ok(None);
+                }
+                self.scan_heartbeat.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let (offset, len) = match section.raw_data_range(headers.file_len) {
+                Some(range) => range,
+                None => continue,
+            };
+
+            reader.seek(SeekFrom::Start(offset))?;
+            let mut hasher = Md5::new();
+            io::copy(&mut (&mut reader).take(len), &mut hasher)?;
+            let section_hash = HexDigest::of(&hasher.finalize());
+
+            if let Some(db) = signatures
+                .enabled(disabled)
+                .find(|db| db.section_iocs.contains(section_hash.as_str()))
+            {
+                let label = format!("PE section {}", section.name);
+                let matched = context::capture(&mut reader, label, offset, len)?;
+                return Ok(Some((section_hash.as_str().to_string(), section.name, matched, db.name.clone())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Public API entry point, scans from a root folder including all children, this can be used on a small
+    /// scale for a folder scan, or used to initiate a system scan.
+    ///
+    /// Files are scanned on a worker thread, watched by this thread as it walks the folders. Where the worker makes no
+    /// progress on a file for the stall timeout in the settings, the file is recorded as stalled and the scan carries
+    /// on with a new worker.
+    pub fn begin_scan(self: &Arc<Self>, input_dirs: Vec<PathBuf>) -> Result<FileScannerState, io::Error> {
+        // counted on this thread and merged once the scan finishes, rather than locking the scan info for every file
+        let mut extensions = ExtensionCounts::default();
+        let result = self.walk_targets(input_dirs, &mut extensions);
+        self.merge_extensions(extensions);
+
+        result
+    }
+
+    /// Scans the targets of [`FileScanner::begin_scan`], counting each file scanned against its extension
+    fn walk_targets(
+        self: &Arc<Self>,
+        input_dirs: Vec<PathBuf>,
+        extensions: &mut ExtensionCounts,
+    ) -> Result<FileScannerState, io::Error> {
+        let mut discovered_dirs: Vec<PathBuf> = Vec::new();
+
+        // If the target is a directory, then add it back to the discovered dirs as that will be iterated
+        // separate to the target - target is just used for scanning a single file.
+        // This could be refactored at a later date so this check is done more inline below whilst still adhering
+        // to how the functionality works.
+        let mut target = PathBuf::new();
+        if input_dirs.len() == 1 {
+            target = input_dirs.clone().pop().unwrap();
+            discovered_dirs.push(target.clone());
+        } else {
+            for t in input_dirs {
+                if t.exists() && t.is_dir() && !self.is_excluded(&t) {
+                    discovered_dirs.push(t.clone());
+                    target = t;
+                }
+            }
+        }
+
+        let stop_clock = Arc::new(Mutex::new(false));
+        let clock_clone = Arc::clone(&stop_clock);
+        let self_scanning_info_clone = Arc::clone(&self.scanning_info);
+
+        let files_scanned: Arc<Mutex<u32>> = Arc::new(Mutex::new(0));
+        let files_scanned_clone = Arc::clone(&files_scanned);
+        let files_scanned_for_scanner = Arc::clone(&files_scanned);
+
+        // timer in its own green thread
+        thread::spawn(move || {
+            let timer = Instant::now();
+
+            loop {
+                // first check if the task is cancelled
+                if *clock_clone.lock().unwrap() == true {
+                    break;
+                }
+
+                // not cancelled, so get the elapsed time
+                let elapsed = timer.elapsed();
+                let delta_files_scanned = {
+                    let mut files_scanned_lock = files_scanned_clone.lock().unwrap();
+                    let r = *files_scanned_lock; // get the result value
+                    *files_scanned_lock = 0; // reset to 0
+                    r
+                };
+                {
+                    let mut lock = self_scanning_info_clone.lock().unwrap();
+                    lock.time_taken = elapsed;
+                    lock.num_files_scanned = lock.num_files_scanned + delta_files_scanned as u128;
+                }
+
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let mut worker = ScanWorker::spawn(self, &files_scanned_for_scanner);
+
+        // if the target is a FILE, then scan only the 1 file
+        if !target.is_dir() {
+            if self.is_excluded(&target)
+                || self.skip_remote(&target)
+                || !self.should_scan_changed(fs::metadata(&target))
+                || !self.should_scan_type(&target)
+            {
+                *stop_clock.lock().unwrap() = true;
+                return Ok(FileScannerState::Finished);
+            }
+
+            let res = self.scan_on_worker(&mut worker, &target, &files_scanned_for_scanner);
+            match res {
+                Ok(verdict) => {
+                    self.record_verdict(verdict);
+                    extensions.record(&target, fs::metadata(&target).map(|m| m.len()).unwrap_or(0));
+                    *stop_clock.lock().unwrap() = true;
+                    return Ok(FileScannerState::Finished);
+                }
+                Err(e) if is_in_use(&e) => {
+                    let finished = self.retry_in_use(&mut worker, vec![target.clone()], &files_scanned_for_scanner, extensions);
+                    *stop_clock.lock().unwrap() = true;
+                    return Ok(if finished { FileScannerState::Finished } else { FileScannerState::Cancelled });
+                }
+                Err(e) => {
+                    *stop_clock.lock().unwrap() = true;
+
+                    if e.kind() == io::ErrorKind::Uncategorized {
+                        // results will be empty here
+                        return Ok(FileScannerState::Cancelled);
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        // otherwise, we are a directory so start this off
+        let roots: Vec<PathBuf> = discovered_dirs.into_iter().filter(|root| !self.skip_remote(root)).collect();
+        let mut last_progress = Instant::now();
+        self.scanning_info.lock().unwrap().roots = roots
+            .iter()
+            .map(|root| RootProgress {
+                root: root.clone(),
+                state: RootState::Pending,
+                num_files_scanned: 0,
+            })
+            .collect();
+        self.journal.roots(&roots);
+
+        // files another process holds open are put aside, to be retried once everything else has been scanned
+        let mut in_use: Vec<PathBuf> = Vec::new();
+
+        // each root is walked in full before the next, so that progress can be given per root
+        for (index, root) in roots.iter().enumerate() {
+            self.update_root(index, RootState::Scanning, 0);
+            let mut discovered_dirs = vec![root.clone()];
+            let mut root_files_scanned: u64 = 0;
+
+            while !discovered_dirs.is_empty() {
+                // pop a directory
+                let target = discovered_dirs.pop();
+                if target.is_none() {
+                    continue;
+                }
+
+                // attempt to read the directory, if we don't have permission even with the backup privilege, record
+                // it as inaccessible and continue to next item.
+                let target = target.unwrap();
+                let read_dir = read_dir(&target);
+                if let Err(e) = &read_dir {
+                    // unless the target itself has gone, such as removable media being pulled out, in which case the
+                    // rest of the walk would fail and the scan would wrongly look complete
+                    if let Some(e) = unavailable_target(&roots) {
+                        *stop_clock.lock().unwrap() = true;
+                        return Err(e);
+                    }
+                    if e.kind() == io::ErrorKind::PermissionDenied {
+                        self.record_inaccessible(&target);
+                    }
+                    continue;
+                }
+
+                for entry in read_dir.unwrap() {
+                    let entry = match entry {
+                        Ok(b) => b,
+                        Err(e) => {
+                            self.log
+                                .log(LogLevel::Warning, &format!("[-] Error with entry, e: {e}"));
+                            continue;
+                        }
+                    };
+
+                    // check whether the scan is cancelled
+                    if self.is_cancelled() {
+                        // todo update the error type of this fn to something more flexible
+                        *stop_clock.lock().unwrap() = true;
+                        return Err(io::Error::new(
+                            io::ErrorKind::Uncategorized,
+                            "User cancelled scan.",
+                        ));
+                    }
+
+                    let path = entry.path();
+
+                    // excluded folders are not walked, so nothing beneath them is scanned
+                    if self.is_excluded(&path) {
+                        if entry.file_type().is_ok_and(|t| !t.is_dir()) {
+                            self.scanning_info.lock().unwrap().skipped_excluded += 1;
+                        }
+                        continue;
+                    }
+
+                    // todo some profiling here to see where the slowdowns are and if it can be improved
+                    // i suspect large file size ingests is causing the difference in speed as it reads it
+                    // into a buffer.
+
+                    // a link can lead the walk onto a network share, which is skipped as a root on one would be
+                    if entry.file_type().is_ok_and(|t| t.is_symlink())
+                        && path.is_dir()
+                        && fs::canonicalize(&path).is_ok_and(|resolved| self.skip_remote(&resolved))
+                    {
+                        continue;
+                    }
+
+                    // add the folder to the next iteration
+                    if path.is_dir() {
+                        discovered_dirs.push(path);
+                        continue; // keep searching for a file
+                    }
+
+                    // the time comes with the walk, so is checked before the type, which means reading the file
+                    if !self.should_scan_changed(entry.metadata()) || !self.should_scan_type(&path) {
+                        continue;
+                    }
+
+                    //
+                    // Check the file against the hashes, we are only interested in positive matches at this stage
+                    //
+                    match self.scan_on_worker(&mut worker, &path, &files_scanned_for_scanner) {
+                        Ok(verdict) => {
+                            self.record_verdict(verdict);
+                            extensions.record(&path, entry.metadata().map(|m| m.len()).unwrap_or(0));
+                            root_files_scanned += 1;
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => self.record_inaccessible(&path),
+                        Err(e) if is_in_use(&e) && in_use.len() < MAX_IN_USE_DEFERRED => in_use.push(path),
+                        Err(e) if is_in_use(&e) => self.record_in_use(&path),
+                        Err(e) => self
+                            .log
+                            .log(LogLevel::Warning, &format!("[-] Error scanning: {e}")),
+                    }
+
+                    if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                        self.update_root(index, RootState::Scanning, root_files_scanned);
+                        self.publish_progress();
+                        last_progress = Instant::now();
+                    }
+                }
+            }
+
+            self.update_root(index, RootState::Finished, root_files_scanned);
+        }
+
+        if !self.retry_in_use(&mut worker, in_use, &files_scanned_for_scanner, extensions) {
+            *stop_clock.lock().unwrap() = true;
+            return Err(io::Error::new(io::ErrorKind::Uncategorized, "User cancelled scan."));
+        }
+
+        *stop_clock.lock().unwrap() = true;
+
+        // the files of the last folders read may have failed to open as the target went away
+        if let Some(e) = unavailable_target(&roots) {
+            return Err(e);
+        }
+
+        Ok(FileScannerState::Finished)
+    }
+
+    /// Records a file or folder the scan was denied even with the backup privilege, so could not scan
+    fn record_inaccessible(&self, path: &Path) {
+        self.log.log(LogLevel::Warning, &format!("Access denied, even with the backup privilege: {}", path.display()));
+
+        let mut lock = self.scanning_info.lock().unwrap();
+        lock.inaccessible_count += 1;
+        if lock.inaccessible.len() < MAX_INACCESSIBLE_LISTED {
+            lock.inaccessible.push(path.to_path_buf());
+        }
+    }
+
+    /// Scans again the files another process held open when the scan reached them, backing off before each attempt
+    /// to give the process time to let go of them. Those still held open after the last attempt are recorded as
+    /// skipped. Files scanned on a retry count towards the scan as a whole, but not towards any one root.
+    ///
+    /// # Returns
+    ///
+    /// Whether the retries were finished, or false where the scan was cancelled whilst retrying.
+    fn retry_in_use(
+        self: &Arc<Self>,
+        worker: &mut ScanWorker,
+        mut in_use: Vec<PathBuf>,
+        files_scanned: &Arc<Mutex<u32>>,
+        extensions: &mut ExtensionCounts,
+    ) -> bool {
+        let mut backoff = IN_USE_RETRY_BACKOFF;
+
+        for _ in 0..IN_USE_RETRY_ATTEMPTS {
+            if in_use.is_empty() {
+                break;
+            }
+
+            // waited out in short steps, so that cancelling the scan is not held up by the backoff
+            let waited = Instant::now();
+            while waited.elapsed() < backoff {
+                if self.is_cancelled() {
+                    return false;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            backoff *= 2;
+
+            let mut still_in_use = Vec::new();
+            for path in in_use {
+                if self.is_cancelled() {
+                    return false;
+                }
+
+                match self.scan_on_worker(worker, &path, files_scanned) {
+                    Ok(verdict) => {
+                        self.record_verdict(verdict);
+                        extensions.record(&path, fs::metadata(&path).map(|m| m.len()).unwrap_or(0));
+                        self.scanning_info.lock().unwrap().scanned_on_retry += 1;
+                    }
+                    Err(e) if is_in_use(&e) => still_in_use.push(path),
+                    Err(e) if e.kind() == io::ErrorKind::PermissionDenied => self.record_inaccessible(&path),
+                    Err(e) => self.log.log(LogLevel::Warning, &format!("[-] Error scanning: {e}")),
+                }
+            }
+            in_use = still_in_use;
+        }
+
+        for path in &in_use {
+            self.record_in_use(path);
+        }
+
+        true
+    }
+
+    /// Records a file another process held open, so the scan skipped
+    fn record_in_use(&self, path: &Path) {
+        self.log.log(LogLevel::Warning, &format!("Skipped, as it is in use by another process: {}", path.display()));
+
+        let mut lock = self.scanning_info.lock().unwrap();
+        lock.skipped_in_use_count += 1;
+        if lock.skipped_in_use.len() < MAX_IN_USE_LISTED {
+            lock.skipped_in_use.push(path.to_path_buf());
+        }
+    }
+
+    /// Records how far the scan has got through one of its roots
+    fn update_root(&self, index: usize, state: RootState, num_files_scanned: u64) {
+        if let Some(root) = self.scanning_info.lock().unwrap().roots.get_mut(index) {
+            root.state = state;
+            root.num_files_scanned = num_files_scanned;
+        }
+    }
+
+    /// Scans the file on the worker, replacing the worker where it stalls on the file or has exited. A stalled file is
+    /// recorded against the scan, and gives an error as the file could not be scanned.
+    fn scan_on_worker(
+        self: &Arc<Self>,
+        worker: &mut ScanWorker,
+        path: &Path,
+        files_scanned: &Arc<Mutex<u32>>,
+    ) -> Result<FileVerdict, io::Error> {
+        self.wait_for_memory(path);
+        let stall_timeout = Duration::from_secs(self.stall_timeout_secs.load(Ordering::Relaxed));
+
+        match worker.scan(self, path, stall_timeout) {
+            WorkerOutcome::Scanned(verdict) => verdict,
+            WorkerOutcome::Stalled(stalled_for) => {
+                self.log.log(
+                    LogLevel::Warning,
+                    &format!(
+                        "Scan worker made no progress on {} for {}s, marking the file as failed and restarting the \
+                        worker.",
+                        path.display(),
+                        stalled_for.as_secs()
+                    ),
+                );
+                let stalled = StalledFile {
+                    file: path.to_path_buf(),
+                    stalled_for,
+                };
+                self.journal.stalled(&stalled);
+                self.scanning_info.lock().unwrap().stalled_files.push(stalled);
+
+                // the stalled thread cannot be stopped, so it is left to exit by itself should the file ever return
+                *worker = ScanWorker::spawn(self, files_scanned);
+
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("Scanning {} stalled, so the file was not scanned.", path.display()),
+                ))
+            }
+            WorkerOutcome::Exited => {
+                self.log.log(
+                    LogLevel::Error,
+                    &format!("Scan worker exited whilst scanning {}, restarting the worker.", path.display()),
+                );
+                *worker = ScanWorker::spawn(self, files_scanned);
+
+                Err(io::Error::other(format!(
+                    "The scan worker exited whilst scanning {}.",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    /// Holds back a large file whilst the system is low on physical memory, so that reading it does not push the system
+    /// into paging, until memory recovers, the scan is cancelled, or the longest pause in the settings has passed.
+    /// The pause is counted against the scan, and the file is scanned afterwards regardless.
+    fn wait_for_memory(&self, path: &Path) {
+        if !self.memory.under_pressure() {
+            return;
+        }
+
+        // a file which cannot be read is left for the worker to report as such
+        let Ok(len) = fs::metadata(path).map(|metadata| metadata.len()) else {
+            return;
+        };
+        if !self.memory.is_large(len) {
+            return;
+        }
+
+        self.log.log(
+            LogLevel::Info,
+            &format!("Available memory is low, holding back {} until it recovers.", path.display()),
+        );
+        self.scanning_info.lock().unwrap().memory_throttled = true;
+
+        let paused = Instant::now();
+        let max_pause = self.memory.max_pause();
+        while self.memory.under_pressure() && !self.is_cancelled() && paused.elapsed() < max_pause {
+            thread::sleep(MEMORY_POLL_INTERVAL);
+        }
+
+        let mut sli = self.scanning_info.lock().unwrap();
+        sli.memory_throttled = false;
+        sli.memory_pauses += 1;
+        sli.memory_paused_ms += paused.elapsed().as_millis() as u64;
+    }
+
+    /// Public entrypoint for scanning, which moves the scanner into the Scanning state for the targets. The scan itself
+    /// is then run with [`FileScanner::run_scan`], which the caller should do off of the async runtime as it blocks
+    /// until the scan completes.
+    ///
+    /// This is split in two so that the caller can report the scan as started (with its ID) straight away, rather
+    /// than only hearing about the scan once it has finished.
+    ///
+    /// # Returns
+    ///
+    /// Information about the started scan, or None if a scan is already in progress.
+    pub fn prepare_scan(&self, targets: &[PathBuf]) -> Option<ScanStarted> {
+        let scan_id = self.scan_started(Vec::new())?;
+
+        Some(self.announce_scan(ScanStarted {
+            scan_id,
+            targets: targets.to_vec(),
+            mode: scan_type_of(targets),
+            estimated_file_count: None,
+            skipped: Vec::new(),
+            target: Some(scan_target_of(targets)),
+        }))
+    }
+
+    /// Prepares a scan of the files and folders which first estimates its size, as [`FileScanner::prepare_scan`] does
+    /// for a scan without an estimate. The scan is run, estimate first, with [`FileScanner::run_scan_with_estimate`]
+    /// on the returned `ScanStarted`.
+    ///
+    /// The scan is in the Scanning state from the moment this returns, so can be cancelled by its ID whilst it is still
+    /// estimating. A `ScanEstimating` event is published now, and the `ScanStarted` event once the estimate is made.
+    ///
+    /// # Returns
+    ///
+    /// Information about the started scan, which has no estimate yet, or None if a scan is already in progress.
+    pub fn prepare_scan_with_estimate(&self, targets: &[PathBuf]) -> Option<ScanStarted> {
+        let scan_id = self.scan_started(Vec::new())?;
+
+        let started = ScanStarted {
+            scan_id,
+            targets: targets.to_vec(),
+            mode: scan_type_of(targets),
+            estimated_file_count: None,
+            skipped: Vec::new(),
+            target: Some(scan_target_of(targets)),
+        };
+
+        // kept now so that the scan is listed whilst it estimates
+        self.track_scan(started.clone());
+        self.events.publish(EngineEvent::ScanEstimating {
+            scan_id,
+            targets: started.targets.clone(),
+        });
+
+        Some(started)
+    }
+
+    /// Runs a scan prepared with [`FileScanner::prepare_scan_with_estimate`], first walking the targets to estimate the
+    /// number of files, then scanning them under the same scan ID. This blocks until the scan completes, so should be
+    /// run off of the async runtime.
+    ///
+    /// A scan cancelled whilst estimating ends without scanning any file, and is recorded in the scan history as
+    /// cancelled. The time limit of the options applies from when scanning begins, after the estimate.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the scan, as with [`FileScanner::run_scan`].
+    pub fn run_scan_with_estimate(self: &Arc<Self>, started: ScanStarted, options: ScanOptions) -> FileScannerState {
+        let estimate = self.walk_for_estimate(&started.targets, || {
+            self.get_state() != FileScannerState::Scanning
+        });
+
+        if estimate.cancelled {
+            let outcome = FileScannerState::Cancelled;
+            self.finalise_scan_stats();
+            self.end_scan(outcome.clone());
+            self.record_history(&outcome);
+
+            return outcome;
+        }
+
+        let started = self.announce_scan(ScanStarted {
+            estimated_file_count: Some(estimate.file_count),
+            ..started
+        });
+
+        self.run_scan(started.targets, options)
+    }
+
+    /// Prepares a full scan of the volume for the drive letter, as [`FileScanner::prepare_scan`] does for files and
+    /// folders. The scan is run with [`FileScanner::run_scan`] on the single target in the returned `ScanStarted`.
+    ///
+    /// The folders reserved by the system at the root of the volume, such as `$Recycle.Bin` and
+    /// `System Volume Information`, are skipped and reported in the returned `ScanStarted`.
+    ///
+    /// # Returns
+    ///
+    /// Information about the started scan, or an error if the drive letter does not resolve to a volume or a scan
+    /// is already in progress.
+    pub fn prepare_volume_scan(&self, drive: &str) -> Result<ScanStarted, String> {
+        let root = resolve_volume_root(drive)?;
+        let skipped = reserved_entries(&root);
+
+        let scan_id = self
+            .scan_started(skipped.clone())
+            .ok_or_else(|| self.scan_refused())?;
+
+        Ok(self.announce_scan(ScanStarted {
+            scan_id,
+            targets: vec![root],
+            mode: ScanType::Volume,
+            estimated_file_count: None,
+            skipped,
+            target: Some(ScanTarget::Volume(drive.to_string())),
+        }))
+    }
+
+    /// Prepares a scan of the contents of a disk image, mounting it read only. This blocks whilst the image is mounted,
+    /// so should be run off of the async runtime. The scan is run with [`FileScanner::run_scan`] on the targets in the
+    /// returned `ScanStarted`, which are the roots of the image's volumes, and the image must be kept mounted until it
+    /// completes. Dropping the returned image unmounts it.
+    ///
+    /// As with a volume scan, the folders reserved by the system at the root of each volume are skipped.
+    ///
+    /// # Returns
+    ///
+    /// Information about the started scan and the mounted image, or an error if the image could not be mounted or a
+    /// scan is already in progress. The image is not left mounted on error.
+    pub fn prepare_image_scan(&self, image: &Path) -> Result<(ScanStarted, MountedImage), String> {
+        // check before mounting, so an image is not mounted only to be refused
+        if self.scan_in_flight.load(Ordering::SeqCst) || self.update.is_open() {
+            return Err(self.scan_refused());
+        }
+
+        let mounted = MountedImage::mount(image)?;
+        let targets = mounted.volume_roots().to_vec();
+        let skipped: Vec<PathBuf> = targets.iter().flat_map(|root| reserved_entries(root)).collect();
+
+        let scan_id = self
+            .scan_started(skipped.clone())
+            .ok_or_else(|| self.scan_refused())?;
+
+        self.log.log(
+            LogLevel::Info,
+            &format!("Mounted image {} read only at {:?} for scan {scan_id}.", image.display(), targets),
+        );
+
+        let started = self.announce_scan(ScanStarted {
+            scan_id,
+            targets,
+            mode: ScanType::Image {
+                image: image.to_path_buf(),
+            },
+            estimated_file_count: None,
+            skipped,
+            target: Some(ScanTarget::Image(image.to_path_buf())),
+        });
+
+        Ok((started, mounted))
+    }
+
+    /// Prepares a scan of the executable a running process was started from, as [`FileScanner::prepare_scan`] does
+    /// for a file. The scan is run with [`FileScanner::run_scan`] on the single target in the returned `ScanStarted`,
+    /// which is the executable.
+    ///
+    /// # Returns
+    ///
+    /// Information about the started scan, or an error if the process could not be resolved to its executable or a
+    /// scan is already in progress.
+    pub fn prepare_process_scan(&self, pid: u32) -> Result<ScanStarted, String> {
+        let image = process_image(pid)?;
+
+        let scan_id = self
+            .scan_started(Vec::new())
+            .ok_or_else(|| self.scan_refused())?;
+
+        Ok(self.announce_scan(ScanStarted {
+            scan_id,
+            targets: vec![image],
+            mode: ScanType::Process { pid },
+            estimated_file_count: None,
+            skipped: Vec::new(),
+            target: Some(ScanTarget::Process(pid)),
+        }))
+    }
+
+    /// Prepares a scan of the target, routing it to the preparation for its kind of target. This may block, such as
+    /// whilst a disk image is mounted, so should be run off of the async runtime. The scan is run with
+    /// [`FileScanner::run_scan`] on the targets in the returned `ScanStarted`, and any image returned must be kept
+    /// mounted until it completes.
+    ///
+    /// # Returns
+    ///
+    /// Information about the started scan and the image mounted for it, if any, or an error if the target is not what
+    /// it says it is, such as a file given as a folder, it could not be prepared, or a scan is already in progress.
+    /// Bytes are streamed over the connection as the scan runs, so are not prepared here.
+    pub fn prepare_target_scan(&self, target: &ScanTarget) -> Result<(ScanStarted, Option<MountedImage>), String> {
+        let in_progress = || self.scan_refused();
+
+        match target {
+            ScanTarget::File(path) => {
+                if !path.is_file() {
+                    return Err(format!("{} is not a file.", path.display()));
+                }
+                self.prepare_scan(std::slice::from_ref(path))
+                    .map(|started| (started, None))
+                    .ok_or_else(in_progress)
+            }
+            ScanTarget::Folder(paths) => {
+                if paths.is_empty() {
+                    return Err("No folders were given to scan.".to_string());
+                }
+                if let Some(file) = paths.iter().find(|path| path.is_file()) {
+                    return Err(format!("{} is a file, not a folder.", file.display()));
+                }
+                self.prepare_scan(paths).map(|started| (started, None)).ok_or_else(in_progress)
+            }
+            ScanTarget::Volume(drive) => self.prepare_volume_scan(drive).map(|started| (started, None)),
+            ScanTarget::Image(image) => self
+                .prepare_image_scan(image)
+                .map(|(started, mounted)| (started, Some(mounted))),
+            ScanTarget::Process(pid) => self.prepare_process_scan(*pid).map(|started| (started, None)),
+            // streamed scans read their content from the connection as they run
+            ScanTarget::Bytes(_) => Err("A scan of bytes cannot be started in the background.".to_string()),
+        }
+    }
+
+    /// Adds the files a scan has counted by extension to the scan's stats
+    fn merge_extensions(&self, counts: ExtensionCounts) {
+        let inline = counts
+            .inline
+            .into_iter()
+            .map(|(key, counts)| (String::from_utf8_lossy(key.as_bytes()).into_owned(), counts));
+
+        let mut sli = self.scanning_info.lock().unwrap();
+        for (extension, (files, bytes)) in inline.chain(counts.other) {
+            match sli.extensions.iter_mut().find(|stats| stats.extension == extension) {
+                Some(stats) => {
+                    stats.files += files;
+                    stats.bytes += bytes;
+                }
+                None => sli.extensions.push(ExtensionStats { extension, files, bytes }),
+            }
+        }
+    }
+
+    /// Whether the file is scanned given its type, which is every file unless the scan is of executables only. A file
+    /// which is skipped is counted against the scan. A file which cannot be read to find its type is scanned, so that
+    /// the scan reports why it could not be read.
+    fn should_scan_type(&self, path: &Path) -> bool {
+        if !self.executables_only.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        match classify(path) {
+            Ok(kind) if !kind.can_execute() => {
+                self.scanning_info.lock().unwrap().skipped_not_executable += 1;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the file is scanned given when it last changed, which is every file unless the scan is of files changed
+    /// since a time. A file which is skipped is counted against the scan. A file whose times cannot be read is scanned,
+    /// as whether it changed is not known.
+    fn should_scan_changed(&self, metadata: io::Result<fs::Metadata>) -> bool {
+        let Some(unchanged_before) = *self.unchanged_before.read().unwrap() else {
+            return true;
+        };
+
+        // a copied file keeps the time it was modified, but is created when it is copied
+        let changed = metadata.ok().and_then(|m| match (m.modified(), m.created()) {
+            (Ok(modified), Ok(created)) => Some(modified.max(created)),
+            (modified, created) => modified.or(created).ok(),
+        });
+
+        match changed {
+            Some(changed) if changed < unchanged_before => {
+                self.scanning_info.lock().unwrap().skipped_unchanged += 1;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Tests whether the path is skipped as it is on a remote volume, counting it as skipped where it is. Remote volumes
+    /// are skipped where the settings say to, unless the scan's options include them.
+    fn skip_remote(&self, path: &Path) -> bool {
+        if !self.skip_remote_volumes.load(Ordering::Relaxed) || self.include_remote_volumes.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        if !is_remote_volume(path) {
+            return false;
+        }
+
+        self.log.log(LogLevel::Info, &format!("Skipping {} as it is on a remote volume.", path.display()));
+        self.scanning_info.lock().unwrap().skipped_remote += 1;
+        true
+    }
+
+    /// Checks the options of a scan before it is started, so a scan with invalid options is refused rather than run.
+    ///
+    /// # Returns
+    ///
+    /// An error describing the first invalid option.
+    pub fn check_options(&self, options: &ScanOptions) -> Result<(), String> {
+        // a time in the future would skip every file, which is more likely a mistake than meant
+        if let Some(modified_since) = options.modified_since
+            && modified_since > SystemTime::now() + MODIFIED_SINCE_GRACE
+        {
+            return Err("Cannot scan only the files changed since a time which is in the future.".to_string());
+        }
+
+        let Some(thresholds) = options.thresholds else {
+            return Ok(());
+        };
+
+        if thresholds.heuristic_score == Some(0) {
+            return Err("Heuristic score threshold must be greater than 0.".to_string());
+        }
+        if let Some(similarity) = thresholds.fuzzy_similarity
+            && !(1..=100).contains(&similarity)
+        {
+            return Err(format!(
+                "Fuzzy hash similarity threshold must be between 1 and 100, got {similarity}."
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The thresholds of the current scan, each taken from the settings where the scan does not override it
+    fn effective_thresholds(&self) -> DetectionThresholds {
+        let overrides = *self.scan_thresholds.read().unwrap();
+
+        DetectionThresholds {
+            heuristic_score: Some(
+                overrides
+                    .heuristic_score
+                    .unwrap_or_else(|| self.heuristics.read().unwrap().score_threshold()),
+            ),
+            fuzzy_similarity: Some(
+                overrides
+                    .fuzzy_similarity
+                    .unwrap_or_else(|| self.fuzzy_settings.read().unwrap().similarity_threshold),
+            ),
+        }
+    }
+
+    /// Runs a scan previously prepared with [`FileScanner::prepare_scan`], blocking until it completes.
+    ///
+    /// This function ensures all state is accurate for whether a scan is in progress etc.
+    ///
+    /// Where the options set a `max_duration`, a watchdog cancels the scan once it is reached. The watchdog is woken
+    /// as soon as the scan ends, so it never outlives the scan.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the scan, which is also held as the state of the scanner until the next scan or a reset.
+    pub fn run_scan(self: &Arc<Self>, targets: Vec<PathBuf>, options: ScanOptions) -> FileScannerState {
+        *self.scan_action.write().unwrap() = options.action;
+        *self.scan_thresholds.write().unwrap() = options.thresholds.unwrap_or_default();
+        self.executables_only.store(options.executables_only, Ordering::SeqCst);
+        self.include_remote_volumes.store(options.include_remote_volumes, Ordering::SeqCst);
+        *self.unchanged_before.write().unwrap() = options
+            .modified_since
+            .map(|since| since.checked_sub(MODIFIED_SINCE_GRACE).unwrap_or(since));
+        self.scanning_info.lock().unwrap().thresholds = Some(self.effective_thresholds());
+
+        let result = thread::scope(|scope| {
+            // dropping the sender when the scan ends wakes the watchdog
+            let (scan_ended, wait_for_end) = mpsc::channel::<()>();
+
+            if let Some(max_duration) = options.max_duration {
+                scope.spawn(move || {
+                    if wait_for_end.recv_timeout(max_duration) == Err(RecvTimeoutError::Timeout) {
+                        self.scan_timed_out(max_duration);
+                    }
+                });
+            }
+
+            // send the job for a scan
+            let result = self.begin_scan(targets);
+            drop(scan_ended);
+
+            result
+        });
+
+        let outcome = match result {
+            Ok(state) => state,
+            // a cancelled folder scan is reported as an uncategorised error from the walk
+            Err(e) if e.kind() == io::ErrorKind::Uncategorized => FileScannerState::Cancelled,
+            Err(e) => FileScannerState::FinishedWithError(e.to_string()),
+        };
+
+        // a scan cancelled whilst scanning its last file completes as normal, but was cancelled all the same
+        let outcome = match outcome {
+            FileScannerState::Finished if self.get_state() == FileScannerState::Cancelled => {
+                FileScannerState::Cancelled
+            }
+            outcome => outcome,
+        };
+
+        // stats are finalised before the state changes so anyone seeing the scan has ended gets the final stats
+        self.finalise_scan_stats();
+        self.end_scan(outcome.clone()); // update state
+        self.record_history(&outcome);
+
+        outcome
+    }
+
+    /// Scans the files of a scan of part of the system, such as of the autoruns, and ends the scan. Each file is given
+    /// with the context it was found in, such as the autorun entry which starts it, and is scanned once however many
+    /// contexts it is given with, what became of it being recorded against each context in turn.
+    ///
+    /// The scan must already have been started. Files are taken from the iterator only as they are scanned, so one
+    /// which walks the system as it goes walks no further once the scan is cancelled.
+    ///
+    /// # Args
+    /// - `kind`: What the files are, such as `autorun`, for the log
+    /// - `record`: Records what became of a file against the context it was given with
+    fn scan_system_files<C>(
+        &self,
+        kind: &str,
+        files: impl IntoIterator<Item = (PathBuf, C)>,
+        mut record: impl FnMut(C, SystemFileOutcome),
+    ) -> SystemScanSummary {
+        let files_scanned = Arc::new(Mutex::new(0));
+        let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+        let mut verdicts: HashMap<PathBuf, FileVerdict> = HashMap::new();
+
+        for (file, context) in files {
+            if self.is_cancelled() {
+                break;
+            }
+
+            if self.is_excluded(&file) {
+                record(context, SystemFileOutcome::Excluded);
+                continue;
+            }
+
+            let verdict = match verdicts.get(&file) {
+                Some(verdict) => verdict.clone(),
+                None => match self.scan_file_against_hashes(&file, &files_scanned, &mut scratch) {
+                    Ok(verdict) => {
+                        self.record_verdict(verdict.clone());
+                        verdicts.insert(file, verdict.clone());
+                        verdict
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        record(context, SystemFileOutcome::Missing);
+                        continue;
+                    }
+                    Err(e) => {
+                        self.log.log(
+                            LogLevel::Warning,
+                            &format!("[-] Error scanning {kind} {}: {e}", file.display()),
+                        );
+                        record(context, SystemFileOutcome::Failed);
+                        continue;
+                    }
+                },
+            };
+
+            record(context, SystemFileOutcome::Scanned(verdict));
+        }
+
+        // a scan cancelled whilst scanning its last file is cancelled all the same, as with a scan of folders
+        let cancelled = self.is_cancelled();
+        self.scanning_info.lock().unwrap().num_files_scanned = verdicts.len() as u128;
+
+        let outcome = if cancelled {
+            FileScannerState::Cancelled
+        } else {
+            FileScannerState::Finished
+        };
+
+        self.finalise_scan_stats();
+        self.end_scan(outcome.clone());
+        self.record_history(&outcome);
+
+        SystemScanSummary {
+            files_scanned: verdicts.len(),
+            cancelled,
+        }
+    }
+
+    /// Scans the binaries started from the autorun locations (Run keys, Startup folders, scheduled tasks and
+    /// services), blocking until the scan completes. This is tracked as a scan in its own right, so cannot run
+    /// alongside another scan, can be cancelled, and is recorded in the scan history.
+    ///
+    /// Each binary is scanned once, however many entries start it, and every entry which starts a malicious binary is
+    /// reported so that each persistence mechanism can be removed.
+    ///
+    /// # Returns
+    ///
+    /// The detections paired with the entries which start them, or an error if a scan is already in progress.
+    pub fn scan_autoruns(&self) -> Result<AutorunScanResult, String> {
+        let scan_id = self.start_system_scan("autoruns")?;
+
+        let entries = enumerate_autoruns();
+        self.log.log(
+            LogLevel::Info,
+            &format!("Scanning autoruns, found {} entries.", entries.len()),
+        );
+
+        // an entry whose binary cannot be found has nothing to scan, but is still reported
+        let (resolved, unresolved): (Vec<_>, Vec<_>) = entries.into_iter().partition(|entry| entry.image.is_some());
+
+        let mut result = AutorunScanResult {
+            scan_id,
+            entries_found: resolved.len() + unresolved.len(),
+            binaries_scanned: 0,
+            detections: Vec::new(),
+            changed_during_scan: Vec::new(),
+            unresolved,
+            cancelled: false,
+        };
+
+        let files = resolved.into_iter().map(|entry| (entry.image.clone().unwrap(), entry));
+        let scanned = self.scan_system_files("autorun", files, |entry, outcome| match outcome {
+            SystemFileOutcome::Scanned(FileVerdict::Malicious(ioc)) => {
+                result.detections.push(AutorunDetection { entry, ioc })
+            }
+            SystemFileOutcome::Scanned(FileVerdict::ChangedDuringScan(_)) => result.changed_during_scan.push(entry),
+            _ => (),
+        });
+
+        result.binaries_scanned = scanned.files_scanned;
+        result.cancelled = scanned.cancelled;
+
+        Ok(result)
+    }
+
+    /// Scans the extensions installed into the browsers of every user, blocking until the scan completes. Like a scan
+    /// of the autoruns, this is tracked as a scan in its own right, so cannot run alongside another scan, can be
+    /// cancelled, and is recorded in the scan history.
+    ///
+    /// Each detection is reported with the extension whose file it is, and extensions which request dangerous
+    /// permissions are reported whether or not any of their files were detected.
+    ///
+    /// # Returns
+    ///
+    /// The detections paired with their extensions, or an error if a scan is already in progress.
+    pub fn scan_browser_extensions(&self) -> Result<BrowserExtensionScanResult, String> {
+        let scan_id = self.start_system_scan("browser extensions")?;
+
+        let extensions = enumerate_browser_extensions();
+        self.log.log(
+            LogLevel::Info,
+            &format!("Scanning browser extensions, found {} extensions.", extensions.len()),
+        );
+
+        let mut result = BrowserExtensionScanResult {
+            scan_id,
+            extensions_found: extensions.len(),
+            files_scanned: 0,
+            detections: Vec::new(),
+            flagged: extensions
+                .iter()
+                .filter(|extension| !extension.dangerous_permissions.is_empty())
+                .cloned()
+                .collect(),
+            changed_during_scan: Vec::new(),
+            cancelled: false,
+        };
+
+        let files = extensions
+            .iter()
+            .flat_map(|extension| extension_files(extension).into_iter().map(move |file| (file, extension)));
+        let scanned = self.scan_system_files("browser extension file", files, |extension, outcome| match outcome {
+            SystemFileOutcome::Scanned(FileVerdict::Malicious(ioc)) => {
+                result.detections.push(BrowserExtensionDetection {
+                    extension: extension.clone(),
+                    ioc,
+                })
+            }
+            SystemFileOutcome::Scanned(FileVerdict::ChangedDuringScan(path)) => result.changed_during_scan.push(path),
+            _ => (),
+        });
+
+        result.files_scanned = scanned.files_scanned;
+        result.cancelled = scanned.cancelled;
+
+        Ok(result)
+    }
+
+    /// Lists the kernel drivers loaded on the machine, with the signature of the file each was loaded from. Unlike
+    /// [`FileScanner::scan_drivers`] nothing is scanned, but each file's signature is verified, so this blocks for a
+    /// few seconds and should be run off of the async runtime.
+    pub fn list_drivers(&self) -> Result<Vec<DriverInfo>, String> {
+        enumerate_drivers(&self.driver_load_times)
+    }
+
+    /// Scans the files of the kernel drivers loaded on the machine, blocking until the scan completes. Like a scan of
+    /// the autoruns, this is tracked as a scan in its own right, so cannot run alongside another scan, can be
+    /// cancelled, and is recorded in the scan history.
+    ///
+    /// Drivers which are unsigned or whose signature is not trusted are reported whether or not their file was
+    /// detected.
+    ///
+    /// # Returns
+    ///
+    /// The detections paired with the drivers whose files they are, or an error if the drivers could not be listed
+    /// or a scan is already in progress.
+    pub fn scan_drivers(&self) -> Result<DriverScanResult, String> {
+        let drivers = enumerate_drivers(&self.driver_load_times)?;
+
+        let scan_id = self.start_system_scan("drivers")?;
+
+        self.log.log(
+            LogLevel::Info,
+            &format!("Scanning loaded drivers, found {} drivers.", drivers.len()),
+        );
+
+        let drivers_found = drivers.len();
+        let flagged = drivers.iter().filter(|driver| !driver.trusted).cloned().collect();
+
+        // a driver whose file cannot be found has nothing to scan, but is still reported
+        let (resolved, unresolved): (Vec<_>, Vec<_>) =
+            drivers.into_iter().partition(|driver| driver.path.as_ref().is_some_and(|path| path.is_file()));
+
+        let mut result = DriverScanResult {
+            scan_id,
+            drivers_found,
+            files_scanned: 0,
+            detections: Vec::new(),
+            flagged,
+            changed_during_scan: Vec::new(),
+            unresolved,
+            cancelled: false,
+        };
+
+        let files = resolved.into_iter().map(|driver| (driver.path.clone().unwrap(), driver));
+        let scanned = self.scan_system_files("driver", files, |driver, outcome| match outcome {
+            SystemFileOutcome::Scanned(FileVerdict::Malicious(ioc)) => {
+                result.detections.push(DriverDetection { driver, ioc })
+            }
+            SystemFileOutcome::Scanned(FileVerdict::ChangedDuringScan(_)) => result.changed_during_scan.push(driver),
+            _ => (),
+        });
+
+        result.files_scanned = scanned.files_scanned;
+        result.cancelled = scanned.cancelled;
+
+        Ok(result)
+    }
+
+    /// Scans the files run by the tasks registered with the Task Scheduler, blocking until the scan completes. Like a
+    /// scan of the autoruns, this is tracked as a scan in its own right, so cannot run alongside another scan, can be
+    /// cancelled, and is recorded in the scan history.
+    ///
+    /// Each file is scanned once, however many tasks run it. Tasks which look suspicious, such as one running an
+    /// encoded PowerShell command or hidden from the Task Scheduler, are reported whether or not any file they run was
+    /// detected.
+    ///
+    /// # Returns
+    ///
+    /// The detections paired with the tasks which run them, or an error if a scan is already in progress.
+    pub fn scan_scheduled_tasks(&self) -> Result<ScheduledTaskScanResult, String> {
+        let scan_id = self.start_system_scan("scheduled tasks")?;
+
+        let tasks = enumerate_scheduled_tasks();
+        self.log.log(
+            LogLevel::Info,
+            &format!("Scanning scheduled tasks, found {} tasks.", tasks.len()),
+        );
+
+        let mut result = ScheduledTaskScanResult {
+            scan_id,
+            tasks_found: tasks.len(),
+            files_scanned: 0,
+            detections: Vec::new(),
+            flagged: tasks.iter().filter(|task| !task.indicators.is_empty()).cloned().collect(),
+            changed_during_scan: Vec::new(),
+            // a task whose actions run no file which can be found has nothing to scan, but is still reported
+            unresolved: tasks
+                .iter()
+                .filter(|task| !task.actions.is_empty() && task.actions.iter().all(|action| action.files.is_empty()))
+                .cloned()
+                .collect(),
+            cancelled: false,
+        };
+
+        // each file is given with the index of the task which runs it, so a task is reported once as changed however
+        // many of its files changed
+        let files = tasks.iter().enumerate().flat_map(|(index, task)| {
+            task.actions
+                .iter()
+                .flat_map(|action| action.files.iter().cloned())
+                .map(move |file| (file, index))
+        });
+        let mut changed: Vec<usize> = Vec::new();
+        let scanned = self.scan_system_files("scheduled task file", files, |index, outcome| match outcome {
+            SystemFileOutcome::Scanned(FileVerdict::Malicious(ioc)) => {
+                result.detections.push(ScheduledTaskDetection {
+                    task: tasks[index].clone(),
+                    ioc,
+                })
+            }
+            SystemFileOutcome::Scanned(FileVerdict::ChangedDuringScan(_)) if changed.last() != Some(&index) => {
+                changed.push(index)
+            }
+            _ => (),
+        });
+
+        result.changed_during_scan = changed.into_iter().map(|index| tasks[index].clone()).collect();
+        result.files_scanned = scanned.files_scanned;
+        result.cancelled = scanned.cancelled;
+
+        Ok(result)
+    }
+
+    /// Scans a subtree of the registry, blocking until the scan completes. The files each value references are
+    /// scanned, and the contents of each value are matched against the registry signatures. Like a scan of the
+    /// autoruns, this is tracked as a scan in its own right, so cannot run alongside another scan, can be cancelled,
+    /// and is recorded in the scan history.
+    ///
+    /// Each file is scanned once, however many values reference it, and every value which references a malicious file
+    /// is reported. A value whose contents match a registry signature is only reported, and is not recorded among the
+    /// scan's detections, as there is no file to act on.
+    ///
+    /// # Returns
+    ///
+    /// The detections paired with the values which reference them, and the values matching a registry signature, or
+    /// an error if the key could not be opened or a scan is already in progress.
+    pub fn scan_registry(&self, request: RegistryScanRequest) -> Result<RegistryScanResult, String> {
+        let mut walk = RegistryWalk::new(&request)?;
+
+        let scan_id = self.start_system_scan("registry")?;
+
+        self.log.log(LogLevel::Info, &format!("Scanning the registry beneath {}.", walk.root));
+
+        let signatures = self.signatures();
+        let disabled = self.disabled_databases();
+
+        let mut result = RegistryScanResult {
+            scan_id,
+            root: walk.root.clone(),
+            keys_scanned: 0,
+            values_scanned: 0,
+            files_scanned: 0,
+            detections: Vec::new(),
+            content_matches: Vec::new(),
+            changed_during_scan: Vec::new(),
+            inaccessible_keys: 0,
+            cancelled: false,
+        };
+
+        // values are matched against the registry signatures as the walk reaches them, and the walk stops once the
+        // scan is cancelled, whether or not the values it is reaching reference any file
+        let mut values_scanned = 0;
+        let mut content_matches = Vec::new();
+        let files = walk.by_ref().take_while(|_| !self.is_cancelled()).flat_map(|entry| {
+            values_scanned += 1;
+
+            if let Some((db, signature)) = signatures.registry_match(&disabled, &entry.contents) {
+                content_matches.push(RegistryContentMatch {
+                    location: entry.location.clone(),
+                    signature: signature.to_string(),
+                    source: db.name.clone(),
+                });
+            }
+
+            let location = entry.location;
+            entry.files.into_iter().map(move |file| (file, location.clone()))
+        });
+        let scanned = self.scan_system_files("registry referenced file", files, |location, outcome| match outcome {
+            SystemFileOutcome::Scanned(FileVerdict::Malicious(ioc)) => {
+                result.detections.push(RegistryDetection { location, ioc })
+            }
+            SystemFileOutcome::Scanned(FileVerdict::ChangedDuringScan(_)) => result.changed_during_scan.push(location),
+            _ => (),
+        });
+
+        result.values_scanned = values_scanned;
+        result.content_matches = content_matches;
+        result.keys_scanned = walk.keys_scanned;
+        result.inaccessible_keys = walk.inaccessible_keys;
+        result.files_scanned = scanned.files_scanned;
+        result.cancelled = scanned.cancelled;
+
+        Ok(result)
+    }
+
+    /// Rescans just the files detected by an earlier scan, taken from its record in the scan history, with the
+    /// current signatures, engines and exclusions. This blocks until the rescan completes, and like a scan of the
+    /// autoruns it is tracked and recorded in the history as a scan in its own right.
+    ///
+    /// Detections on a rescan are only reported, as the earlier scan has already had the chance to act on them.
+    ///
+    /// # Returns
+    ///
+    /// Which of the files are still detected, cleared or removed, or an error if the earlier scan is not in the
+    /// history or a scan is already in progress.
+    pub fn rescan_detections(&self, previous_scan_id: u64) -> Result<RescanResult, String> {
+        let record = self
+            .history
+            .find(previous_scan_id)
+            .ok_or_else(|| format!("Scan {previous_scan_id} was not found in the scan history."))?;
+
+        // a file may have several detections, such as from more than one engine, but is rescanned once
+        let mut files: Vec<PathBuf> = Vec::new();
+        for ioc in record.detections {
+            if !files.contains(&ioc.file) {
+                files.push(ioc.file);
+            }
+        }
+
+        let scan_id = self.start_system_scan("detections")?;
+
+        self.log.log(
+            LogLevel::Info,
+            &format!(
+                "Rescanning {} files detected by scan {previous_scan_id}.",
+                files.len()
+            ),
+        );
+
+        let mut result = RescanResult {
+            scan_id,
+            previous_scan_id,
+            files_rescanned: 0,
+            still_detected: Vec::new(),
+            cleared: Vec::new(),
+            removed: Vec::new(),
+            changed_during_scan: Vec::new(),
+            cancelled: false,
+        };
+
+        // a file already gone is not rescanned, but is reported as removed
+        let (files, removed): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| file.exists());
+        result.removed = removed;
+
+        let files = files.into_iter().map(|file| (file.clone(), file));
+        let scanned = self.scan_system_files("rescanned file", files, |file, outcome| {
+            result.files_rescanned += 1;
+
+            match outcome {
+                SystemFileOutcome::Scanned(FileVerdict::Clean) | SystemFileOutcome::Excluded => {
+                    result.cleared.push(file)
+                }
+                SystemFileOutcome::Scanned(FileVerdict::Malicious(ioc)) => result.still_detected.push(ioc),
+                SystemFileOutcome::Scanned(FileVerdict::ChangedDuringScan(path)) => {
+                    result.changed_during_scan.push(path)
+                }
+                SystemFileOutcome::Missing => result.removed.push(file),
+                SystemFileOutcome::Failed => (),
+            }
+        });
+
+        result.cancelled = scanned.cancelled;
+
+        Ok(result)
+    }
+
+    /// Quarantines every detection of a scan in the history, such as once the user has reviewed a scan which only
+    /// reported. Quarantining is idempotent, so a detection already quarantined keeps its entry, and a file detected
+    /// more than once is quarantined once. Files matching an exclusion and detections triaged as benign are left in
+    /// place. A file which cannot be quarantined does not stop the rest, each detection being reported on its own.
+    ///
+    /// The detections quarantined are recorded against the scan in the history, and in the results of the last scan
+    /// where that is the scan.
+    ///
+    /// # Returns
+    ///
+    /// What became of each detection, or an error if the scan is not in the history.
+    pub fn quarantine_scan_detections(&self, scan_id: u64) -> Result<BatchQuarantineResult, String> {
+        let record = self
+            .history
+            .find(scan_id)
+            .ok_or_else(|| format!("Scan {scan_id} was not found in the scan history."))?;
+
+        let mut result = BatchQuarantineResult {
+            scan_id,
+            quarantined: 0,
+            skipped: 0,
+            failed: 0,
+            items: Vec::with_capacity(record.detections.len()),
+        };
+        let mut outcomes: HashMap<PathBuf, BatchQuarantineOutcome> = HashMap::new();
+        let mut actions = Vec::new();
+
+        for (detection_id, ioc) in record.detections.iter().enumerate() {
+            let benign = record
+                .annotations
+                .get(&detection_id)
+                .is_some_and(|annotation| annotation.status == TriageStatus::Benign);
+
+            let outcome = if benign {
+                BatchQuarantineOutcome::SkippedBenign
+            } else if let Some(outcome) = outcomes.get(&ioc.file) {
+                outcome.clone()
+            } else if self.exclusions.read().unwrap().is_excluded(&ioc.file) {
+                BatchQuarantineOutcome::SkippedExcluded
+            } else {
+                let outcome = match self.quarantine.quarantine(&ioc.file, &ioc.hash) {
+                    Ok(entry) => BatchQuarantineOutcome::Quarantined { id: entry.id },
+                    Err(e) => BatchQuarantineOutcome::Failed(describe_error(&e)),
+                };
+                outcomes.insert(ioc.file.clone(), outcome.clone());
+                outcome
+            };
+
+            match &outcome {
+                BatchQuarantineOutcome::Quarantined { id } => {
+                    result.quarantined += 1;
+                    actions.push((detection_id, ActionResult::Quarantined { id: id.clone() }));
+                }
+                BatchQuarantineOutcome::SkippedExcluded | BatchQuarantineOutcome::SkippedBenign => result.skipped += 1,
+                BatchQuarantineOutcome::Failed(_) => result.failed += 1,
+            }
+            result.items.push(BatchQuarantineItem {
+                detection_id,
+                file: ioc.file.clone(),
+                outcome,
+            });
+        }
+
+        self.log.log(
+            LogLevel::Info,
+            &format!(
+                "Quarantined {} of {} detections of scan {scan_id}, skipping {} and failing {}.",
+                result.quarantined,
+                record.detections.len(),
+                result.skipped,
+                result.failed
+            ),
+        );
+
+        if actions.is_empty() {
+            return Ok(result);
+        }
+
+        // the last scan's results are what the results page shows, so are kept in step with the history
+        {
+            let mut sli = self.scanning_info.lock().unwrap();
+            if sli.scan_id == Some(scan_id) {
+                for ioc in sli.scan_results.iter_mut() {
+                    if let Some(BatchQuarantineOutcome::Quarantined { id }) = outcomes.get(&ioc.file) {
+                        ioc.action_taken = ActionResult::Quarantined { id: id.clone() };
+                    }
+                }
+            }
+        }
+
+        // the files are in quarantine either way, so failing to record it is only logged
+        if let Err(e) = self.history.record_actions(scan_id, actions) {
+            self.log.log(
+                LogLevel::Error,
+                &format!("Could not record the quarantined detections of scan {scan_id} in the history. {e}"),
+            );
+        }
+
+        Ok(result)
+    }
+
+    /// Instructs the scanner to cancel its scan, returning information about the results
+    pub fn cancel_scan(&self) -> Option<ScanningLiveInfo> {
+        self.cancel_scan_with_reason(CANCEL_REASON_USER)
+    }
+
+    /// Cancels the scan with the ID, so that a client cancelling a scan from a list cannot cancel a different scan
+    /// which has since started in its place.
+    ///
+    /// # Returns
+    ///
+    /// An error if the scan is not running.
+    pub fn cancel_scan_by_id(&self, scan_id: u64) -> Result<(), String> {
+        let mut lock = self.state.lock().unwrap();
+        let mut sli = self.scanning_info.lock().unwrap();
+
+        if *lock != FileScannerState::Scanning || sli.scan_id != Some(scan_id) {
+            return Err(format!("Scan {scan_id} is not running."));
+        }
+
+        self.set_state(&mut lock, FileScannerState::Cancelled);
+        sli.cancel_reason = Some(CANCEL_REASON_USER.to_string());
+
+        Ok(())
+    }
+
+    /// Cancels every running scan with the path as one of its targets, for a client which knows what it scanned but no
+    /// longer has the ID of the scan. Paths are compared case insensitively and with `\` and `/` alike, and only a
+    /// scan of the path itself is cancelled, not a scan of a folder above it.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of the scans which were cancelled, which is empty where no running scan has the path as a target.
+    pub fn cancel_scans_by_target(&self, path: &Path) -> Vec<u64> {
+        let target = normalise(&path.to_string_lossy());
+
+        // a scan started since in place of the one matched is not cancelled, as it is cancelled by its ID
+        let matched: Vec<u64> = self
+            .scans
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|started| started.targets.iter().any(|t| normalise(&t.to_string_lossy()) == target))
+            .map(|started| started.scan_id)
+            .collect();
+
+        matched
+            .into_iter()
+            .filter(|scan_id| self.cancel_scan_by_id(*scan_id).is_ok())
+            .collect()
+    }
+
+    /// Cancels the scan, recording why it was cancelled against its results
+    pub fn cancel_scan_with_reason(&self, reason: &str) -> Option<ScanningLiveInfo> {
+        let mut lock = self.state.lock().unwrap();
+
+        // check we are scanning, if not return
+        if *lock == FileScannerState::Scanning {
+            self.set_state(&mut lock, FileScannerState::Cancelled);
+            let mut sli = self.scanning_info.lock().unwrap();
+            sli.cancel_reason = Some(reason.to_string());
+
+            return Some(sli.clone());
+        }
+
+        None
+    }
+
+    /// Cancels the scan as it has run for longer than its time limit, notifying subscribers
+    fn scan_timed_out(&self, max_duration: Duration) {
+        let Some(sli) = self.cancel_scan_with_reason(CANCEL_REASON_TIME_LIMIT) else {
+            return;
+        };
+
+        self.log.log(
+            LogLevel::Info,
+            &format!("Scan cancelled as it reached its time limit of {}s.", max_duration.as_secs()),
+        );
+
+        if let Some(scan_id) = sli.scan_id {
+            self.events.publish(EngineEvent::ScanTimedOut {
+                scan_id,
+                max_duration,
+            });
+        }
+    }
+
+    /// Estimates the size of a scan of the targets by walking them the way a scan would, applying the exclusions, but
+    /// without reading any file, so it returns far quicker than the scan itself. This is independent of any scan and
+    /// does not affect the scanner state. It blocks until the walk completes or is cancelled with
+    /// [`FileScanner::cancel_estimate`], so should be run off of the async runtime.
+    ///
+    /// Paths skipped by default for a particular scan, such as the reserved folders at the root of a volume, are not
+    /// skipped, so an estimate of a volume may count slightly more than the scan will.
+    ///
+    /// # Returns
+    ///
+    /// The estimate, or an error if an estimate is already in progress.
+    pub fn estimate_scan(&self, targets: &[PathBuf]) -> Result<ScanEstimate, String> {
+        if self.estimate_in_flight.swap(true, Ordering::SeqCst) {
+            return Err("A scan estimate is already in progress.".to_string());
+        }
+        self.estimate_cancelled.store(false, Ordering::SeqCst);
+
+        let estimate = self.walk_for_estimate(targets, || self.estimate_cancelled.load(Ordering::SeqCst));
+
+        self.estimate_in_flight.store(false, Ordering::SeqCst);
+
+        Ok(estimate)
+    }
+
+    /// Cancels the running scan estimate, if there is one
+    pub fn cancel_estimate(&self) {
+        if self.estimate_in_flight.load(Ordering::SeqCst) {
+            self.estimate_cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Walks the targets for an estimate, stopping part way through once `cancelled` returns true
+    fn walk_for_estimate(&self, targets: &[PathBuf], cancelled: impl Fn() -> bool) -> ScanEstimate {
+        let mut estimate = ScanEstimate::default();
+        let mut discovered_dirs: Vec<PathBuf> = Vec::new();
+
+        let count = |estimate: &mut ScanEstimate, path: &Path, metadata: Option<fs::Metadata>| {
+            if self.exclusions.read().unwrap().is_excluded(path) {
+                estimate.skipped_by_exclusions += 1;
+                return None;
+            }
+
+            if path.is_dir() {
+                return Some(path.to_path_buf());
+            }
+
+            if let Some(metadata) = metadata.or_else(|| fs::metadata(path).ok()) {
+                estimate.file_count += 1;
+                estimate.total_bytes += metadata.len();
+            }
+
+            None
+        };
+
+        for target in targets {
+            if let Some(dir) = count(&mut estimate, target, None) {
+                discovered_dirs.push(dir);
+            }
+        }
+
+        while let Some(dir) = discovered_dirs.pop() {
+            // as when scanning, folders which cannot be read are skipped
+            let Ok(read_dir) = read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in read_dir.map_while(Result::ok) {
+                if cancelled() {
+                    estimate.cancelled = true;
+                    return estimate;
+                }
+
+                // the metadata of an entry comes from the folder listing on Windows, so this does not open the file
+                if let Some(dir) = count(&mut estimate, &entry.path(), entry.metadata().ok()) {
+                    discovered_dirs.push(dir);
+                }
+            }
+        }
+
+        estimate
+    }
+
+    /// Runs the detection engines over a single file and gives their verdict, without anything being recorded or acted
+    /// on: no scan is started, nothing is written to the scan history or quarantined, and no event is published. It
+    /// can run alongside a scan, which it neither counts towards nor is cancelled with, and its matched bytes are
+    /// returned rather than kept for the detection details. This blocks until the file has been read, so should be run
+    /// off of the async runtime.
+    ///
+    /// The exclusions are not applied, as the file was asked about by name, and the thresholds are those of the
+    /// settings, never the overrides of a running scan.
+    ///
+    /// # Returns
+    ///
+    /// The verdict of each engine and the detection resolved from them, or an error if the file could not be read.
+    pub fn classify_file(&self, path: &Path) -> Result<Classification, String> {
+        let (file, _) = open_for_read(path).map_err(|e| format!("Could not open {}. {e}", path.display()))?;
+        let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+
+        let inspection = self
+            .inspect_file(path, &file, &mut scratch, false)
+            .map_err(|e| format!("Could not classify {}. {e}", path.display()))?
+            .ok_or_else(|| format!("Classifying {} was cancelled.", path.display()))?;
+        let (detection, regions) = match inspection.detection {
+            Some((ioc, regions)) => (Some(ioc), regions),
+            None => (None, Vec::new()),
+        };
+
+        Ok(Classification {
+            file: path.to_path_buf(),
+            md5: inspection.md5.map(|h| h.as_str().to_string()),
+            sha256: inspection.sha256.map(|h| h.as_str().to_string()),
+            detection,
+            verdicts: inspection.verdicts,
+            regions,
+        })
+    }
+
+    /// Explains the verdict on a single file: for each engine which did not detect it, why not, such as a hash not
+    /// being in any database or a score below the threshold, along with anything which would keep a detection from
+    /// being reported or acted on, being the user's exclusions, a triage of the file as benign, and disabled signature
+    /// databases. The engines are run as with [`FileScanner::classify_file`], so nothing is recorded or acted on, and
+    /// this blocks until the file has been read, so should be run off of the async runtime.
+    ///
+    /// # Returns
+    ///
+    /// The explanation, or an error if the file could not be read.
+    pub fn explain_file(&self, path: &Path) -> Result<Explanation, String> {
+        let (file, _) = open_for_read(path).map_err(|e| format!("Could not open {}. {e}", path.display()))?;
+        let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+        let could_not_explain = |e: io::Error| format!("Could not explain {}. {e}", path.display());
+
+        let inspection = self
+            .inspect_file(path, &file, &mut scratch, false)
+            .map_err(could_not_explain)?
+            .ok_or_else(|| format!("Explaining {} was cancelled.", path.display()))?;
+
+        let signatures = self.signatures();
+        let disabled = self.disabled_databases();
+        let ctx = FileContext {
+            path,
+            file: &file,
+            md5: inspection.md5.as_ref().map(|h| h.as_str()),
+            sha256: inspection.sha256.as_ref().map(|h| h.as_str()),
+            scanner: self,
+            signatures: &signatures,
+            disabled: &disabled,
+            engines: self.detection_engines(),
+            in_scan: false,
+        };
+
+        // each engine which did not detect the file is asked why, by the detector which gave the verdict
+        let detectors = self.detectors();
+        let mut engines = Vec::with_capacity(inspection.verdicts.len());
+        for verdict in inspection.verdicts {
+            let reason = match (&verdict.match_mode, detectors.iter().find(|d| d.name() == verdict.engine)) {
+                (Some(_), _) => None,
+                (None, Some(detector)) => Some(detector.explain(&ctx).map_err(could_not_explain)?),
+                // the detector was registered by name and is no longer loaded
+                (None, None) => Some(NotDetectedReason::NoMatch),
+            };
+
+            engines.push(EngineExplanation {
+                engine: verdict.engine,
+                match_mode: verdict.match_mode,
+                source: verdict.source,
+                reason,
+            });
+        }
+
+        let exclusion = self.test_exclusion(path);
+        let triage = self.history.latest_annotation(path);
+
+        Ok(Explanation {
+            file: path.to_path_buf(),
+            md5: inspection.md5.map(|h| h.as_str().to_string()),
+            sha256: inspection.sha256.map(|h| h.as_str().to_string()),
+            detection: inspection.detection.map(|(ioc, _)| ioc),
+            excluded_by: exclusion.excluded.then_some(exclusion),
+            triaged_benign: triage.filter(|a| a.status == TriageStatus::Benign),
+            disabled_databases: disabled.iter().filter(|name| signatures.has_database(name)).cloned().collect(),
+            engines,
+        })
+    }
+
+    /// Hashes the file with each of the algorithms, in the format signatures are written in, so that a signature made
+    /// from the result matches the file exactly as a scan would. The file is opened and hashed as scanning opens and
+    /// hashes it, so a file only readable with the backup privilege is still hashed; this blocks until the file has
+    /// been read, so should be run off of the async runtime.
+    ///
+    /// # Returns
+    ///
+    /// A hash for each algorithm in the order requested, or an error if the file could not be read, or is too large to
+    /// be fuzzy hashed where a fuzzy hash was asked for.
+    pub fn hash_file(&self, request: &HashRequest) -> io::Result<Vec<HashResult>> {
+        if request.algorithms.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "No hash algorithms were given."));
+        }
+
+        let (mut file, _) = open_for_read(&request.path)?;
+        let size = file.metadata()?.file_size();
+        let wants = |algo: HashAlgorithm| request.algorithms.contains(&algo);
+
+        if wants(HashAlgorithm::Fuzzy) && size > FUZZY_MAX_FILE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is larger than the {FUZZY_MAX_FILE_SIZE} bytes which are fuzzy hashed, so would never match a fuzzy hash.",
+                    request.path.display()
+                ),
+            ));
+        }
+
+        let mut hasher = FileHasher::new(wants(HashAlgorithm::Sha256));
+        // the fuzzy hash needs the whole file, as when scanning
+        let mut data = wants(HashAlgorithm::Fuzzy).then(|| Vec::with_capacity(size as usize));
+        let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+
+        loop {
+            let count = file.read(&mut scratch)?;
+            if count == 0 {
+                break;
+            }
+            hasher.update(&scratch[..count]);
+            if let Some(data) = &mut data {
+                data.extend_from_slice(&scratch[..count]);
+            }
+        }
+
+        let size = hasher.len;
+        let (md5, sha256) = hasher.finish();
+        let fuzzy = data.map(|data| FuzzyHash::hash(&data).to_string());
+
+        Ok(request
+            .algorithms
+            .iter()
+            .map(|&algo| HashResult {
+                algo,
+                hex: match algo {
+                    HashAlgorithm::Md5 => md5.as_str().to_string(),
+                    HashAlgorithm::Sha256 => sha256.as_ref().map(|h| h.as_str().to_string()).unwrap_or_default(),
+                    HashAlgorithm::Fuzzy => fuzzy.clone().unwrap_or_default(),
+                },
+                size,
+            })
+            .collect())
+    }
+
+    /// Begins a scan of content which is to be streamed in as bytes. This is independent of any file / folder scan
+    /// and does not affect the scanner state.
+    pub fn begin_bytes_scan(&self) -> StreamingScan {
+        StreamingScan {
+            hasher: Md5::new(),
+            len: 0,
+        }
+    }
+
+    /// Completes a scan of streamed content, checking the hash of the content against the known bad hashes.
+    ///
+    /// # Args
+    ///
+    /// - `scan`: The streamed scan to complete
+    /// - `name`: A name for the content to use in place of a file path in the results
+    pub fn finish_bytes_scan(&self, scan: StreamingScan, name: &str) -> BytesScanResult {
+        let hash = to_hex(&scan.hasher.finalize());
+
+        let signatures = self.signatures();
+        let disabled = self.disabled_databases();
+
+        // bytes have no path nor file to check conditions against, so only signatures without conditions match them
+        let ioc = signatures
+            .enabled(&disabled)
+            .find(|db| db.iocs.contains(hash.as_str()) && db.conditions(&hash).is_none())
+            .map(|db| MatchedIOC {
+                hash: hash.clone(),
+                file: PathBuf::from(name),
+                match_mode: MatchMode::FileHash,
+                action_taken: ActionResult::None,
+                source: Some(db.name.clone()),
+                verdicts: Vec::new(),
+                resolved_by: None,
+                conditions: Vec::new(),
+            });
+
+        BytesScanResult {
+            hash,
+            len: scan.len,
+            ioc,
+        }
+    }
+
+    /// Measures how fast the engine scans, by streaming synthetic files generated in memory through the same path as
+    /// `scanner_scan_bytes`, so that throughput can be compared across machines, versions and settings without a tree
+    /// of files to scan. Nothing is recorded, and a file which happens to match a signature is not acted on. This
+    /// blocks until every file has been scanned, so should be run off of the async runtime.
+    ///
+    /// Only the file hash engine runs over scanned bytes, as the other engines need a file on disk, so it is the only
+    /// engine timed. This is only served by an engine built with the `test_mode` feature, so that a production engine
+    /// cannot be kept busy generating content.
+    ///
+    /// # Returns
+    ///
+    /// The throughput and time spent in each engine, or an error if benchmarks are not enabled in this build or the
+    /// request is outside of the limits of a benchmark.
+    pub fn benchmark_scan(&self, request: &BenchmarkRequest) -> Result<BenchmarkReport, String> {
+        if !cfg!(feature = "test_mode") {
+            return Err("Benchmarking requires an engine built with the test_mode feature.".to_string());
+        }
+        benchmark::validate(request)?;
+
+        let mut files = SyntheticFiles::new(request);
+        let (mut hashing, mut matching) = (Duration::ZERO, Duration::ZERO);
+        let mut bytes_scanned = 0;
+
+        while let Some(size) = files.next_file() {
+            let mut scan = self.begin_bytes_scan();
+
+            while scan.bytes_received() < size {
+                let chunk = files.chunk(scan.bytes_received(), size);
+                let started = Instant::now();
+                scan.update(chunk);
+                hashing += started.elapsed();
+            }
+            bytes_scanned += size;
+
+            let started = Instant::now();
+            self.finish_bytes_scan(scan, "benchmark");
+            matching += started.elapsed();
+        }
+
+        let elapsed = hashing + matching;
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+
+        Ok(BenchmarkReport {
+            file_count: request.file_count,
+            bytes_scanned,
+            elapsed,
+            files_per_sec: request.file_count as f64 / secs,
+            mb_per_sec: bytes_scanned as f64 / (1024.0 * 1024.0) / secs,
+            engines: vec![EngineTiming {
+                engine: "file_hash".to_string(),
+                hashing,
+                matching,
+            }],
+        })
+    }
+
+    /// Takes a slot to serve an on-demand request in, a running scan counting as using one.
+    ///
+    /// # Returns
+    ///
+    /// The slot, freed when the permit is dropped, or the response to refuse the request with as the engine is busy.
+    pub fn acquire_on_demand(&self, priority: RequestPriority) -> Result<OnDemandPermit, EngineBusy> {
+        self.on_demand.try_acquire(priority, self.scan_in_flight.load(Ordering::SeqCst))
+    }
+
+    /// Clears the results of the last scan and returns the scanner to the Inactive state, so that stale results are not
+    /// served to the GUI after the user has dismissed them.
+    ///
+    /// # Returns
+    ///
+    /// An error if a scan is currently in progress, in which case nothing is cleared.
+    pub fn reset(&self) -> Result<(), String> {
+        let mut lock = self.state.lock().unwrap();
+        if *lock == FileScannerState::Scanning || self.scan_in_flight.load(Ordering::SeqCst) {
+            return Err("Cannot reset the scanner whilst a scan is in progress.".to_string());
+        }
+
+        self.set_state(&mut lock, FileScannerState::Inactive);
+        self.scanning_info.lock().unwrap().reset();
+
+        Ok(())
+    }
+
+    /// Sets the state, publishing it to the snapshot read by [`FileScanner::get_state`]. This takes the state lock's
+    /// guard so that the state can only be changed under the lock, and the snapshot is always the latest state.
+    fn set_state(&self, lock: &mut MutexGuard<FileScannerState>, state: FileScannerState) {
+        self.state_snapshot.send_replace(state.clone());
+        **lock = state;
+    }
+
+    /// Checks whether the scan has been cancelled, without cloning the state as [`FileScanner::get_state`] does, for
+    /// checks made for every file or chunk of a file
+    fn is_cancelled(&self) -> bool {
+        *self.state_snapshot.borrow() == FileScannerState::Cancelled
+    }
+
+    /// Gets the state of the scanner. This reads the published snapshot of the state rather than taking the state lock,
+    /// so it returns straight away however busy the scanner is.
+    pub fn get_state(&self) -> FileScannerState {
+        self.state_snapshot.borrow().clone()
+    }
+
+    /// Gets the stats of the current or most recent scan, without its detections, which are paged through with
+    /// [`FileScanner::results_page`] so that polling a scan with thousands of detections stays cheap.
+    pub fn scanner_get_scan_data(&self) -> ScanningLiveInfo {
+        let sli = self.scanning_info.lock().unwrap();
+
+        ScanningLiveInfo {
+            scan_results: Vec::new(),
+            detection_count: sli.scan_results.len(),
+            changed_during_scan: sli.changed_during_scan.clone(),
+            stalled_files: sli.stalled_files.clone(),
+            roots: sli.roots.clone(),
+            inaccessible: sli.inaccessible.clone(),
+            skipped_in_use: sli.skipped_in_use.clone(),
+            extensions: sli.extensions.clone(),
+            cancel_reason: sli.cancel_reason.clone(),
+            target: sli.target.clone(),
+            ..*sli
+        }
+    }
+
+    /// Gets a page of the detections of the scan, which may be the scan in progress or a scan in the history.
+    ///
+    /// # Returns
+    ///
+    /// The page, which is empty where the offset is beyond the last detection, or an error if the scan is neither the
+    /// current or most recent scan nor in the history.
+    pub fn results_page(&self, request: &ResultsPageRequest) -> Result<ResultsPage, String> {
+        let page = |results: &[MatchedIOC], complete: bool| ResultsPage {
+            scan_id: request.scan_id,
+            offset: request.offset,
+            total: results.len(),
+            results: results
+                .iter()
+                .skip(request.offset)
+                .take(request.limit.min(MAX_RESULTS_PAGE_LEN))
+                .cloned()
+                .collect(),
+            complete,
+        };
+
+        {
+            let sli = self.scanning_info.lock().unwrap();
+            if sli.scan_id == Some(request.scan_id) {
+                // the scan in progress has not ended until it has been moved out of the Scanning state
+                let complete = !self.scan_in_flight.load(Ordering::SeqCst);
+                return Ok(page(&sli.scan_results, complete));
+            }
+        }
+
+        self.history
+            .find(request.scan_id)
+            .map(|record| page(&record.detections, true))
+            .ok_or_else(|| format!("No scan with ID {} was found.", request.scan_id))
+    }
+
+    /// Applies the scanner related settings, such as exclusions and fuzzy hashing, to subsequent scans. The settings
+    /// are validated in full before any are applied, so invalid settings leave the current settings in place.
+    ///
+    /// # Returns
+    ///
+    /// An error describing the first invalid setting.
+    pub fn apply_settings(&self, settings: &SanctumSettings) -> Result<(), String> {
+        let (compiled, heuristics) = Self::validate_settings(settings)?;
+
+        *self.exclusions.write().unwrap() = compiled;
+        *self.fuzzy_settings.write().unwrap() = settings.fuzzy_hashing.clone();
+        *self.disabled_databases.write().unwrap() = settings.disabled_signature_databases.as_slice().into();
+        *self.heuristics.write().unwrap() = heuristics;
+        self.stall_timeout_secs.store(settings.scan_watchdog.stall_timeout_secs, Ordering::Relaxed);
+        self.journal.set_enabled(settings.scan_journal.enabled);
+        self.notifier.set_limit(settings.notifications.detection_toast_limit);
+        self.on_demand.set_limits(&settings.on_demand);
+        self.memory.set_limits(&settings.memory_pressure);
+        self.skip_remote_volumes.store(settings.remote_volumes.skip_remote_volumes, Ordering::Relaxed);
+        *self.verdict_precedence.write().unwrap() = settings.verdicts.precedence.as_slice().into();
+        *self.jit_modules.write().unwrap() = settings.injection.jit_modules.clone();
+        self.set_detection_engines(settings.detection_engines);
+
+        Ok(())
+    }
+
+    /// Validates the scanner related settings as [`FileScanner::apply_settings`] does, without applying them
+    pub fn check_settings(settings: &SanctumSettings) -> Result<(), String> {
+        Self::validate_settings(settings).map(|_| ())
+    }
+
+    /// Validates the scanner related settings, compiling the exclusions and heuristics they hold
+    fn validate_settings(settings: &SanctumSettings) -> Result<(CompiledExclusions, CompiledHeuristics), String> {
+        let compiled = CompiledExclusions::compile(&settings.exclusions)?;
+        let heuristics = CompiledHeuristics::compile(&settings.heuristics)?;
+
+        let threshold = settings.fuzzy_hashing.similarity_threshold;
+        if !(1..=100).contains(&threshold) {
+            return Err(format!(
+                "Fuzzy hash similarity threshold must be between 1 and 100, got {threshold}."
+            ));
+        }
+
+        if settings.detection_engines.has_unknown_bits() {
+            return Err(format!(
+                "Unknown detection engines set in flags {:#x}.",
+                settings.detection_engines.0
+            ));
+        }
+
+        let stall_timeout_secs = settings.scan_watchdog.stall_timeout_secs;
+        if stall_timeout_secs < MIN_STALL_TIMEOUT_SECS {
+            return Err(format!(
+                "Scan stall timeout must be at least {MIN_STALL_TIMEOUT_SECS} seconds, got {stall_timeout_secs}."
+            ));
+        }
+
+        let on_demand = &settings.on_demand;
+        if on_demand.max_concurrent == 0 {
+            return Err("At least one on-demand request must be allowed at once.".to_string());
+        }
+        if on_demand.reserved_critical >= on_demand.max_concurrent {
+            return Err(format!(
+                "The on-demand slots reserved for critical requests ({}) must be fewer than the most allowed at once \
+                ({}), or no other request could be served.",
+                on_demand.reserved_critical, on_demand.max_concurrent
+            ));
+        }
+
+        let precedence = &settings.verdicts.precedence;
+        if precedence.iter().any(|engine| engine.trim().is_empty()) {
+            return Err("An engine in the verdict precedence has no name.".to_string());
+        }
+        if let Some(engine) =
+            precedence.iter().enumerate().find_map(|(i, engine)| precedence[..i].contains(engine).then_some(engine))
+        {
+            return Err(format!("The engine {engine} is named more than once in the verdict precedence."));
+        }
+
+        if settings.injection.jit_modules.iter().any(|module| module.trim().is_empty()) {
+            return Err("A JIT module in the injection settings has no file name.".to_string());
+        }
+
+        Ok((compiled, heuristics))
+    }
+
+    /// Injects a made up detection of the file through the detection pipeline, for testing the alerting path end to end
+    /// without real malware. The request's action is taken on the file, the user is notified, and the detection is
+    /// published as a detection found by a scan would be, reaching the SIEM and any subscribed GUI. The detection
+    /// belongs to no scan, so it is not in the results or history of a scan, and is notified individually rather than
+    /// being coalesced with the detections of a scan.
+    ///
+    /// This is only served by an engine built with the `test_mode` feature, so a production engine can never be made
+    /// to act on a file it did not detect.
+    ///
+    /// # Returns
+    ///
+    /// The detection as published, with the action taken, or an error if simulations are not enabled in this build or
+    /// the request is invalid.
+    pub fn simulate_detection(&self, request: &SimulatedDetectionRequest) -> Result<MatchedIOC, String> {
+        if !cfg!(feature = "test_mode") {
+            return Err("Simulating detections requires an engine built with the test_mode feature.".to_string());
+        }
+
+        if request.name.trim().is_empty() {
+            return Err("A simulated detection must have a name.".to_string());
+        }
+        if request.severity > MAX_SIMULATED_SEVERITY {
+            return Err(format!("Severity must be between 0 and {MAX_SIMULATED_SEVERITY}, got {}.", request.severity));
+        }
+
+        // the hash names the file in the quarantine, so a file which cannot be read is still given one
+        let hash = self
+            .hash_file(&HashRequest {
+                path: request.path.clone(),
+                algorithms: vec![HashAlgorithm::Md5],
+            })
+            .ok()
+            .and_then(|hashes| hashes.into_iter().next())
+            .map(|hash| hash.hex)
+            .unwrap_or_else(|| "0".repeat(32));
+
+        let mut ioc = MatchedIOC {
+            hash,
+            file: request.path.clone(),
+            match_mode: MatchMode::Simulated {
+                name: request.name.clone(),
+                severity: request.severity,
+            },
+            action_taken: ActionResult::None,
+            source: Some("simulation".to_string()),
+            verdicts: Vec::new(),
+            resolved_by: None,
+            conditions: Vec::new(),
+        };
+        ioc.action_taken = take_action(request.action, &ioc, &self.quarantine);
+
+        self.log.log(
+            LogLevel::Warning,
+            &format!("Simulated detection {} of {}, action: {:?}", request.name, ioc.file.display(), ioc.action_taken),
+        );
+
+        self.events.publish(EngineEvent::Notify {
+            title: "Threat detected (simulated)".to_string(),
+            message: format!("{} was detected as {}.", ioc.file.display(), request.name),
+            scan_id: None,
+        });
+        self.events.publish(EngineEvent::Detection {
+            scan_id: None,
+            ioc: ioc.clone(),
+        });
+
+        Ok(ioc)
+    }
+
+    /// Gets the bytes which matched the detection of the file in the current or most recent scan, along with an
+    /// analyst's triage of its detection.
+    ///
+    /// # Returns
+    ///
+    /// The matched bytes and the triage of the detection, or an error if neither is known for the file. Bytes are
+    /// only captured for PE section hash detections, as the other engines match the file as a whole, so for other
+    /// detections only the triage is returned.
+    pub fn detection_details(&self, file: &Path) -> Result<DetectionDetails, String> {
+        let annotation = self.history.latest_annotation(file);
+        let captured = self.detection_details.lock().unwrap().get(file).cloned();
+
+        match (captured, annotation) {
+            (Some(details), annotation) => Ok(DetectionDetails { annotation, ..details }),
+            (None, Some(annotation)) => Ok(DetectionDetails {
+                file: file.to_path_buf(),
+                regions: Vec::new(),
+                annotation: Some(annotation),
+            }),
+            (None, None) => Err(format!(
+                "No matched bytes were captured for {}. Bytes are only captured for PE section hash detections in \
+                the most recent scan.",
+                file.display()
+            )),
+        }
+    }
+
+    /// Gets the detection engines which currently run during a scan
+    pub fn detection_engines(&self) -> EngineFlags {
+        EngineFlags(self.detection_engines.load(Ordering::Relaxed))
+    }
+
+    /// Sets the detection engines which run, applying to any scan in progress from the next file
+    fn set_detection_engines(&self, engines: EngineFlags) {
+        self.detection_engines.store(engines.0, Ordering::Relaxed);
+
+        // record the newly enabled engines against the running scan
+        if *self.state.lock().unwrap() == FileScannerState::Scanning {
+            let mut sli = self.scanning_info.lock().unwrap();
+            sli.engines |= engines;
+        }
+    }
+
+    /// Checks whether the path is excluded from scanning, either by the user's exclusions or by default for the
+    /// current scan
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.scan_skips.read().unwrap().iter().any(|skip| skip == path)
+            || self.exclusions.read().unwrap().is_excluded(path)
+    }
+
+    /// Tests whether the path would be excluded from scans by the user's exclusions, and by which rule. This uses the
+    /// same compiled exclusions as the scanner, so the answer is what a scan would do. A path is excluded when it, or
+    /// any folder above it, matches an exclusion.
+    ///
+    /// Paths skipped by default for a particular scan, such as the reserved folders at the root of a volume, are not
+    /// included, as they are not part of the user's exclusions.
+    pub fn test_exclusion(&self, path: &Path) -> ExclusionMatch {
+        let exclusions = self.exclusions.read().unwrap();
+
+        for ancestor in path.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            if let Some(rule) = exclusions.matching_rule(ancestor) {
+                return ExclusionMatch {
+                    excluded: true,
+                    rule: Some(rule.clone()),
+                    matched_path: Some(ancestor.to_path_buf()),
+                };
+            }
+        }
+
+        ExclusionMatch {
+            excluded: false,
+            rule: None,
+            matched_path: None,
+        }
+    }
+
+    /// Previews what adding an exclusion would have changed about an earlier scan in the scan history: which of its
+    /// detections the exclusion would have excluded, and how many files it would have kept out of the scan. The
+    /// exclusion is not added to the exclusions.
+    ///
+    /// The scan history does not keep the files each scan scanned, so the files are counted by walking the folders
+    /// the scan walked as they are now, applying the current exclusions, as with an estimate. The walk shares the
+    /// estimate's guard, so runs whilst no estimate does and is cancelled with [`FileScanner::cancel_estimate`]. It
+    /// blocks until the walk completes, so should be run off of the async runtime.
+    ///
+    /// # Returns
+    ///
+    /// The preview, or an error if the exclusion is not valid, the scan is not in the history, or an estimate is
+    /// already in progress.
+    pub fn preview_exclusion(&self, request: &ExclusionPreviewRequest) -> Result<ExclusionPreview, String> {
+        let candidate = CompiledExclusions::compile(&ExclusionList {
+            rules: vec![request.rule.clone()],
+        })?;
+        let record = self
+            .history
+            .find(request.scan_id)
+            .ok_or_else(|| format!("Scan {} was not found in the scan history.", request.scan_id))?;
+
+        let mut preview = ExclusionPreview {
+            scan_id: request.scan_id,
+            detections_total: record.detections.len(),
+            detections_excluded: record
+                .detections
+                .iter()
+                .filter(|ioc| excluded_beneath(&candidate, &ioc.file))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        let walked = match (record.roots.is_empty(), &record.target) {
+            (false, _) => record.roots,
+            (true, Some(ScanTarget::File(file))) => vec![file.clone()],
+            (true, Some(ScanTarget::Folder(folders))) => folders.clone(),
+            _ => return Ok(preview),
+        };
+
+        if self.estimate_in_flight.swap(true, Ordering::SeqCst) {
+            return Err("A scan estimate is already in progress.".to_string());
+        }
+        self.estimate_cancelled.store(false, Ordering::SeqCst);
+
+        preview.files_counted = true;
+        self.walk_for_preview(&mut preview, &walked, &candidate);
+
+        self.estimate_in_flight.store(false, Ordering::SeqCst);
+
+        Ok(preview)
+    }
+
+    /// Walks the folders of a scan for a preview of an exclusion, counting the files the current exclusions let
+    /// through and which of those the candidate exclusion would exclude
+    fn walk_for_preview(&self, preview: &mut ExclusionPreview, targets: &[PathBuf], candidate: &CompiledExclusions) {
+        // each folder is walked with whether the candidate excludes it, so that everything beneath is counted as
+        // excluded without being matched again
+        let mut discovered_dirs: Vec<(PathBuf, bool)> = Vec::new();
+
+        let count = |preview: &mut ExclusionPreview, path: &Path, excluded: bool, metadata: Option<fs::Metadata>| {
+            if self.exclusions.read().unwrap().is_excluded(path) {
+                return None;
+            }
+
+            let excluded = excluded || candidate.is_excluded(path);
+
+            if path.is_dir() {
+                return Some((path.to_path_buf(), excluded));
+            }
+
+            if let Some(metadata) = metadata.or_else(|| fs::metadata(path).ok()) {
+                preview.files_total += 1;
+                if excluded {
+                    preview.files_excluded += 1;
+                    preview.bytes_excluded += metadata.len();
+                }
+            }
+
+            None
+        };
+
+        for target in targets {
+            let excluded = excluded_beneath(candidate, target);
+            if let Some(dir) = count(preview, target, excluded, None) {
+                discovered_dirs.push(dir);
+            }
+        }
+
+        while let Some((dir, excluded)) = discovered_dirs.pop() {
+            let Ok(read_dir) = read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in read_dir.map_while(Result::ok) {
+                if self.estimate_cancelled.load(Ordering::SeqCst) {
+                    preview.cancelled = true;
+                    return;
+                }
+
+                if let Some(dir) = count(preview, &entry.path(), excluded, entry.metadata().ok()) {
+                    discovered_dirs.push(dir);
+                }
+            }
+        }
+    }
+
+    /// Gets the signature sets currently matched against
+    fn signatures(&self) -> Arc<SignatureSet> {
+        Arc::clone(&self.signatures.read().unwrap())
+    }
+
+    /// Gets the detectors each file is currently given to, in the order they run
+    fn detectors(&self) -> Arc<[Arc<dyn Detector>]> {
+        Arc::clone(&self.detectors.read().unwrap())
+    }
+
+    /// Gets the order in which the verdicts of the detectors currently take precedence
+    fn verdict_precedence(&self) -> Arc<[String]> {
+        Arc::clone(&self.verdict_precedence.read().unwrap())
+    }
+
+    /// Gets the names of the signature databases which are currently not matched against
+    fn disabled_databases(&self) -> Arc<[String]> {
+        Arc::clone(&self.disabled_databases.read().unwrap())
+    }
+
+    /// Reloads the signatures from disk, picking up signature files which have been added to or removed from the
+    /// signatures folder. The new set replaces the old one whole once it has loaded, so a running scan carries on
+    /// without interruption, matching each file from then on against the new set.
+    ///
+    /// # Returns
+    ///
+    /// The number of signatures now loaded, or an error if the new set could not be loaded, in which case the
+    /// current set remains in use.
+    pub async fn reload_signatures(&self) -> Result<SignatureCounts, String> {
+        let signatures = SignatureSet::load()
+            .await
+            .map_err(|e| format!("Could not reload signatures. {e}"))?;
+        let counts = signatures.counts();
+
+        *self.signatures.write().unwrap() = Arc::new(signatures);
+
+        Ok(counts)
+    }
+
+    /// Begins an update of the signatures or the engine's binaries. Until the update is committed or aborted, or is
+    /// left open for longer than `MAX_UPDATE_WINDOW`, no scan can be started, and removable media inserted in the
+    /// meantime waits to be scanned. A running scan is cancelled, and its results recorded in the scan history, so
+    /// that nothing reads the files being replaced. Real-time protection carries on with the signatures loaded when
+    /// the update began.
+    ///
+    /// Beginning an update which has already begun waits again for the scans to wind down, so an updater told the
+    /// engine is not ready can ask again.
+    ///
+    /// # Returns
+    ///
+    /// Whether every scan has ended within [`UPDATE_QUIESCE_TIMEOUT`] so the update can be applied, or an error
+    /// where an update is already waiting on the engine to restart.
+    pub async fn update_begin(&self) -> Result<UpdateReadiness, String> {
+        if self.update.restart_required() {
+            return Err("An update is already installed, and is waiting on the engine to restart.".to_string());
+        }
+
+        if self.update.open() {
+            self.log.log(LogLevel::Info, "Update begun, scans are paused until it ends.");
+        }
+
+        let scans_cancelled: Vec<u64> =
+            self.cancel_scan_with_reason(CANCEL_REASON_UPDATE).and_then(|sli| sli.scan_id).into_iter().collect();
+        self.cancel_estimate();
+
+        // a cancelled scan stops at the next file, then records itself in the history
+        let waited = Instant::now();
+        while self.scan_in_flight.load(Ordering::SeqCst) && waited.elapsed() < UPDATE_QUIESCE_TIMEOUT {
+            tokio::time::sleep(UPDATE_QUIESCE_POLL_INTERVAL).await;
+        }
+
+        Ok(UpdateReadiness {
+            ready: !self.scan_in_flight.load(Ordering::SeqCst),
+            scans_cancelled,
+        })
+    }
+
+    /// Commits the update begun with [`FileScanner::update_begin`]. Where the engine's binaries were replaced, scans
+    /// stay refused until the engine restarts. Otherwise the signatures are reloaded and scans resume.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the update, or an error where no update was begun, or the new signatures could not be loaded.
+    /// When they cannot be loaded the update stays open, with the signatures from before it still in use, so the
+    /// updater can put back the files it replaced and abort.
+    pub async fn update_commit(&self, restart_required: bool) -> Result<UpdateCommitted, String> {
+        if !self.update.is_open() {
+            return Err("No update is in progress.".to_string());
+        }
+
+        if restart_required {
+            self.update.require_restart();
+            self.log.log(LogLevel::Warning, "Update installed, the engine must restart to finish it.");
+            self.events.publish(EngineEvent::Notify {
+                title: "Update installed".to_string(),
+                message: "Restart the engine to finish the update.".to_string(),
+                scan_id: None,
+            });
+
+            return Ok(UpdateCommitted {
+                restart_required,
+                signatures: None,
+            });
+        }
+
+        let signatures = self.reload_signatures().await?;
+        self.update.close();
+        self.log.log(LogLevel::Info, "Update committed, scans have resumed.");
+
+        Ok(UpdateCommitted {
+            restart_required,
+            signatures: Some(signatures),
+        })
+    }
+
+    /// Aborts the update begun with [`FileScanner::update_begin`], allowing scans again. The signatures are not
+    /// reloaded, so those loaded before the update stay in use.
+    ///
+    /// # Returns
+    ///
+    /// Whether an update was in progress.
+    pub fn update_abort(&self) -> bool {
+        let aborted = self.update.close();
+        if aborted {
+            self.log.log(LogLevel::Info, "Update aborted, scans have resumed.");
+        }
+
+        aborted
+    }
+
+    /// Whether an update is in progress, during which no scan is started
+    pub fn is_updating(&self) -> bool {
+        self.update.is_open()
+    }
+
+    /// Imports the signatures from a file in a third party format into the signatures folder, then reloads the
+    /// signatures so they are matched from the next file scanned.
+    ///
+    /// # Returns
+    ///
+    /// How many signatures were imported and skipped, or an error if the file could not be read, no signature could
+    /// be imported from it, or the signatures could not be reloaded.
+    pub async fn import_signatures(
+        &self,
+        format: SignatureImportFormat,
+        path: &Path,
+    ) -> Result<SignatureImportReport, String> {
+        let mut report = import_signatures(format, path)?;
+        report.signatures = self.reload_signatures().await?;
+
+        self.log.log(
+            LogLevel::Info,
+            &format!(
+                "Imported {} signatures from {} to {}, skipped {} malformed and {} unsupported.",
+                report.imported,
+                path.display(),
+                report.file.display(),
+                report.skipped_malformed,
+                report.skipped_unsupported
+            ),
+        );
+
+        Ok(report)
+    }
+
+    /// Exports the signatures of the enabled databases to a single file, which can be imported on another machine. The
+    /// signatures exported are those of the set matched against when the export starts, so a reload part way through
+    /// does not mix two sets in the one export.
+    pub fn export_signatures(&self, dest: &Path) -> Result<SignatureExportReport, String> {
+        let signatures = self.signatures();
+        let disabled = self.disabled_databases();
+        let report = export_signatures(&signatures, &disabled, dest)?;
+
+        self.log.log(
+            LogLevel::Info,
+            &format!(
+                "Exported {} hash, {} section, {} import hash, {} fuzzy and {} registry signatures from databases {:?} \
+                to {}.",
+                report.signatures.hash_signatures,
+                report.signatures.section_signatures,
+                report.signatures.import_hash_signatures,
+                report.signatures.fuzzy_signatures,
+                report.signatures.registry_signatures,
+                report.databases,
+                dest.display()
+            ),
+        );
+
+        Ok(report)
+    }
+
+    /// Checks a signature file without loading it, so a file can be checked before it is put in the signatures folder.
+    /// The signatures matched against are left untouched.
+    pub fn validate_signatures(&self, path: &Path) -> Result<SignatureValidationReport, String> {
+        let report = validate_signature_file(path)?;
+
+        self.log.log(
+            LogLevel::Info,
+            &format!(
+                "Validated signature file {}, {} with {} issues.",
+                path.display(),
+                if report.valid { "valid" } else { "invalid" },
+                report.issues.len()
+            ),
+        );
+
+        Ok(report)
+    }
+
+    /// Gets the version string identifying the loaded IOC list
+    pub fn signature_version(&self) -> String {
+        self.signatures().version.clone()
+    }
+
+    /// Gets the number of signatures loaded from the IOC list
+    pub fn signature_count(&self) -> usize {
+        self.signatures().count
+    }
+
+    /// Gets the number of signatures loaded into each detection engine. The counts are all taken from the signature
+    /// sets the scanner is matching against, so always describe the active sets.
+    pub fn signature_counts(&self) -> SignatureCounts {
+        self.signatures().counts()
+    }
+
+    /// Gets the outcome of loading each file in the signatures folder, for the set currently matched against
+    pub fn signature_files(&self) -> Vec<SignatureFileStats> {
+        self.signatures().files.clone()
+    }
+
+    /// Gets each signature database loaded, and whether it is matched against
+    pub fn signature_databases(&self) -> Vec<SignatureDatabaseInfo> {
+        self.signatures().database_info(&self.disabled_databases())
+    }
+
+    /// Whether a signature database of the given name is loaded
+    pub fn has_signature_database(&self, name: &str) -> bool {
+        self.signatures().has_database(name)
+    }
+
+    /// Adds a detector to those run on every file scanned, from the next file scanned. Registered detectors run in the
+    /// order they were registered, after the signature engines and ahead of the heuristics, and their verdicts take
+    /// precedence as the verdict settings give.
+    pub fn register_detector(&self, detector: Box<dyn Detector>) -> Result<(), String> {
+        let mut registered = self.detectors.write().unwrap();
+        if registered.iter().any(|d| d.name() == detector.name()) {
+            return Err(format!("A detector named {} is already registered.", detector.name()));
+        }
+
+        let mut detectors = registered.to_vec();
+        let heuristics = detectors
+            .iter()
+            .position(|d| d.name() == HeuristicDetector::NAME)
+            .unwrap_or(detectors.len());
+        detectors.insert(heuristics, Arc::from(detector));
+        *registered = detectors.into();
+
+        Ok(())
+    }
+
+    /// Gets how the verdicts of the detectors are resolved, being every detector loaded in order of precedence, and
+    /// any engine the precedence in the settings names which is not loaded
+    pub fn verdict_policy(&self) -> VerdictPolicy {
+        let precedence = self.verdict_precedence();
+        let mut engines: Vec<String> =
+            self.detectors().iter().map(|d| d.name().to_string()).collect();
+        // the sort is stable, so detectors ranked alike keep the order they run in
+        engines.sort_by_key(|engine| verdict_rank(&precedence, engine));
+
+        let unknown = precedence
+            .iter()
+            .filter(|engine| *engine != OTHER_ENGINES && !engines.contains(engine))
+            .cloned()
+            .collect();
+
+        VerdictPolicy {
+            precedence: engines,
+            unknown,
+        }
+    }
+
+    /// Inspects the memory of a running process for signs that code was injected into it, such as writable and
+    /// executable memory or a module which no longer matches its file. This can take a while on a process with a large
+    /// address space, so should be run off of the async runtime.
+    ///
+    /// # Returns
+    ///
+    /// What was found, or an error if the process could not be opened, such as a protected process.
+    pub fn inspect_process_memory(&self, pid: u32) -> Result<ProcessMemoryReport, String> {
+        let jit_modules = self.jit_modules.read().unwrap().clone();
+        let report = inspect_process(pid, &jit_modules)?;
+
+        if !report.findings.is_empty() {
+            self.log.log(
+                LogLevel::Warning,
+                &format!(
+                    "Found {} signs of injected code in process {pid} ({}).",
+                    report.findings.len(),
+                    report.image.display()
+                ),
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Scores the file on the heuristics, against the threshold of the scan where the file is part of a scan which
+    /// overrides it
+    pub(super) fn evaluate_heuristics(
+        &self,
+        path: &Path,
+        file: &File,
+        in_scan: bool,
+    ) -> Result<Option<(u32, Vec<HeuristicSignal>)>, io::Error> {
+        let threshold = match in_scan {
+            true => self.scan_thresholds.read().unwrap().heuristic_score,
+            false => None,
+        };
+
+        self.heuristics.read().unwrap().evaluate(path, file, threshold)
+    }
+
+    /// Explains why the heuristics did not detect the file, by its score against the threshold in the settings
+    pub(super) fn explain_heuristics(&self, path: &Path, file: &File) -> io::Result<NotDetectedReason> {
+        let heuristics = self.heuristics.read().unwrap();
+
+        Ok(match heuristics.score(path, file)? {
+            Some((score, _)) => NotDetectedReason::BelowThreshold {
+                score,
+                threshold: heuristics.score_threshold(),
+            },
+            None => NotDetectedReason::DisabledInSettings,
+        })
+    }
+}
+
+/// The thread the files of a scan are scanned on, watched by the thread walking the scan's folders. A thread blocked
+/// in a read cannot be stopped, so a worker which stalls is abandoned for a new one; the abandoned thread exits once it
+/// finishes the file, if it ever does, as nothing is left to send it files or receive its verdict.
+/// The longest extension which is counted without allocating
+const INLINE_EXTENSION_LEN: usize = 15;
+
+/// The files and bytes a scan has scanned, counted by the extension of each file on the thread walking the scan
+#[derive(Default)]
+struct ExtensionCounts {
+    /// Extensions of ASCII no longer than [`INLINE_EXTENSION_LEN`], which is nearly all of them, keyed on the stack
+    inline: HashMap<InlineExtension, (u64, u64)>,
+    /// Every other extension, in lowercase
+    other: HashMap<String, (u64, u64)>,
+}
+
+/// An extension in lowercase ASCII, held inline
+#[derive(PartialEq, Eq, Hash)]
+struct InlineExtension {
+    len: u8,
+    bytes: [u8; INLINE_EXTENSION_LEN],
+}
+
+impl InlineExtension {
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl ExtensionCounts {
+    /// Counts a file the scan has scanned against its extension, or against no extension where it has none
+    fn record(&mut self, path: &Path, len: u64) {
+        let extension = path.extension().unwrap_or_default();
+        let raw = extension.as_encoded_bytes();
+
+        let counts = if raw.len() <= INLINE_EXTENSION_LEN && raw.is_ascii() {
+            let mut bytes = [0; INLINE_EXTENSION_LEN];
+            bytes[..raw.len()].copy_from_slice(raw);
+            bytes.make_ascii_lowercase();
+
+            self.inline
+                .entry(InlineExtension { len: raw.len() as u8, bytes })
+                .or_default()
+        } else {
+            self.other.entry(extension.to_string_lossy().to_lowercase()).or_default()
+        };
+
+        counts.0 += 1;
+        counts.1 += len;
+    }
+}
+
+struct ScanWorker {
+    files: mpsc::Sender<PathBuf>,
+    verdicts: mpsc::Receiver<Result<FileVerdict, io::Error>>,
+}
+
+/// What became of a file given to a scan of part of the system
+enum SystemFileOutcome {
+    Scanned(FileVerdict),
+    /// The file matched an exclusion, so was not scanned
+    Excluded,
+    /// The file was not there to scan
+    Missing,
+    /// The file could not be scanned, which has been logged
+    Failed,
+}
+
+/// How a scan of part of the system went, once it has ended
+struct SystemScanSummary {
+    /// The files scanned, each counted once however many contexts it was given with
+    files_scanned: usize,
+    cancelled: bool,
+}
+
+/// The outcome of scanning a file on a worker
+enum WorkerOutcome {
+    Scanned(Result<FileVerdict, io::Error>),
+    /// The worker made no progress on the file for this long
+    Stalled(Duration),
+    /// The worker exited without a verdict, as it panicked
+    Exited,
+}
+
+impl ScanWorker {
+    fn spawn(scanner: &Arc<FileScanner>, files_scanned: &Arc<Mutex<u32>>) -> Self {
+        let (files, jobs) = mpsc::channel::<PathBuf>();
+        let (results, verdicts) = mpsc::channel();
+        let scanner = Arc::clone(scanner);
+        let files_scanned = Arc::clone(files_scanned);
+
+        thread::spawn(move || {
+            // the buffer files are read into whilst hashing, reused for every file the worker scans
+            let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+
+            for path in jobs {
+                let verdict = scanner.scan_file_against_hashes(&path, &files_scanned, &mut scratch);
+                if results.send(verdict).is_err() {
+                    break;
+                }
+            }
+        });
+
+        ScanWorker { files, verdicts }
+    }
+
+    /// Scans the file on the worker, waiting for as long as the worker keeps making progress on it
+    fn scan(&self, scanner: &FileScanner, path: &Path, stall_timeout: Duration) -> WorkerOutcome {
+        if self.files.send(path.to_path_buf()).is_err() {
+            return WorkerOutcome::Exited;
+        }
+
+        let mut heartbeat = scanner.scan_heartbeat.load(Ordering::Relaxed);
+        let mut last_progress = Instant::now();
+        let mut last_published = Instant::now();
+
+        loop {
+            match self.verdicts.recv_timeout(WATCHDOG_POLL_INTERVAL) {
+                Ok(verdict) => return WorkerOutcome::Scanned(verdict),
+                Err(RecvTimeoutError::Disconnected) => return WorkerOutcome::Exited,
+                Err(RecvTimeoutError::Timeout) => {
+                    // a large file can take minutes to hash, so its progress is published whilst it is waited on
+                    if last_published.elapsed() >= PROGRESS_INTERVAL && scanner.file_progress().is_some() {
+                        scanner.publish_progress();
+                        last_published = Instant::now();
+                    }
+
+                    let beat = scanner.scan_heartbeat.load(Ordering::Relaxed);
+                    if beat != heartbeat {
+                        heartbeat = beat;
+                        last_progress = Instant::now();
+                    } else if last_progress.elapsed() >= stall_timeout {
+                        return WorkerOutcome::Stalled(last_progress.elapsed());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reports a large file as the one the scan is hashing for as long as it is held, however hashing the file ends
+struct LargeFileProgress<'a> {
+    scanner: &'a FileScanner,
+}
+
+impl<'a> LargeFileProgress<'a> {
+    fn start(scanner: &'a FileScanner, path: &Path, total_bytes: u64) -> Self {
+        scanner.large_file_bytes.store(0, Ordering::Relaxed);
+        *scanner.large_file.lock().unwrap() = Some((path.to_path_buf(), total_bytes));
+
+        LargeFileProgress { scanner }
+    }
+}
+
+impl Drop for LargeFileProgress<'_> {
+    fn drop(&mut self) {
+        *self.scanner.large_file.lock().unwrap() = None;
+    }
+}
+
+/// What the detectors found in a file, before it is reported or acted on
+struct FileInspection {
+    md5: Option<HexDigest>,
+    sha256: Option<HexDigest>,
+    /// The verdict of each detector, left empty for a clean file in a scan
+    verdicts: Vec<EngineVerdict>,
+    /// The detection reported and the regions of the file it matched, None where the file is clean
+    detection: Option<(MatchedIOC, Vec<MatchedBytes>)>,
+}
+
+/// The attributes of a file which change when it is written to or replaced, used to detect a file changing whilst
+/// it is being scanned
+#[derive(PartialEq)]
+struct FileFingerprint {
+    size: u64,
+    last_write_time: u64,
+    creation_time: u64,
+}
+
+impl FileFingerprint {
+    fn of(metadata: &fs::Metadata) -> Self {
+        FileFingerprint {
+            size: metadata.file_size(),
+            last_write_time: metadata.last_write_time(),
+            creation_time: metadata.creation_time(),
+        }
+    }
+}
+
+/// Gets the root of a folder scan being walked
+fn current_root(roots: &[RootProgress]) -> Option<PathBuf> {
+    roots
+        .iter()
+        .find(|root| root.state == RootState::Scanning)
+        .map(|root| root.root.clone())
+}
+
+/// Gets the type of a scan of the files and folders; a single file is a file scan, anything else a folder scan
+fn scan_type_of(targets: &[PathBuf]) -> ScanType {
+    if targets.len() == 1 && targets[0].is_file() {
+        ScanType::File
+    } else {
+        ScanType::Folder
+    }
+}
+
+/// Ranks a detector in the verdict precedence, lowest first. A detector the precedence does not name ranks where the
+/// precedence has [`OTHER_ENGINES`], or after every detector it names where it does not.
+fn verdict_rank(precedence: &[String], name: &str) -> usize {
+    precedence
+        .iter()
+        .position(|engine| engine == name)
+        .or_else(|| precedence.iter().position(|engine| engine == OTHER_ENGINES))
+        .unwrap_or(precedence.len())
+}
+
+/// Gets the target of a scan of the files and folders, as [`scan_type_of`] gets its type
+fn scan_target_of(targets: &[PathBuf]) -> ScanTarget {
+    match scan_type_of(targets) {
+        ScanType::File => ScanTarget::File(targets[0].clone()),
+        _ => ScanTarget::Folder(targets.to_vec()),
+    }
+}
+
+/// Whether the error is of a file another process holds open without sharing it, or has locked the part being read
+fn is_in_use(e: &io::Error) -> bool {
+    e.raw_os_error()
+        .is_some_and(|code| code == ERROR_SHARING_VIOLATION.0 as i32 || code == ERROR_LOCK_VIOLATION.0 as i32)
+}
+
+/// Whether the exclusions exclude the path or any folder above it, as an excluded folder is not walked
+fn excluded_beneath(exclusions: &CompiledExclusions, path: &Path) -> bool {
+    path.ancestors().filter(|a| !a.as_os_str().is_empty()).any(|a| exclusions.is_excluded(a))
+}
+
+/// Gets an error describing the first root of a scan which no longer exists, such as a volume which was removed whilst
+/// it was being scanned
+fn unavailable_target(roots: &[PathBuf]) -> Option<io::Error> {
+    roots.iter().find(|root| !root.exists()).map(|root| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "The scan target {} became unavailable during the scan, so was not fully scanned.",
+                root.display()
+            ),
+        )
+    })
+}
+
+/// Formats a digest as an uppercase hex string, matching the format of the IOC lists.
+pub(super) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Hashes a file as it is read, with MD5 and optionally SHA256, giving the hashes in the format of the IOC lists. Used
+/// both when scanning and when hashing a file for a signature, so the two always agree.
+struct FileHasher {
+    md5: Md5,
+    sha256: Option<Sha256>,
+    /// The number of bytes hashed
+    len: u64,
+}
+
+impl FileHasher {
+    fn new(sha256: bool) -> Self {
+        FileHasher {
+            md5: Md5::new(),
+            sha256: sha256.then(Sha256::new),
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(data);
+        }
+        self.len += data.len() as u64;
+    }
+
+    fn finish(self) -> (HexDigest, Option<HexDigest>) {
+        (
+            HexDigest::of(&self.md5.finalize()),
+            self.sha256.map(|h| HexDigest::of(&h.finalize())),
+        )
+    }
+}
+
+/// An MD5 or SHA256 digest formatted as uppercase hex on the stack, so it can be checked against the IOC lists
+/// without allocating
+struct HexDigest {
+    hex: [u8; 64],
+    len: usize,
+}
+
+impl HexDigest {
+    fn of(digest: &[u8]) -> Self {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+        let mut hex = [0u8; 64];
+        let digest = &digest[..digest.len().min(32)];
+        for (i, byte) in digest.iter().enumerate() {
+            hex[i * 2] = HEX[(byte >> 4) as usize];
+            hex[i * 2 + 1] = HEX[(byte & 0x0F) as usize];
+        }
+
+        HexDigest {
+            hex,
+            len: digest.len() * 2,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // only ever holds ascii hex digits
+        std::str::from_utf8(&self.hex[..self.len]).unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use super::*;
+    use crate::filescanner::{
+        pe::tests::crafted_pe,
+        signatures::tests::{signature_set, signature_set_with_sections},
+    };
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts the allocations made on each thread, so that a test counts only those of the code it runs and not those
+    /// of the tests running alongside it
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// A scanner matching against the MD5 signatures given, which keeps its history and quarantine in the folder
+    pub(crate) fn scanner(dir: &Path, md5: &[&str]) -> FileScanner {
+        FileScanner::with_signatures(
+            Arc::new(EventBus::new()),
+            Arc::new(ScanHistory::load_from(dir)),
+            Arc::new(QuarantineStore::load_from(dir)),
+            signature_set(md5),
+        )
+    }
+
+    /// A folder of its own for the test to keep its files in
+    pub(crate) fn test_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sanctum-{test}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn fresh_scanner_is_inactive() {
+        let dir = test_dir("fresh-scanner");
+        let scanner = scanner(&dir, &[]);
+
+        // queried straight away, as the GUI does when it checks the state of the page
+        let state = scanner.get_state();
+        let serialised = serde_json::to_value(&state).unwrap();
+        drop(scanner);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(state, FileScannerState::Inactive);
+        assert_eq!(state.name(), "Inactive");
+        assert_eq!(serde_json::from_value::<FileScannerState>(serialised).unwrap(), state);
+    }
+
+    /// The number of times the state is queried whilst the scan holds its locks
+    const STATE_QUERIES: usize = 10_000;
+
+    #[test]
+    fn state_is_read_whilst_a_scan_holds_its_locks() {
+        let dir = test_dir("state-during-scan");
+        let scanner = Arc::new(scanner(&dir, &[]));
+
+        // a long scan holds the state and its live stats whilst it works
+        let mut state = scanner.state.lock().unwrap();
+        scanner.set_state(&mut state, FileScannerState::Scanning);
+        let stats = scanner.scanning_info.lock().unwrap();
+
+        let (queried, answers) = std::sync::mpsc::channel();
+        let polling = Arc::clone(&scanner);
+        std::thread::spawn(move || {
+            let scanning = (0..STATE_QUERIES).all(|_| polling.get_state() == FileScannerState::Scanning);
+            let _ = queried.send(scanning);
+        });
+
+        let scanning = answers.recv_timeout(Duration::from_secs(5));
+        drop((stats, state));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(scanning, Ok(true), "the state was not read {STATE_QUERIES} times whilst the scan held its locks");
+    }
+
+    /// The number of times the clean file is inspected whilst allocations are counted
+    const INSPECTIONS: usize = 1000;
+
+    #[test]
+    fn clean_file_not_in_database_does_not_allocate() {
+        let dir = test_dir("clean-path");
+        let path = dir.join("clean.bin");
+        // larger than the scratch buffer, so that the file is hashed in more than one chunk
+        fs::write(&path, vec![0x5A; HASH_CHUNK_SIZE + 1]).unwrap();
+
+        let scanner = scanner(&dir, &["44D88612FEA8A8F36DE82E1278ABB02F"]);
+        scanner.set_detection_engines(EngineFlags::FILE_HASH);
+
+        let file = File::open(&path).unwrap();
+        let mut scratch = vec![0u8; HASH_CHUNK_SIZE];
+        let mut inspect = || {
+            (&file).seek(SeekFrom::Start(0)).unwrap();
+            scanner.inspect_file(&path, &file, &mut scratch, true).unwrap().unwrap()
+        };
+
+        let inspection = inspect();
+        assert!(inspection.detection.is_none());
+        let md5 = to_hex(&Md5::digest(fs::read(&path).unwrap()));
+        assert_eq!(inspection.md5.as_ref().map(HexDigest::as_str), Some(md5.as_str()));
+
+        let before = ALLOCATIONS.with(Cell::get);
+        for _ in 0..INSPECTIONS {
+            drop(inspect());
+        }
+        let allocations = ALLOCATIONS.with(Cell::get) - before;
+
+        drop(file);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(allocations, 0, "{allocations} allocations inspecting a clean file {INSPECTIONS} times");
+    }
+
+    #[test]
+    fn section_signature_matches_after_garbage_is_appended() {
+        let dir = test_dir("section-signature-appended");
+        let payload: &[u8] = b"a payload whose code section is known to be malicious";
+        let original = crafted_pe(0, 0, &[(".text", 0x1000, payload)]);
+
+        // the signature is of the section's raw data as laid out in the file, padding and all
+        let headers = PeHeaders::parse(&mut io::Cursor::new(&original)).unwrap().unwrap();
+        let (offset, len) = headers.sections[0].raw_data_range(headers.file_len).unwrap();
+        let section_md5 = to_hex(&Md5::digest(&original[offset as usize..(offset + len) as usize]));
+
+        let mut appended = original.clone();
+        appended.extend_from_slice(b"trailing garbage which changes the whole file hash");
+
+        let scanner = FileScanner::with_signatures(
+            Arc::new(EventBus::new()),
+            Arc::new(ScanHistory::load_from(&dir)),
+            Arc::new(QuarantineStore::load_from(&dir)),
+            signature_set_with_sections(&[], &[&section_md5]),
+        );
+
+        for (name, pe) in [("original.exe", original), ("appended.exe", appended)] {
+            let path = dir.join(name);
+            fs::write(&path, &pe).unwrap();
+
+            let detection = scanner.classify_file(&path).unwrap().detection;
+            let detection = detection.unwrap_or_else(|| panic!("{name} was not detected by its section hash"));
+            assert_eq!(detection.hash, section_md5, "{name}");
+            assert_eq!(
+                detection.match_mode,
+                MatchMode::SectionHash {
+                    section: ".text".to_string()
+                },
+                "{name}"
+            );
+        }
+
+        drop(scanner);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+//! Classifying files by their content, so a scan of executables only can skip files which cannot run.
+//!
+//! A file is classified from the magic bytes at its start rather than its extension, so an executable renamed to look
+//! like a document is still scanned. Scripts are the exception: they are plain text with no magic bytes, so are
+//! recognised by a `#!` line or, failing that, by their extension.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// The number of bytes read from the start of a file to classify it
+const HEADER_LEN: usize = 8;
+
+/// The magic bytes of an OLE compound file, such as an MSI or a legacy Office document
+pub const COMPOUND_FILE_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+/// The magic bytes of a zip, including zip based packages and OOXML Office documents
+pub const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// The magic bytes of a shortcut: the size of its header, then the start of the shell link CLSID
+pub const SHORTCUT_MAGIC: [u8; 8] = [0x4C, 0x00, 0x00, 0x00, 0x01, 0x14, 0x02, 0x00];
+
+/// Extensions of scripts run by an interpreter which ships with Windows, or is commonly installed
+const SCRIPT_EXTENSIONS: [&str; 15] = [
+    "bat", "cmd", "ps1", "psm1", "psd1", "vbs", "vbe", "js", "jse", "wsf", "wsh", "hta", "py", "sh", "reg",
+];
+
+/// What kind of file the content is, as far as the scanner is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// A PE, ELF or Mach-O binary
+    Executable,
+    Script,
+    /// An MSI or other compound document, a cabinet, or a zip based package such as a JAR or MSIX. Compound
+    /// documents include Office documents, which may carry macros.
+    Installer,
+    /// A shortcut, which can run a command line when opened
+    Shortcut,
+    Other,
+}
+
+impl FileKind {
+    /// Whether the kind is scanned by a scan of executables only
+    pub fn can_execute(&self) -> bool {
+        *self != FileKind::Other
+    }
+
+    /// Gets the kind by its name in a signature condition, such as `executable`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "executable" => Some(FileKind::Executable),
+            "script" => Some(FileKind::Script),
+            "installer" => Some(FileKind::Installer),
+            "shortcut" => Some(FileKind::Shortcut),
+            "other" => Some(FileKind::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Classifies the file from its first bytes, falling back on its extension for scripts.
+///
+/// # Returns
+///
+/// The kind of file, or an error if the file could not be read.
+pub fn classify(path: &Path) -> io::Result<FileKind> {
+    let mut header = [0u8; HEADER_LEN];
+    let mut file = File::open(path)?;
+    let len = read_header(&mut file, &mut header)?;
+
+    Ok(classify_header(&header[..len]).unwrap_or_else(|| classify_extension(path)))
+}
+
+fn read_header(file: &mut File, header: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < header.len() {
+        match file.read(&mut header[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    Ok(len)
+}
+
+fn classify_header(header: &[u8]) -> Option<FileKind> {
+    const MAGICS: [(&[u8], FileKind); 12] = [
+        (b"MZ", FileKind::Executable),
+        (b"\x7FELF", FileKind::Executable),
+        (&[0xFE, 0xED, 0xFA, 0xCE], FileKind::Executable),
+        (&[0xFE, 0xED, 0xFA, 0xCF], FileKind::Executable),
+        (&[0xCE, 0xFA, 0xED, 0xFE], FileKind::Executable),
+        (&[0xCF, 0xFA, 0xED, 0xFE], FileKind::Executable),
+        (&[0xCA, 0xFE, 0xBA, 0xBE], FileKind::Executable),
+        (b"#!", FileKind::Script),
+        (&COMPOUND_FILE_MAGIC, FileKind::Installer),
+        (b"MSCF", FileKind::Installer),
+        (ZIP_MAGIC, FileKind::Installer),
+        (&SHORTCUT_MAGIC, FileKind::Shortcut),
+    ];
+
+    MAGICS
+        .iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, kind)| *kind)
+}
+
+fn classify_extension(path: &Path) -> FileKind {
+    let is_script = path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy();
+        SCRIPT_EXTENSIONS
+            .iter()
+            .any(|script| script.eq_ignore_ascii_case(&ext))
+    });
+
+    match is_script {
+        true => FileKind::Script,
+        false => FileKind::Other,
+    }
+}
@@ -0,0 +1,340 @@
+//! Context triggered piecewise hashing (the ssdeep / spamsum algorithm) for detecting near duplicates of known
+//! malware.
+//!
+//! A fuzzy hash is made up of pieces, each a hash of the data between two trigger points chosen by a rolling hash
+//! over the content. Changing a few bytes only changes the pieces covering those bytes, so two files which are
+//! mostly the same produce mostly the same hash. Hashes are compared with an edit distance, giving a similarity score
+//! between 0 and 100.
+//!
+//! Hashes are produced in the same `blocksize:hash:hash` format as ssdeep, so signature lists made with ssdeep can be
+//! used directly.
+
+use std::collections::BTreeMap;
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u32 = 3;
+const HASH_PRIME: u32 = 0x0100_0193;
+const HASH_INIT: u32 = 0x2802_1967;
+const SPAMSUM_LENGTH: usize = 64;
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The rolling hash over the last ROLLING_WINDOW bytes, used to pick the trigger points
+struct RollingHash {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl RollingHash {
+    fn new() -> Self {
+        RollingHash {
+            window: [0; ROLLING_WINDOW],
+            h1: 0,
+            h2: 0,
+            h3: 0,
+            n: 0,
+        }
+    }
+
+    fn update(&mut self, c: u8) -> u32 {
+        let c32 = c as u32;
+        let slot = self.n % ROLLING_WINDOW;
+
+        self.h2 = self.h2.wrapping_sub(self.h1);
+        self.h2 = self.h2.wrapping_add(ROLLING_WINDOW as u32 * c32);
+
+        self.h1 = self.h1.wrapping_add(c32);
+        self.h1 = self.h1.wrapping_sub(self.window[slot] as u32);
+
+        self.window[slot] = c;
+        self.n += 1;
+
+        self.h3 = (self.h3 << 5) ^ c32;
+
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+/// A parsed fuzzy hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyHash {
+    block_size: u32,
+    /// The hash at block_size
+    first: Vec<u8>,
+    /// The hash at double the block_size
+    second: Vec<u8>,
+}
+
+impl FuzzyHash {
+    /// Computes the fuzzy hash of the data
+    pub fn hash(data: &[u8]) -> FuzzyHash {
+        let mut block_size = MIN_BLOCKSIZE;
+        while (block_size as u64) * (SPAMSUM_LENGTH as u64) < data.len() as u64 {
+            block_size *= 2;
+        }
+
+        loop {
+            let (first, second) = Self::hash_at_block_size(data, block_size);
+
+            // too few pieces at this block size to make a useful hash, so try again with smaller pieces
+            if first.len() < SPAMSUM_LENGTH / 2 && block_size > MIN_BLOCKSIZE {
+                block_size /= 2;
+                continue;
+            }
+
+            return FuzzyHash {
+                block_size,
+                first,
+                second,
+            };
+        }
+    }
+
+    fn hash_at_block_size(data: &[u8], block_size: u32) -> (Vec<u8>, Vec<u8>) {
+        let mut roll = RollingHash::new();
+        let mut h1 = HASH_INIT;
+        let mut h2 = HASH_INIT;
+        let mut first = Vec::with_capacity(SPAMSUM_LENGTH);
+        let mut second = Vec::with_capacity(SPAMSUM_LENGTH / 2);
+
+        for &c in data {
+            h1 = sum_hash(c, h1);
+            h2 = sum_hash(c, h2);
+            let rh = roll.update(c);
+
+            if rh % block_size == block_size - 1 {
+                if first.len() < SPAMSUM_LENGTH - 1 {
+                    first.push(B64[(h1 % 64) as usize]);
+                    h1 = HASH_INIT;
+                }
+
+                if rh % (block_size * 2) == block_size * 2 - 1 && second.len() < SPAMSUM_LENGTH / 2 - 1
+                {
+                    second.push(B64[(h2 % 64) as usize]);
+                    h2 = HASH_INIT;
+                }
+            }
+        }
+
+        if h1 != HASH_INIT {
+            first.push(B64[(h1 % 64) as usize]);
+        }
+        if h2 != HASH_INIT {
+            second.push(B64[(h2 % 64) as usize]);
+        }
+
+        (first, second)
+    }
+
+    /// Parses a hash in the ssdeep `blocksize:hash:hash` format. Anything after a comma, such as the file name in
+    /// ssdeep's output, is ignored.
+    pub fn parse(s: &str) -> Option<FuzzyHash> {
+        let s = s.split(',').next()?.trim();
+        let mut parts = s.splitn(3, ':');
+
+        let block_size: u32 = parts.next()?.parse().ok()?;
+        let first = parts.next()?.as_bytes().to_vec();
+        let second = parts.next()?.as_bytes().to_vec();
+
+        if block_size < MIN_BLOCKSIZE || first.len() > SPAMSUM_LENGTH || second.len() > SPAMSUM_LENGTH {
+            return None;
+        }
+
+        Some(FuzzyHash {
+            block_size,
+            first,
+            second,
+        })
+    }
+
+    /// Scores the similarity of two hashes from 0 (nothing in common) to 100 (identical, or near enough).
+    pub fn compare(&self, other: &FuzzyHash) -> u32 {
+        let (a, b) = (self, other);
+
+        // hashes are only comparable when their block sizes are the same, or one is double the other
+        if a.block_size == b.block_size {
+            let first = score_strings(&a.first, &b.first, a.block_size);
+            let second = score_strings(&a.second, &b.second, a.block_size * 2);
+            first.max(second)
+        } else if a.block_size == b.block_size * 2 {
+            score_strings(&a.first, &b.second, a.block_size)
+        } else if b.block_size == a.block_size * 2 {
+            score_strings(&a.second, &b.first, b.block_size)
+        } else {
+            0
+        }
+    }
+}
+
+impl std::fmt::Display for FuzzyHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.block_size,
+            String::from_utf8_lossy(&self.first),
+            String::from_utf8_lossy(&self.second)
+        )
+    }
+}
+
+/// Scores two pieces of a hash taken at the same block size
+fn score_strings(s1: &[u8], s2: &[u8], block_size: u32) -> u32 {
+    let s1 = collapse_runs(s1);
+    let s2 = collapse_runs(s2);
+
+    // without a run in common as long as the rolling window, any similarity is likely coincidental
+    if s1.len() < ROLLING_WINDOW || s2.len() < ROLLING_WINDOW || !has_common_substring(&s1, &s2) {
+        return 0;
+    }
+
+    let distance = edit_distance(&s1, &s2) as u64;
+    let total_len = (s1.len() + s2.len()) as u64;
+
+    let scaled = distance * SPAMSUM_LENGTH as u64 / total_len;
+    let scaled = 100 * scaled / SPAMSUM_LENGTH as u64;
+    if scaled >= 100 {
+        return 0;
+    }
+    let mut score = 100 - scaled as u32;
+
+    // at small block sizes the hash is only a handful of pieces, so don't claim more similarity than it can show
+    let cap_block_size = (99 + ROLLING_WINDOW as u32) / ROLLING_WINDOW as u32 * MIN_BLOCKSIZE;
+    if block_size < cap_block_size {
+        let cap = block_size / MIN_BLOCKSIZE * s1.len().min(s2.len()) as u32;
+        score = score.min(cap);
+    }
+
+    score
+}
+
+/// Reduces runs of more than three identical characters to three, as long runs carry little information and would
+/// otherwise dominate the edit distance
+fn collapse_runs(s: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(s.len());
+    for &c in s {
+        let len = out.len();
+        if len >= 3 && out[len - 1] == c && out[len - 2] == c && out[len - 3] == c {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn has_common_substring(s1: &[u8], s2: &[u8]) -> bool {
+    s1.windows(ROLLING_WINDOW)
+        .any(|w| s2.windows(ROLLING_WINDOW).any(|other| w == other))
+}
+
+/// Levenshtein distance where a substitution costs the same as a deletion and an insertion, as in ssdeep
+fn edit_distance(s1: &[u8], s2: &[u8]) -> usize {
+    let mut prev: Vec<usize> = (0..=s2.len()).collect();
+    let mut curr = vec![0usize; s2.len() + 1];
+
+    for (i, &c1) in s1.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, &c2) in s2.iter().enumerate() {
+            let substitution = prev[j] + if c1 == c2 { 0 } else { 2 };
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[s2.len()]
+}
+
+/// A set of fuzzy hash signatures, indexed by block size so that a lookup only compares against signatures
+/// which are comparable to the hash
+#[derive(Default)]
+pub struct FuzzySignatureSet {
+    by_block_size: BTreeMap<u32, Vec<FuzzyHash>>,
+    len: usize,
+}
+
+impl FuzzySignatureSet {
+    pub fn insert(&mut self, hash: FuzzyHash) {
+        self.by_block_size
+            .entry(hash.block_size)
+            .or_default()
+            .push(hash);
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FuzzyHash> {
+        self.by_block_size.values().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Finds the most similar signature to the hash, where its similarity is at least the threshold.
+    ///
+    /// # Returns
+    ///
+    /// The matched signature and its similarity score, or None if nothing met the threshold.
+    pub fn best_match(&self, hash: &FuzzyHash, threshold: u32) -> Option<(&FuzzyHash, u32)> {
+        let candidates = [hash.block_size / 2, hash.block_size, hash.block_size * 2];
+
+        candidates
+            .iter()
+            .filter_map(|bs| self.by_block_size.get(bs))
+            .flatten()
+            .map(|sig| (sig, hash.compare(sig)))
+            .filter(|(_, score)| *score >= threshold)
+            .max_by_key(|(_, score)| *score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared_std::settings::FuzzyHashSettings;
+
+    use super::*;
+
+    /// Bytes which look random, so that the sample has as many trigger points as compiled code would
+    fn sample(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn variant_with_flipped_bytes_matches_above_threshold() {
+        let original = sample(64 * 1024, 0x5eed);
+        let mut variant = original.clone();
+        for offset in [4_000, 23_000, 51_000] {
+            variant[offset] ^= 0xff;
+        }
+
+        let mut signatures = FuzzySignatureSet::default();
+        signatures.insert(FuzzyHash::parse(&FuzzyHash::hash(&original).to_string()).unwrap());
+
+        let threshold = FuzzyHashSettings::default().similarity_threshold;
+        let (_, similarity) = signatures
+            .best_match(&FuzzyHash::hash(&variant), threshold)
+            .expect("the variant should match the original");
+        assert!(similarity < 100, "flipping bytes should change the hash, scored {similarity}");
+        assert!(similarity >= threshold);
+
+        // another file altogether shares nothing with the original
+        assert!(signatures.best_match(&FuzzyHash::hash(&sample(64 * 1024, 0xfeed)), threshold).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+//! Heuristic scoring of files, for detecting malware which matches no signature.
+//!
+//! Each heuristic produces a signal with a score when it finds something suspicious about a file, and a file is
+//! detected once the scores of its signals add up to the threshold in the settings. No signal is expected to be
+//! enough by itself in every case, so the scores of each can be tuned against the others.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use shared_std::{
+    file_scanner::{HeuristicReason, HeuristicSignal},
+    settings::{HeuristicSettings, SuspiciousRoot},
+};
+
+use super::{
+    autoruns::expand_env_vars,
+    exclusions::{normalise, prefix_matches},
+    pe::PeHeaders,
+};
+
+/// [`HeuristicSettings`] which have been validated, with the suspicious roots expanded and normalised for matching
+#[derive(Default)]
+pub struct CompiledHeuristics {
+    enabled: bool,
+    score_threshold: u32,
+    /// The normalised (see [`normalise`]) path of each root, with the root as it was configured
+    roots: Vec<(String, SuspiciousRoot)>,
+}
+
+impl CompiledHeuristics {
+    /// Compiles the heuristic settings. Environment variables in the suspicious roots are expanded here, so a root
+    /// follows the environment of the engine at the time the settings were applied.
+    ///
+    /// # Returns
+    ///
+    /// An error describing the first invalid setting.
+    pub fn compile(settings: &HeuristicSettings) -> Result<Self, String> {
+        if settings.score_threshold == 0 {
+            return Err("Heuristic score threshold must be greater than 0.".to_string());
+        }
+
+        let mut roots = Vec::with_capacity(settings.suspicious_roots.len());
+        for root in &settings.suspicious_roots {
+            let normalised = normalise(&expand_env_vars(root.path.trim()));
+            if normalised.is_empty() {
+                return Err("Suspicious root path cannot be empty.".to_string());
+            }
+
+            roots.push((normalised, root.clone()));
+        }
+
+        Ok(CompiledHeuristics {
+            enabled: settings.enabled,
+            score_threshold: settings.score_threshold,
+            roots,
+        })
+    }
+
+    /// Scores the file on each heuristic. Where a threshold is given, it is used in place of the threshold in the
+    /// settings.
+    ///
+    /// # Returns
+    ///
+    /// - Ok(Some) containing the total score and the signals which contributed to it, where it met the threshold
+    /// - Ok(None) if heuristics are disabled, or the score was below the threshold
+    pub fn evaluate(
+        &self,
+        path: &Path,
+        file: &File,
+        score_threshold: Option<u32>,
+    ) -> Result<Option<(u32, Vec<HeuristicSignal>)>, io::Error> {
+        let Some((score, signals)) = self.score(path, file)? else {
+            return Ok(None);
+        };
+
+        if signals.is_empty() || score < score_threshold.unwrap_or(self.score_threshold) {
+            return Ok(None);
+        }
+
+        Ok(Some((score, signals)))
+    }
+
+    /// Scores the file on each heuristic, whatever the threshold.
+    ///
+    /// # Returns
+    ///
+    /// The total score and the signals which contributed to it, which are empty where nothing was found, or None if
+    /// heuristics are disabled.
+    pub fn score(&self, path: &Path, file: &File) -> Result<Option<(u32, Vec<HeuristicSignal>)>, io::Error> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let mut signals = Vec::new();
+
+        // location is the only heuristic, and only applies to a PE, so the file is only parsed when it is somewhere
+        // suspicious
+        if let Some(signal) = self.location_signal(path)
+            && PeHeaders::parse(&mut BufReader::new(file))?.is_some()
+        {
+            signals.push(signal);
+        }
+
+        let score = signals.iter().fold(0u32, |acc, s| acc.saturating_add(s.score));
+
+        Ok(Some((score, signals)))
+    }
+
+    /// The score at which a file is reported, as set in the settings
+    pub fn score_threshold(&self) -> u32 {
+        self.score_threshold
+    }
+
+    /// Finds the suspicious root the path is beneath. Where roots overlap, such as `%TEMP%` and
+    /// `%LOCALAPPDATA%\Temp` which are usually the same folder, only the highest scoring root counts, so a file is
+    /// not scored twice for the one location.
+    fn location_signal(&self, path: &Path) -> Option<HeuristicSignal> {
+        let normalised = normalise(&path.to_string_lossy());
+
+        self.roots
+            .iter()
+            .filter(|(prefix, _)| prefix_matches(prefix, &normalised))
+            .max_by_key(|(_, root)| root.score)
+            .map(|(_, root)| HeuristicSignal {
+                score: root.score,
+                reason: HeuristicReason::SuspiciousLocation {
+                    root: root.path.clone(),
+                },
+            })
+    }
+}
@@ -0,0 +1,285 @@
+//! Mounting disk images (VHD, VHDX and ISO) read only, so that their contents can be scanned.
+//!
+//! The image is attached through the virtual disk API without a drive letter, and each of its volumes is scanned
+//! through its volume GUID path (`\\?\Volume{...}\`), which is reported as the virtual root of the scan. The image is
+//! attached for only as long as the [`MountedImage`] lives: dropping it detaches the image, so it is unmounted however
+//! the scan ends, including on error or a panic.
+
+use std::{
+    ffi::{OsStr, c_void},
+    fs,
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, ERROR_SUCCESS, HANDLE, WIN32_ERROR},
+        Storage::{
+            FileSystem::{
+                CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_READ, FILE_SHARE_WRITE, FindFirstVolumeW,
+                FindNextVolumeW, FindVolumeClose, IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS, OPEN_EXISTING,
+            },
+            Vhd::{
+                ATTACH_VIRTUAL_DISK_FLAG_NO_DRIVE_LETTER, ATTACH_VIRTUAL_DISK_FLAG_READ_ONLY, AttachVirtualDisk,
+                DETACH_VIRTUAL_DISK_FLAG_NONE, DetachVirtualDisk, GetVirtualDiskPhysicalPath,
+                OPEN_VIRTUAL_DISK_FLAG_NONE, OpenVirtualDisk, VIRTUAL_DISK_ACCESS_READ, VIRTUAL_STORAGE_TYPE,
+                VIRTUAL_STORAGE_TYPE_DEVICE_ISO, VIRTUAL_STORAGE_TYPE_DEVICE_VHD, VIRTUAL_STORAGE_TYPE_DEVICE_VHDX,
+                VIRTUAL_STORAGE_TYPE_VENDOR_MICROSOFT,
+            },
+        },
+        System::{
+            IO::DeviceIoControl,
+            Ioctl::VOLUME_DISK_EXTENTS,
+        },
+    },
+    core::{Error, PCWSTR, PWSTR},
+};
+
+use crate::utils::log::{Log, LogLevel};
+
+/// How long to wait for the volumes of a newly attached image to be brought online by Windows
+const VOLUME_ARRIVAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A disk image attached read only, which is detached when dropped
+pub struct MountedImage {
+    handle: HANDLE,
+    image: PathBuf,
+    volume_roots: Vec<PathBuf>,
+}
+
+impl MountedImage {
+    /// Attaches the image read only and finds the roots of its volumes.
+    ///
+    /// # Returns
+    ///
+    /// The mounted image, or an error describing why it could not be mounted, such as the image being corrupt or
+    /// holding no volume which can be read. The image is never left attached on error.
+    pub fn mount(image: &Path) -> Result<Self, String> {
+        let storage_type = storage_type(image)?;
+        let wide_path = to_wide(image.as_os_str());
+
+        let mut handle = HANDLE::default();
+        let result = unsafe {
+            OpenVirtualDisk(
+                &storage_type,
+                PCWSTR::from_raw(wide_path.as_ptr()),
+                VIRTUAL_DISK_ACCESS_READ,
+                OPEN_VIRTUAL_DISK_FLAG_NONE,
+                None,
+                &mut handle,
+            )
+        };
+        if result != ERROR_SUCCESS {
+            return Err(format!(
+                "Could not open the image {}, it may be corrupt or not a disk image. {}",
+                image.display(),
+                describe(result)
+            ));
+        }
+
+        let result = unsafe {
+            AttachVirtualDisk(
+                handle,
+                None,
+                ATTACH_VIRTUAL_DISK_FLAG_READ_ONLY | ATTACH_VIRTUAL_DISK_FLAG_NO_DRIVE_LETTER,
+                0,
+                None,
+                None,
+            )
+        };
+        if result != ERROR_SUCCESS {
+            let _ = unsafe { CloseHandle(handle) };
+            return Err(format!(
+                "Could not mount the image {} read only. {}",
+                image.display(),
+                describe(result)
+            ));
+        }
+
+        // from here the image is attached, so is detached by the drop of the MountedImage on any error
+        let mut mounted = MountedImage {
+            handle,
+            image: image.to_path_buf(),
+            volume_roots: Vec::new(),
+        };
+
+        let disk_number = mounted.disk_number()?;
+        mounted.volume_roots = wait_for_volumes(disk_number);
+
+        if mounted.volume_roots.is_empty() {
+            return Err(format!(
+                "The image {} was mounted but holds no volume which can be read. It may be encrypted (such as with \
+                BitLocker), unpartitioned, or use a file system Windows does not support.",
+                image.display()
+            ));
+        }
+
+        Ok(mounted)
+    }
+
+    /// The roots of the image's volumes, which are the virtual roots the scan walks from
+    pub fn volume_roots(&self) -> &[PathBuf] {
+        &self.volume_roots
+    }
+
+    /// Gets the number of the physical disk the image is attached as, from its path such as `\\.\PhysicalDrive3`
+    fn disk_number(&self) -> Result<u32, String> {
+        let mut buf = [0u16; 260];
+        let mut size = (buf.len() * size_of::<u16>()) as u32;
+
+        let result =
+            unsafe { GetVirtualDiskPhysicalPath(self.handle, &mut size, PWSTR(buf.as_mut_ptr())) };
+        if result != ERROR_SUCCESS {
+            return Err(format!(
+                "Could not find the disk the image was mounted as. {}",
+                describe(result)
+            ));
+        }
+
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        let path = String::from_utf16_lossy(&buf[..len]);
+
+        path.to_ascii_lowercase()
+            .rsplit_once("physicaldrive")
+            .and_then(|(_, n)| n.parse().ok())
+            .ok_or_else(|| format!("Unexpected disk path for the mounted image: {path}"))
+    }
+}
+
+// the handle is only used to detach the image, which can be done from any thread
+unsafe impl Send for MountedImage {}
+
+impl Drop for MountedImage {
+    /// Detaches the image. Closing the handle would also detach it, but detaching first means a failure to unmount
+    /// is logged rather than going unnoticed.
+    fn drop(&mut self) {
+        let result = unsafe { DetachVirtualDisk(self.handle, DETACH_VIRTUAL_DISK_FLAG_NONE, 0) };
+        if result != ERROR_SUCCESS {
+            Log::new().log(
+                LogLevel::Error,
+                &format!("Failed to unmount image {}. {}", self.image.display(), describe(result)),
+            );
+        }
+
+        let _ = unsafe { CloseHandle(self.handle) };
+    }
+}
+
+/// Chooses the type of virtual disk to open from the extension of the image
+fn storage_type(image: &Path) -> Result<VIRTUAL_STORAGE_TYPE, String> {
+    let extension = image
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+
+    let device_id = match extension.as_deref() {
+        Some("iso") => VIRTUAL_STORAGE_TYPE_DEVICE_ISO,
+        Some("vhd") => VIRTUAL_STORAGE_TYPE_DEVICE_VHD,
+        Some("vhdx") => VIRTUAL_STORAGE_TYPE_DEVICE_VHDX,
+        _ => {
+            return Err(format!(
+                "{} is not a supported disk image, only .vhd, .vhdx and .iso images can be scanned.",
+                image.display()
+            ));
+        }
+    };
+
+    Ok(VIRTUAL_STORAGE_TYPE {
+        DeviceId: device_id,
+        VendorId: VIRTUAL_STORAGE_TYPE_VENDOR_MICROSOFT,
+    })
+}
+
+/// Waits for Windows to bring the volumes of the disk online, returning the roots of those which can be read
+fn wait_for_volumes(disk_number: u32) -> Vec<PathBuf> {
+    let started = Instant::now();
+
+    loop {
+        let roots: Vec<PathBuf> = volumes_on_disk(disk_number)
+            .into_iter()
+            .filter(|root| fs::read_dir(root).is_ok())
+            .collect();
+
+        if !roots.is_empty() || started.elapsed() >= VOLUME_ARRIVAL_TIMEOUT {
+            return roots;
+        }
+
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+/// Gets the GUID paths of every volume which lies on the disk
+fn volumes_on_disk(disk_number: u32) -> Vec<PathBuf> {
+    let mut volumes = Vec::new();
+    let mut name = [0u16; 260];
+
+    let Ok(find) = (unsafe { FindFirstVolumeW(&mut name) }) else {
+        return volumes;
+    };
+
+    loop {
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        let volume = String::from_utf16_lossy(&name[..len]);
+
+        if volume_disk_number(&volume) == Some(disk_number) {
+            volumes.push(PathBuf::from(volume));
+        }
+
+        if unsafe { FindNextVolumeW(find, &mut name) }.is_err() {
+            break;
+        }
+    }
+
+    let _ = unsafe { FindVolumeClose(find) };
+
+    volumes
+}
+
+/// Gets the number of the disk the volume lies on, given its GUID path with the trailing backslash
+fn volume_disk_number(volume: &str) -> Option<u32> {
+    // the volume device itself is opened without the trailing backslash, which would instead open its root folder
+    let device = to_wide(OsStr::new(volume.trim_end_matches('\\')));
+
+    let handle = unsafe {
+        CreateFileW(
+            PCWSTR::from_raw(device.as_ptr()),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    }
+    .ok()?;
+
+    let mut extents = VOLUME_DISK_EXTENTS::default();
+    let mut bytes_returned: u32 = 0;
+    let result = unsafe {
+        DeviceIoControl(
+            handle,
+            IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS,
+            None,
+            0,
+            Some(&mut extents as *mut _ as *mut c_void),
+            size_of_val(&extents) as u32,
+            Some(&mut bytes_returned),
+            None,
+        )
+    };
+    let _ = unsafe { CloseHandle(handle) };
+
+    // a volume spanning several disks does not fit the buffer and fails, but an image's volumes lie on the image alone
+    result.ok()?;
+    (extents.NumberOfDiskExtents > 0).then_some(extents.Extents[0].DiskNumber)
+}
+
+fn to_wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain(std::iter::once(0)).collect()
+}
+
+fn describe(e: WIN32_ERROR) -> Error {
+    Error::from(e.to_hresult())
+}
@@ -0,0 +1,228 @@
+//! The import hash (imphash) of a PE, which clusters executables by the functions they import and the order they
+//! import them in. A family of malware which is recompiled with small changes keeps its imports, and so its imphash,
+//! whilst its whole file and section hashes all change.
+//!
+//! The hash is computed as pefile computes it, so that imphashes published for known malware can be used as
+//! signatures. Each import is written as `dll.function` in lowercase, with a `.dll`, `.ocx` or `.sys` extension dropped
+//! from the name of the DLL, and the imports are joined with commas in the order of the import table. The MD5 of the
+//! result is the imphash. A function imported by ordinal is written as `ord` followed by the ordinal. pefile instead
+//! names the ordinals of ws2_32.dll, wsock32.dll and oleaut32.dll from tables of their exports, so a PE importing from
+//! those by ordinal has an imphash here which differs from the one pefile gives.
+//!
+//! As with the rest of the PE parsing, every RVA and length is bounds checked against the file, and the import table
+//! is only read up to fixed limits, so a malformed import table gives no imphash rather than an error.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use md5::{Digest, Md5};
+
+use super::{
+    filescanner::to_hex,
+    pe::{PeHeaders, read_u32},
+};
+
+const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+/// The most DLLs read from the import table, well beyond what any real PE imports from
+const MAX_IMPORTED_DLLS: usize = 1024;
+/// The most functions read from each DLL imported
+const MAX_IMPORTS_PER_DLL: usize = 8192;
+/// The longest name of a DLL or function read
+const MAX_NAME_LEN: usize = 256;
+/// The flag set on a thunk which imports by ordinal, in a PE32 and a PE32+
+const ORDINAL_FLAG_32: u64 = 1 << 31;
+const ORDINAL_FLAG_64: u64 = 1 << 63;
+
+/// Computes the imphash of the PE, in the uppercase form the signatures are held in.
+///
+/// # Returns
+///
+/// - Ok(Some) containing the imphash
+/// - Ok(None) if the PE imports nothing, or its import table is malformed
+/// - Err if there was an IO error reading the file
+pub fn import_hash<R: Read + Seek>(reader: &mut R, headers: &PeHeaders) -> io::Result<Option<String>> {
+    let Some(directory) = headers.import_directory else {
+        return Ok(None);
+    };
+    let (thunk_size, ordinal_flag) = match headers.pe32_plus {
+        true => (8, ORDINAL_FLAG_64),
+        false => (4, ORDINAL_FLAG_32),
+    };
+
+    let mut imports: Vec<String> = Vec::new();
+
+    for index in 0..MAX_IMPORTED_DLLS {
+        let rva = rva_at(directory, index, IMPORT_DESCRIPTOR_SIZE);
+        let Some(descriptor) = read_rva(reader, headers, rva, IMPORT_DESCRIPTOR_SIZE)? else {
+            return Ok(None);
+        };
+        // the table ends with a descriptor of all zeroes
+        if descriptor.iter().all(|b| *b == 0) {
+            break;
+        }
+
+        let Some(dll) = read_name(reader, headers, Some(read_u32(&descriptor, 12)))? else {
+            return Ok(None);
+        };
+        let dll = dll.to_lowercase();
+        let dll = match dll.rsplit_once('.') {
+            Some((name, "dll" | "ocx" | "sys")) => name.to_string(),
+            _ => dll,
+        };
+
+        // the lookup table is left out by some linkers, in which case the address table holds the same until loaded
+        let thunks = match read_u32(&descriptor, 0) {
+            0 => read_u32(&descriptor, 16),
+            original_first_thunk => original_first_thunk,
+        };
+
+        for thunk_index in 0..MAX_IMPORTS_PER_DLL {
+            let Some(thunk) = read_rva(reader, headers, rva_at(thunks, thunk_index, thunk_size), thunk_size)? else {
+                return Ok(None);
+            };
+            let mut value = [0u8; 8];
+            value[..thunk_size].copy_from_slice(&thunk);
+            let thunk = u64::from_le_bytes(value);
+            if thunk == 0 {
+                break;
+            }
+
+            let function = if thunk & ordinal_flag != 0 {
+                format!("ord{}", thunk & 0xFFFF)
+            } else {
+                // the name follows the 2 byte hint of which export the loader should look at first
+                let hint_name = (thunk & 0x7FFF_FFFF) as u32;
+                match read_name(reader, headers, hint_name.checked_add(2))? {
+                    Some(name) => name.to_lowercase(),
+                    None => return Ok(None),
+                }
+            };
+
+            imports.push(format!("{dll}.{function}"));
+        }
+    }
+
+    if imports.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(to_hex(&Md5::digest(imports.join(",").as_bytes()))))
+}
+
+/// The RVA of the entry at the index of a table of entries of the given size, or None where it would overflow
+fn rva_at(table: u32, index: usize, entry_size: usize) -> Option<u32> {
+    u32::try_from(index * entry_size).ok().and_then(|offset| table.checked_add(offset))
+}
+
+/// Reads the bytes at the RVA, or None where they are not all in the file
+fn read_rva<R: Read + Seek>(
+    reader: &mut R,
+    headers: &PeHeaders,
+    rva: Option<u32>,
+    len: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    let Some(offset) = rva.and_then(|rva| headers.rva_to_offset(rva)) else {
+        return Ok(None);
+    };
+    if offset + len as u64 > headers.file_len {
+        return Ok(None);
+    }
+
+    let mut buf = vec![0u8; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+
+    Ok(Some(buf))
+}
+
+/// Reads the nul terminated name at the RVA, or None where it is not in the file or is longer than the most read
+fn read_name<R: Read + Seek>(reader: &mut R, headers: &PeHeaders, rva: Option<u32>) -> io::Result<Option<String>> {
+    let Some(offset) = rva.and_then(|rva| headers.rva_to_offset(rva)) else {
+        return Ok(None);
+    };
+    let len = (headers.file_len - offset).min(MAX_NAME_LEN as u64 + 1) as usize;
+
+    let Some(buf) = read_rva(reader, headers, rva, len)? else {
+        return Ok(None);
+    };
+
+    Ok(buf.iter().position(|b| *b == 0).map(|end| String::from_utf8_lossy(&buf[..end]).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{super::pe::tests::crafted_pe, *};
+
+    const IDATA_RVA: u32 = 0x3000;
+
+    /// Builds an import section loaded at IDATA_RVA, importing the functions of each DLL by name. The descriptors are
+    /// followed by the given padding, and then by each DLL's lookup table, hint/name entries and name.
+    fn import_section(padding: usize, dlls: &[(&str, &[&str])]) -> Vec<u8> {
+        let rva = |offset: usize| IDATA_RVA + offset as u32;
+        let mut data = vec![0u8; (dlls.len() + 1) * IMPORT_DESCRIPTOR_SIZE + padding];
+
+        for (i, (dll, functions)) in dlls.iter().enumerate() {
+            let lookup = data.len();
+            data.resize(lookup + (functions.len() + 1) * 4, 0);
+
+            for (j, function) in functions.iter().enumerate() {
+                let hint_name = rva(data.len());
+                data.extend_from_slice(&[0, 0]);
+                data.extend_from_slice(function.as_bytes());
+                data.push(0);
+                data[lookup + j * 4..lookup + j * 4 + 4].copy_from_slice(&hint_name.to_le_bytes());
+            }
+
+            let name = rva(data.len());
+            data.extend_from_slice(dll.as_bytes());
+            data.push(0);
+
+            let descriptor = i * IMPORT_DESCRIPTOR_SIZE;
+            data[descriptor..descriptor + 4].copy_from_slice(&rva(lookup).to_le_bytes());
+            data[descriptor + 12..descriptor + 16].copy_from_slice(&name.to_le_bytes());
+            data[descriptor + 16..descriptor + 20].copy_from_slice(&rva(lookup).to_le_bytes());
+        }
+
+        data
+    }
+
+    fn imphash(pe: Vec<u8>) -> Option<String> {
+        let mut reader = Cursor::new(pe);
+        let headers = PeHeaders::parse(&mut reader).unwrap().expect("the crafted PE should parse");
+        import_hash(&mut reader, &headers).unwrap()
+    }
+
+    #[test]
+    fn pes_with_the_same_imports_share_an_imphash() {
+        let imports: &[(&str, &[&str])] =
+            &[("KERNEL32.dll", &["CreateFileW", "WriteFile"]), ("user32.DLL", &["MessageBoxA"])];
+
+        // built at different times, with different code and the import table laid out differently
+        let first = crafted_pe(
+            0x6500_0000,
+            IDATA_RVA,
+            &[(".text", 0x1000, &[0xCC; 0x100]), (".idata", IDATA_RVA, &import_section(0, imports))],
+        );
+        let second = crafted_pe(
+            0x6600_0000,
+            IDATA_RVA,
+            &[
+                (".text", 0x1000, &[0x90; 0x900]),
+                (".rdata", 0x2000, b"a string the first does not have"),
+                (".idata", IDATA_RVA, &import_section(0x40, imports)),
+            ],
+        );
+        assert_ne!(first, second);
+
+        let expected = to_hex(&Md5::digest(b"kernel32.createfilew,kernel32.writefile,user32.messageboxa"));
+        assert_eq!(imphash(first), Some(expected.clone()));
+        assert_eq!(imphash(second), Some(expected.clone()));
+
+        // the order of the imports is part of the hash
+        let reordered: &[(&str, &[&str])] =
+            &[("KERNEL32.dll", &["WriteFile", "CreateFileW"]), ("user32.DLL", &["MessageBoxA"])];
+        let third = crafted_pe(0x6500_0000, IDATA_RVA, &[(".idata", IDATA_RVA, &import_section(0, reordered))]);
+        assert_ne!(imphash(third), Some(expected));
+    }
+}
@@ -0,0 +1,242 @@
+//! Inspecting the memory of a running process for signs that code was injected into it, such as by process hollowing.
+//!
+//! Unlike a scan, nothing is matched against signatures; the heuristic looks at how the memory of the process is laid
+//! out, flagging:
+//!
+//! - memory which is both writable and executable without being mapped from a file, as shellcode is commonly written
+//!   into
+//! - memory mapped from a file which is both writable and executable, where the executable of the process is signed,
+//!   so its own code is not expected to change
+//! - modules whose headers in memory differ from the file they were loaded from, or whose memory is no longer mapped
+//!   from the file at all, as where a process was hollowed out and another executable put in its place
+//!
+//! A JIT compiles code into writable memory as it runs, so a process with a JIT engine loaded is not reported for its
+//! writable and executable memory, only for its modules. Only the headers of a module are compared against its file,
+//! as the code itself differs wherever the loader relocated the module.
+
+use std::{
+    ffi::{OsString, c_void},
+    fs::File,
+    io::Cursor,
+    mem,
+    os::windows::ffi::OsStringExt,
+    path::PathBuf,
+};
+
+use shared_std::file_scanner::{InjectionIndicator, InjectionSuspected, ProcessMemoryReport};
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::{
+            Diagnostics::{
+                Debug::ReadProcessMemory,
+                ToolHelp::{
+                    CreateToolhelp32Snapshot, MODULEENTRY32W, Module32FirstW, Module32NextW, TH32CS_SNAPMODULE,
+                    TH32CS_SNAPMODULE32,
+                },
+            },
+            Memory::{
+                MEM_COMMIT, MEM_IMAGE, MEM_PRIVATE, MEMORY_BASIC_INFORMATION, PAGE_EXECUTE_READWRITE,
+                PAGE_EXECUTE_WRITECOPY, VirtualQueryEx,
+            },
+            Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ},
+        },
+    },
+};
+
+use super::{authenticode, pe::PeHeaders, process::process_image};
+
+/// The bytes read from the base of a module to compare its headers, which is the page the loader maps them in
+const HEADER_PAGE_LEN: usize = 4096;
+
+/// A module loaded in the process
+struct LoadedModule {
+    base: usize,
+    path: PathBuf,
+}
+
+/// Inspects the memory of the process for signs of injected code.
+///
+/// # Args
+///
+/// - `jit_modules`: The file names of the modules of JIT engines, a process with any of which loaded is not reported
+///   for its writable and executable memory
+///
+/// # Returns
+///
+/// What was found, or an error if there is no such process or its memory could not be read, such as a protected
+/// process.
+pub fn inspect_process(pid: u32, jit_modules: &[String]) -> Result<ProcessMemoryReport, String> {
+    let image = process_image(pid)?;
+    let modules = loaded_modules(pid)?;
+
+    let jit_module = modules
+        .iter()
+        .filter_map(|module| module.path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .find(|name| jit_modules.iter().any(|jit| jit.eq_ignore_ascii_case(name)));
+    let signed = authenticode::verify_embedded(&image).trusted;
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid) }
+        .map_err(|e| format!("Could not open the memory of process {pid}. {e}"))?;
+
+    let mut report = ProcessMemoryReport {
+        pid,
+        image,
+        signed,
+        jit_module,
+        regions_inspected: 0,
+        modules_inspected: modules.len(),
+        findings: Vec::new(),
+    };
+
+    inspect_regions(process, &mut report);
+    report.findings.extend(modules.iter().filter_map(|module| inspect_module(process, module)));
+
+    let _ = unsafe { CloseHandle(process) };
+
+    Ok(report)
+}
+
+/// Walks every region of the process's address space, reporting those which are both writable and executable
+fn inspect_regions(process: HANDLE, report: &mut ProcessMemoryReport) {
+    let mut address = 0usize;
+
+    while let Some(region) = query(process, address) {
+        report.regions_inspected += 1;
+
+        let writable_executable = region.State == MEM_COMMIT
+            && region.Protect.0 & (PAGE_EXECUTE_READWRITE.0 | PAGE_EXECUTE_WRITECOPY.0) != 0;
+
+        if writable_executable && report.jit_module.is_none() {
+            let indicator = match region.Type {
+                MEM_PRIVATE => Some(InjectionIndicator::WritableExecutablePrivate),
+                _ if report.signed => Some(InjectionIndicator::WritableExecutableInSigned),
+                _ => None,
+            };
+
+            if let Some(indicator) = indicator {
+                report.findings.push(InjectionSuspected {
+                    address: region.BaseAddress as u64,
+                    size: region.RegionSize as u64,
+                    protection: region.Protect.0,
+                    indicator,
+                });
+            }
+        }
+
+        // the walk ends at the top of the address space, which the next region would wrap past
+        match (region.BaseAddress as usize).checked_add(region.RegionSize) {
+            Some(next) if next > address => address = next,
+            _ => break,
+        }
+    }
+}
+
+/// Compares the headers of the module in memory against those of the file it was loaded from.
+///
+/// # Returns
+///
+/// The finding where the module does not match its file, or None where it does or either could not be read, such as
+/// a file deleted since it was loaded.
+fn inspect_module(process: HANDLE, module: &LoadedModule) -> Option<InjectionSuspected> {
+    let region = query(process, module.base)?;
+    let mismatch = |difference: String| {
+        Some(InjectionSuspected {
+            address: module.base as u64,
+            size: region.RegionSize as u64,
+            protection: region.Protect.0,
+            indicator: InjectionIndicator::ImageMismatch {
+                file: module.path.clone(),
+                difference,
+            },
+        })
+    };
+
+    if region.Type != MEM_IMAGE {
+        return mismatch("The memory the module was loaded at is no longer mapped from its file.".to_string());
+    }
+
+    let mut header = vec![0u8; HEADER_PAGE_LEN];
+    let mut read = 0;
+    unsafe {
+        ReadProcessMemory(
+            process,
+            module.base as *const c_void,
+            header.as_mut_ptr() as *mut c_void,
+            header.len(),
+            Some(&mut read),
+        )
+    }
+    .ok()?;
+    header.truncate(read);
+
+    let on_disk = PeHeaders::parse(&mut File::open(&module.path).ok()?).ok().flatten()?;
+    let Some(in_memory) = PeHeaders::parse(&mut Cursor::new(header)).ok().flatten() else {
+        return mismatch("The headers of the module in memory are not those of a PE.".to_string());
+    };
+
+    header_difference(&in_memory, &on_disk).and_then(mismatch)
+}
+
+/// Describes the first difference between the headers of a module in memory and on disk, of those the loader leaves
+/// as they are in the file
+fn header_difference(in_memory: &PeHeaders, on_disk: &PeHeaders) -> Option<String> {
+    let fields = [
+        ("entry point", in_memory.entry_point, on_disk.entry_point),
+        ("size of image", in_memory.image_size, on_disk.image_size),
+        ("timestamp", in_memory.timestamp, on_disk.timestamp),
+    ];
+    if let Some((name, memory, disk)) = fields.into_iter().find(|(_, memory, disk)| memory != disk) {
+        return Some(format!("The {name} is {memory:#x} in memory, but {disk:#x} in the file."));
+    }
+
+    let section_names = |headers: &PeHeaders| headers.sections.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+    let (memory, disk) = (section_names(in_memory), section_names(on_disk));
+
+    (memory != disk).then(|| {
+        format!("The sections are {} in memory, but {} in the file.", memory.join(", "), disk.join(", "))
+    })
+}
+
+/// Gets the region of the process's memory containing the address, or None where the address is beyond the last
+fn query(process: HANDLE, address: usize) -> Option<MEMORY_BASIC_INFORMATION> {
+    let mut region = MEMORY_BASIC_INFORMATION::default();
+    let len = unsafe {
+        VirtualQueryEx(
+            process,
+            Some(address as *const c_void),
+            &mut region,
+            mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+
+    (len != 0).then_some(region)
+}
+
+/// Lists the modules loaded in the process, the first being its executable
+fn loaded_modules(pid: u32) -> Result<Vec<LoadedModule>, String> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, pid) }
+        .map_err(|e| format!("Could not list the modules of process {pid}. {e}"))?;
+
+    let mut entry = MODULEENTRY32W {
+        dwSize: mem::size_of::<MODULEENTRY32W>() as u32,
+        ..Default::default()
+    };
+    let mut modules = Vec::new();
+
+    let mut found = unsafe { Module32FirstW(snapshot, &mut entry) }.is_ok();
+    while found {
+        let len = entry.szExePath.iter().position(|c| *c == 0).unwrap_or(entry.szExePath.len());
+        modules.push(LoadedModule {
+            base: entry.modBaseAddr as usize,
+            path: PathBuf::from(OsString::from_wide(&entry.szExePath[..len])),
+        });
+
+        found = unsafe { Module32NextW(snapshot, &mut entry) }.is_ok();
+    }
+
+    let _ = unsafe { CloseHandle(snapshot) };
+
+    Ok(modules)
+}
@@ -0,0 +1,323 @@
+//! The journal of the running scan, where the results of a scan are written to disk as they are found, so that a scan
+//! which never ends (the engine crashing or being killed, or the machine losing power) still leaves its results behind.
+//!
+//! The journal is a JSON line file of [`JournalEntry`], which is started afresh with each scan and removed once the
+//! scan has been recorded in the scan history. A journal found when the engine starts is therefore that of a scan which
+//! was interrupted, and is recovered into the history as such.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use shared_no_std::constants::SCAN_JOURNAL_LOCATION;
+use shared_std::{
+    file_scanner::{MatchedIOC, StalledFile},
+    history::{OUTCOME_INTERRUPTED, ScanRecord},
+};
+
+use crate::{
+    history::ScanHistory,
+    utils::log::{Log, LogLevel},
+};
+
+/// The least time between checkpoints of the scan's counters, so that a long scan does not grow the journal by a line
+/// for every progress update
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A line of the journal
+#[derive(Serialize, Deserialize)]
+enum JournalEntry {
+    /// The first line of the journal
+    Started { scan_id: u64, started_at: Option<SystemTime> },
+    /// The folders the scan walks, written once they are known
+    Roots(Vec<PathBuf>),
+    Detection(MatchedIOC),
+    ChangedDuringScan(PathBuf),
+    Stalled(StalledFile),
+    /// The counters of the scan so far, superseding those of any earlier checkpoint
+    Checkpoint {
+        at: SystemTime,
+        num_files_scanned: u128,
+        bytes_scanned: u64,
+        skipped_excluded: u64,
+        skipped_not_executable: u64,
+        #[serde(default)]
+        skipped_remote: u64,
+        #[serde(default)]
+        skipped_unchanged: u64,
+    },
+}
+
+/// The counters written at a checkpoint
+pub struct JournalCounters {
+    pub num_files_scanned: u128,
+    pub bytes_scanned: u64,
+    pub skipped_excluded: u64,
+    pub skipped_not_executable: u64,
+    pub skipped_remote: u64,
+    pub skipped_unchanged: u64,
+}
+
+pub struct ScanJournal {
+    path: Option<PathBuf>,
+    enabled: AtomicBool,
+    // the journal of the running scan, None where journalling is off, no scan is running or writing to it failed
+    file: Mutex<Option<File>>,
+    last_checkpoint: Mutex<Option<Instant>>,
+    log: Log,
+}
+
+impl ScanJournal {
+    pub fn new() -> Self {
+        ScanJournal {
+            path: journal_path(),
+            enabled: AtomicBool::new(false),
+            file: Mutex::new(None),
+            last_checkpoint: Mutex::new(None),
+            log: Log::new(),
+        }
+    }
+
+    /// Turns journalling on or off, taking effect from the next scan
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Starts the journal of a new scan, replacing that of the last scan
+    pub fn begin(&self, scan_id: u64, started_at: Option<SystemTime>) {
+        let mut lock = self.file.lock().unwrap();
+        *lock = None;
+        *self.last_checkpoint.lock().unwrap() = None;
+
+        let Some(path) = self.path.as_ref().filter(|_| self.enabled.load(Ordering::Relaxed)) else {
+            return;
+        };
+
+        match File::create(path) {
+            Ok(file) => *lock = Some(file),
+            Err(e) => {
+                self.log.log(
+                    LogLevel::Warning,
+                    &format!("Could not create scan journal {}, scan {scan_id} is not journalled. {e}", path.display()),
+                );
+                return;
+            }
+        }
+
+        self.append(&mut lock, &JournalEntry::Started { scan_id, started_at }, true);
+    }
+
+    pub fn roots(&self, roots: &[PathBuf]) {
+        self.append(&mut self.file.lock().unwrap(), &JournalEntry::Roots(roots.to_vec()), false);
+    }
+
+    /// Journals a detection, syncing it to disk, as detections are few and are what a recovered scan is for
+    pub fn detection(&self, ioc: &MatchedIOC) {
+        self.append(&mut self.file.lock().unwrap(), &JournalEntry::Detection(ioc.clone()), true);
+    }
+
+    pub fn changed_during_scan(&self, path: &Path) {
+        self.append(&mut self.file.lock().unwrap(), &JournalEntry::ChangedDuringScan(path.to_path_buf()), false);
+    }
+
+    pub fn stalled(&self, stalled: &StalledFile) {
+        self.append(&mut self.file.lock().unwrap(), &JournalEntry::Stalled(stalled.clone()), false);
+    }
+
+    /// Checkpoints the counters of the scan, where [`CHECKPOINT_INTERVAL`] has passed since the last checkpoint
+    pub fn checkpoint(&self, counters: impl FnOnce() -> JournalCounters) {
+        let mut lock = self.file.lock().unwrap();
+        if lock.is_none() {
+            return;
+        }
+
+        {
+            let mut last = self.last_checkpoint.lock().unwrap();
+            if last.is_some_and(|at| at.elapsed() < CHECKPOINT_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+
+        let counters = counters();
+        let entry = JournalEntry::Checkpoint {
+            at: SystemTime::now(),
+            num_files_scanned: counters.num_files_scanned,
+            bytes_scanned: counters.bytes_scanned,
+            skipped_excluded: counters.skipped_excluded,
+            skipped_not_executable: counters.skipped_not_executable,
+            skipped_remote: counters.skipped_remote,
+            skipped_unchanged: counters.skipped_unchanged,
+        };
+        self.append(&mut lock, &entry, false);
+    }
+
+    /// Ends the journal of the scan once it has been recorded in the scan history, which leaves nothing to recover
+    pub fn end(&self) {
+        if self.file.lock().unwrap().take().is_none() {
+            return;
+        }
+
+        if let Some(path) = &self.path
+            && let Err(e) = fs::remove_file(path)
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            self.log
+                .log(LogLevel::Warning, &format!("Could not remove scan journal {}. {e}", path.display()));
+        }
+    }
+
+    /// Appends the entry to the journal. Should the write fail, such as where the disk is full, journalling stops for
+    /// the rest of the scan rather than leaving a journal with results missing from the middle of it.
+    fn append(&self, file: &mut Option<File>, entry: &JournalEntry, sync: bool) {
+        let Some(journal) = file.as_mut() else {
+            return;
+        };
+
+        let mut line = serde_json::to_vec(entry).unwrap();
+        line.push(b'\n');
+
+        let result = journal
+            .write_all(&line)
+            .and_then(|_| if sync { journal.sync_data() } else { Ok(()) });
+
+        if let Err(e) = result {
+            self.log.log(
+                LogLevel::Warning,
+                &format!("Could not write to the scan journal, journalling stops for this scan. {e}"),
+            );
+            *file = None;
+        }
+    }
+}
+
+/// Recovers the results of a scan which was interrupted, from the journal it left behind, recording them in the scan
+/// history with the outcome [`OUTCOME_INTERRUPTED`]. This is done when the engine starts, before any scan can begin.
+///
+/// The last line of the journal may have been cut short as the scan was interrupted, so the journal is read up to the
+/// first line which cannot be parsed.
+///
+/// # Returns
+///
+/// The ID of the scan recovered, or None if there was no journal to recover.
+pub fn recover_interrupted_scan(history: &ScanHistory) -> Option<u64> {
+    let path = journal_path()?;
+    let file = File::open(&path).ok()?;
+    let log = Log::new();
+
+    let mut entries = BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok().and_then(|l| serde_json::from_str::<JournalEntry>(&l).ok()));
+
+    let record = match entries.next() {
+        Some(JournalEntry::Started { scan_id, started_at }) => {
+            let mut record = ScanRecord {
+                scan_id,
+                started_at,
+                finished_at: None,
+                outcome: OUTCOME_INTERRUPTED.to_string(),
+                cancel_reason: None,
+                num_files_scanned: 0,
+                bytes_scanned: 0,
+                detections: Vec::new(),
+                changed_during_scan: Vec::new(),
+                stalled_files: Vec::new(),
+                skipped_not_executable: 0,
+                skipped_excluded: 0,
+                skipped_remote: 0,
+                skipped_unchanged: 0,
+                scanned_with_backup_privilege: 0,
+                inaccessible: Vec::new(),
+                inaccessible_count: 0,
+                scanned_on_retry: 0,
+                skipped_in_use: Vec::new(),
+                skipped_in_use_count: 0,
+                extensions: Vec::new(),
+                thresholds: None,
+                annotations: Default::default(),
+                roots: Vec::new(),
+                target: None,
+            };
+
+            for entry in entries {
+                match entry {
+                    JournalEntry::Started { .. } => break,
+                    JournalEntry::Roots(roots) => record.roots = roots,
+                    JournalEntry::Detection(ioc) => record.detections.push(ioc),
+                    JournalEntry::ChangedDuringScan(path) => record.changed_during_scan.push(path),
+                    JournalEntry::Stalled(stalled) => record.stalled_files.push(stalled),
+                    JournalEntry::Checkpoint {
+                        at,
+                        num_files_scanned,
+                        bytes_scanned,
+                        skipped_excluded,
+                        skipped_not_executable,
+                        skipped_remote,
+                        skipped_unchanged,
+                    } => {
+                        // the scan was last known to be running at its last checkpoint
+                        record.finished_at = Some(at);
+                        record.num_files_scanned = num_files_scanned;
+                        record.bytes_scanned = bytes_scanned;
+                        record.skipped_excluded = skipped_excluded;
+                        record.skipped_not_executable = skipped_not_executable;
+                        record.skipped_remote = skipped_remote;
+                        record.skipped_unchanged = skipped_unchanged;
+                    }
+                }
+            }
+
+            Some(record)
+        }
+        _ => None,
+    };
+
+    // a scan interrupted between being recorded in the history and its journal being removed is already recorded
+    let scan_id = match record {
+        Some(record) if history.find(record.scan_id).is_none() => {
+            let scan_id = record.scan_id;
+            let detections = record.detections.len();
+            match history.record(record) {
+                Ok(_) => {
+                    log.log(
+                        LogLevel::Warning,
+                        &format!(
+                            "Recovered scan {scan_id}, which was interrupted before it ended, from its journal with \
+                            {detections} detection(s)."
+                        ),
+                    );
+                    Some(scan_id)
+                }
+                Err(e) => {
+                    // the journal is kept so that recovery is tried again on the next start
+                    log.log(
+                        LogLevel::Error,
+                        &format!("Could not record scan {scan_id} recovered from its journal in the history. {e}"),
+                    );
+                    return None;
+                }
+            }
+        }
+        _ => None,
+    };
+
+    if let Err(e) = fs::remove_file(&path) {
+        log.log(LogLevel::Warning, &format!("Could not remove scan journal {}. {e}", path.display()));
+    }
+
+    scan_id
+}
+
+fn journal_path() -> Option<PathBuf> {
+    std::env::var("APPDATA")
+        .ok()
+        .map(|app_data| PathBuf::from(app_data).join(SCAN_JOURNAL_LOCATION))
+}
@@ -0,0 +1,651 @@
+//! Extracting the VBA macros from Office documents, a classic way onto a machine: the user is sent a document which
+//! runs a macro as it is opened, downloading and running the malware. The document itself holds nothing a byte
+//! signature would match, as the macro source is stored compressed.
+//!
+//! A legacy document (`.doc`, `.xls`) is an OLE compound file with its VBA project stored in it, whereas an OOXML
+//! document (`.docm`, `.xlsm`) is a zip holding the VBA project as a compound file of its own, `vbaProject.bin`. Either
+//! way, the `dir` stream of the project lists its modules, and the source of each module is stored compressed in a
+//! stream of the project. A document is routed here by its magic bytes rather than its extension, so a document renamed
+//! to look like something else is still scanned.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use super::filetype::{COMPOUND_FILE_MAGIC, ZIP_MAGIC};
+
+/// The largest stream read from a compound file, and the largest module source decompressed, so that a malformed or
+/// malicious document cannot exhaust the memory of the engine
+const MAX_STREAM_LEN: usize = 16 * 1024 * 1024;
+
+/// Subroutines Office runs by itself, as the document is opened, closed or created
+const AUTO_EXEC: [&str; 12] = [
+    "AutoOpen",
+    "AutoExec",
+    "AutoClose",
+    "AutoNew",
+    "Auto_Open",
+    "Auto_Close",
+    "Document_Open",
+    "Document_Close",
+    "Document_New",
+    "DocumentOpen",
+    "Workbook_Open",
+    "Workbook_Activate",
+];
+
+/// Calls and objects which run commands, download files or run code in memory, which a macro has little legitimate
+/// reason to use
+const SUSPICIOUS_CALLS: [&str; 14] = [
+    "Shell",
+    "ShellExecute",
+    "WScript.Shell",
+    "Shell.Application",
+    "URLDownloadToFile",
+    "MSXML2.XMLHTTP",
+    "WinHttp.WinHttpRequest",
+    "ADODB.Stream",
+    "PowerShell",
+    "cmd.exe",
+    "VirtualAlloc",
+    "RtlMoveMemory",
+    "CreateThread",
+    "winmgmts",
+];
+
+/// Marks a free sector, or the end of a chain of sectors, in the allocation tables of a compound file
+const FREE_SECTOR: u32 = 0xFFFF_FFFF;
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+/// Marks a directory entry as having no sibling or child
+const NO_STREAM: u32 = 0xFFFF_FFFF;
+
+/// The number of allocation table sectors listed in the header of a compound file, beyond which they are listed in a
+/// chain of sectors of their own
+const HEADER_DIFAT_ENTRIES: usize = 109;
+
+/// The source of a macro module extracted from a document
+pub struct MacroModule {
+    pub name: String,
+    pub source: String,
+}
+
+/// What was found in the macros of a document which is detected
+pub struct MacroFinding {
+    /// The module the suspicious calls were found in
+    pub module: String,
+    /// The subroutines which run by themselves, from any module of the document
+    pub auto_exec: Vec<String>,
+    pub suspicious_calls: Vec<String>,
+}
+
+/// Extracts the VBA macros from the file, where it is an Office document with a VBA project.
+///
+/// # Returns
+///
+/// The source of each module, which is empty where the file is not an Office document, has no macros, or its VBA
+/// project could not be parsed. An error is only returned where the file could not be read.
+pub fn extract_macros(file: &File) -> io::Result<Vec<MacroModule>> {
+    let mut reader = file;
+    let mut header = [0u8; 8];
+    reader.seek(SeekFrom::Start(0))?;
+    match reader.read_exact(&mut header) {
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    }
+
+    if header == COMPOUND_FILE_MAGIC {
+        return vba_modules(reader);
+    }
+
+    if header.starts_with(ZIP_MAGIC) {
+        // a zip which is not an OOXML document, or is not a valid zip at all, is left to the other engines
+        let Ok(mut archive) = zip::ZipArchive::new(reader) else {
+            return Ok(Vec::new());
+        };
+
+        let mut modules = Vec::new();
+        for i in 0..archive.len() {
+            let Ok(entry) = archive.by_index(i) else {
+                continue;
+            };
+            if !entry.name().to_ascii_lowercase().ends_with("vbaproject.bin") || entry.size() > MAX_STREAM_LEN as u64 {
+                continue;
+            }
+
+            let mut project = Vec::with_capacity(entry.size() as usize);
+            if entry.take(MAX_STREAM_LEN as u64).read_to_end(&mut project).is_ok() {
+                modules.extend(vba_modules(Cursor::new(project))?);
+            }
+        }
+
+        return Ok(modules);
+    }
+
+    Ok(Vec::new())
+}
+
+/// Looks for the subroutines which run by themselves and the suspicious calls in the macros of a document. Neither is
+/// enough alone, as plenty of legitimate documents run a macro as they are opened, and plenty of legitimate macros
+/// run a program, so the document is only detected where a macro runs by itself and a module makes suspicious calls.
+///
+/// Comments are skipped, so a call mentioned in a comment is not counted.
+///
+/// # Returns
+///
+/// What was found, where the document is detected.
+pub fn analyse_macros(modules: &[MacroModule]) -> Option<MacroFinding> {
+    let mut auto_exec = Vec::new();
+    let mut suspicious: Option<(&str, Vec<String>)> = None;
+
+    for module in modules {
+        let mut calls = Vec::new();
+
+        for line in module.source.lines().map(str::trim) {
+            let lower = line.to_ascii_lowercase();
+            if lower.starts_with('\'') || lower.starts_with("rem ") {
+                continue;
+            }
+
+            if let Some(name) = declared_subroutine(line)
+                && let Some(entry) = AUTO_EXEC.iter().find(|e| e.eq_ignore_ascii_case(name))
+                && !auto_exec.iter().any(|e| e == entry)
+            {
+                auto_exec.push(entry.to_string());
+            }
+
+            for call in SUSPICIOUS_CALLS {
+                if !calls.iter().any(|c| c == call) && contains_word(&lower, &call.to_ascii_lowercase()) {
+                    calls.push(call.to_string());
+                }
+            }
+        }
+
+        // the module with the most suspicious calls is reported, as the one an analyst should look at first
+        if !calls.is_empty() && suspicious.as_ref().is_none_or(|(_, most)| calls.len() > most.len()) {
+            suspicious = Some((&module.name, calls));
+        }
+    }
+
+    match (auto_exec.is_empty(), suspicious) {
+        (false, Some((module, suspicious_calls))) => Some(MacroFinding {
+            module: module.to_string(),
+            auto_exec,
+            suspicious_calls,
+        }),
+        _ => None,
+    }
+}
+
+/// Gets the name of the subroutine or function the line declares, such as `Document_Open` from
+/// `Private Sub Document_Open()`
+fn declared_subroutine(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace().peekable();
+    while words
+        .peek()
+        .is_some_and(|w| ["private", "public", "friend", "static"].contains(&w.to_ascii_lowercase().as_str()))
+    {
+        words.next();
+    }
+
+    match words.next()?.to_ascii_lowercase().as_str() {
+        "sub" | "function" => words.next()?.split('(').next(),
+        _ => None,
+    }
+}
+
+/// Whether the needle appears in the haystack as a whole word, so that `Shell` is found in `Shell "cmd"` but not in
+/// `EggShells`. Both should be lowercase.
+fn contains_word(haystack: &str, needle: &str) -> bool {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    haystack.match_indices(needle).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + needle.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+/// Extracts the source of each module of each VBA project in the compound file
+fn vba_modules<R: Read + Seek>(reader: R) -> io::Result<Vec<MacroModule>> {
+    let Some(mut compound) = CompoundFile::open(reader)? else {
+        return Ok(Vec::new());
+    };
+
+    // the project is the storage named VBA, which is at Macros\VBA in Word and _VBA_PROJECT_CUR\VBA in Excel
+    let projects: Vec<usize> = (0..compound.entries.len())
+        .filter(|&i| compound.entries[i].is_storage() && compound.entries[i].name.eq_ignore_ascii_case("VBA"))
+        .collect();
+
+    let mut modules = Vec::new();
+    for project in projects {
+        let streams = compound.children(project);
+        let find = |compound: &CompoundFile<R>, name: &str| {
+            streams
+                .iter()
+                .copied()
+                .find(|&i| compound.entries[i].is_stream() && compound.entries[i].name.eq_ignore_ascii_case(name))
+        };
+
+        let Some(dir) = find(&compound, "dir") else {
+            continue;
+        };
+        let Some(dir) = compound.stream(dir)?.and_then(|data| decompress(&data)) else {
+            continue;
+        };
+
+        for module in parse_dir(&dir) {
+            let Some(stream) = find(&compound, &module.stream) else {
+                continue;
+            };
+            let Some(data) = compound.stream(stream)? else {
+                continue;
+            };
+
+            // the stream holds the compiled module ahead of its source, which starts at the offset given in dir
+            if let Some(source) = data.get(module.offset as usize..).and_then(decompress) {
+                modules.push(MacroModule {
+                    name: module.name,
+                    source: source.iter().map(|&b| b as char).collect(),
+                });
+            }
+        }
+    }
+
+    Ok(modules)
+}
+
+/// A module as listed in the dir stream of a VBA project
+struct ModuleRecord {
+    name: String,
+    /// The name of the stream holding the module
+    stream: String,
+    /// The offset of the compressed source within the stream
+    offset: u32,
+}
+
+/// Parses the modules from the decompressed dir stream of a VBA project (MS-OVBA 2.3.4.2). Each record of the stream
+/// is an ID and a size followed by its data, so records other than those describing a module are skipped over.
+fn parse_dir(dir: &[u8]) -> Vec<ModuleRecord> {
+    const PROJECT_VERSION: u16 = 0x0009;
+    const MODULE_NAME: u16 = 0x0019;
+    const MODULE_STREAM_NAME: u16 = 0x001A;
+    const MODULE_STREAM_NAME_UNICODE: u16 = 0x0032;
+    const MODULE_OFFSET: u16 = 0x0031;
+    const MODULE_TERMINATOR: u16 = 0x002B;
+
+    let mut modules = Vec::new();
+    let (mut name, mut stream, mut offset) = (None, None, 0);
+
+    let mut pos = 0;
+    while let (Some(id), Some(size)) = (u16_at(dir, pos), u32_at(dir, pos + 2)) {
+        pos += 6;
+        // the version record gives a size of 4, but is followed by 6 bytes of data
+        let size = if id == PROJECT_VERSION { 6 } else { size as usize };
+        let Some(data) = dir.get(pos..pos.saturating_add(size)) else {
+            break;
+        };
+        pos += size;
+
+        match id {
+            MODULE_NAME => {
+                name = Some(String::from_utf8_lossy(data).to_string());
+                stream = None;
+                offset = 0;
+            }
+            // the stream name is given in the code page of the project, followed by UTF-16, which is preferred as it is
+            // how the stream is named in the compound file
+            MODULE_STREAM_NAME if stream.is_none() => stream = Some(String::from_utf8_lossy(data).to_string()),
+            MODULE_STREAM_NAME_UNICODE => stream = Some(utf16(data)),
+            MODULE_OFFSET => offset = u32_at(data, 0).unwrap_or(0),
+            MODULE_TERMINATOR => {
+                if let (Some(name), Some(stream)) = (name.take(), stream.take()) {
+                    modules.push(ModuleRecord { name, stream, offset });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    modules
+}
+
+/// Decompresses data compressed with the VBA compression of MS-OVBA 2.4.1, which the dir stream and the source of each
+/// module are stored with.
+///
+/// # Returns
+///
+/// The decompressed data, or None where it is not validly compressed or decompresses to more than
+/// [`MAX_STREAM_LEN`].
+fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    const CHUNK_LEN: usize = 4096;
+
+    if data.first() != Some(&1) {
+        return None;
+    }
+
+    let mut out = Vec::new();
+    let mut pos = 1;
+
+    while let Some(header) = u16_at(data, pos) {
+        let chunk_start = pos;
+        let chunk_end = (chunk_start + (header & 0x0FFF) as usize + 3).min(data.len());
+        pos += 2;
+        let out_chunk_start = out.len();
+
+        if header & 0x8000 == 0 {
+            // an uncompressed chunk is always the full chunk length
+            out.extend_from_slice(data.get(pos..(pos + CHUNK_LEN).min(data.len()))?);
+            pos += CHUNK_LEN;
+        } else {
+            while pos < chunk_end {
+                let flags = data[pos];
+                pos += 1;
+
+                for bit in 0..8 {
+                    if pos >= chunk_end {
+                        break;
+                    }
+
+                    if flags & (1 << bit) == 0 {
+                        out.push(data[pos]);
+                        pos += 1;
+                        continue;
+                    }
+
+                    // a copy token, copying from earlier in the chunk. The split of the token between the offset and
+                    // the length depends on how far into the chunk it is.
+                    let token = u16_at(data, pos)?;
+                    pos += 2;
+
+                    let decompressed = out.len() - out_chunk_start;
+                    let mut bit_count = 4;
+                    while (1 << bit_count) < decompressed && bit_count < 12 {
+                        bit_count += 1;
+                    }
+
+                    let length_mask = 0xFFFFu16 >> bit_count;
+                    let length = (token & length_mask) as usize + 3;
+                    let offset = (token >> (16 - bit_count)) as usize + 1;
+                    if offset > decompressed {
+                        return None;
+                    }
+
+                    let copy_start = out.len() - offset;
+                    for i in 0..length {
+                        out.push(out[copy_start + i]);
+                    }
+                }
+            }
+            pos = chunk_end;
+        }
+
+        if out.len() > MAX_STREAM_LEN {
+            return None;
+        }
+    }
+
+    Some(out)
+}
+
+/// An entry of the directory of a compound file
+struct DirEntry {
+    name: String,
+    kind: u8,
+    left: u32,
+    right: u32,
+    child: u32,
+    start_sector: u32,
+    size: u64,
+}
+
+impl DirEntry {
+    fn is_storage(&self) -> bool {
+        self.kind == 1
+    }
+
+    fn is_stream(&self) -> bool {
+        self.kind == 2
+    }
+}
+
+/// A reader of an OLE compound file (MS-CFB), the container of legacy Office documents and of VBA projects. The file is
+/// read a sector at a time as its streams are read, rather than loaded whole, as compound files such as MSIs can be
+/// large.
+struct CompoundFile<R> {
+    reader: R,
+    sector_len: usize,
+    mini_sector_len: usize,
+    /// Streams smaller than this are stored in the mini stream
+    mini_stream_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    entries: Vec<DirEntry>,
+    /// The mini stream, read when a stream stored in it is first read
+    mini_stream: Option<Vec<u8>>,
+}
+
+impl<R: Read + Seek> CompoundFile<R> {
+    /// Opens the compound file, reading its allocation tables and directory.
+    ///
+    /// # Returns
+    ///
+    /// The compound file, or None where it is not a valid compound file.
+    fn open(mut reader: R) -> io::Result<Option<Self>> {
+        let mut header = [0u8; 512];
+        reader.seek(SeekFrom::Start(0))?;
+        if reader.read_exact(&mut header).is_err() || header[..8] != COMPOUND_FILE_MAGIC {
+            return Ok(None);
+        }
+
+        let field = |offset: usize| u32_at(&header, offset).unwrap_or(FREE_SECTOR);
+        let sector_len = match u16_at(&header, 0x1E) {
+            Some(9) => 512,
+            Some(12) => 4096,
+            _ => return Ok(None),
+        };
+        let mini_sector_len = match u16_at(&header, 0x20) {
+            Some(shift) if shift < 12 => 1 << shift,
+            _ => return Ok(None),
+        };
+
+        let mut compound = CompoundFile {
+            reader,
+            sector_len,
+            mini_sector_len,
+            mini_stream_cutoff: field(0x38) as u64,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            entries: Vec::new(),
+            mini_stream: None,
+        };
+
+        // the sectors of the allocation table are listed in the header, then in a chain of further sectors
+        let num_fat_sectors = field(0x2C) as usize;
+        let mut fat_sectors: Vec<u32> = (0..HEADER_DIFAT_ENTRIES).map(|i| field(0x4C + i * 4)).collect();
+        let mut difat_sector = field(0x44);
+        let mut visited = HashSet::new();
+        while difat_sector < END_OF_CHAIN && fat_sectors.len() < num_fat_sectors {
+            if !visited.insert(difat_sector) {
+                return Ok(None);
+            }
+            let Some(sector) = compound.read_sector(difat_sector)? else {
+                return Ok(None);
+            };
+
+            let ids = sector_len / 4 - 1;
+            fat_sectors.extend((0..ids).filter_map(|i| u32_at(&sector, i * 4)));
+            difat_sector = u32_at(&sector, ids * 4).unwrap_or(END_OF_CHAIN);
+        }
+
+        for &sector_id in fat_sectors.iter().filter(|&&s| s < END_OF_CHAIN).take(num_fat_sectors) {
+            let Some(sector) = compound.read_sector(sector_id)? else {
+                return Ok(None);
+            };
+            compound.fat.extend((0..sector_len / 4).filter_map(|i| u32_at(&sector, i * 4)));
+        }
+
+        let Some(directory) = compound.read_chain(field(0x30), None, false)? else {
+            return Ok(None);
+        };
+        // the upper half of the size of a stream is only used by version 4 files, which have 4096 byte sectors, as
+        // version 3 files may leave anything in it
+        let wide_sizes = sector_len == 4096;
+        compound.entries = directory.chunks_exact(128).map(|e| parse_dir_entry(e, wide_sizes)).collect();
+        if compound.entries.is_empty() {
+            return Ok(None);
+        }
+
+        let num_mini_fat_sectors = field(0x40);
+        if num_mini_fat_sectors > 0 {
+            let Some(mini_fat) = compound.read_chain(field(0x3C), None, false)? else {
+                return Ok(None);
+            };
+            compound.mini_fat = (0..mini_fat.len() / 4).filter_map(|i| u32_at(&mini_fat, i * 4)).collect();
+        }
+
+        Ok(Some(compound))
+    }
+
+    /// Gets the entries directly beneath the storage, which are held as a tree of siblings below its child
+    fn children(&self, storage: usize) -> Vec<usize> {
+        let mut children = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.entries[storage].child];
+
+        while let Some(id) = pending.pop() {
+            let index = id as usize;
+            if id == NO_STREAM || index >= self.entries.len() || !visited.insert(index) {
+                continue;
+            }
+
+            children.push(index);
+            pending.push(self.entries[index].left);
+            pending.push(self.entries[index].right);
+        }
+
+        children
+    }
+
+    /// Reads the stream of the entry.
+    ///
+    /// # Returns
+    ///
+    /// The stream, or None where it is larger than [`MAX_STREAM_LEN`] or its chain of sectors is invalid.
+    fn stream(&mut self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let (start, size) = (self.entries[index].start_sector, self.entries[index].size);
+        if size > MAX_STREAM_LEN as u64 {
+            return Ok(None);
+        }
+
+        if size >= self.mini_stream_cutoff {
+            return self.read_chain(start, Some(size as usize), false);
+        }
+
+        if self.mini_stream.is_none() {
+            let (root_start, root_size) = (self.entries[0].start_sector, self.entries[0].size);
+            if root_size > MAX_STREAM_LEN as u64 {
+                return Ok(None);
+            }
+            match self.read_chain(root_start, Some(root_size as usize), false)? {
+                Some(mini_stream) => self.mini_stream = Some(mini_stream),
+                None => return Ok(None),
+            }
+        }
+
+        self.read_chain(start, Some(size as usize), true)
+    }
+
+    /// Reads the chain of sectors starting at the sector, from the file or from the mini stream, truncated to the size
+    /// where it is given
+    fn read_chain(&mut self, start: u32, size: Option<usize>, mini: bool) -> io::Result<Option<Vec<u8>>> {
+        let mut data = Vec::new();
+        let mut sector = start;
+        // a chain can be no longer than its table, so a longer chain loops
+        let mut remaining = if mini { self.mini_fat.len() } else { self.fat.len() } + 1;
+
+        while sector < END_OF_CHAIN && size.is_none_or(|size| data.len() < size) {
+            if remaining == 0 || data.len() > MAX_STREAM_LEN {
+                return Ok(None);
+            }
+            remaining -= 1;
+
+            if mini {
+                let offset = sector as usize * self.mini_sector_len;
+                let Some(bytes) =
+                    self.mini_stream.as_ref().and_then(|ms| ms.get(offset..offset + self.mini_sector_len))
+                else {
+                    return Ok(None);
+                };
+                data.extend_from_slice(bytes);
+            } else {
+                let Some(bytes) = self.read_sector(sector)? else {
+                    return Ok(None);
+                };
+                data.extend_from_slice(&bytes);
+            }
+
+            let table = if mini { &self.mini_fat } else { &self.fat };
+            sector = match table.get(sector as usize) {
+                Some(&next) => next,
+                None => return Ok(None),
+            };
+        }
+
+        if let Some(size) = size {
+            if data.len() < size {
+                return Ok(None);
+            }
+            data.truncate(size);
+        }
+
+        Ok(Some(data))
+    }
+
+    /// Reads the sector, which follows the header and the sectors before it
+    fn read_sector(&mut self, sector: u32) -> io::Result<Option<Vec<u8>>> {
+        let offset = (sector as u64 + 1) * self.sector_len as u64;
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        let mut data = vec![0u8; self.sector_len];
+        match self.reader.read_exact(&mut data) {
+            Ok(_) => Ok(Some(data)),
+            // a sector past the end of the file is an invalid compound file rather than a failure to read it
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn parse_dir_entry(entry: &[u8], wide_sizes: bool) -> DirEntry {
+    // the length of the name is given in bytes, including its terminating null
+    let name_len = (u16_at(entry, 64).unwrap_or(0) as usize).clamp(2, 64) - 2;
+
+    DirEntry {
+        name: utf16(&entry[..name_len]),
+        kind: entry[66],
+        left: u32_at(entry, 68).unwrap_or(NO_STREAM),
+        right: u32_at(entry, 72).unwrap_or(NO_STREAM),
+        child: u32_at(entry, 76).unwrap_or(NO_STREAM),
+        start_sector: u32_at(entry, 116).unwrap_or(END_OF_CHAIN),
+        size: match wide_sizes {
+            true => (u64::from(u32_at(entry, 124).unwrap_or(0)) << 32) | u64::from(u32_at(entry, 120).unwrap_or(0)),
+            false => u64::from(u32_at(entry, 120).unwrap_or(0)),
+        },
+    }
+}
+
+fn utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
@@ -0,0 +1,102 @@
+//! Holding back large files from a scan whilst the system is low on physical memory.
+//!
+//! Scanning a large file can read much of it into memory at once, such as to fuzzy hash it or extract its macros, so
+//! on a machine short of memory a scan could push the system into paging and slow everything down. Whilst available
+//! physical memory is below the threshold in the settings, a large file waits before it is given to the worker, until
+//! memory recovers or the longest pause allowed has passed. Smaller files carry on being scanned meanwhile, as they
+//! are read in bounded chunks.
+
+use std::{
+    mem,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use shared_std::settings::MemoryPressureSettings;
+use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+/// The least time between samples of the available memory, as the throttle is checked for every file scanned
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+pub struct MemoryThrottle {
+    enabled: AtomicBool,
+    min_available_bytes: AtomicU64,
+    large_file_bytes: AtomicU64,
+    max_pause_secs: AtomicU64,
+    // when the available memory was last sampled, and whether it was below the threshold
+    last_sample: Mutex<Option<(Instant, bool)>>,
+}
+
+impl MemoryThrottle {
+    pub fn new() -> Self {
+        let throttle = MemoryThrottle {
+            enabled: AtomicBool::new(false),
+            min_available_bytes: AtomicU64::new(0),
+            large_file_bytes: AtomicU64::new(0),
+            max_pause_secs: AtomicU64::new(0),
+            last_sample: Mutex::new(None),
+        };
+        throttle.set_limits(&MemoryPressureSettings::default());
+
+        throttle
+    }
+
+    /// Applies the limits from the settings, from the next file scanned
+    pub fn set_limits(&self, settings: &MemoryPressureSettings) {
+        self.enabled.store(settings.enabled, Ordering::Relaxed);
+        self.min_available_bytes.store(settings.min_available_mb.saturating_mul(BYTES_PER_MB), Ordering::Relaxed);
+        self.large_file_bytes.store(settings.large_file_mb.saturating_mul(BYTES_PER_MB), Ordering::Relaxed);
+        self.max_pause_secs.store(settings.max_pause_secs, Ordering::Relaxed);
+        *self.last_sample.lock().unwrap() = None;
+    }
+
+    /// Whether a file of the given size is held back whilst memory is low
+    pub fn is_large(&self, len: u64) -> bool {
+        len >= self.large_file_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The longest a file is held back for, after which it is scanned regardless so that a machine which stays short
+    /// of memory cannot stall a scan forever
+    pub fn max_pause(&self) -> Duration {
+        Duration::from_secs(self.max_pause_secs.load(Ordering::Relaxed))
+    }
+
+    /// Whether the available physical memory is below the threshold, where throttling is enabled. The memory is
+    /// sampled at most once per [`SAMPLE_INTERVAL`], the last sample being reused in between.
+    pub fn under_pressure(&self) -> bool {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let mut last_sample = self.last_sample.lock().unwrap();
+        if let Some((sampled_at, low)) = *last_sample
+            && sampled_at.elapsed() < SAMPLE_INTERVAL
+        {
+            return low;
+        }
+
+        // where the memory cannot be read, the scan is not held up on account of it
+        let low = available_physical_memory()
+            .is_some_and(|available| available < self.min_available_bytes.load(Ordering::Relaxed));
+        *last_sample = Some((Instant::now(), low));
+
+        low
+    }
+}
+
+/// Gets the physical memory available to be used without paging, or None if it could not be read
+fn available_physical_memory() -> Option<u64> {
+    let mut status = MEMORYSTATUSEX {
+        dwLength: mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..Default::default()
+    };
+
+    unsafe { GlobalMemoryStatusEx(&mut status) }.ok()?;
+
+    Some(status.ullAvailPhys)
+}
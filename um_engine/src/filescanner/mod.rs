@@ -0,0 +1,34 @@
+mod authenticode;
+mod autoruns;
+mod benchmark;
+mod bloom;
+mod browser_extensions;
+mod conditions;
+mod context;
+pub mod detector;
+mod drivers;
+mod exclusions;
+mod filetype;
+mod fuzzy;
+mod heuristics;
+mod image;
+mod imports;
+mod injection;
+mod journal;
+mod macros;
+mod memory;
+pub mod filescanner;
+mod pe;
+mod privileges;
+mod process;
+mod registry;
+mod remediation;
+pub mod scheduler;
+mod scheduled_tasks;
+mod shortcut;
+mod signatures;
+mod update;
+mod volume;
+
+// to prevent requiring double filescanner::filescanner in imports
+pub use filescanner::FileScanner;
@@ -0,0 +1,375 @@
+//! Minimal parsing of the Portable Executable format for use by the file scanner.
+//!
+//! Only the parts of the format which the scanner makes decisions on are parsed. As every file being scanned
+//! is untrusted, all offsets and lengths read from the headers are bounds checked against the size of the file
+//! before being used; a malformed PE is treated as though it was not a PE at all.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// 'MZ'
+const DOS_MAGIC: u16 = 0x5A4D;
+/// 'PE\0\0'
+const NT_SIGNATURE: u32 = 0x0000_4550;
+/// Offset of `e_lfanew` in the DOS header
+const E_LFANEW_OFFSET: usize = 0x3C;
+const DOS_HEADER_SIZE: usize = 64;
+/// The NT signature followed by the COFF file header
+const NT_HEADER_SIZE: usize = 24;
+const SECTION_HEADER_SIZE: usize = 40;
+/// The optional header up to and including `SizeOfImage`, which sits at the same offset in PE32 and PE32+
+const OPTIONAL_HEADER_IMAGE_FIELDS_SIZE: usize = 60;
+/// The optional header of a PE32+ up to the end of the import directory, the furthest into the header anything is read
+const OPTIONAL_HEADER_READ_SIZE: usize = 128;
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+/// The index of the import directory amongst the data directories
+const IMPORT_DIRECTORY_INDEX: usize = 1;
+const DATA_DIRECTORY_SIZE: usize = 8;
+/// The PE specification limits the loader to 96 sections
+const MAX_SECTIONS: u16 = 96;
+
+/// A single entry from the section table of a PE
+#[derive(Debug, Clone)]
+pub struct PeSection {
+    pub name: String,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    /// The RVA the section is loaded at
+    pub virtual_address: u32,
+}
+
+impl PeSection {
+    /// Gets the offset and length of the section's raw data within the file, clamped to the length of the file.
+    ///
+    /// # Returns
+    ///
+    /// None if the section has no raw data on disk, or if the raw data lies entirely outside of the file.
+    pub fn raw_data_range(&self, file_len: u64) -> Option<(u64, u64)> {
+        let offset = self.raw_offset as u64;
+        if self.raw_size == 0 || offset >= file_len {
+            return None;
+        }
+
+        let len = (self.raw_size as u64).min(file_len - offset);
+
+        Some((offset, len))
+    }
+}
+
+/// The headers of a PE file which are of interest to the scanner
+#[derive(Debug, Clone)]
+pub struct PeHeaders {
+    pub file_len: u64,
+    pub sections: Vec<PeSection>,
+    /// When the linker says the PE was built, from the COFF file header
+    pub timestamp: u32,
+    /// The RVAs of the entry point and the size the PE takes once loaded, from the optional header, or 0 where the
+    /// optional header is too short to hold them
+    pub entry_point: u32,
+    pub image_size: u32,
+    /// Whether the PE is a PE32+, whose import thunks are 8 bytes rather than 4
+    pub pe32_plus: bool,
+    /// The RVA of the import directory, or None where the PE imports nothing
+    pub import_directory: Option<u32>,
+}
+
+impl PeHeaders {
+    /// Attempts to parse the headers of a PE from the reader. The reader may be positioned anywhere in the file, and
+    /// its position after the call is unspecified.
+    ///
+    /// # Returns
+    ///
+    /// - Ok(Some) if the file is a well formed PE
+    /// - Ok(None) if the file is not a PE, or the headers are malformed
+    /// - Err if there was an IO error reading the file
+    pub fn parse<R: Read + Seek>(reader: &mut R) -> io::Result<Option<PeHeaders>> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        if file_len < DOS_HEADER_SIZE as u64 {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(0))?;
+
+        //
+        // DOS header
+        //
+        let mut dos_header = [0u8; DOS_HEADER_SIZE];
+        reader.read_exact(&mut dos_header)?;
+        if read_u16(&dos_header, 0) != DOS_MAGIC {
+            return Ok(None);
+        }
+
+        let e_lfanew = read_u32(&dos_header, E_LFANEW_OFFSET) as u64;
+        if e_lfanew + NT_HEADER_SIZE as u64 > file_len {
+            return Ok(None);
+        }
+
+        //
+        // NT signature & COFF file header
+        //
+        let mut nt_header = [0u8; NT_HEADER_SIZE];
+        reader.seek(SeekFrom::Start(e_lfanew))?;
+        reader.read_exact(&mut nt_header)?;
+        if read_u32(&nt_header, 0) != NT_SIGNATURE {
+            return Ok(None);
+        }
+
+        let number_of_sections = read_u16(&nt_header, 6);
+        let timestamp = read_u32(&nt_header, 8);
+        let size_of_optional_header = read_u16(&nt_header, 20) as u64;
+        if number_of_sections == 0 || number_of_sections > MAX_SECTIONS {
+            return Ok(None);
+        }
+
+        //
+        // Optional header, of which only the fields describing the loaded image and the import directory are read
+        //
+        let optional_header_offset = e_lfanew + NT_HEADER_SIZE as u64;
+        let optional_header_len = size_of_optional_header.min(OPTIONAL_HEADER_READ_SIZE as u64);
+        let mut optional_header = Vec::new();
+        if optional_header_offset + optional_header_len <= file_len {
+            optional_header.resize(optional_header_len as usize, 0);
+            reader.seek(SeekFrom::Start(optional_header_offset))?;
+            reader.read_exact(&mut optional_header)?;
+        }
+
+        let (entry_point, image_size) = if optional_header.len() >= OPTIONAL_HEADER_IMAGE_FIELDS_SIZE {
+            (read_u32(&optional_header, 16), read_u32(&optional_header, 56))
+        } else {
+            (0, 0)
+        };
+        let pe32_plus = optional_header.len() >= 2 && read_u16(&optional_header, 0) == PE32_PLUS_MAGIC;
+        let import_directory = import_directory(&optional_header);
+
+        //
+        // Section table, which immediately follows the optional header
+        //
+        let section_table_offset = optional_header_offset + size_of_optional_header;
+        let section_table_len = number_of_sections as usize * SECTION_HEADER_SIZE;
+        if section_table_offset + section_table_len as u64 > file_len {
+            return Ok(None);
+        }
+
+        let mut section_table = vec![0u8; section_table_len];
+        reader.seek(SeekFrom::Start(section_table_offset))?;
+        reader.read_exact(&mut section_table)?;
+
+        let sections = section_table
+            .chunks_exact(SECTION_HEADER_SIZE)
+            .map(|header| {
+                let name_len = header[..8].iter().position(|b| *b == 0).unwrap_or(8);
+
+                PeSection {
+                    name: String::from_utf8_lossy(&header[..name_len]).to_string(),
+                    virtual_address: read_u32(header, 12),
+                    raw_size: read_u32(header, 16),
+                    raw_offset: read_u32(header, 20),
+                }
+            })
+            .collect();
+
+        Ok(Some(PeHeaders {
+            file_len,
+            sections,
+            timestamp,
+            entry_point,
+            image_size,
+            pe32_plus,
+            import_directory,
+        }))
+    }
+
+    /// Maps an RVA onto the offset in the file it was loaded from, through the section whose raw data holds it.
+    ///
+    /// # Returns
+    ///
+    /// None where no section holds the RVA in its raw data, such as an RVA in a section's uninitialised data, which is
+    /// not in the file at all.
+    pub fn rva_to_offset(&self, rva: u32) -> Option<u64> {
+        self.sections
+            .iter()
+            .find(|section| rva >= section.virtual_address && rva - section.virtual_address < section.raw_size)
+            .map(|section| section.raw_offset as u64 + (rva - section.virtual_address) as u64)
+            .filter(|offset| *offset < self.file_len)
+    }
+}
+
+/// Gets the RVA of the import directory from the optional header, where the header is long enough to hold it and the
+/// PE has one
+fn import_directory(optional_header: &[u8]) -> Option<u32> {
+    if optional_header.len() < 2 {
+        return None;
+    }
+
+    // the data directories follow the fields which differ in size between PE32 and PE32+
+    let (count_offset, directories_offset) = match read_u16(optional_header, 0) {
+        PE32_MAGIC => (92, 96),
+        PE32_PLUS_MAGIC => (108, 112),
+        _ => return None,
+    };
+    let entry = directories_offset + IMPORT_DIRECTORY_INDEX * DATA_DIRECTORY_SIZE;
+    if optional_header.len() < entry + DATA_DIRECTORY_SIZE
+        || read_u32(optional_header, count_offset) as usize <= IMPORT_DIRECTORY_INDEX
+    {
+        return None;
+    }
+
+    match read_u32(optional_header, entry) {
+        0 => None,
+        rva => Some(rva),
+    }
+}
+
+/// Reads a little endian u16 from the buffer at the given offset; the caller must ensure the offset is in bounds.
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+/// Reads a little endian u32 from the buffer at the given offset; the caller must ensure the offset is in bounds.
+pub(super) fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const E_LFANEW: usize = 0x40;
+    const OPTIONAL_HEADER_SIZE: usize = 0xE0;
+    const FILE_ALIGNMENT: usize = 0x200;
+
+    fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+        buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+        buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Builds a PE32 holding the sections, each given as its name, the RVA it is loaded at and its raw data, which are
+    /// laid out in the file one after another
+    pub(in crate::filescanner) fn crafted_pe(timestamp: u32, import_directory: u32, sections: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        let section_table = E_LFANEW + NT_HEADER_SIZE + OPTIONAL_HEADER_SIZE;
+        assert!(section_table + sections.len() * SECTION_HEADER_SIZE <= FILE_ALIGNMENT);
+
+        let mut pe = vec![0u8; FILE_ALIGNMENT];
+        put_u16(&mut pe, 0, DOS_MAGIC);
+        put_u32(&mut pe, E_LFANEW_OFFSET, E_LFANEW as u32);
+
+        put_u32(&mut pe, E_LFANEW, NT_SIGNATURE);
+        put_u16(&mut pe, E_LFANEW + 4, 0x14C);
+        put_u16(&mut pe, E_LFANEW + 6, sections.len() as u16);
+        put_u32(&mut pe, E_LFANEW + 8, timestamp);
+        put_u16(&mut pe, E_LFANEW + 20, OPTIONAL_HEADER_SIZE as u16);
+
+        let optional_header = E_LFANEW + NT_HEADER_SIZE;
+        put_u16(&mut pe, optional_header, PE32_MAGIC);
+        put_u32(&mut pe, optional_header + 16, sections.first().map_or(0, |(_, rva, _)| *rva));
+        put_u32(&mut pe, optional_header + 56, sections.last().map_or(0, |(_, rva, _)| rva + 0x1000));
+        put_u32(&mut pe, optional_header + 92, 16);
+        put_u32(&mut pe, optional_header + 96 + IMPORT_DIRECTORY_INDEX * DATA_DIRECTORY_SIZE, import_directory);
+
+        for (i, (name, rva, data)) in sections.iter().enumerate() {
+            let raw_offset = pe.len();
+            pe.extend_from_slice(data);
+            pe.resize(pe.len().div_ceil(FILE_ALIGNMENT) * FILE_ALIGNMENT, 0);
+            let raw_size = pe.len() - raw_offset;
+
+            let header = section_table + i * SECTION_HEADER_SIZE;
+            pe[header..header + name.len()].copy_from_slice(name.as_bytes());
+            put_u32(&mut pe, header + 8, data.len() as u32);
+            put_u32(&mut pe, header + 12, *rva);
+            put_u32(&mut pe, header + 16, raw_size as u32);
+            put_u32(&mut pe, header + 20, raw_offset as u32);
+        }
+
+        pe
+    }
+
+    fn parse(pe: Vec<u8>) -> Option<PeHeaders> {
+        PeHeaders::parse(&mut Cursor::new(pe)).unwrap()
+    }
+
+    fn sample() -> Vec<u8> {
+        crafted_pe(0x6500_0000, 0x2000, &[(".text", 0x1000, &[0xCC; 0x300]), (".idata", 0x2000, &[0; 0x80])])
+    }
+
+    #[test]
+    fn parses_crafted_pe() {
+        let headers = parse(sample()).expect("the crafted PE should parse");
+
+        assert_eq!(headers.timestamp, 0x6500_0000);
+        assert_eq!((headers.entry_point, headers.image_size), (0x1000, 0x3000));
+        assert!(!headers.pe32_plus);
+        assert_eq!(headers.import_directory, Some(0x2000));
+
+        let sections: Vec<_> = headers
+            .sections
+            .iter()
+            .map(|s| (s.name.as_str(), s.virtual_address, s.raw_offset, s.raw_size))
+            .collect();
+        assert_eq!(sections, [(".text", 0x1000, 0x200, 0x400), (".idata", 0x2000, 0x600, 0x200)]);
+        assert_eq!(headers.rva_to_offset(0x2010), Some(0x610));
+        assert_eq!(headers.rva_to_offset(0x2200), None);
+    }
+
+    #[test]
+    fn truncated_headers_are_not_a_pe() {
+        let pe = sample();
+
+        // shorter than the DOS header, then cut off part way through the NT headers, then the section table
+        let section_table = E_LFANEW + NT_HEADER_SIZE + OPTIONAL_HEADER_SIZE;
+        for len in [DOS_HEADER_SIZE - 1, E_LFANEW + NT_HEADER_SIZE - 1, section_table + 20] {
+            assert!(parse(pe[..len].to_vec()).is_none(), "truncated to {len} bytes");
+        }
+    }
+
+    #[test]
+    fn out_of_range_e_lfanew_is_not_a_pe() {
+        for e_lfanew in [u32::MAX, sample().len() as u32 - NT_HEADER_SIZE as u32 + 1] {
+            let mut pe = sample();
+            put_u32(&mut pe, E_LFANEW_OFFSET, e_lfanew);
+            assert!(parse(pe).is_none(), "e_lfanew of {e_lfanew:#x}");
+        }
+    }
+
+    #[test]
+    fn more_sections_than_the_loader_allows_is_not_a_pe() {
+        let mut pe = sample();
+        put_u16(&mut pe, E_LFANEW + 6, MAX_SECTIONS + 1);
+        // room for the whole section table, so only the count itself is wrong
+        pe.resize(FILE_ALIGNMENT * 16, 0);
+        assert!(parse(pe.clone()).is_none());
+
+        put_u16(&mut pe, E_LFANEW + 6, MAX_SECTIONS);
+        assert!(parse(pe).is_some());
+    }
+
+    #[test]
+    fn section_past_end_of_file_has_no_raw_data() {
+        let mut pe = sample();
+        let file_len = pe.len() as u64;
+        let idata = E_LFANEW + NT_HEADER_SIZE + OPTIONAL_HEADER_SIZE + SECTION_HEADER_SIZE;
+
+        // wholly past the end of the file, then starting inside it but running past its end
+        put_u32(&mut pe, idata + 20, 0x10_0000);
+        let headers = parse(pe.clone()).expect("raw data out of the file does not stop the headers parsing");
+        assert_eq!(headers.sections[1].raw_data_range(file_len), None);
+        assert_eq!(headers.rva_to_offset(0x2010), None);
+
+        put_u32(&mut pe, idata + 20, 0x700);
+        let headers = parse(pe).unwrap();
+        assert_eq!(headers.sections[1].raw_data_range(file_len), Some((0x700, file_len - 0x700)));
+
+        // a section table running past the end of the file is not a PE
+        let mut pe = sample();
+        put_u16(&mut pe, E_LFANEW + 6, 60);
+        assert!(parse(pe).is_none());
+    }
+}
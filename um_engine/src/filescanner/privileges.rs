@@ -0,0 +1,109 @@
+//! Reading files and folders the engine is denied, such as other users' profiles, with the backup privilege.
+//!
+//! A file opened with backup semantics whilst SeBackupPrivilege is enabled can be read whatever its security
+//! descriptor says. The privilege is enabled on the engine's token the first time a scan is denied something, and is
+//! left enabled; it only takes effect where an open asks for backup intent, which the scanner only does to read a file
+//! it was denied. Windows lists folders with backup intent, so once the privilege is enabled the walk can also list
+//! folders it was denied.
+//!
+//! SeRestorePrivilege is the counterpart for writing, so is not enabled, as the scanner never writes to a file it was
+//! denied.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    os::windows::fs::OpenOptionsExt,
+    path::Path,
+    sync::OnceLock,
+};
+
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, ERROR_NOT_ALL_ASSIGNED, GetLastError, HANDLE, LUID},
+        Security::{
+            AdjustTokenPrivileges, LUID_AND_ATTRIBUTES, LookupPrivilegeValueW, SE_BACKUP_NAME, SE_PRIVILEGE_ENABLED,
+            TOKEN_ADJUST_PRIVILEGES, TOKEN_PRIVILEGES, TOKEN_QUERY,
+        },
+        Storage::FileSystem::FILE_FLAG_BACKUP_SEMANTICS,
+        System::Threading::{GetCurrentProcess, OpenProcessToken},
+    },
+    core::PCWSTR,
+};
+
+use crate::utils::log::{Log, LogLevel};
+
+/// Whether the backup privilege was enabled, which is only attempted once for the life of the engine
+static BACKUP_PRIVILEGE: OnceLock<bool> = OnceLock::new();
+
+/// Opens the file for reading. Where the engine is denied the file, it is opened again with backup semantics, once
+/// the backup privilege has been enabled.
+///
+/// # Returns
+///
+/// The file, and whether it could only be opened with the backup privilege, or the error of opening the file where
+/// it could not be opened either way.
+pub fn open_for_read(path: &Path) -> io::Result<(File, bool)> {
+    match File::open(path) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied && enable_backup_privilege() => {
+            // others may go on reading, writing and deleting the file whilst it is open, as with an ordinary open
+            let file = OpenOptions::new().read(true).custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0).open(path)?;
+            Ok((file, true))
+        }
+        result => result.map(|file| (file, false)),
+    }
+}
+
+/// Lists the folder, listing it again once the backup privilege has been enabled where the engine is denied it
+pub fn read_dir(path: &Path) -> io::Result<fs::ReadDir> {
+    match fs::read_dir(path) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied && enable_backup_privilege() => fs::read_dir(path),
+        result => result,
+    }
+}
+
+/// Enables the backup privilege on the engine's token, the first time it is called. Where it cannot be enabled, such
+/// as where the engine is not running as SYSTEM or an administrator, this is logged the once.
+fn enable_backup_privilege() -> bool {
+    *BACKUP_PRIVILEGE.get_or_init(|| match adjust_token() {
+        Ok(_) => {
+            Log::new().log(LogLevel::Info, "Enabled SeBackupPrivilege, to scan files the engine is denied.");
+            true
+        }
+        Err(e) => {
+            Log::new().log(LogLevel::Warning, &format!("Files the engine is denied will not be scanned. {e}"));
+            false
+        }
+    })
+}
+
+fn adjust_token() -> Result<(), String> {
+    let mut token = HANDLE::default();
+    unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_ADJUST_PRIVILEGES | TOKEN_QUERY, &mut token) }
+        .map_err(|e| format!("Could not open the engine's token. {e}"))?;
+
+    let mut luid = LUID::default();
+    let result = unsafe { LookupPrivilegeValueW(PCWSTR::null(), SE_BACKUP_NAME, &mut luid) }
+        .map_err(|e| format!("Could not look up SeBackupPrivilege. {e}"))
+        .and_then(|_| {
+            let privileges = TOKEN_PRIVILEGES {
+                PrivilegeCount: 1,
+                Privileges: [LUID_AND_ATTRIBUTES {
+                    Luid: luid,
+                    Attributes: SE_PRIVILEGE_ENABLED,
+                }],
+            };
+
+            unsafe { AdjustTokenPrivileges(token, false, Some(&privileges), 0, None, None) }
+                .map_err(|e| format!("Could not enable SeBackupPrivilege. {e}"))?;
+
+            // the call succeeds where the token does not hold the privilege at all, only setting the last error
+            match unsafe { GetLastError() } {
+                ERROR_NOT_ALL_ASSIGNED => Err("The engine's account does not hold SeBackupPrivilege.".to_string()),
+                _ => Ok(()),
+            }
+        });
+
+    let _ = unsafe { CloseHandle(token) };
+
+    result
+}
@@ -0,0 +1,40 @@
+//! Resolving a running process to the executable it was started from, for a scan of the process.
+//!
+//! Only the executable on disk is scanned, not the memory of the process, so a process whose code was changed once it
+//! had started, such as by injection, is not detected by what it now runs. Signs of injection are looked for in the
+//! memory of a process separately, by [`super::injection`].
+
+use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+
+use windows::{
+    Win32::{
+        Foundation::CloseHandle,
+        System::Threading::{
+            OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION, QueryFullProcessImageNameW,
+        },
+    },
+    core::PWSTR,
+};
+
+/// The longest path an executable may have, as paths beyond MAX_PATH are allowed with long path support
+const MAX_IMAGE_PATH_LEN: usize = 32_768;
+
+/// Gets the path of the executable the process was started from.
+///
+/// # Returns
+///
+/// The path, or an error if there is no such process or it could not be opened, such as a protected process.
+pub fn process_image(pid: u32) -> Result<PathBuf, String> {
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }
+        .map_err(|e| format!("Could not open process {pid}. {e}"))?;
+
+    let mut buf = vec![0u16; MAX_IMAGE_PATH_LEN];
+    let mut len = buf.len() as u32;
+    let result =
+        unsafe { QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(buf.as_mut_ptr()), &mut len) };
+    let _ = unsafe { CloseHandle(process) };
+
+    result.map_err(|e| format!("Could not get the executable of process {pid}. {e}"))?;
+
+    Ok(PathBuf::from(OsString::from_wide(&buf[..len as usize])))
+}
@@ -0,0 +1,191 @@
+//! Walking a subtree of the registry, reading the contents of each value and resolving the files it references.
+//!
+//! Malware keeps its persistence, and often its payload, in the registry: a Run key may point at a dropped binary, a
+//! COM server at a DLL, or a value may hold an encoded script which another value runs. Each string value is resolved
+//! to the files it references in the same way an autorun command is resolved, along with any path given as an
+//! argument, such as the DLL given to rundll32. The contents of every string and binary value are also given in
+//! lowercase, for matching against registry signatures.
+//!
+//! Keys are walked by their path from the key the walk started from and opened as they are reached, so a wide
+//! subtree does not hold a handle open for every key waiting to be walked.
+
+use std::{collections::VecDeque, path::PathBuf};
+
+use shared_std::file_scanner::{MAX_REGISTRY_DATA_LISTED, RegistryRoot, RegistryScanRequest, RegistryValue};
+use windows_registry::{CLASSES_ROOT, CURRENT_CONFIG, Key, LOCAL_MACHINE, Type, USERS, Value};
+
+use super::{
+    autoruns::{expand_env_vars, normalise_native_path, resolve_command},
+    filescanner::to_hex,
+};
+
+/// A value read from the registry, with what it holds in the forms it is scanned in
+pub struct RegistryEntry {
+    pub location: RegistryValue,
+    /// The contents of the value in lowercase, in each form signatures are matched against. Binary data is read both
+    /// as single byte and as UTF-16 text, as either may be used to hide a script.
+    pub contents: Vec<String>,
+    /// The files on disk the value references
+    pub files: Vec<PathBuf>,
+}
+
+/// A walk of the values of a subtree of the registry, in no particular order
+pub struct RegistryWalk {
+    start: Key,
+    /// The full path of the key the walk started from, such as `HKLM\Software`
+    pub root: String,
+    // keys yet to be read, by their path beneath the start
+    keys: Vec<String>,
+    // values read from the last key, yet to be walked
+    entries: VecDeque<RegistryEntry>,
+    pub keys_scanned: u64,
+    pub inaccessible_keys: u64,
+}
+
+impl RegistryWalk {
+    /// Starts a walk of the subtree, failing where the key it starts from cannot be opened
+    pub fn new(request: &RegistryScanRequest) -> Result<Self, String> {
+        let (hive, hive_name) = match request.root_key {
+            RegistryRoot::LocalMachine => (LOCAL_MACHINE, "HKLM"),
+            RegistryRoot::Users => (USERS, "HKU"),
+            RegistryRoot::ClassesRoot => (CLASSES_ROOT, "HKCR"),
+            RegistryRoot::CurrentConfig => (CURRENT_CONFIG, "HKCC"),
+        };
+
+        let subkey = request.subkey.trim_matches('\\');
+        let root = match subkey.is_empty() {
+            true => hive_name.to_string(),
+            false => format!("{hive_name}\\{subkey}"),
+        };
+
+        let start = hive.open(subkey).map_err(|e| format!("Could not open {root}. {e}"))?;
+
+        Ok(RegistryWalk {
+            start,
+            root,
+            keys: vec![String::new()],
+            entries: VecDeque::new(),
+            keys_scanned: 0,
+            inaccessible_keys: 0,
+        })
+    }
+
+    /// Reads the values of the next key, queueing the keys beneath it
+    fn read_key(&mut self, relative: String) {
+        let Ok(key) = self.start.open(&relative) else {
+            self.inaccessible_keys += 1;
+            return;
+        };
+        self.keys_scanned += 1;
+
+        let path = match relative.is_empty() {
+            true => self.root.clone(),
+            false => format!("{}\\{relative}", self.root),
+        };
+
+        if let Ok(values) = key.values() {
+            self.entries
+                .extend(values.filter_map(|(name, value)| read_entry(&path, name, &value)));
+        }
+
+        if let Ok(subkeys) = key.keys() {
+            self.keys.extend(subkeys.map(|name| match relative.is_empty() {
+                true => name,
+                false => format!("{relative}\\{name}"),
+            }));
+        }
+    }
+}
+
+impl Iterator for RegistryWalk {
+    type Item = RegistryEntry;
+
+    fn next(&mut self) -> Option<RegistryEntry> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Some(entry);
+            }
+
+            let relative = self.keys.pop()?;
+            self.read_key(relative);
+        }
+    }
+}
+
+/// Reads a value into an entry, or None for a value of a type which holds neither text nor a payload, such as a
+/// number
+fn read_entry(key: &str, name: String, value: &Value) -> Option<RegistryEntry> {
+    let bytes: &[u8] = value;
+
+    let (data, contents, files) = match value.ty() {
+        Type::String | Type::ExpandString => {
+            let text = utf16_text(bytes);
+            let files = referenced_files(&text);
+            (text.clone(), vec![text.to_lowercase()], files)
+        }
+        // each string of a multi string is resolved on its own, as each may be a command or path
+        Type::MultiString => {
+            let text = utf16_text(bytes);
+            let files = text.split('\0').flat_map(referenced_files).collect();
+            let text = text.replace('\0', "\n");
+            (text.clone(), vec![text.to_lowercase()], files)
+        }
+        Type::Bytes => {
+            let single_byte: String = bytes.iter().map(|&b| b as char).collect();
+            let contents = vec![single_byte.to_lowercase(), utf16_text(bytes).to_lowercase()];
+            let listed = &bytes[..bytes.len().min(MAX_REGISTRY_DATA_LISTED / 2)];
+            (to_hex(listed), contents, Vec::new())
+        }
+        _ => return None,
+    };
+
+    Some(RegistryEntry {
+        location: RegistryValue {
+            key: key.to_string(),
+            value: name,
+            data: data.chars().take(MAX_REGISTRY_DATA_LISTED).collect(),
+        },
+        contents,
+        files,
+    })
+}
+
+/// Decodes the UTF-16 data of a value, dropping the terminating nuls
+fn utf16_text(bytes: &[u8]) -> String {
+    let chars: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+
+    String::from_utf16_lossy(&chars).trim_end_matches('\0').to_string()
+}
+
+/// Resolves the files a string value references, being the binary it starts where it is a command, and each path
+/// given in it as an argument. Text which holds no path is not resolved, as most values are not commands and looking
+/// each one up on disk would be slow.
+pub(super) fn referenced_files(text: &str) -> Vec<PathBuf> {
+    let expanded = expand_env_vars(text);
+    if !normalise_native_path(&expanded).contains(":\\") {
+        return Vec::new();
+    }
+
+    let mut files: Vec<PathBuf> = resolve_command(text).into_iter().collect();
+
+    // an argument is resolved from each drive letter it starts at, such as the DLL in `rundll32.exe C:\x.dll,Entry`
+    for part in expanded.split(['"', ',']) {
+        let starts = part
+            .match_indices(":\\")
+            .filter_map(|(i, _)| i.checked_sub(1))
+            .filter(|&start| part.as_bytes()[start].is_ascii_alphabetic());
+
+        for start in starts {
+            if let Some(file) = resolve_command(&part[start..])
+                && !files.contains(&file)
+            {
+                files.push(file);
+            }
+        }
+    }
+
+    files
+}
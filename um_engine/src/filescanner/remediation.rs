@@ -0,0 +1,73 @@
+//! Acting on files detected as malicious, according to the action chosen for the scan.
+
+use std::{fs, io, os::windows::ffi::OsStrExt, path::Path};
+
+use shared_std::file_scanner::{ActionResult, DetectionAction, MatchedIOC};
+use windows::{
+    Win32::{
+        Foundation::ERROR_SHARING_VIOLATION,
+        Storage::FileSystem::{MOVEFILE_DELAY_UNTIL_REBOOT, MoveFileExW},
+    },
+    core::PCWSTR,
+};
+
+use crate::quarantine::QuarantineStore;
+
+/// Takes the action on the detected file, reporting what was actually done. A failure to act leaves the file in place
+/// and is reported rather than hidden, as a detection which was not handled still needs the user's attention.
+pub fn take_action(
+    action: DetectionAction,
+    ioc: &MatchedIOC,
+    quarantine: &QuarantineStore,
+) -> ActionResult {
+    match action {
+        DetectionAction::Report => ActionResult::None,
+        DetectionAction::Quarantine => match quarantine.quarantine(&ioc.file, &ioc.hash) {
+            Ok(entry) => ActionResult::Quarantined { id: entry.id },
+            Err(e) => ActionResult::ActionFailed(describe_error(&e)),
+        },
+        DetectionAction::Delete => delete(&ioc.file),
+    }
+}
+
+/// Deletes the file, or where it is in use, schedules it to be deleted when Windows next starts
+fn delete(path: &Path) -> ActionResult {
+    let e = match fs::remove_file(path) {
+        Ok(_) => return ActionResult::Deleted,
+        Err(e) => e,
+    };
+
+    if e.raw_os_error() != Some(ERROR_SHARING_VIOLATION.0 as i32) {
+        return ActionResult::ActionFailed(describe_error(&e));
+    }
+
+    let mut wide_path: Vec<u16> = path.as_os_str().encode_wide().collect();
+    wide_path.push(0);
+
+    match unsafe {
+        MoveFileExW(
+            PCWSTR::from_raw(wide_path.as_ptr()),
+            PCWSTR::null(),
+            MOVEFILE_DELAY_UNTIL_REBOOT,
+        )
+    } {
+        Ok(_) => ActionResult::DeletePending,
+        Err(reboot_err) => ActionResult::ActionFailed(format!(
+            "{}, and it could not be scheduled for deletion at restart. {reboot_err}",
+            describe_error(&e)
+        )),
+    }
+}
+
+/// Describes why an action failed in terms the user can act on
+pub(super) fn describe_error(e: &io::Error) -> String {
+    if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION.0 as i32) {
+        return "file in use".to_string();
+    }
+
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => "access denied".to_string(),
+        io::ErrorKind::NotFound => "file no longer exists".to_string(),
+        _ => e.to_string(),
+    }
+}
@@ -0,0 +1,176 @@
+//! Reading the tasks registered with the Task Scheduler, resolving the files each runs, and flagging those which look
+//! like persistence rather than maintenance.
+//!
+//! Scheduled tasks are a favourite of malware as a task can run as SYSTEM, at logon or every few minutes, without
+//! leaving a binary in any of the usual places. A task is flagged where it runs an encoded PowerShell command, runs
+//! from a temporary folder, fetches a payload from a URL, or is hidden. A task whose security descriptor has been
+//! removed from the task cache is flagged too: its definition is left on disk, but the Task Scheduler and `schtasks`
+//! no longer list it, which is how the Tarrask malware hid its tasks.
+
+use std::path::PathBuf;
+
+use shared_std::file_scanner::{ScheduledTask, TaskAction, TaskIndicator};
+use windows_registry::LOCAL_MACHINE;
+
+use super::{
+    autoruns::{expand_env_vars, resolve_command, scheduled_task_definitions, xml_elements},
+    registry::referenced_files,
+};
+
+/// The key of the task cache holding the security descriptor of each task, by the task's path
+const TASK_TREE_KEY: &str = "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Schedule\\TaskCache\\Tree";
+
+/// The PowerShell parameter which takes a base64 encoded command, which may be given as any prefix of it
+const ENCODED_COMMAND_PARAM: &str = "encodedcommand";
+
+/// Reads every task registered with the Task Scheduler, with the files each of its actions runs and anything about
+/// it which looks suspicious
+pub fn enumerate_scheduled_tasks() -> Vec<ScheduledTask> {
+    scheduled_task_definitions()
+        .into_iter()
+        .map(|(name, xml)| read_task(name, &xml))
+        .collect()
+}
+
+fn read_task(name: String, xml: &str) -> ScheduledTask {
+    let actions = exec_actions(xml);
+    let settings = element(xml, "Settings").unwrap_or_default();
+
+    let mut indicators = Vec::new();
+    for action in &actions {
+        for indicator in action_indicators(action) {
+            if !indicators.contains(&indicator) {
+                indicators.push(indicator);
+            }
+        }
+    }
+    if xml_elements(settings, "Hidden").first().is_some_and(|hidden| hidden == "true") {
+        indicators.push(TaskIndicator::Hidden);
+    }
+    if !has_security_descriptor(&name) {
+        indicators.push(TaskIndicator::NoSecurityDescriptor);
+    }
+
+    // the principal gives the account as a user or, for a task run by every member of a group, the group
+    let principals = element(xml, "Principals").unwrap_or_default();
+    let run_as = xml_elements(principals, "UserId")
+        .into_iter()
+        .chain(xml_elements(principals, "GroupId"))
+        .next();
+
+    ScheduledTask {
+        name,
+        triggers: trigger_kinds(xml),
+        run_as,
+        // a task is enabled unless its settings say otherwise
+        enabled: xml_elements(settings, "Enabled").first().is_none_or(|enabled| enabled != "false"),
+        actions,
+        indicators,
+    }
+}
+
+/// Reads the actions of the task which start a program, resolving the files each runs
+fn exec_actions(xml: &str) -> Vec<TaskAction> {
+    let actions = element(xml, "Actions").unwrap_or_default();
+
+    actions
+        .split("<Exec>")
+        .skip(1)
+        .filter_map(|s| s.split("</Exec>").next())
+        .filter_map(|exec| {
+            let command = xml_elements(exec, "Command").into_iter().next()?;
+            let arguments = xml_elements(exec, "Arguments").into_iter().next().unwrap_or_default();
+
+            let mut files: Vec<PathBuf> = resolve_command(&command).into_iter().collect();
+            for file in referenced_files(&arguments) {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+
+            Some(TaskAction {
+                command,
+                arguments,
+                files,
+            })
+        })
+        .collect()
+}
+
+/// The kind of each trigger of the task, being the name of its element without the `Trigger` suffix
+fn trigger_kinds(xml: &str) -> Vec<String> {
+    let Some(triggers) = element(xml, "Triggers") else {
+        return Vec::new();
+    };
+
+    // closing tags start with a slash, so give an empty name
+    triggers
+        .split('<')
+        .skip(1)
+        .filter_map(|s| s.split(['>', '/', ' ', '\t', '\r', '\n']).next())
+        .filter_map(|tag| tag.strip_suffix("Trigger"))
+        .filter(|kind| !kind.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn action_indicators(action: &TaskAction) -> Vec<TaskIndicator> {
+    let command_line = format!("{} {}", action.command, action.arguments);
+    let expanded = expand_env_vars(&command_line).to_lowercase();
+
+    let mut indicators = Vec::new();
+    if runs_encoded_powershell(&expanded) {
+        indicators.push(TaskIndicator::EncodedPowerShell);
+    }
+    if expanded.contains("\\temp\\") || expanded.contains("\\tmp\\") {
+        indicators.push(TaskIndicator::RunsFromTemp);
+    }
+    if expanded.contains("http://") || expanded.contains("https://") {
+        indicators.push(TaskIndicator::RemotePayload);
+    }
+
+    indicators
+}
+
+/// Whether the lowercase command line runs PowerShell with an encoded command, including where PowerShell is started
+/// through another program such as cmd
+pub(super) fn runs_encoded_powershell(command_line: &str) -> bool {
+    if !command_line.contains("powershell") && !command_line.contains("pwsh") {
+        return false;
+    }
+
+    command_line
+        .split_whitespace()
+        .filter_map(|arg| arg.trim_matches('"').strip_prefix(['-', '/']))
+        .any(|param| param.starts_with('e') && ENCODED_COMMAND_PARAM.starts_with(param))
+}
+
+/// Whether the task cache holds a security descriptor for the task. A task missing from the cache altogether, or
+/// whose entry cannot be read, is given the benefit of the doubt, as only a descriptor removed from an entry which is
+/// otherwise intact is a sign of tampering.
+fn has_security_descriptor(name: &str) -> bool {
+    let Ok(key) = LOCAL_MACHINE.open(format!("{TASK_TREE_KEY}{name}")) else {
+        return true;
+    };
+
+    match key.values() {
+        Ok(mut values) => values.any(|(value, _)| value.eq_ignore_ascii_case("SD")),
+        Err(_) => true,
+    }
+}
+
+/// The raw contents of the first element with the tag, which unlike [`xml_elements`] may carry attributes, such as the
+/// `Context` of the actions
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+
+    let start = xml
+        .match_indices(&open)
+        .map(|(i, _)| i + open.len())
+        .find(|&i| xml[i..].starts_with(['>', ' ', '\t', '\r', '\n']))?;
+    let body = &xml[start..];
+    let body = &body[body.find('>')? + 1..];
+
+    body.find(&close).map(|end| &body[..end])
+}
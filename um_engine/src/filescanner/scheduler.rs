@@ -0,0 +1,109 @@
+//! Scheduling the on-demand requests the engine serves alongside a scan, such as hashing a file or scanning the
+//! autoruns, so that a large scan cannot leave them queued behind it indefinitely.
+//!
+//! Each request takes a slot for as long as it runs, and a running scan takes one more. A request is only served
+//! where a slot is free, otherwise it is refused straight away as busy with how long to wait before retrying, so the
+//! caller is told the engine is busy rather than appearing to hang. The last slots are reserved for critical requests,
+//! which are always served, even over the limit, so that they never wait on the rest.
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use shared_std::{
+    file_scanner::{EngineBusy, STATUS_ENGINE_BUSY},
+    settings::OnDemandSettings,
+};
+
+/// How urgent an on-demand request is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestPriority {
+    /// Requests which must not wait on any other work, such as those checking protection is working. These may use
+    /// the reserved slots, and are never refused.
+    Critical,
+    /// Requests which can be retried later, refused where only the reserved slots are free
+    Normal,
+}
+
+pub struct OnDemandScheduler {
+    // the on-demand requests being served, not counting a running scan
+    in_flight: Mutex<u32>,
+    max_concurrent: AtomicU32,
+    reserved_critical: AtomicU32,
+    retry_after_secs: AtomicU64,
+}
+
+/// A slot held by an on-demand request, which is freed when dropped
+pub struct OnDemandPermit {
+    scheduler: Arc<OnDemandScheduler>,
+}
+
+impl Drop for OnDemandPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.scheduler.in_flight.lock().unwrap();
+        *in_flight = in_flight.saturating_sub(1);
+    }
+}
+
+impl OnDemandScheduler {
+    pub fn new() -> Self {
+        let defaults = OnDemandSettings::default();
+
+        OnDemandScheduler {
+            in_flight: Mutex::new(0),
+            max_concurrent: AtomicU32::new(defaults.max_concurrent),
+            reserved_critical: AtomicU32::new(defaults.reserved_critical),
+            retry_after_secs: AtomicU64::new(defaults.retry_after_secs),
+        }
+    }
+
+    /// Applies the limits from the settings. Requests already being served keep their slots, so a lower limit is
+    /// reached as they finish.
+    pub fn set_limits(&self, settings: &OnDemandSettings) {
+        self.max_concurrent.store(settings.max_concurrent, Ordering::Relaxed);
+        self.reserved_critical.store(settings.reserved_critical, Ordering::Relaxed);
+        self.retry_after_secs.store(settings.retry_after_secs, Ordering::Relaxed);
+    }
+
+    /// Takes a slot for a request of the given priority.
+    ///
+    /// # Args
+    ///
+    /// - `scan_running`: Whether a scan is running, which takes a slot of its own
+    ///
+    /// # Returns
+    ///
+    /// The slot, held until the permit is dropped, or the busy response to send where a normal request would need one
+    /// of the reserved slots.
+    pub fn try_acquire(
+        self: &Arc<Self>,
+        priority: RequestPriority,
+        scan_running: bool,
+    ) -> Result<OnDemandPermit, EngineBusy> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        if priority == RequestPriority::Normal {
+            let max_concurrent = self.max_concurrent.load(Ordering::Relaxed);
+            let available = max_concurrent.saturating_sub(self.reserved_critical.load(Ordering::Relaxed));
+            let used = *in_flight + u32::from(scan_running);
+
+            if used >= available {
+                let retry_after_secs = self.retry_after_secs.load(Ordering::Relaxed);
+                return Err(EngineBusy {
+                    status: STATUS_ENGINE_BUSY.to_string(),
+                    message: format!(
+                        "The engine is busy{}, try again in {retry_after_secs} seconds.",
+                        if scan_running { " with a scan" } else { "" }
+                    ),
+                    retry_after_secs,
+                });
+            }
+        }
+
+        *in_flight += 1;
+        Ok(OnDemandPermit {
+            scheduler: Arc::clone(self),
+        })
+    }
+}
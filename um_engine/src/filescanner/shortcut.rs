@@ -0,0 +1,297 @@
+//! Parsing shortcuts (.lnk), so a scan sees what a shortcut runs rather than only its bytes.
+//!
+//! A shortcut holds no code of its own, so its bytes rarely match a signature, yet opening it runs whatever command
+//! line it was made with. Malicious shortcuts, commonly sent in archives or dropped in the Startup folder, point at a
+//! payload or at a binary which ships with Windows, such as mshta or PowerShell, with the payload hidden in its
+//! arguments. The target is read from the LinkInfo of the shell link, falling back on the environment variable block
+//! and then the relative path. Shortcuts which only identify their target by a shell item ID list are checked on
+//! their arguments alone.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use shared_std::file_scanner::ShortcutIndicator;
+
+use super::{autoruns::expand_env_vars, filetype::SHORTCUT_MAGIC, scheduled_tasks::runs_encoded_powershell};
+
+/// The size of the header of a shell link, which is also the first field of the header
+const HEADER_SIZE: usize = 0x4C;
+
+/// Shortcuts are a few KB; arguments are limited to 64K characters, so anything much larger is not a shortcut
+const MAX_SHORTCUT_LEN: u64 = 1024 * 1024;
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 1 << 0;
+const HAS_LINK_INFO: u32 = 1 << 1;
+const HAS_NAME: u32 = 1 << 2;
+const HAS_RELATIVE_PATH: u32 = 1 << 3;
+const HAS_WORKING_DIR: u32 = 1 << 4;
+const HAS_ARGUMENTS: u32 = 1 << 5;
+const HAS_ICON_LOCATION: u32 = 1 << 6;
+const IS_UNICODE: u32 = 1 << 7;
+
+/// The LinkInfo flag for a target on a local volume, rather than a network share
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 1 << 0;
+
+/// The extra data block giving the target as a path with environment variables, such as `%windir%\System32\cmd.exe`
+const ENVIRONMENT_VARIABLE_BLOCK: u32 = 0xA000_0001;
+/// The size of the environment variable block: its size and signature, then the target as ANSI and as UTF-16
+const ENVIRONMENT_VARIABLE_BLOCK_SIZE: usize = 0x314;
+const MAX_PATH: usize = 260;
+
+/// Binaries which ship with Windows and run or fetch code given on their command line, by file name without extension
+const LIVING_OFF_THE_LAND: [&str; 18] = [
+    "bitsadmin",
+    "certutil",
+    "cmd",
+    "cmstp",
+    "cscript",
+    "forfiles",
+    "installutil",
+    "msbuild",
+    "msiexec",
+    "mshta",
+    "powershell",
+    "pwsh",
+    "regasm",
+    "regsvcs",
+    "regsvr32",
+    "rundll32",
+    "wmic",
+    "wscript",
+];
+
+/// The number of whitespace characters in a row in the arguments which is taken as padding
+const PADDING_RUN: usize = 64;
+
+/// What a shortcut runs
+pub struct Shortcut {
+    /// The file the shortcut points to, where it could be resolved from the shortcut
+    pub target: Option<PathBuf>,
+    pub arguments: String,
+}
+
+/// Reads the shortcut from the open file, which is at the path given.
+///
+/// # Returns
+///
+/// What the shortcut runs, None where the file is not a shortcut or is too malformed to read, or an error if the file
+/// could not be read.
+pub fn read_shortcut(mut file: &File, path: &Path) -> io::Result<Option<Shortcut>> {
+    // most files scanned are not shortcuts, so only the header is read until the file is known to be one
+    let mut data = vec![0u8; HEADER_SIZE];
+    file.seek(SeekFrom::Start(0))?;
+    let len = read_fully(&mut file, &mut data)?;
+    if len < HEADER_SIZE || !data.starts_with(&SHORTCUT_MAGIC) || file.metadata()?.len() > MAX_SHORTCUT_LEN {
+        return Ok(None);
+    }
+
+    file.take(MAX_SHORTCUT_LEN).read_to_end(&mut data)?;
+
+    Ok(parse_shortcut(&data, path))
+}
+
+/// Gets the file the shortcut at the path points to, where it can be resolved
+pub fn shortcut_target(path: &Path) -> Option<PathBuf> {
+    let file = File::open(path).ok()?;
+    read_shortcut(&file, path).ok()??.target
+}
+
+/// Why the shortcut looks suspicious, empty where nothing about it does
+pub fn shortcut_indicators(shortcut: &Shortcut) -> Vec<ShortcutIndicator> {
+    let target = shortcut.target.as_ref().map(|t| t.to_string_lossy().to_string()).unwrap_or_default();
+    let command_line = expand_env_vars(&format!("{target} {}", shortcut.arguments)).to_lowercase();
+
+    let mut indicators = Vec::new();
+    if runs_encoded_powershell(&command_line) {
+        indicators.push(ShortcutIndicator::EncodedPowerShell);
+    }
+
+    // a shortcut to one of the binaries with no arguments, such as the Command Prompt on the Start menu, is not flagged
+    let target_binary = shortcut
+        .target
+        .as_ref()
+        .and_then(|t| t.file_stem())
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .filter(|stem| !shortcut.arguments.trim().is_empty() && LIVING_OFF_THE_LAND.contains(&stem.as_str()));
+    let named_binary = || {
+        shortcut
+            .arguments
+            .to_lowercase()
+            .split(|c: char| c.is_whitespace() || "\"'\\/,;&|()".contains(c))
+            .map(|token| token.strip_suffix(".exe").unwrap_or(token).to_string())
+            .find(|token| LIVING_OFF_THE_LAND.contains(&token.as_str()))
+    };
+    if let Some(binary) = target_binary.or_else(named_binary) {
+        indicators.push(ShortcutIndicator::LivingOffTheLand { binary });
+    }
+
+    if command_line.contains("http://") || command_line.contains("https://") {
+        indicators.push(ShortcutIndicator::RemotePayload);
+    }
+
+    let longest_padding = shortcut
+        .arguments
+        .split(|c: char| !c.is_whitespace())
+        .map(|run| run.chars().count())
+        .max()
+        .unwrap_or(0);
+    if longest_padding >= PADDING_RUN {
+        indicators.push(ShortcutIndicator::PaddedArguments);
+    }
+
+    indicators
+}
+
+fn read_fully(file: &mut &File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    Ok(len)
+}
+
+/// Parses the shell link. Each structure is bounds checked, so a truncated or malformed shortcut gives None, or the
+/// parts of it which could be read, rather than an error.
+fn parse_shortcut(data: &[u8], path: &Path) -> Option<Shortcut> {
+    if u32_at(data, 0)? as usize != HEADER_SIZE {
+        return None;
+    }
+
+    let link_flags = u32_at(data, 0x14)?;
+    let unicode = link_flags & IS_UNICODE != 0;
+    let mut offset = HEADER_SIZE;
+
+    if link_flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        offset = offset.checked_add(2 + u16_at(data, offset)? as usize)?;
+    }
+
+    let mut target = None;
+    if link_flags & HAS_LINK_INFO != 0 {
+        let size = u32_at(data, offset)? as usize;
+        let link_info = data.get(offset..offset.checked_add(size)?)?;
+        target = link_info_path(link_info);
+        offset += size;
+    }
+
+    // the strings follow in this order, each only where its flag is set
+    let mut relative_path = None;
+    let mut arguments = String::new();
+    for flag in [HAS_NAME, HAS_RELATIVE_PATH, HAS_WORKING_DIR, HAS_ARGUMENTS, HAS_ICON_LOCATION] {
+        if link_flags & flag == 0 {
+            continue;
+        }
+
+        let chars = u16_at(data, offset)? as usize;
+        let len = if unicode { chars * 2 } else { chars };
+        let bytes = data.get(offset + 2..offset + 2 + len)?;
+        offset += 2 + len;
+
+        let s = decode(bytes, unicode);
+        match flag {
+            HAS_RELATIVE_PATH => relative_path = Some(s),
+            HAS_ARGUMENTS => arguments = s,
+            _ => (),
+        }
+    }
+
+    // the target is also given in the extra data as a path with environment variables, where it was made with one
+    if target.is_none() {
+        target = environment_target(data.get(offset..).unwrap_or_default());
+    }
+
+    // a relative path is relative to the folder of the shortcut
+    if target.is_none()
+        && let Some(relative_path) = relative_path.filter(|p| !p.is_empty())
+    {
+        target = path.parent().map(|dir| dir.join(relative_path));
+    }
+
+    Some(Shortcut { target, arguments })
+}
+
+/// Reads the local path of the target from the LinkInfo, being the local base path joined with the common path suffix
+fn link_info_path(link_info: &[u8]) -> Option<PathBuf> {
+    let header_size = u32_at(link_info, 4)? as usize;
+    if u32_at(link_info, 8)? & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return None;
+    }
+
+    // a header of at least 0x24 bytes carries the offsets of the paths as UTF-16 too, which are preferred where set
+    let unicode_offsets = (header_size >= 0x24)
+        .then(|| Some((u32_at(link_info, 28)? as usize, u32_at(link_info, 32)? as usize)))
+        .flatten()
+        .filter(|&(base, _)| base != 0);
+
+    let path = match unicode_offsets {
+        Some((base, suffix)) => {
+            let suffix = if suffix == 0 { String::new() } else { nul_terminated(link_info, suffix, true)? };
+            nul_terminated(link_info, base, true)? + &suffix
+        }
+        None => {
+            let base = nul_terminated(link_info, u32_at(link_info, 16)? as usize, false)?;
+            let suffix = nul_terminated(link_info, u32_at(link_info, 24)? as usize, false).unwrap_or_default();
+            base + &suffix
+        }
+    };
+
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Reads the target from the environment variable block of the extra data, expanding its environment variables
+fn environment_target(mut extra_data: &[u8]) -> Option<PathBuf> {
+    // the extra data is a list of blocks each starting with its size, ending with a block smaller than 4 bytes
+    loop {
+        let size = u32_at(extra_data, 0)? as usize;
+        if size < 4 {
+            return None;
+        }
+
+        if u32_at(extra_data, 4)? == ENVIRONMENT_VARIABLE_BLOCK && size >= ENVIRONMENT_VARIABLE_BLOCK_SIZE {
+            let block = extra_data.get(..ENVIRONMENT_VARIABLE_BLOCK_SIZE)?;
+            let target = nul_terminated(block, 8 + MAX_PATH, true)
+                .filter(|t| !t.is_empty())
+                .or_else(|| nul_terminated(block, 8, false))?;
+
+            return (!target.is_empty()).then(|| PathBuf::from(expand_env_vars(&target)));
+        }
+
+        extra_data = extra_data.get(size..)?;
+    }
+}
+
+/// Reads the string at the offset up to its terminating nul, or up to the end of the data where it has none
+fn nul_terminated(data: &[u8], offset: usize, unicode: bool) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let len = match unicode {
+        true => bytes.chunks_exact(2).position(|c| c == [0, 0]).map_or(bytes.len() & !1, |i| i * 2),
+        false => bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len()),
+    };
+
+    Some(decode(&bytes[..len], unicode))
+}
+
+fn decode(bytes: &[u8], unicode: bool) -> String {
+    match unicode {
+        true => {
+            let chars: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&chars)
+        }
+        false => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset.checked_add(2)?).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
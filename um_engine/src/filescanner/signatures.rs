@@ -0,0 +1,1317 @@
+//! Loading the signature sets the scanner matches against.
+//!
+//! Signatures come from the IOC lists in the Sanctum folder, and from any `*.sig` files in the signatures folder,
+//! which lets signatures be distributed and updated split across files by category. Each line of a `.sig` file is a
+//! signature, with blank lines and lines starting with `#` ignored:
+//!
+//! ```text
+//! # whole file MD5, optionally prefixed with md5:
+//! 44D88612FEA8A8F36DE82E1278ABB02F
+//! md5:44D88612FEA8A8F36DE82E1278ABB02F
+//! # whole file SHA256
+//! sha256:275A021BBFB6489E54D471899F7DB9D1663FC695EC2FE2A2C4538AABF651FD0F
+//! # MD5 of a single PE section
+//! section:0123456789ABCDEF0123456789ABCDEF
+//! # import hash of a PE, described in super::imports
+//! imphash:F34D5F2D4577ED6D9CEEC516C1F5A744
+//! # ssdeep fuzzy hash
+//! fuzzy:3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C
+//! # text found in the contents of a registry value, regardless of case, matched by a registry scan
+//! registry:powershell -nop -w hidden -enc
+//! ```
+//!
+//! A whole file MD5 or SHA256 signature may be followed by conditions the file must also meet, such as its size, type
+//! or path, described in [`super::conditions`]. Conditions were added in version 2 of the format, so a file using them
+//! must say so with a `# sig_format: 2` line, which an engine too old to understand conditions reports as a version it
+//! cannot load rather than misreading the file. A signature without conditions matches on its hash alone, as before.
+//!
+//! A file containing a line which is not a valid signature is skipped in full, and the error reported against the
+//! file in the load stats, so one bad file cannot stop the rest from loading nor load half of its signatures. A file
+//! can be checked before it is put in the folder, which reports every problem in it at once rather than the first.
+//!
+//! Signatures in third party formats are imported by converting them into a `.sig` file in the signatures folder, so
+//! they are loaded the same way as any other signature file from then on.
+//!
+//! The signatures matched against can be exported to a single file to move them to another machine, where the export
+//! is imported like any other format. An export is itself a `.sig` file, with a header of comments recording where it
+//! came from and a checksum of its signatures, which is verified on import:
+//!
+//! ```text
+//! # Sanctum signature export
+//! # export_format: 2
+//! # sig_format: 2
+//! # signature_version: 1024-0123456789AB
+//! # databases: sanctum, feed.sig
+//! # checksum: sha256:275A021BBFB6489E54D471899F7DB9D1663FC695EC2FE2A2C4538AABF651FD0F
+//! md5:44D88612FEA8A8F36DE82E1278ABB02F
+//! md5:0123456789ABCDEF0123456789ABCDEF type=executable path=**/temp/**
+//! ```
+//!
+//! An export containing no signatures with conditions is written as version 1 of both formats, so it can still be
+//! imported by an engine which predates conditions.
+//!
+//! Each source of signatures is loaded as a database of its own: the IOC lists make up the built in database, and
+//! each `.sig` file is a database named after the file. A detection is attributed to the database which matched it,
+//! and a database can be disabled without unloading it, so it can be enabled again without waiting on a reload.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use shared_no_std::constants::{
+    IOC_DIRECTORY_LOCATION, IOC_FUZZY_LIST_LOCATION, IOC_LIST_LOCATION, IOC_SECTION_LIST_LOCATION, IOC_URL,
+};
+use shared_std::version::{
+    SignatureCounts, SignatureDatabaseInfo, SignatureExportReport, SignatureFileStats, SignatureImportFormat,
+    SignatureImportReport, SignatureIssue, SignatureIssueKind, SignatureIssueSeverity, SignatureValidationReport,
+};
+
+use crate::utils::log::{Log, LogLevel};
+
+use super::{
+    bloom::IocSet,
+    conditions::SignatureConditions,
+    filescanner::to_hex,
+    fuzzy::{FuzzyHash, FuzzySignatureSet},
+};
+
+/// MD5 of the EICAR standard antivirus test file, which is detected by the built in database regardless of the IOC
+/// list so that detection can be tested without real malware
+pub const EICAR_MD5: &str = "44D88612FEA8A8F36DE82E1278ABB02F";
+
+/// The name of the database made up of the IOC lists in the Sanctum folder
+pub const BUILTIN_DATABASE: &str = "sanctum";
+
+/// The first line of a signature export, identifying the file as one
+const EXPORT_HEADER: &str = "# Sanctum signature export";
+
+/// The version of the export format, raised should the format change in a way older engines cannot import
+const EXPORT_FORMAT_VERSION: u32 = 2;
+
+/// The newest version of the `.sig` format this engine loads; version 2 added conditions on signatures
+const SIG_FORMAT_VERSION: u32 = 2;
+
+/// The version of both formats which predates conditions, written where an export has no conditions to carry
+const UNCONDITIONAL_FORMAT_VERSION: u32 = 1;
+
+const MD5_HEX_LEN: usize = 32;
+const SHA1_HEX_LEN: usize = 40;
+const SHA256_HEX_LEN: usize = 64;
+
+/// The signatures the scanner matches against. A loaded set is never modified, a reload builds a new set which
+/// replaces it whole, so a scan always matches against one consistent set.
+pub struct SignatureSet {
+    // databases: the built in database first, then those from the signatures folder in the order of their file names.
+    // Databases are matched in this order, so a signature in more than one is always attributed to the same database.
+    pub databases: Vec<SignatureDatabase>,
+    // identifies the loaded IOC list, reported to the GUI for support and triage
+    pub version: String,
+    // the number of whole file MD5 signatures loaded across the databases, excluding built in signatures
+    pub count: usize,
+    // the outcome of loading each file from the signatures folder
+    pub files: Vec<SignatureFileStats>,
+}
+
+/// A named source of signatures
+pub struct SignatureDatabase {
+    pub name: String,
+    // the signature file the database was loaded from, None for the built in database
+    pub path: Option<PathBuf>,
+    // the number of whole file MD5 signatures in the database, excluding built in signatures
+    pub count: usize,
+    // iocs:
+    // Using a BTreeSet for the IOCs as it has the best time complexity for searching - Rust's implementation in the stdlib
+    // I don't think is the best optimised BTree out there, but it will do the job for now. Not adding any IOC metadata to this
+    // list of hashes (aka turning this into a BTreeMap) as it's a waste of memory and that metadata can be looked up with automations
+    // either locally on disk or in the cloud. A bloom filter in front of the set means most clean files never reach the BTree.
+    pub iocs: IocSet,
+    // sha256_iocs: whole file SHA256 hashes, which are only computed during a scan when there is at least one
+    pub sha256_iocs: IocSet,
+    // section_iocs: hashes of individual PE sections known to be malicious, matched when the whole file hash does not
+    // match so that appending / padding a known bad file does not evade detection.
+    pub section_iocs: IocSet,
+    // import_iocs: import hashes of known malware, catching variants of a family which were recompiled but kept its
+    // imports
+    pub import_iocs: IocSet,
+    // fuzzy_iocs: fuzzy hashes of known malware, for detecting near duplicates when enabled in the settings
+    pub fuzzy_iocs: FuzzySignatureSet,
+    // registry_iocs: text known to be malicious in the contents of a registry value, in lowercase, only matched by a
+    // registry scan. There are few, and each is a substring rather than a hash, so they are kept in a list.
+    pub registry_iocs: Vec<String>,
+    // conditions: the conditions on each whole file MD5 or SHA256 signature which has them, any one of which the file
+    // must meet for the hash to match. A hash also listed without conditions has none here, as it matches regardless.
+    conditions: BTreeMap<String, Vec<SignatureConditions>>,
+}
+
+impl SignatureDatabase {
+    fn new(
+        name: String,
+        path: Option<PathBuf>,
+        mut iocs: BTreeSet<String>,
+        mut sha256_iocs: BTreeSet<String>,
+        section_iocs: BTreeSet<String>,
+        import_iocs: BTreeSet<String>,
+        fuzzy_iocs: FuzzySignatureSet,
+        registry_iocs: Vec<String>,
+        conditional: Vec<(String, SignatureConditions)>,
+    ) -> Self {
+        // a hash with conditions is looked up in the same sets as the rest, and its conditions checked once it matches
+        let mut conditions: BTreeMap<String, Vec<SignatureConditions>> = BTreeMap::new();
+
+        for (hash, hash_conditions) in conditional {
+            let set = match hash.len() {
+                MD5_HEX_LEN => &mut iocs,
+                _ => &mut sha256_iocs,
+            };
+
+            // a hash already in the set without conditions matches regardless, so its conditions are dropped
+            if set.insert(hash.clone()) || conditions.contains_key(&hash) {
+                conditions.entry(hash).or_default().push(hash_conditions);
+            }
+        }
+
+        SignatureDatabase {
+            name,
+            path,
+            count: iocs.len(),
+            iocs: IocSet::new(iocs),
+            sha256_iocs: IocSet::new(sha256_iocs),
+            section_iocs: IocSet::new(section_iocs),
+            import_iocs: IocSet::new(import_iocs),
+            fuzzy_iocs,
+            registry_iocs,
+            conditions,
+        }
+    }
+
+    /// The conditions on a whole file hash in the database, one set of which the file must meet for the hash to
+    /// match, or None where the hash matches regardless
+    pub fn conditions(&self, hash: &str) -> Option<&[SignatureConditions]> {
+        self.conditions.get(hash).map(Vec::as_slice)
+    }
+
+    /// The memory used by the bloom filters in front of the database's sets, in bytes
+    fn filter_memory_bytes(&self) -> usize {
+        self.iocs.filter_memory_bytes()
+            + self.sha256_iocs.filter_memory_bytes()
+            + self.section_iocs.filter_memory_bytes()
+            + self.import_iocs.filter_memory_bytes()
+    }
+}
+
+/// The signatures read from a single `.sig` file
+#[derive(Default)]
+struct SignatureFile {
+    hashes: Vec<String>,
+    sha256: Vec<String>,
+    sections: Vec<String>,
+    imports: Vec<String>,
+    fuzzy: Vec<FuzzyHash>,
+    registry: Vec<String>,
+    // whole file MD5 and SHA256 signatures with conditions, which are not in hashes or sha256
+    conditional: Vec<(String, SignatureConditions)>,
+}
+
+impl SignatureFile {
+    /// The number of whole file MD5 and SHA256 signatures, with or without conditions
+    fn hash_signatures(&self) -> usize {
+        self.hashes.len() + self.sha256.len() + self.conditional.len()
+    }
+}
+
+impl SignatureSet {
+    /// Loads the signatures from the IOC lists and the signatures folder. The main IOC list is downloaded if it does
+    /// not yet exist; the other lists and the signatures folder are optional.
+    pub async fn load() -> Result<Self, io::Error> {
+        let log = Log::new();
+
+        //
+        // ingest latest IOC hash list
+        //
+        let mut bts: BTreeSet<String> = BTreeSet::new();
+        let mut ioc_location: String = std::env::var("APPDATA")
+            .expect("[-] Could not find App Data folder in environment variables.]");
+        ioc_location.push_str(format!("\\{}", IOC_LIST_LOCATION).as_str());
+
+        let file = match File::open(&ioc_location) {
+            Ok(f) => f,
+            Err(e) => {
+                log.log(
+                    LogLevel::Warning,
+                    format!("[-] IOC list not found, downloading to {}.", ioc_location).as_str(),
+                );
+                if e.kind() == io::ErrorKind::NotFound {
+                    let file_data = reqwest::get(IOC_URL).await.unwrap().text().await.unwrap();
+                    let mut f = File::create_new(&ioc_location).expect(
+                        format!(
+                            "[-] Could not create new file for IOCs. Loc: {}",
+                            ioc_location
+                        )
+                        .as_str(),
+                    );
+                    f.write_all(file_data.as_bytes())
+                        .expect("[-] Could not write data for IOCs");
+
+                    // the file was opened for writing, so open it again to read it back from the start
+                    File::open(&ioc_location)?
+                } else {
+                    panic!("[-] Unknown error occurred when trying to ingest IOC files. {e}");
+                }
+            }
+        };
+        let lines = BufReader::new(file).lines();
+
+        for line in lines.map_while(Result::ok) {
+            bts.insert(line);
+        }
+
+        //
+        // ingest the PE section hash list; unlike the IOC list this is optional and is not downloaded
+        //
+        let mut section_iocs: BTreeSet<String> = BTreeSet::new();
+        let mut section_ioc_location: String = std::env::var("APPDATA")
+            .expect("[-] Could not find App Data folder in environment variables.]");
+        section_ioc_location.push_str(format!("\\{}", IOC_SECTION_LIST_LOCATION).as_str());
+
+        match File::open(&section_ioc_location) {
+            Ok(f) => {
+                for line in BufReader::new(f).lines().map_while(Result::ok) {
+                    let line = line.trim();
+                    if !line.is_empty() {
+                        section_iocs.insert(line.to_uppercase());
+                    }
+                }
+            }
+            Err(e) => log.log(
+                LogLevel::Info,
+                &format!(
+                    "No PE section IOC list loaded from {}, section hash matching disabled. {e}",
+                    section_ioc_location
+                ),
+            ),
+        }
+
+        //
+        // ingest the fuzzy hash list; again this is optional, and only used when fuzzy hashing is enabled
+        //
+        let mut fuzzy_iocs = FuzzySignatureSet::default();
+        let mut fuzzy_ioc_location: String = std::env::var("APPDATA")
+            .expect("[-] Could not find App Data folder in environment variables.]");
+        fuzzy_ioc_location.push_str(format!("\\{}", IOC_FUZZY_LIST_LOCATION).as_str());
+
+        match File::open(&fuzzy_ioc_location) {
+            Ok(f) => {
+                for line in BufReader::new(f).lines().map_while(Result::ok) {
+                    // lists produced by ssdeep have a header line, which will fail to parse and so be skipped
+                    if let Some(hash) = FuzzyHash::parse(&line) {
+                        fuzzy_iocs.insert(hash);
+                    }
+                }
+            }
+            Err(e) => log.log(
+                LogLevel::Info,
+                &format!(
+                    "No fuzzy hash IOC list loaded from {}, fuzzy hash matching disabled. {e}",
+                    fuzzy_ioc_location
+                ),
+            ),
+        }
+
+        //
+        // load each signature file from the signatures folder
+        //
+        let (loaded, files) = load_directory(&signature_directory());
+
+        // the version and count describe every MD5 signature loaded, whichever database it is in
+        let (version, count) = {
+            let all_hashes: BTreeSet<&str> = bts
+                .iter()
+                .chain(loaded.iter().flat_map(|(_, file)| {
+                    let conditional = file.conditional.iter().map(|(hash, _)| hash);
+                    file.hashes.iter().chain(conditional.filter(|hash| hash.len() == MD5_HEX_LEN))
+                }))
+                .map(String::as_str)
+                .collect();
+
+            (signature_version(&all_hashes), all_hashes.len())
+        };
+
+        let listed = bts.len();
+        bts.insert(EICAR_MD5.to_string());
+        let mut builtin = SignatureDatabase::new(
+            BUILTIN_DATABASE.to_string(),
+            None,
+            bts,
+            BTreeSet::new(),
+            section_iocs,
+            BTreeSet::new(),
+            fuzzy_iocs,
+            Vec::new(),
+            Vec::new(),
+        );
+        builtin.count = listed;
+        let mut databases = vec![builtin];
+
+        for (path, file) in loaded {
+            let mut fuzzy = FuzzySignatureSet::default();
+            file.fuzzy.into_iter().for_each(|h| fuzzy.insert(h));
+
+            databases.push(SignatureDatabase::new(
+                database_name(&path),
+                Some(path),
+                file.hashes.into_iter().collect(),
+                file.sha256.into_iter().collect(),
+                file.sections.into_iter().collect(),
+                file.imports.into_iter().collect(),
+                fuzzy,
+                file.registry,
+                file.conditional,
+            ));
+        }
+
+        let set = SignatureSet {
+            databases,
+            version,
+            count,
+            files,
+        };
+
+        let counts = set.counts();
+        log.log(
+            LogLevel::Info,
+            &format!(
+                "Loaded {} signature databases with {} hash IOCs, {} PE section IOCs, {} import hash IOCs, {} fuzzy \
+                hash IOCs and {} registry IOCs, bloom filters using {} KB.",
+                set.databases.len(),
+                counts.hash_signatures,
+                counts.section_signatures,
+                counts.import_hash_signatures,
+                counts.fuzzy_signatures,
+                counts.registry_signatures,
+                set.databases.iter().map(|db| db.filter_memory_bytes()).sum::<usize>() / 1024
+            ),
+        );
+
+        Ok(set)
+    }
+
+    /// The databases which are matched against, in the order they are matched
+    ///
+    /// # Args
+    /// - `disabled`: The names of the databases which are disabled in the settings
+    pub fn enabled<'a>(&'a self, disabled: &'a [String]) -> impl Iterator<Item = &'a SignatureDatabase> {
+        self.databases.iter().filter(|db| !disabled.contains(&db.name))
+    }
+
+    /// Finds the first registry signature of the enabled databases found in any of the contents of a registry value,
+    /// which are given in lowercase
+    ///
+    /// # Returns
+    ///
+    /// The database the signature is in and the signature, or None where no registry signature matches
+    pub fn registry_match<'a>(
+        &'a self,
+        disabled: &'a [String],
+        contents: &[String],
+    ) -> Option<(&'a SignatureDatabase, &'a str)> {
+        self.enabled(disabled).find_map(|db| {
+            db.registry_iocs
+                .iter()
+                .find(|signature| contents.iter().any(|c| c.contains(signature.as_str())))
+                .map(|signature| (db, signature.as_str()))
+        })
+    }
+
+    /// Whether a database of the given name is loaded
+    pub fn has_database(&self, name: &str) -> bool {
+        self.databases.iter().any(|db| db.name == name)
+    }
+
+    /// Describes each database loaded, and whether it is matched against
+    pub fn database_info(&self, disabled: &[String]) -> Vec<SignatureDatabaseInfo> {
+        self.databases
+            .iter()
+            .map(|db| SignatureDatabaseInfo {
+                name: db.name.clone(),
+                path: db.path.clone(),
+                enabled: !disabled.contains(&db.name),
+                hash_signatures: db.count + db.sha256_iocs.len(),
+                section_signatures: db.section_iocs.len(),
+                import_hash_signatures: db.import_iocs.len(),
+                registry_signatures: db.registry_iocs.len(),
+                fuzzy_signatures: db.fuzzy_iocs.len(),
+            })
+            .collect()
+    }
+
+    /// The number of signatures loaded into each detection engine, across all databases whether or not they are
+    /// enabled
+    pub fn counts(&self) -> SignatureCounts {
+        SignatureCounts {
+            hash_signatures: self.count + self.databases.iter().map(|db| db.sha256_iocs.len()).sum::<usize>(),
+            section_signatures: self.databases.iter().map(|db| db.section_iocs.len()).sum(),
+            import_hash_signatures: self.databases.iter().map(|db| db.import_iocs.len()).sum(),
+            registry_signatures: self.databases.iter().map(|db| db.registry_iocs.len()).sum(),
+            yara_rules: 0,
+            fuzzy_signatures: self.databases.iter().map(|db| db.fuzzy_iocs.len()).sum(),
+        }
+    }
+}
+
+/// Loads each signature file in the signatures folder.
+///
+/// # Returns
+///
+/// The signatures of each valid file, in the order of their file names, and the outcome of loading each file,
+/// including those which were skipped.
+fn load_directory(dir: &Path) -> (Vec<(PathBuf, SignatureFile)>, Vec<SignatureFileStats>) {
+    // the folder is optional, so not existing is not an error
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    // load in a stable order so the load stats read the same each time
+    paths.sort();
+
+    let log = Log::new();
+    let mut loaded = Vec::new();
+    let mut stats = Vec::new();
+
+    for path in paths {
+        let extension = path.extension().map(|e| e.to_ascii_lowercase());
+
+        let result = match extension.as_ref().and_then(|e| e.to_str()) {
+            Some("sig") => parse_signature_file(&path),
+            Some("yar") => Err("YARA rules are not supported by this engine, so the file was skipped.".to_string()),
+            _ => continue,
+        };
+
+        let file_stats = match result {
+            Ok(file) => {
+                let file_stats = SignatureFileStats {
+                    path: path.clone(),
+                    hash_signatures: file.hash_signatures(),
+                    section_signatures: file.sections.len(),
+                    import_hash_signatures: file.imports.len(),
+                    registry_signatures: file.registry.len(),
+                    fuzzy_signatures: file.fuzzy.len(),
+                    error: None,
+                };
+
+                loaded.push((path, file));
+
+                file_stats
+            }
+            Err(e) => {
+                log.log(
+                    LogLevel::Warning,
+                    &format!("Skipped signature file {}. {e}", path.display()),
+                );
+
+                SignatureFileStats {
+                    path,
+                    hash_signatures: 0,
+                    section_signatures: 0,
+                    import_hash_signatures: 0,
+                    registry_signatures: 0,
+                    fuzzy_signatures: 0,
+                    error: Some(e),
+                }
+            }
+        };
+
+        stats.push(file_stats);
+    }
+
+    (loaded, stats)
+}
+
+/// The name of the database loaded from a signature file, which is the name of the file. File names are unique in the
+/// folder, and the extension keeps them from clashing with the built in database.
+fn database_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Parses a `.sig` file in full, failing on the first line which is not a valid signature
+fn parse_signature_file(path: &Path) -> Result<SignatureFile, String> {
+    let file = File::open(path).map_err(|e| format!("Could not open file. {e}"))?;
+    parse_signatures(BufReader::new(file))
+}
+
+/// Parses signatures in the format of a `.sig` file, failing on the first line which is not a valid signature, or on
+/// a `# sig_format` line naming a version newer than this engine loads
+fn parse_signatures(reader: impl BufRead) -> Result<SignatureFile, String> {
+    let mut signatures = SignatureFile::default();
+    let mut format = UNCONDITIONAL_FORMAT_VERSION;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Could not read line {}. {e}", i + 1))?;
+        let line = line.trim();
+
+        match parse_line(line, format).map_err(|e| e.describe(i + 1, line))? {
+            SignatureLine::Ignored => (),
+            SignatureLine::Format(version) => {
+                if version > SIG_FORMAT_VERSION {
+                    return Err(newer_format(version));
+                }
+                format = version;
+            }
+            SignatureLine::Signature(signature) => signatures.add(signature),
+        }
+    }
+
+    Ok(signatures)
+}
+
+/// What a single line of a `.sig` file holds
+enum SignatureLine {
+    /// A blank line or a comment
+    Ignored,
+    /// A `# sig_format` line, giving the version of the format the rest of the file is in
+    Format(u32),
+    Signature(ParsedSignature),
+}
+
+/// A single signature read from a `.sig` file
+enum ParsedSignature {
+    Md5(String),
+    Sha256(String),
+    Section(String),
+    Import(String),
+    Fuzzy(FuzzyHash),
+    Registry(String),
+    /// A whole file MD5 or SHA256 hash, with the conditions the file must also meet
+    Conditional(String, SignatureConditions),
+}
+
+impl ParsedSignature {
+    /// The signature as it is written in an export, which is the same for two lines holding the same signature
+    fn key(&self) -> String {
+        match self {
+            ParsedSignature::Md5(hash) => format!("md5:{hash}"),
+            ParsedSignature::Sha256(hash) => format!("sha256:{hash}"),
+            ParsedSignature::Section(hash) => format!("section:{hash}"),
+            ParsedSignature::Import(hash) => format!("imphash:{hash}"),
+            ParsedSignature::Fuzzy(hash) => format!("fuzzy:{hash}"),
+            ParsedSignature::Registry(text) => format!("registry:{text}"),
+            ParsedSignature::Conditional(hash, conditions) => format!("{} {conditions}", hash_key(hash)),
+        }
+    }
+}
+
+/// The key of a whole file hash without its conditions, by which a hash given both with and without conditions is found
+fn hash_key(hash: &str) -> String {
+    match hash.len() {
+        MD5_HEX_LEN => format!("md5:{hash}"),
+        _ => format!("sha256:{hash}"),
+    }
+}
+
+impl SignatureFile {
+    fn add(&mut self, signature: ParsedSignature) {
+        match signature {
+            ParsedSignature::Md5(hash) => self.hashes.push(hash),
+            ParsedSignature::Sha256(hash) => self.sha256.push(hash),
+            ParsedSignature::Section(hash) => self.sections.push(hash),
+            ParsedSignature::Import(hash) => self.imports.push(hash),
+            ParsedSignature::Fuzzy(hash) => self.fuzzy.push(hash),
+            ParsedSignature::Registry(text) => self.registry.push(text),
+            ParsedSignature::Conditional(hash, conditions) => self.conditional.push((hash, conditions)),
+        }
+    }
+}
+
+/// Why a line of a `.sig` file could not be parsed
+enum LineError {
+    /// The line is not a valid signature, such as a hash of the wrong length
+    Invalid,
+    /// The line is a `# sig_format` line which does not give a version number
+    InvalidFormat,
+    /// The line starts a YARA rule, which is written in a `.yar` file rather than a `.sig` file
+    Yara,
+    /// The line has conditions, but the file does not declare a format version which allows them
+    ConditionsNeedFormat,
+    /// The line has conditions which are not valid, with why
+    Conditions(String),
+}
+
+impl LineError {
+    fn describe(&self, line_number: usize, line: &str) -> String {
+        match self {
+            LineError::Invalid => format!("Line {line_number} is not a valid signature: {line}"),
+            LineError::InvalidFormat => format!("Line {line_number} does not give a valid format version: {line}"),
+            LineError::Yara => {
+                format!("Line {line_number} is a YARA rule, which is not supported by this engine: {line}")
+            }
+            LineError::ConditionsNeedFormat => format!(
+                "Line {line_number} has conditions, which need the file to declare # sig_format: {SIG_FORMAT_VERSION}: \
+                {line}"
+            ),
+            LineError::Conditions(e) => format!("Line {line_number} has conditions which are not valid. {e}"),
+        }
+    }
+}
+
+/// The error for a file in a version of the format newer than this engine loads
+fn newer_format(version: u32) -> String {
+    format!("The file is in version {version} of the signature format, which is newer than this engine can load.")
+}
+
+/// Parses a single trimmed line of a `.sig` file, in a file in the given version of the format
+fn parse_line(line: &str, format: u32) -> Result<SignatureLine, LineError> {
+    if let Some(comment) = line.strip_prefix('#') {
+        return match comment.trim().strip_prefix("sig_format:") {
+            Some(version) => version.trim().parse().map(SignatureLine::Format).map_err(|_| LineError::InvalidFormat),
+            None => Ok(SignatureLine::Ignored),
+        };
+    }
+    if line.is_empty() {
+        return Ok(SignatureLine::Ignored);
+    }
+
+    let signature = if let Some(fuzzy) = line.strip_prefix("fuzzy:") {
+        ParsedSignature::Fuzzy(FuzzyHash::parse(fuzzy).ok_or(LineError::Invalid)?)
+    } else if let Some(section) = line.strip_prefix("section:") {
+        ParsedSignature::Section(parse_hash(section, MD5_HEX_LEN).ok_or(LineError::Invalid)?)
+    } else if let Some(imphash) = line.strip_prefix("imphash:") {
+        ParsedSignature::Import(parse_hash(imphash, MD5_HEX_LEN).ok_or(LineError::Invalid)?)
+    } else if let Some(text) = line.strip_prefix("registry:") {
+        // the text is matched as it is, spaces and all, so only an empty signature is invalid
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(LineError::Invalid);
+        }
+        ParsedSignature::Registry(text.to_lowercase())
+    } else if line.starts_with("rule ") || line.starts_with("private rule ") || line.starts_with("global rule ") {
+        return Err(LineError::Yara);
+    } else {
+        let (hash, hex_len) = match line.strip_prefix("sha256:") {
+            Some(hash) => (hash, SHA256_HEX_LEN),
+            None => (line.strip_prefix("md5:").unwrap_or(line), MD5_HEX_LEN),
+        };
+
+        match hash.split_once(char::is_whitespace) {
+            Some((hash, conditions)) => {
+                if format < SIG_FORMAT_VERSION {
+                    return Err(LineError::ConditionsNeedFormat);
+                }
+
+                let hash = parse_hash(hash, hex_len).ok_or(LineError::Invalid)?;
+                let conditions = SignatureConditions::parse(conditions).map_err(LineError::Conditions)?;
+                ParsedSignature::Conditional(hash, conditions)
+            }
+            None if hex_len == SHA256_HEX_LEN => {
+                ParsedSignature::Sha256(parse_hash(hash, hex_len).ok_or(LineError::Invalid)?)
+            }
+            None => ParsedSignature::Md5(parse_hash(hash, hex_len).ok_or(LineError::Invalid)?),
+        }
+    };
+
+    Ok(SignatureLine::Signature(signature))
+}
+
+/// Parses a hash of the given number of hex digits, returning it in the uppercase form the scanner produces
+fn parse_hash(s: &str, hex_len: usize) -> Option<String> {
+    let s = s.trim();
+    (s.len() == hex_len && s.chars().all(|c| c.is_ascii_hexdigit())).then(|| s.to_uppercase())
+}
+
+/// The hashes read from a file being imported
+#[derive(Default)]
+struct ImportedHashes {
+    md5: BTreeSet<String>,
+    sha256: BTreeSet<String>,
+    malformed: usize,
+    unsupported: usize,
+}
+
+impl ImportedHashes {
+    /// Adds the hash by its length. SHA1 hashes are valid in the formats imported, but cannot be matched by the engine.
+    fn add(&mut self, hash: &str) {
+        let hash = hash.trim();
+        if let Some(md5) = parse_hash(hash, MD5_HEX_LEN) {
+            self.md5.insert(md5);
+        } else if let Some(sha256) = parse_hash(hash, SHA256_HEX_LEN) {
+            self.sha256.insert(sha256);
+        } else if parse_hash(hash, SHA1_HEX_LEN).is_some() {
+            self.unsupported += 1;
+        } else {
+            self.malformed += 1;
+        }
+    }
+}
+
+/// Imports the signatures from a file in a third party format, writing them to a `.sig` file in the signatures folder
+/// named after the file. Importing the same file again replaces the signatures previously imported from it. Malformed
+/// lines are skipped and counted rather than failing the import, as feeds commonly contain the odd bad line.
+///
+/// The signatures are not loaded until the signatures are next reloaded.
+///
+/// # Returns
+///
+/// The outcome of the import, or an error if the file could not be read or written, or contained no signature which
+/// could be imported.
+pub fn import_signatures(
+    format: SignatureImportFormat,
+    path: &Path,
+) -> Result<SignatureImportReport, String> {
+    if format == SignatureImportFormat::SanctumExport {
+        return import_export(path);
+    }
+
+    let file = File::open(path).map_err(|e| format!("Could not open {}. {e}", path.display()))?;
+    let mut hashes = ImportedHashes::default();
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Could not read {}. {e}", path.display()))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match format {
+            SignatureImportFormat::HashList => hashes.add(line),
+            // hash:size:name, with an optional minimum engine level; the size may be * for any size
+            SignatureImportFormat::ClamAv => {
+                let fields: Vec<&str> = line.split(':').collect();
+                let size_valid = fields.get(1).is_some_and(|size| {
+                    *size == "*" || (!size.is_empty() && size.chars().all(|c| c.is_ascii_digit()))
+                });
+
+                if fields.len() >= 3 && size_valid && !fields[2].is_empty() {
+                    hashes.add(fields[0]);
+                } else {
+                    hashes.malformed += 1;
+                }
+            }
+            SignatureImportFormat::SanctumExport => unreachable!("exports are imported whole by import_export"),
+        }
+    }
+
+    let imported = hashes.md5.len() + hashes.sha256.len();
+    if imported == 0 {
+        return Err(format!(
+            "No signatures could be imported from {}, {} lines were malformed and {} unsupported. Check the format \
+            chosen matches the file.",
+            path.display(),
+            hashes.malformed,
+            hashes.unsupported
+        ));
+    }
+
+    let destination = import_destination(path)?;
+
+    let mut contents = format!("# Imported from {} ({format:?})\n", path.display());
+    for hash in &hashes.md5 {
+        contents.push_str(&format!("md5:{hash}\n"));
+    }
+    for hash in &hashes.sha256 {
+        contents.push_str(&format!("sha256:{hash}\n"));
+    }
+
+    fs::write(&destination, contents)
+        .map_err(|e| format!("Could not write the imported signatures to {}. {e}", destination.display()))?;
+
+    Ok(SignatureImportReport {
+        imported,
+        skipped_malformed: hashes.malformed,
+        skipped_unsupported: hashes.unsupported,
+        file: destination,
+        signatures: SignatureCounts::default(),
+    })
+}
+
+/// Gets the file in the signatures folder that signatures imported from the file are written to, creating the folder
+/// if need be. The file is named after the source, so that importing an updated copy of a feed replaces the previous
+/// import.
+fn import_destination(path: &Path) -> Result<PathBuf, String> {
+    let dir = signature_directory();
+    fs::create_dir_all(&dir).map_err(|e| format!("Could not create the signatures folder. {e}"))?;
+
+    let stem: String = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    Ok(dir.join(format!("imported-{stem}.sig")))
+}
+
+/// Exports the signatures of the enabled databases to a single file, which can be imported on another machine as a
+/// [`SignatureImportFormat::SanctumExport`]. Signatures in more than one database are exported once, and the EICAR
+/// signature built in to every engine is left out.
+///
+/// Nothing specific to this machine is exported, such as the paths the databases were loaded from or which are
+/// disabled in the settings; the names of the databases are recorded only to say where the signatures came from.
+///
+/// # Returns
+///
+/// The outcome of the export, or an error if the file could not be written, in which case no partial export is left.
+pub fn export_signatures(
+    set: &SignatureSet,
+    disabled: &[String],
+    dest: &Path,
+) -> Result<SignatureExportReport, String> {
+    let mut md5 = BTreeSet::new();
+    let mut sha256 = BTreeSet::new();
+    let mut sections = BTreeSet::new();
+    let mut imports = BTreeSet::new();
+    let mut fuzzy = BTreeSet::new();
+    let mut registry = BTreeSet::new();
+    let mut databases = Vec::new();
+
+    for db in set.enabled(disabled) {
+        md5.extend(db.iocs.iter().filter(|hash| *hash != EICAR_MD5).flat_map(|hash| conditioned(db, hash)));
+        sha256.extend(db.sha256_iocs.iter().flat_map(|hash| conditioned(db, hash)));
+        sections.extend(db.section_iocs.iter());
+        imports.extend(db.import_iocs.iter());
+        fuzzy.extend(db.fuzzy_iocs.iter().map(|hash| hash.to_string()));
+        registry.extend(db.registry_iocs.iter().cloned());
+        databases.push(db.name.clone());
+    }
+
+    let lines: Vec<String> = md5
+        .iter()
+        .map(|hash| format!("md5:{hash}"))
+        .chain(sha256.iter().map(|hash| format!("sha256:{hash}")))
+        .chain(sections.iter().map(|hash| format!("section:{hash}")))
+        .chain(imports.iter().map(|hash| format!("imphash:{hash}")))
+        .chain(fuzzy.iter().map(|hash| format!("fuzzy:{hash}")))
+        .chain(registry.iter().map(|text| format!("registry:{text}")))
+        .collect();
+    let checksum = export_checksum(lines.iter().map(String::as_str));
+
+    // an export without conditions is kept readable by engines which predate them
+    let has_conditions = md5.iter().chain(sha256.iter()).any(|signature| signature.contains(' '));
+    let (export_format, sig_format) = match has_conditions {
+        true => (EXPORT_FORMAT_VERSION, SIG_FORMAT_VERSION),
+        false => (UNCONDITIONAL_FORMAT_VERSION, UNCONDITIONAL_FORMAT_VERSION),
+    };
+
+    let mut contents = format!(
+        "{EXPORT_HEADER}\n# export_format: {export_format}\n# sig_format: {sig_format}\n# signature_version: {}\n\
+        # databases: {}\n# checksum: sha256:{checksum}\n",
+        set.version,
+        databases.join(", ")
+    );
+    for line in &lines {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+
+    if let Err(e) = fs::write(dest, contents) {
+        let _ = fs::remove_file(dest);
+        return Err(format!("Could not write the signature export to {}. {e}", dest.display()));
+    }
+
+    Ok(SignatureExportReport {
+        dest: dest.to_path_buf(),
+        signatures: SignatureCounts {
+            hash_signatures: md5.len() + sha256.len(),
+            section_signatures: sections.len(),
+            import_hash_signatures: imports.len(),
+            registry_signatures: registry.len(),
+            yara_rules: 0,
+            fuzzy_signatures: fuzzy.len(),
+        },
+        signature_version: set.version.clone(),
+        databases,
+        checksum,
+    })
+}
+
+/// The signatures of a whole file hash in the database as they are written in a signature file, being the hash alone
+/// where it has no conditions, or the hash followed by each set of its conditions
+fn conditioned(db: &SignatureDatabase, hash: &str) -> Vec<String> {
+    match db.conditions(hash) {
+        Some(conditions) => conditions.iter().map(|c| format!("{hash} {c}")).collect(),
+        None => vec![hash.to_string()],
+    }
+}
+
+/// Imports a signature export, writing its signatures to a `.sig` file in the signatures folder. The export is checked
+/// in full before anything is written: it must be an export in a format this engine understands, its signatures must
+/// match its checksum, and every one must be valid, so a damaged or tampered export imports nothing.
+fn import_export(path: &Path) -> Result<SignatureImportReport, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}. {e}", path.display()))?;
+    let (header, signatures): (Vec<&str>, Vec<&str>) = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .partition(|line| line.starts_with('#'));
+
+    if header.first() != Some(&EXPORT_HEADER) {
+        return Err(format!("{} is not a Sanctum signature export.", path.display()));
+    }
+
+    let field = |name: &str| {
+        header
+            .iter()
+            .find_map(|line| line.strip_prefix('#')?.trim().strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+
+    match field("export_format").and_then(|v| v.parse::<u32>().ok()) {
+        Some(version) if version <= EXPORT_FORMAT_VERSION => (),
+        Some(version) => {
+            return Err(format!(
+                "{} is a version {version} export, which is newer than this engine can import.",
+                path.display()
+            ));
+        }
+        None => return Err(format!("{} does not say which version of export it is.", path.display())),
+    }
+
+    let expected = field("checksum")
+        .and_then(|c| c.strip_prefix("sha256:"))
+        .ok_or_else(|| format!("{} has no checksum.", path.display()))?;
+    if !expected.eq_ignore_ascii_case(&export_checksum(signatures.iter().copied())) {
+        return Err(format!(
+            "The signatures in {} do not match its checksum, so the export is damaged or has been changed. Nothing \
+            was imported.",
+            path.display()
+        ));
+    }
+
+    // the header is parsed along with the signatures, as it gives the version of the signature format
+    let parsed = parse_signatures(contents.as_bytes())
+        .map_err(|e| format!("{} contains a signature which is not valid. {e}", path.display()))?;
+    let imported = parsed.hash_signatures()
+        + parsed.sections.len()
+        + parsed.imports.len()
+        + parsed.fuzzy.len()
+        + parsed.registry.len();
+    if imported == 0 {
+        return Err(format!("{} contains no signatures.", path.display()));
+    }
+
+    // the export is written as it is, with its header, so the signature file says where its signatures came from
+    let destination = import_destination(path)?;
+    fs::write(&destination, &contents)
+        .map_err(|e| format!("Could not write the imported signatures to {}. {e}", destination.display()))?;
+
+    Ok(SignatureImportReport {
+        imported,
+        skipped_malformed: 0,
+        skipped_unsupported: 0,
+        file: destination,
+        signatures: SignatureCounts::default(),
+    })
+}
+
+/// The checksum of the signatures of an export, as the SHA256 of each line followed by a newline. Line endings are not
+/// part of the checksum, so an export which has had its line endings converted in moving it still imports.
+fn export_checksum<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    let mut hasher = Sha256::new();
+    for line in lines {
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+
+    to_hex(&hasher.finalize())
+}
+
+/// Checks a signature file without loading it, reporting every problem which would stop the engine loading the file,
+/// along with those which would let it load but not as its author likely meant: a signature given more than once, or
+/// a hash given both with and without conditions, which matches regardless of its conditions. Unlike a load, the
+/// check goes on past the first bad line, so every problem in the file is found at once.
+///
+/// # Returns
+///
+/// The report, or an error if the file could not be read.
+pub fn validate_signature_file(path: &Path) -> Result<SignatureValidationReport, String> {
+    let mut issues = Vec::new();
+    let issue = |line, severity, kind, message| SignatureIssue {
+        line,
+        severity,
+        kind,
+        message,
+    };
+
+    let extension = path.extension().map(|e| e.to_ascii_lowercase());
+    match extension.as_ref().and_then(|e| e.to_str()) {
+        Some("sig") => (),
+        // the rules are not parsed, as their lines would each be reported as a malformed signature
+        Some("yar") => {
+            return Ok(SignatureValidationReport {
+                path: path.to_path_buf(),
+                valid: false,
+                signatures: SignatureCounts::default(),
+                issues: vec![issue(
+                    None,
+                    SignatureIssueSeverity::Error,
+                    SignatureIssueKind::Yara,
+                    "YARA rules are not supported by this engine, so the file would be skipped.".to_string(),
+                )],
+            });
+        }
+        _ => issues.push(issue(
+            None,
+            SignatureIssueSeverity::Warning,
+            SignatureIssueKind::UnsupportedFormat,
+            "Only .sig files are loaded from the signatures folder, so the file would not be loaded.".to_string(),
+        )),
+    }
+
+    let file = File::open(path).map_err(|e| format!("Could not open {}. {e}", path.display()))?;
+
+    let mut signatures = SignatureFile::default();
+    let mut format = UNCONDITIONAL_FORMAT_VERSION;
+    // the line each signature was first given on, and each whole file hash given with and without conditions
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut unconditional: HashMap<String, usize> = HashMap::new();
+    let mut conditional: HashMap<String, usize> = HashMap::new();
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.map_err(|e| format!("Could not read line {line_number}. {e}"))?;
+        let line = line.trim();
+
+        let signature = match parse_line(line, format) {
+            Ok(SignatureLine::Ignored) => continue,
+            Ok(SignatureLine::Format(version)) if version > SIG_FORMAT_VERSION => {
+                // the rest of the file cannot be checked against a format this engine does not know
+                issues.push(issue(
+                    Some(line_number),
+                    SignatureIssueSeverity::Error,
+                    SignatureIssueKind::UnsupportedFormat,
+                    newer_format(version),
+                ));
+                break;
+            }
+            Ok(SignatureLine::Format(version)) => {
+                format = version;
+                continue;
+            }
+            Ok(SignatureLine::Signature(signature)) => signature,
+            Err(e) => {
+                let kind = match e {
+                    LineError::Invalid => SignatureIssueKind::Malformed,
+                    LineError::InvalidFormat => SignatureIssueKind::UnsupportedFormat,
+                    LineError::Yara => SignatureIssueKind::Yara,
+                    LineError::ConditionsNeedFormat | LineError::Conditions(_) => {
+                        SignatureIssueKind::UnsupportedCondition
+                    }
+                };
+                issues.push(issue(
+                    Some(line_number),
+                    SignatureIssueSeverity::Error,
+                    kind,
+                    e.describe(line_number, line),
+                ));
+                continue;
+            }
+        };
+
+        let key = signature.key();
+        if let Some(first) = seen.get(&key) {
+            issues.push(issue(
+                Some(line_number),
+                SignatureIssueSeverity::Warning,
+                SignatureIssueKind::Duplicate,
+                format!("Line {line_number} repeats the signature on line {first}: {line}"),
+            ));
+            continue;
+        }
+        seen.insert(key, line_number);
+
+        // a hash given without conditions matches regardless, so any conditions given for it have no effect
+        let conflict = match &signature {
+            ParsedSignature::Md5(hash) | ParsedSignature::Sha256(hash) => {
+                let key = hash_key(hash);
+                let conflict = conditional.get(&key).map(|other| {
+                    format!(
+                        "Line {line_number} gives the hash without conditions, so the conditions given for it on \
+                        line {other} have no effect: {line}"
+                    )
+                });
+                unconditional.entry(key).or_insert(line_number);
+                conflict
+            }
+            ParsedSignature::Conditional(hash, _) => {
+                let key = hash_key(hash);
+                let conflict = unconditional.get(&key).map(|other| {
+                    format!(
+                        "Line {line_number} gives conditions for a hash given without them on line {other}, so they \
+                        have no effect: {line}"
+                    )
+                });
+                conditional.entry(key).or_insert(line_number);
+                conflict
+            }
+            _ => None,
+        };
+        if let Some(message) = conflict {
+            issues.push(issue(
+                Some(line_number),
+                SignatureIssueSeverity::Warning,
+                SignatureIssueKind::Conflicting,
+                message,
+            ));
+        }
+
+        signatures.add(signature);
+    }
+
+    Ok(SignatureValidationReport {
+        path: path.to_path_buf(),
+        valid: !issues.iter().any(|i| i.severity == SignatureIssueSeverity::Error),
+        signatures: SignatureCounts {
+            hash_signatures: signatures.hash_signatures(),
+            section_signatures: signatures.sections.len(),
+            import_hash_signatures: signatures.imports.len(),
+            registry_signatures: signatures.registry.len(),
+            yara_rules: 0,
+            fuzzy_signatures: signatures.fuzzy.len(),
+        },
+        issues,
+    })
+}
+
+/// The folder signature files are loaded from
+fn signature_directory() -> PathBuf {
+    let app_data = std::env::var("APPDATA")
+        .expect("[-] Could not find App Data folder in environment variables.]");
+    PathBuf::from(app_data).join(IOC_DIRECTORY_LOCATION)
+}
+
+/// Derives a version string for an IOC list, made up of the number of signatures and a fingerprint of their content.
+/// The set is ordered, so the same list always produces the same version regardless of the order of the file.
+fn signature_version(iocs: &BTreeSet<&str>) -> String {
+    let mut hasher = Md5::new();
+    for ioc in iocs {
+        hasher.update(ioc.as_bytes());
+        hasher.update(b"\n");
+    }
+    let fingerprint = to_hex(&hasher.finalize());
+
+    format!("{}-{}", iocs.len(), &fingerprint[..12])
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A signature set of the built in database alone, holding the whole file MD5 signatures given
+    pub(in crate::filescanner) fn signature_set(md5: &[&str]) -> SignatureSet {
+        signature_set_with_sections(md5, &[])
+    }
+
+    /// A signature set of the built in database alone, holding the whole file MD5 and PE section MD5 signatures given
+    pub(in crate::filescanner) fn signature_set_with_sections(md5: &[&str], sections: &[&str]) -> SignatureSet {
+        let iocs: BTreeSet<String> = md5.iter().map(|hash| hash.to_string()).collect();
+        let section_iocs: BTreeSet<String> = sections.iter().map(|hash| hash.to_string()).collect();
+        let count = iocs.len();
+        let builtin = SignatureDatabase::new(
+            BUILTIN_DATABASE.to_string(),
+            None,
+            iocs,
+            BTreeSet::new(),
+            section_iocs,
+            BTreeSet::new(),
+            FuzzySignatureSet::default(),
+            Vec::new(),
+            Vec::new(),
+        );
+
+        SignatureSet {
+            databases: vec![builtin],
+            version: "test".to_string(),
+            count,
+            files: Vec::new(),
+        }
+    }
+
+    const VALID: &str = "\
+# a feed split across files by category
+44D88612FEA8A8F36DE82E1278ABB02F
+sha256:275A021BBFB6489E54D471899F7DB9D1663FC695EC2FE2A2C4538AABF651FD0F
+section:0123456789ABCDEF0123456789ABCDEF
+imphash:F34D5F2D4577ED6D9CEEC516C1F5A744
+fuzzy:3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C
+registry:powershell -nop -w hidden -enc
+";
+
+    /// Valid but for its third line, an MD5 one digit short
+    const MALFORMED: &str = "\
+md5:0123456789ABCDEF0123456789ABCDEF
+section:FEDCBA9876543210FEDCBA9876543210
+md5:0123456789ABCDEF0123456789ABCDE
+";
+
+    #[test]
+    fn load_directory_skips_malformed_file_and_loads_the_rest() {
+        let dir = std::env::temp_dir().join(format!("sanctum-signatures-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.sig"), VALID).unwrap();
+        fs::write(dir.join("b.sig"), MALFORMED).unwrap();
+        // only .sig and .yar files are signature files
+        fs::write(dir.join("readme.txt"), "not signatures").unwrap();
+
+        let (loaded, stats) = load_directory(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(stats.len(), 2);
+        let (valid, malformed) = (&stats[0], &stats[1]);
+
+        assert_eq!(valid.path, dir.join("a.sig"));
+        assert_eq!(valid.error, None);
+        assert_eq!(
+            (
+                valid.hash_signatures,
+                valid.section_signatures,
+                valid.import_hash_signatures,
+                valid.fuzzy_signatures,
+                valid.registry_signatures,
+            ),
+            (2, 1, 1, 1, 1)
+        );
+
+        // nothing of a malformed file is loaded, not even the lines before the bad one
+        assert_eq!(malformed.path, dir.join("b.sig"));
+        assert!(malformed.error.as_deref().is_some_and(|e| e.starts_with("Line 3 ")), "{:?}", malformed.error);
+        assert_eq!((malformed.hash_signatures, malformed.section_signatures), (0, 0));
+
+        assert_eq!(loaded.len(), 1);
+        let (path, file) = &loaded[0];
+        assert_eq!(path, &dir.join("a.sig"));
+        assert_eq!(file.hashes, ["44D88612FEA8A8F36DE82E1278ABB02F"]);
+        assert_eq!(file.registry, ["powershell -nop -w hidden -enc"]);
+        assert_eq!(
+            stats.iter().map(|s| s.hash_signatures + s.section_signatures).sum::<usize>(),
+            file.hash_signatures() + file.sections.len()
+        );
+    }
+
+    #[test]
+    fn parse_signatures_reports_first_bad_line() {
+        let file = parse_signatures(Cursor::new(VALID)).unwrap();
+        assert_eq!(file.hash_signatures(), 2);
+        assert_eq!(file.sha256, ["275A021BBFB6489E54D471899F7DB9D1663FC695EC2FE2A2C4538AABF651FD0F"]);
+
+        let error = parse_signatures(Cursor::new(MALFORMED)).err().unwrap();
+        assert_eq!(error, "Line 3 is not a valid signature: md5:0123456789ABCDEF0123456789ABCDE");
+
+        // conditions need the file to declare the format which introduced them
+        let conditional = "md5:0123456789ABCDEF0123456789ABCDEF type=executable";
+        assert!(parse_signatures(Cursor::new(conditional)).is_err());
+        let file = parse_signatures(Cursor::new(format!("# sig_format: 2\n{conditional}"))).unwrap();
+        assert_eq!((file.hashes.len(), file.conditional.len()), (0, 1));
+    }
+}
@@ -0,0 +1,104 @@
+//! The window in which an updater replaces the engine's signatures or binaries, during which no scan is started, so
+//! that no scan reads a signature file whilst it is half written, or holds a file the updater is replacing.
+//!
+//! An updater which crashes part way through would leave the engine refusing scans forever, so the window closes by
+//! itself once it has been open for [`MAX_UPDATE_WINDOW`], as though the update had been aborted. Like a snooze of
+//! real-time protection, the window is only held in memory, so restarting the engine closes it.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::utils::log::{Log, LogLevel};
+
+/// The longest an update may keep scans from starting before the window closes by itself
+pub const MAX_UPDATE_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+struct OpenWindow {
+    opened_at: Instant,
+    // set once the update is committed with a restart required, after which scans stay refused until the restart
+    restart_required: bool,
+}
+
+pub struct UpdateWindow {
+    window: Mutex<Option<OpenWindow>>,
+}
+
+impl UpdateWindow {
+    pub fn new() -> Self {
+        UpdateWindow {
+            window: Mutex::new(None),
+        }
+    }
+
+    /// Opens the window, where it is not already open.
+    ///
+    /// # Returns
+    ///
+    /// Whether the window was opened, false where it was already open.
+    pub fn open(&self) -> bool {
+        let mut window = self.window.lock().unwrap();
+        expire(&mut window);
+
+        if window.is_some() {
+            return false;
+        }
+
+        *window = Some(OpenWindow {
+            opened_at: Instant::now(),
+            restart_required: false,
+        });
+
+        true
+    }
+
+    /// Closes the window, so that scans can be started again.
+    ///
+    /// # Returns
+    ///
+    /// Whether the window was open.
+    pub fn close(&self) -> bool {
+        let mut window = self.window.lock().unwrap();
+        expire(&mut window);
+
+        window.take().is_some()
+    }
+
+    /// Keeps the window open until the engine restarts, or the window runs out
+    pub fn require_restart(&self) {
+        if let Some(window) = self.window.lock().unwrap().as_mut() {
+            window.restart_required = true;
+        }
+    }
+
+    /// Whether an update is in progress, in which case no scan may be started
+    pub fn is_open(&self) -> bool {
+        let mut window = self.window.lock().unwrap();
+        expire(&mut window);
+
+        window.is_some()
+    }
+
+    /// Whether the update has been committed and is waiting on the engine to restart
+    pub fn restart_required(&self) -> bool {
+        self.window.lock().unwrap().as_ref().is_some_and(|w| w.restart_required)
+    }
+}
+
+/// Closes the window where it has been open for longer than [`MAX_UPDATE_WINDOW`]
+fn expire(window: &mut Option<OpenWindow>) {
+    if window
+        .as_ref()
+        .is_some_and(|w| w.opened_at.elapsed() >= MAX_UPDATE_WINDOW)
+    {
+        *window = None;
+        Log::new().log(
+            LogLevel::Warning,
+            &format!(
+                "The update did not finish within {} minutes, so scans are allowed again.",
+                MAX_UPDATE_WINDOW.as_secs() / 60
+            ),
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Resolving a drive letter to the root of its volume for a full volume scan.
+//!
+//! A bare `D:` is not the root of the volume on Windows but the current directory on that drive, so the root is
+//! always built as `D:\`. The root of a volume also holds folders reserved by the system which cannot usefully be
+//! scanned (they are either inaccessible, or hold copies of files which are scanned elsewhere), so these are skipped
+//! by default.
+//!
+//! Volumes on the network, such as a mapped network drive or a UNC path, are told apart here so that scans can skip
+//! them, as walking a file server can take many hours.
+
+use std::{
+    fs,
+    os::windows::ffi::OsStrExt,
+    path::{Component, Path, PathBuf, Prefix},
+};
+
+use windows::{
+    Win32::{
+        Foundation::MAX_PATH,
+        Storage::FileSystem::{GetDriveTypeW, GetVolumePathNameW},
+        System::WindowsProgramming::DRIVE_REMOTE,
+    },
+    core::PCWSTR,
+};
+
+/// Entries at the root of a volume which are skipped by default in a volume scan, matched case insensitively
+const RESERVED_ROOT_ENTRIES: [&str; 5] = [
+    "$Recycle.Bin",
+    "System Volume Information",
+    "pagefile.sys",
+    "swapfile.sys",
+    "hiberfil.sys",
+];
+
+/// Resolves a drive letter, given as `D`, `D:` or `D:\`, to the root of its volume.
+///
+/// # Returns
+///
+/// The root of the volume, or an error if the drive letter is not valid or there is no such volume.
+pub fn resolve_volume_root(drive: &str) -> Result<PathBuf, String> {
+    let drive = drive.trim();
+    let trimmed = drive.trim_end_matches(['\\', '/']);
+    let letter = trimmed.strip_suffix(':').unwrap_or(trimmed);
+
+    let mut chars = letter.chars();
+    let letter = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => c.to_ascii_uppercase(),
+        _ => return Err(format!("'{drive}' is not a drive letter.")),
+    };
+
+    let root = PathBuf::from(format!("{letter}:\\"));
+    if !root.is_dir() {
+        return Err(format!("Volume {letter}: could not be found."));
+    }
+
+    Ok(root)
+}
+
+/// Gets the reserved entries which exist at the root of the volume, to be skipped during the scan
+pub fn reserved_entries(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| {
+            path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                RESERVED_ROOT_ENTRIES
+                    .iter()
+                    .any(|reserved| reserved.eq_ignore_ascii_case(&name))
+            })
+        })
+        .collect()
+}
+
+/// Tests whether the path is on a network or other remote volume, such as a mapped network drive or a UNC path. The
+/// volume is found from the path as given, so a symbolic link is not followed; resolve the link to test its target.
+pub fn is_remote_volume(path: &Path) -> bool {
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    // the volume of a relative path may be longer than the path itself
+    let mut volume = vec![0u16; wide_path.len().max(MAX_PATH as usize) + 1];
+
+    if unsafe { GetVolumePathNameW(PCWSTR::from_raw(wide_path.as_ptr()), &mut volume) }.is_err() {
+        // where the volume cannot be found, a UNC path is still known to be on the network
+        return matches!(
+            path.components().next(),
+            Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::UNC(..) | Prefix::VerbatimUNC(..))
+        );
+    }
+
+    let drive_type = unsafe { GetDriveTypeW(PCWSTR::from_raw(volume.as_ptr())) };
+
+    drive_type == DRIVE_REMOTE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_entries_are_only_skipped_at_the_root() {
+        let root = std::env::temp_dir().join(format!("sanctum-volume-root-{}", std::process::id()));
+        // named as Windows names them, which differs in case from the reserved names
+        fs::create_dir_all(root.join("$RECYCLE.BIN")).unwrap();
+        fs::create_dir_all(root.join("System Volume Information")).unwrap();
+        fs::write(root.join("pagefile.sys"), []).unwrap();
+        fs::create_dir_all(root.join("Users").join("$Recycle.Bin")).unwrap();
+        fs::write(root.join("Users").join("hiberfil.sys"), []).unwrap();
+        fs::write(root.join("notes.txt"), []).unwrap();
+
+        let mut skipped = reserved_entries(&root);
+        skipped.sort();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            skipped,
+            [
+                root.join("$RECYCLE.BIN"),
+                root.join("System Volume Information"),
+                root.join("pagefile.sys"),
+            ]
+        );
+    }
+
+    #[test]
+    fn drive_letter_resolves_to_the_volume_root() {
+        // the temporary folder is on a volume which is known to exist
+        let temp = std::env::temp_dir();
+        let letter = match temp.components().next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter.to_ascii_uppercase() as char,
+                _ => panic!("The temporary folder {} is not on a drive.", temp.display()),
+            },
+            _ => panic!("The temporary folder {} is not on a drive.", temp.display()),
+        };
+        let root = PathBuf::from(format!("{letter}:\\"));
+
+        let lower = letter.to_ascii_lowercase();
+        for drive in [format!("{letter}"), format!("{lower}:"), format!("{letter}:\\"), format!(" {letter}:/ ")] {
+            assert_eq!(resolve_volume_root(&drive), Ok(root.clone()), "{drive}");
+        }
+
+        for drive in ["", "CD", "1:", "C:\\Windows"] {
+            assert!(resolve_volume_root(drive).is_err(), "{drive}");
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Forwarding detections from the event bus to a destination outside the engine, such as a SIEM collector or a
+//! webhook.
+//!
+//! Detections are taken from the event bus as they are published, so forwarding never holds up a scan, and the bus
+//! is still read whilst a send is retried. Those which cannot be sent are kept in a bounded queue and retried in
+//! order; once the queue is full the oldest are dropped, and the number dropped is logged, so a destination which is
+//! down for a long time cannot grow the engine's memory without bound.
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    future::Future,
+    sync::{Arc, Mutex as SyncMutex},
+    time::Duration,
+};
+
+use shared_std::{events::EngineEvent, file_scanner::MatchedIOC, settings::SanctumSettings};
+use tokio::sync::{Mutex, Notify};
+
+use crate::{
+    events::EventBus,
+    utils::log::{Log, LogLevel},
+};
+
+/// The most detections held whilst the destination cannot be reached
+pub const FORWARD_QUEUE_CAPACITY: usize = 512;
+
+/// How many times a detection is sent before it is left in the queue for later
+const SEND_ATTEMPTS: u32 = 3;
+/// How long to wait between attempts to send a detection
+const SEND_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How long to wait before retrying the queue, once a detection could not be sent
+const QUEUE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// How long to wait on the destination before the attempt is failed
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Somewhere detections are forwarded to
+pub trait Destination: Send + Sync + 'static {
+    /// A detection as it is sent
+    type Message: Send + Sync + 'static;
+    /// Where detections are sent, as given in the settings, which is shown in the log
+    type Target: Display + Send + Sync;
+
+    /// What the destination is, such as `SIEM collector`, for the log
+    const NAME: &'static str;
+
+    /// The target in the settings, or None where forwarding is turned off
+    fn target(settings: &SanctumSettings) -> Option<Self::Target>;
+
+    /// Formats a detection as it is sent, as of the settings it was made under
+    fn message(&self, settings: &SanctumSettings, scan_id: Option<u64>, ioc: &MatchedIOC) -> Self::Message;
+
+    /// Makes one attempt at sending the message to the target
+    fn send(&self, target: &Self::Target, message: &Self::Message) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Forwards every detection published to the event bus to the destination in the settings, for as long as the engine
+/// runs. Changes to the settings apply from the next detection.
+pub async fn forward_detections<D: Destination>(
+    destination: D,
+    events: Arc<EventBus>,
+    settings: Arc<Mutex<SanctumSettings>>,
+) {
+    let logger = Log::new();
+    let destination = Arc::new(destination);
+    let queue = Arc::new(SyncMutex::new(Queue::default()));
+    let queued = Arc::new(Notify::new());
+
+    // taken from the bus in a task of their own, so that the bus is still read whilst a send is being retried, which
+    // can take long enough for the bus to wrap
+    let mut intake = tokio::spawn(take_detections(
+        Arc::clone(&destination),
+        events,
+        Arc::clone(&settings),
+        Arc::clone(&queue),
+        Arc::clone(&queued),
+    ));
+
+    loop {
+        let waiting = !queue.lock().unwrap().waiting.is_empty();
+        tokio::select! {
+            _ = &mut intake => return,
+            _ = queued.notified() => (),
+            _ = tokio::time::sleep(QUEUE_RETRY_INTERVAL), if waiting => (),
+        }
+
+        // turning forwarding off discards what has not yet been sent
+        let Some(target) = D::target(&*settings.lock().await) else {
+            queue.lock().unwrap().waiting.clear();
+            continue;
+        };
+
+        let dropped = std::mem::take(&mut queue.lock().unwrap().dropped);
+        if dropped > 0 {
+            logger.log(
+                LogLevel::Warning,
+                &format!(
+                    "Dropped {dropped} detections which could not be sent to the {} {target} in time.",
+                    D::NAME
+                ),
+            );
+        }
+
+        // sent in order, stopping at the first which cannot be sent so that the rest are not sent out of order
+        loop {
+            let Some((number, message)) = queue.lock().unwrap().front() else {
+                break;
+            };
+
+            match send_with_retry(&*destination, &target, &message).await {
+                Ok(_) => queue.lock().unwrap().sent(number),
+                Err(e) => {
+                    logger.log(
+                        LogLevel::Warning,
+                        &format!(
+                            "Could not send {} detections to the {} {target}, retrying in {}s. {e}",
+                            queue.lock().unwrap().waiting.len(),
+                            D::NAME,
+                            QUEUE_RETRY_INTERVAL.as_secs()
+                        ),
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// The detections waiting to be sent, in the order they were made
+struct Queue<M> {
+    /// Each detection is held with the number it was queued as, so that one which was sent can be told apart from one
+    /// which took its place at the front as the queue overflowed
+    waiting: VecDeque<(u64, Arc<M>)>,
+    next: u64,
+    /// The number dropped as the queue overflowed, since they were last logged
+    dropped: usize,
+}
+
+impl<M> Default for Queue<M> {
+    fn default() -> Self {
+        Queue {
+            waiting: VecDeque::new(),
+            next: 0,
+            dropped: 0,
+        }
+    }
+}
+
+impl<M> Queue<M> {
+    /// Queues a detection, dropping the oldest if the queue is full
+    fn push(&mut self, message: M) {
+        if self.waiting.len() == FORWARD_QUEUE_CAPACITY {
+            self.waiting.pop_front();
+            self.dropped += 1;
+        }
+
+        self.waiting.push_back((self.next, Arc::new(message)));
+        self.next += 1;
+    }
+
+    /// The oldest detection, which is the next to be sent
+    fn front(&self) -> Option<(u64, Arc<M>)> {
+        self.waiting.front().map(|(number, message)| (*number, Arc::clone(message)))
+    }
+
+    /// Removes the detection queued as the number, once it has been sent. If it was dropped whilst being sent,
+    /// nothing is removed.
+    fn sent(&mut self, number: u64) {
+        if self.waiting.front().is_some_and(|(front, _)| *front == number) {
+            self.waiting.pop_front();
+        }
+    }
+}
+
+/// Moves each detection published to the event bus into the queue for as long as the engine runs, waking the sender
+async fn take_detections<D: Destination>(
+    destination: Arc<D>,
+    events: Arc<EventBus>,
+    settings: Arc<Mutex<SanctumSettings>>,
+    queue: Arc<SyncMutex<Queue<D::Message>>>,
+    queued: Arc<Notify>,
+) {
+    let logger = Log::new();
+
+    let mut watch = events.watch();
+    let mut last_seq = events.latest_seq();
+
+    while watch.changed().await.is_ok() {
+        let settings = settings.lock().await;
+        let forwarding = D::target(&settings).is_some();
+        let envelopes = events.events_after(last_seq);
+
+        // the bus only holds so many events, so any published after the last seen which it no longer holds were missed
+        if let Some(first) = envelopes.first() {
+            let missed = first.seq.saturating_sub(last_seq + 1);
+            if missed > 0 && forwarding {
+                logger.log(
+                    LogLevel::Warning,
+                    &format!(
+                        "Missed {missed} events, which may have held detections, before they were queued for the {}.",
+                        D::NAME
+                    ),
+                );
+            }
+        }
+
+        let mut messages = Vec::new();
+        for envelope in envelopes {
+            last_seq = envelope.seq;
+
+            let EngineEvent::Detection { scan_id, ioc } = envelope.event else {
+                continue;
+            };
+            if forwarding {
+                messages.push(destination.message(&settings, scan_id, &ioc));
+            }
+        }
+        drop(settings);
+
+        if !messages.is_empty() {
+            let mut queue = queue.lock().unwrap();
+            for message in messages {
+                queue.push(message);
+            }
+            drop(queue);
+
+            queued.notify_one();
+        }
+    }
+}
+
+async fn send_with_retry<D: Destination>(
+    destination: &D,
+    target: &D::Target,
+    message: &D::Message,
+) -> Result<(), String> {
+    let mut attempt = 1;
+
+    loop {
+        let result = tokio::time::timeout(SEND_TIMEOUT, destination.send(target, message))
+            .await
+            .unwrap_or_else(|_| Err("timed out".to_string()));
+
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt >= SEND_ATTEMPTS => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(SEND_RETRY_DELAY).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Keeping track of the clients connected to the IPC server, for `connections_list`.
+//!
+//! Each connection is registered when it is accepted and removed when it closes however it ends, so the list only ever
+//! holds the connections which are open.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use shared_no_std::ipc::{CommandRequest, ConnectionRole};
+use shared_std::{
+    connections::{ConnectionInfo, ConnectionKind, EventSubscriptionInfo},
+    events::EventCategory,
+};
+
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: Mutex<BTreeMap<u64, ConnectionInfo>>,
+}
+
+/// A connection in the registry, which is removed from it when dropped
+pub struct RegisteredConnection {
+    registry: Arc<ConnectionRegistry>,
+    id: u64,
+}
+
+impl ConnectionRegistry {
+    /// Registers a connection which has just been accepted
+    pub fn register(self: &Arc<Self>, id: u64, kind: ConnectionKind) -> RegisteredConnection {
+        self.connections.lock().unwrap().insert(
+            id,
+            ConnectionInfo {
+                id,
+                kind,
+                command: None,
+                subscriptions: Vec::new(),
+                connected_at: SystemTime::now(),
+                is_observer: false,
+            },
+        );
+
+        RegisteredConnection {
+            registry: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// The connections which are open, in the order they connected
+    pub fn list(&self) -> Vec<ConnectionInfo> {
+        self.connections.lock().unwrap().values().cloned().collect()
+    }
+}
+
+impl RegisteredConnection {
+    /// Records the request the client sent over the connection
+    pub fn request(&self, request: &CommandRequest) {
+        self.update(|info| {
+            info.command = Some(request.command.clone());
+            info.is_observer = request.role == ConnectionRole::Observer;
+        });
+    }
+
+    /// Records the connection subscribing to events
+    pub fn subscribed(&self, after_seq: Option<u64>, last_sent_seq: u64, categories: EventCategory) {
+        self.update(|info| {
+            info.subscriptions.push(EventSubscriptionInfo {
+                after_seq,
+                last_sent_seq,
+                categories,
+                subscribed_at: SystemTime::now(),
+            })
+        });
+    }
+
+    /// Records the event with the sequence number having been sent to the subscriber
+    pub fn event_sent(&self, seq: u64) {
+        self.update(|info| {
+            if let Some(subscription) = info.subscriptions.last_mut() {
+                subscription.last_sent_seq = seq;
+            }
+        });
+    }
+
+    /// Records the subscriber changing the categories of event it is sent
+    pub fn categories_changed(&self, categories: EventCategory) {
+        self.update(|info| {
+            if let Some(subscription) = info.subscriptions.last_mut() {
+                subscription.categories = categories;
+            }
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ConnectionInfo)) {
+        if let Some(info) = self.registry.connections.lock().unwrap().get_mut(&self.id) {
+            f(info);
+        }
+    }
+}
+
+impl Drop for RegisteredConnection {
+    fn drop(&mut self) {
+        self.registry.connections.lock().unwrap().remove(&self.id);
+    }
+}
@@ -0,0 +1,52 @@
+//! Telling whether the client at the other end of the named pipe is running elevated, for the commands which may only
+//! be sent by an administrator.
+//!
+//! The client is identified by the process the pipe says connected to it, rather than anything in its request, so a
+//! client cannot claim to be elevated. Where the client's process or token cannot be read, it is taken as not being
+//! elevated.
+
+use std::{ffi::c_void, mem, os::windows::io::AsRawHandle};
+
+use tokio::net::windows::named_pipe::NamedPipeServer;
+use windows::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    Security::{GetTokenInformation, TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation},
+    System::{
+        Pipes::GetNamedPipeClientProcessId,
+        Threading::{OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION},
+    },
+};
+
+/// Whether the process connected to the pipe is running elevated
+pub fn pipe_client_elevated(pipe: &NamedPipeServer) -> bool {
+    let mut pid = 0;
+    if unsafe { GetNamedPipeClientProcessId(HANDLE(pipe.as_raw_handle()), &mut pid) }.is_err() {
+        return false;
+    }
+
+    let Ok(process) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }) else {
+        return false;
+    };
+
+    let mut token = HANDLE::default();
+    let opened = unsafe { OpenProcessToken(process, TOKEN_QUERY, &mut token) };
+    let _ = unsafe { CloseHandle(process) };
+    if opened.is_err() {
+        return false;
+    }
+
+    let mut elevation = TOKEN_ELEVATION::default();
+    let mut len = 0;
+    let queried = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut c_void),
+            mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        )
+    };
+    let _ = unsafe { CloseHandle(token) };
+
+    queried.is_ok() && elevation.TokenIsElevated != 0
+}
@@ -7,40 +7,110 @@
 //!
 //! This IPC module is the main event loop for the application.
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use super::{
+    connections::{ConnectionRegistry, RegisteredConnection},
+    elevation::pipe_client_elevated,
+    trace::IpcTrace,
+    transport::{IpcTransport, validate_remote_settings},
+};
+#[cfg(feature = "remote-management")]
+use super::tls::{remote_client_access, tls_acceptor};
 use crate::{
+    audit::{self, AuditRecord},
     core::core::Core,
+    diagnostics::{run_self_test, version_info, warmup},
+    diagnostics_export::{BUNDLE_RECENT_SCANS, DiagnosticsBundle, export_diagnostics},
     driver_manager::SanctumDriverManager,
-    filescanner::FileScanner,
-    settings::get_setting_paths,
+    events::EventBus,
+    export::export_scan,
+    filescanner::{FileScanner, scheduler::RequestPriority},
+    history::ScanHistory,
+    maintenance::run_maintenance,
+    protection_score::protection_score,
+    quarantine::QuarantineStore,
+    realtime::RealtimeSnooze,
+    resources::resource_usage,
+    settings::{effective_config, get_setting_paths, reload_settings},
+    siem::validate_siem_settings,
     utils::{
         env::get_logged_in_username,
-        log::{Log, LogLevel},
+        log::{Log, LogLevel, rotate_log},
     },
+    webhook::validate_webhook_settings,
 };
 use serde_json::{Value, from_slice, to_value, to_vec};
 use shared_no_std::{
-    constants::PIPE_NAME,
-    ipc::{CommandRequest, CommandResponse},
+    constants::{
+        IPC_EVENT_HEARTBEAT_SECS, IPC_MAX_BATCH_LEN, IPC_MAX_CONCURRENT_CONNECTIONS, IPC_MAX_REQUEST_LEN,
+        IPC_STREAM_MAX_CHUNK_LEN, IPC_STREAM_MAX_TOTAL_LEN, PIPE_NAME, PIPE_NAME_OBSERVER,
+    },
+    ipc::{
+        CodecKind, CommandRequest, CommandResponse, ConnectionRole, IpcHandshake, STATUS_PERMISSION_DENIED,
+    },
+};
+use shared_std::{
+    codec::{Codec, Postcard, encode_tagged},
+    connections::ConnectionKind,
+    diagnostics::DiagnosticsExportRequest,
+    driver_manager::{DriverAvailability, RealtimeStatus, STATUS_DRIVER_UNAVAILABLE},
+    events::{EngineEventEnvelope, EventCategory, EventSubscribeArgs, EventSubscriptionUpdate},
+    history::{DetectionAnnotationRequest, ScanDiffRequest, ScanExportRequest, StatsPeriod, TriageStatus},
+    file_scanner::{
+        BenchmarkRequest, CANCEL_REASON_STOP_ALL, CancelledByTarget, EngineFlags, ImageScanRequest, RegistryScanRequest,
+        ResultsPageRequest, STATUS_ENGINE_BUSY, ScanRequest, ScanStarted, ScanTarget, ScanTargetRequest,
+        SimulatedDetectionRequest, StopAllSummary, VolumeScanRequest,
+    },
+    quarantine::{BatchQuarantineOutcome, QuarantineExtractRequest, QuarantineRestoreRequest},
+    settings::{ExclusionPreviewRequest, ExclusionRule, RemoteManagementSettings, SanctumSettings},
+    version::{
+        HashRequest, SignatureDatabaseToggle, SignatureExportRequest, SignatureImportRequest,
+        SignatureValidationRequest, UpdateCommitRequest,
+    },
 };
-use shared_std::settings::SanctumSettings;
 use tokio::{
     fs,
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::windows::named_pipe::ServerOptions,
-    sync::Mutex,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::windows::named_pipe::{NamedPipeServer, ServerOptions},
+    sync::{Mutex, Semaphore, watch},
+    task::JoinError,
 };
 
+/// The most detections returned by `detection_recent`
+const RECENT_DETECTIONS_LIMIT: usize = 200;
+
+/// How long a remote management client has to complete the TLS handshake before it is dropped
+#[cfg(feature = "remote-management")]
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// An interface for the usermode IPC server
 pub struct UmIpc {}
 
 impl UmIpc {
+    /// Runs the IPC server, serving up to [`IPC_MAX_CONCURRENT_CONNECTIONS`] clients at once (such as the GUI
+    /// alongside an integrator). A client which connects whilst the server is at the limit waits until another
+    /// disconnects.
+    ///
+    /// A new pipe instance is created for each client, and each connection is served by its own task which shares the
+    /// engine state with the others. Anything which lives for the length of a connection, such as an event
+    /// subscription, belongs to that connection's task alone, so one client cannot affect what another receives.
+    ///
+    /// Clients which connect to [`PIPE_NAME_OBSERVER`] rather than [`PIPE_NAME`] are served as observers, whatever
+    /// role their requests give.
     pub async fn listen(
         settings: Arc<Mutex<SanctumSettings>>,
         core: Arc<Core>,
         file_scanner: Arc<FileScanner>,
         driver_manager: Arc<Mutex<SanctumDriverManager>>,
+        events: Arc<EventBus>,
+        history: Arc<ScanHistory>,
+        quarantine: Arc<QuarantineStore>,
+        realtime_snooze: Arc<RealtimeSnooze>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let logger = Log::new();
         logger.log(
@@ -50,7 +120,7 @@ impl UmIpc {
 
         // set up IPC
         // todo default server type to Bytes and see if it causes an issue, if not - delete the commented out pipe_mode.
-        let mut server = ServerOptions::new()
+        let server = ServerOptions::new()
             .first_pipe_instance(true)
             // .pipe_mode(PipeMode::Message)
             .create(PIPE_NAME)?;
@@ -60,90 +130,1084 @@ impl UmIpc {
             &format!("Named pipe listening on {}", PIPE_NAME),
         );
 
-        loop {
-            // create the next server instance before accepting the client connection, without this
-            // there is a fraction of time where there will be no server listening
-            let next_server = ServerOptions::new().create(PIPE_NAME)?;
+        let state = IpcState {
+            settings,
+            core,
+            file_scanner,
+            driver_manager,
+            events,
+            history,
+            quarantine,
+            realtime_snooze,
+            trace: Arc::new(IpcTrace::from_env()),
+            connections: Arc::new(ConnectionRegistry::default()),
+        };
+
+        // remote management is opt in, and only read at startup, so a change to it applies once the engine restarts
+        let remote = state.settings.lock().await.remote_management.clone();
+        if remote.enabled {
+            start_remote_management(&state, remote);
+        }
+
+        // observers have their own pipe, so that their role is bound to the connection rather than taken from what
+        // they send, and their own connections, so that they cannot use up those the GUI is served on
+        let observer_server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(PIPE_NAME_OBSERVER)?;
+        {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let listened = listen_pipe(observer_server, PIPE_NAME_OBSERVER, ConnectionRole::Observer, state).await;
+                if let Err(e) = listened {
+                    Log::new().log(
+                        LogLevel::Error,
+                        &format!("Observer pipe listener stopped. {e}"),
+                    );
+                }
+            });
+        }
+
+        listen_pipe(server, PIPE_NAME, ConnectionRole::Controller, state).await?;
+
+        Ok(())
+    }
+}
+
+/// Accepts clients on the named pipe, serving up to [`IPC_MAX_CONCURRENT_CONNECTIONS`] of them at once, each of which
+/// is served with the role of the pipe.
+///
+/// # Args
+///
+/// - `server`: The first instance of the pipe, which has already been created
+async fn listen_pipe(
+    mut server: NamedPipeServer,
+    pipe_name: &str,
+    role: ConnectionRole,
+    state: IpcState,
+) -> std::io::Result<()> {
+    let logger = Log::new();
+    let connections = Arc::new(Semaphore::new(IPC_MAX_CONCURRENT_CONNECTIONS as usize));
+
+    loop {
+        // held by the connection's task until it ends; the current instance stays listening meanwhile, so a
+        // client connecting at the limit is queued rather than refused
+        let connection_permit = Arc::clone(&connections).acquire_owned().await.unwrap();
 
-            server.connect().await?;
+        // create the next server instance before accepting the client connection, without this
+        // there is a fraction of time where there will be no server listening
+        let next_server = ServerOptions::new().create(pipe_name)?;
 
-            // move the current server instance to a client handler
-            let mut client = server;
+        // a client which goes away before it is accepted only affects its own instance, so the server carries on
+        // with the next instance rather than no longer accepting any clients
+        if let Err(e) = server.connect().await {
+            logger.log(
+                LogLevel::Warning,
+                &format!("IPC client failed to connect. {e}"),
+            );
             server = next_server;
+            continue;
+        }
 
-            let settings_clone = Arc::clone(&settings);
-            let core_clone = Arc::clone(&core);
-            let scanner_clone = Arc::clone(&file_scanner);
-            let drv_mgr_clone = Arc::clone(&driver_manager);
+        // move the current server instance to a client handler
+        let mut client = server;
+        server = next_server;
 
-            tokio::spawn(async move {
-                let mut buffer = vec![0; 1024];
-                let logger = Log::new();
-
-                // read the request
-                match client.read(&mut buffer).await {
-                    Ok(bytes_read) => {
-                        if bytes_read == 0 {
-                            logger.log(LogLevel::Info, "IPC client disconnected");
-                            return;
+        let elevated = pipe_client_elevated(&client);
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _connection_permit = connection_permit;
+            serve_connection(&mut client, ConnectionKind::Pipe, role, elevated, state).await;
+        });
+    }
+}
+
+/// What the task serving each connection shares with the others, whichever transport the client connected over
+#[derive(Clone)]
+struct IpcState {
+    settings: Arc<Mutex<SanctumSettings>>,
+    core: Arc<Core>,
+    file_scanner: Arc<FileScanner>,
+    driver_manager: Arc<Mutex<SanctumDriverManager>>,
+    events: Arc<EventBus>,
+    history: Arc<ScanHistory>,
+    quarantine: Arc<QuarantineStore>,
+    realtime_snooze: Arc<RealtimeSnooze>,
+    trace: Arc<IpcTrace>,
+    connections: Arc<ConnectionRegistry>,
+}
+
+/// Starts the remote management listener on a task of its own, which serves alongside the pipe
+#[cfg(feature = "remote-management")]
+fn start_remote_management(state: &IpcState, remote: RemoteManagementSettings) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = listen_tcp(state, remote).await {
+            Log::new().log(
+                LogLevel::Error,
+                &format!("Remote management listener stopped. {e}"),
+            );
+        }
+    });
+}
+
+/// Settings which turn on remote management are refused by an engine which cannot listen for it, but one which has
+/// been given them all the same still serves the pipe
+#[cfg(not(feature = "remote-management"))]
+fn start_remote_management(_state: &IpcState, _remote: RemoteManagementSettings) {
+    Log::new().log(
+        LogLevel::Warning,
+        "Remote management is turned on in the settings, but this engine was built without it.",
+    );
+}
+
+/// Listens for remote management clients on TCP, serving up to [`IPC_MAX_CONCURRENT_CONNECTIONS`] at once apart from
+/// those connected over the pipe, so that remote consoles cannot use up the connections the GUI is served on.
+///
+/// Each client must complete the TLS handshake, presenting a client certificate, before its request is read. The
+/// request is then served as one over the pipe is, with the role and elevation the settings give the certificate.
+#[cfg(feature = "remote-management")]
+async fn listen_tcp(state: IpcState, remote: RemoteManagementSettings) -> std::io::Result<()> {
+    let logger = Log::new();
+    let acceptor = tls_acceptor(&remote).map_err(std::io::Error::other)?;
+    let address = remote.listen_address.clone().unwrap_or_default();
+    let listener = tokio::net::TcpListener::bind(address.trim()).await?;
+
+    logger.log(
+        LogLevel::Success,
+        &format!("Remote management listening on {address}"),
+    );
+
+    let connections = Arc::new(Semaphore::new(IPC_MAX_CONCURRENT_CONNECTIONS as usize));
+
+    loop {
+        let connection_permit = Arc::clone(&connections).acquire_owned().await.unwrap();
+
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                logger.log(
+                    LogLevel::Warning,
+                    &format!("Remote management client failed to connect. {e}"),
+                );
+                continue;
+            }
+        };
+
+        let acceptor = acceptor.clone();
+        let remote = remote.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _connection_permit = connection_permit;
+            let logger = Log::new();
+
+            // a client which has no valid certificate, or does not finish the handshake in time, is dropped
+            let mut client = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(client)) => client,
+                Ok(Err(e)) => {
+                    logger.log(
+                        LogLevel::Warning,
+                        &format!("Remote management client {peer} failed the TLS handshake. {e}"),
+                    );
+                    return;
+                }
+                Err(_) => {
+                    logger.log(
+                        LogLevel::Warning,
+                        &format!("Remote management client {peer} timed out in the TLS handshake."),
+                    );
+                    return;
+                }
+            };
+
+            // what the client may do is decided by the certificate it presented, which it cannot change without
+            // failing the handshake, rather than by anything in its request
+            let certificate = client.get_ref().1.peer_certificates().and_then(|certificates| certificates.first());
+            let (role, elevated) = remote_client_access(&remote, certificate);
+
+            logger.log(
+                LogLevel::Debug,
+                &format!("Remote management client connected from {peer} as {role:?}, elevated: {elevated}"),
+            );
+            let kind = ConnectionKind::Remote { peer: peer.to_string() };
+            serve_connection(&mut client, kind, role, elevated, state).await;
+
+            // closing the TLS session tells the client it has the whole response
+            let _ = client.shutdown().await;
+        });
+    }
+}
+
+/// Serves the request a client sends over its connection. Each connection carries a single request, other than an
+/// event subscription or streamed scan, which hold the connection open until they finish.
+///
+/// # Args
+///
+/// - `role`: The role the connection allows; a client on an observer connection is served as an observer even if
+///   its request claims to be a controller, whereas a controller may ask to be served as an observer
+/// - `elevated`: Whether the client is running as an administrator, which the commands [`requires_elevation`] gives
+///   are only served to
+async fn serve_connection(
+    client: &mut impl IpcTransport,
+    kind: ConnectionKind,
+    role: ConnectionRole,
+    elevated: bool,
+    state: IpcState,
+) {
+    let IpcState {
+        settings,
+        core,
+        file_scanner,
+        driver_manager,
+        events,
+        history,
+        quarantine,
+        realtime_snooze,
+        trace,
+        connections,
+    } = state.clone();
+    let correlation_id = trace.next_correlation_id();
+    // listed by connections_list until the connection closes
+    let connection = connections.register(correlation_id, kind);
+    let mut buffer = vec![0; IPC_MAX_REQUEST_LEN as usize];
+    let logger = Log::new();
+
+    // read the request
+    match client.read(&mut buffer).await {
+        Ok(bytes_read) => {
+            if bytes_read == 0 {
+                logger.log(LogLevel::Info, "IPC client disconnected");
+                return;
+            }
+
+            // deserialise the request
+            match from_slice::<CommandRequest>(&buffer[..bytes_read]) {
+                Ok(request) => {
+                    let request = bind_role(request, role);
+                    trace.request(correlation_id, &request);
+                    connection.request(&request);
+                    let request = route_streamed_scan(request);
+
+                    // an observer may only watch the engine, so anything which could change it is refused
+                    // before it reaches a handler
+                    let _observer = match request.role {
+                        ConnectionRole::Observer => {
+                            if !OBSERVER_COMMANDS.contains(&request.command.as_str()) {
+                                let response = permission_denied_response(&request.command);
+                                let response_bytes = to_vec(&response).unwrap();
+                                trace.response(correlation_id, STATUS_PERMISSION_DENIED, response_bytes.len());
+                                if let Err(e) = client.write_all(&response_bytes).await {
+                                    logger.log(LogLevel::Error, &format!("[-] Failed to send response to client: {}", e));
+                                }
+                                return;
+                            }
+                            Some(ObserverSession::start(&request.command))
                         }
+                        ConnectionRole::Controller => None,
+                    };
+
+                    if !elevated && requires_elevation(&request, &settings).await {
+                        let response = elevation_required_response(&request.command);
+                        let response_bytes = to_vec(&response).unwrap();
+                        trace.response(correlation_id, STATUS_PERMISSION_DENIED, response_bytes.len());
+                        if let Err(e) = client.write_all(&response_bytes).await {
+                            logger.log(LogLevel::Error, &format!("[-] Failed to send response to client: {}", e));
+                        }
+                        return;
+                    }
+
+                    // on-demand requests are refused whilst the engine has no slot free for them, rather than
+                    // queueing behind a scan; the slot is held until the request has been answered
+                    let _on_demand_permit = match on_demand_priority(&request.command) {
+                        Some(priority) => match file_scanner.acquire_on_demand(priority) {
+                            Ok(permit) => Some(permit),
+                            Err(busy) => {
+                                let response_bytes = to_vec(&busy).unwrap();
+                                trace.response(correlation_id, STATUS_ENGINE_BUSY, response_bytes.len());
+                                if let Err(e) = client.write_all(&response_bytes).await {
+                                    logger.log(LogLevel::Error, &format!("[-] Failed to send response to client: {}", e));
+                                }
+                                return;
+                            }
+                        },
+                        None => None,
+                    };
+
+                    // each command of a batch is checked and served as though it had been sent on its own
+                    if request.command == "batch" {
+                        let response = handle_batch(request, elevated, &state).await;
+                        let response_bytes = to_vec(&response).unwrap();
+                        trace.response(correlation_id, response_status(&response), response_bytes.len());
+                        if let Err(e) = client.write_all(&response_bytes).await {
+                            logger.log(LogLevel::Error, &format!("[-] Failed to send response to client: {}", e));
+                        }
+                        return;
+                    }
 
-                        // deserialise the request
-                        match from_slice::<CommandRequest>(&buffer[..bytes_read]) {
-                            Ok(request) => {
-                                //
-                                // Handle the incoming IPC request here
-                                //
-                                if let Some(response) = handle_ipc(
+                    // event subscriptions hold the pipe open to stream events, so are handled separately
+                    if request.command == "events_subscribe" {
+                        let status = match handle_event_subscription(client, request, &events, &connection).await {
+                            Ok(_) => "closed",
+                            Err(e) => {
+                                logger.log(LogLevel::Debug, &format!("Event subscriber disconnected. {e}"));
+                                "disconnected"
+                            }
+                        };
+                        trace.response(correlation_id, status, 0);
+                        return;
+                    }
+
+                    // streamed scans read their content from the pipe itself, so are handled separately
+                    if request.command == "scanner_scan_bytes" {
+                        let response =
+                            handle_scan_bytes_stream(client, request, &file_scanner)
+                                .await;
+                        let response_bytes = to_vec(&response).unwrap();
+                        trace.response(correlation_id, response_status(&response), response_bytes.len());
+                        if let Err(e) = client.write_all(&response_bytes).await {
+                            logger.log(LogLevel::Error, &format!("[-] Failed to send response to client: {}", e));
+                        }
+                        return;
+                    }
+
+                    let response_codec = request.response_codec;
+
+                    //
+                    // Handle the incoming IPC request here. Commands on hot paths may be answered with a
+                    // binary codec where the client asked for one, otherwise fall back to JSON.
+                    //
+                    let response_bytes = match encode_binary_response(
+                        &request.command,
+                        response_codec,
+                        &file_scanner,
+                    ) {
+                        Some(bytes) => {
+                            if let Ok(b) = &bytes {
+                                trace.response(correlation_id, "ok", b.len());
+                            }
+                            Some(bytes)
+                        }
+                        None => {
+                            let command = request.command.clone();
+                            let response = handle_isolated(
+                                &command,
+                                handle_ipc(
                                     request,
-                                    settings_clone,
-                                    core_clone,
-                                    scanner_clone,
-                                    drv_mgr_clone,
-                                )
-                                .await
-                                {
-                                    //
-                                    // Serialise and send the response back to the client
-                                    //
-                                    match to_vec(&response) {
-                                        Ok(response_bytes) => {
-                                            if let Err(e) = client.write_all(&response_bytes).await
-                                            {
-                                                logger.log(LogLevel::Error, &format!("[-] Failed to send response to client via pipe: {}", e));
-                                            }
-                                        }
-                                        // err serialising to vec
-                                        Err(e) => logger.log(
-                                            LogLevel::Error,
-                                            &format!("[-] Failed to serialise response: {}", e),
-                                        ),
-                                    };
-                                };
+                                    settings,
+                                    core,
+                                    file_scanner,
+                                    driver_manager,
+                                    history,
+                                    quarantine,
+                                    realtime_snooze,
+                                    connections,
+                                ),
+                            )
+                            .await;
+
+                            response.map(|response| {
+                                let encoded = encode_json_response(&response, response_codec);
+                                if let Ok(b) = &encoded {
+                                    trace.response(correlation_id, response_status(&response), b.len());
+                                }
+                                encoded
+                            })
+                        }
+                    };
+
+                    //
+                    // Send the serialised response back to the client
+                    //
+                    match response_bytes {
+                        Some(Ok(response_bytes)) => {
+                            if let Err(e) = client.write_all(&response_bytes).await {
+                                logger.log(LogLevel::Error, &format!("[-] Failed to send response to client: {}", e));
                             }
-                            // err serialising into CommandRequest
-                            Err(e) => logger.log(
+                        }
+                        // err serialising to vec
+                        Some(Err(e)) => {
+                            trace.response(correlation_id, "serialise error", 0);
+                            logger.log(
                                 LogLevel::Error,
-                                &format!(
-                                    "Failed to deserialise request: {:?}. Err: {}. Bytes read: {}",
-                                    &buffer[..bytes_read],
-                                    e,
-                                    bytes_read
-                                ),
-                            ),
+                                &format!("[-] Failed to serialise response: {}", e),
+                            )
                         }
-                    }
-                    // err reading IPC
-                    Err(e) => logger.log(
-                        LogLevel::Error,
-                        &format!("Failed to read from client: {}", e),
-                    ),
+                        None => trace.response(correlation_id, "no response", 0),
+                    };
                 }
-            });
+                // err serialising into CommandRequest
+                Err(e) => logger.log(
+                    LogLevel::Error,
+                    &format!(
+                        "Failed to deserialise request: {:?}. Err: {}. Bytes read: {}",
+                        &buffer[..bytes_read],
+                        e,
+                        bytes_read
+                    ),
+                ),
+            }
         }
+        // err reading IPC
+        Err(e) => logger.log(
+            LogLevel::Error,
+            &format!("Failed to read from client: {}", e),
+        ),
     }
 }
 
+/// Handles a `batch` request, whose args are the commands to serve, so that a client which needs several pieces of
+/// state, such as a page of the GUI as it loads, can get them in one round trip.
+///
+/// The commands are served one after another, each as though it had been sent on its own connection by the same
+/// client: it is sent with the role of the batch, so an observer cannot send an action by batching it, and needs
+/// elevation and an on-demand slot as it would on its own. A command which is refused, fails or panics is answered
+/// with its own error, and the rest of the batch is still served.
+///
+/// # Returns
+///
+/// The response to each command, in the order they were sent, or a `CommandResponse` describing why the batch itself
+/// was refused.
+async fn handle_batch(request: CommandRequest, elevated: bool, state: &IpcState) -> Value {
+    let requests = match request.args.map(serde_json::from_value::<Vec<CommandRequest>>) {
+        Some(Ok(requests)) => requests,
+        _ => {
+            return to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No commands passed to batch".to_string(),
+            })
+            .unwrap();
+        }
+    };
+
+    if requests.len() > IPC_MAX_BATCH_LEN as usize {
+        return to_value(CommandResponse {
+            status: "error".to_string(),
+            message: format!(
+                "A batch may hold at most {IPC_MAX_BATCH_LEN} commands, but {} were sent.",
+                requests.len()
+            ),
+        })
+        .unwrap();
+    }
+
+    let mut responses = Vec::with_capacity(requests.len());
+    for batched in requests {
+        let batched = route_streamed_scan(CommandRequest {
+            role: request.role,
+            response_codec: CodecKind::Json,
+            ..batched
+        });
+        responses.push(serve_batched(batched, elevated, state).await);
+    }
+
+    to_value(responses).unwrap()
+}
+
+/// Serves a single command of a batch, making the same checks as a command sent on its own
+async fn serve_batched(request: CommandRequest, elevated: bool, state: &IpcState) -> Value {
+    // commands which hold the connection open, and batches themselves, can only be sent on their own
+    if ["batch", "events_subscribe", "scanner_scan_bytes"].contains(&request.command.as_str()) {
+        return to_value(CommandResponse {
+            status: "error".to_string(),
+            message: format!("{} cannot be sent in a batch.", request.command),
+        })
+        .unwrap();
+    }
+
+    if request.role == ConnectionRole::Observer && !OBSERVER_COMMANDS.contains(&request.command.as_str()) {
+        return permission_denied_response(&request.command);
+    }
+    if !elevated && requires_elevation(&request, &state.settings).await {
+        return elevation_required_response(&request.command);
+    }
+
+    let _on_demand_permit = match on_demand_priority(&request.command) {
+        Some(priority) => match state.file_scanner.acquire_on_demand(priority) {
+            Ok(permit) => Some(permit),
+            Err(busy) => return to_value(busy).unwrap(),
+        },
+        None => None,
+    };
+
+    let command = request.command.clone();
+    let handler = handle_ipc(
+        request,
+        Arc::clone(&state.settings),
+        Arc::clone(&state.core),
+        Arc::clone(&state.file_scanner),
+        Arc::clone(&state.driver_manager),
+        Arc::clone(&state.history),
+        Arc::clone(&state.quarantine),
+        Arc::clone(&state.realtime_snooze),
+        Arc::clone(&state.connections),
+    );
+
+    handle_isolated(&command, handler).await.unwrap_or(Value::Null)
+}
+
+/// Handles a `scanner_scan_bytes` request, where the content to be scanned is streamed over the pipe rather than
+/// being read from disk.
+///
+/// # Protocol
+///
+/// Once the request has been received, the engine replies with a `CommandResponse` with the status `ready`. The client
+/// then sends the content as a series of chunks, each prefixed with its length as a little endian u32, terminated by a
+/// zero length chunk. Chunks may be at most [`IPC_STREAM_MAX_CHUNK_LEN`] bytes, and the content at most
+/// [`IPC_STREAM_MAX_TOTAL_LEN`] bytes in total. The content is hashed as it arrives so memory use stays flat.
+///
+/// The args of the request may optionally contain a name for the content to be reported in place of a file path.
+///
+/// # Returns
+///
+/// The `BytesScanResult` on success, otherwise a `CommandResponse` describing the error.
+async fn handle_scan_bytes_stream(
+    client: &mut impl IpcTransport,
+    request: CommandRequest,
+    file_scanner: &Arc<FileScanner>,
+) -> Value {
+    let name = request
+        .args
+        .and_then(|a| serde_json::from_value::<String>(a).ok())
+        .unwrap_or_else(|| "<stdin>".to_string());
+
+    let error = |message: String| {
+        to_value(CommandResponse {
+            status: "error".to_string(),
+            message,
+        })
+        .unwrap()
+    };
+
+    // tell the client we are ready to receive the content
+    let ready = to_vec(&CommandResponse {
+        status: "ready".to_string(),
+        message: String::new(),
+    })
+    .unwrap();
+    if let Err(e) = client.write_all(&ready).await {
+        return error(format!("Failed to acknowledge stream. {e}"));
+    }
+
+    let mut scan = file_scanner.begin_bytes_scan();
+    let mut buf: Vec<u8> = Vec::new();
+
+    loop {
+        let chunk_len = match client.read_u32_le().await {
+            Ok(len) => len,
+            Err(e) => return error(format!("Failed to read chunk length. {e}")),
+        };
+
+        // a zero length chunk terminates the stream
+        if chunk_len == 0 {
+            break;
+        }
+
+        if chunk_len > IPC_STREAM_MAX_CHUNK_LEN {
+            return error(format!(
+                "Chunk of {chunk_len} bytes exceeds the maximum of {IPC_STREAM_MAX_CHUNK_LEN}."
+            ));
+        }
+        if scan.bytes_received() + chunk_len as u64 > IPC_STREAM_MAX_TOTAL_LEN {
+            return error(format!(
+                "Streamed content exceeds the maximum of {IPC_STREAM_MAX_TOTAL_LEN} bytes."
+            ));
+        }
+
+        buf.resize(chunk_len as usize, 0);
+        if let Err(e) = client.read_exact(&mut buf).await {
+            return error(format!("Failed to read chunk. {e}"));
+        }
+
+        scan.update(&buf);
+    }
+
+    to_value(file_scanner.finish_bytes_scan(scan, &name)).unwrap()
+}
+
+/// Handles an `events_subscribe` request, holding the pipe open and streaming events to the subscriber until it
+/// disconnects.
+///
+/// # Protocol
+///
+/// Everything the engine sends is framed, prefixed with its length as a little endian u32. The first frame is a
+/// `CommandResponse` with the status `ready`, whose message is the sequence number of the latest event. Each frame
+/// which follows is an `EngineEventEnvelope`, serialised as JSON, or tagged with the binary codec where the subscriber
+/// asked for it as its `response_codec`. Where no events are raised for [`IPC_EVENT_HEARTBEAT_SECS`], a zero length
+/// frame is sent as a heartbeat.
+///
+/// Where the args contain the sequence number of the last event the subscriber received, retained events raised since
+/// are sent first, so a subscriber which reconnects does not miss events.
+///
+/// Only events in the categories the subscriber asked for are sent. Whilst subscribed, the subscriber may send a
+/// `subscribe_update` request over the pipe, framed as the engine's frames are, to change its categories from the next
+/// event on. Anything else it sends ends the subscription.
+///
+/// # Returns
+///
+/// An error once the subscriber can no longer be written to, which is how a disconnect is noticed.
+async fn handle_event_subscription(
+    client: &mut impl IpcTransport,
+    request: CommandRequest,
+    events: &EventBus,
+    connection: &RegisteredConnection,
+) -> std::io::Result<()> {
+    let args: EventSubscribeArgs = request
+        .args
+        .and_then(|a| serde_json::from_value(a).ok())
+        .unwrap_or_default();
+
+    if args.categories.has_unknown_bits() {
+        let refused = to_vec(&CommandResponse {
+            status: "error".to_string(),
+            message: format!("Unknown event categories {:#x}.", args.categories.0),
+        })?;
+        client.write_u32_le(refused.len() as u32).await?;
+        client.write_all(&refused).await?;
+        return Ok(());
+    }
+
+    // watch before reading the backlog, so an event raised in between is not missed
+    let latest = events.watch();
+    let sent_seq = args.after_seq.unwrap_or_else(|| events.latest_seq());
+    connection.subscribed(args.after_seq, sent_seq, args.categories);
+
+    let ready = to_vec(&CommandResponse {
+        status: "ready".to_string(),
+        message: events.latest_seq().to_string(),
+    })?;
+    client.write_u32_le(ready.len() as u32).await?;
+    client.write_all(&ready).await?;
+
+    let (categories_tx, categories) = watch::channel(args.categories);
+    let codec = request.response_codec;
+    let (mut reader, mut writer) = tokio::io::split(client);
+
+    tokio::select! {
+        result = read_subscription_updates(&mut reader, &categories_tx, connection) => result,
+        result = stream_events(&mut writer, events, latest, sent_seq, &categories, codec, connection) => result,
+    }
+}
+
+/// Streams the events raised after `sent_seq` to the subscriber, in the categories it last asked for
+async fn stream_events(
+    writer: &mut (impl AsyncWrite + Unpin),
+    events: &EventBus,
+    mut latest: watch::Receiver<u64>,
+    mut sent_seq: u64,
+    categories: &watch::Receiver<EventCategory>,
+    codec: CodecKind,
+    connection: &RegisteredConnection,
+) -> std::io::Result<()> {
+    loop {
+        let wanted = *categories.borrow();
+
+        for envelope in events.events_after(sent_seq) {
+            // events the subscriber does not want are passed over as though sent, so they are not sent should it ask
+            // for their category later
+            if wanted.contains(envelope.event.category()) {
+                let frame = encode_event(&envelope, codec)?;
+                writer.write_u32_le(frame.len() as u32).await?;
+                writer.write_all(&frame).await?;
+            }
+            sent_seq = envelope.seq;
+            connection.event_sent(sent_seq);
+        }
+
+        match tokio::time::timeout(
+            Duration::from_secs(IPC_EVENT_HEARTBEAT_SECS),
+            latest.changed(),
+        )
+        .await
+        {
+            Ok(Ok(_)) => (),
+            // the event bus has gone, so the engine is shutting down
+            Ok(Err(_)) => return Ok(()),
+            Err(_) => writer.write_u32_le(0).await?,
+        }
+    }
+}
+
+/// Encodes an event for a subscriber. The event stream is the busiest of the engine's responses, so a subscriber which
+/// asked for the binary codec is sent events tagged with it; any other is sent untagged JSON as it always has been.
+fn encode_event(envelope: &EngineEventEnvelope, codec: CodecKind) -> std::io::Result<Vec<u8>> {
+    if codec != Postcard::KIND {
+        return to_vec(envelope).map_err(std::io::Error::from);
+    }
+
+    encode_tagged::<Postcard, _>(envelope)
+}
+
+/// Reads the `subscribe_update` requests the subscriber sends, changing the categories of event it is sent to those
+/// it asks for.
+///
+/// # Returns
+///
+/// An error once the subscriber disconnects, or sends something other than a valid `subscribe_update`.
+async fn read_subscription_updates(
+    reader: &mut (impl AsyncRead + Unpin),
+    categories: &watch::Sender<EventCategory>,
+    connection: &RegisteredConnection,
+) -> std::io::Result<()> {
+    loop {
+        let len = reader.read_u32_le().await?;
+        if len > IPC_STREAM_MAX_CHUNK_LEN {
+            return Err(std::io::Error::other(format!(
+                "Subscription update of {len} bytes exceeds the maximum of {IPC_STREAM_MAX_CHUNK_LEN}."
+            )));
+        }
+
+        let mut frame = vec![0u8; len as usize];
+        reader.read_exact(&mut frame).await?;
+
+        let request: CommandRequest = from_slice(&frame)?;
+        if request.command != "subscribe_update" {
+            return Err(std::io::Error::other(format!(
+                "'{}' cannot be sent over an event subscription.",
+                request.command
+            )));
+        }
+
+        let update: EventSubscriptionUpdate = serde_json::from_value(request.args.unwrap_or_default())?;
+        if update.categories.has_unknown_bits() {
+            return Err(std::io::Error::other(format!("Unknown event categories {:#x}.", update.categories.0)));
+        }
+
+        categories.send_replace(update.categories);
+        connection.categories_changed(update.categories);
+    }
+}
+
+/// Commands whose responses, or for a subscription its events, may be encoded with a codec other than JSON, advertised
+/// to clients in the handshake.
+const BINARY_COMMANDS: [&str; 2] = ["scanner_get_scan_stats", "events_subscribe"];
+
+/// Encodes the response for commands on hot paths (such as the live scan stats, which are polled throughout a scan)
+/// with the binary codec requested by the client.
+///
+/// # Returns
+///
+/// None if the client asked for JSON, or the command does not support a binary encoding; in which case the request
+/// should be handled by [`handle_ipc`].
+fn encode_binary_response(
+    command: &str,
+    codec: CodecKind,
+    file_scanner: &Arc<FileScanner>,
+) -> Option<std::io::Result<Vec<u8>>> {
+    if codec != Postcard::KIND {
+        return None;
+    }
+
+    match command {
+        "scanner_get_scan_stats" => Some(encode_tagged::<Postcard, _>(
+            &file_scanner.scanner_get_scan_data(),
+        )),
+        _ => None,
+    }
+}
+
+/// Encodes a JSON response. Clients which asked for JSON get an untagged response as they always have; a client which
+/// asked for another codec is told via the tag that the command was answered with JSON instead.
+fn encode_json_response(response: &Value, requested: CodecKind) -> std::io::Result<Vec<u8>> {
+    match requested {
+        CodecKind::Json => to_vec(response).map_err(std::io::Error::from),
+        _ => encode_tagged::<shared_std::codec::Json, _>(response),
+    }
+}
+
+/// Handles a command on a task of its own, so that where the handler panics the caller is answered with an internal
+/// error, rather than its connection or the rest of its batch being dropped.
+async fn handle_isolated(
+    command: &str,
+    handler: impl Future<Output = Option<Value>> + Send + 'static,
+) -> Option<Value> {
+    match tokio::spawn(handler).await {
+        Ok(response) => response,
+        Err(e) => Some(internal_error_response(command, e)),
+    }
+}
+
+/// The response to a command whose handler panicked or was cancelled. The panic itself is logged with its backtrace by
+/// the panic hook, so only which command it was is logged here.
+fn internal_error_response(command: &str, e: JoinError) -> Value {
+    let reason = if e.is_panic() {
+        let payload = e.into_panic();
+        payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string())
+    } else {
+        "handler was cancelled".to_string()
+    };
+
+    Log::new().log(
+        LogLevel::Error,
+        &format!("Internal error handling IPC command {command}. {reason}"),
+    );
+
+    to_value(CommandResponse {
+        status: "error".to_string(),
+        message: format!("Internal error in the engine handling {command}: {reason}"),
+    })
+    .unwrap()
+}
+
+/// Builds the response refusing a command which is only served to an administrator, from a client which is not
+/// running elevated
+fn elevation_required_response(command: &str) -> Value {
+    Log::new().log(
+        LogLevel::Warning,
+        &format!("Refused {command} from a client which is not running elevated."),
+    );
+
+    to_value(CommandResponse {
+        status: STATUS_PERMISSION_DENIED.to_string(),
+        message: format!("{command} may only be sent by a client running as an administrator."),
+    })
+    .unwrap()
+}
+
+/// The commands a client connecting as an observer may send, none of which change the state of the engine
+const OBSERVER_COMMANDS: [&str; 21] = [
+    "ipc_handshake",
+    "echo",
+    // each command of a batch is checked against this list on its own
+    "batch",
+    "events_subscribe",
+    "connections_list",
+    "scanner_check_page_state",
+    "scanner_current_event",
+    "scanner_get_scan_stats",
+    "scanner_results_page",
+    "scans_list",
+    "driver_get_state",
+    "driver_availability",
+    "driver_stats",
+    "realtime_status",
+    "version_info",
+    "resource_usage",
+    "verdict_policy",
+    "protection_stats",
+    "protection_score",
+    "threat_timeline",
+    "quarantine_status",
+];
+
+/// Commands answered with a scan done whilst the caller waits, which are served in the on-demand slots alongside any
+/// running scan
+const ON_DEMAND_COMMANDS: [&str; 15] = [
+    "scanner_scan_bytes",
+    "benchmark_scan",
+    "scanner_classify",
+    "scanner_explain",
+    "scanner_scan_autoruns",
+    "scanner_scan_browser_extensions",
+    "scanner_scan_drivers",
+    "drivers_list",
+    "scanner_scan_scheduled_tasks",
+    "scanner_scan_registry",
+    "scanner_inspect_process",
+    "scanner_rescan_detections",
+    "scanner_estimate",
+    "exclusions_preview",
+    "hash_file",
+];
+
+/// Commands which could put the machine at risk, such as by letting a sample out of quarantine or keeping scans from
+/// starting for an update, or ending an administrator's update early, so are only served to a client running as an
+/// administrator
+const ELEVATED_COMMANDS: [&str; 5] = [
+    "quarantine_extract",
+    "quarantine_restore",
+    "update_begin",
+    "update_commit",
+    "update_abort",
+];
+
+/// Whether the request may only be served to a client running as an administrator, being one of
+/// [`ELEVATED_COMMANDS`], or a change to the settings which changes the remote management settings, as they decide
+/// which remote consoles may manage the engine and what they may do
+async fn requires_elevation(request: &CommandRequest, settings: &Mutex<SanctumSettings>) -> bool {
+    if ELEVATED_COMMANDS.contains(&request.command.as_str()) {
+        return true;
+    }
+    if request.command != "settings_update_settings" {
+        return false;
+    }
+
+    // settings which cannot be read are answered with an error by the handler, whoever sent them
+    match request.args.clone().map(serde_json::from_value::<SanctumSettings>) {
+        Some(Ok(updated)) => updated.remote_management != settings.lock().await.remote_management,
+        _ => false,
+    }
+}
+
+/// Commands which check that protection is working, so must be answered however busy the engine is
+const CRITICAL_COMMANDS: [&str; 2] = ["self_test", "warmup"];
+
+/// Routes a `scanner_start_scan` of bytes to `scanner_scan_bytes`, as the content of a scan of bytes is streamed over
+/// the connection rather than the scan being run in the background. Any other request is returned as it is.
+fn route_streamed_scan(request: CommandRequest) -> CommandRequest {
+    if request.command != "scanner_start_scan" {
+        return request;
+    }
+
+    match request.args.clone().map(serde_json::from_value::<ScanTargetRequest>) {
+        Some(Ok(ScanTargetRequest {
+            target: ScanTarget::Bytes(name),
+            ..
+        })) => CommandRequest {
+            command: "scanner_scan_bytes".to_string(),
+            args: Some(Value::String(name)),
+            ..request
+        },
+        _ => request,
+    }
+}
+
+/// Gets the priority the command is served in the on-demand slots at, or None where it does not take a slot
+fn on_demand_priority(command: &str) -> Option<RequestPriority> {
+    if CRITICAL_COMMANDS.contains(&command) {
+        Some(RequestPriority::Critical)
+    } else if ON_DEMAND_COMMANDS.contains(&command) {
+        Some(RequestPriority::Normal)
+    } else {
+        None
+    }
+}
+
+/// An observer's connection, recorded in the audit log when it connects and when it disconnects, however the connection
+/// ends
+struct ObserverSession {
+    command: String,
+}
+
+impl ObserverSession {
+    fn start(command: &str) -> Self {
+        let session = ObserverSession {
+            command: command.to_string(),
+        };
+        session.record("observer_connected", format!("Observer connected, sending {command}."));
+
+        session
+    }
+
+    /// Records the change to the connection in the audit log, as well as the engine log
+    fn record(&self, action: &str, detail: String) {
+        let logger = Log::new();
+        logger.log(LogLevel::Info, &detail);
+
+        let record = AuditRecord {
+            at: SystemTime::now(),
+            action,
+            detail,
+            error: None,
+        };
+        if let Err(e) = audit::record(&record) {
+            logger.log(
+                LogLevel::Error,
+                &format!("Could not record the observer in the audit log. {}. {e}", record.detail),
+            );
+        }
+    }
+}
+
+impl Drop for ObserverSession {
+    fn drop(&mut self) {
+        self.record("observer_disconnected", format!("Observer disconnected after {}.", self.command));
+    }
+}
+
+/// Gives the request the role it is served with, which is the role of the connection it was sent on unless the request
+/// asks for the lesser role of an observer. The role a client sets in its request is otherwise ignored, so a client
+/// on an observer connection cannot claim to be a controller.
+fn bind_role(request: CommandRequest, connection_role: ConnectionRole) -> CommandRequest {
+    let role = match connection_role {
+        ConnectionRole::Observer => ConnectionRole::Observer,
+        ConnectionRole::Controller => request.role,
+    };
+
+    CommandRequest { role, ..request }
+}
+
+/// The response to an action command sent by an observer, which is logged as the observer should never send one
+fn permission_denied_response(command: &str) -> Value {
+    Log::new().log(
+        LogLevel::Warning,
+        &format!("Refused {command} from an observer connection, observers may only watch the engine."),
+    );
+
+    to_value(CommandResponse {
+        status: STATUS_PERMISSION_DENIED.to_string(),
+        message: format!("{command} is not permitted for an observer connection."),
+    })
+    .unwrap()
+}
+
+/// The response to a command which needs the driver whilst the engine is running without it
+fn driver_unavailable_response(reason: &str) -> Value {
+    to_value(CommandResponse {
+        status: STATUS_DRIVER_UNAVAILABLE.to_string(),
+        message: reason.to_string(),
+    })
+    .unwrap()
+}
+
+/// Gets the status of a response for tracing; a `CommandResponse` carries its own status, anything else is a result
+fn response_status(response: &Value) -> &str {
+    response
+        .get("status")
+        .and_then(|s| s.as_str())
+        .unwrap_or("ok")
+}
+
+/// Makes a change to the live settings, validating and applying it to the scanner before persisting it. If the
+/// change is invalid or cannot be saved, the live settings are left unchanged.
+///
+/// # Args
+///
+/// - `update`: Makes the change to a copy of the settings, returning the message to report on success
+async fn update_settings(
+    settings: &Mutex<SanctumSettings>,
+    file_scanner: &FileScanner,
+    update: impl FnOnce(&mut SanctumSettings) -> String,
+) -> CommandResponse {
+    let mut lock = settings.lock().await;
+    let mut updated = lock.clone();
+    let message = update(&mut updated);
+
+    if let Err(e) = file_scanner.apply_settings(&updated) {
+        return CommandResponse {
+            status: "error".to_string(),
+            message: e,
+        };
+    }
+
+    match persist_settings(&updated).await {
+        Ok(_) => {
+            *lock = updated;
+            CommandResponse {
+                status: "success".to_string(),
+                message,
+            }
+        }
+        Err(e) => {
+            // keep the scanner in step with the settings which are still live
+            let _ = file_scanner.apply_settings(&lock);
+            CommandResponse {
+                status: "error".to_string(),
+                message: format!("Error saving settings. {e}"),
+            }
+        }
+    }
+}
+
+/// Writes the settings to the config file on disk
+async fn persist_settings(settings: &SanctumSettings) -> std::io::Result<()> {
+    let settings_str = serde_json::to_string(settings).unwrap();
+    let path = get_setting_paths(&get_logged_in_username().unwrap()).1;
+    fs::write(path, settings_str).await
+}
+
 /// IPC logic handler, this function accepts a request and an Arc of UmEngine which matches on a
 /// string based command to decide on what to do, this is considered the heart of the tasking of the
 /// engine where its come from the GUI, or even other sources which may feed in via IPC (such as injected
@@ -165,29 +1229,501 @@ pub async fn handle_ipc(
     core: Arc<Core>,
     file_scanner: Arc<FileScanner>,
     driver_manager: Arc<Mutex<SanctumDriverManager>>,
+    history: Arc<ScanHistory>,
+    quarantine: Arc<QuarantineStore>,
+    realtime_snooze: Arc<RealtimeSnooze>,
+    connections: Arc<ConnectionRegistry>,
 ) -> Option<Value> {
     let response: Value = match request.command.as_str() {
+        //
+        // Connection / protocol
+        //
+        "ipc_handshake" => to_value(IpcHandshake {
+            codecs: vec![CodecKind::Json, CodecKind::Postcard],
+            binary_commands: BINARY_COMMANDS.iter().map(|c| c.to_string()).collect(),
+            role: request.role,
+            observer_commands: OBSERVER_COMMANDS.iter().map(|c| c.to_string()).collect(),
+            max_concurrent_connections: IPC_MAX_CONCURRENT_CONNECTIONS,
+            supports_streaming: true,
+            max_request_len: IPC_MAX_REQUEST_LEN,
+            max_batch_len: IPC_MAX_BATCH_LEN,
+        })
+        .unwrap(),
+        // the args are returned unchanged without touching any state, to test the round trip over the pipe; the
+        // payload is bounded by the size of a request, as any other args are
+        "echo" => request.args.unwrap_or(Value::Null),
+        "connections_list" => to_value(connections.list()).unwrap(),
+
         //
         // Scanner IPC requests
         //
         "scanner_check_page_state" => to_value(file_scanner.get_state()).unwrap(),
+        "scanner_current_event" => to_value(file_scanner.current_event()).unwrap(),
         "scanner_get_scan_stats" => to_value(file_scanner.scanner_get_scan_data()).unwrap(),
+        "scanner_results_page" => match request.args.map(serde_json::from_value::<ResultsPageRequest>) {
+            Some(Ok(page)) => to_value(file_scanner.results_page(&page)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No scan and page passed to get the results of".to_string(),
+            })
+            .unwrap(),
+        },
         "scanner_cancel_scan" => {
-            file_scanner.cancel_scan();
-            to_value("").unwrap()
+            // every scan is given an ID when it starts
+            let result = file_scanner
+                .cancel_scan()
+                .map(|sli| sli.scan_id.unwrap_or_default())
+                .ok_or_else(|| "No scan is in progress to cancel.".to_string());
+            to_value(result).unwrap()
         }
-        "scanner_start_folder_scan" => {
-            if let Some(args) = request.args {
-                let target: Vec<PathBuf> = serde_json::from_value(args).unwrap();
-                to_value(file_scanner.start_scan(target)).unwrap()
-            } else {
-                to_value(CommandResponse {
-                    status: "error".to_string(),
-                    message: "No path passed to scanner".to_string(),
+        "scans_list" => to_value(file_scanner.scans_list()).unwrap(),
+        "scans_cancel" => match request.args.map(serde_json::from_value::<u64>) {
+            Some(Ok(scan_id)) => to_value(file_scanner.cancel_scan_by_id(scan_id)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No scan ID passed to cancel".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_cancel_by_target" => match request.args.map(serde_json::from_value::<PathBuf>) {
+            Some(Ok(path)) => {
+                let scan_ids = file_scanner.cancel_scans_by_target(&path);
+                to_value(CancelledByTarget {
+                    cancelled: scan_ids.len(),
+                    scan_ids,
                 })
                 .unwrap()
             }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No target passed to cancel the scans of".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_reset" => match file_scanner.reset() {
+            Ok(_) => to_value(CommandResponse {
+                status: "success".to_string(),
+                message: "Scanner state reset.".to_string(),
+            })
+            .unwrap(),
+            Err(e) => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: e,
+            })
+            .unwrap(),
+        },
+        "scanner_start_folder_scan" => match request.args.map(serde_json::from_value::<ScanRequest>) {
+            Some(Ok(scan)) => {
+                if let Err(e) = file_scanner.check_options(&scan.options) {
+                    return Some(to_value(Err::<ScanStarted, String>(e)).unwrap());
+                }
+
+                // the scan is run in the background so the caller learns the scan ID straight away
+                let result: Result<ScanStarted, String> = match file_scanner.prepare_scan(&scan.targets) {
+                    Some(started) => {
+                        let scanner = Arc::clone(&file_scanner);
+                        tokio::task::spawn_blocking(move || scanner.run_scan(scan.targets, scan.options));
+                        Ok(started)
+                    }
+                    None => Err(file_scanner.scan_refused()),
+                };
+
+                to_value(result).unwrap()
+            }
+            Some(Err(e)) => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: format!("Invalid scan passed to scanner. {e}"),
+            })
+            .unwrap(),
+            None => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No path passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_start_scan" => match request.args.map(serde_json::from_value::<ScanTargetRequest>) {
+            Some(Ok(scan)) => {
+                if let Err(e) = file_scanner.check_options(&scan.options) {
+                    return Some(to_value(Err::<ScanStarted, String>(e)).unwrap());
+                }
+
+                // preparing may block, such as whilst an image is mounted, so is done off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let target = scan.target.clone();
+                let prepared = tokio::task::spawn_blocking(move || scanner.prepare_target_scan(&target))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Starting the scan failed. {e}")));
+
+                // the walk runs in the background, and any image mounted for it is unmounted when it ends
+                let result = prepared.map(|(started, mounted)| {
+                    let scanner = Arc::clone(&file_scanner);
+                    let targets = started.targets.clone();
+                    tokio::task::spawn_blocking(move || {
+                        scanner.run_scan(targets, scan.options);
+                        drop(mounted);
+                    });
+                    started
+                });
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No target passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_start_scan_with_estimate" => match request.args.map(serde_json::from_value::<ScanRequest>) {
+            Some(Ok(scan)) => {
+                if let Err(e) = file_scanner.check_options(&scan.options) {
+                    return Some(to_value(Err::<ScanStarted, String>(e)).unwrap());
+                }
+
+                // the estimate and the scan run in the background, so the caller can cancel either by the scan ID
+                let result: Result<ScanStarted, String> = match file_scanner.prepare_scan_with_estimate(&scan.targets) {
+                    Some(started) => {
+                        let scanner = Arc::clone(&file_scanner);
+                        let for_scan = started.clone();
+                        tokio::task::spawn_blocking(move || scanner.run_scan_with_estimate(for_scan, scan.options));
+                        Ok(started)
+                    }
+                    None => Err(file_scanner.scan_refused()),
+                };
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No path passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_scan_volume" => match request.args.map(serde_json::from_value::<VolumeScanRequest>) {
+            Some(Ok(scan)) => {
+                if let Err(e) = file_scanner.check_options(&scan.options) {
+                    return Some(to_value(Err::<ScanStarted, String>(e)).unwrap());
+                }
+
+                // as with a folder scan, the walk runs in the background once the scan has been accepted
+                let result = file_scanner.prepare_volume_scan(&scan.drive).inspect(|started| {
+                    let scanner = Arc::clone(&file_scanner);
+                    let targets = started.targets.clone();
+                    tokio::task::spawn_blocking(move || scanner.run_scan(targets, scan.options));
+                });
+
+                to_value(result).unwrap()
+            }
+            Some(Err(e)) => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: format!("Invalid volume scan passed to scanner. {e}"),
+            })
+            .unwrap(),
+            None => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No drive letter passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_scan_image" => match request.args.map(serde_json::from_value::<ImageScanRequest>) {
+            Some(Ok(scan)) => {
+                if let Err(e) = file_scanner.check_options(&scan.options) {
+                    return Some(to_value(Err::<ScanStarted, String>(e)).unwrap());
+                }
+
+                // mounting waits on Windows to bring the image's volumes online, so is done off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let image = scan.image.clone();
+                let prepared = tokio::task::spawn_blocking(move || scanner.prepare_image_scan(&image))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Mounting the image failed. {e}")));
+
+                // the walk runs in the background, and the image is unmounted when it ends however it ends
+                let result = prepared.map(|(started, mounted)| {
+                    let scanner = Arc::clone(&file_scanner);
+                    let targets = started.targets.clone();
+                    tokio::task::spawn_blocking(move || {
+                        scanner.run_scan(targets, scan.options);
+                        drop(mounted);
+                    });
+                    started
+                });
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No image passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_scan_autoruns" => {
+            // unlike the other scans, the caller waits for the results, as only the binaries started from the autorun
+            // locations are scanned
+            let scanner = Arc::clone(&file_scanner);
+            let result = tokio::task::spawn_blocking(move || scanner.scan_autoruns())
+                .await
+                .unwrap_or_else(|e| Err(format!("Autoruns scan failed. {e}")));
+
+            to_value(result).unwrap()
+        }
+        "scanner_scan_browser_extensions" => {
+            // a scan of part of the system, which the caller waits on for its results, as only the files of the
+            // installed extensions are scanned
+            let scanner = Arc::clone(&file_scanner);
+            let result = tokio::task::spawn_blocking(move || scanner.scan_browser_extensions())
+                .await
+                .unwrap_or_else(|e| Err(format!("Browser extensions scan failed. {e}")));
+
+            to_value(result).unwrap()
+        }
+        "scanner_scan_drivers" => {
+            // a scan of part of the system, which the caller waits on for its results, as only the files the loaded
+            // drivers were loaded from are scanned
+            let scanner = Arc::clone(&file_scanner);
+            let result = tokio::task::spawn_blocking(move || scanner.scan_drivers())
+                .await
+                .unwrap_or_else(|e| Err(format!("Drivers scan failed. {e}")));
+
+            to_value(result).unwrap()
+        }
+        "drivers_list" => {
+            // the signature of each driver's file is verified, so this is run off of the runtime
+            let scanner = Arc::clone(&file_scanner);
+            let result = tokio::task::spawn_blocking(move || scanner.list_drivers())
+                .await
+                .unwrap_or_else(|e| Err(format!("Could not list the loaded drivers. {e}")));
+
+            to_value(result).unwrap()
+        }
+        "scanner_scan_scheduled_tasks" => {
+            // a scan of part of the system, which the caller waits on for its results, as only the files run by the
+            // registered tasks are scanned
+            let scanner = Arc::clone(&file_scanner);
+            let result = tokio::task::spawn_blocking(move || scanner.scan_scheduled_tasks())
+                .await
+                .unwrap_or_else(|e| Err(format!("Scheduled tasks scan failed. {e}")));
+
+            to_value(result).unwrap()
+        }
+        "scanner_scan_registry" => match request.args.map(serde_json::from_value::<RegistryScanRequest>) {
+            Some(Ok(registry_request)) => {
+                // a scan of part of the system, which the caller waits on for its results, as only the one subtree is
+                // walked
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.scan_registry(registry_request))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Registry scan failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No registry key passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_inspect_process" => match request.args.map(serde_json::from_value::<u32>) {
+            Some(Ok(pid)) => {
+                // the whole address space of the process is walked, so the caller waits off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.inspect_process_memory(pid))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Process memory inspection failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No process ID passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_classify" => match request.args.map(serde_json::from_value::<PathBuf>) {
+            Some(Ok(path)) => {
+                // as with hashing, the file is read whilst the caller waits, off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.classify_file(&path))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Classification failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No file passed to classify".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_explain" => match request.args.map(serde_json::from_value::<PathBuf>) {
+            Some(Ok(path)) => {
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.explain_file(&path))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Explaining the file failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No file passed to explain".to_string(),
+            })
+            .unwrap(),
+        },
+        "benchmark_scan" => match request.args.map(serde_json::from_value::<BenchmarkRequest>) {
+            Some(Ok(benchmark)) => {
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.benchmark_scan(&benchmark))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Benchmark failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No benchmark passed to run".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_rescan_detections" => match request.args.map(serde_json::from_value::<u64>) {
+            Some(Ok(scan_id)) => {
+                // only the files detected by the earlier scan are scanned, so the caller waits on it for the results
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.rescan_detections(scan_id))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Rescan failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No scan ID passed to scanner".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_estimate" => match request.args.map(serde_json::from_value::<Vec<PathBuf>>) {
+            Some(Ok(targets)) => {
+                // the walk can take a while on a large tree, so is run off of the runtime whilst the caller waits
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.estimate_scan(&targets))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Scan estimate failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No paths passed to estimate".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_cancel_estimate" => {
+            file_scanner.cancel_estimate();
+            to_value("").unwrap()
+        }
+        "scanner_detection_details" => match request.args.map(serde_json::from_value::<PathBuf>) {
+            Some(Ok(file)) => to_value(file_scanner.detection_details(&file)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No file passed to get the detection details of".to_string(),
+            })
+            .unwrap(),
+        },
+        "simulate_detection" => match request.args.map(serde_json::from_value::<SimulatedDetectionRequest>) {
+            Some(Ok(simulated)) => to_value(file_scanner.simulate_detection(&simulated)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No detection passed to simulate".to_string(),
+            })
+            .unwrap(),
+        },
+        "detection_annotate" => match request.args.map(serde_json::from_value::<DetectionAnnotationRequest>) {
+            Some(Ok(annotation)) => to_value(history.annotate(annotation)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No detection passed to annotate".to_string(),
+            })
+            .unwrap(),
+        },
+        "detection_recent" => {
+            // with no status given, detections of every status are returned
+            let status = request
+                .args
+                .and_then(|args| serde_json::from_value::<Option<TriageStatus>>(args).ok())
+                .flatten();
+
+            to_value(history.recent_detections(status, RECENT_DETECTIONS_LIMIT)).unwrap()
         }
+        "protection_stats" => {
+            // with no period given, the stats are of the last month
+            let period = request
+                .args
+                .and_then(|args| serde_json::from_value::<Option<StatsPeriod>>(args).ok())
+                .flatten()
+                .unwrap_or_default();
+
+            to_value(history.protection_stats(period)).unwrap()
+        }
+        "protection_score" => to_value(
+            protection_score(&file_scanner, &driver_manager, &history, &settings, &realtime_snooze).await,
+        )
+        .unwrap(),
+        "threat_timeline" => match request.args.map(serde_json::from_value::<String>) {
+            Some(Ok(hash)) => to_value(history.threat_timeline(&hash)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No hash passed to threat_timeline".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_diff" => match request.args.map(serde_json::from_value::<ScanDiffRequest>) {
+            Some(Ok(diff)) => to_value(history.diff(&diff)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No scans passed to compare".to_string(),
+            })
+            .unwrap(),
+        },
+        "scanner_export_results" => match request.args.map(serde_json::from_value::<ScanExportRequest>) {
+            Some(Ok(export)) => {
+                // a large scan takes a while to serialise and compress, so is written off of the runtime
+                let history = Arc::clone(&history);
+                let result = tokio::task::spawn_blocking(move || export_scan(&history, &export))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Export failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No scan passed to export".to_string(),
+            })
+            .unwrap(),
+        },
+        "exclusions_test" => match request.args.map(serde_json::from_value::<PathBuf>) {
+            Some(Ok(path)) => to_value(file_scanner.test_exclusion(&path)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No path passed to test against the exclusions".to_string(),
+            })
+            .unwrap(),
+        },
+        "exclusions_preview" => match request.args.map(serde_json::from_value::<ExclusionPreviewRequest>) {
+            Some(Ok(preview)) => {
+                // the scan's folders are walked, which can take a while, so as with an estimate this is run off of
+                // the runtime whilst the caller waits
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.preview_exclusion(&preview))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Exclusion preview failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No exclusion and scan passed to preview".to_string(),
+            })
+            .unwrap(),
+        },
         "settings_get_common_scan_areas" => to_value({
             let lock = settings.lock().await;
             lock.common_scan_areas.clone()
@@ -201,10 +1737,50 @@ pub async fn handle_ipc(
             let res = settings.lock().await.clone();
             to_value(res).unwrap()
         }
+        "config_effective" => to_value(effective_config(&*settings.lock().await)).unwrap(),
+        "config_reload" => {
+            // the lock is held throughout so no change to the settings can land between the file being read and the
+            // settings in use being replaced with it
+            let mut lock = settings.lock().await;
+
+            let result = reload_settings(&lock).and_then(|(reloaded, report)| {
+                file_scanner.apply_settings(&reloaded)?;
+                *lock = reloaded;
+                Ok(report)
+            });
+
+            match &result {
+                Ok(report) => Log::new().log(
+                    LogLevel::Info,
+                    &format!(
+                        "Reloaded the settings file, applying {:?}, with {:?} taking effect once the engine restarts.",
+                        report.applied, report.restart_required
+                    ),
+                ),
+                Err(e) => Log::new().log(LogLevel::Warning, &format!("Did not reload the settings file. {e}")),
+            }
+
+            to_value(result).unwrap()
+        }
         "settings_update_settings" => {
             if let Some(args) = request.args {
                 let settings_local: SanctumSettings = serde_json::from_value(args).unwrap();
 
+                // reject invalid settings now, rather than them silently not applying at scan time
+                if let Err(e) = validate_siem_settings(&settings_local.siem)
+                    .and_then(|_| validate_remote_settings(&settings_local.remote_management))
+                    .and_then(|_| validate_webhook_settings(&settings_local.webhook))
+                    .and_then(|_| file_scanner.apply_settings(&settings_local))
+                {
+                    return Some(
+                        to_value(CommandResponse {
+                            status: "error".to_string(),
+                            message: e,
+                        })
+                        .unwrap(),
+                    );
+                }
+
                 {
                     // change the live state
                     let mut lock = settings.lock().await;
@@ -212,10 +1788,7 @@ pub async fn handle_ipc(
                 }
 
                 // write the new file
-                let settings_str = serde_json::to_string(&settings_local).unwrap();
-                let path = get_setting_paths(&get_logged_in_username().unwrap()).1;
-                let res = fs::write(path, settings_str).await;
-                match res {
+                match persist_settings(&settings_local).await {
                     Ok(_) => to_value("").unwrap(),
                     Err(e) => to_value(CommandResponse {
                         status: "error".to_string(),
@@ -232,30 +1805,80 @@ pub async fn handle_ipc(
             }
         }
 
+        "detection_engines_get" => to_value(file_scanner.detection_engines()).unwrap(),
+        "detection_engines_set" => match request.args.map(serde_json::from_value::<EngineFlags>) {
+            Some(Ok(engines)) => {
+                let response = update_settings(&settings, &file_scanner, |s| {
+                    s.detection_engines = engines;
+                    format!("Detection engines set to {:#x}.", engines.0)
+                })
+                .await;
+
+                to_value(response).unwrap()
+            }
+            Some(Err(e)) => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: format!("Invalid engine flags passed to engine. {e}"),
+            })
+            .unwrap(),
+            None => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No engine flags passed to engine".to_string(),
+            })
+            .unwrap(),
+        },
+        "verdict_policy" => to_value(file_scanner.verdict_policy()).unwrap(),
+        "exclusions_add" => match request.args.map(serde_json::from_value::<ExclusionRule>) {
+            Some(Ok(rule)) => {
+                let response = update_settings(&settings, &file_scanner, |s| {
+                    s.exclusions.rules.push(rule);
+                    "Exclusion added.".to_string()
+                })
+                .await;
+
+                to_value(response).unwrap()
+            }
+            Some(Err(e)) => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: format!("Invalid exclusion passed to engine. {e}"),
+            })
+            .unwrap(),
+            None => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No exclusion passed to engine".to_string(),
+            })
+            .unwrap(),
+        },
+
         //
         // Driver control from GUI
         //
+        // the driver is probed after each change so the engine leaves or enters degraded mode straight away
         "driver_install_driver" => to_value({
             let mut lock = driver_manager.lock().await;
             lock.install_driver();
+            lock.probe_availability();
             lock.get_state()
         })
         .unwrap(),
         "driver_uninstall_driver" => to_value({
             let mut lock = driver_manager.lock().await;
             lock.uninstall_driver();
+            lock.probe_availability();
             lock.get_state()
         })
         .unwrap(),
         "driver_start_driver" => to_value({
             let mut lock = driver_manager.lock().await;
             lock.start_driver();
+            lock.probe_availability();
             lock.get_state()
         })
         .unwrap(),
         "driver_stop_driver" => to_value({
             let mut lock = driver_manager.lock().await;
             lock.stop_driver();
+            lock.probe_availability();
             lock.get_state()
         })
         .unwrap(),
@@ -264,6 +1887,364 @@ pub async fn handle_ipc(
             lock.get_state()
         })
         .unwrap(),
+        "driver_availability" => to_value(driver_manager.lock().await.get_availability()).unwrap(),
+        "driver_stats" => to_value(core.driver_stats(&driver_manager).await).unwrap(),
+        "realtime_status" => to_value(RealtimeStatus {
+            policy: settings.lock().await.realtime,
+            driver: driver_manager.lock().await.get_availability(),
+            snooze_remaining: realtime_snooze.remaining(),
+        })
+        .unwrap(),
+        "realtime_snooze" => match request.args.map(serde_json::from_value::<u64>) {
+            Some(Ok(secs)) => to_value(realtime_snooze.snooze(Duration::from_secs(secs))).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No duration passed to snooze real-time protection for".to_string(),
+            })
+            .unwrap(),
+        },
+        "realtime_resume" => to_value(realtime_snooze.resume()).unwrap(),
+
+        //
+        // Diagnostics
+        //
+        "self_test" => to_value(run_self_test(&file_scanner, &driver_manager).await).unwrap(),
+        "warmup" => to_value(warmup(&file_scanner, &driver_manager).await).unwrap(),
+        "export_diagnostics" => match request.args.map(serde_json::from_value::<DiagnosticsExportRequest>) {
+            Some(Ok(export)) => {
+                let bundle = DiagnosticsBundle {
+                    versions: version_info(&file_scanner, &driver_manager).await,
+                    self_test: run_self_test(&file_scanner, &driver_manager).await,
+                    settings: settings.lock().await.clone(),
+                    effective_config: effective_config(&*settings.lock().await),
+                    recent_scans: history.recent(BUNDLE_RECENT_SCANS),
+                };
+
+                // the log is read and redacted, and the archive compressed, off of the runtime
+                let result = tokio::task::spawn_blocking(move || export_diagnostics(&export, &bundle))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Diagnostics export failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No destination passed for the diagnostics bundle".to_string(),
+            })
+            .unwrap(),
+        },
+        "logs_rotate" => to_value(rotate_log().map_err(|e| format!("Could not rotate the log. {e}"))).unwrap(),
+        "maintenance_run" => to_value({
+            let retention = settings.lock().await.maintenance.clone();
+            run_maintenance(&history, &quarantine, &retention)
+        })
+        .unwrap(),
+        "quarantine_verify" => {
+            // without args the quarantine is only checked, and nothing is repaired
+            let repair = request
+                .args
+                .and_then(|args| serde_json::from_value::<bool>(args).ok())
+                .unwrap_or(false);
+
+            // every quarantined file is hashed, so this is done off of the runtime
+            let quarantine = Arc::clone(&quarantine);
+            let result = tokio::task::spawn_blocking(move || quarantine.verify(repair))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r.map_err(|e| e.to_string()))
+                .map_err(|e| format!("Could not verify the quarantine. {e}"));
+
+            to_value(result).unwrap()
+        }
+        "quarantine_status" => match request.args.map(serde_json::from_value::<PathBuf>) {
+            Some(Ok(path)) => to_value(quarantine.status(&path)).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No path passed to quarantine_status".to_string(),
+            })
+            .unwrap(),
+        },
+        "quarantine_extract" => match request.args.map(serde_json::from_value::<QuarantineExtractRequest>) {
+            Some(Ok(extract)) => {
+                // the sample is copied and hashed, so this is done off of the runtime
+                let quarantine = Arc::clone(&quarantine);
+                let result = tokio::task::spawn_blocking(move || {
+                    let extracted = quarantine.extract(&extract.entry_id, &extract.dest);
+
+                    // an extraction which cannot be recorded in the audit log is undone, so that none goes unrecorded
+                    let record = AuditRecord {
+                        at: SystemTime::now(),
+                        action: "quarantine_extract",
+                        detail: match &extracted {
+                            Ok(extraction) => format!(
+                                "Extracted {} (entry {}) to {}",
+                                extraction.entry.original_path.display(),
+                                extraction.entry.id,
+                                extraction.path.display()
+                            ),
+                            Err(_) => format!("Extract entry {} to {}", extract.entry_id, extract.dest.display()),
+                        },
+                        error: extracted.as_ref().err().map(|e| e.to_string()),
+                    };
+                    match (audit::record(&record), extracted) {
+                        (Ok(_), extracted) => extracted.map_err(|e| e.to_string()),
+                        (Err(e), Ok(extraction)) => {
+                            let _ = std::fs::remove_file(&extraction.path);
+                            Err(format!("The extraction could not be recorded in the audit log, so was undone. {e}"))
+                        }
+                        (Err(_), Err(e)) => Err(e.to_string()),
+                    }
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r)
+                .map_err(|e| format!("Could not extract the quarantined file. {e}"));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No quarantine entry passed to extract".to_string(),
+            })
+            .unwrap(),
+        },
+        "quarantine_restore" => match request.args.map(serde_json::from_value::<QuarantineRestoreRequest>) {
+            Some(Ok(restore)) => {
+                // the file is copied back and hashed, so this is done off of the runtime
+                let quarantine = Arc::clone(&quarantine);
+                let result = tokio::task::spawn_blocking(move || {
+                    let restored = quarantine.restore(&restore.entry_id, restore.conflict_policy);
+
+                    // unlike an extraction, a restore cannot be undone once its entry is gone, so one which cannot be
+                    // recorded in the audit log is logged instead
+                    let record = AuditRecord {
+                        at: SystemTime::now(),
+                        action: "quarantine_restore",
+                        detail: match &restored {
+                            Ok(restoration) => format!(
+                                "Restored {} (entry {}) to {}{}",
+                                restoration.entry.original_path.display(),
+                                restoration.entry.id,
+                                restoration.path.display(),
+                                match (&restoration.conflict, &restoration.existing_renamed_to) {
+                                    (Some(_), Some(renamed)) => {
+                                        format!(", renaming the file there to {}", renamed.display())
+                                    }
+                                    (Some(policy), None) => format!(", where the path was taken ({policy:?})"),
+                                    (None, _) => String::new(),
+                                }
+                            ),
+                            Err(_) => format!(
+                                "Restore entry {} ({:?} where the path is taken)",
+                                restore.entry_id, restore.conflict_policy
+                            ),
+                        },
+                        error: restored.as_ref().err().map(|e| e.to_string()),
+                    };
+                    if let Err(e) = audit::record(&record) {
+                        Log::new().log(
+                            LogLevel::Error,
+                            &format!("Could not record the restore in the audit log. {}. {e}", record.detail),
+                        );
+                    }
+
+                    restored.map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r)
+                .map_err(|e| format!("Could not restore the quarantined file. {e}"));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No quarantine entry passed to restore".to_string(),
+            })
+            .unwrap(),
+        },
+        "quarantine_scan_detections" => match request.args.map(serde_json::from_value::<u64>) {
+            Some(Ok(scan_id)) => {
+                // every detected file is moved and hashed, so this is done off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || {
+                    let batch = scanner.quarantine_scan_detections(scan_id);
+
+                    // the batch is recorded as one event listing each file quarantined; as with a restore, the files
+                    // are not put back where it cannot be recorded, so that is logged instead
+                    let record = AuditRecord {
+                        at: SystemTime::now(),
+                        action: "quarantine_scan_detections",
+                        detail: match &batch {
+                            Ok(batch) => {
+                                let files: Vec<String> = batch
+                                    .items
+                                    .iter()
+                                    .filter_map(|item| match &item.outcome {
+                                        BatchQuarantineOutcome::Quarantined { id } => {
+                                            Some(format!("{} (entry {id})", item.file.display()))
+                                        }
+                                        _ => None,
+                                    })
+                                    .collect();
+                                format!(
+                                    "Quarantined {} of {} detections of scan {scan_id}, skipping {} and failing {}: {}",
+                                    batch.quarantined,
+                                    batch.items.len(),
+                                    batch.skipped,
+                                    batch.failed,
+                                    files.join(", ")
+                                )
+                            }
+                            Err(_) => format!("Quarantine the detections of scan {scan_id}"),
+                        },
+                        error: batch.as_ref().err().cloned(),
+                    };
+                    if let Err(e) = audit::record(&record) {
+                        Log::new().log(
+                            LogLevel::Error,
+                            &format!("Could not record the batch quarantine in the audit log. {}. {e}", record.detail),
+                        );
+                    }
+
+                    batch
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r)
+                .map_err(|e| format!("Could not quarantine the detections of the scan. {e}"));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No scan ID passed to quarantine".to_string(),
+            })
+            .unwrap(),
+        },
+        "stop_all" => to_value({
+            // only one scan runs at a time, so cancelling it stops every scan; calling this when nothing is running
+            // does nothing other than record the request
+            let scans_cancelled: Vec<u64> = file_scanner
+                .cancel_scan_with_reason(CANCEL_REASON_STOP_ALL)
+                .and_then(|sli| sli.scan_id)
+                .into_iter()
+                .collect();
+
+            Log::new().log(
+                LogLevel::Warning,
+                &format!("Stop all requested. Scans cancelled: {scans_cancelled:?}."),
+            );
+
+            StopAllSummary { scans_cancelled }
+        })
+        .unwrap(),
+
+        //
+        // Version info
+        //
+        "signatures_reload" => to_value(file_scanner.reload_signatures().await).unwrap(),
+        "update_begin" => to_value(file_scanner.update_begin().await).unwrap(),
+        "update_commit" => match request.args.map(serde_json::from_value::<UpdateCommitRequest>) {
+            Some(Ok(commit)) => to_value(file_scanner.update_commit(commit.restart_required).await).unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No update commit passed to engine".to_string(),
+            })
+            .unwrap(),
+        },
+        "update_abort" => to_value(file_scanner.update_abort()).unwrap(),
+        "signatures_import" => match request.args.map(serde_json::from_value::<SignatureImportRequest>) {
+            Some(Ok(import)) => {
+                to_value(file_scanner.import_signatures(import.format, &import.path).await).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No file passed to import signatures from".to_string(),
+            })
+            .unwrap(),
+        },
+        "signatures_export" => match request.args.map(serde_json::from_value::<SignatureExportRequest>) {
+            Some(Ok(export)) => {
+                // every signature is written out, so this is done off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.export_signatures(&export.dest))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Signature export failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No destination passed for the signature export".to_string(),
+            })
+            .unwrap(),
+        },
+        "signatures_validate" => match request.args.map(serde_json::from_value::<SignatureValidationRequest>) {
+            Some(Ok(validate)) => {
+                // the whole file is read, so this is done off of the runtime
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || scanner.validate_signatures(&validate.path))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Signature validation failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No file passed to validate signatures in".to_string(),
+            })
+            .unwrap(),
+        },
+        "signatures_set_database_enabled" => match request.args.map(serde_json::from_value::<SignatureDatabaseToggle>) {
+            // enabling only removes the name from those disabled, so is allowed for a database which is no longer
+            // loaded; a database must be loaded to be disabled, so a mistyped name is not saved to the settings
+            Some(Ok(toggle)) if toggle.enabled || file_scanner.has_signature_database(&toggle.name) => {
+                let response = update_settings(&settings, &file_scanner, |s| {
+                    s.disabled_signature_databases.retain(|name| *name != toggle.name);
+                    if toggle.enabled {
+                        format!("Signature database {} enabled.", toggle.name)
+                    } else {
+                        s.disabled_signature_databases.push(toggle.name.clone());
+                        format!("Signature database {} disabled.", toggle.name)
+                    }
+                })
+                .await;
+
+                to_value(response).unwrap()
+            }
+            Some(Ok(toggle)) => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: format!("No signature database named {} is loaded.", toggle.name),
+            })
+            .unwrap(),
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No signature database passed to engine".to_string(),
+            })
+            .unwrap(),
+        },
+        "hash_file" => match request.args.map(serde_json::from_value::<HashRequest>) {
+            Some(Ok(hash)) => {
+                let scanner = Arc::clone(&file_scanner);
+                let result = tokio::task::spawn_blocking(move || {
+                    scanner
+                        .hash_file(&hash)
+                        .map_err(|e| format!("Could not hash {}. {e}", hash.path.display()))
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("Hashing the file failed. {e}")));
+
+                to_value(result).unwrap()
+            }
+            _ => to_value(CommandResponse {
+                status: "error".to_string(),
+                message: "No file passed to hash".to_string(),
+            })
+            .unwrap(),
+        },
+        "version_info" => to_value(version_info(&file_scanner, &driver_manager).await).unwrap(),
+        "resource_usage" => to_value(resource_usage(&file_scanner).await).unwrap(),
 
         //
         // Processes page in driver
@@ -320,14 +2301,19 @@ pub async fn handle_ipc(
         // **NOTE** Do NOT use this for future work; any driver comms should take place in the core module. This is here
         // as a demonstration incase I want to do it again in the future for some weird edge case.
         //
-        "ioctl_ping_driver" => to_value({
+        "ioctl_ping_driver" => {
             let mut lock = driver_manager.lock().await;
-            lock.ioctl_ping_driver()
-        })
-        .unwrap(),
-        "driver_collect_knl_dbg_msg" => {
-            to_value({ core.get_cached_driver_messages().await }).unwrap()
+            match lock.get_availability() {
+                DriverAvailability::Available => to_value(lock.ioctl_ping_driver()).unwrap(),
+                DriverAvailability::Unavailable { reason } => driver_unavailable_response(&reason),
+            }
         }
+        "driver_collect_knl_dbg_msg" => match driver_manager.lock().await.get_availability() {
+            DriverAvailability::Available => {
+                to_value({ core.get_cached_driver_messages().await }).unwrap()
+            }
+            DriverAvailability::Unavailable { reason } => driver_unavailable_response(&reason),
+        },
 
         //
         // Unhandled requests
@@ -341,3 +2327,257 @@ pub async fn handle_ipc(
 
     Some(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+    use shared_std::{
+        connections::ConnectionInfo,
+        events::{EngineEvent, EngineEventEnvelope},
+    };
+    use tokio::io::{DuplexStream, duplex};
+
+    use super::*;
+    use crate::{
+        driver_manager::driver_manager::tests::without_driver,
+        filescanner::filescanner::tests::{scanner, test_dir},
+        settings::default_settings,
+    };
+
+    /// The engine state shared by the connections of a test, which keeps its files in the folder
+    fn state(dir: &Path) -> IpcState {
+        let events = Arc::new(EventBus::new());
+
+        IpcState {
+            settings: Arc::new(Mutex::new(default_settings("test"))),
+            core: Arc::new(Core::from(500)),
+            file_scanner: Arc::new(scanner(dir, &[])),
+            driver_manager: Arc::new(Mutex::new(without_driver())),
+            realtime_snooze: Arc::new(RealtimeSnooze::new(Arc::clone(&events))),
+            events,
+            history: Arc::new(ScanHistory::load_from(dir)),
+            quarantine: Arc::new(QuarantineStore::load_from(dir)),
+            trace: Arc::new(IpcTrace::from_env()),
+            connections: Arc::new(ConnectionRegistry::default()),
+        }
+    }
+
+    /// Connects a client, whose connection is served on a task of its own as the server serves each pipe instance
+    fn connect(state: &IpcState) -> DuplexStream {
+        connect_as(state, ConnectionRole::Controller, true)
+    }
+
+    /// Connects a client to a pipe which serves its clients with the role, as a client running as an administrator is
+    /// served where `elevated`
+    fn connect_as(state: &IpcState, role: ConnectionRole, elevated: bool) -> DuplexStream {
+        let (client, mut server) = duplex(IPC_MAX_REQUEST_LEN as usize);
+        let state = state.clone();
+        tokio::spawn(async move {
+            serve_connection(&mut server, ConnectionKind::Pipe, role, elevated, state).await;
+        });
+
+        client
+    }
+
+    fn request(command: &str, args: Option<Value>) -> Vec<u8> {
+        to_vec(&CommandRequest {
+            command: command.to_string(),
+            args,
+            response_codec: CodecKind::Json,
+            role: ConnectionRole::Controller,
+        })
+        .unwrap()
+    }
+
+    /// Sends a request on a connection of its own, reading the response until the server closes the connection
+    async fn send(state: &IpcState, command: &str, args: Option<Value>) -> Value {
+        send_as(state, ConnectionRole::Controller, true, command, args).await
+    }
+
+    /// Sends a request, which claims to be from a controller, on a connection served with the role and elevation
+    async fn send_as(
+        state: &IpcState,
+        role: ConnectionRole,
+        elevated: bool,
+        command: &str,
+        args: Option<Value>,
+    ) -> Value {
+        let mut client = connect_as(state, role, elevated);
+        client.write_all(&request(command, args)).await.unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        from_slice(&response).unwrap()
+    }
+
+    /// Reads the next frame of an event subscription, passing over heartbeats
+    async fn read_frame(client: &mut DuplexStream) -> Vec<u8> {
+        loop {
+            let len = client.read_u32_le().await.unwrap();
+            let mut frame = vec![0u8; len as usize];
+            client.read_exact(&mut frame).await.unwrap();
+            if !frame.is_empty() {
+                return frame;
+            }
+        }
+    }
+
+    /// Subscribes to events in the categories, leaving the subscription open
+    async fn subscribe(state: &IpcState, categories: EventCategory) -> DuplexStream {
+        let mut client = connect(state);
+        let args = to_value(EventSubscribeArgs {
+            after_seq: None,
+            categories,
+        })
+        .unwrap();
+        client.write_all(&request("events_subscribe", Some(args))).await.unwrap();
+
+        let ready: CommandResponse = from_slice(&read_frame(&mut client).await).unwrap();
+        assert_eq!(ready.status, "ready");
+
+        client
+    }
+
+    #[tokio::test]
+    async fn two_clients_are_served_at_once() {
+        let dir = test_dir("ipc-two-clients");
+        let state = state(&dir);
+
+        // each subscription holds its connection open whilst the other is served, and whilst further clients are
+        let mut telemetry = subscribe(&state, EventCategory::TELEMETRY).await;
+        let mut tamper = subscribe(&state, EventCategory::TAMPER).await;
+
+        let echoed = send(&state, "echo", Some(json!({ "client": 3 }))).await;
+        assert_eq!(echoed, json!({ "client": 3 }));
+
+        let connections: Vec<ConnectionInfo> =
+            serde_json::from_value(send(&state, "connections_list", None).await).unwrap();
+        let subscribed: Vec<EventCategory> = connections
+            .iter()
+            .flat_map(|connection| connection.subscriptions.iter().map(|s| s.categories))
+            .collect();
+        assert_eq!(subscribed, [EventCategory::TELEMETRY, EventCategory::TAMPER]);
+
+        state.events.publish(EngineEvent::Telemetry {
+            source: "driver".to_string(),
+            message: "telemetry".to_string(),
+        });
+        state.events.publish(EngineEvent::Tamper {
+            description: "tamper".to_string(),
+        });
+
+        // each subscriber is only sent the events it asked for, however the other subscribed
+        let envelope: EngineEventEnvelope = from_slice(&read_frame(&mut telemetry).await).unwrap();
+        assert!(matches!(envelope.event, EngineEvent::Telemetry { .. }), "{envelope:?}");
+        let envelope: EngineEventEnvelope = from_slice(&read_frame(&mut tamper).await).unwrap();
+        assert!(matches!(envelope.event, EngineEvent::Tamper { .. }), "{envelope:?}");
+
+        drop((telemetry, tamper));
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    async fn panicking_handler() -> Option<Value> {
+        panic!("A deliberate panic in a handler")
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_leaves_the_server_up() {
+        let dir = test_dir("ipc-panicking-handler");
+        let state = state(&dir);
+        let mut subscriber = subscribe(&state, EventCategory::TAMPER).await;
+
+        let response = handle_isolated("panicking_command", panicking_handler()).await.unwrap();
+        let response: CommandResponse = serde_json::from_value(response).unwrap();
+        assert_eq!(response.status, "error");
+        assert!(response.message.contains("panicking_command"), "{}", response.message);
+        assert!(response.message.contains("A deliberate panic in a handler"), "{}", response.message);
+
+        // a client connecting after the panic is still served, and a client connected before it is still sent events
+        assert_eq!(send(&state, "echo", Some(json!("after the panic"))).await, json!("after the panic"));
+        state.events.publish(EngineEvent::Tamper {
+            description: "after the panic".to_string(),
+        });
+        let envelope: EngineEventEnvelope = from_slice(&read_frame(&mut subscriber).await).unwrap();
+        assert!(matches!(envelope.event, EngineEvent::Tamper { .. }), "{envelope:?}");
+
+        drop(subscriber);
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    /// Sends the command with arguments it cannot parse, asserting it is refused naming what was wrong with them
+    async fn assert_refused_naming(state: &IpcState, command: &str, args: Value, argument: &str) {
+        let response: CommandResponse = serde_json::from_value(send(state, command, Some(args)).await).unwrap();
+        assert_eq!(response.status, "error", "{command}");
+        assert!(response.message.contains(argument), "{command}: {}", response.message);
+    }
+
+    #[tokio::test]
+    async fn malformed_arguments_are_refused_naming_the_argument() {
+        let dir = test_dir("ipc-malformed-arguments");
+        let state = state(&dir);
+
+        assert_refused_naming(&state, "detection_engines_set", json!("every engine"), "engine flags").await;
+        assert_refused_naming(&state, "exclusions_add", json!({ "pattern": 7 }), "exclusion").await;
+        assert_refused_naming(&state, "scanner_start_folder_scan", json!({ "targets": 7 }), "scan").await;
+        assert_refused_naming(&state, "scanner_scan_volume", json!({ "drive": ["D"] }), "volume scan").await;
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn observer_connection_is_refused_a_controller_command() {
+        let dir = test_dir("ipc-observer-refused");
+        let state = state(&dir);
+        let rule = json!({ "pattern": "C:\\Tools\\**" });
+        let rules = state.settings.lock().await.exclusions.rules.len();
+
+        // the request claims to be from a controller, but the connection is an observer's
+        let response = send_as(&state, ConnectionRole::Observer, true, "exclusions_add", Some(rule.clone())).await;
+        let response: CommandResponse = serde_json::from_value(response).unwrap();
+        assert_eq!(response.status, STATUS_PERMISSION_DENIED);
+
+        // nor can the command be batched past the check
+        let batched = json!([{ "command": "exclusions_add", "args": rule, "role": "Controller" }]);
+        let responses = send_as(&state, ConnectionRole::Observer, true, "batch", Some(batched)).await;
+        let responses: Vec<CommandResponse> = serde_json::from_value(responses).unwrap();
+        assert_eq!(responses[0].status, STATUS_PERMISSION_DENIED);
+        assert_eq!(state.settings.lock().await.exclusions.rules.len(), rules);
+
+        // whilst the observer may still watch the engine
+        let echoed = send_as(&state, ConnectionRole::Observer, true, "echo", Some(json!("watching"))).await;
+        assert_eq!(echoed, json!("watching"));
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remote_management_changes_need_elevation() {
+        let dir = test_dir("ipc-remote-management-elevation");
+        let state = state(&dir);
+
+        let unchanged = default_settings("test");
+        let mut listening = unchanged.clone();
+        listening.remote_management.enabled = true;
+        listening.remote_management.client_ca_file = Some(dir.join("attacker-ca.pem"));
+
+        let update = |settings: &SanctumSettings| CommandRequest {
+            command: "settings_update_settings".to_string(),
+            args: Some(to_value(settings).unwrap()),
+            response_codec: CodecKind::Json,
+            role: ConnectionRole::Controller,
+        };
+        assert!(requires_elevation(&update(&listening), &state.settings).await);
+        assert!(!requires_elevation(&update(&unchanged), &state.settings).await);
+
+        // a client which is not running as an administrator cannot open the engine to remote consoles
+        let args = Some(to_value(&listening).unwrap());
+        let response = send_as(&state, ConnectionRole::Controller, false, "settings_update_settings", args).await;
+        let response: CommandResponse = serde_json::from_value(response).unwrap();
+        assert_eq!(response.status, STATUS_PERMISSION_DENIED);
+        assert!(!state.settings.lock().await.remote_management.enabled);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
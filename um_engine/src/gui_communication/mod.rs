@@ -1 +1,7 @@
+mod connections;
+mod elevation;
 pub mod ipc;
+#[cfg(feature = "remote-management")]
+mod tls;
+mod trace;
+pub mod transport;
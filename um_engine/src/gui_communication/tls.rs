@@ -0,0 +1,126 @@
+//! The TLS which the remote management listener serves clients over, and what each client may do given the
+//! certificate it presents.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use sha2::{Digest, Sha256};
+use shared_no_std::ipc::ConnectionRole;
+use shared_std::settings::RemoteManagementSettings;
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        RootCertStore, ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        server::WebPkiClientVerifier,
+    },
+};
+
+use super::transport::normalise_fingerprint;
+
+/// Builds the TLS configuration of the remote management listener, which requires each client to present a
+/// certificate issued by the client CA before any of its request is read
+pub fn tls_acceptor(settings: &RemoteManagementSettings) -> Result<TlsAcceptor, String> {
+    let cert_file = required_file(&settings.cert_file, "certificate")?;
+    let key_file = required_file(&settings.key_file, "private key")?;
+    let ca_file = required_file(&settings.client_ca_file, "client CA")?;
+
+    let certs = load_certs(cert_file)?;
+    let key = load_private_key(key_file)?;
+
+    let mut roots = RootCertStore::empty();
+    for ca in load_certs(ca_file)? {
+        roots
+            .add(ca)
+            .map_err(|e| format!("Invalid client CA certificate in {}. {e}", ca_file.display()))?;
+    }
+    let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+        .build()
+        .map_err(|e| format!("Could not use the client CA in {}. {e}", ca_file.display()))?;
+
+    let config = ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid remote management certificate or private key. {e}"))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn required_file<'a>(file: &'a Option<PathBuf>, name: &str) -> Result<&'a Path, String> {
+    file.as_deref()
+        .ok_or_else(|| format!("A {name} file must be given for remote management, which always requires TLS."))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("Could not open {}. {e}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Could not read the certificates in {}. {e}", path.display()))?;
+
+    match certs.is_empty() {
+        true => Err(format!("No certificates were found in {}.", path.display())),
+        false => Ok(certs),
+    }
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let file = File::open(path).map_err(|e| format!("Could not open {}. {e}", path.display()))?;
+
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("Could not read the private key in {}. {e}", path.display()))?
+        .ok_or_else(|| format!("No private key was found in {}.", path.display()))
+}
+
+/// The role a remote client is served with, and whether it is served as a client running as an administrator is,
+/// from the certificate it presented in the TLS handshake. A client whose certificate is not listed in the settings
+/// is served as an observer.
+pub fn remote_client_access(
+    settings: &RemoteManagementSettings,
+    certificate: Option<&CertificateDer>,
+) -> (ConnectionRole, bool) {
+    let Some(certificate) = certificate else {
+        return (ConnectionRole::Observer, false);
+    };
+
+    let fingerprint: String = Sha256::digest(certificate.as_ref()).iter().map(|byte| format!("{byte:02x}")).collect();
+    let listed = |fingerprints: &[String]| {
+        fingerprints
+            .iter()
+            .any(|listed| normalise_fingerprint(listed).as_ref() == Some(&fingerprint))
+    };
+
+    if listed(&settings.elevated_certificates) {
+        (ConnectionRole::Controller, true)
+    } else if listed(&settings.controller_certificates) {
+        (ConnectionRole::Controller, false)
+    } else {
+        (ConnectionRole::Observer, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_clients_are_observers_unless_their_certificate_is_listed() {
+        let certificate = CertificateDer::from(b"a client certificate".to_vec());
+        let fingerprint: String = Sha256::digest(certificate.as_ref()).iter().map(|b| format!("{b:02X}")).collect();
+        let mut settings = RemoteManagementSettings::default();
+
+        assert_eq!(remote_client_access(&settings, Some(&certificate)), (ConnectionRole::Observer, false));
+        assert_eq!(remote_client_access(&settings, None), (ConnectionRole::Observer, false));
+
+        settings.controller_certificates = vec![fingerprint.clone()];
+        assert_eq!(remote_client_access(&settings, Some(&certificate)), (ConnectionRole::Controller, false));
+
+        // as certificate tools show it, in uppercase with its bytes separated by colons
+        let shown = fingerprint.as_bytes().chunks(2).map(|b| str::from_utf8(b).unwrap()).collect::<Vec<_>>().join(":");
+        settings.elevated_certificates = vec![shown];
+        assert_eq!(remote_client_access(&settings, Some(&certificate)), (ConnectionRole::Controller, true));
+    }
+}
@@ -0,0 +1,98 @@
+//! Optional tracing of every command received over the IPC pipe, for debugging issues such as a GUI button
+//! appearing to do nothing.
+//!
+//! Tracing is enabled with the `SANCTUM_IPC_TRACE` environment variable:
+//!
+//! - `1` / `on`: logs the command name, size of the args and the response status and size of every request.
+//! - `verbose`: additionally logs the content of the args. This may include paths or other sensitive data, so is not
+//!   the default.
+//!
+//! Each connection is given a correlation ID so a request can be matched to its response in the log.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use shared_no_std::ipc::CommandRequest;
+
+use crate::utils::log::{Log, LogLevel};
+
+const TRACE_ENV_VAR: &str = "SANCTUM_IPC_TRACE";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IpcTraceMode {
+    Off,
+    /// Argument contents are redacted
+    Redacted,
+    Verbose,
+}
+
+pub struct IpcTrace {
+    mode: IpcTraceMode,
+    next_id: AtomicU64,
+    log: Log,
+}
+
+impl IpcTrace {
+    /// Creates the tracer, with the mode read from the environment
+    pub fn from_env() -> Self {
+        let mode = match std::env::var(TRACE_ENV_VAR) {
+            Ok(v) => match v.to_lowercase().as_str() {
+                "1" | "on" | "true" => IpcTraceMode::Redacted,
+                "verbose" => IpcTraceMode::Verbose,
+                _ => IpcTraceMode::Off,
+            },
+            Err(_) => IpcTraceMode::Off,
+        };
+
+        let log = Log::new();
+        if mode != IpcTraceMode::Off {
+            log.log(LogLevel::Info, &format!("IPC trace enabled, mode: {mode:?}."));
+        }
+
+        IpcTrace {
+            mode,
+            next_id: AtomicU64::new(1),
+            log,
+        }
+    }
+
+    /// Gets the correlation ID for a new connection
+    pub fn next_correlation_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn request(&self, correlation_id: u64, request: &CommandRequest) {
+        if self.mode == IpcTraceMode::Off {
+            return;
+        }
+
+        let args = request
+            .args
+            .as_ref()
+            .map(|a| serde_json::to_string(a).unwrap_or_default());
+        let args_len = args.as_ref().map(|a| a.len()).unwrap_or(0);
+
+        let msg = match (self.mode, args) {
+            (IpcTraceMode::Verbose, Some(args)) => format!(
+                "[ipc #{correlation_id}] -> {} args: {args_len} bytes {args}",
+                request.command
+            ),
+            _ => format!(
+                "[ipc #{correlation_id}] -> {} args: {args_len} bytes",
+                request.command
+            ),
+        };
+
+        self.log.log(LogLevel::Debug, &msg);
+    }
+
+    pub fn response(&self, correlation_id: u64, status: &str, len: usize) {
+        if self.mode == IpcTraceMode::Off {
+            return;
+        }
+
+        self.log.log(
+            LogLevel::Debug,
+            &format!("[ipc #{correlation_id}] <- {status}, {len} bytes"),
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! The transports IPC clients connect to the engine over. The named pipe is always served, for the GUI and anything
+//! else on the machine; the engine may additionally listen on TCP, so that it can be managed from a remote console.
+//!
+//! The command protocol is the same over either. A TCP connection is always TLS, and the client must present a
+//! certificate issued by the client CA in the settings, so that only a console which has been issued a certificate
+//! can connect to the engine. There is no way to listen on TCP without TLS. What a remote console may do is decided
+//! by the fingerprint of its certificate: only those listed in the settings may change the engine, or send the
+//! commands which need an administrator.
+//!
+//! The TCP listener is only built into an engine built with the `remote-management` feature, as TLS needs a C compiler
+//! to build.
+
+use std::net::SocketAddr;
+
+use shared_std::settings::RemoteManagementSettings;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// A connection which a client's request is read from, and its response written to
+pub trait IpcTransport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> IpcTransport for T {}
+
+/// Checks the remote management settings, loading the certificates they name, so that a listener which could not
+/// start is refused when the settings are changed rather than when the engine next starts.
+///
+/// # Returns
+///
+/// An error describing the first invalid setting.
+pub fn validate_remote_settings(settings: &RemoteManagementSettings) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    match &settings.listen_address {
+        Some(address) if address.trim().parse::<SocketAddr>().is_ok_and(|a| a.port() != 0) => (),
+        Some(address) => return Err(format!("Remote management address '{address}' must be given as ip:port.")),
+        None => return Err("An address must be given to listen for remote management on.".to_string()),
+    }
+
+    let fingerprints = settings.controller_certificates.iter().chain(&settings.elevated_certificates);
+    for fingerprint in fingerprints {
+        if normalise_fingerprint(fingerprint).is_none() {
+            return Err(format!(
+                "Client certificate fingerprint '{fingerprint}' must be given as the 64 hex digits of its SHA-256."
+            ));
+        }
+    }
+
+    check_tls(settings)
+}
+
+#[cfg(feature = "remote-management")]
+fn check_tls(settings: &RemoteManagementSettings) -> Result<(), String> {
+    super::tls::tls_acceptor(settings).map(|_| ())
+}
+
+/// Remote management cannot be turned on in an engine which cannot listen for it
+#[cfg(not(feature = "remote-management"))]
+fn check_tls(_settings: &RemoteManagementSettings) -> Result<(), String> {
+    Err("Remote management requires an engine built with the remote-management feature.".to_string())
+}
+
+/// A SHA-256 fingerprint as lowercase hex, allowing for it being given in uppercase or with its bytes separated by
+/// colons as certificate tools show them
+pub(super) fn normalise_fingerprint(fingerprint: &str) -> Option<String> {
+    let fingerprint: String = fingerprint.trim().chars().filter(|c| *c != ':').collect();
+
+    match fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Some(fingerprint.to_ascii_lowercase()),
+        false => None,
+    }
+}
@@ -0,0 +1,545 @@
+//! The history of past scans, persisted to disk so that results remain available after the scanner is reset or the
+//! engine restarts.
+//!
+//! The history is small (one record per scan), so it is held in memory and written out in full whenever it changes.
+//! Alongside it is kept the totals of each day, so that protection stats can be given without going over every
+//! detection of every scan.
+//!
+//! The timeline of each threat, when it was first and last seen, is kept in a file of its own rather than worked out
+//! from the records, so that when a threat was first seen is not lost once the scan which saw it is pruned.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, io, iter,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use shared_no_std::constants::{SCAN_HISTORY_LOCATION, THREAT_TIMELINE_LOCATION};
+use shared_std::{
+    file_scanner::{ActionResult, FileScannerState, MatchedIOC, ScanTarget},
+    history::{
+        DailyProtectionStats, DetectionAnnotation, DetectionAnnotationRequest, MAX_THREAT_SIGHTINGS, ProtectionStats,
+        RecentDetection, ScanDiff, ScanDiffRequest, ScanRecord, StatsPeriod, ThreatSighting, ThreatTimeline,
+        TriageStatus,
+    },
+};
+
+use crate::utils::{
+    files::write_atomic,
+    log::{Log, LogLevel},
+};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct ScanHistory {
+    path: PathBuf,
+    records: Mutex<Vec<ScanRecord>>,
+    // the totals of the scans which ended each day, by the number of the day since the Unix epoch, or None for scans
+    // recorded without a time. Always taken after the records, where both are held.
+    daily: Mutex<BTreeMap<Option<u64>, DailyProtectionStats>>,
+    threats_path: PathBuf,
+    // the timeline of each threat, by the hash it was detected by. Always taken after the records, where both are held.
+    threats: Mutex<HashMap<String, ThreatTimeline>>,
+}
+
+impl ScanHistory {
+    /// Loads the scan history from disk. If the history cannot be read, such as on first run, it starts empty.
+    pub fn load() -> Self {
+        let app_data = std::env::var("APPDATA")
+            .expect("[-] Could not find App Data folder in environment variables.]");
+        Self::load_from(Path::new(&app_data))
+    }
+
+    /// Loads the scan history kept under the App Data folder given
+    pub(crate) fn load_from(app_data: &Path) -> Self {
+        let path = app_data.join(SCAN_HISTORY_LOCATION);
+        let threats_path = app_data.join(THREAT_TIMELINE_LOCATION);
+
+        let records = match fs::read(&path) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(r) => r,
+                Err(e) => {
+                    Log::new().log(
+                        LogLevel::Warning,
+                        &format!("Scan history at {} is corrupt, starting afresh. {e}", path.display()),
+                    );
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+
+        // where the timelines cannot be read, such as when first updated to a version which keeps them, they are
+        // worked out again from the scans still in the history
+        let threats = match fs::read(&threats_path).map(|data| serde_json::from_slice::<Vec<ThreatTimeline>>(&data)) {
+            Ok(Ok(timelines)) => timelines.into_iter().map(|t| (t.hash.clone(), t)).collect(),
+            Ok(Err(e)) => {
+                Log::new().log(
+                    LogLevel::Warning,
+                    &format!(
+                        "Threat timelines at {} are corrupt, rebuilding them from the scan history. {e}",
+                        threats_path.display()
+                    ),
+                );
+                threat_timelines(&records)
+            }
+            Err(_) => threat_timelines(&records),
+        };
+
+        ScanHistory {
+            path,
+            daily: Mutex::new(daily_totals(&records)),
+            records: Mutex::new(records),
+            threats_path,
+            threats: Mutex::new(threats),
+        }
+    }
+
+    /// Adds the record of a scan which has ended, along with its detections to the timeline of each threat
+    pub fn record(&self, record: ScanRecord) -> io::Result<()> {
+        let mut records = self.records.lock().unwrap();
+        let mut threats = self.threats.lock().unwrap();
+        add_to_totals(&mut self.daily.lock().unwrap(), &record);
+        add_sightings(&mut threats, &record);
+        records.push(record);
+        self.persist(&records)?;
+        self.persist_threats(&threats)
+    }
+
+    /// Totals what the engine did over the period, from the daily totals of the history
+    pub fn protection_stats(&self, period: StatsPeriod) -> ProtectionStats {
+        let last_scan = self.records.lock().unwrap().iter().rev().find_map(|r| r.finished_at);
+        let daily = self.daily.lock().unwrap();
+
+        let today = day_number(SystemTime::now()).unwrap_or_default();
+        let first = match period.days() {
+            Some(days) => Some(today.saturating_sub(days - 1)),
+            None => daily.keys().find_map(|day| *day),
+        };
+
+        let mut stats = ProtectionStats {
+            period,
+            scans: 0,
+            files_scanned: 0,
+            bytes_scanned: 0,
+            detections: 0,
+            threats_blocked: 0,
+            last_scan,
+            days: Vec::new(),
+        };
+
+        // scans without a time cannot be placed in a period, so only count towards the lifetime
+        if period == StatsPeriod::Lifetime
+            && let Some(undated) = daily.get(&None)
+        {
+            add_day(&mut stats, undated);
+        }
+
+        for day in first.into_iter().flat_map(|first| first..=today) {
+            let totals = daily.get(&Some(day)).cloned().unwrap_or_else(|| empty_day(day));
+            add_day(&mut stats, &totals);
+            stats.days.push(totals);
+        }
+
+        stats
+    }
+
+    /// Gets the record of the scan, if it is still in the history
+    pub fn find(&self, scan_id: u64) -> Option<ScanRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.scan_id == scan_id)
+            .cloned()
+    }
+
+    /// Gets the records of the most recent scans, newest first
+    pub fn recent(&self, limit: usize) -> Vec<ScanRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Sets the triage status and note of a detection, replacing any earlier annotation.
+    ///
+    /// # Returns
+    ///
+    /// The annotation as stored, or an error if the detection is not in the history or the history could not be
+    /// written.
+    pub fn annotate(&self, request: DetectionAnnotationRequest) -> Result<DetectionAnnotation, String> {
+        let mut records = self.records.lock().unwrap();
+
+        let record = records
+            .iter_mut()
+            .find(|r| r.scan_id == request.scan_id)
+            .ok_or_else(|| format!("Scan {} was not found in the scan history.", request.scan_id))?;
+
+        if request.detection_id >= record.detections.len() {
+            return Err(format!(
+                "Scan {} has no detection {}, it has {} detections.",
+                request.scan_id,
+                request.detection_id,
+                record.detections.len()
+            ));
+        }
+
+        let annotation = DetectionAnnotation {
+            status: request.status,
+            note: request.note,
+            updated_at: SystemTime::now(),
+        };
+        record
+            .annotations
+            .insert(request.detection_id, annotation.clone());
+
+        self.persist(&records)
+            .map_err(|e| format!("Could not save the annotation to the scan history. {e}"))?;
+
+        Ok(annotation)
+    }
+
+    /// Records the actions taken on detections of a scan after it ended, such as by quarantining them in a batch, so
+    /// the history shows what became of each. The totals of the day the scan ended count any detection newly blocked.
+    ///
+    /// # Returns
+    ///
+    /// An error if the scan is not in the history or the history could not be written.
+    pub fn record_actions(&self, scan_id: u64, actions: Vec<(usize, ActionResult)>) -> io::Result<()> {
+        let mut records = self.records.lock().unwrap();
+
+        let record = records
+            .iter_mut()
+            .find(|r| r.scan_id == scan_id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Scan {scan_id} was not found.")))?;
+
+        let blocks = |action: &ActionResult| {
+            matches!(action, ActionResult::Quarantined { .. } | ActionResult::Deleted | ActionResult::DeletePending)
+        };
+        let mut newly_blocked = 0;
+        for (detection_id, action) in actions {
+            let Some(ioc) = record.detections.get_mut(detection_id) else {
+                continue;
+            };
+            if blocks(&action) && !blocks(&ioc.action_taken) {
+                newly_blocked += 1;
+            }
+            ioc.action_taken = action;
+        }
+
+        if newly_blocked > 0 {
+            let day = record.finished_at.or(record.started_at).and_then(day_number);
+            if let Some(totals) = self.daily.lock().unwrap().get_mut(&day) {
+                totals.threats_blocked += newly_blocked;
+            }
+        }
+
+        self.persist(&records)
+    }
+
+    /// Gets the annotation of the most recent detection of the file, if that detection has been annotated
+    pub fn latest_annotation(&self, file: &Path) -> Option<DetectionAnnotation> {
+        let records = self.records.lock().unwrap();
+
+        records.iter().rev().find_map(|r| {
+            let detection_id = r.detections.iter().rposition(|ioc| ioc.file == file)?;
+            Some(r.annotations.get(&detection_id).cloned())
+        })?
+    }
+
+    /// When the most recent scan of a whole volume which ran to the end finished, which is what counts as a full scan
+    pub fn last_full_scan(&self) -> Option<SystemTime> {
+        let records = self.records.lock().unwrap();
+
+        records
+            .iter()
+            .rev()
+            .filter(|r| matches!(r.target, Some(ScanTarget::Volume(_))))
+            .filter(|r| r.outcome == FileScannerState::Finished.name())
+            .find_map(|r| r.finished_at)
+    }
+
+    /// Counts the files whose most recent detection left them in place, as no action was taken or the action failed,
+    /// other than those triaged as benign
+    pub fn outstanding_detections(&self) -> usize {
+        let records = self.records.lock().unwrap();
+        let mut seen = HashSet::new();
+
+        records
+            .iter()
+            .rev()
+            .flat_map(|r| r.detections.iter().enumerate().rev().map(move |(id, ioc)| (r, id, ioc)))
+            .filter(|&(_, _, ioc)| seen.insert(&ioc.file))
+            .filter(|(_, _, ioc)| matches!(ioc.action_taken, ActionResult::None | ActionResult::ActionFailed(_)))
+            .filter(|(r, id, _)| r.annotations.get(id).is_none_or(|a| a.status != TriageStatus::Benign))
+            .count()
+    }
+
+    /// Gets the most recent detections across the history, newest first, optionally only those with the triage
+    /// status.
+    pub fn recent_detections(&self, status: Option<TriageStatus>, limit: usize) -> Vec<RecentDetection> {
+        let records = self.records.lock().unwrap();
+
+        records
+            .iter()
+            .rev()
+            .flat_map(|r| {
+                r.detections
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(detection_id, ioc)| RecentDetection {
+                        scan_id: r.scan_id,
+                        detection_id,
+                        finished_at: r.finished_at,
+                        ioc: ioc.clone(),
+                        annotation: r.annotations.get(&detection_id).cloned(),
+                    })
+            })
+            .filter(|d| status.is_none_or(|s| d.status() == s))
+            .take(limit)
+            .collect()
+    }
+
+    /// Compares the detections of scan A with those of the later scan B.
+    ///
+    /// A detection in A which B did not repeat is only taken as resolved where B scanned the file again, being beneath
+    /// one of B's folders and not a file B could not verify; otherwise it is listed as not rescanned.
+    ///
+    /// # Returns
+    ///
+    /// The comparison, or an error if either scan is not in the history.
+    pub fn diff(&self, request: &ScanDiffRequest) -> Result<ScanDiff, String> {
+        let find = |scan_id: u64| {
+            self.find(scan_id)
+                .ok_or_else(|| format!("Scan {scan_id} was not found in the scan history."))
+        };
+        let a = find(request.scan_id_a)?;
+        let b = find(request.scan_id_b)?;
+
+        let detected_by_a: HashMap<String, &MatchedIOC> =
+            a.detections.iter().map(|ioc| (path_key(&ioc.file), ioc)).collect();
+        let detected_by_b: HashMap<String, &MatchedIOC> =
+            b.detections.iter().map(|ioc| (path_key(&ioc.file), ioc)).collect();
+        let unverified: HashSet<String> = b
+            .changed_during_scan
+            .iter()
+            .chain(b.stalled_files.iter().map(|s| &s.file))
+            .map(|file| path_key(file))
+            .collect();
+        let roots: Vec<PathBuf> = b.roots.iter().map(|root| PathBuf::from(path_key(root))).collect();
+
+        // where the hash is matched, a file whose hash changed has been replaced, so is not the same detection
+        let same = |earlier: &MatchedIOC, later: &MatchedIOC| !request.match_hash || earlier.hash == later.hash;
+
+        let mut diff = ScanDiff {
+            scan_id_a: a.scan_id,
+            scan_id_b: b.scan_id,
+            new_detections: Vec::new(),
+            resolved_detections: Vec::new(),
+            persistent_detections: Vec::new(),
+            not_rescanned: Vec::new(),
+            files_scanned_change: b.num_files_scanned as i128 - a.num_files_scanned as i128,
+        };
+
+        for ioc in &b.detections {
+            match detected_by_a.get(&path_key(&ioc.file)) {
+                Some(earlier) if same(earlier, ioc) => diff.persistent_detections.push(ioc.clone()),
+                _ => diff.new_detections.push(ioc.clone()),
+            }
+        }
+
+        for ioc in &a.detections {
+            let key = path_key(&ioc.file);
+            match detected_by_b.get(&key) {
+                Some(later) if same(ioc, later) => (),
+                Some(_) => diff.resolved_detections.push(ioc.clone()),
+                None if unverified.contains(&key) || !roots.iter().any(|root| Path::new(&key).starts_with(root)) => {
+                    diff.not_rescanned.push(ioc.clone())
+                }
+                None => diff.resolved_detections.push(ioc.clone()),
+            }
+        }
+
+        Ok(diff)
+    }
+
+    /// Gets when the threat was first and last seen, and by which scans, by the hash it was detected by, along with
+    /// the files it was detected in which are still on disk.
+    ///
+    /// # Returns
+    ///
+    /// The timeline, or an error if no scan has detected the threat.
+    pub fn threat_timeline(&self, hash: &str) -> Result<ThreatTimeline, String> {
+        let hash = hash.trim();
+        let threats = self.threats.lock().unwrap();
+
+        // hex hashes may be given in either case, though a fuzzy hash must match exactly
+        let mut timeline = threats
+            .get(hash)
+            .or_else(|| threats.values().find(|t| t.hash.eq_ignore_ascii_case(hash)))
+            .cloned()
+            .ok_or_else(|| format!("No threat with the hash {hash} has been detected."))?;
+        drop(threats);
+
+        // whether a file was left in place is taken from its latest sighting
+        let mut latest: HashMap<String, &ThreatSighting> = HashMap::new();
+        for sighting in iter::once(&timeline.first_seen).chain(&timeline.sightings) {
+            latest.insert(path_key(&sighting.file), sighting);
+        }
+        let mut present: Vec<PathBuf> = latest
+            .into_values()
+            .filter(|s| matches!(s.action_taken, ActionResult::None | ActionResult::ActionFailed(_)))
+            .map(|s| s.file.clone())
+            .filter(|file| file.exists())
+            .collect();
+        present.sort();
+        timeline.present = present;
+
+        Ok(timeline)
+    }
+
+    /// Removes records of scans which finished before the cutoff, and compacts the history file. The timeline of each
+    /// threat is kept, so that when a threat was first seen outlives the scan which saw it.
+    ///
+    /// # Returns
+    ///
+    /// The number of records removed, followed by the size of the history file before and after compaction.
+    pub fn prune_older_than(&self, cutoff: SystemTime) -> io::Result<(usize, u64, u64)> {
+        let mut records = self.records.lock().unwrap();
+        let bytes_before = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+
+        let count_before = records.len();
+        records.retain(|r| match r.finished_at.or(r.started_at) {
+            Some(at) => at >= cutoff,
+            // without a time we cannot know its age, so keep it
+            None => true,
+        });
+        let pruned = count_before - records.len();
+        *self.daily.lock().unwrap() = daily_totals(&records);
+
+        // always rewrite, as this is also what compacts the file
+        self.persist(&records)?;
+        let bytes_after = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+
+        Ok((pruned, bytes_before, bytes_after))
+    }
+
+    fn persist(&self, records: &[ScanRecord]) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec(records)?)
+    }
+
+    fn persist_threats(&self, threats: &HashMap<String, ThreatTimeline>) -> io::Result<()> {
+        let timelines: Vec<&ThreatTimeline> = threats.values().collect();
+        write_atomic(&self.threats_path, &serde_json::to_vec(&timelines)?)
+    }
+}
+
+/// Totals the scans of each day, for when the history is loaded or pruned
+fn daily_totals(records: &[ScanRecord]) -> BTreeMap<Option<u64>, DailyProtectionStats> {
+    let mut daily = BTreeMap::new();
+    for record in records {
+        add_to_totals(&mut daily, record);
+    }
+
+    daily
+}
+
+fn add_to_totals(daily: &mut BTreeMap<Option<u64>, DailyProtectionStats>, record: &ScanRecord) {
+    let day = record.finished_at.or(record.started_at).and_then(day_number);
+    let totals = daily.entry(day).or_insert_with(|| empty_day(day.unwrap_or_default()));
+
+    totals.scans += 1;
+    totals.files_scanned += record.num_files_scanned;
+    totals.bytes_scanned += record.bytes_scanned;
+    totals.detections += record.detections.len() as u64;
+    totals.threats_blocked += record
+        .detections
+        .iter()
+        .filter(|ioc| {
+            matches!(
+                ioc.action_taken,
+                ActionResult::Quarantined { .. } | ActionResult::Deleted | ActionResult::DeletePending
+            )
+        })
+        .count() as u64;
+}
+
+/// Works out the timeline of each threat from the records, for when the timelines cannot be loaded
+fn threat_timelines(records: &[ScanRecord]) -> HashMap<String, ThreatTimeline> {
+    let mut threats = HashMap::new();
+    for record in records {
+        add_sightings(&mut threats, record);
+    }
+
+    threats
+}
+
+fn add_sightings(threats: &mut HashMap<String, ThreatTimeline>, record: &ScanRecord) {
+    let seen_at = record.finished_at.or(record.started_at);
+    // a scan which detects a threat in several files still counts as one scan which saw it
+    let mut counted = HashSet::new();
+
+    for ioc in record.detections.iter().filter(|ioc| !ioc.hash.is_empty()) {
+        let sighting = ThreatSighting {
+            scan_id: record.scan_id,
+            seen_at,
+            file: ioc.file.clone(),
+            action_taken: ioc.action_taken.clone(),
+        };
+
+        let timeline = threats.entry(ioc.hash.clone()).or_insert_with(|| ThreatTimeline {
+            hash: ioc.hash.clone(),
+            first_seen: sighting.clone(),
+            last_seen: sighting.clone(),
+            scans: 0,
+            sightings: Vec::new(),
+            present: Vec::new(),
+        });
+
+        if counted.insert(ioc.hash.as_str()) {
+            timeline.scans += 1;
+        }
+        timeline.last_seen = sighting.clone();
+        timeline.sightings.push(sighting);
+        if timeline.sightings.len() > MAX_THREAT_SIGHTINGS {
+            timeline.sightings.remove(0);
+        }
+    }
+}
+
+fn add_day(stats: &mut ProtectionStats, day: &DailyProtectionStats) {
+    stats.scans += day.scans;
+    stats.files_scanned += day.files_scanned;
+    stats.bytes_scanned += day.bytes_scanned;
+    stats.detections += day.detections;
+    stats.threats_blocked += day.threats_blocked;
+}
+
+fn empty_day(day: u64) -> DailyProtectionStats {
+    DailyProtectionStats {
+        day: SystemTime::UNIX_EPOCH + Duration::from_secs(day * SECS_PER_DAY),
+        scans: 0,
+        files_scanned: 0,
+        bytes_scanned: 0,
+        detections: 0,
+        threats_blocked: 0,
+    }
+}
+
+/// The number of the UTC day the time falls on, counting from the Unix epoch
+fn day_number(time: SystemTime) -> Option<u64> {
+    time.duration_since(SystemTime::UNIX_EPOCH).ok().map(|since| since.as_secs() / SECS_PER_DAY)
+}
+
+/// The form of a path files are compared by, as paths on Windows are case insensitive
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
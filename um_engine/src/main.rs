@@ -4,25 +4,56 @@
 
 #![feature(io_error_uncategorized)]
 
+use std::{backtrace::Backtrace, panic};
+
 use engine::Engine;
-use utils::log::Log;
+use utils::log::{Log, LogLevel};
 
+mod audit;
 mod core;
+mod diagnostics;
+mod diagnostics_export;
 mod driver_manager;
 mod engine;
+mod events;
+mod export;
 mod filescanner;
+mod forwarder;
 mod gui_communication;
+mod history;
+mod maintenance;
+mod notifications;
+mod protection_score;
+mod quarantine;
+mod realtime;
+mod removable_media;
+mod resources;
 mod settings;
+mod siem;
 mod strings;
 mod utils;
+mod webhook;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    //
+    // Log every panic with its backtrace. A panic in a task, such as an IPC command handler, is caught by the task
+    // so does not bring down the engine, but it should still be investigated.
+    //
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        Log::new().log(
+            LogLevel::Error,
+            &format!("Panic: {info}\n{}", Backtrace::force_capture()),
+        );
+        default_hook(info);
+    }));
+
     //
     // Start the engine, this will kick off and run the application; note this should never return,
     // unless an error occurred.
     //
-    let error = Engine::start().await;
+    let error = Engine::default().start().await;
 
     let logger = Log::new();
     logger.panic(&format!(
@@ -0,0 +1,53 @@
+//! Maintenance of the engine's stores on disk, so that the scan history and quarantine do not grow unbounded.
+//!
+//! Maintenance is run periodically in the background, and may also be run on demand via the `maintenance_run`
+//! command.
+
+use std::time::{Duration, SystemTime};
+
+use shared_std::{maintenance::MaintenanceReport, settings::MaintenanceSettings};
+
+use crate::{history::ScanHistory, quarantine::QuarantineStore};
+
+/// How often maintenance is run in the background
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Prunes scan history older than the retention period, compacting the history file, and removes orphaned files
+/// and manifest entries from the quarantine. A step which fails is reported in the errors of the report, and does not
+/// stop the other steps from running.
+pub fn run_maintenance(
+    history: &ScanHistory,
+    quarantine: &QuarantineStore,
+    settings: &MaintenanceSettings,
+) -> MaintenanceReport {
+    let mut report = MaintenanceReport::default();
+
+    // a retention of 0 keeps history forever, though the file is still compacted
+    let cutoff = match settings.history_retention_days {
+        0 => SystemTime::UNIX_EPOCH,
+        days => SystemTime::now()
+            .checked_sub(Duration::from_secs(days as u64 * SECS_PER_DAY))
+            .unwrap_or(SystemTime::UNIX_EPOCH),
+    };
+
+    match history.prune_older_than(cutoff) {
+        Ok((pruned, before, after)) => {
+            report.history_records_pruned = pruned;
+            report.history_bytes_before = before;
+            report.history_bytes_after = after;
+        }
+        Err(e) => report.errors.push(format!("Could not prune scan history. {e}")),
+    }
+
+    match quarantine.prune_orphans() {
+        Ok((files, entries)) => {
+            report.orphaned_quarantine_files = files;
+            report.orphaned_quarantine_entries = entries;
+        }
+        Err(e) => report.errors.push(format!("Could not prune quarantine. {e}")),
+    }
+
+    report
+}
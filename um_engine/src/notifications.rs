@@ -0,0 +1,102 @@
+//! Coalescing the notifications of detections shown to the user, so that a scan which finds hundreds of infected files
+//! does not raise hundreds of toasts.
+//!
+//! Each detection of a scan is notified individually up to the limit in the settings, after which the detections are
+//! counted and notified together, at most once each [`SUMMARY_INTERVAL`] and once more when the scan ends. Only the
+//! notifications are coalesced; every detection is still published as an [`EngineEvent::Detection`].
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use shared_std::{events::EngineEvent, file_scanner::MatchedIOC, settings::NotificationSettings};
+
+use crate::events::EventBus;
+
+/// The least time between summaries of the detections beyond the limit, whilst a scan is running
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct DetectionNotifier {
+    events: Arc<EventBus>,
+    // the detections of a scan which are notified individually, from the settings
+    limit: AtomicU32,
+    state: Mutex<NotifierState>,
+}
+
+#[derive(Default)]
+struct NotifierState {
+    scan_id: Option<u64>,
+    notified: u32,
+    // detections beyond the limit which have yet to be included in a summary
+    pending: u32,
+    last_summary: Option<Instant>,
+}
+
+impl DetectionNotifier {
+    pub fn new(events: Arc<EventBus>) -> Self {
+        DetectionNotifier {
+            events,
+            limit: AtomicU32::new(NotificationSettings::default().detection_toast_limit),
+            state: Mutex::new(NotifierState::default()),
+        }
+    }
+
+    pub fn set_limit(&self, limit: u32) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Starts counting the detections of a new scan afresh
+    pub fn begin(&self, scan_id: u64) {
+        *self.state.lock().unwrap() = NotifierState {
+            scan_id: Some(scan_id),
+            ..Default::default()
+        };
+    }
+
+    /// Notifies the user of the detection, or counts it towards the next summary where the limit has been reached
+    pub fn detection(&self, ioc: &MatchedIOC) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.notified < self.limit.load(Ordering::Relaxed) {
+            state.notified += 1;
+            self.events.publish(EngineEvent::Notify {
+                title: "Threat detected".to_string(),
+                message: format!("{} was detected as malware.", ioc.file.display()),
+                scan_id: state.scan_id,
+            });
+            return;
+        }
+
+        state.pending += 1;
+        // the first summary waits a full interval after the limit is reached, so a burst of detections is coalesced
+        let last_summary = *state.last_summary.get_or_insert_with(Instant::now);
+        if last_summary.elapsed() >= SUMMARY_INTERVAL {
+            self.publish_summary(&mut state);
+        }
+    }
+
+    /// Notifies the user of any detections of the scan which have yet to be, as the scan has ended
+    pub fn end(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.publish_summary(&mut state);
+        state.scan_id = None;
+    }
+
+    fn publish_summary(&self, state: &mut NotifierState) {
+        if state.pending == 0 {
+            return;
+        }
+
+        self.events.publish(EngineEvent::Notify {
+            title: format!("{} more threats found", state.pending),
+            message: "Open the scan results to see every threat found.".to_string(),
+            scan_id: state.scan_id,
+        });
+        state.pending = 0;
+        state.last_summary = Some(Instant::now());
+    }
+}
@@ -0,0 +1,231 @@
+//! A single number from 0 to 100 summarising how well the machine is protected, for users who want to know at a glance
+//! whether anything needs their attention.
+//!
+//! The score is made up of factors, each weighted by [`FACTOR_WEIGHTS`], and each factor earns some share of its
+//! weight from what the engine already knows: the real-time policy and any snooze, whether the driver is loaded, how
+//! old the signatures are, when the last full scan finished, and the detections left unhandled in the scan history.
+//! Factors which age, such as the signatures, earn their full weight whilst fresh and lose it bit by bit as they go
+//! stale, so the score does not drop all at once the day after a deadline. Each factor which falls short says what
+//! would earn the rest of its weight, for the GUI to show as what to improve.
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use shared_no_std::constants::{IOC_FUZZY_LIST_LOCATION, IOC_LIST_LOCATION, IOC_SECTION_LIST_LOCATION};
+use shared_std::{
+    diagnostics::{ProtectionFactor, ProtectionFactorKind, ProtectionScore},
+    driver_manager::DriverAvailability,
+    settings::{RealtimeAction, SanctumSettings},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    driver_manager::SanctumDriverManager, filescanner::FileScanner, history::ScanHistory, realtime::RealtimeSnooze,
+};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The most each factor adds to the score, adding up to 100. The score is tuned by changing these.
+pub const FACTOR_WEIGHTS: [(ProtectionFactorKind, u32); 5] = [
+    (ProtectionFactorKind::RealtimeProtection, 30),
+    (ProtectionFactorKind::DriverLoaded, 20),
+    (ProtectionFactorKind::SignaturesUpToDate, 20),
+    (ProtectionFactorKind::RecentFullScan, 15),
+    (ProtectionFactorKind::NoOutstandingDetections, 15),
+];
+
+/// Signatures updated within this long earn their full weight, which falls away until they are [`SIGNATURES_STALE`]
+const SIGNATURES_FRESH: Duration = Duration::from_secs(7 * SECS_PER_DAY);
+const SIGNATURES_STALE: Duration = Duration::from_secs(30 * SECS_PER_DAY);
+
+/// A full scan within this long earns its full weight, which falls away until it is [`FULL_SCAN_STALE`]
+const FULL_SCAN_FRESH: Duration = Duration::from_secs(7 * SECS_PER_DAY);
+const FULL_SCAN_STALE: Duration = Duration::from_secs(30 * SECS_PER_DAY);
+
+/// The share of real-time protection's weight earned where it only logs detections, as it still sees them
+const LOG_ONLY_CREDIT: f64 = 0.5;
+
+/// What was found for a factor, before it is weighted
+struct Assessment {
+    /// The share of the factor's weight earned, from 0 to 1
+    credit: f64,
+    detail: String,
+    improvement: String,
+}
+
+/// Works out the protection score from the engine's current state and scan history
+pub async fn protection_score(
+    file_scanner: &FileScanner,
+    driver_manager: &Arc<Mutex<SanctumDriverManager>>,
+    history: &ScanHistory,
+    settings: &Arc<Mutex<SanctumSettings>>,
+    realtime_snooze: &RealtimeSnooze,
+) -> ProtectionScore {
+    let realtime_action = settings.lock().await.realtime.action;
+    let driver = driver_manager.lock().await.get_availability();
+    let now = SystemTime::now();
+
+    let factors: Vec<ProtectionFactor> = FACTOR_WEIGHTS
+        .iter()
+        .map(|&(kind, weight)| {
+            let assessment = match kind {
+                ProtectionFactorKind::RealtimeProtection => {
+                    assess_realtime(realtime_action, realtime_snooze.remaining())
+                }
+                ProtectionFactorKind::DriverLoaded => assess_driver(&driver),
+                ProtectionFactorKind::SignaturesUpToDate => assess_signatures(file_scanner, now),
+                ProtectionFactorKind::RecentFullScan => assess_full_scan(history.last_full_scan(), now),
+                ProtectionFactorKind::NoOutstandingDetections => assess_detections(history.outstanding_detections()),
+            };
+
+            let points = (weight as f64 * assessment.credit.clamp(0.0, 1.0)).round() as u32;
+
+            ProtectionFactor {
+                kind,
+                weight,
+                points,
+                detail: assessment.detail,
+                improvement: (points < weight).then_some(assessment.improvement),
+            }
+        })
+        .collect();
+
+    ProtectionScore {
+        score: factors.iter().map(|f| f.points).sum::<u32>().min(100),
+        factors,
+    }
+}
+
+fn assess_realtime(action: RealtimeAction, snooze_remaining: Option<Duration>) -> Assessment {
+    let improvement = "Resume real-time protection, and set it to block or quarantine detected files.".to_string();
+
+    if let Some(remaining) = snooze_remaining {
+        return Assessment {
+            credit: 0.0,
+            detail: format!(
+                "Real-time protection is snoozed for another {} minutes.",
+                remaining.as_secs().div_ceil(60)
+            ),
+            improvement,
+        };
+    }
+
+    match action {
+        RealtimeAction::LogOnly => Assessment {
+            credit: LOG_ONLY_CREDIT,
+            detail: "Real-time protection only logs the files it detects, leaving them to be opened.".to_string(),
+            improvement,
+        },
+        RealtimeAction::Block | RealtimeAction::Quarantine => Assessment {
+            credit: 1.0,
+            detail: "Real-time protection is on.".to_string(),
+            improvement,
+        },
+    }
+}
+
+fn assess_driver(driver: &DriverAvailability) -> Assessment {
+    let improvement = "Install and start the driver from the settings page.".to_string();
+
+    match driver {
+        DriverAvailability::Available => Assessment {
+            credit: 1.0,
+            detail: "The driver is loaded.".to_string(),
+            improvement,
+        },
+        DriverAvailability::Unavailable { reason } => Assessment {
+            credit: 0.0,
+            detail: format!("The driver is not loaded. {reason}"),
+            improvement,
+        },
+    }
+}
+
+fn assess_signatures(file_scanner: &FileScanner, now: SystemTime) -> Assessment {
+    let improvement = "Update the signatures.".to_string();
+
+    if file_scanner.signature_count() == 0 {
+        return Assessment {
+            credit: 0.0,
+            detail: "No signatures are loaded.".to_string(),
+            improvement,
+        };
+    }
+
+    // a time ahead of the clock, as where the clock was put back, counts as just now
+    match signatures_updated(file_scanner).map(|updated| now.duration_since(updated).unwrap_or_default()) {
+        Some(age) => Assessment {
+            credit: recency_credit(age, SIGNATURES_FRESH, SIGNATURES_STALE),
+            detail: format!("The signatures were updated {}.", describe_age(age)),
+            improvement,
+        },
+        None => Assessment {
+            credit: 0.0,
+            detail: "When the signatures were last updated is not known.".to_string(),
+            improvement,
+        },
+    }
+}
+
+fn assess_full_scan(last_full_scan: Option<SystemTime>, now: SystemTime) -> Assessment {
+    let improvement = "Run a full scan of your drives.".to_string();
+
+    match last_full_scan.map(|finished| now.duration_since(finished).unwrap_or_default()) {
+        Some(age) => Assessment {
+            credit: recency_credit(age, FULL_SCAN_FRESH, FULL_SCAN_STALE),
+            detail: format!("The last full scan finished {}.", describe_age(age)),
+            improvement,
+        },
+        None => Assessment {
+            credit: 0.0,
+            detail: "No full scan has finished.".to_string(),
+            improvement,
+        },
+    }
+}
+
+fn assess_detections(outstanding: usize) -> Assessment {
+    Assessment {
+        credit: if outstanding == 0 { 1.0 } else { 0.0 },
+        detail: match outstanding {
+            0 => "Every detected file has been dealt with.".to_string(),
+            1 => "1 detected file has been left in place.".to_string(),
+            n => format!("{n} detected files have been left in place."),
+        },
+        improvement: "Quarantine or delete the detected files, or triage them as benign.".to_string(),
+    }
+}
+
+/// When the signatures on disk were last changed, being the newest of the IOC lists and the signature files
+fn signatures_updated(file_scanner: &FileScanner) -> Option<SystemTime> {
+    let app_data = PathBuf::from(env::var("APPDATA").ok()?);
+
+    [IOC_LIST_LOCATION, IOC_SECTION_LIST_LOCATION, IOC_FUZZY_LIST_LOCATION]
+        .iter()
+        .map(|location| app_data.join(location))
+        .chain(file_scanner.signature_files().into_iter().map(|file| file.path))
+        .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+/// The share of a factor's weight earned by something of the age, which is all of it up to `fresh`, falling evenly to
+/// none at `stale`
+fn recency_credit(age: Duration, fresh: Duration, stale: Duration) -> f64 {
+    if age <= fresh {
+        return 1.0;
+    }
+
+    (1.0 - (age - fresh).as_secs_f64() / (stale - fresh).as_secs_f64()).max(0.0)
+}
+
+fn describe_age(age: Duration) -> String {
+    match age.as_secs() / SECS_PER_DAY {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{days} days ago"),
+    }
+}
@@ -0,0 +1,979 @@
+//! The quarantine store, where files detected as malicious are held so they can no longer run.
+//!
+//! Quarantined files live in the quarantine folder named by their entry ID, alongside a manifest describing where
+//! each came from. The manifest is the source of truth: a file without an entry cannot be restored, and an entry
+//! without a file has nothing to restore.
+//!
+//! Quarantined files are held obfuscated, XORed with a keystream particular to their entry, so that a sample in
+//! quarantine can neither be run nor be found by another scanner. This is not encryption: the key is not secret, as
+//! anyone able to read the quarantine folder may restore or extract its files anyway.
+//!
+//! So that the two never disagree, a file is quarantined in two phases. A pending entry is written to the manifest
+//! before the file is moved, and committed once it has been. An engine which stops part way through leaves a pending
+//! entry behind, which is completed or rolled back when the store is next loaded, depending on where the file is.
+//!
+//! A quarantined file can also be extracted for analysis without being restored. The copy is defanged, so that it is
+//! not run or opened by accident, and the file stays in quarantine.
+//!
+//! A file restored from quarantine is copied in beside its original path and checked against the hash recorded when
+//! it was quarantined before it is put in place, so a file which changed whilst in quarantine is never restored, and a
+//! restore which fails part way through never takes the place of another file. Where the original path now holds
+//! another file, a [`RestoreConflictPolicy`] decides what is done with the two.
+//!
+//! Alongside the manifest is kept an index of the latest entry for each original path, rebuilt whenever the manifest
+//! is written, so whether a file is quarantined can be answered without going over every entry.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, Read, Write},
+    iter,
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    ptr,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+use shared_no_std::constants::{QUARANTINE_LOCATION, QUARANTINE_MANIFEST_NAME};
+use shared_std::quarantine::{
+    EXTRACTED_EXTENSION, QuarantineEntry, QuarantineExtraction, QuarantineMismatch, QuarantineReport,
+    QuarantineRestoration, RestoreConflictPolicy,
+};
+use windows::{
+    Win32::{
+        Foundation::{HLOCAL, LocalFree},
+        Security::{
+            ACL,
+            Authorization::{
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1, SE_FILE_OBJECT,
+                SetNamedSecurityInfoW,
+            },
+            DACL_SECURITY_INFORMATION, GetSecurityDescriptorDacl, PROTECTED_DACL_SECURITY_INFORMATION,
+            PSECURITY_DESCRIPTOR,
+        },
+    },
+    core::{BOOL, PCWSTR},
+};
+
+use crate::utils::{
+    files::write_atomic,
+    log::{Log, LogLevel},
+};
+
+pub struct QuarantineStore {
+    dir: PathBuf,
+    manifest: Mutex<Vec<QuarantineEntry>>,
+    /// Set when the manifest on disk could not be read, in which case nothing which relies on the manifest being
+    /// complete (such as pruning orphaned files) is done, as every quarantined file would look orphaned
+    manifest_corrupt: bool,
+    /// The latest committed entry for each original path, by [`path_key`]. Always taken after the manifest, where both
+    /// are held.
+    by_path: Mutex<HashMap<String, QuarantineEntry>>,
+}
+
+impl QuarantineStore {
+    /// Loads the quarantine manifest from disk, creating the quarantine folder if it does not yet exist.
+    pub fn load() -> Self {
+        let app_data = std::env::var("APPDATA")
+            .expect("[-] Could not find App Data folder in environment variables.]");
+        Self::load_from(Path::new(&app_data))
+    }
+
+    /// Loads the quarantine manifest kept under the App Data folder given, creating the quarantine folder if it does
+    /// not yet exist
+    pub(crate) fn load_from(app_data: &Path) -> Self {
+        let dir = app_data.join(QUARANTINE_LOCATION);
+
+        if let Err(e) = fs::create_dir_all(&dir) {
+            Log::new().log(
+                LogLevel::Error,
+                &format!("Could not create quarantine folder {}. {e}", dir.display()),
+            );
+        }
+
+        let mut manifest_corrupt = false;
+        let manifest = match fs::read(dir.join(QUARANTINE_MANIFEST_NAME)) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(m) => m,
+                Err(e) => {
+                    Log::new().log(LogLevel::Error, &format!("Quarantine manifest is corrupt. {e}"));
+                    manifest_corrupt = true;
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+
+        let store = QuarantineStore {
+            dir,
+            by_path: Mutex::new(index_by_path(&manifest)),
+            manifest: Mutex::new(manifest),
+            manifest_corrupt,
+        };
+        if !store.manifest_corrupt {
+            store.recover_pending();
+        }
+
+        store
+    }
+
+    /// Completes or rolls back the entries left pending by an engine which stopped part way through quarantining a
+    /// file. An entry whose file was moved into quarantine is committed. An entry whose file was not moved, or was only
+    /// partly copied and so is still in place, is removed, along with any partial copy.
+    fn recover_pending(&self) {
+        let logger = Log::new();
+        let mut manifest = self.manifest.lock().unwrap();
+        if !manifest.iter().any(|entry| entry.pending) {
+            return;
+        }
+
+        let mut recovered = Vec::with_capacity(manifest.len());
+        for mut entry in manifest.drain(..) {
+            if !entry.pending {
+                recovered.push(entry);
+                continue;
+            }
+
+            let dest = self.dir.join(&entry.id);
+            if dest.is_file() && !entry.original_path.exists() {
+                entry.size = fs::metadata(&dest).map(|m| m.len()).ok();
+                entry.sha256 = self.open_quarantined(&entry).and_then(|mut file| sha256_of(&mut file)).ok();
+                entry.pending = false;
+
+                logger.log(
+                    LogLevel::Info,
+                    &format!("Completed quarantining {}, which was interrupted.", entry.original_path.display()),
+                );
+                recovered.push(entry);
+            } else {
+                let _ = fs::remove_file(&dest);
+
+                logger.log(
+                    LogLevel::Warning,
+                    &format!(
+                        "Rolled back quarantining {}, which was interrupted. The file remains where it was.",
+                        entry.original_path.display()
+                    ),
+                );
+            }
+        }
+        *manifest = recovered;
+
+        if let Err(e) = self.persist(&manifest) {
+            logger.log(
+                LogLevel::Error,
+                &format!("Could not record the recovered quarantine entries, they will be recovered again. {e}"),
+            );
+        }
+    }
+
+    /// Moves a file into quarantine, recording it in the manifest so it can later be restored.
+    ///
+    /// The file is copied into the quarantine folder, obfuscated as it is copied, and then removed. If the original
+    /// cannot be removed (such as when it is in use), the copy is removed again, so a file is never left both in
+    /// quarantine and in place.
+    ///
+    /// Quarantining a file which is already quarantined does nothing, returning its existing entry. That is a file
+    /// within the quarantine folder, or one which is no longer in place and was quarantined from there with the same
+    /// hash, such as when a scan is retried after its detections were quarantined.
+    ///
+    /// # Returns
+    ///
+    /// The manifest entry for the quarantined file, or an error if it could not be quarantined, in which case the file
+    /// remains where it was.
+    pub fn quarantine(&self, path: &Path, hash: &str) -> io::Result<QuarantineEntry> {
+        // writing the manifest now would overwrite the entries which could not be read
+        if self.manifest_corrupt {
+            return Err(io::Error::other(
+                "The quarantine manifest is corrupt, so no more files can be quarantined.",
+            ));
+        }
+
+        let mut manifest = self.manifest.lock().unwrap();
+
+        let in_quarantine = path.parent() == Some(self.dir.as_path());
+        let existing = manifest.iter().find(|entry| match in_quarantine {
+            true => path.file_name().is_some_and(|name| *name == *entry.id),
+            false => !path.exists() && entry.original_path == path && entry.hash == hash,
+        });
+        if let Some(existing) = existing {
+            return Ok(existing.clone());
+        }
+
+        let quarantined_at = SystemTime::now();
+        let millis = quarantined_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let id = free_id(&self.dir, &manifest, millis, hash);
+        let dest = self.dir.join(&id);
+
+        // pending until the file has been moved, so an engine which stops part way through can tell where it got to
+        manifest.push(QuarantineEntry {
+            id,
+            original_path: path.to_path_buf(),
+            hash: hash.to_string(),
+            quarantined_at,
+            size: None,
+            sha256: None,
+            obfuscated: true,
+            pending: true,
+        });
+        if let Err(e) = self.persist(&manifest) {
+            manifest.pop();
+            return Err(e);
+        }
+
+        // the ID is free, so this never opens another file in quarantine, which is not removed where it fails
+        let mut copy = match File::options().write(true).create_new(true).open(&dest) {
+            Ok(copy) => copy,
+            Err(e) => {
+                self.abandon_pending(&mut manifest);
+                return Err(e);
+            }
+        };
+
+        // the original is only removed once the copy is complete, so an engine which stops part way through leaves the
+        // file in place, and its entry is rolled back
+        let id = &manifest.last().unwrap().id;
+        let copied = File::open(path)
+            .and_then(|mut original| copy_obfuscated(&mut original, &mut copy, id))
+            .and_then(|copied| copy.sync_all().map(|_| copied));
+        drop(copy);
+        let (size, sha256) = match copied.and_then(|copied| fs::remove_file(path).map(|_| copied)) {
+            Ok(copied) => copied,
+            Err(e) => {
+                let _ = fs::remove_file(&dest);
+                self.abandon_pending(&mut manifest);
+                return Err(e);
+            }
+        };
+
+        // committed; the entry is the last in the manifest, as the lock has been held since it was pushed
+        let entry = manifest.last_mut().unwrap();
+        entry.size = Some(size);
+        entry.sha256 = Some(sha256);
+        entry.pending = false;
+        let entry = entry.clone();
+
+        // the file is in quarantine either way; an entry left pending on disk is committed when the store next loads
+        if let Err(e) = self.persist(&manifest) {
+            Log::new().log(
+                LogLevel::Warning,
+                &format!(
+                    "Quarantined {}, but could not commit its manifest entry. It is committed when the engine next starts. {e}",
+                    path.display()
+                ),
+            );
+        }
+
+        Ok(entry)
+    }
+
+    /// Removes the pending entry which is the last in the manifest, as its file could not be moved into quarantine. If
+    /// the manifest cannot be written, the entry is rolled back when the store next loads, as its file is not in
+    /// quarantine.
+    fn abandon_pending(&self, manifest: &mut Vec<QuarantineEntry>) {
+        manifest.pop();
+        if let Err(e) = self.persist(manifest) {
+            Log::new().log(
+                LogLevel::Warning,
+                &format!("Could not remove an abandoned quarantine entry, it is removed when the engine next starts. {e}"),
+            );
+        }
+    }
+
+    /// Removes files in the quarantine folder which have no manifest entry, and manifest entries whose file is
+    /// missing.
+    ///
+    /// # Returns
+    ///
+    /// The orphaned files which were deleted, and the orphaned entries which were removed.
+    pub fn prune_orphans(&self) -> io::Result<(Vec<PathBuf>, Vec<QuarantineEntry>)> {
+        if self.manifest_corrupt {
+            return Err(io::Error::other(
+                "The quarantine manifest is corrupt, so orphaned files cannot be identified.",
+            ));
+        }
+
+        let mut manifest = self.manifest.lock().unwrap();
+
+        // entries whose file has gone
+        let (kept, orphaned_entries): (Vec<_>, Vec<_>) = manifest
+            .drain(..)
+            .partition(|entry| self.dir.join(&entry.id).is_file());
+        *manifest = kept;
+
+        if !orphaned_entries.is_empty() {
+            self.persist(&manifest)?;
+        }
+
+        // files with no entry
+        let ids: BTreeSet<&str> = manifest.iter().map(|e| e.id.as_str()).collect();
+        let mut orphaned_files = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name == QUARANTINE_MANIFEST_NAME || ids.contains(name.as_ref()) {
+                continue;
+            }
+
+            fs::remove_file(&path)?;
+            orphaned_files.push(path);
+        }
+
+        Ok((orphaned_files, orphaned_entries))
+    }
+
+    /// Checks each manifest entry against its file in the quarantine folder, that the file exists and has the size and
+    /// hash it had when quarantined, and finds files in the folder which have no entry. Where `repair` is set, entries
+    /// whose file is missing are removed from the manifest, as they can never be restored. Nothing else is changed;
+    /// orphaned files are removed by maintenance.
+    ///
+    /// # Returns
+    ///
+    /// What was found, or an error if the manifest is corrupt or the quarantine folder could not be read.
+    pub fn verify(&self, repair: bool) -> io::Result<QuarantineReport> {
+        if self.manifest_corrupt {
+            return Err(io::Error::other(
+                "The quarantine manifest is corrupt, so it cannot be verified against the quarantined files.",
+            ));
+        }
+
+        let mut manifest = self.manifest.lock().unwrap();
+        let mut report = QuarantineReport {
+            entries_checked: manifest.len(),
+            ..Default::default()
+        };
+
+        for entry in manifest.iter() {
+            let file = self.dir.join(&entry.id);
+            let metadata = match fs::metadata(&file) {
+                Ok(m) if m.is_file() => m,
+                _ => {
+                    report.dangling_entries.push(entry.clone());
+                    continue;
+                }
+            };
+
+            let (Some(size), Some(expected_hash)) = (entry.size, &entry.sha256) else {
+                report.unverified += 1;
+                continue;
+            };
+
+            let problem = if metadata.len() != size {
+                Some(format!("size is {} bytes, but was {size} bytes", metadata.len()))
+            } else {
+                match self.open_quarantined(entry).and_then(|mut file| sha256_of(&mut file)) {
+                    Ok(actual) if actual.eq_ignore_ascii_case(expected_hash) => None,
+                    Ok(actual) => Some(format!("SHA256 is {actual}, but was {expected_hash}")),
+                    Err(e) => Some(format!("could not be read to check its hash. {e}")),
+                }
+            };
+
+            match problem {
+                Some(problem) => report.mismatched.push(QuarantineMismatch {
+                    entry: entry.clone(),
+                    problem,
+                }),
+                None => report.healthy += 1,
+            }
+        }
+
+        // files with no entry
+        let ids: BTreeSet<&str> = manifest.iter().map(|e| e.id.as_str()).collect();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name != QUARANTINE_MANIFEST_NAME && !ids.contains(name.as_ref()) {
+                report.orphaned_files.push(path);
+            }
+        }
+
+        if repair && !report.dangling_entries.is_empty() {
+            manifest.retain(|entry| !report.dangling_entries.contains(entry));
+            self.persist(&manifest)?;
+            report.repaired = true;
+
+            Log::new().log(
+                LogLevel::Info,
+                &format!(
+                    "Removed {} quarantine entries whose file was missing.",
+                    report.dangling_entries.len()
+                ),
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Copies a quarantined file out of quarantine for analysis, such as in a sandbox, leaving its entry and file in
+    /// quarantine. The copy is defanged: it is given the [`EXTRACTED_EXTENSION`] extension, and its permissions deny
+    /// everyone executing it, administrators included. A file already at the destination is never overwritten.
+    ///
+    /// # Args
+    ///
+    /// - `dest`: The folder to extract into, where the copy is named after the original file, or the path of the copy
+    ///
+    /// # Returns
+    ///
+    /// The extracted copy, or an error if there is no such entry or the copy could not be made, in which case nothing
+    /// is left at the destination.
+    pub fn extract(&self, id: &str, dest: &Path) -> io::Result<QuarantineExtraction> {
+        let entry = self
+            .manifest
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.id == id && !entry.pending)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No quarantined file has the ID {id}.")))?;
+
+        let path = extracted_path(&entry, dest);
+        if path.starts_with(&self.dir) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A quarantined file cannot be extracted into the quarantine folder.",
+            ));
+        }
+
+        // the copy is defanged before anything is written to it, so there is never a runnable copy on disk
+        let mut copy = File::options().write(true).create_new(true).open(&path)?;
+        let written = deny_execute(&path)
+            .and_then(|_| io::copy(&mut self.open_quarantined(&entry)?, &mut copy))
+            .and_then(|_| copy.sync_all());
+        drop(copy);
+
+        let sha256 = written.and_then(|_| sha256_file(&path)).and_then(|sha256| match &entry.sha256 {
+            Some(expected) if !expected.eq_ignore_ascii_case(&sha256) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The quarantined file's SHA256 is {sha256}, but was {expected}, so it was not extracted."),
+            )),
+            _ => Ok(sha256),
+        });
+        if sha256.is_err() {
+            let _ = fs::remove_file(&path);
+        }
+
+        Ok(QuarantineExtraction {
+            entry,
+            path,
+            sha256: sha256?,
+        })
+    }
+
+    /// Restores a quarantined file to where it was quarantined from, removing it from quarantine. Where the original
+    /// path now holds another file, the policy decides what is done, and by default nothing is restored.
+    ///
+    /// # Returns
+    ///
+    /// What was restored and where, or an error if there is no such entry, the original path is taken and the policy
+    /// is to fail, or the file could not be restored, in which case it stays in quarantine and any file at the
+    /// original path is left as it was.
+    pub fn restore(&self, id: &str, policy: RestoreConflictPolicy) -> io::Result<QuarantineRestoration> {
+        // writing the manifest now would overwrite the entries which could not be read
+        if self.manifest_corrupt {
+            return Err(io::Error::other("The quarantine manifest is corrupt, so no file can be restored."));
+        }
+
+        let mut manifest = self.manifest.lock().unwrap();
+        let index = manifest
+            .iter()
+            .position(|entry| entry.id == id && !entry.pending)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No quarantined file has the ID {id}.")))?;
+        let entry = manifest[index].clone();
+        let original = entry.original_path.clone();
+
+        // a link at the original path takes it as much as a file does, so the link itself is looked for
+        let conflict = original.symlink_metadata().is_ok().then_some(policy);
+        let taken = || {
+            io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} holds another file, so the quarantined file was not restored.", original.display()),
+            )
+        };
+        if conflict == Some(RestoreConflictPolicy::Fail) {
+            return Err(taken());
+        }
+
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // staged beside the original path, so it is put in place by a rename within the folder
+        let staged = free_path(&original, "restoring");
+        let written = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&staged)
+            .and_then(|mut copy| {
+                io::copy(&mut self.open_quarantined(&entry)?, &mut copy)?;
+                copy.sync_all()
+            });
+
+        let sha256 = written.and_then(|_| sha256_file(&staged)).and_then(|sha256| match &entry.sha256 {
+            Some(expected) if !expected.eq_ignore_ascii_case(&sha256) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The quarantined file's SHA256 is {sha256}, but was {expected}, so it was not restored."),
+            )),
+            _ => Ok(sha256),
+        });
+
+        let placed = sha256.and_then(|sha256| {
+            let (path, existing_renamed_to) = match conflict {
+                // a rename replaces any file at the destination
+                None | Some(RestoreConflictPolicy::Overwrite) => {
+                    fs::rename(&staged, &original)?;
+                    (original.clone(), None)
+                }
+                Some(RestoreConflictPolicy::RenameRestored) => {
+                    let path = free_path(&original, "restored");
+                    fs::rename(&staged, &path)?;
+                    (path, None)
+                }
+                Some(RestoreConflictPolicy::RenameExisting) => {
+                    let renamed = free_path(&original, "existing");
+                    fs::rename(&original, &renamed)?;
+                    if let Err(e) = fs::rename(&staged, &original) {
+                        let _ = fs::rename(&renamed, &original);
+                        return Err(e);
+                    }
+                    (original.clone(), Some(renamed))
+                }
+                Some(RestoreConflictPolicy::Fail) => return Err(taken()),
+            };
+
+            Ok((path, existing_renamed_to, sha256))
+        });
+
+        let (path, existing_renamed_to, sha256) = match placed {
+            Ok(placed) => placed,
+            Err(e) => {
+                let _ = fs::remove_file(&staged);
+                return Err(e);
+            }
+        };
+
+        // a quarantined file left behind is removed as orphaned by maintenance once its entry is gone
+        let _ = fs::remove_file(self.dir.join(&entry.id));
+        manifest.remove(index);
+        if let Err(e) = self.persist(&manifest) {
+            Log::new().log(
+                LogLevel::Warning,
+                &format!(
+                    "Restored {}, but could not remove its quarantine entry, which is removed by repairing the \
+                     quarantine. {e}",
+                    original.display()
+                ),
+            );
+        }
+
+        Ok(QuarantineRestoration {
+            verified: entry.sha256.is_some(),
+            entry,
+            path,
+            sha256,
+            conflict,
+            existing_renamed_to,
+        })
+    }
+
+    /// Gets the latest entry quarantining the file from its original path, or None where the file has never been
+    /// quarantined from there. A file quarantined from the same path more than once, such as one malware keeps
+    /// dropping, has an entry for each time, of which the latest is given.
+    pub fn status(&self, path: &Path) -> Option<QuarantineEntry> {
+        self.by_path.lock().unwrap().get(&path_key(path)).cloned()
+    }
+
+    /// Opens the file held in quarantine for the entry, which reads as the file was before it was quarantined
+    fn open_quarantined(&self, entry: &QuarantineEntry) -> io::Result<QuarantinedFile> {
+        Ok(QuarantinedFile {
+            file: File::open(self.dir.join(&entry.id))?,
+            keystream: entry.obfuscated.then(|| Keystream::new(&entry.id)),
+        })
+    }
+
+    /// Writes the manifest to disk, first updating the index from it, so the index matches the manifest held in
+    /// memory even where it cannot be written
+    fn persist(&self, manifest: &[QuarantineEntry]) -> io::Result<()> {
+        *self.by_path.lock().unwrap() = index_by_path(manifest);
+
+        write_atomic(
+            &self.dir.join(QUARANTINE_MANIFEST_NAME),
+            &serde_json::to_vec(manifest)?,
+        )
+    }
+}
+
+/// Indexes the committed entries of the manifest by their original path, keeping the latest entry for each path
+fn index_by_path(manifest: &[QuarantineEntry]) -> HashMap<String, QuarantineEntry> {
+    let mut index: HashMap<String, QuarantineEntry> = HashMap::new();
+
+    for entry in manifest.iter().filter(|entry| !entry.pending) {
+        let key = path_key(&entry.original_path);
+        if index.get(&key).is_none_or(|latest| latest.quarantined_at <= entry.quarantined_at) {
+            index.insert(key, entry.clone());
+        }
+    }
+
+    index
+}
+
+/// The form of a path the index is keyed by, as paths on Windows are case insensitive
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+/// The permissions given to a sample extracted from quarantine: everyone is denied executing it, and otherwise only
+/// the system, administrators and the owner of the copy, being whoever extracted it, may access it. Only
+/// FILE_EXECUTE (`0x20`) is denied, as the generic execute right `FX` also holds the rights needed to read the file.
+/// The permissions are protected, so none are inherited from the folder.
+const EXTRACTED_SDDL: &str = "D:P(D;;0x20;;;WD)(A;;FA;;;SY)(A;;FA;;;BA)(A;;FA;;;OW)";
+
+/// Gets the path a quarantined file is extracted to, being the destination itself unless it is a folder, in which
+/// case the copy is named after the original file. The path always ends with [`EXTRACTED_EXTENSION`], so that the
+/// copy is not opened as its original type.
+fn extracted_path(entry: &QuarantineEntry, dest: &Path) -> PathBuf {
+    let mut path = match dest.is_dir() {
+        true => dest.join(entry.original_path.file_name().unwrap_or(OsStr::new(&entry.id))),
+        false => dest.to_path_buf(),
+    };
+
+    if path.extension().is_none_or(|extension| *extension != *EXTRACTED_EXTENSION) {
+        let mut name = OsString::from(path.file_name().unwrap_or_default());
+        name.push(".");
+        name.push(EXTRACTED_EXTENSION);
+        path.set_file_name(name);
+    }
+
+    path
+}
+
+/// Gets an ID for a file quarantined at the time, named after its hash, which no entry or file in the quarantine folder
+/// has, such as `1700000000000-<hash>`, or `1700000000000-2-<hash>` where that is taken. Copies of one sample quarantined
+/// within the same millisecond, such as from a batch, would otherwise share an ID, with the second moved over the
+/// first. The ID is chosen whilst the manifest is locked, so its file stays free until the file is moved there.
+fn free_id(dir: &Path, manifest: &[QuarantineEntry], millis: u128, hash: &str) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => format!("{millis}-{hash}"),
+            n => format!("{millis}-{n}-{hash}"),
+        })
+        .find(|id| !manifest.iter().any(|entry| entry.id == *id) && dir.join(id).symlink_metadata().is_err())
+        .unwrap()
+}
+
+/// Gets a path beside the file which no file is at, named after it with the label, such as `setup (restored).exe`,
+/// or `setup (restored 2).exe` where that is taken too
+fn free_path(path: &Path, label: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| match n {
+            1 => path.with_file_name(format!("{stem} ({label}){extension}")),
+            n => path.with_file_name(format!("{stem} ({label} {n}){extension}")),
+        })
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .unwrap()
+}
+
+/// Replaces the permissions of the file with [`EXTRACTED_SDDL`], so that nobody can execute it
+fn deny_execute(path: &Path) -> io::Result<()> {
+    let sddl: Vec<u16> = EXTRACTED_SDDL.encode_utf16().chain(iter::once(0)).collect();
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(iter::once(0)).collect();
+
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+    unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            PCWSTR::from_raw(sddl.as_ptr()),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )
+    }
+    .map_err(io::Error::other)?;
+
+    let mut present = BOOL::default();
+    let mut defaulted = BOOL::default();
+    let mut dacl: *mut ACL = ptr::null_mut();
+    let result = unsafe { GetSecurityDescriptorDacl(descriptor, &mut present, &mut dacl, &mut defaulted) }
+        .map_err(io::Error::other)
+        .and_then(|_| {
+            unsafe {
+                SetNamedSecurityInfoW(
+                    PCWSTR::from_raw(wide_path.as_ptr()),
+                    SE_FILE_OBJECT,
+                    DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                    None,
+                    None,
+                    Some(dacl as *const ACL),
+                    None,
+                )
+            }
+            .ok()
+            .map_err(io::Error::other)
+        });
+
+    // the descriptor was allocated by ConvertStringSecurityDescriptorToSecurityDescriptorW
+    let _ = unsafe { LocalFree(Some(HLOCAL(descriptor.0))) };
+
+    result
+}
+
+/// Hashes the whole of the file with SHA256, as lowercase hex
+fn sha256_file(path: &Path) -> io::Result<String> {
+    sha256_of(&mut File::open(path)?)
+}
+
+/// Hashes everything read from the reader with SHA256, as lowercase hex
+fn sha256_of(reader: &mut impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The key quarantined files are obfuscated with. It need not be secret, as it only keeps files in quarantine from
+/// being run or matched by a scanner.
+const OBFUSCATION_KEY: [u8; 16] = [
+    0x53, 0x9d, 0x2e, 0x71, 0xc4, 0x08, 0xb6, 0x5f, 0xe3, 0x1a, 0x97, 0x6c, 0x40, 0xf2, 0x8b, 0x25,
+];
+
+/// SHA256 in counter mode over the key and the ID of an entry, XORed over the file held for the entry, so that no two
+/// quarantined files are obfuscated alike. XORing with the keystream a second time gives back the file as it was.
+struct Keystream {
+    keyed: Sha256,
+    block: [u8; 32],
+    counter: u64,
+    used: usize,
+}
+
+impl Keystream {
+    fn new(id: &str) -> Self {
+        let mut keyed = Sha256::new();
+        keyed.update(OBFUSCATION_KEY);
+        keyed.update(id.as_bytes());
+
+        Keystream {
+            keyed,
+            block: [0; 32],
+            counter: 0,
+            used: 32,
+        }
+    }
+
+    /// XORs the bytes, which follow those the keystream was last applied to, with the keystream
+    fn apply(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            if self.used == self.block.len() {
+                let mut hasher = self.keyed.clone();
+                hasher.update(self.counter.to_le_bytes());
+                self.block = hasher.finalize().into();
+                self.counter += 1;
+                self.used = 0;
+            }
+
+            *byte ^= self.block[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+/// A file held in quarantine, which reads as the file was before it was quarantined
+struct QuarantinedFile {
+    file: File,
+    /// The keystream the file was obfuscated with, None where it was quarantined before files were obfuscated
+    keystream: Option<Keystream>,
+}
+
+impl Read for QuarantinedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.file.read(buf)?;
+        if let Some(keystream) = &mut self.keystream {
+            keystream.apply(&mut buf[..count]);
+        }
+
+        Ok(count)
+    }
+}
+
+/// Copies a file into quarantine for the entry of the ID, obfuscating it as it is copied
+///
+/// # Returns
+///
+/// The size and SHA256 of the file as it was, so that it can be verified once restored.
+fn copy_obfuscated(original: &mut File, copy: &mut File, id: &str) -> io::Result<(u64, String)> {
+    let mut keystream = Keystream::new(id);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let count = match original.read(&mut buf) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        hasher.update(&buf[..count]);
+        keystream.apply(&mut buf[..count]);
+        copy.write_all(&buf[..count])?;
+        size += count as u64;
+    }
+
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filescanner::filescanner::tests::test_dir;
+
+    const SAMPLE: &[u8] = b"MZ a sample held in quarantine";
+
+    /// A store in the folder holding one sample, quarantined from `sample.exe` in the folder
+    fn quarantined(dir: &Path) -> (QuarantineStore, QuarantineEntry) {
+        let store = QuarantineStore::load_from(dir);
+        let original = dir.join("sample.exe");
+        fs::write(&original, SAMPLE).unwrap();
+
+        let entry = store.quarantine(&original, "d41d8cd98f00b204e9800998ecf8427e").unwrap();
+        (store, entry)
+    }
+
+    #[test]
+    fn extracted_sample_reads_back_as_it_was_quarantined() {
+        let dir = test_dir("quarantine-extract");
+        let (store, entry) = quarantined(&dir);
+        let dest = dir.join("extracted");
+        fs::create_dir_all(&dest).unwrap();
+
+        let extraction = store.extract(&entry.id, &dest).unwrap();
+        assert_eq!(extraction.path, dest.join(format!("sample.exe.{EXTRACTED_EXTENSION}")));
+        assert_eq!(fs::read(&extraction.path).unwrap(), SAMPLE);
+        assert_eq!(Some(extraction.sha256), entry.sha256);
+
+        // the sample stays in quarantine
+        assert_eq!(store.status(&entry.original_path), Some(entry));
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const ANOTHER: &[u8] = b"another file since put at the original path";
+
+    /// A store in a folder of the test's own holding one sample, whose original path has since been taken by another
+    /// file, with the folder
+    fn quarantined_and_taken(test: &str) -> (PathBuf, QuarantineStore, QuarantineEntry) {
+        let dir = test_dir(test);
+        let (store, entry) = quarantined(&dir);
+        fs::write(&entry.original_path, ANOTHER).unwrap();
+
+        (dir, store, entry)
+    }
+
+    #[test]
+    fn restore_to_a_free_path_puts_the_sample_back() {
+        let dir = test_dir("quarantine-restore-free");
+        let (store, entry) = quarantined(&dir);
+
+        let restored = store.restore(&entry.id, RestoreConflictPolicy::Fail).unwrap();
+        assert_eq!(restored.path, entry.original_path);
+        assert_eq!(restored.conflict, None);
+        assert!(restored.verified);
+        assert_eq!(fs::read(&entry.original_path).unwrap(), SAMPLE);
+        assert_eq!(store.status(&entry.original_path), None);
+        assert!(!store.dir.join(&entry.id).exists());
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_overwriting_replaces_the_file_at_the_original_path() {
+        let (dir, store, entry) = quarantined_and_taken("quarantine-restore-overwrite");
+
+        let restored = store.restore(&entry.id, RestoreConflictPolicy::Overwrite).unwrap();
+        assert_eq!(restored.path, entry.original_path);
+        assert_eq!(restored.conflict, Some(RestoreConflictPolicy::Overwrite));
+        assert_eq!(fs::read(&entry.original_path).unwrap(), SAMPLE);
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_renaming_the_restored_file_leaves_the_other_in_place() {
+        let (dir, store, entry) = quarantined_and_taken("quarantine-restore-rename");
+
+        let restored = store.restore(&entry.id, RestoreConflictPolicy::RenameRestored).unwrap();
+        assert_eq!(restored.path, dir.join("sample (restored).exe"));
+        assert_eq!(fs::read(&restored.path).unwrap(), SAMPLE);
+        assert_eq!(fs::read(&entry.original_path).unwrap(), ANOTHER);
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_renaming_the_existing_file_moves_it_aside() {
+        let (dir, store, entry) = quarantined_and_taken("quarantine-restore-existing");
+
+        let restored = store.restore(&entry.id, RestoreConflictPolicy::RenameExisting).unwrap();
+        let renamed = dir.join("sample (existing).exe");
+        assert_eq!(restored.path, entry.original_path);
+        assert_eq!(restored.existing_renamed_to, Some(renamed.clone()));
+        assert_eq!(fs::read(&entry.original_path).unwrap(), SAMPLE);
+        assert_eq!(fs::read(&renamed).unwrap(), ANOTHER);
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_failing_on_a_taken_path_changes_nothing() {
+        let (dir, store, entry) = quarantined_and_taken("quarantine-restore-fail");
+
+        let e = store.restore(&entry.id, RestoreConflictPolicy::Fail).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&entry.original_path).unwrap(), ANOTHER);
+        assert_eq!(store.status(&entry.original_path), Some(entry));
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tampered_sample_is_not_restored() {
+        let dir = test_dir("quarantine-restore-tampered");
+        let (store, entry) = quarantined(&dir);
+        fs::write(store.dir.join(&entry.id), b"not the sample which was quarantined").unwrap();
+
+        let e = store.restore(&entry.id, RestoreConflictPolicy::Fail).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // nothing is left at the original path, nor staged beside it, and the entry stays in quarantine
+        assert!(!entry.original_path.exists());
+        assert!(!dir.join("sample (restoring).exe").exists());
+        assert_eq!(store.status(&entry.original_path), Some(entry));
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
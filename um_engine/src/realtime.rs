@@ -0,0 +1,123 @@
+//! Snoozing real-time protection, turning it off for a bounded time so that it always comes back on by itself, rather
+//! than being turned off and forgotten about.
+//!
+//! A snooze is only held in memory, so restarting the engine, or the machine, ends it and protection is back on.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use shared_std::events::EngineEvent;
+
+use crate::{
+    events::EventBus,
+    utils::log::{Log, LogLevel},
+};
+
+/// The longest real-time protection may be snoozed for at once
+pub const MAX_REALTIME_SNOOZE: Duration = Duration::from_secs(4 * 60 * 60);
+
+pub struct RealtimeSnooze {
+    // when the current snooze ends, with the generation of the snooze, so that the timer of a snooze which has since
+    // been replaced or resumed early does not end the one after it
+    snoozed_until: Mutex<Option<(Instant, u64)>>,
+    generation: AtomicU64,
+    events: Arc<EventBus>,
+}
+
+impl RealtimeSnooze {
+    pub fn new(events: Arc<EventBus>) -> Self {
+        RealtimeSnooze {
+            snoozed_until: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            events,
+        }
+    }
+
+    /// Snoozes real-time protection for the duration, after which it resumes by itself. Snoozing whilst already
+    /// snoozed replaces the current snooze, so protection resumes once the new duration has passed.
+    ///
+    /// # Returns
+    ///
+    /// An error if the duration is zero or longer than [`MAX_REALTIME_SNOOZE`].
+    pub fn snooze(self: &Arc<Self>, duration: Duration) -> Result<(), String> {
+        if duration.is_zero() || duration > MAX_REALTIME_SNOOZE {
+            return Err(format!(
+                "Real-time protection can be snoozed for up to {} minutes.",
+                MAX_REALTIME_SNOOZE.as_secs() / 60
+            ));
+        }
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        *self.snoozed_until.lock().unwrap() = Some((Instant::now() + duration, generation));
+
+        Log::new().log(
+            LogLevel::Warning,
+            &format!(
+                "Real-time protection snoozed for {} seconds.",
+                duration.as_secs()
+            ),
+        );
+        self.events.publish(EngineEvent::RealtimeSnoozed { duration });
+
+        let snooze = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            snooze.end(Some(generation));
+        });
+
+        Ok(())
+    }
+
+    /// Resumes real-time protection before the snooze has run out.
+    ///
+    /// # Returns
+    ///
+    /// Whether protection was snoozed.
+    pub fn resume(&self) -> bool {
+        self.end(None)
+    }
+
+    /// The time left until real-time protection resumes, None when it is not snoozed
+    pub fn remaining(&self) -> Option<Duration> {
+        self.snoozed_until
+            .lock()
+            .unwrap()
+            .map(|(until, _)| until.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether real-time protection is snoozed, in which case file operations are let through without being scanned
+    #[allow(dead_code)] // for when the minifilter sends file operations to the engine to scan
+    pub fn is_snoozed(&self) -> bool {
+        self.snoozed_until.lock().unwrap().is_some()
+    }
+
+    /// Ends the snooze, where it is the snooze of the generation given. Without a generation, any snooze is ended.
+    fn end(&self, generation: Option<u64>) -> bool {
+        let ended = {
+            let mut lock = self.snoozed_until.lock().unwrap();
+            match *lock {
+                Some((_, current)) if generation.is_none_or(|g| g == current) => lock.take().is_some(),
+                _ => false,
+            }
+        };
+
+        if ended {
+            let early = generation.is_none();
+            Log::new().log(
+                LogLevel::Info,
+                match early {
+                    true => "Real-time protection resumed early.",
+                    false => "Real-time protection resumed, as its snooze ran out.",
+                },
+            );
+            self.events.publish(EngineEvent::RealtimeResumed { early });
+        }
+
+        ended
+    }
+}
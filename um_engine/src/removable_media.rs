@@ -0,0 +1,93 @@
+//! Watching for removable media, such as USB sticks, so that each volume can be scanned as soon as it is inserted.
+//!
+//! The drives are polled rather than waiting on `WM_DEVICECHANGE`, as the engine runs without a window to receive
+//! device notifications on. A volume is identified by its drive letter and serial number, so a stick swapped for
+//! another between polls is still seen as an arrival. Volumes already present when the engine starts are not
+//! treated as arrivals.
+
+use std::{
+    collections::HashSet,
+    os::windows::ffi::OsStrExt,
+    path::PathBuf,
+    time::Duration,
+};
+
+use windows::{
+    Win32::{
+        Storage::FileSystem::{GetDriveTypeW, GetLogicalDrives, GetVolumeInformationW},
+        System::WindowsProgramming::DRIVE_REMOVABLE,
+    },
+    core::PCWSTR,
+};
+
+/// How often the drives are checked for newly inserted removable media
+pub const REMOVABLE_MEDIA_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A mounted removable volume
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemovableVolume {
+    /// The drive letter, such as `E:`
+    pub drive: String,
+    /// The serial number of the volume as Windows shows it, such as `1A2B-3C4D`
+    pub serial: String,
+}
+
+impl RemovableVolume {
+    /// Whether the volume is still mounted, which is false once the media has been removed
+    pub fn is_present(&self) -> bool {
+        removable_volumes().contains(self)
+    }
+}
+
+/// Tracks which removable volumes are mounted, to tell when one is inserted
+pub struct RemovableMediaWatcher {
+    present: HashSet<RemovableVolume>,
+}
+
+impl RemovableMediaWatcher {
+    /// Creates the watcher, taking note of the removable volumes which are already mounted so they are not reported
+    /// as arrivals
+    pub fn new() -> Self {
+        RemovableMediaWatcher {
+            present: removable_volumes(),
+        }
+    }
+
+    /// Checks the drives, returning the removable volumes which have been mounted since the last poll
+    pub fn poll(&mut self) -> Vec<RemovableVolume> {
+        let current = removable_volumes();
+        let mut arrived: Vec<RemovableVolume> = current.difference(&self.present).cloned().collect();
+        arrived.sort_by(|a, b| a.drive.cmp(&b.drive));
+
+        self.present = current;
+
+        arrived
+    }
+}
+
+/// Gets the removable volumes which are mounted with a drive letter. A drive with no media in it, such as an empty
+/// card reader, has no serial number so is left out.
+fn removable_volumes() -> HashSet<RemovableVolume> {
+    let drives = unsafe { GetLogicalDrives() };
+
+    (0..26u8)
+        .filter(|i| drives & (1 << i) != 0)
+        .filter_map(|i| {
+            let root = PathBuf::from(format!("{}:\\", (b'A' + i) as char));
+            let wide_root: Vec<u16> = root.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+            let root_ptr = PCWSTR::from_raw(wide_root.as_ptr());
+
+            if unsafe { GetDriveTypeW(root_ptr) } != DRIVE_REMOVABLE {
+                return None;
+            }
+
+            let mut serial: u32 = 0;
+            unsafe { GetVolumeInformationW(root_ptr, None, Some(&mut serial), None, None, None) }.ok()?;
+
+            Some(RemovableVolume {
+                drive: format!("{}:", (b'A' + i) as char),
+                serial: format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,104 @@
+//! The engine's own use of the machine's resources, so that users can see it is not hogging them and support can spot
+//! a leak, such as a handle count which keeps growing.
+//!
+//! CPU is sampled over [`CPU_SAMPLE_WINDOW`] from the time the process has spent running, rather than taken from a
+//! single instant, so that the figure reflects what the engine is doing rather than whichever thread happened to be
+//! running when asked.
+
+use std::{
+    mem, thread,
+    time::{Duration, Instant},
+};
+
+use shared_std::diagnostics::ResourceUsage;
+use windows::Win32::{
+    Foundation::{CloseHandle, FILETIME},
+    System::{
+        Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW, TH32CS_SNAPPROCESS,
+        },
+        ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+        Threading::{GetCurrentProcess, GetCurrentProcessId, GetProcessHandleCount, GetProcessTimes},
+    },
+};
+
+use crate::filescanner::FileScanner;
+
+/// How long CPU use is sampled over
+pub const CPU_SAMPLE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Measures the engine's use of the machine's resources, sampling its CPU use over [`CPU_SAMPLE_WINDOW`].
+///
+/// # Returns
+///
+/// The engine's resource usage, or an error where the engine could not query its own process.
+pub async fn resource_usage(file_scanner: &FileScanner) -> Result<ResourceUsage, String> {
+    let started_cpu = cpu_time()?;
+    let started = Instant::now();
+    tokio::time::sleep(CPU_SAMPLE_WINDOW).await;
+    let cpu = cpu_time()?.saturating_sub(started_cpu);
+
+    // as a share of the whole machine, so a single busy thread on an eight core machine is 12.5%
+    let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as f64;
+    let cpu_percent = (cpu.as_secs_f64() / started.elapsed().as_secs_f64() / cores * 100.0).min(100.0);
+
+    let process = unsafe { GetCurrentProcess() };
+
+    let mut memory = PROCESS_MEMORY_COUNTERS {
+        cb: mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetProcessMemoryInfo(process, &mut memory, memory.cb) }
+        .map_err(|e| format!("Could not get the engine's memory use. {e}"))?;
+
+    let mut handle_count = 0;
+    unsafe { GetProcessHandleCount(process, &mut handle_count) }
+        .map_err(|e| format!("Could not get the engine's handle count. {e}"))?;
+
+    Ok(ResourceUsage {
+        cpu_percent,
+        working_set_bytes: memory.WorkingSetSize as u64,
+        handle_count,
+        thread_count: thread_count()?,
+        open_scans: file_scanner.scans_list().len(),
+    })
+}
+
+/// The time the engine has spent running, in kernel and user mode across all of its threads
+fn cpu_time() -> Result<Duration, String> {
+    let (mut creation, mut exit, mut kernel, mut user) =
+        (FILETIME::default(), FILETIME::default(), FILETIME::default(), FILETIME::default());
+    unsafe { GetProcessTimes(GetCurrentProcess(), &mut creation, &mut exit, &mut kernel, &mut user) }
+        .map_err(|e| format!("Could not get the engine's CPU time. {e}"))?;
+
+    // each is a count of 100 nanosecond intervals
+    let ticks = |time: FILETIME| ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64;
+
+    Ok(Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+}
+
+/// The number of threads the engine is running, from a snapshot of the processes on the machine
+fn thread_count() -> Result<u32, String> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }
+        .map_err(|e| format!("Could not list the processes to count the engine's threads. {e}"))?;
+    let pid = unsafe { GetCurrentProcessId() };
+
+    let mut entry = PROCESSENTRY32W {
+        dwSize: mem::size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+    let mut count = None;
+
+    let mut found = unsafe { Process32FirstW(snapshot, &mut entry) }.is_ok();
+    while found {
+        if entry.th32ProcessID == pid {
+            count = Some(entry.cntThreads);
+            break;
+        }
+        found = unsafe { Process32NextW(snapshot, &mut entry) }.is_ok();
+    }
+
+    let _ = unsafe { CloseHandle(snapshot) };
+
+    count.ok_or_else(|| "The engine's process was not in the list of processes.".to_string())
+}
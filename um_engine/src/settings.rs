@@ -1,8 +1,31 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
 
-use shared_std::settings::SanctumSettings;
+use serde_json::Value;
+use shared_std::{
+    file_scanner::EngineFlags,
+    settings::{
+        ConfigReloadReport, EffectiveConfig, EffectiveSetting, ExclusionList, FuzzyHashSettings, HeuristicSettings,
+        InjectionSettings, MaintenanceSettings, MemoryPressureSettings, NotificationSettings, OnDemandSettings,
+        RealtimePolicy, RemoteManagementSettings, RemoteVolumeSettings, RemovableMediaSettings, SanctumSettings,
+        ScanJournalSettings, ScanWatchdogSettings, SettingSource, SiemSettings, VerdictSettings, WebhookSettings,
+    },
+};
 
-use crate::utils::{env::get_logged_in_username, log::Log};
+use crate::{
+    filescanner::FileScanner,
+    gui_communication::transport::validate_remote_settings,
+    siem::validate_siem_settings,
+    utils::{env::get_logged_in_username, log::Log},
+    webhook::validate_webhook_settings,
+};
+
+/// The settings which are only read as the engine starts, by the start of their key, so a change to them takes effect
+/// once the engine is restarted
+const RESTART_REQUIRED_SETTINGS: [&str; 1] = ["remote_management"];
 
 pub trait SanctumSettingsImpl {
     fn load() -> Self;
@@ -20,14 +43,7 @@ impl SanctumSettingsImpl for SanctumSettings {
 
         // if the path doesn't exist, the app is likely running for the first time, so configure any app defaults
         let settings = if !dir.exists() {
-            let settings = SanctumSettings {
-                common_scan_areas: vec![
-                    PathBuf::from(format!("C:\\Users\\{}", username)),
-                    PathBuf::from("C:\\ProgramData"),
-                    PathBuf::from("C:\\Temp"),
-                    PathBuf::from("C:\\temp"),
-                ],
-            };
+            let settings = default_settings(&username);
 
             let settings_string = serde_json::to_string(&settings).unwrap();
             fs::create_dir_all(&dir).expect("[-] Unable to create directory file.");
@@ -54,6 +70,25 @@ impl SanctumSettingsImpl for SanctumSettings {
     fn update_settings(&mut self, settings: SanctumSettings) -> Self {
         // update self fields in memory
         self.common_scan_areas = settings.clone().common_scan_areas;
+        self.exclusions = settings.clone().exclusions;
+        self.fuzzy_hashing = settings.clone().fuzzy_hashing;
+        self.detection_engines = settings.clone().detection_engines;
+        self.maintenance = settings.clone().maintenance;
+        self.heuristics = settings.clone().heuristics;
+        self.removable_media = settings.clone().removable_media;
+        self.siem = settings.clone().siem;
+        self.realtime = settings.realtime;
+        self.disabled_signature_databases = settings.clone().disabled_signature_databases;
+        self.scan_watchdog = settings.scan_watchdog;
+        self.remote_management = settings.clone().remote_management;
+        self.scan_journal = settings.scan_journal;
+        self.notifications = settings.notifications;
+        self.on_demand = settings.on_demand;
+        self.remote_volumes = settings.remote_volumes;
+        self.verdicts = settings.clone().verdicts;
+        self.memory_pressure = settings.memory_pressure;
+        self.injection = settings.clone().injection;
+        self.webhook = settings.clone().webhook;
 
         // write new file to disk
         let settings_str = serde_json::to_string(&settings).unwrap();
@@ -64,6 +99,168 @@ impl SanctumSettingsImpl for SanctumSettings {
     }
 }
 
+/// The settings the app is configured with when it is run for the first time
+pub(crate) fn default_settings(username: &str) -> SanctumSettings {
+    SanctumSettings {
+        common_scan_areas: vec![
+            PathBuf::from(format!("C:\\Users\\{}", username)),
+            PathBuf::from("C:\\ProgramData"),
+            PathBuf::from("C:\\Temp"),
+            PathBuf::from("C:\\temp"),
+        ],
+        exclusions: ExclusionList::default(),
+        fuzzy_hashing: FuzzyHashSettings::default(),
+        detection_engines: EngineFlags::default(),
+        maintenance: MaintenanceSettings::default(),
+        heuristics: HeuristicSettings::default(),
+        removable_media: RemovableMediaSettings::default(),
+        siem: SiemSettings::default(),
+        realtime: RealtimePolicy::default(),
+        disabled_signature_databases: Vec::new(),
+        scan_watchdog: ScanWatchdogSettings::default(),
+        remote_management: RemoteManagementSettings::default(),
+        scan_journal: ScanJournalSettings::default(),
+        notifications: NotificationSettings::default(),
+        on_demand: OnDemandSettings::default(),
+        remote_volumes: RemoteVolumeSettings::default(),
+        verdicts: VerdictSettings::default(),
+        memory_pressure: MemoryPressureSettings::default(),
+        injection: InjectionSettings::default(),
+        webhook: WebhookSettings::default(),
+    }
+}
+
+/// Annotates each of the settings in use with whether it was read from the settings file or took its default, and
+/// validates them as a change to the settings would be.
+///
+/// The settings file is read as it is now, so a file edited by hand since the engine started may not match the
+/// settings in use until it is reloaded with [`reload_settings`], or the engine is restarted.
+pub fn effective_config(settings: &SanctumSettings) -> Result<EffectiveConfig, String> {
+    let username = get_logged_in_username()?;
+    let path = get_setting_paths(&username).1;
+
+    let file = fs::read_to_string(&path)
+        .map_err(|e| format!("Could not read the settings file {}. {e}", path.display()))?;
+    let stored: Value = serde_json::from_str(&file)
+        .map_err(|e| format!("Could not parse the settings file {}. {e}", path.display()))?;
+
+    let effective = serde_json::to_value(settings).unwrap();
+    let defaults = serde_json::to_value(default_settings(&username)).unwrap();
+
+    let mut values = Vec::new();
+    flatten_settings("", &effective, &mut values);
+    let settings_list = values
+        .into_iter()
+        .map(|(key, value)| EffectiveSetting {
+            default: lookup_setting(&defaults, &key).cloned().unwrap_or(Value::Null),
+            source: match lookup_setting(&stored, &key) {
+                Some(_) => SettingSource::SettingsFile,
+                None => SettingSource::Default,
+            },
+            key,
+            value,
+        })
+        .collect();
+
+    Ok(EffectiveConfig {
+        settings_file: path,
+        settings: settings_list,
+        unknown: unknown_settings(&stored, &effective),
+        validation_error: validate_settings(settings).err(),
+    })
+}
+
+/// Reads the settings file again after it was changed by something other than the engine, validating it in full as a
+/// change to the settings would be, and compares it against the settings in use. Nothing is applied, so the caller
+/// replaces the settings in use with those returned only once this succeeds.
+///
+/// # Returns
+///
+/// The settings read from the file and which of them changed, or an error where the file could not be read or does
+/// not hold valid settings, in which case the settings in use are to be kept.
+pub fn reload_settings(running: &SanctumSettings) -> Result<(SanctumSettings, ConfigReloadReport), String> {
+    let path = get_setting_paths(&get_logged_in_username()?).1;
+
+    let file = fs::read_to_string(&path)
+        .map_err(|e| format!("Could not read the settings file {}. {e}", path.display()))?;
+    let stored: Value = serde_json::from_str(&file)
+        .map_err(|e| format!("Could not parse the settings file {}. {e}", path.display()))?;
+    let settings: SanctumSettings = serde_json::from_value(stored.clone())
+        .map_err(|e| format!("The settings file {} does not hold valid settings. {e}", path.display()))?;
+    validate_settings(&settings)
+        .map_err(|e| format!("The settings in {} are not valid, so were not loaded. {e}", path.display()))?;
+
+    let flattened = |settings: &SanctumSettings| {
+        let mut values = Vec::new();
+        flatten_settings("", &serde_json::to_value(settings).unwrap(), &mut values);
+        values.into_iter().collect::<BTreeMap<String, Value>>()
+    };
+    let before = flattened(running);
+    let after = flattened(&settings);
+
+    // a setting may be in only one of the two, such as a list which was empty and so is flattened as its own key
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let (restart_required, applied) = keys
+        .into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .partition(|key| {
+            RESTART_REQUIRED_SETTINGS
+                .iter()
+                .any(|prefix| key.as_str() == *prefix || key.starts_with(&format!("{prefix}.")))
+        });
+
+    let report = ConfigReloadReport {
+        unknown: unknown_settings(&stored, &serde_json::to_value(&settings).unwrap()),
+        settings_file: path,
+        applied,
+        restart_required,
+    };
+
+    Ok((settings, report))
+}
+
+/// Validates the settings as a change to the settings is validated, without applying them
+fn validate_settings(settings: &SanctumSettings) -> Result<(), String> {
+    validate_siem_settings(&settings.siem)
+        .and_then(|_| validate_remote_settings(&settings.remote_management))
+        .and_then(|_| validate_webhook_settings(&settings.webhook))
+        .and_then(|_| FileScanner::check_settings(settings))
+}
+
+/// The settings in the settings file which the engine does not know, being those not in the settings it read from it
+fn unknown_settings(stored: &Value, effective: &Value) -> Vec<String> {
+    let mut stored_values = Vec::new();
+    flatten_settings("", stored, &mut stored_values);
+
+    stored_values
+        .into_iter()
+        .filter(|(key, _)| lookup_setting(effective, key).is_none())
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Flattens the settings into each of their values, keyed by the path to the value. Lists are taken as a single value,
+/// as they are replaced whole rather than merged with their default.
+fn flatten_settings(prefix: &str, value: &Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (name, field) in fields {
+                let key = match prefix {
+                    "" => name.clone(),
+                    _ => format!("{prefix}.{name}"),
+                };
+                flatten_settings(&key, field, out);
+            }
+        }
+        _ => out.push((prefix.to_string(), value.clone())),
+    }
+}
+
+fn lookup_setting<'a>(settings: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').try_fold(settings, |value, name| value.get(name))
+}
+
 /// Get the base path and file name of the settings file, from the AppData folder.
 pub fn get_setting_paths(username: &String) -> (PathBuf, PathBuf) {
     let base_path = format!("C:\\Users\\{username}\\AppData\\Roaming\\Sanctum\\");
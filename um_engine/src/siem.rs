@@ -0,0 +1,284 @@
+//! Formatting detections for a SIEM, as CEF or LEEF, and forwarding them to a syslog collector.
+//!
+//! Detections are taken from the event bus and queued by the [`forwarder`], so forwarding never holds up a scan.
+
+use std::{fmt, io, sync::Arc, time::SystemTime};
+
+use shared_std::{
+    file_scanner::{ActionResult, MatchMode, MatchedIOC},
+    settings::{SanctumSettings, SiemFormat, SiemSettings, SyslogTransport},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpStream, UdpSocket},
+    sync::Mutex,
+};
+
+use crate::{
+    events::EventBus,
+    forwarder::{self, Destination},
+};
+
+/// The syslog facility detections are sent with, local4
+const SYSLOG_FACILITY: u8 = 20;
+
+/// Checks the SIEM settings, so that a bad collector address is refused when the settings are changed rather than
+/// failing each time a detection is sent.
+///
+/// # Returns
+///
+/// An error describing the first invalid setting.
+pub fn validate_siem_settings(settings: &SiemSettings) -> Result<(), String> {
+    let Some(collector) = &settings.collector else {
+        return match settings.enabled {
+            true => Err("A SIEM collector must be given to forward detections to.".to_string()),
+            false => Ok(()),
+        };
+    };
+
+    match collector.trim().rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p != 0) => Ok(()),
+        _ => Err(format!("SIEM collector '{collector}' must be given as host:port.")),
+    }
+}
+
+/// Formats the detection as a single CEF or LEEF message.
+///
+/// # Args
+/// - `time`: When the detection was made, or None where it is not known
+/// - `host`: The name of the machine the detection was made on
+pub fn format_detection(
+    format: SiemFormat,
+    ioc: &MatchedIOC,
+    scan_id: Option<u64>,
+    time: Option<SystemTime>,
+    host: &str,
+) -> String {
+    let time_ms = time
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis().to_string());
+    let file_name = ioc
+        .file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let fields = [
+        ("rt", time_ms),
+        ("dvchost", Some(host.to_string())),
+        ("filePath", Some(ioc.file.to_string_lossy().into_owned())),
+        ("fname", Some(file_name)),
+        ("fileHash", Some(ioc.hash.clone())),
+        ("act", Some(describe_action(&ioc.action_taken).to_string())),
+        ("cs1Label", scan_id.map(|_| "scanId".to_string())),
+        ("cs1", scan_id.map(|id| id.to_string())),
+        ("cs2Label", ioc.source.as_ref().map(|_| "signatureDatabase".to_string())),
+        ("cs2", ioc.source.clone()),
+    ];
+    let fields = fields.into_iter().filter_map(|(key, value)| value.map(|v| (key, v)));
+
+    match format {
+        SiemFormat::Cef => {
+            let extensions: Vec<String> =
+                fields.map(|(key, value)| format!("{key}={}", escape_cef_value(&value))).collect();
+
+            format!(
+                "CEF:0|Sanctum|EDR|{}|{}|{}|{}|{}",
+                escape_cef_header(env!("CARGO_PKG_VERSION")),
+                signature_id(&ioc.match_mode),
+                escape_cef_header(&describe_match(&ioc.match_mode)),
+                severity(&ioc.match_mode),
+                extensions.join(" ")
+            )
+        }
+        SiemFormat::Leef => {
+            let mut attributes = vec![
+                format!("sev={}", severity(&ioc.match_mode)),
+                format!("name={}", escape_leef_value(&describe_match(&ioc.match_mode))),
+            ];
+            attributes.extend(fields.map(|(key, value)| format!("{key}={}", escape_leef_value(&value))));
+
+            format!(
+                "LEEF:1.0|Sanctum|EDR|{}|{}|{}",
+                escape_leef_header(env!("CARGO_PKG_VERSION")),
+                signature_id(&ioc.match_mode),
+                attributes.join("\t")
+            )
+        }
+    }
+}
+
+/// Forwards every detection published to the event bus to the SIEM collector in the settings, for as long as the
+/// engine runs. Changes to the settings apply from the next detection.
+pub async fn forward_detections(events: Arc<EventBus>, settings: Arc<Mutex<SanctumSettings>>) {
+    let host = std::env::var("COMPUTERNAME").unwrap_or_else(|_| "-".to_string());
+
+    forwarder::forward_detections(Siem { host }, events, settings).await;
+}
+
+/// The SIEM collector in the settings, to which detections are sent as syslog messages
+struct Siem {
+    /// The name of the machine, which the messages are sent from
+    host: String,
+}
+
+/// Where a SIEM collector listens for syslog messages
+struct Collector {
+    address: String,
+    transport: SyslogTransport,
+}
+
+impl fmt::Display for Collector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.address)
+    }
+}
+
+impl Destination for Siem {
+    /// The detection as CEF or LEEF, held with its CEF severity for the priority of its syslog message
+    type Message = (u8, String);
+    type Target = Collector;
+
+    const NAME: &'static str = "SIEM collector";
+
+    fn target(settings: &SanctumSettings) -> Option<Collector> {
+        match (&settings.siem.collector, settings.siem.enabled) {
+            (Some(collector), true) => Some(Collector {
+                address: collector.trim().to_string(),
+                transport: settings.siem.transport,
+            }),
+            _ => None,
+        }
+    }
+
+    fn message(&self, settings: &SanctumSettings, scan_id: Option<u64>, ioc: &MatchedIOC) -> (u8, String) {
+        let message = format_detection(settings.siem.format, ioc, scan_id, Some(SystemTime::now()), &self.host);
+        (severity(&ioc.match_mode), message)
+    }
+
+    async fn send(&self, collector: &Collector, (severity, message): &(u8, String)) -> Result<(), String> {
+        let frame = syslog_frame(*severity, message, &self.host, collector.transport);
+        send(collector.transport, &collector.address, &frame).await.map_err(|e| e.to_string())
+    }
+}
+
+/// Wraps the message in an RFC 5424 syslog header. The timestamp is left for the collector to fill in, as the time of
+/// the detection is carried in the message itself.
+fn syslog_frame(cef_severity: u8, message: &str, host: &str, transport: SyslogTransport) -> Vec<u8> {
+    let priority = SYSLOG_FACILITY * 8 + syslog_severity(cef_severity);
+
+    let mut frame = format!("<{priority}>1 - {host} Sanctum - - - {message}").into_bytes();
+    if transport == SyslogTransport::Tcp {
+        frame.push(b'\n');
+    }
+
+    frame
+}
+
+async fn send(transport: SyslogTransport, collector: &str, frame: &[u8]) -> io::Result<()> {
+    match transport {
+        SyslogTransport::Udp => {
+            let socket = UdpSocket::bind("0.0.0.0:0").await?;
+            socket.send_to(frame, collector).await?;
+        }
+        SyslogTransport::Tcp => {
+            let mut stream = TcpStream::connect(collector).await?;
+            stream.write_all(frame).await?;
+            stream.shutdown().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The CEF severity (0 - 10) of the detection. Sanctum has no severity of its own, so this follows how certain the
+/// match is: an exact match of known malware is the most severe, and the heuristics, which flag some legitimate
+/// software, the least. A simulated detection takes the severity it was simulated with.
+pub(crate) fn severity(mode: &MatchMode) -> u8 {
+    match mode {
+        MatchMode::FileHash => 9,
+        MatchMode::SectionHash { .. } => 8,
+        // imports are shared by unrelated software more often than code is, such as by small tools built alike
+        MatchMode::ImportHash => 7,
+        MatchMode::FuzzyHash { similarity } if *similarity >= 90 => 7,
+        MatchMode::FuzzyHash { .. } => 5,
+        MatchMode::Heuristic { .. } => 3,
+        MatchMode::Custom { .. } => 6,
+        MatchMode::Macro { .. } => 6,
+        // a shortcut to detected malware is as severe as the malware, else it only looks like a payload
+        MatchMode::Shortcut { target_detection: Some(ioc), .. } => severity(&ioc.match_mode),
+        MatchMode::Shortcut { .. } => 4,
+        MatchMode::Simulated { severity, .. } => *severity,
+    }
+}
+
+/// Maps a CEF severity onto the syslog severity of the message
+fn syslog_severity(cef_severity: u8) -> u8 {
+    match cef_severity {
+        9.. => 2,    // critical
+        7..=8 => 3,  // error
+        4..=6 => 4,  // warning
+        _ => 5,      // notice
+    }
+}
+
+fn signature_id(mode: &MatchMode) -> &'static str {
+    match mode {
+        MatchMode::FileHash => "FileHash",
+        MatchMode::SectionHash { .. } => "SectionHash",
+        MatchMode::ImportHash => "ImportHash",
+        MatchMode::FuzzyHash { .. } => "FuzzyHash",
+        MatchMode::Heuristic { .. } => "Heuristic",
+        MatchMode::Custom { .. } => "Custom",
+        MatchMode::Macro { .. } => "Macro",
+        MatchMode::Shortcut { .. } => "Shortcut",
+        MatchMode::Simulated { .. } => "Simulated",
+    }
+}
+
+pub(crate) fn describe_match(mode: &MatchMode) -> String {
+    match mode {
+        MatchMode::FileHash => "Known malware".to_string(),
+        MatchMode::SectionHash { section } => format!("Known malware in section {section}"),
+        MatchMode::ImportHash => "Imports of known malware".to_string(),
+        MatchMode::FuzzyHash { similarity } => format!("Similar to known malware ({similarity}%)"),
+        MatchMode::Heuristic { score, .. } => format!("Suspicious file (heuristic score {score})"),
+        MatchMode::Custom { reason } => reason.clone(),
+        MatchMode::Macro { module, .. } => format!("Suspicious auto-running macro in module {module}"),
+        MatchMode::Shortcut { target_detection: Some(ioc), .. } => {
+            format!("Shortcut to {}: {}", ioc.file.display(), describe_match(&ioc.match_mode))
+        }
+        MatchMode::Shortcut { .. } => "Shortcut with suspicious arguments".to_string(),
+        MatchMode::Simulated { name, .. } => format!("Simulated detection {name}"),
+    }
+}
+
+fn describe_action(action: &ActionResult) -> &'static str {
+    match action {
+        ActionResult::None => "reported",
+        ActionResult::Quarantined { .. } => "quarantined",
+        ActionResult::DeletePending => "delete pending",
+        ActionResult::Deleted => "deleted",
+        ActionResult::ActionFailed(_) => "action failed",
+    }
+}
+
+fn escape_cef_header(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn escape_cef_value(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+fn escape_leef_header(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+/// LEEF attributes are separated by tabs, so tabs and line breaks in a value are replaced
+fn escape_leef_value(s: &str) -> String {
+    s.replace(['\t', '\r', '\n'], " ")
+}
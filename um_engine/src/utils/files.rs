@@ -0,0 +1,11 @@
+use std::{fs, io, path::Path};
+
+/// Writes the file by writing to a temporary file alongside it and renaming it into place, so that a crash part way
+/// through never leaves a truncated file behind.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
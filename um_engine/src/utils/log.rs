@@ -1,21 +1,29 @@
 use std::{
     fs::{self, OpenOptions},
-    io::Write,
+    io::{self, Write},
     path::PathBuf,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use shared_no_std::constants::LOG_PATH;
+use shared_std::diagnostics::LogRotation;
 
 use crate::settings::get_setting_paths;
 
 use super::env::get_logged_in_username;
 
+/// Held whilst a message is written to the log, and whilst the log is rotated, so that a message is never split across
+/// a rotation nor written to a file as it is being renamed
+static LOG_WRITE: Mutex<()> = Mutex::new(());
+
 #[derive(Debug)]
 pub struct Log {
     log_file_path: PathBuf,
 }
 
 pub enum LogLevel {
+    Debug,
     Info,
     Warning,
     Success,
@@ -52,6 +60,8 @@ impl Log {
     /// This function does not return and will panic.
     #[track_caller]
     pub fn panic(&self, msg: &str) -> ! {
+        let write = lock_log();
+
         // open the file
         let mut file = OpenOptions::new()
             .create(true)
@@ -67,12 +77,15 @@ impl Log {
 
         // write to the file
         writeln!(file, "{}", msg).expect("Unable to write to log file");
+        drop(write);
 
         panic!("[fatal] {}", msg);
     }
 
     /// Log messages to the log file defined in the applications constant strings.
     pub fn log(&self, level: LogLevel, msg: &str) {
+        let write = lock_log();
+
         // open the file
         let mut file = OpenOptions::new()
             .create(true)
@@ -88,9 +101,11 @@ impl Log {
 
         // write to the file
         writeln!(file, "{}", msg).expect("Unable to write to log file");
+        drop(write);
 
         // console log the message
         match level {
+            LogLevel::Debug => println!("[d] {}", msg),
             LogLevel::Info => println!("[i] {}", msg),
             LogLevel::Warning => println!("[w] {}", msg),
             LogLevel::Success => println!("[+] {}", msg),
@@ -107,3 +122,53 @@ pub fn get_log_file_path() -> PathBuf {
     sanctum_app_data_path.push(LOG_PATH);
     sanctum_app_data_path
 }
+
+/// Rotates the log, closing the current file and starting a fresh one, so that a copy of the log for a support case
+/// holds everything logged up to the moment it was rotated. The current file is renamed alongside it after the time it
+/// was rotated, such as `sanctum-1760436900.log`, and the fresh file takes its name.
+///
+/// Every message is written straight through to the file, so there is nothing buffered to flush first. Messages logged
+/// whilst the log is rotated wait for it, then go to the fresh file.
+///
+/// # Returns
+///
+/// The path of the file which was closed, and of the fresh file, or an error if the log could not be renamed, in which
+/// case logging carries on to the current file.
+pub fn rotate_log() -> io::Result<LogRotation> {
+    let current = get_log_file_path();
+    let _write = lock_log();
+
+    let rotated_at = SystemTime::now();
+    let secs = rotated_at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let stem = current.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+    // a second rotation within the same second is given a suffix, rather than replacing the first
+    let rotated = (0..)
+        .map(|n| match n {
+            0 => current.with_file_name(format!("{stem}-{secs}.log")),
+            n => current.with_file_name(format!("{stem}-{secs}-{n}.log")),
+        })
+        .find(|path| !path.exists())
+        .expect("an unused name is always found");
+
+    // the closed file ends by saying where the log carries on, so it is clear nothing was cut off
+    let mut file = OpenOptions::new().create(true).append(true).open(&current)?;
+    writeln!(file, "Log rotated at {secs}, continued in {}", current.display())?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&current, &rotated)?;
+    fs::write(&current, "")?;
+
+    Ok(LogRotation {
+        rotated,
+        current,
+        rotated_at,
+    })
+}
+
+/// Takes the lock on writing the log. A panic whilst it was held leaves nothing half done, as each write is a single
+/// line, so a poisoned lock is taken regardless.
+fn lock_log() -> MutexGuard<'static, ()> {
+    LOG_WRITE.lock().unwrap_or_else(PoisonError::into_inner)
+}
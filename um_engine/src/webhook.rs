@@ -0,0 +1,184 @@
+//! Posting each detection to a webhook as JSON the moment it is made, for automation which would otherwise have to
+//! poll the engine.
+//!
+//! Each request is signed with HMAC-SHA256 over the timestamp and body, keyed by the secret in the settings, so the
+//! receiver can check the detection came from this engine and is not being replayed. The signature is sent as
+//! `X-Sanctum-Signature: sha256=<hex>`, over `<timestamp>.<body>` where the timestamp is the value of the
+//! `X-Sanctum-Timestamp` header.
+//!
+//! As with SIEM forwarding, detections are taken from the event bus and queued by the [`forwarder`], so a slow or
+//! unreachable endpoint never holds up a scan.
+
+use std::{fmt, path::PathBuf, sync::Arc, time::SystemTime};
+
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+use shared_std::{
+    file_scanner::MatchedIOC,
+    settings::{SanctumSettings, WebhookSettings},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    events::EventBus,
+    forwarder::{self, Destination, SEND_TIMEOUT},
+    siem::{describe_match, severity},
+    utils::log::{Log, LogLevel},
+};
+
+/// The body posted to the webhook for a detection
+#[derive(Serialize)]
+struct WebhookPayload {
+    path: PathBuf,
+    hash: String,
+    /// What matched the file, such as `Known malware` or `Suspicious file (heuristic score 80)`
+    rule: String,
+    /// The signature database or registered detector which matched, or None for a heuristic detection
+    source: Option<String>,
+    /// The severity of the detection from 0 to 10, as sent to a SIEM
+    severity: u8,
+    host: String,
+    /// When the detection was made, in milliseconds since the Unix epoch
+    timestamp: u128,
+    scan_id: Option<u64>,
+}
+
+/// Checks the webhook settings, so that a bad URL or a missing secret is refused when the settings are changed rather
+/// than failing each time a detection is posted.
+///
+/// # Returns
+///
+/// An error describing the first invalid setting.
+pub fn validate_webhook_settings(settings: &WebhookSettings) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let Some(url) = &settings.url else {
+        return Err("A webhook URL must be given to post detections to.".to_string());
+    };
+    match reqwest::Url::parse(url.trim()) {
+        Ok(url) if matches!(url.scheme(), "https" | "http") => (),
+        _ => return Err(format!("Webhook URL '{url}' must be an http:// or https:// URL.")),
+    }
+
+    match &settings.secret {
+        Some(secret) if !secret.is_empty() => Ok(()),
+        _ => Err("A webhook secret must be given to sign detections with.".to_string()),
+    }
+}
+
+/// Posts every detection published to the event bus to the webhook in the settings, for as long as the engine runs.
+/// Changes to the settings apply from the next detection.
+pub async fn post_detections(events: Arc<EventBus>, settings: Arc<Mutex<SanctumSettings>>) {
+    let host = std::env::var("COMPUTERNAME").unwrap_or_else(|_| "-".to_string());
+    let client = match Client::builder().timeout(SEND_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            Log::new().log(
+                LogLevel::Error,
+                &format!("Could not create the webhook client, detections will not be posted. {e}"),
+            );
+            return;
+        }
+    };
+
+    forwarder::forward_detections(Webhook { client, host }, events, settings).await;
+}
+
+/// The webhook in the settings, to which detections are posted as JSON
+struct Webhook {
+    client: Client,
+    /// The name of the machine, which the detections are posted from
+    host: String,
+}
+
+/// Where a webhook is posted, and the secret the posts are signed with
+struct Endpoint {
+    url: String,
+    secret: String,
+}
+
+/// Shows only the URL, so that the secret is never logged
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+impl Destination for Webhook {
+    /// The JSON body posted for the detection
+    type Message = Vec<u8>;
+    type Target = Endpoint;
+
+    const NAME: &'static str = "webhook";
+
+    fn target(settings: &SanctumSettings) -> Option<Endpoint> {
+        match (&settings.webhook.url, &settings.webhook.secret, settings.webhook.enabled) {
+            (Some(url), Some(secret), true) => Some(Endpoint {
+                url: url.trim().to_string(),
+                secret: secret.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn message(&self, _settings: &SanctumSettings, scan_id: Option<u64>, ioc: &MatchedIOC) -> Vec<u8> {
+        payload(ioc, scan_id, &self.host)
+    }
+
+    async fn send(&self, endpoint: &Endpoint, body: &Vec<u8>) -> Result<(), String> {
+        send(&self.client, &endpoint.url, &endpoint.secret, body).await
+    }
+}
+
+fn payload(ioc: &MatchedIOC, scan_id: Option<u64>, host: &str) -> Vec<u8> {
+    let payload = WebhookPayload {
+        path: ioc.file.clone(),
+        hash: ioc.hash.clone(),
+        rule: describe_match(&ioc.match_mode),
+        source: ioc.source.clone(),
+        severity: severity(&ioc.match_mode),
+        host: host.to_string(),
+        timestamp: unix_millis(SystemTime::now()),
+        scan_id,
+    };
+
+    serde_json::to_vec(&payload).unwrap()
+}
+
+/// Posts the body, signed as of now, so that each attempt carries a fresh timestamp
+async fn send(client: &Client, url: &str, secret: &str, body: &[u8]) -> Result<(), String> {
+    let timestamp = unix_millis(SystemTime::now()).to_string();
+
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Sanctum-Timestamp", &timestamp)
+        .header("X-Sanctum-Signature", format!("sha256={}", sign(secret, &timestamp, body)))
+        .body(body.to_vec())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("The webhook responded {status}.")),
+    }
+}
+
+/// Signs the timestamp and body with the secret, as lowercase hex
+fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes a key of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac.finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or_default()
+}